@@ -0,0 +1,12 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate rastermind;
+
+use rastermind::wavefront::{LoadOptions, Object};
+
+
+// any bytes must either parse into an object or fail with an error, in both the lenient and the strict parser
+fuzz_target!(|data: &[u8]| {
+    let _ = Object::from_bytes(data, &LoadOptions::default());
+    let _ = Object::from_bytes(data, &LoadOptions { strict: true, ..Default::default() });
+});
@@ -0,0 +1,480 @@
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+use nalgebra::{Matrix4, Vector3, Vector4};
+
+use post;
+use render;
+use render::DepthOptions;
+use shader::Shader;
+use wavefront;
+
+
+/// A fragment of a surface recorded in an A-buffer
+///
+/// The depth is the value the fragment would store in a depth buffer, where
+/// larger values are nearer, and the alpha is its opacity from 0 to 1. The
+/// distance is how far the fragment is from the camera along the view
+/// direction, or from the plane through the center of the view with an
+/// orthographic projection, which has no camera position.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fragment {
+    pub color: image::Rgb<u8>,
+    pub depth: f64,
+    pub alpha: f64,
+    pub distance: f64
+}
+
+
+/// A buffer that records every fragment drawn into every pixel instead of only the nearest
+///
+/// The fragments of every pixel are kept in a linked list, with the head of
+/// every list in a buffer of its own and all the fragments in a single pool,
+/// so drawing only appends to the pool. Transparent surfaces can then be
+/// blended exactly in a single pass whatever order they were drawn in, and
+/// the fragments of every pixel make a deep image.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::abuffer::*;
+/// # use rastermind::render::DepthOptions;
+/// # use rastermind::{shader, wavefront};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let (eye, center, up) = (Vector3::<f64>::new(0.0, 0.0, 3.0), Vector3::zeros(), Vector3::y());
+/// # let light_vector = Vector3::<f64>::z();
+/// # let view_port = shader::viewport(64, 64, 384, 384, 255);
+/// # let projection = shader::projection(-1.0 / 3.0);
+/// # let model_view = shader::lookat(&eye, &center, &up);
+/// let mut abuffer = ABuffer::new(512, 512);
+///
+/// draw_faces(&coordinates, &mut abuffer, &texture, &DepthOptions::default(), &view_port, &projection,
+///            &model_view, &light_vector, 0.5, |_| Box::new(shader::GouraudShader::new()));
+///
+/// let image = abuffer.resolve(&image::RgbImage::new(512, 512));
+/// # Ok(())
+/// # }
+/// ```
+///
+pub struct ABuffer {
+    pub width: u32,
+    pub height: u32,
+    heads: Vec<Option<usize>>,
+    pool: Vec<(Fragment, Option<usize>)>
+}
+
+
+impl ABuffer {
+    /// Create a new empty ABuffer of the given size
+    pub fn new(width: u32, height: u32) -> ABuffer {
+        ABuffer { width, height, heads: vec![None; (width * height) as usize], pool: Vec::new() }
+    }
+
+    /// Record a fragment in the given pixel
+    pub fn push(&mut self, x: u32, y: u32, fragment: Fragment) {
+        let index = (x + y * self.width) as usize;
+
+        self.pool.push((fragment, self.heads[index]));
+        self.heads[index] = Some(self.pool.len() - 1);
+    }
+
+    /// The number of fragments recorded in every pixel together
+    pub fn len(&self) -> usize {
+        self.pool.len()
+    }
+
+    /// Whether no fragment has been recorded
+    pub fn is_empty(&self) -> bool {
+        self.pool.is_empty()
+    }
+
+    /// The fragments recorded in the given pixel, from the last one drawn to the first
+    pub fn fragments(&self, x: u32, y: u32) -> impl Iterator<Item = Fragment> + '_ {
+        self.indices(x, y).map(move |index| self.pool[index].0)
+    }
+
+    /// The indices in the pool of the fragments recorded in the given pixel, from the last one drawn to the first
+    fn indices(&self, x: u32, y: u32) -> Indices<'_> {
+        Indices { abuffer: self, next: self.heads[(x + y * self.width) as usize] }
+    }
+
+    /// The fragments recorded in the given pixel from the nearest to the furthest
+    pub fn sorted(&self, x: u32, y: u32) -> Vec<Fragment> {
+        let mut fragments: Vec<Fragment> = self.fragments(x, y).collect();
+        fragments.sort_by(|a, b| b.depth.total_cmp(&a.depth));
+
+        fragments
+    }
+
+    /// The depth of the nearest fragment of every pixel, or -1 where nothing was recorded, like a depth buffer
+    pub fn nearest_depths(&self) -> Vec<f64> {
+        (0..self.height).flat_map(|y| (0..self.width).map(move |x| (x, y))).map(|(x, y)| {
+            self.fragments(x, y).map(|fragment| fragment.depth).fold(-1.0, f64::max)
+        }).collect()
+    }
+
+    /// Blend the fragments of every pixel over the given background from the furthest to the nearest
+    ///
+    /// The colors are blended in linear light, so the result does not depend
+    /// on the order the fragments were drawn in.
+    ///
+    pub fn resolve(&self, background: &image::RgbImage) -> image::RgbImage {
+        image::ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            let background = background.get_pixel(x, y);
+            let mut color = [0, 1, 2].map(|i| post::to_linear(background[i]));
+
+            for fragment in self.sorted(x, y).iter().rev() {
+                let alpha = fragment.alpha.clamp(0.0, 1.0);
+
+                (0..=2).for_each(|i| color[i] = post::to_linear(fragment.color[i]) * alpha +
+                                                color[i] * (1.0 - alpha));
+            }
+
+            image::Rgb(color.map(post::to_srgb))
+        })
+    }
+}
+
+
+/// An iterator over the linked list of fragments of a pixel of an ABuffer
+struct Indices<'a> {
+    abuffer: &'a ABuffer,
+    next: Option<usize>
+}
+
+
+impl<'a> Iterator for Indices<'a> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let index = self.next?;
+        self.next = self.abuffer.pool[index].1;
+
+        Some(index)
+    }
+}
+
+
+/// Record every fragment of every face of an Object in an ABuffer with the given opacity
+///
+/// Nothing is depth tested, so every fragment the shader does not discard is
+/// recorded, including those of faces hidden behind opaque ones, which are
+/// hidden again when the A-buffer is resolved. Pixels on an edge shared by
+/// two faces of the Object are only recorded once.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::abuffer::*;
+/// # use rastermind::render::DepthOptions;
+/// # use rastermind::{shader, wavefront};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let (eye, center, up) = (Vector3::<f64>::new(0.0, 0.0, 3.0), Vector3::zeros(), Vector3::y());
+/// # let light_vector = Vector3::<f64>::z();
+/// # let view_port = shader::viewport(64, 64, 384, 384, 255);
+/// # let projection = shader::projection(-1.0 / 3.0);
+/// # let model_view = shader::lookat(&eye, &center, &up);
+/// # let mut abuffer = ABuffer::new(512, 512);
+/// # let glass = coordinates.clone();
+/// draw_faces(&glass, &mut abuffer, &texture, &DepthOptions::default(), &view_port, &projection,
+///            &model_view, &light_vector, 0.3, |_| Box::new(shader::GouraudShader::new()));
+/// # Ok(())
+/// # }
+/// ```
+///
+#[allow(clippy::too_many_arguments)]
+pub fn draw_faces<'a, F>(coordinates: &wavefront::Object, abuffer: &mut ABuffer, texture: &image::RgbImage,
+                         depth: &DepthOptions, view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+                         model_view: &Matrix4<f64>, light_vector: &Vector3<f64>, alpha: f64,
+                         mut shader_for_face: F) where F: FnMut(usize) -> Box<dyn Shader + 'a> {
+
+    let camera_distance = -1.0 / projection[(3, 2)];
+    let (width, height) = (abuffer.width, abuffer.height);
+    let first = abuffer.pool.len();
+    let to_view = (view_port * projection).try_inverse().unwrap_or_else(Matrix4::identity);
+
+    for face_index in 0..coordinates.geometric_faces.len() {
+        let mut shader = shader_for_face(face_index);
+        let screen_coordinates: Vec<Vector4<f64>> = (0..=2).map(|vertex_index| {
+            shader.vertex(coordinates, view_port, projection, model_view, light_vector, face_index, vertex_index)
+        }).collect();
+
+        // the depths of the vertices in view space, which are interpolated like the other varyings
+        let view_depths: Vec<f64> = screen_coordinates.iter().map(|point| {
+            let view = to_view * point;
+            -view.z / view.w
+        }).collect();
+
+        render::rasterize_triangle(&screen_coordinates, width, height, depth, camera_distance, shader.as_ref(),
+                                   |x, y, coordinate, value| {
+            // pixels on the edges shared by faces are covered by both, but must only be blended once
+            let tolerance = 1e-9 * value.abs().max(1.0);
+            let shared = abuffer.indices(x, y)
+                                .take_while(|&index| index >= first)
+                                .any(|index| (abuffer.pool[index].0.depth - value).abs() <= tolerance);
+
+            if !shared {
+                let perspective = (0..=2).map(|i| coordinate[i] / screen_coordinates[i].w).sum::<f64>();
+                let view_depth = (0..=2).map(|i| coordinate[i] * view_depths[i] / screen_coordinates[i].w)
+                                        .sum::<f64>() / perspective;
+                let distance = if camera_distance.is_finite() { camera_distance + view_depth } else { view_depth };
+
+                abuffer.push(x, y, Fragment { color: shader.fragment(coordinate, texture), depth: value, alpha,
+                                              distance });
+            }
+        });
+    }
+}
+
+
+/// The magic number that starts every OpenEXR file
+const EXR_MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+
+/// The version of OpenEXR files, with the flag of files that hold deep data instead of an image
+const EXR_VERSION: u32 = 2 | 0x800;
+
+
+/// Encode the fragments of an ABuffer as a deep scanline OpenEXR file
+///
+/// Every pixel holds its fragments from the nearest to the furthest as
+/// samples with the A, B, G, R, and Z channels as 32 bit floats, where the
+/// colors are linear and premultiplied by the alpha and Z is the distance
+/// of the fragment. The file is uncompressed with one scanline per chunk,
+/// and its first row is at the top, so the rows are flipped from the buffer.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate rastermind;
+/// # use std::fs;
+/// # use rastermind::abuffer::*;
+/// # let abuffer = ABuffer::new(512, 512);
+/// fs::write("render.exr", deep_exr(&abuffer)).unwrap();
+/// ```
+///
+pub fn deep_exr(abuffer: &ABuffer) -> Vec<u8> {
+    let (width, height) = (abuffer.width, abuffer.height);
+    let rows: Vec<Vec<Vec<Fragment>>> = (0..height).rev().map(|y| {
+        (0..width).map(|x| {
+            let mut fragments: Vec<Fragment> = abuffer.fragments(x, y).collect();
+            fragments.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+
+            fragments
+        }).collect()
+    }).collect();
+    let most = rows.iter().flatten().map(Vec::len).max().unwrap_or(0);
+
+    let channels: Vec<u8> = ["A", "B", "G", "R", "Z"].iter().flat_map(|name| {
+        // 32 bit floats that are not linear in perception and not subsampled
+        [name.as_bytes(), &[0], &2i32.to_le_bytes(), &[0; 4], &1i32.to_le_bytes(), &1i32.to_le_bytes()].concat()
+    }).chain(Some(0)).collect();
+    let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1].iter().flat_map(|v| v.to_le_bytes()).collect();
+
+    let attributes: Vec<(&str, &str, Vec<u8>)> = vec![
+        ("channels", "chlist", channels),
+        ("chunkCount", "int", (height as i32).to_le_bytes().to_vec()),
+        ("compression", "compression", vec![0]),
+        ("dataWindow", "box2i", window.clone()),
+        ("displayWindow", "box2i", window),
+        ("lineOrder", "lineOrder", vec![0]),
+        ("maxSamplesPerPixel", "int", (most as i32).to_le_bytes().to_vec()),
+        ("pixelAspectRatio", "float", 1f32.to_le_bytes().to_vec()),
+        ("screenWindowCenter", "v2f", [0f32.to_le_bytes(), 0f32.to_le_bytes()].concat()),
+        ("screenWindowWidth", "float", 1f32.to_le_bytes().to_vec()),
+        ("type", "string", b"deepscanline".to_vec()),
+        ("version", "int", 1i32.to_le_bytes().to_vec())
+    ];
+
+    let mut bytes = EXR_MAGIC.to_vec();
+    bytes.extend_from_slice(&EXR_VERSION.to_le_bytes());
+
+    for (name, kind, value) in attributes {
+        bytes.extend_from_slice(&[name.as_bytes(), &[0], kind.as_bytes(), &[0]].concat());
+        bytes.extend_from_slice(&(value.len() as i32).to_le_bytes());
+        bytes.extend_from_slice(&value);
+    }
+    bytes.push(0);
+
+    let chunks: Vec<Vec<u8>> = rows.iter().enumerate().map(|(y, row)| {
+        let mut counts = Vec::new();
+        let mut samples = Vec::new();
+        let mut total = 0i32;
+
+        for fragments in row {
+            total += fragments.len() as i32;
+            counts.extend_from_slice(&total.to_le_bytes());
+        }
+
+        for channel in 0..5 {
+            for fragment in row.iter().flatten() {
+                let value = match channel {
+                    0 => fragment.alpha,
+                    4 => fragment.distance,
+                    _ => post::to_linear(fragment.color[3 - channel]) * fragment.alpha
+                };
+
+                samples.extend_from_slice(&(value as f32).to_le_bytes());
+            }
+        }
+
+        [&(y as i32).to_le_bytes()[..], &(counts.len() as u64).to_le_bytes(), &(samples.len() as u64).to_le_bytes(),
+         &(samples.len() as u64).to_le_bytes(), &counts, &samples].concat()
+    }).collect();
+
+    // the offsets of the chunks from the start of the file follow the header
+    let mut offset = (bytes.len() + chunks.len() * 8) as u64;
+
+    for chunk in &chunks {
+        bytes.extend_from_slice(&offset.to_le_bytes());
+        offset += chunk.len() as u64;
+    }
+
+    chunks.iter().for_each(|chunk| bytes.extend_from_slice(chunk));
+
+    bytes
+}
+
+
+/// Save the fragments of an ABuffer as a deep scanline OpenEXR file for deep compositing
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate rastermind;
+/// # use rastermind::abuffer::*;
+/// # let abuffer = ABuffer::new(512, 512);
+/// save_deep_exr("render.exr", &abuffer).unwrap();
+/// ```
+///
+pub fn save_deep_exr<P: AsRef<Path>>(path: P, abuffer: &ABuffer) -> io::Result<()> {
+    File::create(path)?.write_all(&deep_exr(abuffer))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shader;
+
+    #[test]
+    fn test_abuffer() {
+        let mut abuffer = ABuffer::new(2, 1);
+        let fragment = |value: u8, depth: f64, alpha: f64| {
+            Fragment { color: image::Rgb([value; 3]), depth, alpha, distance: 4.0 - depth }
+        };
+
+        abuffer.push(0, 0, fragment(10, 1.0, 1.0));
+        abuffer.push(0, 0, fragment(20, 3.0, 0.5));
+        abuffer.push(0, 0, fragment(30, 2.0, 0.5));
+
+        // the list of a pixel runs from the last fragment drawn, and sorting puts the nearest first
+        assert_eq!(abuffer.len(), 3);
+        assert_eq!(abuffer.fragments(0, 0).map(|fragment| fragment.color[0]).collect::<Vec<u8>>(), vec![30, 20, 10]);
+        assert_eq!(abuffer.sorted(0, 0).iter().map(|fragment| fragment.depth).collect::<Vec<f64>>(),
+                   vec![3.0, 2.0, 1.0]);
+        assert_eq!(abuffer.fragments(1, 0).count(), 0);
+        assert_eq!(abuffer.nearest_depths(), vec![3.0, -1.0]);
+
+        let background = image::RgbImage::from_pixel(2, 1, image::Rgb([255, 255, 255]));
+        let image = abuffer.resolve(&background);
+
+        let expected = (post::to_linear(10) * 0.25 + post::to_linear(30) * 0.25) + post::to_linear(20) * 0.5;
+        assert_eq!(image.get_pixel(0, 0)[0], post::to_srgb(expected));
+        assert_eq!(*image.get_pixel(1, 0), image::Rgb([255, 255, 255]));
+    }
+
+    #[test]
+    fn test_deep_exr() {
+        let mut abuffer = ABuffer::new(2, 2);
+        abuffer.push(0, 1, Fragment { color: image::Rgb([255, 0, 0]), depth: 1.0, alpha: 1.0, distance: 5.0 });
+        abuffer.push(0, 1, Fragment { color: image::Rgb([0, 0, 255]), depth: 2.0, alpha: 0.5, distance: 3.0 });
+        abuffer.push(1, 0, Fragment { color: image::Rgb([255, 255, 255]), depth: 1.0, alpha: 1.0, distance: 4.0 });
+
+        let bytes = deep_exr(&abuffer);
+        let int = |at: usize| i32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]);
+        let long = |at: usize| int(at) as usize;
+        let float = |at: usize| f32::from_bits(int(at) as u32);
+        let find = |needle: &[u8]| bytes.windows(needle.len()).position(|window| window == needle).unwrap();
+
+        assert_eq!(&bytes[..4], &EXR_MAGIC);
+        assert_eq!(int(4), 0x802);
+
+        let samples = find(b"maxSamplesPerPixel\0int\0") + 23;
+        assert_eq!((int(samples), int(samples + 4)), (4, 2));
+
+        // the offset table follows the end of the header and points at the chunks of both rows
+        let table = find(b"deepscanline") + "deepscanline".len() + "version\0int\0".len() + 8 + 1;
+        let (top, bottom) = (long(table), long(table + 8));
+
+        // the top row of the file is the top row of the buffer, with both fragments of its first pixel
+        assert_eq!(int(top), 0);
+        assert_eq!((long(top + 4), long(top + 12), long(top + 20)), (8, 40, 40));
+        assert_eq!((int(top + 28), int(top + 32)), (2, 2));
+
+        // the nearer half transparent blue sample comes first, premultiplied by its alpha
+        let data = top + 36;
+        assert_eq!((float(data), float(data + 4)), (0.5, 1.0));
+        assert_eq!((float(data + 8), float(data + 24)), (0.5, 0.0));
+        assert_eq!((float(data + 32), float(data + 36)), (3.0, 5.0));
+
+        assert_eq!(int(bottom), 1);
+        assert_eq!((int(bottom + 28), int(bottom + 32)), (0, 1));
+        assert_eq!(bottom + 36 + 20, bytes.len());
+    }
+
+    #[test]
+    fn test_draw_faces() {
+        let square = |z: f64| {
+            let bytes = format!("v -1 -1 {0}\nv 1 -1 {0}\nv 1 1 {0}\nv -1 1 {0}\nvt 0 0\nvn 0 0 1\n\
+                                 f 1/1/1 2/1/1 3/1/1\nf 1/1/1 3/1/1 4/1/1\n", z);
+            wavefront::Object::from_bytes(bytes.as_bytes(), &wavefront::LoadOptions::default()).unwrap()
+        };
+
+        let draw = |abuffer: &mut ABuffer, z: f64, color: image::Rgb<u8>, alpha: f64| {
+            draw_faces(&square(z), abuffer, &image::RgbImage::new(1, 1), &DepthOptions::default(),
+                       &shader::viewport(4, 4, 24, 24, 255), &shader::projection(0.0), &Matrix4::identity(),
+                       &Vector3::z(), alpha, |_| -> Box<dyn Shader> {
+                Box::new(shader::ColorShader::new(color, false))
+            });
+        };
+
+        let (red, blue) = (image::Rgb([255, 0, 0]), image::Rgb([0, 0, 255]));
+        let mut front_first = ABuffer::new(32, 32);
+        let mut back_first = ABuffer::new(32, 32);
+
+        draw(&mut front_first, 0.5, blue, 0.5);
+        draw(&mut front_first, 0.0, red, 1.0);
+        draw(&mut back_first, 0.0, red, 1.0);
+        draw(&mut back_first, 0.5, blue, 0.5);
+
+        // every fragment of both squares is kept, and the order they were drawn in does not matter
+        assert_eq!(front_first.fragments(16, 16).count(), 2);
+        assert_eq!(front_first.len(), back_first.len());
+        assert_eq!(front_first.sorted(16, 16)[0].color, blue);
+
+        // without a camera position the distance is measured from the plane through the center of the view
+        assert!((front_first.sorted(16, 16)[0].distance + 0.5).abs() < 1e-9);
+        assert!(front_first.sorted(16, 16)[1].distance.abs() < 1e-9);
+
+        let background = image::RgbImage::new(32, 32);
+        let image = front_first.resolve(&background);
+
+        assert_eq!(image.clone().into_raw(), back_first.resolve(&background).into_raw());
+        assert_eq!(*image.get_pixel(16, 16), image::Rgb([post::to_srgb(0.5), 0, post::to_srgb(0.5)]));
+    }
+}
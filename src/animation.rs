@@ -0,0 +1,711 @@
+use nalgebra::{Matrix4, Rotation3, Vector3};
+
+use motion;
+use scene;
+use scene::Scene;
+
+
+/// Where a camera is and what it looks at
+///
+/// # Examples
+///
+/// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::animation::*;
+/// let camera = Camera::new(Vector3::new(0.0, 2.0, 5.0), Vector3::zeros(), Vector3::y());
+/// ```
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    pub eye: Vector3<f64>,
+    pub center: Vector3<f64>,
+    pub up: Vector3<f64>
+}
+
+
+impl Camera {
+    /// Create a new Camera at the eye looking at the center with the given up direction
+    pub fn new(eye: Vector3<f64>, center: Vector3<f64>, up: Vector3<f64>) -> Camera {
+        Camera { eye, center, up }
+    }
+}
+
+
+impl Default for Camera {
+    fn default() -> Camera {
+        Camera::new(Vector3::new(0.0, 0.0, 3.0), Vector3::zeros(), Vector3::y())
+    }
+}
+
+
+/// How a value moves from one keyframe to the next
+///
+/// Step holds the value of a keyframe until the next keyframe, while the
+/// others blend between them, starting slowly, stopping slowly, or both.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    Step
+}
+
+
+impl Easing {
+    /// Map the fraction of the time between two keyframes to the fraction of the change in value
+    pub fn apply(self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+            Easing::Step => if t < 1.0 { 0.0 } else { 1.0 }
+        }
+    }
+}
+
+
+/// A value at a time in frames, with the easing towards the next keyframe
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keyframe {
+    pub time: f64,
+    pub value: Vector3<f64>,
+    pub easing: Easing
+}
+
+
+impl Keyframe {
+    /// Create a new Keyframe that eases in and out towards the next keyframe
+    pub fn new(time: f64, value: Vector3<f64>) -> Keyframe {
+        Keyframe { time, value, easing: Easing::EaseInOut }
+    }
+}
+
+
+/// Keyframes of a value over time
+///
+/// Before the first keyframe and after the last the value is held, unless the
+/// track is looped, in which case it repeats from the time of the first
+/// keyframe with a period of the time between the first and last keyframes.
+/// Keyframes must be sorted by time.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::animation::*;
+/// // bob up and down every two seconds at 24 frames per second
+/// let track = Track { keyframes: vec![Keyframe::new(0.0, Vector3::zeros()), Keyframe::new(24.0, Vector3::y()),
+///                                     Keyframe::new(48.0, Vector3::zeros())],
+///                     looped: true };
+/// ```
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Track {
+    pub keyframes: Vec<Keyframe>,
+    pub looped: bool
+}
+
+
+impl Track {
+    /// Find the value of the track at the given time in frames, which may be between frames
+    pub fn sample(&self, time: f64) -> Option<Vector3<f64>> {
+        let first = self.keyframes.first()?;
+        let last = self.keyframes.last()?;
+        let period = last.time - first.time;
+
+        let time = if self.looped && period > 0.0 {
+            first.time + (time - first.time).rem_euclid(period)
+        } else {
+            time
+        };
+
+        let next = self.keyframes.iter().position(|keyframe| keyframe.time > time);
+
+        Some(match next {
+            None => last.value,
+            Some(0) => first.value,
+            Some(index) => {
+                let (from, to) = (&self.keyframes[index - 1], &self.keyframes[index]);
+                let t = from.easing.apply((time - from.time) / (to.time - from.time));
+
+                from.value + (to.value - from.value) * t
+            }
+        })
+    }
+}
+
+
+/// How the camera moves between the waypoints of a spline
+///
+/// A Catmull-Rom spline passes through every waypoint with tangents that
+/// point from the waypoint before to the waypoint after. A Bezier spline
+/// passes through the waypoints too, but leaves each one towards its handle,
+/// and waypoints without a handle leave as they would on a Catmull-Rom spline.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interpolation {
+    #[default]
+    CatmullRom,
+    Bezier
+}
+
+
+/// Where the camera looks while it follows a spline
+///
+/// The camera either keeps looking at a target, or faces along the spline
+/// in the direction it is moving, as if it flew along it.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum Orientation {
+    LookAt { target: [f64; 3] },
+    Tangent
+}
+
+
+impl Default for Orientation {
+    fn default() -> Orientation {
+        Orientation::LookAt { target: [0.0, 0.0, 0.0] }
+    }
+}
+
+
+/// A point a spline passes through at a time in frames, with the Bezier handle it leaves towards
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Waypoint {
+    pub position: [f64; 3],
+    pub time: f64,
+    #[serde(default)]
+    pub handle: Option<[f64; 3]>
+}
+
+
+impl Waypoint {
+    /// Create a new Waypoint at the given position and time without a handle
+    pub fn new(position: [f64; 3], time: f64) -> Waypoint {
+        Waypoint { position, time, handle: None }
+    }
+}
+
+
+/// A smooth path for the camera through waypoints, for fly-throughs of a scene
+///
+/// The camera reaches every waypoint at its own time, so it speeds up
+/// between waypoints that are far apart in space but close in time. Before
+/// the first waypoint and after the last the camera is held. Waypoints must
+/// be sorted by time.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate rastermind;
+/// # extern crate serde_json;
+/// # use rastermind::animation::*;
+/// # fn main() -> Result<(), serde_json::Error> {
+/// # let mut animation = Animation::new(120, 64, 64);
+/// let path: CameraSpline = serde_json::from_str(r#"{
+///     "waypoints": [{"position": [0, 2, 10], "time": 0}, {"position": [8, 3, 0], "time": 48},
+///                   {"position": [0, 4, -10], "time": 120}],
+///     "orientation": {"mode": "tangent"}
+/// }"#)?;
+///
+/// animation.path = Some(path);
+/// # Ok(())
+/// # }
+/// ```
+///
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CameraSpline {
+    pub waypoints: Vec<Waypoint>,
+    #[serde(default)]
+    pub interpolation: Interpolation,
+    #[serde(default)]
+    pub orientation: Orientation,
+    #[serde(default = "CameraSpline::default_up")]
+    pub up: [f64; 3]
+}
+
+
+impl CameraSpline {
+    /// Create a new Catmull-Rom spline through the given waypoints looking at the origin
+    pub fn new(waypoints: Vec<Waypoint>) -> CameraSpline {
+        CameraSpline { waypoints, interpolation: Interpolation::default(), orientation: Orientation::default(),
+                       up: CameraSpline::default_up() }
+    }
+
+    fn default_up() -> [f64; 3] {
+        [0.0, 1.0, 0.0]
+    }
+
+    /// The four control points of the cubic Bezier curve from the waypoint at the given index to the next
+    fn segment(&self, index: usize) -> [Vector3<f64>; 4] {
+        let last = self.waypoints.len() - 1;
+        let position = |index: usize| Vector3::from(self.waypoints[index].position);
+
+        // the ends of the spline continue in a straight line to their neighbour
+        let before = |index: usize| match index {
+            0 => position(0) * 2.0 - position(1),
+            _ => position(index - 1)
+        };
+        let after = |index: usize| match index {
+            _ if index == last => position(last) * 2.0 - position(last - 1),
+            _ => position(index + 1)
+        };
+        let tangent = |index: usize| (after(index) - before(index)) / 6.0;
+
+        let handle = |index: usize| match (self.interpolation, self.waypoints[index].handle) {
+            (Interpolation::Bezier, Some(handle)) => Vector3::from(handle) - position(index),
+            _ => tangent(index)
+        };
+
+        // the handle of the next waypoint is mirrored, so the spline leaves it as smoothly as it arrives
+        [position(index), position(index) + handle(index), position(index + 1) - handle(index + 1),
+         position(index + 1)]
+    }
+
+    /// Find the position and direction of motion of the spline at the given time in frames
+    pub fn sample(&self, time: f64) -> Option<(Vector3<f64>, Vector3<f64>)> {
+        let first = self.waypoints.first()?;
+
+        if self.waypoints.len() == 1 {
+            return Some((Vector3::from(first.position), Vector3::zeros()));
+        }
+
+        let next = self.waypoints.iter().position(|waypoint| waypoint.time > time)
+                                 .unwrap_or(self.waypoints.len() - 1).max(1);
+        let (from, to) = (&self.waypoints[next - 1], &self.waypoints[next]);
+        let duration = to.time - from.time;
+        let t = if duration > 0.0 { ((time - from.time) / duration).clamp(0.0, 1.0) } else { 1.0 };
+
+        let [b0, b1, b2, b3] = self.segment(next - 1);
+        let s = 1.0 - t;
+
+        let position = b0 * s * s * s + b1 * 3.0 * s * s * t + b2 * 3.0 * s * t * t + b3 * t * t * t;
+        let direction = (b1 - b0) * 3.0 * s * s + (b2 - b1) * 6.0 * s * t + (b3 - b2) * 3.0 * t * t;
+
+        // a handle on top of its waypoint stops the curve there, so it faces along the whole segment instead
+        Some((position, direction.try_normalize(1e-12).unwrap_or_else(|| (b3 - b0).normalize())))
+    }
+
+    /// Find the camera on the spline at the given time in frames
+    pub fn camera(&self, time: f64) -> Option<Camera> {
+        let (eye, direction) = self.sample(time)?;
+
+        let center = match self.orientation {
+            Orientation::LookAt { target } => Vector3::from(target),
+            Orientation::Tangent => eye + direction
+        };
+
+        Some(Camera::new(eye, center, Vector3::from(self.up)))
+    }
+}
+
+
+/// Keyframe tracks that place one object of a scene over time
+///
+/// The rotation is in radians about the x, y, and z axes, so a full turn is
+/// two keyframes apart. Empty tracks leave the object at the origin, without
+/// rotation, and at its own scale.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use std::f64::consts::PI;
+/// # use nalgebra::Vector3;
+/// # use rastermind::animation::*;
+/// # let mut animation = Animation::new(120, 64, 64);
+/// // spin the first object once every 120 frames
+/// let mut spin = ObjectTracks::new(0);
+/// spin.rotation = Track { keyframes: vec![Keyframe { time: 0.0, value: Vector3::zeros(), easing: Easing::Linear },
+///                                         Keyframe::new(120.0, Vector3::y() * 2.0 * PI)],
+///                         looped: true };
+/// animation.objects.push(spin);
+/// ```
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjectTracks {
+    pub object: usize,
+    pub position: Track,
+    pub rotation: Track,
+    pub scale: Track
+}
+
+
+impl ObjectTracks {
+    /// Create new empty ObjectTracks for the object of the scene with the given index
+    pub fn new(object: usize) -> ObjectTracks {
+        ObjectTracks { object, position: Track::default(), rotation: Track::default(), scale: Track::default() }
+    }
+
+    /// Find the transform of the object at the given time, which scales, then rotates, then moves it
+    pub fn transform(&self, time: f64) -> Matrix4<f64> {
+        let position = self.position.sample(time).unwrap_or_else(Vector3::zeros);
+        let rotation = self.rotation.sample(time).unwrap_or_else(Vector3::zeros);
+        let scale = self.scale.sample(time).unwrap_or_else(|| Vector3::repeat(1.0));
+
+        Matrix4::new_translation(&position) *
+            Rotation3::from_euler_angles(rotation.x, rotation.y, rotation.z).to_homogeneous() *
+            Matrix4::new_nonuniform_scaling(&scale)
+    }
+}
+
+
+/// The scene and camera of a frame that is about to be drawn at a time in frames, which may be changed
+pub struct FrameSetup<'a> {
+    pub frame: usize,
+    pub time: f64,
+    pub scene: &'a mut Scene,
+    pub camera: &'a mut Camera
+}
+
+
+/// The buffers of a frame that has been drawn along with the scene and camera it was drawn with
+pub struct DrawnFrame<'a> {
+    pub frame: usize,
+    pub scene: &'a Scene,
+    pub camera: &'a Camera,
+    pub color: &'a image::RgbImage,
+    pub depth: &'a [f64]
+}
+
+
+type BeforeFrame<'a> = Box<dyn FnMut(&mut FrameSetup) + 'a>;
+
+type AfterFrame<'a> = Box<dyn FnMut(&DrawnFrame) + 'a>;
+
+
+/// Render the frames of a scene, calling back before and after every frame
+///
+/// Before every frame the tracks of the camera and objects are evaluated,
+/// and the camera follows its spline instead of its tracks if it has one,
+/// and then the callbacks may move the camera, change the lights, or change
+/// the objects, so the scene can follow a simulation or a tool. The callbacks
+/// after a frame are given the color and depth buffers to save, show, or
+/// inspect. Callbacks are called in the order they are added.
+///
+/// With motion blur every frame is drawn at every time its shutter is open,
+/// and the tracks are evaluated and the callbacks before the frame are called
+/// at each of those times, so they can place everything in between frames.
+/// The depth buffer of a blurred frame is the one drawn first.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::animation::*;
+/// # use rastermind::light::Light;
+/// # use rastermind::scene::{Scene, SceneLight};
+/// # let mut scene = Scene::new();
+/// # scene.lights.push(SceneLight::new("sun", Light::Directional(Vector3::y())));
+/// let mut animation = Animation::new(120, 512, 512);
+///
+/// animation.before_frame(|setup| {
+///     setup.camera.eye = Vector3::new(0.0, 2.0, 5.0 - setup.frame as f64 * 0.01);
+///     setup.scene.lights[0].light = Light::Directional(Vector3::new(setup.time / 120.0, 1.0, 0.0).normalize());
+/// });
+/// animation.after_frame(|frame| {
+///     image::ImageRgb8(frame.color.clone()).flipv().save(format!("frame_{:04}.png", frame.frame)).unwrap();
+/// });
+///
+/// animation.render(&mut scene, &mut Camera::default());
+/// ```
+///
+pub struct Animation<'a> {
+    pub frames: usize,
+    pub width: u32,
+    pub height: u32,
+    pub depth: u32,
+    pub eye: Track,
+    pub center: Track,
+    pub path: Option<CameraSpline>,
+    pub objects: Vec<ObjectTracks>,
+    pub motion_blur: Option<motion::MotionBlur>,
+    before: Vec<BeforeFrame<'a>>,
+    after: Vec<AfterFrame<'a>>
+}
+
+
+impl<'a> Animation<'a> {
+    /// Create a new Animation of the given number of frames of the given size without any callbacks
+    pub fn new(frames: usize, width: u32, height: u32) -> Animation<'a> {
+        Animation { frames, width, height, depth: 255, eye: Track::default(), center: Track::default(), path: None,
+                    objects: Vec::new(), motion_blur: None, before: Vec::new(), after: Vec::new() }
+    }
+
+    /// Move the camera and the objects with tracks to where they are at the given time in frames
+    ///
+    /// Times between frames place everything in between, so motion blur can
+    /// sample the animation while the shutter is open.
+    ///
+    pub fn evaluate(&self, time: f64, scene: &mut Scene, camera: &mut Camera) {
+        if let Some(eye) = self.eye.sample(time) {
+            camera.eye = eye;
+        }
+
+        if let Some(center) = self.center.sample(time) {
+            camera.center = center;
+        }
+
+        if let Some(spline) = self.path.as_ref().and_then(|path| path.camera(time)) {
+            *camera = spline;
+        }
+
+        for tracks in &self.objects {
+            if let Some(object) = scene.objects.get_mut(tracks.object) {
+                object.transform = tracks.transform(time);
+            }
+        }
+    }
+
+    /// Add a callback that is called before every frame is drawn
+    pub fn before_frame<F>(&mut self, callback: F) where F: FnMut(&mut FrameSetup) + 'a {
+        self.before.push(Box::new(callback));
+    }
+
+    /// Add a callback that is called after every frame is drawn
+    pub fn after_frame<F>(&mut self, callback: F) where F: FnMut(&DrawnFrame) + 'a {
+        self.after.push(Box::new(callback));
+    }
+
+    /// Draw every frame, leaving the scene and camera as the callbacks left them after the last frame
+    pub fn render(&mut self, scene: &mut Scene, camera: &mut Camera) {
+        for frame in 0..self.frames {
+            let mut color = image::ImageBuffer::new(self.width, self.height);
+            let mut depth = Vec::new();
+
+            match self.motion_blur {
+                Some(blur) => {
+                    color = motion::render_motion_blur(self.width, self.height, frame as f64, &blur, |time, buffer| {
+                        let drawn = self.draw(frame, time, scene, camera, buffer);
+
+                        if depth.is_empty() {
+                            depth = drawn;
+                        }
+                    });
+                },
+                None => depth = self.draw(frame, frame as f64, scene, camera, &mut color)
+            }
+
+            let drawn = DrawnFrame { frame, scene, camera, color: &color, depth: &depth };
+
+            for callback in self.after.iter_mut() {
+                callback(&drawn);
+            }
+        }
+    }
+
+    /// Set up the scene and camera of a frame at the given time and draw it into the buffer, returning its depths
+    fn draw(&mut self, frame: usize, time: f64, scene: &mut Scene, camera: &mut Camera,
+            buffer: &mut image::RgbImage) -> Vec<f64> {
+
+        self.evaluate(time, scene, camera);
+
+        for callback in self.before.iter_mut() {
+            callback(&mut FrameSetup { frame, time, scene, camera });
+        }
+
+        scene::render_scene(scene, buffer, self.depth, &camera.eye, &camera.center, &camera.up)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use light::Light;
+    use render;
+    use scene::{SceneLight, SceneObject};
+    use wavefront;
+    use super::*;
+
+    #[test]
+    fn test_animation() {
+        let square = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\nvt 0 0\nvn 0 0 1\n\
+                                                     f 1/1/1 2/1/1 3/1/1\nf 1/1/1 3/1/1 4/1/1\n",
+                                                   &wavefront::LoadOptions::default()).unwrap();
+        let mut object = SceneObject::new(square, image::RgbImage::new(1, 1));
+        object.colors = render::ColorOverride { object: Some(image::Rgb([255, 255, 255])), lit: false,
+                                                ..Default::default() };
+
+        let mut scene = Scene::new();
+        scene.lights.push(SceneLight::new("key", Light::Directional(Vector3::z())));
+        scene.objects.push(object);
+
+        let mut centers = Vec::new();
+        let mut animation = Animation::new(3, 32, 32);
+
+        // the square gets brighter and the camera moves right every frame
+        animation.before_frame(|setup| {
+            setup.scene.objects[0].colors.object = Some(image::Rgb([50 + 100 * setup.frame as u8; 3]));
+            setup.camera.eye.x = setup.frame as f64;
+        });
+        animation.after_frame(|frame| {
+            let drawn = frame.color.pixels().filter(|&&pixel| pixel != image::Rgb([0, 0, 0])).count();
+
+            assert_eq!(frame.depth.iter().filter(|&&depth| depth != -1.0).count(), drawn);
+            centers.push((frame.frame, frame.color.get_pixel(16, 16)[0]));
+        });
+
+        let mut camera = Camera::default();
+        animation.render(&mut scene, &mut camera);
+        drop(animation);
+
+        assert_eq!(camera.eye.x, 2.0);
+        assert_eq!(centers, vec![(0, 50), (1, 150), (2, 250)]);
+
+        // with motion blur every frame is set up at every time its shutter is open
+        let mut times = Vec::new();
+        let mut blurred = Animation::new(2, 8, 8);
+        blurred.motion_blur = Some(motion::MotionBlur { samples: 2, shutter: 1.0, seed: 0 });
+        blurred.before_frame(|setup| times.push((setup.frame, setup.time)));
+        blurred.render(&mut scene, &mut camera);
+        drop(blurred);
+
+        assert_eq!(times.iter().map(|&(frame, _)| frame).collect::<Vec<usize>>(), vec![0, 0, 1, 1]);
+        assert!(times.iter().all(|&(frame, time)| time >= frame as f64 && time < frame as f64 + 1.0));
+        assert!(times[0].1 < 0.5 && times[1].1 >= 0.5);
+    }
+
+    #[test]
+    fn test_easing() {
+        for &easing in &[Easing::Linear, Easing::EaseIn, Easing::EaseOut, Easing::EaseInOut] {
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert_eq!(easing.apply(1.0), 1.0);
+        }
+
+        assert_eq!(Easing::EaseIn.apply(0.5), 0.25);
+        assert_eq!(Easing::EaseOut.apply(0.5), 0.75);
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+        assert_eq!(Easing::Step.apply(0.99), 0.0);
+    }
+
+    #[test]
+    fn test_track() {
+        let linear = |time, value| Keyframe { time, value, easing: Easing::Linear };
+        let mut track = Track { keyframes: vec![linear(10.0, Vector3::zeros()), linear(20.0, Vector3::x()),
+                                                linear(30.0, Vector3::zeros())],
+                                looped: false };
+
+        assert_eq!(Track::default().sample(0.0), None);
+        assert_eq!(track.sample(0.0), Some(Vector3::zeros()));
+        assert_eq!(track.sample(15.0), Some(Vector3::x() * 0.5));
+        assert_eq!(track.sample(22.5), Some(Vector3::x() * 0.75));
+        assert_eq!(track.sample(40.0), Some(Vector3::zeros()));
+
+        track.looped = true;
+        assert_eq!(track.sample(35.0), Some(Vector3::x() * 0.5));
+        assert_eq!(track.sample(5.0), Some(Vector3::x() * 0.5));
+    }
+
+    #[test]
+    fn test_camera_spline() {
+        let mut path = CameraSpline::new(vec![Waypoint::new([0.0, 0.0, 0.0], 0.0), Waypoint::new([1.0, 1.0, 0.0], 10.0),
+                                              Waypoint::new([2.0, 0.0, 0.0], 30.0)]);
+
+        // the spline passes through every waypoint at its time and holds the ends
+        assert_eq!(CameraSpline::new(Vec::new()).sample(0.0), None);
+        assert_eq!(path.sample(-5.0).unwrap().0, Vector3::zeros());
+        assert_eq!(path.sample(10.0).unwrap().0, Vector3::new(1.0, 1.0, 0.0));
+        assert_eq!(path.sample(50.0).unwrap().0, Vector3::new(2.0, 0.0, 0.0));
+
+        // at the top of the arc the camera moves along x, and a smooth curve rises above the straight line before it
+        let (_, direction) = path.sample(10.0).unwrap();
+        assert!((direction - Vector3::x()).norm() < 1e-12);
+        assert!(path.sample(5.0).unwrap().0.y > 0.5);
+
+        assert_eq!(path.camera(10.0).unwrap().center, Vector3::zeros());
+        path.orientation = Orientation::Tangent;
+        assert_eq!(path.camera(10.0).unwrap().center, Vector3::new(2.0, 1.0, 0.0));
+
+        // a Bezier handle pulls the curve towards it, and waypoints without one are left as they were
+        path.interpolation = Interpolation::Bezier;
+        path.waypoints[1].handle = Some([1.0, 3.0, 0.0]);
+        assert_eq!(path.sample(10.0).unwrap().1, Vector3::y());
+        assert!(path.sample(20.0).unwrap().0.y > path.sample(5.0).unwrap().0.y);
+
+        let parsed: CameraSpline = serde_json::from_str(r#"{"waypoints": [{"position": [0, 0, 5], "time": 0},
+                                                                           {"position": [5, 0, 0], "time": 24}],
+                                                            "orientation": {"mode": "look_at", "target": [0, 1, 0]}}"#)
+                                              .unwrap();
+        assert_eq!(parsed.interpolation, Interpolation::CatmullRom);
+        assert_eq!(parsed.camera(0.0).unwrap(), Camera::new(Vector3::new(0.0, 0.0, 5.0), Vector3::y(), Vector3::y()));
+
+        let mut animation = Animation::new(1, 8, 8);
+        animation.eye.keyframes = vec![Keyframe::new(0.0, Vector3::x())];
+        animation.path = Some(parsed);
+
+        let mut camera = Camera::default();
+        animation.evaluate(24.0, &mut Scene::new(), &mut camera);
+        assert_eq!(camera.eye, Vector3::new(5.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_object_tracks() {
+        let mut tracks = ObjectTracks::new(0);
+        assert_eq!(tracks.transform(5.0), Matrix4::identity());
+
+        tracks.position.keyframes = vec![Keyframe::new(0.0, Vector3::zeros()), Keyframe::new(10.0, Vector3::y())];
+        tracks.rotation.keyframes = vec![Keyframe::new(0.0, Vector3::zeros()),
+                                         Keyframe::new(10.0, Vector3::z() * std::f64::consts::FRAC_PI_2)];
+        tracks.scale.keyframes = vec![Keyframe::new(0.0, Vector3::repeat(2.0))];
+
+        // x is scaled, turned a quarter turn onto y, and moved up
+        let point = tracks.transform(10.0).transform_point(&nalgebra::Point3::new(1.0, 0.0, 0.0));
+        assert!((point.coords - Vector3::new(0.0, 3.0, 0.0)).norm() < 1e-12);
+
+        let mut scene = Scene::new();
+        scene.objects.push(SceneObject::new(wavefront::Object::from_bytes(b"", &Default::default()).unwrap(),
+                                            image::RgbImage::new(1, 1)));
+
+        let mut animation = Animation::new(1, 8, 8);
+        animation.eye.keyframes = vec![Keyframe::new(0.0, Vector3::zeros()), Keyframe::new(10.0, Vector3::x())];
+        animation.objects.push(tracks);
+
+        let mut camera = Camera::default();
+        animation.evaluate(5.0, &mut scene, &mut camera);
+
+        assert_eq!(camera.eye, Vector3::x() * 0.5);
+        assert_eq!(camera.center, Vector3::zeros());
+        assert!((scene.objects[0].transform.column(3).y - 0.5).abs() < 1e-12);
+
+        // a keyframed object moves across the frames the animation draws
+        let square = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\nvt 0 0\nvn 0 0 1\n\
+                                                     f 1/1/1 2/1/1 3/1/1\nf 1/1/1 3/1/1 4/1/1\n",
+                                                   &wavefront::LoadOptions::default()).unwrap();
+        let mut object = SceneObject::new(square, image::RgbImage::new(1, 1));
+        object.colors = render::ColorOverride { object: Some(image::Rgb([255, 255, 255])), lit: false,
+                                                ..Default::default() };
+        scene.objects = vec![object];
+        scene.lights.push(SceneLight::new("key", Light::Directional(Vector3::z())));
+
+        let mut slide = ObjectTracks::new(0);
+        slide.position.keyframes = vec![Keyframe { time: 0.0, value: -Vector3::x(), easing: Easing::Linear },
+                                        Keyframe::new(2.0, Vector3::x())];
+
+        let mut lefts = Vec::new();
+        let mut animation = Animation::new(3, 32, 32);
+        animation.objects.push(slide);
+        animation.after_frame(|frame| {
+            let left = (0..32).find(|&x| frame.color.get_pixel(x, 16) != &image::Rgb([0, 0, 0]));
+            lefts.push(left.unwrap());
+        });
+        animation.render(&mut scene, &mut Camera::default());
+        drop(animation);
+
+        assert!(lefts[0] < lefts[1] && lefts[1] < lefts[2], "the square starts at {:?}", lefts);
+    }
+}
@@ -0,0 +1,108 @@
+use std::path::{Path, PathBuf};
+
+
+/// Where the textures, material libraries, and other assets referenced by a scene are looked for
+///
+/// A path is resolved relative to the directory of the scene file first and
+/// then to every search directory in order, so scenes refer to their assets
+/// with relative paths and can be moved between machines. Absolute paths
+/// that do not exist, such as those written on another machine, are looked
+/// for by their file name alone.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate rastermind;
+/// # use rastermind::assets::SearchPaths;
+/// # use rastermind::texture;
+/// # fn main() -> Result<(), rastermind::Error> {
+/// let paths = SearchPaths::for_scene("scenes/head.obj").with_directory("/usr/share/textures");
+/// let texture = texture::open(paths.resolve("textures/diffuse.png").unwrap().to_str().unwrap());
+/// # Ok(())
+/// # }
+/// ```
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchPaths {
+    pub base: Option<PathBuf>,
+    pub directories: Vec<PathBuf>
+}
+
+
+impl SearchPaths {
+    /// Create new SearchPaths that resolve paths relative to the directory of the given scene file
+    pub fn for_scene<P: AsRef<Path>>(scene: P) -> SearchPaths {
+        let base = scene.as_ref().parent().map(|parent| parent.to_path_buf());
+
+        SearchPaths { base, directories: Vec::new() }
+    }
+
+    /// Add a directory to look for assets in after those already given
+    pub fn with_directory<P: AsRef<Path>>(mut self, directory: P) -> SearchPaths {
+        self.directories.push(directory.as_ref().to_path_buf());
+        self
+    }
+
+    /// Find the file the given path refers to, or None if it is not in any of the searched directories
+    ///
+    /// Paths written with backslashes on Windows are also found on other
+    /// systems.
+    ///
+    pub fn resolve<P: AsRef<Path>>(&self, path: P) -> Option<PathBuf> {
+        let path = path.as_ref();
+        let portable = PathBuf::from(path.to_string_lossy().replace('\\', "/"));
+
+        if portable.is_absolute() && portable.is_file() {
+            return Some(portable);
+        }
+
+        let directories = self.base.iter().chain(&self.directories);
+
+        if !portable.is_absolute() {
+            if let Some(found) = directories.clone().map(|directory| directory.join(&portable)).find(|p| p.is_file()) {
+                return Some(found);
+            }
+        }
+
+        let name = portable.file_name()?;
+
+        directories.map(|directory| directory.join(name)).find(|candidate| candidate.is_file())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_resolve() {
+        let mut root = env::temp_dir();
+        root.push("morpheus_assets");
+
+        let (scenes, textures, shared) = (root.join("scenes"), root.join("scenes/textures"), root.join("shared"));
+        fs::create_dir_all(&textures).unwrap();
+        fs::create_dir_all(&shared).unwrap();
+
+        fs::write(textures.join("diffuse.png"), b"").unwrap();
+        fs::write(shared.join("normal.png"), b"").unwrap();
+        fs::write(shared.join("diffuse.png"), b"").unwrap();
+
+        let paths = SearchPaths::for_scene(scenes.join("head.obj")).with_directory(&shared);
+
+        // the directory of the scene comes before the search directories
+        assert_eq!(paths.resolve("textures/diffuse.png"), Some(textures.join("diffuse.png")));
+        assert_eq!(paths.resolve("normal.png"), Some(shared.join("normal.png")));
+        assert_eq!(paths.resolve("textures\\diffuse.png"), Some(textures.join("diffuse.png")));
+
+        // absolute paths from another machine are found by their file name
+        assert_eq!(paths.resolve("/home/artist/project/normal.png"), Some(shared.join("normal.png")));
+        assert_eq!(paths.resolve(shared.join("diffuse.png")), Some(shared.join("diffuse.png")));
+
+        assert_eq!(paths.resolve("missing.png"), None);
+        assert_eq!(SearchPaths::default().resolve("normal.png"), None);
+    }
+}
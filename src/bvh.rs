@@ -0,0 +1,249 @@
+use nalgebra::Vector3;
+
+use wavefront;
+
+
+/// The most faces a leaf of the hierarchy holds before it is split
+const LEAF_SIZE: usize = 4;
+
+
+/// How close to its origin a hit is ignored, so rays that leave a surface do not hit it again
+pub const RAY_OFFSET: f64 = 1e-6;
+
+
+/// A box of the hierarchy around a range of faces
+///
+/// Leaves hold the faces from the first to the first plus the count, and
+/// the other nodes hold no faces and have their two children at the first
+/// and the one after it.
+///
+#[derive(Clone, Debug)]
+struct Node {
+    minimum: Vector3<f64>,
+    maximum: Vector3<f64>,
+    first: usize,
+    count: usize
+}
+
+
+/// The nearest face hit by a ray, with the distance along the ray and the barycentric coordinate of the hit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    pub face: usize,
+    pub distance: f64,
+    pub coordinate: Vector3<f64>
+}
+
+
+/// A bounding volume hierarchy over the faces of an Object for casting rays against it
+///
+/// The faces are split in half along the longest axis of their centers
+/// until at most a few are left in every box, so a ray only tests the faces
+/// of the boxes it passes through instead of every face. The hierarchy
+/// keeps its own copy of the triangles, so it does not borrow the Object.
+///
+/// # Examples
+///
+/// ```ignore
+/// let bvh = Bvh::new(&coordinates);
+///
+/// if let Some(hit) = bvh.intersect(&origin, &direction, f64::INFINITY) {
+///     println!("hit face {} after {}", hit.face, hit.distance);
+/// }
+/// ```
+///
+pub struct Bvh {
+    nodes: Vec<Node>,
+    faces: Vec<usize>,
+    triangles: Vec<[Vector3<f64>; 3]>
+}
+
+
+impl Bvh {
+    /// Build a new Bvh over every face of the given Object
+    pub fn new(coordinates: &wavefront::Object) -> Bvh {
+        let triangles: Vec<[Vector3<f64>; 3]> = coordinates.geometric_faces.iter().map(|face| {
+            [0, 1, 2].map(|i| coordinates.geometric_vertices[face[i] as usize])
+        }).collect();
+
+        let mut bvh = Bvh { nodes: Vec::new(), faces: (0..triangles.len()).collect(), triangles };
+
+        if !bvh.faces.is_empty() {
+            bvh.nodes.push(Node { minimum: Vector3::zeros(), maximum: Vector3::zeros(), first: 0, count: 0 });
+            bvh.build(0, 0, bvh.faces.len());
+        }
+
+        bvh
+    }
+
+    /// Fit the given node around the given range of faces and split it until its leaves are small enough
+    fn build(&mut self, node: usize, start: usize, end: usize) {
+        let mut minimum = Vector3::repeat(f64::INFINITY);
+        let mut maximum = Vector3::repeat(f64::NEG_INFINITY);
+        let mut lowest = Vector3::repeat(f64::INFINITY);
+        let mut highest = Vector3::repeat(f64::NEG_INFINITY);
+
+        for &face in &self.faces[start..end] {
+            let triangle = &self.triangles[face];
+            let center = (triangle[0] + triangle[1] + triangle[2]) / 3.0;
+
+            triangle.iter().for_each(|vertex| {
+                minimum = minimum.zip_map(vertex, f64::min);
+                maximum = maximum.zip_map(vertex, f64::max);
+            });
+            lowest = lowest.zip_map(&center, f64::min);
+            highest = highest.zip_map(&center, f64::max);
+        }
+
+        self.nodes[node] = Node { minimum, maximum, first: start, count: end - start };
+
+        let extent = highest - lowest;
+        let axis = extent.imax();
+
+        // faces whose centers all coincide cannot be split apart
+        if end - start <= LEAF_SIZE || extent[axis] <= 0.0 {
+            return;
+        }
+
+        let middle = (start + end) / 2;
+        let triangles = &self.triangles;
+        let center = |face: &usize| triangles[*face].iter().map(|vertex| vertex[axis]).sum::<f64>();
+
+        self.faces[start..end].select_nth_unstable_by(middle - start, |a, b| center(a).total_cmp(&center(b)));
+
+        let left = self.nodes.len();
+        let empty = Node { minimum: Vector3::zeros(), maximum: Vector3::zeros(), first: 0, count: 0 };
+
+        self.nodes.push(empty.clone());
+        self.nodes.push(empty);
+        self.nodes[node].first = left;
+        self.nodes[node].count = 0;
+
+        self.build(left, start, middle);
+        self.build(left + 1, middle, end);
+    }
+
+    /// Find where a ray first hits a face closer than the given distance, ignoring hits at the origin
+    pub fn intersect(&self, origin: &Vector3<f64>, direction: &Vector3<f64>, distance: f64) -> Option<Hit> {
+        let inverse = direction.map(|component| 1.0 / component);
+        let mut nearest: Option<Hit> = None;
+        let mut stack = if self.nodes.is_empty() { Vec::new() } else { vec![0] };
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            let limit = nearest.map_or(distance, |hit| hit.distance);
+
+            if !hits_box(&node.minimum, &node.maximum, origin, &inverse, limit) {
+                continue;
+            }
+
+            if node.count == 0 {
+                stack.push(node.first);
+                stack.push(node.first + 1);
+                continue;
+            }
+
+            for &face in &self.faces[node.first..node.first + node.count] {
+                if let Some((distance, coordinate)) = intersect_triangle(&self.triangles[face], origin, direction) {
+                    if distance < nearest.map_or(limit, |hit| hit.distance) {
+                        nearest = Some(Hit { face, distance, coordinate });
+                    }
+                }
+            }
+        }
+
+        nearest
+    }
+
+    /// Whether a ray hits any face closer than the given distance
+    pub fn occluded(&self, origin: &Vector3<f64>, direction: &Vector3<f64>, distance: f64) -> bool {
+        self.intersect(origin, direction, distance).is_some()
+    }
+}
+
+
+/// Whether a ray passes through a box before the given distance, with the slab test
+fn hits_box(minimum: &Vector3<f64>, maximum: &Vector3<f64>, origin: &Vector3<f64>, inverse: &Vector3<f64>,
+            distance: f64) -> bool {
+
+    let (mut near, mut far) = (0.0f64, distance);
+
+    for axis in 0..3 {
+        let a = (minimum[axis] - origin[axis]) * inverse[axis];
+        let b = (maximum[axis] - origin[axis]) * inverse[axis];
+
+        // a ray along the face of a box multiplies zero by infinity, which leaves the slab unbounded
+        if a.is_nan() || b.is_nan() {
+            continue;
+        }
+
+        near = near.max(a.min(b));
+        far = far.min(a.max(b));
+    }
+
+    near <= far
+}
+
+
+/// Find where a ray hits a triangle with the Möller–Trumbore test, as the distance and the barycentric coordinate
+pub fn intersect_triangle(triangle: &[Vector3<f64>; 3], origin: &Vector3<f64>,
+                          direction: &Vector3<f64>) -> Option<(f64, Vector3<f64>)> {
+
+    let a = triangle[0];
+    let (ab, ac) = (triangle[1] - a, triangle[2] - a);
+
+    let p = direction.cross(&ac);
+    let determinant = ab.dot(&p);
+
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+
+    let t = origin - a;
+    let u = t.dot(&p) / determinant;
+    let q = t.cross(&ab);
+    let v = direction.dot(&q) / determinant;
+    let distance = ac.dot(&q) / determinant;
+
+    if u < 0.0 || v < 0.0 || u + v > 1.0 || distance <= RAY_OFFSET {
+        return None;
+    }
+
+    Some((distance, Vector3::new(1.0 - u - v, u, v)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intersect() {
+        // a stack of unit squares one apart along z, so a ray down z hits the nearest
+        let rows: String = (0..20).map(|i| format!("v 0 0 {0}\nv 1 0 {0}\nv 1 1 {0}\nv 0 1 {0}\n", i)).collect();
+        let faces: String = (0..20).map(|i| {
+            let base = 4 * i + 1;
+            format!("f {0}/1/1 {1}/1/1 {2}/1/1\nf {0}/1/1 {2}/1/1 {3}/1/1\n", base, base + 1, base + 2, base + 3)
+        }).collect();
+        let data = format!("{}vt 0 0\nvn 0 0 1\n{}", rows, faces);
+        let coordinates = wavefront::Object::from_bytes(data.as_bytes(), &wavefront::LoadOptions::default())
+                                           .unwrap();
+
+        let bvh = Bvh::new(&coordinates);
+        assert!(bvh.nodes.len() > 1);
+
+        let hit = bvh.intersect(&Vector3::new(0.25, 0.5, 30.0), &-Vector3::z(), f64::INFINITY).unwrap();
+        assert!((hit.distance - 11.0).abs() < 1e-9);
+        assert_eq!(hit.face / 2, 19);
+
+        // starting between the squares finds the next one down, and a short ray finds none
+        let hit = bvh.intersect(&Vector3::new(0.75, 0.5, 4.5), &-Vector3::z(), f64::INFINITY).unwrap();
+        assert_eq!(hit.face / 2, 4);
+        assert!(!bvh.occluded(&Vector3::new(0.75, 0.5, 4.5), &-Vector3::z(), 0.4));
+
+        // rays that miss every square or point away from them hit nothing
+        assert!(bvh.intersect(&Vector3::new(2.0, 0.5, 30.0), &-Vector3::z(), f64::INFINITY).is_none());
+        assert!(bvh.intersect(&Vector3::new(0.5, 0.5, 30.0), &Vector3::z(), f64::INFINITY).is_none());
+        assert!(Bvh::new(&wavefront::Object::default()).intersect(&Vector3::zeros(), &Vector3::z(), 1.0).is_none());
+    }
+}
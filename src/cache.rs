@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::SystemTime;
+
+use error::MorpheusError;
+use texture;
+use wavefront;
+
+
+/// The textures and meshes loaded by the process, with the times their files were modified when they were loaded
+#[derive(Default)]
+struct Cache {
+    textures: HashMap<PathBuf, (SystemTime, Arc<image::RgbImage>)>,
+    meshes: HashMap<PathBuf, Vec<(wavefront::LoadOptions, SystemTime, Arc<wavefront::Object>)>>
+}
+
+
+static CACHE: OnceLock<Mutex<Cache>> = OnceLock::new();
+
+
+/// Lock the cache of the process, which stays usable even if a load panicked while it was locked
+fn cache() -> MutexGuard<'static, Cache> {
+    CACHE.get_or_init(Default::default).lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+
+/// The time the given file was last modified, or None if it cannot be read
+fn modified(filename: &str) -> Option<SystemTime> {
+    fs::metadata(filename).and_then(|metadata| metadata.modified()).ok()
+}
+
+
+/// Load a texture from the given image file, reusing the texture already loaded by the process
+///
+/// Textures are cached by their path and the time their file was modified,
+/// so a batch or an animation that draws the same texture in every frame
+/// only decodes it once, while a file that changed is loaded again. Files
+/// whose modification time cannot be read are always loaded, and textures
+/// that fail to load are not cached.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::{cache, render, wavefront};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let (center, up) = (Vector3::<f64>::zeros(), Vector3::<f64>::y());
+/// # let light_vector = Vector3::<f64>::z();
+/// # let frames = 24;
+/// # let eye = |frame: u32| Vector3::new(frame as f64 / 24.0, 0.0, 3.0);
+/// # let mut buffer = image::RgbImage::new(512, 512);
+/// for frame in 0..frames {
+///     let texture = cache::texture("diffuse.png")?;
+///     render::draw_object(&coordinates, &mut buffer, &texture, 255, &light_vector, &eye(frame), &center, &up);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn texture(filename: &str) -> Result<Arc<image::RgbImage>, MorpheusError> {
+    let modified = match modified(filename) {
+        Some(modified) => modified,
+        None => return texture::open(filename).map(Arc::new)
+    };
+
+    let mut cache = cache();
+
+    if let Some((time, texture)) = cache.textures.get(&PathBuf::from(filename)) {
+        if *time == modified {
+            return Ok(texture.clone());
+        }
+    }
+
+    let texture = Arc::new(texture::open(filename)?);
+    cache.textures.insert(PathBuf::from(filename), (modified, texture.clone()));
+
+    Ok(texture)
+}
+
+
+/// Load a mesh from the given wavefront file with the given options, reusing the mesh already loaded by the process
+///
+/// Meshes are cached like textures, and the same file loaded with different
+/// options is cached once for every set of options.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate rastermind;
+/// # use rastermind::{cache, wavefront};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// let coordinates = cache::mesh("african_head.obj", &wavefront::LoadOptions::default())?;
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn mesh(filename: &str, options: &wavefront::LoadOptions) -> Result<Arc<wavefront::Object>, MorpheusError> {
+    let modified = match modified(filename) {
+        Some(modified) => modified,
+        None => return wavefront::Object::with_options(filename, options).map(Arc::new)
+    };
+
+    let mut cache = cache();
+    let meshes = cache.meshes.entry(PathBuf::from(filename)).or_default();
+
+    // meshes loaded from an older version of the file are dropped
+    meshes.retain(|&(_, time, _)| time == modified);
+
+    if let Some((_, _, mesh)) = meshes.iter().find(|(cached, _, _)| cached == options) {
+        return Ok(mesh.clone());
+    }
+
+    let mesh = Arc::new(wavefront::Object::with_options(filename, options)?);
+    meshes.push((*options, modified, mesh.clone()));
+
+    Ok(mesh)
+}
+
+
+/// Drop every texture and mesh cached by the process
+///
+/// Textures and meshes still in use stay alive until they are dropped.
+///
+pub fn clear() {
+    let mut cache = cache();
+
+    cache.textures.clear();
+    cache.meshes.clear();
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs::File;
+    use std::io::Write;
+    use std::time::Duration;
+
+    #[test]
+    fn test_texture() {
+        let mut path = env::temp_dir();
+        path.push("morpheus_cache_texture.png");
+        let filename = path.to_str().unwrap();
+
+        image::RgbImage::from_pixel(2, 2, image::Rgb([10, 20, 30])).save(filename).unwrap();
+
+        // the second load shares the texture of the first
+        let first = texture(filename).unwrap();
+        assert!(Arc::ptr_eq(&first, &texture(filename).unwrap()));
+
+        // once the file changes it is loaded again
+        image::RgbImage::from_pixel(2, 2, image::Rgb([40, 50, 60])).save(filename).unwrap();
+        File::options().write(true).open(filename).unwrap()
+                       .set_modified(SystemTime::now() + Duration::from_secs(10)).unwrap();
+
+        let second = texture(filename).unwrap();
+        assert!(!Arc::ptr_eq(&first, &second));
+        assert_eq!(*second.get_pixel(0, 0), image::Rgb([40, 50, 60]));
+    }
+
+    #[test]
+    fn test_mesh() {
+        let mut path = env::temp_dir();
+        path.push("morpheus_cache_mesh.obj");
+        let filename = path.to_str().unwrap();
+
+        File::create(filename).unwrap().write_all(b"v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+
+        let options = wavefront::LoadOptions::default();
+        let scaled = wavefront::LoadOptions { scale: 2.0, ..Default::default() };
+
+        let first = mesh(filename, &options).unwrap();
+        assert!(Arc::ptr_eq(&first, &mesh(filename, &options).unwrap()));
+
+        // other options load the file again and are cached alongside
+        let second = mesh(filename, &scaled).unwrap();
+        assert!(!Arc::ptr_eq(&first, &second));
+        assert!(Arc::ptr_eq(&second, &mesh(filename, &scaled).unwrap()));
+        assert_eq!(second.geometric_vertices[1].x, 2.0);
+    }
+}
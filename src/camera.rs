@@ -0,0 +1,481 @@
+use nalgebra::{Matrix3, Matrix4, Vector2, Vector3};
+
+use shader;
+
+
+/// Pinhole camera intrinsics in the computer vision convention
+///
+/// The focal lengths and principal point are in pixels of an image with the
+/// given size whose origin is the top left corner with y pointing down, which
+/// is how rendered images are saved.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::camera;
+/// # let (eye, center) = (Vector3::<f64>::new(0.0, 0.0, 3.0), Vector3::<f64>::zeros());
+/// let intrinsics = camera::Intrinsics::from_render(2048, 2048, &eye, &center);
+/// let k = intrinsics.matrix();
+/// ```
+///
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Intrinsics {
+    pub fx: f64,
+    pub fy: f64,
+    pub cx: f64,
+    pub cy: f64,
+    pub width: u32,
+    pub height: u32
+}
+
+
+impl Intrinsics {
+    /// Create intrinsics from the focal lengths and principal point of a pinhole camera
+    pub fn new(fx: f64, fy: f64, cx: f64, cy: f64, width: u32, height: u32) -> Intrinsics {
+        Intrinsics { fx, fy, cx, cy, width, height }
+    }
+
+    /// Create intrinsics from the K matrix of a pinhole camera
+    pub fn from_matrix(k: &Matrix3<f64>, width: u32, height: u32) -> Intrinsics {
+        Intrinsics::new(k[(0, 0)], k[(1, 1)], k[(0, 2)], k[(1, 2)], width, height)
+    }
+
+    /// Find the effective intrinsics of an image rendered with the default viewport
+    ///
+    /// The default viewport maps the projected scene onto the center three
+    /// quarters of the image, and the projection places the image plane at the
+    /// distance between the eye and the center.
+    ///
+    pub fn from_render(width: u32, height: u32, eye: &Vector3<f64>, center: &Vector3<f64>) -> Intrinsics {
+        let view_port = shader::viewport(width / 8, height / 8, width * 3 / 4, height * 3 / 4, 1);
+        let distance = (eye - center).norm();
+
+        Intrinsics::new(view_port[(0, 0)] * distance, view_port[(1, 1)] * distance,
+                        view_port[(0, 3)], height as f64 - view_port[(1, 3)], width, height)
+    }
+
+    /// The K matrix of the intrinsics
+    pub fn matrix(&self) -> Matrix3<f64> {
+        Matrix3::new(self.fx, 0.0, self.cx,
+                     0.0, self.fy, self.cy,
+                     0.0, 0.0, 1.0)
+    }
+
+    /// Create a viewport matrix that renders with these intrinsics
+    ///
+    /// The viewport must be combined with the projection for the same eye and
+    /// center, `shader::projection(-1.0 / (eye - center).norm())`, and the image
+    /// must have the size of the intrinsics.
+    ///
+    pub fn viewport(&self, eye: &Vector3<f64>, center: &Vector3<f64>, depth: u32) -> Matrix4<f64> {
+        let distance = (eye - center).norm();
+        let mut matrix = Matrix4::identity();
+
+        matrix[(0, 0)] = self.fx / distance;
+        matrix[(1, 1)] = self.fy / distance;
+        matrix[(2, 2)] = depth as f64 / 2.0;
+
+        matrix[(0, 3)] = self.cx;
+        matrix[(1, 3)] = self.height as f64 - self.cy;
+        matrix[(2, 3)] = depth as f64 / 2.0;
+
+        matrix
+    }
+}
+
+
+/// Camera extrinsics in the computer vision convention
+///
+/// A point in world coordinates is transformed into camera coordinates, with
+/// x pointing right, y pointing down, and z pointing forward, by
+/// `rotation * point + translation`.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Extrinsics {
+    pub rotation: Matrix3<f64>,
+    pub translation: Vector3<f64>
+}
+
+
+impl Extrinsics {
+    /// Find the extrinsics of a camera at the eye looking towards the center
+    pub fn from_lookat(eye: &Vector3<f64>, center: &Vector3<f64>, up: &Vector3<f64>) -> Extrinsics {
+        let model_view = shader::lookat(eye, center, up);
+        let flip = Matrix3::from_diagonal(&Vector3::new(1.0, -1.0, -1.0));
+
+        let rotation = flip * model_view.fixed_slice::<nalgebra::U3, nalgebra::U3>(0, 0);
+        let translation = flip * (model_view.fixed_slice::<nalgebra::U3, nalgebra::U1>(0, 3) -
+                                  Vector3::new(0.0, 0.0, (eye - center).norm()));
+
+        Extrinsics { rotation, translation }
+    }
+
+    /// The 3x4 [R | t] matrix of the extrinsics as rows
+    pub fn rows(&self) -> [[f64; 4]; 3] {
+        let mut rows = [[0.0; 4]; 3];
+
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = if j < 3 { self.rotation[(i, j)] } else { self.translation[i] };
+            }
+        }
+
+        rows
+    }
+}
+
+
+/// A camera at the eye looking towards the target, which produces the matrices of a render
+///
+/// Without a field of view the camera maps the scene the way the renders of
+/// the binary always have, with the target plane filling the center three
+/// quarters of the image. A vertical field of view in degrees sets the focal
+/// length of a pinhole camera instead, which keeps the framing of the scene
+/// when the eye moves closer or further away.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::camera::Camera;
+/// let camera = Camera::builder().eye(Vector3::new(0.0, 2.0, 5.0))
+///                               .target(Vector3::new(0.0, 1.0, 0.0))
+///                               .fov(45.0)
+///                               .build();
+///
+/// let transform = camera.viewport(1024, 768) * camera.projection() * camera.model_view();
+/// ```
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    pub eye: Vector3<f64>,
+    pub target: Vector3<f64>,
+    pub up: Vector3<f64>,
+    pub fov: Option<f64>,
+    pub depth: u32
+}
+
+
+impl Default for Camera {
+    fn default() -> Camera {
+        Camera { eye: Vector3::new(0.0, 15.0, 70.0), target: Vector3::zeros(), up: Vector3::y(), fov: None,
+                 depth: 255 }
+    }
+}
+
+
+impl Camera {
+    /// Start building a camera from the default camera of the binary
+    pub fn builder() -> CameraBuilder {
+        CameraBuilder::default()
+    }
+
+    /// The matrix that moves the scene in front of the camera
+    pub fn model_view(&self) -> Matrix4<f64> {
+        shader::lookat(&self.eye, &self.target, &self.up)
+    }
+
+    /// The perspective projection with the image plane at the target
+    pub fn projection(&self) -> Matrix4<f64> {
+        shader::projection(-1.0 / (self.eye - self.target).norm())
+    }
+
+    /// The intrinsics of the camera for an image of the given size
+    pub fn intrinsics(&self, width: u32, height: u32) -> Intrinsics {
+        match self.fov {
+            Some(fov) => {
+                let focal = height as f64 / 2.0 / (fov.to_radians() / 2.0).tan();
+
+                Intrinsics::new(focal, focal, width as f64 / 2.0, height as f64 / 2.0, width, height)
+            },
+            None => Intrinsics::from_render(width, height, &self.eye, &self.target)
+        }
+    }
+
+    /// The viewport that maps the projected scene onto an image of the given size
+    pub fn viewport(&self, width: u32, height: u32) -> Matrix4<f64> {
+        match self.fov {
+            Some(_) => self.intrinsics(width, height).viewport(&self.eye, &self.target, self.depth),
+            None => shader::viewport(width / 8, height / 8, width * 3 / 4, height * 3 / 4, self.depth)
+        }
+    }
+}
+
+
+/// Set the parameters of a camera one at a time
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CameraBuilder {
+    camera: Camera
+}
+
+
+impl CameraBuilder {
+    pub fn eye(mut self, eye: Vector3<f64>) -> CameraBuilder {
+        self.camera.eye = eye;
+        self
+    }
+
+    pub fn target(mut self, target: Vector3<f64>) -> CameraBuilder {
+        self.camera.target = target;
+        self
+    }
+
+    pub fn up(mut self, up: Vector3<f64>) -> CameraBuilder {
+        self.camera.up = up;
+        self
+    }
+
+    /// Set the vertical field of view in degrees
+    pub fn fov(mut self, fov: f64) -> CameraBuilder {
+        self.camera.fov = Some(fov);
+        self
+    }
+
+    /// Set the range of the depth the viewport maps the scene to
+    pub fn depth(mut self, depth: u32) -> CameraBuilder {
+        self.camera.depth = depth;
+        self
+    }
+
+    pub fn build(self) -> Camera {
+        self.camera
+    }
+}
+
+
+/// Brown-Conrady lens distortion with radial and tangential coefficients
+///
+/// The coefficients follow the OpenCV convention and act on normalized image
+/// coordinates, (u - cx) / fx and (v - cy) / fy, so calibrations of real
+/// cameras can be used directly.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::camera;
+/// # let (eye, center) = (Vector3::<f64>::new(0.0, 0.0, 3.0), Vector3::<f64>::zeros());
+/// # let intrinsics = camera::Intrinsics::from_render(64, 64, &eye, &center);
+/// # let buffer = image::RgbImage::new(64, 64);
+/// let distortion = camera::Distortion::new(-0.28, 0.07, 0.0002, 0.00002, 0.0);
+/// let distorted = distortion.distort_image(&buffer, &intrinsics);
+/// ```
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Distortion {
+    pub k1: f64,
+    pub k2: f64,
+    pub p1: f64,
+    pub p2: f64,
+    pub k3: f64
+}
+
+
+impl Distortion {
+    /// Create a distortion from coefficients in the OpenCV order k1, k2, p1, p2, k3
+    pub fn new(k1: f64, k2: f64, p1: f64, p2: f64, k3: f64) -> Distortion {
+        Distortion { k1, k2, p1, p2, k3 }
+    }
+
+    /// Move a point in normalized image coordinates to where the lens images it
+    pub fn distort(&self, point: &Vector2<f64>) -> Vector2<f64> {
+        let (x, y) = (point.x, point.y);
+        let r2 = x * x + y * y;
+        let radial = 1.0 + r2 * (self.k1 + r2 * (self.k2 + r2 * self.k3));
+
+        Vector2::new(x * radial + 2.0 * self.p1 * x * y + self.p2 * (r2 + 2.0 * x * x),
+                     y * radial + self.p1 * (r2 + 2.0 * y * y) + 2.0 * self.p2 * x * y)
+    }
+
+    /// Find the point in normalized image coordinates that the lens moves to the given point
+    ///
+    /// The inverse has no closed form, so it is found by fixed point iteration,
+    /// which converges for the moderate distortion of real lenses.
+    ///
+    pub fn undistort(&self, point: &Vector2<f64>) -> Vector2<f64> {
+        let mut undistorted = *point;
+
+        for _ in 0..20 {
+            undistorted += point - self.distort(&undistorted);
+        }
+
+        undistorted
+    }
+
+    /// Find the pixel of an undistorted image that each pixel of the distorted image shows
+    ///
+    /// Both images are stored as render buffers with the first row at the
+    /// bottom. Pixels that show nothing of the undistorted image are None.
+    ///
+    pub fn remap(&self, intrinsics: &Intrinsics) -> Vec<Option<usize>> {
+        let (width, height) = (intrinsics.width as usize, intrinsics.height as usize);
+        let mut map = Vec::with_capacity(width * height);
+
+        for row in 0..height {
+            for column in 0..width {
+                let u = column as f64 + 0.5;
+                let v = height as f64 - (row as f64 + 0.5);
+                let point = self.undistort(&Vector2::new((u - intrinsics.cx) / intrinsics.fx,
+                                                         (v - intrinsics.cy) / intrinsics.fy));
+
+                let x = (point.x * intrinsics.fx + intrinsics.cx).floor();
+                let y = (height as f64 - (point.y * intrinsics.fy + intrinsics.cy)).floor();
+
+                if x >= 0.0 && y >= 0.0 && x < width as f64 && y < height as f64 {
+                    map.push(Some(y as usize * width + x as usize));
+                } else {
+                    map.push(None);
+                }
+            }
+        }
+
+        map
+    }
+
+    /// Warp a render buffer taken with the given intrinsics as if it was seen through the lens
+    ///
+    /// Pixels are copied from the nearest pixel of the undistorted buffer so that
+    /// label images such as segmentation ids are never blended. Pixels that show
+    /// nothing of the undistorted buffer are black.
+    ///
+    pub fn distort_image(&self, buffer: &image::RgbImage, intrinsics: &Intrinsics) -> image::RgbImage {
+        remap_image(&self.remap(intrinsics), buffer)
+    }
+}
+
+
+/// Rearrange the pixels of a render buffer with a map from `Distortion::remap`
+pub fn remap_image(map: &[Option<usize>], buffer: &image::RgbImage) -> image::RgbImage {
+    let pixels: Vec<image::Rgb<u8>> = buffer.pixels().cloned().collect();
+    let remapped = remap_values(map, &pixels, image::Rgb([0, 0, 0]));
+
+    image::ImageBuffer::from_fn(buffer.width(), buffer.height(), |x, y| {
+        remapped[(y * buffer.width() + x) as usize]
+    })
+}
+
+
+/// Rearrange values such as depths with a map from `Distortion::remap`
+pub fn remap_values<T: Copy>(map: &[Option<usize>], values: &[T], empty: T) -> Vec<T> {
+    map.iter().map(|index| index.map_or(empty, |index| values[index])).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector4;
+    use super::*;
+    use vector;
+
+    fn project(intrinsics: &Intrinsics, extrinsics: &Extrinsics, point: &Vector3<f64>) -> (f64, f64) {
+        let camera = extrinsics.rotation * point + extrinsics.translation;
+        let pixel = intrinsics.matrix() * camera / camera.z;
+
+        (pixel.x, pixel.y)
+    }
+
+    #[test]
+    fn test_from_render_matches_pipeline() {
+        let (width, height) = (800, 600);
+        let eye = Vector3::new(1.0, 2.0, 5.0);
+        let center = Vector3::new(0.0, 0.5, 0.0);
+        let up = Vector3::y();
+        let point = Vector3::new(0.3, -0.2, 0.4);
+
+        let transform = shader::viewport(width / 8, height / 8, width * 3 / 4, height * 3 / 4, 255) *
+                        shader::projection(-1.0 / (eye - center).norm()) *
+                        shader::lookat(&eye, &center, &up);
+        let screen = vector::project_to_3d(&(transform * Vector4::new(point.x, point.y, point.z, 1.0)));
+
+        let intrinsics = Intrinsics::from_render(width, height, &eye, &center);
+        let (u, v) = project(&intrinsics, &Extrinsics::from_lookat(&eye, &center, &up), &point);
+
+        assert!((u - screen.x).abs() < 1e-9);
+        assert!((v - (height as f64 - screen.y)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_viewport_from_intrinsics() {
+        let eye = Vector3::new(0.0, 0.0, 4.0);
+        let center = Vector3::zeros();
+        let intrinsics = Intrinsics::new(500.0, 450.0, 310.0, 250.0, 640, 480);
+        let point = Vector3::new(0.5, 0.25, -1.0);
+
+        let transform = intrinsics.viewport(&eye, &center, 255) *
+                        shader::projection(-1.0 / (eye - center).norm()) *
+                        shader::lookat(&eye, &center, &Vector3::y());
+        let screen = vector::project_to_3d(&(transform * Vector4::new(point.x, point.y, point.z, 1.0)));
+
+        let (u, v) = project(&intrinsics, &Extrinsics::from_lookat(&eye, &center, &Vector3::y()), &point);
+
+        assert!((u - screen.x).abs() < 1e-9);
+        assert!((v - (480.0 - screen.y)).abs() < 1e-9);
+        assert_eq!(Intrinsics::from_matrix(&intrinsics.matrix(), 640, 480), intrinsics);
+    }
+
+    #[test]
+    fn test_camera() {
+        let default = Camera::builder().build();
+        assert_eq!(default, Camera::default());
+        assert_eq!(default.viewport(800, 600), shader::viewport(100, 75, 600, 450, 255));
+
+        let eye = Vector3::new(1.0, 2.0, 5.0);
+        let camera = Camera::builder().eye(eye).target(Vector3::new(0.0, 0.5, 0.0)).up(Vector3::z()).fov(60.0)
+                                      .depth(1000).build();
+        assert_eq!(camera.model_view(), shader::lookat(&eye, &Vector3::new(0.0, 0.5, 0.0), &Vector3::z()));
+
+        // a point on the edge of the field of view lands on the edge of the image
+        let edge = Vector3::new(0.0, (30.0f64).to_radians().tan() * 4.0, -4.0);
+        let camera = Camera::builder().eye(Vector3::zeros()).target(-Vector3::z()).fov(60.0).build();
+        let transform = camera.viewport(640, 480) * camera.projection() * camera.model_view();
+        let screen = vector::project_to_3d(&(transform * Vector4::new(edge.x, edge.y, edge.z, 1.0)));
+
+        assert!((screen.x - 320.0).abs() < 1e-9);
+        assert!((screen.y - 480.0).abs() < 1e-9);
+
+        // and keeps landing there as the eye moves away
+        let further = Camera { target: -Vector3::z() * 3.0, ..camera };
+        let transform = further.viewport(640, 480) * further.projection() * further.model_view();
+        let screen = vector::project_to_3d(&(transform * Vector4::new(edge.x, edge.y, edge.z, 1.0)));
+        assert!((screen.y - 480.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_undistort() {
+        let distortion = Distortion::new(-0.28, 0.07, 0.001, -0.0005, 0.01);
+        let point = Vector2::new(0.3, -0.2);
+
+        let distorted = distortion.distort(&point);
+
+        assert!((distorted - point).norm() > 1e-3);
+        assert!((distortion.undistort(&distorted) - point).norm() < 1e-9);
+    }
+
+    #[test]
+    fn test_remap() {
+        let intrinsics = Intrinsics::new(40.0, 40.0, 16.0, 12.0, 32, 24);
+
+        let identity = Distortion::default().remap(&intrinsics);
+        assert!(identity.iter().enumerate().all(|(index, &source)| source == Some(index)));
+
+        // barrel distortion shows more than the render covers in the corners
+        let barrel = Distortion::new(-0.3, 0.0, 0.0, 0.0, 0.0).remap(&intrinsics);
+        let center = 12 * 32 + 16;
+        assert_eq!(barrel[center], Some(center));
+        assert_eq!(barrel[0], None);
+
+        // while pincushion distortion only shows part of it
+        let pincushion = Distortion::new(0.3, 0.0, 0.0, 0.0, 0.0).remap(&intrinsics);
+        assert!(pincushion.iter().all(|source| source.is_some()));
+        assert_ne!(pincushion[0], Some(0));
+    }
+}
@@ -0,0 +1,582 @@
+use clap::{App, AppSettings, Arg, ArgMatches, Error, ErrorKind, SubCommand};
+use nalgebra::Vector3;
+
+use camera::Camera;
+use lines;
+use plugins;
+use render;
+
+
+/// The options of the binary, shared by all its modes, and the mode it runs
+///
+/// Without a subcommand the binary renders a single model, as described by
+/// RenderOptions. The other modes, such as scenes, turntables, and terminal
+/// previews, are subcommands, and the global flags can be given before or
+/// after them. Invalid arguments and --help return an error describing the
+/// usage, which is printed with exit.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate rastermind;
+/// # use std::env;
+/// # use rastermind::cli;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let options = cli::Options::from_args(env::args())?;
+///
+/// if let cli::Command::Render(ref render) = options.command {
+///     let camera = render.camera();
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    pub backend: render::Backend,
+    pub depth: render::DepthOptions,
+    pub plugins: Vec<String>,
+    pub assignments: Vec<String>,
+    pub command: Command
+}
+
+
+/// A mode of the binary with its arguments
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    /// Render a single model, the mode without a subcommand
+    Render(RenderOptions),
+    /// Render the JSON request of the render service into a PNG file
+    Request { request: String, output: String },
+    /// Render every combination of the values of one or two parameters of a request into a labelled sheet
+    Sweep { output: String, request: String, axes: Vec<String> },
+    /// Serve render requests over stdio
+    Serve,
+    /// Render a labelled dataset of random views of a model
+    Dataset { model: String, texture: String, output: String, frames: Option<usize> },
+    /// Draw the edges of a model into an SVG file
+    Svg { model: String, output: String, hidden_lines: lines::HiddenLines },
+    /// Draw the edges of a model into an image
+    HiddenLine { model: String, output: String, hidden_lines: lines::HiddenLines },
+    /// Render the frames and the optical flow of a model turning once
+    Turntable { model: String, texture: String, output: String, frames: usize, samples: usize },
+    /// Render a camera path recorded in the preview
+    CameraPath { model: String, texture: String, path: String, output: String, fps: f64 },
+    /// Write a model out in another format
+    Export { model: String, output: String, normalize: bool },
+    /// Render a scene exported from Blender or Assimp from its active camera
+    Import { scene: String, output: String, width: u32, height: u32 },
+    /// Render a scene description
+    Scene { scene: String, output: String },
+    /// Record every fragment of a scene description in a deep OpenEXR file
+    DeepExr { scene: String, output: String },
+    /// Render a model over a photo with the intrinsics of its camera
+    Backdrop { model: String, texture: String, photo: String, intrinsics: [f64; 4], output: String },
+    /// Render a model framed and lit by three point lighting
+    Studio { model: String, texture: String, output: String },
+    /// Ray trace a model and report how far the rasterized render is from it
+    Reference { model: String, texture: String, output: String, samples: u32 },
+    /// Render text laid out with a font
+    Text { font: String, text: String, output: String },
+    /// Render a model as the lessons of the Tiny Renderer do
+    TinyRenderer { model: String, texture: Option<String> },
+    /// Orbit a model in the terminal, then keep drawing it as a parameters file changes
+    Preview { model: String, texture: String, frames: usize, parameters: Option<String> },
+    /// Let other tools drive the preview over stdio or TCP
+    Rpc { model: String, texture: String, address: Option<String> },
+    /// Print a model in the terminal as ansi, ascii, sixel, kitty, or iterm output
+    Terminal { output: String, model: String, texture: String, columns: u32 }
+}
+
+
+/// The names and descriptions of the subcommands that print a model in the terminal
+const TERMINAL_OUTPUTS: [(&str, &str); 5] = [
+    ("ansi", "Prints a model in the terminal with ANSI colors"),
+    ("ascii", "Prints a model in the terminal as ASCII art"),
+    ("sixel", "Prints a model in the terminal as sixel graphics"),
+    ("kitty", "Prints a model in the terminal with the kitty graphics protocol"),
+    ("iterm", "Prints a model in the terminal with the iTerm2 inline image protocol")
+];
+
+
+impl Options {
+    /// Parse the options from the arguments of the binary, including its name
+    ///
+    /// The shader of a render is only checked once the plugins are loaded,
+    /// with RenderOptions::check_shader.
+    ///
+    pub fn from_args<I, T>(args: I) -> Result<Options, Error>
+        where I: IntoIterator<Item = T>, T: Into<std::ffi::OsString> + Clone {
+
+        let matches = app().get_matches_from_safe(args)?;
+
+        // the global flags given after a subcommand are only in the matches of the subcommand
+        let global = match matches.subcommand() {
+            (_, Some(subcommand)) => subcommand,
+            _ => &matches
+        };
+
+        let name = global.value_of("backend").unwrap_or("cpu");
+        let backend = render::Backend::parse(name).unwrap_or(render::Backend::Cpu);
+
+        if !backend.is_available() {
+            return Err(Error::with_description(&format!("the {} backend is not available in this build", name),
+                                               ErrorKind::InvalidValue));
+        }
+
+        let depth = render::DepthOptions { check_non_finite: global.is_present("check-nan"),
+                                           merge_micro_triangles: global.is_present("merge-micro-triangles"),
+                                           ..Default::default() };
+        let values = |name: &str| global.values_of(name).map_or(Vec::new(), |values| values.map(String::from)
+                                                                                           .collect());
+
+        Ok(Options { backend, depth, plugins: values("plugin"), assignments: values("set"),
+                     command: Command::from_matches(&matches)? })
+    }
+}
+
+
+impl Command {
+    /// The mode of the given matches, and the arguments of its subcommand
+    fn from_matches(matches: &ArgMatches) -> Result<Command, Error> {
+        let (name, arguments) = match matches.subcommand() {
+            (name, Some(arguments)) => (name, arguments),
+            _ => return RenderOptions::from_matches(matches).map(Command::Render)
+        };
+
+        let value = |name: &str| arguments.value_of(name).unwrap_or_default().to_string();
+        let optional = |name: &str| arguments.value_of(name).map(String::from);
+        let number = |name: &str, default: &str| arguments.value_of(name).unwrap_or(default).to_string();
+        let hidden_lines = match arguments.value_of("hidden-lines") {
+            Some("drawn") => lines::HiddenLines::Drawn,
+            Some("dashed") => lines::HiddenLines::Dashed,
+            _ => lines::HiddenLines::Removed
+        };
+
+        Ok(match name {
+            "render" => Command::Request { request: value("request"), output: value("output") },
+            "sweep" => Command::Sweep { output: value("output"), request: value("request"),
+                                        axes: arguments.values_of("axes").unwrap_or_default().map(String::from)
+                                                       .collect() },
+            "serve" => Command::Serve,
+            "dataset" => Command::Dataset {
+                model: value("model"), texture: value("texture"), output: value("output"),
+                frames: arguments.value_of("frames").map(|frames| parse_number(frames, "frames")).transpose()?
+            },
+            "svg" => Command::Svg { model: value("model"), output: value("output"), hidden_lines },
+            "hidden-line" => Command::HiddenLine { model: value("model"), output: value("output"), hidden_lines },
+            "turntable" => Command::Turntable {
+                model: value("model"), texture: value("texture"), output: value("output"),
+                frames: parse_number(&number("frames", "36"), "frames")?,
+                samples: parse_number(&number("samples", "1"), "samples")?
+            },
+            "camera-path" => Command::CameraPath {
+                model: value("model"), texture: value("texture"), path: value("path"), output: value("output"),
+                fps: parse_number(&number("fps", "24"), "fps")?
+            },
+            "export" => Command::Export { model: value("model"), output: value("output"),
+                                          normalize: arguments.is_present("normalize") },
+            "import" => {
+                let width = parse_number(&number("width", "2048"), "width")?;
+                let height = arguments.value_of("height").map_or(Ok(width), |height| parse_number(height, "height"))?;
+
+                Command::Import { scene: value("scene"), output: value("output"), width, height }
+            }
+            "scene" => Command::Scene { scene: value("scene"), output: value("output") },
+            "deep-exr" => Command::DeepExr { scene: value("scene"), output: value("output") },
+            "backdrop" => {
+                let mut intrinsics = [0.0; 4];
+
+                for (intrinsic, name) in intrinsics.iter_mut().zip(&["fx", "fy", "cx", "cy"]) {
+                    *intrinsic = parse_number(&value(name), name)?;
+                }
+
+                Command::Backdrop { model: value("model"), texture: value("texture"), photo: value("photo"),
+                                    intrinsics, output: value("output") }
+            }
+            "studio" => Command::Studio { model: value("model"), texture: value("texture"), output: value("output") },
+            "reference" => Command::Reference {
+                model: value("model"), texture: value("texture"), output: value("output"),
+                samples: parse_number(&number("samples", "16"), "samples")?
+            },
+            "text" => Command::Text { font: value("font"), text: value("text"), output: value("output") },
+            "tinyrenderer" => Command::TinyRenderer { model: value("model"), texture: optional("texture") },
+            "preview" => Command::Preview {
+                model: value("model"), texture: value("texture"),
+                frames: parse_number(&number("frames", "120"), "frames")?, parameters: optional("parameters")
+            },
+            "rpc" => Command::Rpc { model: value("model"), texture: value("texture"), address: optional("address") },
+            _ => Command::Terminal { output: name.to_string(), model: value("model"), texture: value("texture"),
+                                     columns: parse_size(arguments, "columns")? }
+        })
+    }
+}
+
+
+/// The options of a render of a single model from the command line
+///
+/// The texture is given either after the model or with --texture, and the
+/// light shines from the eye unless it is given. The shader is any of the
+/// shaders registered by name, including those of plugins. The camera always looks at
+/// the origin with y up, as the other modes of the binary do. A progress
+/// path saves the partial render every few seconds while it is drawn.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate rastermind;
+/// # use std::env;
+/// # use rastermind::cli;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let options = cli::RenderOptions::from_args(env::args())?;
+/// let camera = options.camera();
+/// # Ok(())
+/// # }
+/// ```
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderOptions {
+    pub model: String,
+    pub texture: String,
+    pub width: u32,
+    pub height: u32,
+    pub output: String,
+    pub shader: String,
+    pub eye: Vector3<f64>,
+    pub light: Option<Vector3<f64>>,
+    pub progress: Option<(String, u64)>
+}
+
+
+impl RenderOptions {
+    /// Parse the options of a render from the arguments of the binary, including its name
+    ///
+    /// Arguments that select another mode of the binary are an error, and
+    /// the shader is checked against the shaders registered so far.
+    ///
+    pub fn from_args<I, T>(args: I) -> Result<RenderOptions, Error>
+        where I: IntoIterator<Item = T>, T: Into<std::ffi::OsString> + Clone {
+
+        match Options::from_args(args)?.command {
+            Command::Render(options) => options.check_shader().map(|_| options),
+            _ => Err(Error::with_description("the arguments are not those of a render", ErrorKind::InvalidSubcommand))
+        }
+    }
+
+    /// The options of the render in the given matches of the binary without a subcommand
+    fn from_matches(matches: &ArgMatches) -> Result<RenderOptions, Error> {
+        let texture = matches.value_of("texture").or_else(|| matches.value_of("texture-path")).ok_or_else(|| {
+            Error::with_description("a texture is required, either after the model or with --texture",
+                                    ErrorKind::MissingRequiredArgument)
+        })?;
+
+        let progress = match matches.values_of("progress") {
+            Some(mut values) => {
+                let path = values.next().unwrap_or_default().to_string();
+                let seconds = values.next().map_or(Ok(5), |seconds| parse_number(seconds, "--progress"))?;
+
+                Some((path, seconds))
+            },
+            None => None
+        };
+
+        let options = RenderOptions {
+            model: matches.value_of("model").unwrap_or_default().to_string(),
+            texture: texture.to_string(),
+            width: parse_size(matches, "width")?,
+            height: parse_size(matches, "height")?,
+            output: matches.value_of("output").unwrap_or("output.png").to_string(),
+            shader: matches.value_of("shader").unwrap_or("gouraud").to_string(),
+            eye: parse_vector(matches.value_of("eye").unwrap_or("0,15,70"), "--eye")?,
+            light: matches.value_of("light").map(|light| parse_vector(light, "--light")).transpose()?,
+            progress
+        };
+
+        if options.light == Some(Vector3::zeros()) {
+            return Err(Error::with_description("--light must not be zero, as it is the direction the light shines from",
+                                               ErrorKind::InvalidValue));
+        }
+
+        render::validate(options.width, options.height, &options.light_vector(), &options.eye, &Vector3::zeros(),
+                         &Vector3::y()).map_err(|error| Error::with_description(&error.to_string(),
+                                                                                ErrorKind::InvalidValue))?;
+
+        Ok(options)
+    }
+
+    /// Check that the shader of the render is registered, which plugins loaded with --plugin can add to
+    pub fn check_shader(&self) -> Result<(), Error> {
+        let (_, shaders) = plugins::registered();
+
+        if !shaders.contains(&self.shader) {
+            return Err(Error::with_description(&format!("there is no shader named '{}', the shaders are {}",
+                                                        self.shader, shaders.join(", ")),
+                                               ErrorKind::InvalidValue));
+        }
+
+        Ok(())
+    }
+
+    /// The camera at the eye looking at the origin
+    pub fn camera(&self) -> Camera {
+        Camera::builder().eye(self.eye).build()
+    }
+
+    /// The normalized direction the light shines from
+    pub fn light_vector(&self) -> Vector3<f64> {
+        let light = self.light.unwrap_or(self.eye);
+
+        light.try_normalize(0.0).unwrap_or(light)
+    }
+}
+
+
+/// A required positional argument of a subcommand
+fn positional(name: &'static str, help: &'static str) -> Arg<'static, 'static> {
+    Arg::with_name(name).help(help).required(true)
+}
+
+
+/// An optional positional argument of a subcommand
+fn optional(name: &'static str, help: &'static str) -> Arg<'static, 'static> {
+    Arg::with_name(name).help(help)
+}
+
+
+/// The arguments of the binary, with the render of a single model as the mode without a subcommand
+fn app() -> App<'static, 'static> {
+    let model = || positional("model", "the model to draw");
+    let texture = || positional("texture", "the texture of the model");
+    let image = || positional("output", "where to save the image");
+    let hidden_lines = || optional("hidden-lines", "what to do with hidden lines [default: removed]")
+                               .possible_values(&["removed", "drawn", "dashed"]);
+
+    let terminal = TERMINAL_OUTPUTS.iter().map(|&(name, about)| {
+        let columns = optional("columns", "the width in characters [default: 80]").default_value("80")
+                                                                                 .hide_default_value(true);
+
+        SubCommand::with_name(name).about(about).args(&[model(), texture(), columns])
+    });
+
+    App::new("rastermind")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Draws a wavefront model with a texture into an image")
+        // inferring subcommands keeps clap from taking a model or texture for a misspelt subcommand
+        .settings(&[AppSettings::SubcommandsNegateReqs, AppSettings::GlobalVersion,
+                    AppSettings::VersionlessSubcommands, AppSettings::InferSubcommands])
+        .arg(Arg::with_name("model").help("the wavefront OBJ file to draw").required(true))
+        .arg(Arg::with_name("texture").help("the texture of the model, instead of --texture"))
+        .arg(Arg::with_name("texture-path").long("texture").value_name("PATH")
+                                           .help("the texture of the model").conflicts_with("texture"))
+        .arg(Arg::with_name("width").long("width").value_name("PIXELS").default_value("2048")
+                                    .help("the width of the image"))
+        .arg(Arg::with_name("height").long("height").value_name("PIXELS").default_value("2048")
+                                     .help("the height of the image"))
+        .arg(Arg::with_name("output").long("output").short("o").value_name("PATH").default_value("output.png")
+                                     .help("where to save the image"))
+        .arg(Arg::with_name("shader").long("shader").value_name("NAME").default_value("gouraud")
+                                     .help("how the faces are shaded, such as gouraud, flat, cel, or normal"))
+        .arg(Arg::with_name("eye").long("eye").value_name("X,Y,Z").allow_hyphen_values(true)
+                                  .help("where the camera is, looking at the origin [default: 0,15,70]"))
+        .arg(Arg::with_name("light").long("light").value_name("X,Y,Z").allow_hyphen_values(true)
+                                    .help("the direction the light shines from [default: the eye]"))
+        .arg(Arg::with_name("progress").long("progress").value_name("PATH [SECONDS]").min_values(1).max_values(2)
+                                       .help("save the partial image every few seconds [default: 5]"))
+        .arg(Arg::with_name("backend").long("backend").value_name("NAME").possible_values(&["cpu", "gpu"])
+                                      .global(true).help("draw a render on the cpu or with compute shaders on the gpu"))
+        .arg(Arg::with_name("check-nan").long("check-nan").global(true)
+                                        .help("paint fragments shaded with NaN or infinity magenta and report them"))
+        .arg(Arg::with_name("merge-micro-triangles").long("merge-micro-triangles").global(true)
+                                                    .help("draw the triangles that miss the sample of their pixel"))
+        .arg(Arg::with_name("plugin").long("plugin").value_name("LIBRARY").multiple(true).number_of_values(1)
+                                     .global(true).help("load the loaders and shaders of a plugin library"))
+        .arg(Arg::with_name("set").long("set").value_name("PATH=VALUE").multiple(true).number_of_values(1)
+                                  .global(true).help("override a parameter of a render request"))
+        .subcommand(SubCommand::with_name("render").about("Renders a JSON render request into a PNG file")
+                                .args(&[positional("request", "the JSON request"), image()]))
+        .subcommand(SubCommand::with_name("sweep").about("Renders the combinations of one or two parameters")
+                                .args(&[image(), positional("request", "the JSON request"),
+                                        positional("axes", "a parameter and its values, such as path=1,2,3")
+                                            .multiple(true).max_values(2)]))
+        .subcommand(SubCommand::with_name("serve").about("Serves render requests over stdio"))
+        .subcommand(SubCommand::with_name("dataset").about("Renders a dataset of random views of a model")
+                                .args(&[model(), texture(), positional("output", "the directory of the dataset"),
+                                        optional("frames", "the number of views")]))
+        .subcommand(SubCommand::with_name("svg").about("Draws the edges of a model into an SVG file")
+                                .args(&[model(), positional("output", "where to save the drawing"), hidden_lines()]))
+        .subcommand(SubCommand::with_name("hidden-line").about("Draws the edges of a model into an image")
+                                .args(&[model(), image(), hidden_lines()]))
+        .subcommand(SubCommand::with_name("turntable").about("Renders the frames and flow of a model turning once")
+                                .args(&[model(), texture(), positional("output", "the directory of the frames"),
+                                        optional("frames", "the number of frames [default: 36]"),
+                                        optional("samples", "the samples of motion blur [default: 1]")]))
+        .subcommand(SubCommand::with_name("camera-path").about("Renders a camera path recorded in the preview")
+                                .args(&[model(), texture(), positional("path", "the recorded camera path"),
+                                        positional("output", "the directory of the frames"),
+                                        optional("fps", "the frames per second [default: 24]")]))
+        .subcommand(SubCommand::with_name("export").about("Writes a model out in the format of its extension")
+                                .args(&[model(), positional("output", "where to save the model")])
+                                .arg(Arg::with_name("normalize").long("normalize")
+                                                                .help("fit the model into the unit cube first")))
+        .subcommand(SubCommand::with_name("import").about("Renders a scene exported from Blender or Assimp")
+                                .args(&[positional("scene", "the exported scene"), image(),
+                                        optional("width", "the width of the image [default: 2048]"),
+                                        optional("height", "the height of the image [default: the width]")]))
+        .subcommand(SubCommand::with_name("scene").about("Renders a scene description in TOML or JSON")
+                                .args(&[positional("scene", "the scene description"), image()]))
+        .subcommand(SubCommand::with_name("deep-exr").about("Records a scene description in a deep OpenEXR file")
+                                .args(&[positional("scene", "the scene description"),
+                                        positional("output", "where to save the deep image")]))
+        .subcommand(SubCommand::with_name("backdrop").about("Renders a model over a photo")
+                                .args(&[model(), texture(), positional("photo", "the photo behind the model"),
+                                        positional("fx", "the focal length in pixels along x"),
+                                        positional("fy", "the focal length in pixels along y"),
+                                        positional("cx", "the principal point along x"),
+                                        positional("cy", "the principal point along y"), image()]))
+        .subcommand(SubCommand::with_name("studio").about("Renders a model framed and lit by three point lighting")
+                                .args(&[model(), texture(), image()]))
+        .subcommand(SubCommand::with_name("reference").about("Ray traces a model to compare with its render")
+                                .args(&[model(), texture(), image(),
+                                        optional("samples", "the samples of every pixel [default: 16]")]))
+        .subcommand(SubCommand::with_name("text").about("Renders text laid out with a font")
+                                .args(&[positional("font", "the TrueType font"), positional("text", "the text"),
+                                        image()]))
+        .subcommand(SubCommand::with_name("tinyrenderer").about("Renders a model as the Tiny Renderer lessons do")
+                                .args(&[model(), optional("texture", "the texture of the model")]))
+        .subcommand(SubCommand::with_name("preview").about("Orbits a model in the terminal")
+                                .args(&[model(), texture(),
+                                        optional("frames", "the frames of the orbit [default: 120]"),
+                                        optional("parameters", "a parameters file to watch")]))
+        .subcommand(SubCommand::with_name("rpc").about("Lets other tools drive the preview over stdio or TCP")
+                                .args(&[model(), texture(), optional("address", "the address to listen on")]))
+        .subcommands(terminal)
+}
+
+
+/// Parse a positive number of pixels for the argument of the given name
+fn parse_size(matches: &ArgMatches, name: &str) -> Result<u32, Error> {
+    let size: u32 = parse_number(matches.value_of(name).unwrap_or_default(), &format!("--{}", name))?;
+
+    if size == 0 {
+        return Err(Error::with_description(&format!("--{} must be at least one pixel", name),
+                                           ErrorKind::InvalidValue));
+    }
+
+    Ok(size)
+}
+
+
+/// Parse a number for the argument of the given name, naming it and the value when they are not a number
+fn parse_number<N: std::str::FromStr>(value: &str, flag: &str) -> Result<N, Error> {
+    value.trim().parse().map_err(|_| {
+        Error::with_description(&format!("{} expects a number, not '{}'", flag, value), ErrorKind::InvalidValue)
+    })
+}
+
+
+/// Parse a vector written as three numbers separated by commas
+fn parse_vector(value: &str, flag: &str) -> Result<Vector3<f64>, Error> {
+    let numbers = value.split(',').map(|number| number.trim().parse::<f64>().ok().filter(|number| number.is_finite()))
+                       .collect::<Option<Vec<f64>>>();
+
+    match numbers {
+        Some(ref numbers) if numbers.len() == 3 => Ok(Vector3::new(numbers[0], numbers[1], numbers[2])),
+        _ => Err(Error::with_description(&format!("{} expects three numbers such as 0,15,70, not '{}'", flag, value),
+                                         ErrorKind::InvalidValue))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_options() {
+        let options = RenderOptions::from_args(vec!["rastermind", "head.obj", "head.png"]).unwrap();
+
+        assert_eq!((options.width, options.height, options.output.as_str()), (2048, 2048, "output.png"));
+        assert_eq!((options.shader.as_str(), options.eye), ("gouraud", Vector3::new(0.0, 15.0, 70.0)));
+        assert_eq!(options.progress, None);
+        assert_eq!(options.camera(), Camera::builder().eye(Vector3::new(0.0, 15.0, 70.0)).build());
+
+        let options = RenderOptions::from_args(vec!["rastermind", "head.obj", "--texture", "head.png", "--width",
+                                                    "640", "--height", "480", "-o", "head_cel.png", "--shader", "cel",
+                                                    "--eye", "-10,0,30", "--light", "0,0,2", "--progress",
+                                                    "partial.png"]).unwrap();
+
+        assert_eq!((options.texture.as_str(), options.width, options.height), ("head.png", 640, 480));
+        assert_eq!((options.output.as_str(), options.shader.as_str()), ("head_cel.png", "cel"));
+        assert_eq!((options.eye, options.light_vector()), (Vector3::new(-10.0, 0.0, 30.0), Vector3::z()));
+        assert_eq!(options.progress, Some((String::from("partial.png"), 5)));
+    }
+
+    #[test]
+    fn test_invalid_options() {
+        let error = |args: &[&str]| {
+            let args = ["rastermind", "head.obj"].iter().chain(args).cloned().collect::<Vec<&str>>();
+            RenderOptions::from_args(args).unwrap_err()
+        };
+
+        assert!(error(&[]).message.contains("texture is required"));
+        assert!(error(&["head.png", "--width", "0"]).message.contains("at least one pixel"));
+        assert!(error(&["head.png", "--height", "tall"]).message.contains("expects a number"));
+        assert!(error(&["head.png", "--eye", "1,2"]).message.contains("three numbers"));
+        assert!(error(&["head.png", "--eye", "0,0,0"]).message.contains("same point"));
+        assert!(error(&["head.png", "--light", "0,0,0"]).message.contains("must not be zero"));
+        assert!(error(&["head.png", "--shader", "toon"]).message.contains("gouraud, normal"));
+        assert_eq!(error(&["head.png", "--texture", "other.png"]).kind, ErrorKind::ArgumentConflict);
+        assert_eq!(error(&["--help"]).kind, ErrorKind::HelpDisplayed);
+    }
+
+    #[test]
+    fn test_subcommands() {
+        let parse = |args: &[&str]| Options::from_args(["rastermind"].iter().chain(args));
+
+        let options = parse(&["scene", "head.toml", "head.png"]).unwrap();
+        assert_eq!(options.command, Command::Scene { scene: String::from("head.toml"),
+                                                     output: String::from("head.png") });
+        assert_eq!((options.backend, options.depth), (render::Backend::Cpu, render::DepthOptions::default()));
+
+        // optional arguments fall back to their defaults, and numbers are checked
+        let command = parse(&["turntable", "head.obj", "head.png", "frames"]).unwrap().command;
+        assert_eq!(command, Command::Turntable { model: String::from("head.obj"), texture: String::from("head.png"),
+                                                 output: String::from("frames"), frames: 36, samples: 1 });
+
+        let command = parse(&["import", "scene.json", "scene.png", "640"]).unwrap().command;
+        assert_eq!(command, Command::Import { scene: String::from("scene.json"), output: String::from("scene.png"),
+                                              width: 640, height: 640 });
+
+        assert!(parse(&["turntable", "head.obj", "head.png", "frames", "many"]).unwrap_err().message
+                                                                              .contains("frames expects a number"));
+        assert_eq!(parse(&["ansi", "head.obj", "head.png", "0"]).unwrap_err().kind, ErrorKind::InvalidValue);
+
+        // the global flags are read before and after the subcommand
+        let options = parse(&["--set", "width=64", "--set", "height=48", "--plugin", "libply.so", "render",
+                              "request.json", "out.png", "--check-nan"]).unwrap();
+        assert_eq!(options.assignments, vec!["width=64", "height=48"]);
+        assert_eq!(options.plugins, vec!["libply.so"]);
+        assert!(options.depth.check_non_finite && !options.depth.merge_micro_triangles);
+
+        let options = parse(&["--set", "width=64", "render", "request.json", "out.png", "--set", "shader=flat"]);
+        assert_eq!(options.unwrap().assignments, vec!["shader=flat"]);
+
+        let options = parse(&["head.obj", "head.png", "--merge-micro-triangles", "--backend", "cpu"]).unwrap();
+        assert!(options.depth.merge_micro_triangles);
+        assert!(matches!(options.command, Command::Render(ref render) if render.model == "head.obj"));
+
+        // models and textures named like a subcommand are still drawn by the default render
+        let options = parse(&["tri.obj", "tex.png", "--width", "64"]).unwrap();
+        assert!(matches!(options.command, Command::Render(ref render) if render.texture == "tex.png"));
+        assert!(matches!(parse(&["scene.obj", "tex.png"]).unwrap().command, Command::Render(_)));
+
+        // typos, missing arguments, and extra values are reported instead of ignored
+        assert_eq!(parse(&["scene", "head.toml", "head.png", "--sett", "x=1"]).unwrap_err().kind,
+                   ErrorKind::UnknownArgument);
+        assert_eq!(parse(&["scene", "head.toml"]).unwrap_err().kind, ErrorKind::MissingRequiredArgument);
+        assert_eq!(parse(&["svg", "head.obj", "head.svg", "hatched"]).unwrap_err().kind, ErrorKind::InvalidValue);
+        assert!(parse(&["sweep", "sheet.png", "request.json", "a=1", "b=2", "c=3"]).is_err());
+        assert_eq!(parse(&["head.obj", "head.png", "--backend", "tpu"]).unwrap_err().kind, ErrorKind::InvalidValue);
+        assert!(RenderOptions::from_args(vec!["rastermind", "serve"]).is_err());
+    }
+}
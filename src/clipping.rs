@@ -0,0 +1,313 @@
+use nalgebra::{Matrix3, Matrix4, Vector3, Vector4};
+
+use shader::{Contributions, Shader};
+use vector;
+use wavefront;
+
+
+/// A plane that cuts away the geometry on one side of it
+///
+/// Points on the side the normal points towards are kept and points behind
+/// the plane are cut away.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::clipping::ClipPlane;
+/// // cut away everything in front of the plane through the origin facing away from the camera
+/// let plane = ClipPlane::new(&Vector3::zeros(), &-Vector3::z());
+/// ```
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClipPlane {
+    pub normal: Vector3<f64>,
+    pub distance: f64
+}
+
+
+impl ClipPlane {
+    /// Create a new ClipPlane through the given point that keeps the side the normal points towards
+    pub fn new(point: &Vector3<f64>, normal: &Vector3<f64>) -> ClipPlane {
+        let normal = normal.normalize();
+
+        ClipPlane { normal, distance: -normal.dot(point) }
+    }
+
+    /// Whether the given point is kept by the plane
+    pub fn keeps(&self, point: &Vector3<f64>) -> bool {
+        self.normal.dot(point) + self.distance >= 0.0
+    }
+}
+
+
+/// The smallest w of the points kept by clip_segment, just in front of the eye
+const NEAR_W: f64 = 1e-6;
+
+
+/// Clip a segment to the part that is in front of the eye and on a screen of the given size
+///
+/// The end points are homogeneous screen coordinates, with the viewport,
+/// projection, and model view applied but not yet divided by w. The part at
+/// or behind the eye plane, where w is not positive, is cut away before the
+/// division, and what is left is cut to the screen, so the returned screen
+/// coordinates are finite and at most a screen apart. None is returned when
+/// nothing of the segment is left.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector4;
+/// # use rastermind::clipping::clip_segment;
+/// // the end at the eye plane is moved to the edge of the screen
+/// let (start, end) = clip_segment(&Vector4::new(8.0, 8.0, 0.0, 1.0), &Vector4::new(1.0, 0.0, 0.0, 0.0), 16, 16)
+///     .unwrap();
+///
+/// assert_eq!(start.x, 8.0);
+/// assert_eq!(end.x, 16.0);
+/// ```
+///
+pub fn clip_segment(start: &Vector4<f64>, end: &Vector4<f64>, width: u32, height: u32)
+                    -> Option<(Vector3<f64>, Vector3<f64>)> {
+
+    if !start.iter().chain(end.iter()).all(|value| value.is_finite()) || start.w.max(end.w) < NEAR_W {
+        return None;
+    }
+
+    let near = |inside: &Vector4<f64>, outside: &Vector4<f64>| {
+        inside.lerp(outside, (inside.w - NEAR_W) / (inside.w - outside.w))
+    };
+    let start4 = if start.w < NEAR_W { near(end, start) } else { *start };
+    let end4 = if end.w < NEAR_W { near(start, end) } else { *end };
+
+    let start = vector::project_to_3d(&start4);
+    let delta = vector::project_to_3d(&end4) - start;
+
+    if !delta.iter().all(|value| value.is_finite()) {
+        return None;
+    }
+
+    // the parameters of the part of the segment inside each edge of the screen
+    let (mut low, mut high) = (0.0f64, 1.0f64);
+    let edges = [(-delta.x, start.x), (delta.x, width as f64 - start.x),
+                 (-delta.y, start.y), (delta.y, height as f64 - start.y)];
+
+    for &(direction, distance) in &edges {
+        if direction == 0.0 {
+            if distance < 0.0 {
+                return None;
+            }
+        } else if direction < 0.0 {
+            low = low.max(distance / direction);
+        } else {
+            high = high.min(distance / direction);
+        }
+    }
+
+    if low > high {
+        return None;
+    }
+
+    Some((start + delta * low, start + delta * high))
+}
+
+
+/// The fill of the cut where a clipping plane opens up a closed mesh
+///
+/// The cut shows the inside of the faces behind it, which are filled with a
+/// solid color or with diagonal hatching lines every spacing pixels.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cap {
+    Solid(image::Rgb<u8>),
+    Hatch { color: image::Rgb<u8>, background: image::Rgb<u8>, spacing: u32 }
+}
+
+
+impl Cap {
+    /// Find the color of the cap at the given pixel
+    pub fn color(&self, x: f64, y: f64) -> image::Rgb<u8> {
+        match *self {
+            Cap::Solid(color) => color,
+            Cap::Hatch { color, background, spacing } => {
+                if (x + y).floor().rem_euclid(spacing.max(2) as f64) < 1.0 { color } else { background }
+            }
+        }
+    }
+}
+
+
+/// Cut away the fragments of another shader that are behind any of the clipping planes
+///
+/// When a cap is given, the fragments of faces that point away from the
+/// camera are drawn with the cap instead, so a cut through a closed mesh
+/// looks solid.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::clipping::*;
+/// # use rastermind::shader;
+/// let planes = [ClipPlane::new(&Vector3::zeros(), &-Vector3::z())];
+/// let shader = ClipShader::new(Box::new(shader::GouraudShader::new()), &planes,
+///                              Some(Cap::Solid(image::Rgb([255, 0, 0]))));
+/// ```
+///
+pub struct ClipShader<'a> {
+    pub shader: Box<dyn Shader + 'a>,
+    pub planes: &'a [ClipPlane],
+    pub cap: Option<Cap>,
+    pub varying_position: Matrix3<f64>,
+    pub varying_screen: Matrix3<f64>,
+    pub varying_w: Vector3<f64>
+}
+
+
+impl<'a> ClipShader<'a> {
+    /// Create a new instance of a ClipShader that cuts the given shader with the given planes
+    pub fn new(shader: Box<dyn Shader + 'a>, planes: &'a [ClipPlane], cap: Option<Cap>) -> ClipShader<'a> {
+        ClipShader { shader, planes, cap, varying_position: Matrix3::zeros(),
+                     varying_screen: Matrix3::zeros(), varying_w: Vector3::zeros() }
+    }
+
+    /// Whether the face points away from the camera, where counter-clockwise faces on the screen point towards it
+    fn back_facing(&self) -> bool {
+        let a = self.varying_screen.column(1) - self.varying_screen.column(0);
+        let b = self.varying_screen.column(2) - self.varying_screen.column(0);
+
+        a.x * b.y - a.y * b.x < 0.0
+    }
+}
+
+
+impl<'a> Shader for ClipShader<'a> {
+    /// Position the vertices with the clipped shader and keep their positions in the scene and on the screen
+    fn vertex(&mut self, coordinates: &wavefront::Object,
+              view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+              model_view: &Matrix4<f64>, light_vector: &Vector3<f64>,
+              face_index: usize, vertex_index: usize) -> Vector4<f64> {
+
+        let geometric_index = coordinates.geometric_faces[face_index][vertex_index] as usize;
+        self.varying_position.set_column(vertex_index, &coordinates.geometric_vertices[geometric_index]);
+
+        let screen_coordinate = self.shader.vertex(coordinates, view_port, projection, model_view,
+                                                   light_vector, face_index, vertex_index);
+        self.varying_w[vertex_index] = screen_coordinate.w;
+        self.varying_screen.set_column(vertex_index, &vector::project_to_3d(&screen_coordinate));
+
+        screen_coordinate
+    }
+
+    /// Draw the cap on faces that point away from the camera, or the color of the clipped shader
+    fn fragment(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> image::Rgb<u8> {
+        match self.cap {
+            Some(cap) if self.back_facing() => {
+                let pixel = self.varying_screen * vertex;
+                cap.color(pixel.x, pixel.y)
+            },
+            _ => self.shader.fragment(vertex, texture)
+        }
+    }
+
+    /// Draw the cap as diffuse light on faces that point away from the camera, or the terms of the clipped shader
+    fn contributions(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> Contributions {
+        match self.cap {
+            Some(_) if self.back_facing() => Contributions { diffuse: self.fragment(vertex, texture),
+                                                             ..Default::default() },
+            _ => self.shader.contributions(vertex, texture)
+        }
+    }
+
+    /// Discard the fragments behind any of the planes
+    fn discard(&self, vertex: Vector3<f64>) -> bool {
+        let perspective = vertex.component_div(&self.varying_w);
+        let position = self.varying_position * perspective / perspective.sum();
+
+        self.planes.iter().any(|plane| !plane.keeps(&position)) || self.shader.discard(vertex)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use render;
+    use shader;
+
+    #[test]
+    fn test_clip_segment() {
+        let inside = Vector4::new(8.0, 8.0, 2.0, 1.0);
+
+        // a segment inside the screen is only divided by w
+        let (start, end) = clip_segment(&(inside * 2.0), &Vector4::new(4.0, 4.0, 0.0, 1.0), 16, 16).unwrap();
+        assert_eq!((start, end), (Vector3::new(8.0, 8.0, 2.0), Vector3::new(4.0, 4.0, 0.0)));
+
+        // an end at or behind the eye is cut where w is nearly zero, then to the edge of the screen
+        let (_, end) = clip_segment(&inside, &Vector4::new(0.0, 1.0, 0.0, 0.0), 16, 16).unwrap();
+        assert_eq!(end.y, 16.0);
+        assert!(end.x.is_finite() && (end.x - 8.0).abs() < 1e-3);
+
+        let (_, end) = clip_segment(&inside, &Vector4::new(-16.0, -8.0, 0.0, -1.0), 16, 16).unwrap();
+        assert_eq!(end.x, 0.0);
+        assert!((end.y - 8.0).abs() < 1e-3);
+
+        assert_eq!(clip_segment(&-inside, &Vector4::new(1.0, 1.0, 0.0, 0.0), 16, 16), None);
+        assert_eq!(clip_segment(&Vector4::new(20.0, 8.0, 0.0, 1.0), &Vector4::new(30.0, 8.0, 0.0, 1.0), 16, 16),
+                   None);
+        assert_eq!(clip_segment(&inside, &Vector4::new(f64::NAN, 0.0, 0.0, 1.0), 16, 16), None);
+    }
+
+    #[test]
+    fn test_clip_shader() {
+        // a cube seen from the front with its front half cut away
+        let cube = wavefront::Object::from_bytes(b"v -1 -1 -1\nv 1 -1 -1\nv 1 1 -1\nv -1 1 -1\n\
+                                                   v -1 -1 1\nv 1 -1 1\nv 1 1 1\nv -1 1 1\nvt 0 0\nvn 0 0 1\n\
+                                                   f 5/1/1 6/1/1 7/1/1\nf 5/1/1 7/1/1 8/1/1\n\
+                                                   f 2/1/1 1/1/1 4/1/1\nf 2/1/1 4/1/1 3/1/1\n\
+                                                   f 1/1/1 5/1/1 8/1/1\nf 1/1/1 8/1/1 4/1/1\n\
+                                                   f 6/1/1 2/1/1 3/1/1\nf 6/1/1 3/1/1 7/1/1\n\
+                                                   f 8/1/1 7/1/1 3/1/1\nf 8/1/1 3/1/1 4/1/1\n\
+                                                   f 1/1/1 2/1/1 6/1/1\nf 1/1/1 6/1/1 5/1/1\n",
+                                                 &wavefront::LoadOptions::default()).unwrap();
+
+        let planes = [ClipPlane::new(&Vector3::new(0.0, 0.0, 0.5), &-Vector3::z())];
+        let white = image::Rgb([255, 255, 255]);
+        let red = image::Rgb([255, 0, 0]);
+
+        let draw = |cap: Option<Cap>| {
+            let mut buffer = image::RgbImage::new(64, 64);
+            let mut zbuffer = vec![-1.0; 64 * 64];
+
+            render::draw_faces(&cube, &mut buffer, &image::RgbImage::new(1, 1), &mut zbuffer,
+                               &render::DepthOptions::default(), &shader::viewport(8, 8, 48, 48, 255),
+                               &shader::projection(-1.0 / 5.0),
+                               &shader::lookat(&Vector3::new(0.0, 0.0, 5.0), &Vector3::zeros(), &Vector3::y()),
+                               &Vector3::z(), |_| {
+                Box::new(ClipShader::new(Box::new(shader::ColorShader::new(white, false)), &planes, cap))
+            });
+
+            buffer
+        };
+
+        assert!(planes[0].keeps(&Vector3::zeros()));
+        assert!(!planes[0].keeps(&Vector3::new(0.0, 0.0, 1.0)));
+
+        // without a cap the inside of the back face shows through the cut
+        assert_eq!(*draw(None).get_pixel(32, 32), white);
+        assert_eq!(*draw(Some(Cap::Solid(red))).get_pixel(32, 32), red);
+
+        let hatch = Cap::Hatch { color: red, background: white, spacing: 4 };
+        let hatched = draw(Some(hatch));
+        let row: Vec<image::Rgb<u8>> = (30..34).map(|x| *hatched.get_pixel(x, 32)).collect();
+        assert_eq!(row.iter().filter(|&&pixel| pixel == red).count(), 1);
+        assert_eq!(row.iter().filter(|&&pixel| pixel == white).count(), 3);
+    }
+}
@@ -0,0 +1,750 @@
+use std::collections::HashMap;
+use std::fs;
+
+use nalgebra::{Matrix3, Matrix4, Rotation3, U3, Unit, Vector2, Vector3};
+
+use error::MorpheusError;
+use mesh::{Mesh, MeshMaterial};
+use wavefront;
+use wavefront::{AxisConvention, Handedness, UpAxis};
+
+
+/// An element of an XML document with its attributes, child elements, and text
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Element {
+    name: String,
+    attributes: HashMap<String, String>,
+    children: Vec<Element>,
+    text: String
+}
+
+
+impl Element {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.get(name).map(String::as_str)
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> + 'a {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    /// The first element down the given path of child names
+    fn find(&self, path: &[&str]) -> Option<&Element> {
+        path.iter().try_fold(self, |element, name| element.child(name))
+    }
+
+    /// Every element below this one, depth first
+    fn descendants(&self) -> Vec<&Element> {
+        let mut descendants = Vec::new();
+
+        for child in &self.children {
+            descendants.push(child);
+            descendants.extend(child.descendants());
+        }
+
+        descendants
+    }
+
+    /// The numbers separated by whitespace in the text of the element
+    fn numbers(&self) -> Result<Vec<f64>, String> {
+        self.text.split_whitespace().map(|number| {
+            number.parse().map_err(|_| format!("<{}> expects numbers, not '{}'", self.name, number))
+        }).collect()
+    }
+
+    /// The indices separated by whitespace in the text of the element
+    fn indices(&self) -> Result<Vec<usize>, String> {
+        self.text.split_whitespace().map(|index| {
+            index.parse().map_err(|_| format!("<{}> expects indices, not '{}'", self.name, index))
+        }).collect()
+    }
+}
+
+
+/// Replace the entities of the text of an XML document with the characters they stand for
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+
+/// Parse an XML document into its root element, skipping declarations and comments
+fn parse_xml(text: &str) -> Result<Element, String> {
+    let mut stack: Vec<Element> = vec![Element::default()];
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        let skip = |rest: &str, start: &str, end: &str| -> Option<Result<usize, String>> {
+            if !rest.starts_with(start) {
+                return None;
+            }
+
+            Some(rest.find(end).map(|index| index + end.len()).ok_or_else(|| format!("{} is not closed", start)))
+        };
+
+        if let Some(length) = skip(rest, "<?", "?>").or_else(|| skip(rest, "<!--", "-->")) {
+            rest = &rest[length?..];
+        } else if rest.starts_with("<![CDATA[") {
+            let end = rest.find("]]>").ok_or("<![CDATA[ is not closed")?;
+            stack.last_mut().unwrap().text.push_str(&rest[9..end]);
+            rest = &rest[end + 3..];
+        } else if let Some(length) = skip(rest, "<!", ">") {
+            rest = &rest[length?..];
+        } else if rest.starts_with("</") {
+            let end = rest.find('>').ok_or("a closing tag is not closed")?;
+            let name = rest[2..end].trim();
+            let element = stack.pop().filter(|_| !stack.is_empty())
+                               .ok_or_else(|| format!("</{}> closes nothing", name))?;
+
+            if element.name != name {
+                return Err(format!("<{}> is closed by </{}>", element.name, name));
+            }
+
+            stack.last_mut().unwrap().children.push(element);
+            rest = &rest[end + 1..];
+        } else if rest.starts_with('<') {
+            let end = rest.find('>').ok_or("a tag is not closed")?;
+            let tag = &rest[1..end];
+            let (tag, closed) = match tag.strip_suffix('/') {
+                Some(tag) => (tag, true),
+                None => (tag, false)
+            };
+
+            let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+            let mut element = Element { name: tag[..name_end].to_string(), ..Default::default() };
+            let mut attributes = &tag[name_end..];
+
+            while let Some(equals) = attributes.find('=') {
+                let name = attributes[..equals].trim().to_string();
+                let value = attributes[equals + 1..].trim_start();
+                let quote = value.chars().next().filter(|&quote| quote == '"' || quote == '\'')
+                                 .ok_or_else(|| format!("the attribute {} of <{}> is not quoted", name, element.name))?;
+                let length = value[1..].find(quote)
+                                       .ok_or_else(|| format!("the attribute {} of <{}> is not closed", name,
+                                                              element.name))?;
+
+                element.attributes.insert(name, unescape(&value[1..length + 1]));
+                attributes = &value[length + 2..];
+            }
+
+            if closed {
+                stack.last_mut().unwrap().children.push(element);
+            } else {
+                stack.push(element);
+            }
+
+            rest = &rest[end + 1..];
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            stack.last_mut().unwrap().text.push_str(&unescape(&rest[..end]));
+            rest = &rest[end..];
+        }
+    }
+
+    if stack.len() > 1 {
+        return Err(format!("<{}> is not closed", stack[stack.len() - 1].name));
+    }
+
+    stack.pop().and_then(|document| document.children.into_iter().next())
+         .ok_or_else(|| String::from("the document is empty"))
+}
+
+
+/// The elements of a Collada document that are referred to by their ids
+struct Library<'a> {
+    elements: HashMap<&'a str, &'a Element>
+}
+
+
+impl<'a> Library<'a> {
+    fn new(document: &'a Element) -> Library<'a> {
+        let elements = document.descendants().into_iter()
+                               .filter_map(|element| element.attribute("id").map(|id| (id, element))).collect();
+
+        Library { elements }
+    }
+
+    /// The element a url such as #geometry refers to
+    fn get(&self, url: &str) -> Option<&'a Element> {
+        self.elements.get(url.trim_start_matches('#')).cloned()
+    }
+
+    /// The values of a source with the number of values of every element, such as three for positions
+    fn source(&self, url: &str) -> Result<(Vec<f64>, usize), String> {
+        let source = self.get(url).ok_or_else(|| format!("there is no source {}", url))?;
+
+        // the inputs of a vertices element point at the source of its positions
+        if source.name == "vertices" {
+            let position = source.children_named("input").find(|input| input.attribute("semantic") == Some("POSITION"))
+                                 .and_then(|input| input.attribute("source"))
+                                 .ok_or_else(|| format!("the vertices {} have no positions", url))?;
+
+            return self.source(position);
+        }
+
+        let values = source.child("float_array").ok_or_else(|| format!("the source {} has no float_array", url))?
+                           .numbers()?;
+        let stride = source.find(&["technique_common", "accessor"]).and_then(|accessor| accessor.attribute("stride"))
+                           .and_then(|stride| stride.parse().ok()).unwrap_or(1);
+
+        Ok((values, stride))
+    }
+
+    /// The name of the material with the given id, with its diffuse color and the file of its diffuse texture
+    fn material(&self, id: &str) -> MeshMaterial {
+        let material = self.get(id);
+        let name = material.and_then(|material| material.attribute("name").or_else(|| material.attribute("id")))
+                           .unwrap_or(id).to_string();
+
+        let diffuse = material.and_then(|material| material.child("instance_effect"))
+                              .and_then(|instance| instance.attribute("url")).and_then(|url| self.get(url))
+                              .and_then(|effect| {
+                                  effect.descendants().into_iter().find(|element| element.name == "diffuse")
+                                        .map(|diffuse| (effect, diffuse))
+                              });
+
+        let color = diffuse.and_then(|(_, diffuse)| diffuse.child("color")).and_then(|color| color.numbers().ok())
+                           .filter(|color| color.len() >= 3).map(|color| Vector3::new(color[0], color[1], color[2]));
+        let texture = diffuse.and_then(|(effect, diffuse)| {
+            let sampler = diffuse.child("texture")?.attribute("texture")?;
+            self.image(effect, sampler)
+        });
+
+        MeshMaterial { name, diffuse: color.unwrap_or_else(|| Vector3::repeat(1.0)), texture }
+    }
+
+    /// The file of the image a texture of an effect samples, following its sampler and surface parameters
+    fn image(&self, effect: &Element, sampler: &str) -> Option<String> {
+        let parameters: Vec<&Element> = effect.descendants().into_iter()
+                                              .filter(|element| element.name == "newparam").collect();
+        let parameter = |sid: &str| parameters.iter().find(|parameter| parameter.attribute("sid") == Some(sid));
+
+        let mut image = sampler.to_string();
+
+        // Collada 1.4 samples a surface initialized from the image, while 1.5 names the image in the sampler
+        if let Some(sampler) = parameter(sampler).and_then(|parameter| parameter.child("sampler2D")) {
+            image = match (sampler.child("source"), sampler.child("instance_image")) {
+                (Some(source), _) => parameter(source.text.trim()).and_then(|surface| surface.child("surface"))
+                                                            .and_then(|surface| surface.child("init_from"))
+                                                            .map(|init| init.text.trim().to_string())?,
+                (None, Some(instance)) => instance.attribute("url")?.to_string(),
+                (None, None) => return None
+            };
+        }
+
+        let init = self.get(&image)?.child("init_from")?;
+        let path = init.child("ref").unwrap_or(init).text.trim();
+
+        Some(path.trim_start_matches("file://").to_string())
+    }
+}
+
+
+/// Load the static meshes of a Collada file into one Mesh
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate rastermind;
+/// # use rastermind::{collada, texture};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// let mesh = collada::open("duck.dae")?;
+/// let texture = texture::open(mesh.texture().unwrap())?;
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn open(filename: &str) -> Result<Mesh, MorpheusError> {
+    let bytes = fs::read(filename).map_err(|error| MorpheusError::io(filename, error))?;
+
+    parse(&bytes).map_err(|message| MorpheusError::Asset { path: filename.to_string(), message })
+}
+
+
+/// Parse the static meshes of a Collada document into one Mesh
+///
+/// Every geometry instanced by the nodes of the visual scene is placed by the
+/// transforms of the node and its parents and added as a group named after
+/// the node. Documents without a visual scene have their geometries added
+/// where they are. Documents authored with z up are turned so that y is up,
+/// and lengths are converted to meters with the unit of the asset, which is a
+/// meter when the document has none.
+///
+/// The triangles, polylists, and polygons of a geometry are read with their
+/// positions, normals, and first set of texture coordinates. Polygons are
+/// split into triangles around their first corner. When a geometry has no
+/// normals, smooth normals are generated for the whole mesh, and a geometry
+/// without texture coordinates maps all of its corners to the corner of the
+/// texture. Materials bound to a geometry by its instance keep their diffuse
+/// color and the file of their diffuse texture.
+///
+/// Skins, morphs, animations, and instanced nodes are not read.
+///
+pub fn parse(bytes: &[u8]) -> Result<Mesh, String> {
+    let text = String::from_utf8_lossy(bytes);
+    let document = parse_xml(&text)?;
+
+    if document.name != "COLLADA" {
+        return Err(format!("the document is <{}> rather than <COLLADA>", document.name));
+    }
+
+    let library = Library::new(&document);
+    let mut importer = Importer { library: &library, mesh: Mesh::default(), materials: HashMap::new(),
+                                  missing_normals: false };
+
+    let scene = document.find(&["scene", "instance_visual_scene"]).and_then(|instance| instance.attribute("url"))
+                        .and_then(|url| library.get(url))
+                        .or_else(|| document.find(&["library_visual_scenes", "visual_scene"]));
+
+    match scene {
+        Some(scene) => {
+            for node in scene.children_named("node") {
+                importer.add_node(node, &Matrix4::identity())?;
+            }
+        },
+        None => {
+            let geometries = document.child("library_geometries").map_or_else(Vec::new, |library| {
+                library.children_named("geometry").collect()
+            });
+
+            for geometry in geometries {
+                let name = geometry.attribute("name").or_else(|| geometry.attribute("id")).unwrap_or("geometry");
+                importer.add_geometry(geometry, name, &Matrix4::identity(), &HashMap::new())?;
+            }
+        }
+    }
+
+    let mut mesh = importer.mesh;
+
+    if mesh.object.geometric_vertices.is_empty() {
+        return Err(String::from("the document has no meshes"));
+    }
+
+    if importer.missing_normals {
+        mesh.object.generate_normals();
+    }
+
+    if document.find(&["asset", "up_axis"]).map(|axis| axis.text.trim()) == Some("Z_UP") {
+        mesh.object.convert_axes(&AxisConvention::new(UpAxis::Z, Handedness::Right));
+    }
+
+    let meters = document.find(&["asset", "unit"]).and_then(|unit| unit.attribute("meter"))
+                         .and_then(|meter| meter.trim().parse::<f64>().ok())
+                         .filter(|meter| meter.is_finite() && *meter > 0.0);
+
+    if let Some(meters) = meters {
+        mesh.object.scale(meters);
+    }
+
+    Ok(mesh)
+}
+
+
+/// The transform of a node relative to its parent from its transform elements, applied in order
+fn node_transform(node: &Element) -> Result<Matrix4<f64>, String> {
+    let mut transform = Matrix4::identity();
+
+    for element in &node.children {
+        let numbers = match element.name.as_str() {
+            "matrix" | "translate" | "rotate" | "scale" => element.numbers()?,
+            _ => continue
+        };
+
+        let expected = match element.name.as_str() {
+            "matrix" => 16,
+            "rotate" => 4,
+            _ => 3
+        };
+
+        if numbers.len() != expected {
+            return Err(format!("<{}> of the node {} expects {} numbers", element.name,
+                               node.attribute("id").unwrap_or(""), expected));
+        }
+
+        transform *= match element.name.as_str() {
+            "matrix" => Matrix4::from_row_slice(&numbers),
+            "translate" => Matrix4::new_translation(&Vector3::new(numbers[0], numbers[1], numbers[2])),
+            "rotate" => {
+                let axis = Unit::try_new(Vector3::new(numbers[0], numbers[1], numbers[2]), 0.0);
+                axis.map_or_else(Matrix4::identity, |axis| {
+                    Rotation3::from_axis_angle(&axis, numbers[3].to_radians()).to_homogeneous()
+                })
+            },
+            _ => Matrix4::new_nonuniform_scaling(&Vector3::new(numbers[0], numbers[1], numbers[2]))
+        };
+    }
+
+    Ok(transform)
+}
+
+
+/// The mesh of a document as its nodes are added
+struct Importer<'a> {
+    library: &'a Library<'a>,
+    mesh: Mesh,
+    materials: HashMap<String, usize>,
+    missing_normals: bool
+}
+
+
+impl<'a> Importer<'a> {
+    /// Add the geometries instanced by a node and the nodes below it
+    fn add_node(&mut self, node: &Element, parent: &Matrix4<f64>) -> Result<(), String> {
+        let transform = parent * node_transform(node)?;
+        let name = node.attribute("name").or_else(|| node.attribute("id")).unwrap_or("node");
+
+        for instance in node.children_named("instance_geometry") {
+            let url = instance.attribute("url").unwrap_or("");
+            let geometry = self.library.get(url).ok_or_else(|| format!("there is no geometry {}", url))?;
+
+            // the materials of the geometry are bound to the materials of the document by their symbols
+            let bindings: HashMap<&str, &str> = instance.descendants().into_iter()
+                .filter(|element| element.name == "instance_material")
+                .filter_map(|binding| Some((binding.attribute("symbol")?, binding.attribute("target")?)))
+                .collect();
+
+            self.add_geometry(geometry, name, &transform, &bindings)?;
+        }
+
+        for child in node.children_named("node") {
+            self.add_node(child, &transform)?;
+        }
+
+        Ok(())
+    }
+
+    /// Add the primitives of a geometry as a group
+    fn add_geometry(&mut self, geometry: &Element, name: &str, transform: &Matrix4<f64>,
+                    bindings: &HashMap<&str, &str>) -> Result<(), String> {
+
+        let mesh = match geometry.child("mesh") {
+            Some(mesh) => mesh,
+            None => return Ok(())
+        };
+
+        let start = self.mesh.object.geometric_faces.len();
+        let primitives = mesh.children.iter().filter(|element| {
+            element.name == "triangles" || element.name == "polylist" || element.name == "polygons"
+        });
+
+        for primitives in primitives {
+            let material = match primitives.attribute("material") {
+                Some(symbol) => {
+                    let id = bindings.get(symbol).cloned().unwrap_or(symbol).trim_start_matches('#').to_string();
+                    let library = self.library;
+                    let materials = &mut self.mesh.materials;
+
+                    Some(*self.materials.entry(id.clone()).or_insert_with(|| {
+                        materials.push(library.material(&id));
+                        materials.len() - 1
+                    }))
+                },
+                None => None
+            };
+
+            self.add_primitives(primitives, name, transform, material)?;
+        }
+
+        let end = self.mesh.object.geometric_faces.len();
+
+        if end > start {
+            self.mesh.object.groups.push(wavefront::Group { name: name.to_string(), faces: start..end });
+        }
+
+        Ok(())
+    }
+
+    /// Add the polygons of a triangles, polylist, or polygons element with the given material
+    fn add_primitives(&mut self, primitives: &Element, name: &str, transform: &Matrix4<f64>,
+                      material: Option<usize>) -> Result<(), String> {
+
+        let inputs: Vec<&Element> = primitives.children_named("input").collect();
+        let offset = |semantics: &[&str]| -> Result<Option<(usize, &str)>, String> {
+            let input = inputs.iter().filter(|input| semantics.contains(&input.attribute("semantic").unwrap_or("")))
+                              .min_by_key(|input| input.attribute("set").and_then(|set| set.parse::<u32>().ok()));
+
+            match input {
+                Some(input) => {
+                    let offset = input.attribute("offset").unwrap_or("0").parse()
+                                      .map_err(|_| format!("an input of {} has an invalid offset", name))?;
+
+                    Ok(Some((offset, input.attribute("source").unwrap_or(""))))
+                },
+                None => Ok(None)
+            }
+        };
+
+        let positions = offset(&["VERTEX"])?.ok_or_else(|| format!("the primitives of {} have no vertices", name))?;
+        let normals = offset(&["NORMAL"])?;
+        let texture_coordinates = offset(&["TEXCOORD"])?;
+        let stride = inputs.iter().filter_map(|input| input.attribute("offset").and_then(|offset| offset.parse().ok()))
+                           .max().map_or(1, |offset: usize| offset + 1);
+
+        // the normals may be given with the positions in the vertices element rather than as an input of their own
+        let vertex_normals = normals.is_none() && self.library.get(positions.1).is_some_and(|vertices| {
+            vertices.children_named("input").any(|input| input.attribute("semantic") == Some("NORMAL"))
+        });
+        let normal_source = match normals {
+            Some((_, source)) => Some(source.to_string()),
+            None if vertex_normals => self.library.get(positions.1).and_then(|vertices| {
+                vertices.children_named("input").find(|input| input.attribute("semantic") == Some("NORMAL"))
+                        .and_then(|input| input.attribute("source")).map(String::from)
+            }),
+            None => None
+        };
+
+        let object = &mut self.mesh.object;
+        let linear: Matrix3<f64> = transform.fixed_slice::<U3, U3>(0, 0).into_owned();
+        let normal_transform = linear.try_inverse().unwrap_or_else(Matrix3::identity).transpose();
+
+        let geometric_offset = object.geometric_vertices.len();
+        let texture_offset = object.texture_vertices.len();
+        let normal_offset = object.normal_vertices.len();
+
+        let (values, size) = self.library.source(positions.1)?;
+        object.geometric_vertices.extend(values.chunks(size.max(3)).filter(|p| p.len() >= 3).map(|p| {
+            transform.transform_point(&Vector3::new(p[0], p[1], p[2]).into()).coords
+        }));
+
+        if let Some(ref source) = normal_source {
+            let (values, size) = self.library.source(source)?;
+            object.normal_vertices.extend(values.chunks(size.max(3)).filter(|n| n.len() >= 3).map(|n| {
+                (normal_transform * Vector3::new(n[0], n[1], n[2])).normalize()
+            }));
+        } else {
+            self.missing_normals = true;
+        }
+
+        match texture_coordinates {
+            Some((_, source)) => {
+                let (values, size) = self.library.source(source)?;
+                object.texture_vertices.extend(values.chunks(size.max(2)).filter(|t| t.len() >= 2)
+                                                     .map(|t| Vector2::new(t[0], t[1])));
+            },
+            None => object.texture_vertices.push(Vector2::zeros())
+        }
+
+        let counts = [object.geometric_vertices.len() - geometric_offset,
+                      object.texture_vertices.len() - texture_offset,
+                      object.normal_vertices.len() - normal_offset];
+
+        // the indices of every polygon, with the number of corners of each
+        let mut polygons: Vec<(Vec<usize>, Vec<usize>)> = Vec::new();
+
+        match primitives.name.as_str() {
+            "triangles" => {
+                let indices = primitives.child("p").map_or(Ok(Vec::new()), Element::indices)?;
+                let triangles = indices.len() / (3 * stride);
+                polygons.push((indices, vec![3; triangles]));
+            },
+            "polylist" => {
+                let indices = primitives.child("p").map_or(Ok(Vec::new()), Element::indices)?;
+                let vcount = primitives.child("vcount").map_or(Ok(Vec::new()), Element::indices)?;
+                polygons.push((indices, vcount));
+            },
+            _ => {
+                for p in primitives.children_named("p") {
+                    let indices = p.indices()?;
+                    let corners = indices.len() / stride;
+                    polygons.push((indices, vec![corners]));
+                }
+            }
+        }
+
+        let corner = |indices: &[usize], corner: usize, input: Option<(usize, &str)>, count: usize, base: usize| {
+            match input {
+                Some((offset, _)) => match indices.get(corner * stride + offset) {
+                    Some(&index) if index < count => Ok((base + index) as i32),
+                    _ => Err(format!("the primitives of {} have an index out of range", name))
+                },
+                None => Ok(base as i32)
+            }
+        };
+
+        let normal_input = normals.or(if vertex_normals { Some((positions.0, "")) } else { None });
+
+        for (indices, vcount) in polygons {
+            let mut first = 0;
+
+            for corners in vcount {
+                if first * stride >= indices.len() {
+                    break;
+                }
+
+                let mut polygon = Vec::new();
+
+                for i in first..first + corners {
+                    polygon.push(Vector3::new(corner(&indices, i, Some(positions), counts[0], geometric_offset)?,
+                                              corner(&indices, i, texture_coordinates, counts[1], texture_offset)?,
+                                              corner(&indices, i, normal_input, counts[2].max(1), normal_offset)?));
+                }
+
+                for i in 1..polygon.len().saturating_sub(1) {
+                    let (a, b, c) = (polygon[0], polygon[i], polygon[i + 1]);
+
+                    object.geometric_faces.push(Vector3::new(a[0], b[0], c[0]));
+                    object.texture_faces.push(Vector3::new(a[1], b[1], c[1]));
+                    object.normal_faces.push(Vector3::new(a[2], b[2], c[2]));
+                    object.smoothing_groups.push(1);
+                    self.mesh.face_materials.push(material);
+                }
+
+                first += corners;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use plugins;
+
+    const CORNER: &str = r##"<?xml version="1.0" encoding="utf-8"?>
+<COLLADA xmlns="http://www.collada.org/2005/11/COLLADASchema" version="1.4.1">
+  <asset><up_axis>Z_UP</up_axis></asset>
+  <library_images>
+    <image id="wood-image"><init_from>textures/wood.png</init_from></image>
+  </library_images>
+  <library_effects>
+    <effect id="wood-effect">
+      <profile_COMMON>
+        <newparam sid="wood-surface"><surface type="2D"><init_from>wood-image</init_from></surface></newparam>
+        <newparam sid="wood-sampler"><sampler2D><source>wood-surface</source></sampler2D></newparam>
+        <technique sid="common">
+          <lambert><diffuse><texture texture="wood-sampler" texcoord="UVMap"/></diffuse></lambert>
+        </technique>
+      </profile_COMMON>
+    </effect>
+    <effect id="paint-effect">
+      <profile_COMMON><technique sid="common">
+        <phong><diffuse><color sid="diffuse">0.5 0.25 0 1</color></diffuse></phong>
+      </technique></profile_COMMON>
+    </effect>
+  </library_effects>
+  <library_materials>
+    <material id="Wood-material" name="Wood"><instance_effect url="#wood-effect"/></material>
+    <material id="Paint-material" name="Paint"><instance_effect url="#paint-effect"/></material>
+  </library_materials>
+  <library_geometries>
+    <geometry id="corner-mesh" name="Corner">
+      <mesh>
+        <source id="corner-positions">
+          <float_array id="corner-positions-array" count="15">0 0 0 1 0 0 1 1 0 0 1 0 0 0 1</float_array>
+          <technique_common><accessor source="#corner-positions-array" count="5" stride="3"/></technique_common>
+        </source>
+        <source id="corner-normals">
+          <float_array id="corner-normals-array" count="6">0 0 1 -1 0 0</float_array>
+          <technique_common><accessor source="#corner-normals-array" count="2" stride="3"/></technique_common>
+        </source>
+        <source id="corner-uvs">
+          <float_array id="corner-uvs-array" count="8">0 0 1 0 1 1 0 1</float_array>
+          <technique_common><accessor source="#corner-uvs-array" count="4" stride="2"/></technique_common>
+        </source>
+        <vertices id="corner-vertices">
+          <input semantic="POSITION" source="#corner-positions"/>
+        </vertices>
+        <polylist material="Paint-symbol" count="1">
+          <input semantic="VERTEX" source="#corner-vertices" offset="0"/>
+          <input semantic="NORMAL" source="#corner-normals" offset="1"/>
+          <input semantic="TEXCOORD" source="#corner-uvs" offset="2" set="0"/>
+          <vcount>4</vcount>
+          <p>0 0 0 1 0 1 2 0 2 3 0 3</p>
+        </polylist>
+        <triangles material="Wood-symbol" count="1">
+          <input semantic="VERTEX" source="#corner-vertices" offset="0"/>
+          <input semantic="NORMAL" source="#corner-normals" offset="1"/>
+          <input semantic="TEXCOORD" source="#corner-uvs" offset="2" set="0"/>
+          <p>0 1 0 3 1 3 4 1 2</p>
+        </triangles>
+      </mesh>
+    </geometry>
+  </library_geometries>
+  <library_visual_scenes>
+    <visual_scene id="Scene" name="Scene">
+      <node id="Parent" name="Parent">
+        <translate sid="location">10 0 0</translate>
+        <node id="Corner" name="Corner">
+          <scale sid="scale">2 2 2</scale>
+          <instance_geometry url="#corner-mesh">
+            <bind_material><technique_common>
+              <instance_material symbol="Paint-symbol" target="#Paint-material"/>
+              <instance_material symbol="Wood-symbol" target="#Wood-material"/>
+            </technique_common></bind_material>
+          </instance_geometry>
+        </node>
+      </node>
+    </visual_scene>
+  </library_visual_scenes>
+  <scene><instance_visual_scene url="#Scene"/></scene>
+</COLLADA>
+"##;
+
+    #[test]
+    fn test_parse_xml() {
+        let document = parse_xml("<?xml version=\"1.0\"?>\n<!-- a comment -->\n<a x='1' y=\"&lt;2&gt;\">\n\
+                                  <b/><c>one &amp; <![CDATA[<two>]]></c></a>").unwrap();
+
+        assert_eq!((document.name.as_str(), document.attribute("y")), ("a", Some("<2>")));
+        assert_eq!(document.children.len(), 2);
+        assert_eq!(document.find(&["c"]).unwrap().text, "one & <two>");
+
+        assert!(parse_xml("<a><b></a>").unwrap_err().contains("closed by"));
+        assert!(parse_xml("<a>").unwrap_err().contains("not closed"));
+        assert!(parse_xml("<a x=1/>").unwrap_err().contains("not quoted"));
+    }
+
+    #[test]
+    fn test_parse() {
+        let mesh = parse(CORNER.as_bytes()).unwrap();
+        let object = &mesh.object;
+
+        // the corner is scaled, moved with its parent, and turned from z up
+        assert_eq!(object.geometric_vertices[1], Vector3::new(12.0, 0.0, 0.0));
+        assert_eq!(object.geometric_vertices[4], Vector3::new(10.0, 2.0, 0.0));
+        assert_eq!(object.normal_vertices[0], Vector3::new(0.0, 1.0, 0.0));
+
+        // the quad of the polylist is split in two before the triangle of the other material
+        assert_eq!(object.geometric_faces, vec![Vector3::new(0, 1, 2), Vector3::new(0, 2, 3), Vector3::new(5, 8, 9)]);
+        assert_eq!(object.texture_faces[2], Vector3::new(4, 7, 6));
+        assert_eq!(object.normal_faces[2], Vector3::new(3, 3, 3));
+        assert_eq!(object.groups, vec![wavefront::Group { name: String::from("Corner"), faces: 0..3 }]);
+
+        assert_eq!(mesh.face_materials, vec![Some(0), Some(0), Some(1)]);
+        assert_eq!(mesh.materials[0], MeshMaterial { name: String::from("Paint"), diffuse: Vector3::new(0.5, 0.25, 0.0),
+                                                     texture: None });
+        assert_eq!(mesh.materials[1].name, "Wood");
+        assert_eq!(mesh.texture(), Some("textures/wood.png"));
+    }
+
+    #[test]
+    fn test_units() {
+        let centimeters = CORNER.replace("<asset>", r#"<asset><unit name="centimeter" meter="0.01"/>"#);
+        let object = parse(centimeters.as_bytes()).unwrap().object;
+
+        assert!((object.geometric_vertices[1] - Vector3::new(0.12, 0.0, 0.0)).norm() < 1e-12);
+        assert!((object.geometric_vertices[4] - Vector3::new(0.1, 0.02, 0.0)).norm() < 1e-12);
+        assert_eq!(object.normal_vertices[0], Vector3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_invalid_documents() {
+        assert!(parse(b"<scene/>").unwrap_err().contains("rather than <COLLADA>"));
+        assert!(parse(b"<COLLADA></COLLADA>").unwrap_err().contains("no meshes"));
+
+        let out_of_range = CORNER.replace("<p>0 1 0 3 1 3 4 1 2</p>", "<p>0 1 0 3 1 3 9 1 2</p>");
+        assert!(parse(out_of_range.as_bytes()).unwrap_err().contains("out of range"));
+
+        let path = std::env::temp_dir().join("morpheus_corner.dae");
+        fs::write(&path, CORNER).unwrap();
+        assert_eq!(plugins::open(path.to_str().unwrap()).map(|object| object.geometric_faces.len()), Ok(3));
+    }
+}
@@ -0,0 +1,483 @@
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+use wavefront;
+
+
+/// The depth of the viewport of the lessons, which is also the range of their 8 bit depth buffer
+const LESSON_DEPTH: f32 = 255.0;
+
+/// The longest run of pixels in one packet of a run length encoded TGA file
+const TGA_MAX_CHUNK: usize = 128;
+
+const TGA_FOOTER: &[u8] = b"TRUEVISION-XFILE.\0";
+
+
+type Matrix = [[f32; 4]; 4];
+
+
+/// How the faces are shaded in the compatibility mode
+///
+/// Gouraud shades every face white by the light at its vertices as in lesson
+/// 6, and Textured multiplies the diffuse texture by the same light.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LessonShading {
+    Gouraud,
+    Textured
+}
+
+
+/// Options of a render that matches the reference tinyrenderer lessons
+///
+/// The defaults are the camera, light, and image size of the lessons.
+///
+/// # Examples
+///
+/// ```
+/// # use rastermind::compat::*;
+/// let options = LessonOptions { shading: LessonShading::Textured, ..Default::default() };
+/// ```
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LessonOptions {
+    pub width: u32,
+    pub height: u32,
+    pub eye: [f32; 3],
+    pub center: [f32; 3],
+    pub up: [f32; 3],
+    pub light: [f32; 3],
+    pub shading: LessonShading
+}
+
+
+impl Default for LessonOptions {
+    fn default() -> LessonOptions {
+        LessonOptions { width: 800, height: 800, eye: [1.0, 1.0, 3.0], center: [0.0, 0.0, 0.0],
+                        up: [0.0, 1.0, 0.0], light: [1.0, 1.0, 1.0], shading: LessonShading::Gouraud }
+    }
+}
+
+
+/// Render an Object exactly as the reference tinyrenderer lessons do
+///
+/// Every step is done in 32 bit floats in the order of operations of the
+/// lessons, including their lookat that translates by the center inside the
+/// rotation, truncating texture lookups, and an 8 bit depth buffer that starts
+/// at zero, so the color and depth images match the output of the lessons
+/// pixel for pixel. Both images have their first row at the bottom like every
+/// other framebuffer, which is where the lessons put their origin before they
+/// flip the images to write them.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate image;
+/// # extern crate rastermind;
+/// # use rastermind::compat;
+/// # use rastermind::compat::*;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// let (image, zbuffer) = compat::render(&coordinates, &texture, &LessonOptions::default());
+///
+/// compat::save_tga("output.tga", &image).unwrap();
+/// compat::save_tga_gray("zbuffer.tga", &zbuffer).unwrap();
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn render(coordinates: &wavefront::Object, texture: &image::RgbImage,
+              options: &LessonOptions) -> (image::RgbImage, image::GrayImage) {
+
+    let (width, height) = (options.width, options.height);
+    let mut buffer = image::RgbImage::new(width, height);
+    let mut zbuffer = image::GrayImage::new(width, height);
+
+    let model_view = lookat(&options.eye, &options.center, &options.up);
+    let projection = projection(-1.0 / norm(&subtract(&options.eye, &options.center)));
+    let view_port = viewport(width as i32 / 8, height as i32 / 8, width as i32 * 3 / 4, height as i32 * 3 / 4);
+    let transform = multiply(&multiply(&view_port, &projection), &model_view);
+    let light = normalize(&options.light);
+
+    for face_index in 0..coordinates.geometric_faces.len() {
+        let mut points = [[0.0; 4]; 3];
+        let mut intensities = [0.0; 3];
+        let mut uvs = [[0.0; 3]; 2];
+
+        for vertex_index in 0..3 {
+            let geometric_index = coordinates.geometric_faces[face_index][vertex_index] as usize;
+            let vertex = coordinates.geometric_vertices[geometric_index];
+            let normal = coordinates.normal_faces.get(face_index).map(|face| {
+                let normal = coordinates.normal_vertices[face[vertex_index] as usize];
+                normalize(&[normal.x as f32, normal.y as f32, normal.z as f32])
+            });
+
+            intensities[vertex_index] = 0.0f32.max(normal.map_or(0.0, |normal| dot(&normal, &light)));
+            points[vertex_index] = apply(&transform, &[vertex.x as f32, vertex.y as f32, vertex.z as f32, 1.0]);
+
+            if let Some(face) = coordinates.texture_faces.get(face_index) {
+                let uv = coordinates.texture_vertices[face[vertex_index] as usize];
+                uvs[0][vertex_index] = uv.x as f32;
+                uvs[1][vertex_index] = uv.y as f32;
+            }
+        }
+
+        triangle(&points, &mut buffer, &mut zbuffer, |bar| {
+            let intensity = dot(&intensities, bar);
+
+            let color = match options.shading {
+                LessonShading::Gouraud => image::Rgb([255, 255, 255]),
+                LessonShading::Textured => {
+                    let x = (dot(&uvs[0], bar) * texture.width() as f32) as i32;
+                    let y = (dot(&uvs[1], bar) * texture.height() as f32) as i32;
+
+                    if x < 0 || y < 0 || x >= texture.width() as i32 || y >= texture.height() as i32 {
+                        image::Rgb([0, 0, 0])
+                    } else {
+                        *texture.get_pixel(x as u32, y as u32)
+                    }
+                }
+            };
+
+            scale(color, intensity)
+        });
+    }
+
+    (buffer, zbuffer)
+}
+
+
+/// Rasterize a triangle in the order and precision of the lessons
+fn triangle<F>(points: &[[f32; 4]; 3], buffer: &mut image::RgbImage, zbuffer: &mut image::GrayImage,
+               fragment: F) where F: Fn(&[f32; 3]) -> image::Rgb<u8> {
+
+    let projected: Vec<[f32; 2]> = points.iter().map(|point| [point[0] / point[3], point[1] / point[3]]).collect();
+
+    let mut minimum = [f32::MAX, f32::MAX];
+    let mut maximum = [-f32::MAX, -f32::MAX];
+
+    for point in &projected {
+        for j in 0..2 {
+            minimum[j] = minimum[j].min(point[j]);
+            maximum[j] = maximum[j].max(point[j]);
+        }
+    }
+
+    let mut x = minimum[0] as i32;
+
+    while x as f32 <= maximum[0] {
+        let mut y = minimum[1] as i32;
+
+        while y as f32 <= maximum[1] {
+            let bar = barycentric(&projected, &[x as f32, y as f32]);
+            let z = points[0][2] * bar[0] + points[1][2] * bar[1] + points[2][2] * bar[2];
+            let w = points[0][3] * bar[0] + points[1][3] * bar[1] + points[2][3] * bar[2];
+            // the lessons add a double to the float, so the rounding happens in double precision
+            let depth = ((z / w) as f64 + 0.5) as i32;
+            let depth = depth.clamp(0, LESSON_DEPTH as i32) as u8;
+
+            // pixels outside of the image read a depth of zero and are never written
+            let inside = x >= 0 && y >= 0 && x < buffer.width() as i32 && y < buffer.height() as i32;
+
+            if inside && bar.iter().all(|&value| value >= 0.0) &&
+               zbuffer.get_pixel(x as u32, y as u32)[0] <= depth {
+
+                zbuffer.put_pixel(x as u32, y as u32, image::Luma([depth]));
+                buffer.put_pixel(x as u32, y as u32, fragment(&bar));
+            }
+
+            y += 1;
+        }
+
+        x += 1;
+    }
+}
+
+
+fn barycentric(points: &[[f32; 2]], point: &[f32; 2]) -> [f32; 3] {
+    let mut s = [[0.0; 3]; 2];
+
+    for i in (0..2).rev() {
+        s[i] = [points[2][i] - points[0][i], points[1][i] - points[0][i], points[0][i] - point[i]];
+    }
+
+    let u = cross(&s[0], &s[1]);
+
+    if u[2].abs() > 1e-2 {
+        [1.0 - (u[0] + u[1]) / u[2], u[1] / u[2], u[0] / u[2]]
+    } else {
+        [-1.0, 1.0, 1.0]
+    }
+}
+
+
+/// Scale a color by an intensity clamped to [0, 1], truncating like the lessons
+fn scale(color: image::Rgb<u8>, intensity: f32) -> image::Rgb<u8> {
+    let intensity = intensity.clamp(0.0, 1.0);
+
+    image::Rgb([(color[0] as f32 * intensity) as u8, (color[1] as f32 * intensity) as u8,
+                (color[2] as f32 * intensity) as u8])
+}
+
+
+fn lookat(eye: &[f32; 3], center: &[f32; 3], up: &[f32; 3]) -> Matrix {
+    let z = normalize(&subtract(eye, center));
+    let x = normalize(&cross(up, &z));
+    let y = normalize(&cross(&z, &x));
+
+    let mut matrix = identity();
+
+    for i in 0..3 {
+        matrix[0][i] = x[i];
+        matrix[1][i] = y[i];
+        matrix[2][i] = z[i];
+        matrix[i][3] = -center[i];
+    }
+
+    matrix
+}
+
+
+fn projection(coefficient: f32) -> Matrix {
+    let mut matrix = identity();
+    matrix[3][2] = coefficient;
+
+    matrix
+}
+
+
+fn viewport(x: i32, y: i32, width: i32, height: i32) -> Matrix {
+    let mut matrix = identity();
+
+    matrix[0][3] = x as f32 + width as f32 / 2.0;
+    matrix[1][3] = y as f32 + height as f32 / 2.0;
+    matrix[2][3] = LESSON_DEPTH / 2.0;
+
+    matrix[0][0] = width as f32 / 2.0;
+    matrix[1][1] = height as f32 / 2.0;
+    matrix[2][2] = LESSON_DEPTH / 2.0;
+
+    matrix
+}
+
+
+fn identity() -> Matrix {
+    let mut matrix = [[0.0; 4]; 4];
+    (0..4).for_each(|i| matrix[i][i] = 1.0);
+
+    matrix
+}
+
+
+/// Multiply two matrices, summing every product from the first column as the lessons do
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut matrix = [[0.0; 4]; 4];
+
+    for i in 0..4 {
+        for j in 0..4 {
+            matrix[i][j] = dot(&a[i], &[b[0][j], b[1][j], b[2][j], b[3][j]]);
+        }
+    }
+
+    matrix
+}
+
+
+fn apply(matrix: &Matrix, vector: &[f32; 4]) -> [f32; 4] {
+    [dot(&matrix[0], vector), dot(&matrix[1], vector), dot(&matrix[2], vector), dot(&matrix[3], vector)]
+}
+
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).fold(0.0, |sum, (a, b)| sum + a * b)
+}
+
+
+fn cross(a: &[f32; 3], b: &[f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+
+fn subtract(a: &[f32; 3], b: &[f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+
+fn norm(vector: &[f32; 3]) -> f32 {
+    (vector[0] * vector[0] + vector[1] * vector[1] + vector[2] * vector[2]).sqrt()
+}
+
+
+/// Normalize a vector by multiplying it with the reciprocal of its length as the lessons do
+fn normalize(vector: &[f32; 3]) -> [f32; 3] {
+    let factor = 1.0 / norm(vector);
+
+    [vector[0] * factor, vector[1] * factor, vector[2] * factor]
+}
+
+
+/// Save the framebuffer as a run length encoded TGA file byte for byte like the lessons
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate image;
+/// # extern crate rastermind;
+/// # use rastermind::compat::save_tga;
+/// # let image = image::RgbImage::new(8, 8);
+/// save_tga("output.tga", &image).unwrap();
+/// ```
+///
+pub fn save_tga<P: AsRef<Path>>(path: P, buffer: &image::RgbImage) -> io::Result<()> {
+    // TGA stores pixels in blue, green, red order
+    let pixels: Vec<u8> = buffer.rows()
+                                .rev()
+                                .flat_map(|row| row.flat_map(|pixel| vec![pixel[2], pixel[1], pixel[0]]))
+                                .collect();
+
+    write_tga(path, buffer.width(), buffer.height(), 3, &pixels)
+}
+
+
+/// Save the depth buffer as a run length encoded grayscale TGA file byte for byte like the lessons
+pub fn save_tga_gray<P: AsRef<Path>>(path: P, buffer: &image::GrayImage) -> io::Result<()> {
+    let pixels: Vec<u8> = buffer.rows().rev().flat_map(|row| row.map(|pixel| pixel[0])).collect();
+
+    write_tga(path, buffer.width(), buffer.height(), 1, &pixels)
+}
+
+
+/// Write pixels stored from the top row down with the header, packets, and footer the lessons write
+fn write_tga<P: AsRef<Path>>(path: P, width: u32, height: u32, bytes_per_pixel: usize,
+                             pixels: &[u8]) -> io::Result<()> {
+
+    let mut bytes = vec![0, 0, if bytes_per_pixel == 1 { 11 } else { 10 }, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    bytes.extend_from_slice(&(width as u16).to_le_bytes());
+    bytes.extend_from_slice(&(height as u16).to_le_bytes());
+    // the bits per pixel and the descriptor that places the origin at the top left
+    bytes.extend_from_slice(&[(bytes_per_pixel * 8) as u8, 0x20]);
+
+    bytes.extend(run_length(pixels, bytes_per_pixel));
+
+    bytes.extend_from_slice(&[0; 8]);
+    bytes.extend_from_slice(TGA_FOOTER);
+
+    File::create(path)?.write_all(&bytes)
+}
+
+
+/// Split the pixels into raw and repeated packets with the greedy encoder of the lessons
+fn run_length(pixels: &[u8], bytes_per_pixel: usize) -> Vec<u8> {
+    let count = pixels.len() / bytes_per_pixel;
+    let pixel = |index: usize| &pixels[index * bytes_per_pixel..(index + 1) * bytes_per_pixel];
+
+    let mut bytes = Vec::new();
+    let mut current = 0;
+
+    while current < count {
+        let mut length = 1;
+        let mut raw = true;
+
+        while current + length < count && length < TGA_MAX_CHUNK {
+            let equal = pixel(current + length - 1) == pixel(current + length);
+
+            if length == 1 {
+                raw = !equal;
+            }
+
+            if raw && equal {
+                length -= 1;
+                break;
+            }
+
+            if !raw && !equal {
+                break;
+            }
+
+            length += 1;
+        }
+
+        if raw {
+            bytes.push((length - 1) as u8);
+            bytes.extend_from_slice(&pixels[current * bytes_per_pixel..(current + length) * bytes_per_pixel]);
+        } else {
+            bytes.push((length + 127) as u8);
+            bytes.extend_from_slice(pixel(current));
+        }
+
+        current += length;
+    }
+
+    bytes
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matrices() {
+        let model_view = lookat(&[1.0, 1.0, 3.0], &[0.0, 0.0, 0.0], &[0.0, 1.0, 0.0]);
+        let view_port = viewport(100, 100, 600, 600);
+
+        assert_eq!(view_port[0][3], 400.0);
+        assert_eq!(view_port[2][2], 127.5);
+        assert_eq!(projection(-0.5)[3][2], -0.5);
+        assert_eq!(model_view[2][..3], normalize(&[1.0, 1.0, 3.0]));
+        assert_eq!(multiply(&identity(), &view_port), view_port);
+    }
+
+    #[test]
+    fn test_render() {
+        let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 0 1\n\
+                                                          vn 0 0 1\nf 1/1/1 2/2/1 3/3/1\n",
+                                                        &wavefront::LoadOptions::default()).unwrap();
+        let options = LessonOptions { width: 64, height: 64, eye: [0.0, 0.0, 3.0], light: [0.0, 0.0, 1.0],
+                                      ..Default::default() };
+
+        let (image, zbuffer) = render(&coordinates, &image::RgbImage::new(1, 1), &options);
+
+        assert_eq!(*image.get_pixel(32, 32), image::Rgb([255, 255, 255]));
+        assert_eq!(*image.get_pixel(2, 2), image::Rgb([0, 0, 0]));
+        // the plane through the center sits in the middle of the 255 deep viewport
+        assert_eq!(zbuffer.get_pixel(32, 32)[0], 128);
+
+        let mut texture = image::RgbImage::new(2, 2);
+        texture.put_pixel(0, 0, image::Rgb([200, 100, 50]));
+        let textured = LessonOptions { shading: LessonShading::Textured, ..options };
+        let (image, _) = render(&coordinates, &texture, &textured);
+
+        assert_eq!(*image.get_pixel(30, 20), image::Rgb([200, 100, 50]));
+    }
+
+    #[test]
+    fn test_run_length() {
+        // a run of three equal pixels followed by two different pixels
+        assert_eq!(run_length(&[7, 7, 7, 1, 2], 1), vec![130, 7, 1, 1, 2]);
+        assert_eq!(run_length(&[1, 2, 3, 3], 1), vec![1, 1, 2, 129, 3]);
+        assert_eq!(run_length(&[5; 130], 1), vec![255, 5, 129, 5]);
+    }
+
+    #[test]
+    fn test_save_tga() {
+        let mut path = std::env::temp_dir();
+        path.push("test_save_tga.tga");
+
+        let mut buffer = image::RgbImage::new(2, 2);
+        buffer.put_pixel(0, 1, image::Rgb([255, 0, 0]));
+        save_tga(&path, &buffer).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(&bytes[..18], &[0, 0, 10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 2, 0, 24, 0x20]);
+        // the top row comes first with the red pixel in blue, green, red order
+        assert_eq!(&bytes[18..22], &[0, 0, 0, 255]);
+        assert!(bytes.ends_with(b"TRUEVISION-XFILE.\0"));
+    }
+}
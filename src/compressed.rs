@@ -0,0 +1,869 @@
+use std::borrow::Cow;
+use std::fs;
+use std::io;
+use std::io::Read;
+
+#[cfg(feature = "basis")]
+use basis_universal::{Transcoder, TranscoderTextureFormat};
+use flate2::read::ZlibDecoder;
+#[cfg(feature = "basis")]
+use ruzstd::decoding::StreamingDecoder;
+
+
+/// The magic number that starts every DDS file
+const DDS_MAGIC: &[u8; 4] = b"DDS ";
+
+/// The identifier that starts every KTX2 file
+const KTX2_IDENTIFIER: [u8; 12] = [0xab, 0x4b, 0x54, 0x58, 0x20, 0x32, 0x30, 0xbb, 0x0d, 0x0a, 0x1a, 0x0a];
+
+/// The flags of the pixel format of a DDS file
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_ALPHA: u32 = 0x2;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_LUMINANCE: u32 = 0x20000;
+
+/// The flag of a DDS header with the number of bytes in every row
+const DDSD_PITCH: u32 = 0x8;
+
+/// The supercompression schemes of KTX2 files
+const KTX2_BASIS_LZ: u32 = 1;
+const KTX2_ZSTANDARD: u32 = 2;
+const KTX2_ZLIB: u32 = 3;
+
+/// The color model of the data format descriptor of a KTX2 file that holds Basis Universal UASTC data
+const KHR_DF_MODEL_UASTC: u8 = 166;
+
+/// The version and the sizes of the header and of a slice description of the .basis files that are transcoded
+#[cfg(feature = "basis")]
+const BASIS_VERSION: u8 = 0x13;
+#[cfg(feature = "basis")]
+const BASIS_HEADER_SIZE: usize = 77;
+#[cfg(feature = "basis")]
+const BASIS_SLICE_SIZE: usize = 23;
+
+/// The flags of the header of a .basis file
+#[cfg(feature = "basis")]
+const BASIS_ETC1S: usize = 0x1;
+#[cfg(feature = "basis")]
+const BASIS_ALPHA_SLICES: usize = 0x4;
+
+
+/// A block compression format, where every 4x4 block of pixels is stored in 8 or 16 bytes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Block {
+    /// Two colors and 2 bit indices between them, with an optional transparent index
+    Bc1,
+    /// A Bc1 color block after a block of 4 bit alphas
+    Bc2,
+    /// A Bc1 color block after a block of interpolated alphas
+    Bc3,
+    /// A single interpolated channel, which is gray
+    Bc4,
+    /// Two interpolated channels, which are the x and y of a normal map
+    Bc5
+}
+
+
+/// How the pixels of a texture are stored
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Block(Block),
+    /// Pixels of the given number of bytes with the red, green, blue, and alpha channels in the given bits,
+    /// where the red channel is copied to the others when the pixels are gray
+    Pixels { bytes: usize, masks: [u32; 4], gray: bool }
+}
+
+
+/// The encoding of Basis Universal data, which is transcoded instead of decoded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Basis {
+    /// ETC1S blocks, supercompressed with BasisLZ against codebooks shared by the whole file
+    Etc1s,
+    /// UASTC blocks of 16 bytes
+    Uastc
+}
+
+
+impl Format {
+    /// The number of bytes needed by an image of the given size, or None if it does not fit in memory
+    fn size(&self, width: u32, height: u32) -> Option<usize> {
+        let blocks = (width as usize).div_ceil(4).checked_mul((height as usize).div_ceil(4))?;
+
+        match *self {
+            Format::Block(Block::Bc1) | Format::Block(Block::Bc4) => blocks.checked_mul(8),
+            Format::Block(_) => blocks.checked_mul(16),
+            Format::Pixels { bytes, .. } => bytes.checked_mul(width as usize)?.checked_mul(height as usize)
+        }
+    }
+}
+
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+
+#[cfg(not(feature = "basis"))]
+fn unsupported(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, message)
+}
+
+
+fn u32_at(bytes: &[u8], offset: usize) -> io::Result<u32> {
+    bytes.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                                 .ok_or_else(|| invalid("unexpected end of texture data"))
+}
+
+
+fn u64_at(bytes: &[u8], offset: usize) -> io::Result<usize> {
+    Ok((u32_at(bytes, offset)? as u64 | (u32_at(bytes, offset + 4)? as u64) << 32) as usize)
+}
+
+
+/// Whether the given bytes hold a DDS or KTX2 texture container
+pub fn is_container(bytes: &[u8]) -> bool {
+    bytes.starts_with(DDS_MAGIC) || bytes.starts_with(&KTX2_IDENTIFIER)
+}
+
+
+/// Load the largest image of the DDS or KTX2 texture in the given file
+///
+/// # Examples
+///
+/// ```no_run
+/// # use rastermind::compressed;
+/// let image = compressed::open("albedo.dds").unwrap();
+/// ```
+///
+pub fn open(filename: &str) -> io::Result<image::RgbaImage> {
+    from_bytes(&fs::read(filename)?)
+}
+
+
+/// Load the largest image of the DDS or KTX2 texture contained in the given bytes
+///
+/// Block compressed textures in the BC1 to BC5 formats and uncompressed 8
+/// bit textures are decompressed on the CPU, and only the first layer, face,
+/// and slice is read. The rows are kept in the order of the file, with the
+/// first row at the top.
+///
+/// # Examples
+///
+/// ```ignore
+/// let image = compressed::from_bytes(include_bytes!("albedo.ktx2")).unwrap();
+/// ```
+///
+pub fn from_bytes(bytes: &[u8]) -> io::Result<image::RgbaImage> {
+    if bytes.starts_with(DDS_MAGIC) {
+        dds_from_bytes(bytes)
+    } else if bytes.starts_with(&KTX2_IDENTIFIER) {
+        ktx2_from_bytes(bytes)
+    } else {
+        Err(invalid("the texture is neither a DDS nor a KTX2 file"))
+    }
+}
+
+
+/// Load the largest image of a DDS texture
+///
+/// The DXT1 to DXT5, ATI1, and ATI2 formats are read from the pixel format,
+/// and the BC1 to BC5 and 8 bit RGBA and BGRA formats from the DX10 header.
+/// Uncompressed pixel formats are read through their channel masks.
+///
+pub fn dds_from_bytes(bytes: &[u8]) -> io::Result<image::RgbaImage> {
+    if !bytes.starts_with(DDS_MAGIC) {
+        return Err(invalid("the texture is not a DDS file"));
+    }
+
+    let (flags, height, width, pitch) = (u32_at(bytes, 8)?, u32_at(bytes, 12)?, u32_at(bytes, 16)?, u32_at(bytes, 20)?);
+    let (pixel_flags, four_cc, bits) = (u32_at(bytes, 80)?, bytes.get(84..88).unwrap_or(&[]), u32_at(bytes, 88)?);
+    let masks = [u32_at(bytes, 92)?, u32_at(bytes, 96)?, u32_at(bytes, 100)?, u32_at(bytes, 104)?];
+    let mut start = 128;
+
+    let format = if pixel_flags & DDPF_FOURCC == 0 {
+        let alpha = if pixel_flags & (DDPF_ALPHAPIXELS | DDPF_ALPHA) != 0 { masks[3] } else { 0 };
+        let masks = if pixel_flags & DDPF_ALPHA != 0 && pixel_flags & DDPF_LUMINANCE == 0 {
+            [0, 0, 0, alpha]
+        } else {
+            [masks[0], masks[1], masks[2], alpha]
+        };
+
+        Format::Pixels { bytes: bits as usize / 8, masks, gray: pixel_flags & DDPF_LUMINANCE != 0 }
+    } else {
+        match four_cc {
+            b"DXT1" => Format::Block(Block::Bc1),
+            b"DXT2" | b"DXT3" => Format::Block(Block::Bc2),
+            b"DXT4" | b"DXT5" => Format::Block(Block::Bc3),
+            b"ATI1" | b"BC4U" => Format::Block(Block::Bc4),
+            b"ATI2" | b"BC5U" => Format::Block(Block::Bc5),
+            b"DX10" => {
+                start += 20;
+
+                dxgi_format(u32_at(bytes, 128)?)?
+            }
+            _ => return Err(invalid(&format!("unsupported DDS format {}", String::from_utf8_lossy(four_cc))))
+        }
+    };
+
+    match format {
+        Format::Pixels { bytes: size, .. } if size == 0 || size > 4 => Err(invalid("unsupported DDS pixel size")),
+        Format::Pixels { bytes: size, .. } if flags & DDSD_PITCH != 0 && pitch as usize > size * width as usize => {
+            // rows padded past the pixels they hold are packed together first
+            let rows: Vec<u8> = bytes.get(start..).unwrap_or(&[]).chunks(pitch as usize).take(height as usize)
+                                     .flat_map(|row| row.iter().take(size * width as usize).cloned()).collect();
+
+            decode(&rows, width, height, format)
+        }
+        _ => decode(bytes.get(start..).unwrap_or(&[]), width, height, format)
+    }
+}
+
+
+/// The Format of the given DXGI format of a DDS file with a DX10 header
+fn dxgi_format(format: u32) -> io::Result<Format> {
+    let rgba = [0xff, 0xff00, 0xff_0000, 0xff00_0000];
+
+    Ok(match format {
+        28 | 29 => Format::Pixels { bytes: 4, masks: rgba, gray: false },
+        87 | 91 => Format::Pixels { bytes: 4, masks: [0xff_0000, 0xff00, 0xff, 0xff00_0000], gray: false },
+        88 | 93 => Format::Pixels { bytes: 4, masks: [0xff_0000, 0xff00, 0xff, 0], gray: false },
+        61 => Format::Pixels { bytes: 1, masks: [0xff, 0, 0, 0], gray: true },
+        71 | 72 => Format::Block(Block::Bc1),
+        74 | 75 => Format::Block(Block::Bc2),
+        77 | 78 => Format::Block(Block::Bc3),
+        80 | 81 => Format::Block(Block::Bc4),
+        83 | 84 => Format::Block(Block::Bc5),
+        _ => return Err(invalid(&format!("unsupported DXGI format {}", format)))
+    })
+}
+
+
+/// Load the largest image of a KTX2 texture
+///
+/// The BC1 to BC5 formats and the 8 bit R, RG, RGB, RGBA, and BGRA formats
+/// are read, including levels supercompressed with zlib.
+///
+/// Basis Universal textures, in either the ETC1S or the UASTC encoding, and
+/// levels supercompressed with Zstandard are read by builds with the `basis`
+/// feature, which transcodes them to RGBA pixels. Other builds fail on them
+/// with an error of the `Unsupported` kind that names what the texture holds,
+/// so they can be told apart from broken files.
+///
+pub fn ktx2_from_bytes(bytes: &[u8]) -> io::Result<image::RgbaImage> {
+    if !bytes.starts_with(&KTX2_IDENTIFIER) {
+        return Err(invalid("the texture is not a KTX2 file"));
+    }
+
+    let (vk_format, width, height) = (u32_at(bytes, 12)?, u32_at(bytes, 20)?, u32_at(bytes, 24)?.max(1));
+    let supercompression = u32_at(bytes, 44)?;
+    let (offset, length) = (u64_at(bytes, 80)?, u64_at(bytes, 88)?);
+
+    let level = offset.checked_add(length).and_then(|end| bytes.get(offset..end))
+                      .ok_or_else(|| invalid("unexpected end of texture data"))?;
+
+    let level = match supercompression {
+        0 | KTX2_BASIS_LZ => Cow::Borrowed(level),
+        KTX2_ZSTANDARD => Cow::Owned(zstandard(level)?),
+        KTX2_ZLIB => {
+            let mut inflated = Vec::new();
+            ZlibDecoder::new(level).read_to_end(&mut inflated)?;
+
+            Cow::Owned(inflated)
+        }
+        _ => return Err(invalid(&format!("unsupported supercompression scheme {}", supercompression)))
+    };
+
+    // ETC1S data is always supercompressed with BasisLZ, while UASTC data is only marked by its color model
+    if supercompression == KTX2_BASIS_LZ {
+        return transcode(bytes, &level, Basis::Etc1s, width, height);
+    }
+
+    if vk_format == 0 {
+        let model = u32_at(bytes, 48).ok().and_then(|descriptor| bytes.get(descriptor as usize + 12).cloned());
+
+        return match model {
+            Some(KHR_DF_MODEL_UASTC) => transcode(bytes, &level, Basis::Uastc, width, height),
+            _ => Err(invalid("the texture has no format"))
+        };
+    }
+
+    decode(&level, width, height, vulkan_format(vk_format)?)
+}
+
+
+/// The Format of the given Vulkan format of a KTX2 file
+fn vulkan_format(format: u32) -> io::Result<Format> {
+    Ok(match format {
+        9 | 15 => Format::Pixels { bytes: 1, masks: [0xff, 0, 0, 0], gray: false },
+        16 | 22 => Format::Pixels { bytes: 2, masks: [0xff, 0xff00, 0, 0], gray: false },
+        23 | 29 => Format::Pixels { bytes: 3, masks: [0xff, 0xff00, 0xff_0000, 0], gray: false },
+        30 | 36 => Format::Pixels { bytes: 3, masks: [0xff_0000, 0xff00, 0xff, 0], gray: false },
+        37 | 43 => Format::Pixels { bytes: 4, masks: [0xff, 0xff00, 0xff_0000, 0xff00_0000], gray: false },
+        44 | 50 => Format::Pixels { bytes: 4, masks: [0xff_0000, 0xff00, 0xff, 0xff00_0000], gray: false },
+        131..=134 => Format::Block(Block::Bc1),
+        135 | 136 => Format::Block(Block::Bc2),
+        137 | 138 => Format::Block(Block::Bc3),
+        139 | 140 => Format::Block(Block::Bc4),
+        141 | 142 => Format::Block(Block::Bc5),
+        _ => return Err(invalid(&format!("unsupported Vulkan format {}", format)))
+    })
+}
+
+
+/// Inflate a level supercompressed with Zstandard
+#[cfg(feature = "basis")]
+fn zstandard(level: &[u8]) -> io::Result<Vec<u8>> {
+    let mut inflated = Vec::new();
+    StreamingDecoder::new(level).map_err(|error| invalid(&error.to_string()))?.read_to_end(&mut inflated)?;
+
+    Ok(inflated)
+}
+
+
+#[cfg(not(feature = "basis"))]
+fn zstandard(_: &[u8]) -> io::Result<Vec<u8>> {
+    Err(unsupported("the texture is supercompressed with Zstandard, which needs the basis feature"))
+}
+
+
+/// Transcode the given level of a KTX2 file with Basis Universal data to RGBA pixels
+#[cfg(feature = "basis")]
+fn transcode(bytes: &[u8], level: &[u8], basis: Basis, width: u32, height: u32) -> io::Result<image::RgbaImage> {
+    let file = basis_file(bytes, level, basis, width, height)?;
+    let mut transcoder = Transcoder::new();
+
+    transcoder.prepare_transcoding(&file).map_err(|_| invalid("the Basis Universal codebooks are broken"))?;
+
+    let pixels = transcoder.transcode_image_level(&file, TranscoderTextureFormat::RGBA32, Default::default())
+                           .map_err(|error| invalid(&format!("the Basis Universal data is broken: {:?}", error)))?;
+
+    image::RgbaImage::from_raw(width, height, pixels).ok_or_else(|| invalid("the Basis Universal data is broken"))
+}
+
+
+#[cfg(not(feature = "basis"))]
+fn transcode(_: &[u8], _: &[u8], basis: Basis, _: u32, _: u32) -> io::Result<image::RgbaImage> {
+    let name = if basis == Basis::Etc1s { "ETC1S" } else { "UASTC" };
+
+    Err(unsupported(&format!("the texture holds Basis Universal {} data, which needs the basis feature", name)))
+}
+
+
+/// Wrap the given level of a KTX2 file in the .basis file that the Basis Universal transcoder reads
+///
+/// The file holds the level as a single image, after the codebooks and
+/// tables from the global data of the KTX2 file for ETC1S. The alpha of an
+/// ETC1S image is a second slice of the same size.
+///
+#[cfg(feature = "basis")]
+fn basis_file(bytes: &[u8], level: &[u8], basis: Basis, width: u32, height: u32) -> io::Result<Vec<u8>> {
+    if width > 0xffff || height > 0xffff {
+        return Err(invalid("the texture is too large for Basis Universal"));
+    }
+
+    let (blocks_x, blocks_y) = (width.div_ceil(4), height.div_ceil(4));
+    let slice = |offset: u32, length: u32| {
+        level.get(offset as usize..offset as usize + length as usize)
+             .ok_or_else(|| invalid("unexpected end of texture data"))
+    };
+
+    // the global data starts with the sizes of the codebooks, the tables, and the descriptions of the images
+    let (global, slices) = match basis {
+        Basis::Etc1s => {
+            let (offset, length) = (u64_at(bytes, 64)?, u64_at(bytes, 72)?);
+            let global = offset.checked_add(length).and_then(|end| bytes.get(offset..end))
+                               .ok_or_else(|| invalid("unexpected end of texture data"))?;
+            let lengths = [u32_at(global, 4)?, u32_at(global, 8)?, u32_at(global, 12)?, u32_at(global, 16)?];
+            let codebooks = lengths.iter().try_fold(0usize, |total, &length| total.checked_add(length as usize))
+                                   .and_then(|total| global.len().checked_sub(total))
+                                   .filter(|&start| start >= 40)
+                                   .ok_or_else(|| invalid("the Basis Universal global data is broken"))?;
+
+            let mut slices = vec![slice(u32_at(global, 24)?, u32_at(global, 28)?)?];
+
+            if u32_at(global, 36)? > 0 {
+                slices.push(slice(u32_at(global, 32)?, u32_at(global, 36)?)?);
+            }
+
+            ((u32_at(global, 0)?, lengths, &global[codebooks..]), slices)
+        }
+        Basis::Uastc => {
+            if (level.len() as u64) < blocks_x as u64 * blocks_y as u64 * 16 {
+                return Err(invalid("unexpected end of texture data"));
+            }
+
+            ((0, [0; 4], &[][..]), vec![level])
+        }
+    };
+
+    let (counts, lengths, codebooks) = global;
+    let start = BASIS_HEADER_SIZE + BASIS_SLICE_SIZE * slices.len();
+    let mut file = vec![0; start];
+    let put = |file: &mut Vec<u8>, offset: usize, size: usize, value: usize| {
+        file[offset..offset + size].copy_from_slice(&(value as u32).to_le_bytes()[..size]);
+    };
+
+    file.extend_from_slice(codebooks);
+
+    for (index, data) in slices.iter().enumerate() {
+        let (descriptor, offset) = (BASIS_HEADER_SIZE + BASIS_SLICE_SIZE * index, file.len());
+
+        put(&mut file, descriptor + 4, 1, index);
+        put(&mut file, descriptor + 5, 2, width as usize);
+        put(&mut file, descriptor + 7, 2, height as usize);
+        put(&mut file, descriptor + 9, 2, blocks_x as usize);
+        put(&mut file, descriptor + 11, 2, blocks_y as usize);
+        put(&mut file, descriptor + 13, 4, offset);
+        put(&mut file, descriptor + 17, 4, data.len());
+        put(&mut file, descriptor + 21, 2, crc16(data) as usize);
+
+        file.extend_from_slice(data);
+    }
+
+    let (etc1s, alpha) = (basis == Basis::Etc1s, slices.len() > 1);
+    let flags = if etc1s { BASIS_ETC1S } else { 0 } | if alpha { BASIS_ALPHA_SLICES } else { 0 };
+    // the codebooks, tables, and extended data are stored back to back, in that order
+    let offsets = lengths.iter().scan(start, |offset, &length| {
+        *offset += length as usize;
+        Some(*offset - length as usize)
+    }).collect::<Vec<_>>();
+
+    file[..6].copy_from_slice(&[b's', b'B', BASIS_VERSION, 0, BASIS_HEADER_SIZE as u8, 0]);
+
+    let data_size = file.len() - BASIS_HEADER_SIZE;
+    let data_crc = crc16(&file[BASIS_HEADER_SIZE..]);
+
+    put(&mut file, 8, 4, data_size);
+    put(&mut file, 12, 2, data_crc as usize);
+    put(&mut file, 14, 3, slices.len());
+    put(&mut file, 17, 3, 1);
+    put(&mut file, 20, 1, if etc1s { 0 } else { 1 });
+    put(&mut file, 21, 2, flags);
+    put(&mut file, 39, 2, counts as usize & 0xffff);
+    put(&mut file, 41, 4, offsets[0]);
+    put(&mut file, 45, 3, lengths[0] as usize);
+    put(&mut file, 48, 2, counts as usize >> 16);
+    put(&mut file, 50, 4, offsets[1]);
+    put(&mut file, 54, 3, lengths[1] as usize);
+    put(&mut file, 57, 4, offsets[2]);
+    put(&mut file, 61, 4, lengths[2] as usize);
+    put(&mut file, 65, 4, BASIS_HEADER_SIZE);
+    put(&mut file, 69, 4, offsets[3]);
+    put(&mut file, 73, 4, lengths[3] as usize);
+
+    let header_crc = crc16(&file[8..BASIS_HEADER_SIZE]);
+    put(&mut file, 6, 2, header_crc as usize);
+
+    Ok(file)
+}
+
+
+/// The CRC-16 that .basis files hold of their header and data
+#[cfg(feature = "basis")]
+fn crc16(bytes: &[u8]) -> u16 {
+    !bytes.iter().fold(!0u16, |crc, &byte| {
+        let q = byte as u16 ^ crc >> 8;
+        let k = q >> 4 ^ q;
+
+        crc << 8 ^ k ^ k << 5 ^ k << 12
+    })
+}
+
+
+/// Decode the pixels of an image of the given size and Format
+///
+/// The size comes from the header of the file, so it is checked against the
+/// data before the image is allocated, and sizes larger than the data fail
+/// instead of overflowing or allocating more than the file could fill.
+///
+fn decode(data: &[u8], width: u32, height: u32, format: Format) -> io::Result<image::RgbaImage> {
+    let size = format.size(width, height).filter(|&size| size <= data.len())
+                     .ok_or_else(|| invalid("unexpected end of texture data"))?;
+
+    let mut image = image::RgbaImage::new(width, height);
+
+    match format {
+        Format::Pixels { bytes, masks, gray } => {
+            for (index, pixel) in data.chunks(bytes).take(width as usize * height as usize).enumerate() {
+                let value = pixel.iter().rev().fold(0u32, |value, &byte| value << 8 | byte as u32);
+                let mut color = [0, 1, 2, 3].map(|i| channel(value, masks[i]).unwrap_or(if i == 3 { 255 } else { 0 }));
+
+                if gray {
+                    color = [color[0], color[0], color[0], color[3]];
+                }
+
+                image.put_pixel(index as u32 % width, index as u32 / width, image::Rgba(color));
+            }
+        }
+        Format::Block(block) => {
+            let block_size = if block == Block::Bc1 || block == Block::Bc4 { 8 } else { 16 };
+            let across = width.div_ceil(4);
+
+            for (index, data) in data.chunks(block_size).take(size / block_size).enumerate() {
+                let (block_x, block_y) = (index as u32 % across * 4, index as u32 / across * 4);
+                let pixels = decode_block(data, block);
+
+                for (i, color) in pixels.iter().enumerate() {
+                    let (x, y) = (block_x + i as u32 % 4, block_y + i as u32 / 4);
+
+                    if x < width && y < height {
+                        image.put_pixel(x, y, image::Rgba(*color));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(image)
+}
+
+
+/// Scale the bits of the given mask in a pixel to 8 bits, or None if the mask is empty
+fn channel(value: u32, mask: u32) -> Option<u8> {
+    if mask == 0 {
+        return None;
+    }
+
+    let maximum = (mask >> mask.trailing_zeros()) as u64;
+
+    Some((((value & mask) >> mask.trailing_zeros()) as u64 * 255 / maximum) as u8)
+}
+
+
+/// Decode the 16 pixels of a compressed block, row by row
+fn decode_block(data: &[u8], block: Block) -> [[u8; 4]; 16] {
+    let mut pixels = [[0, 0, 0, 255]; 16];
+
+    match block {
+        Block::Bc1 => pixels = color_block(data, true),
+        Block::Bc2 => {
+            pixels = color_block(&data[8..], false);
+
+            for (i, pixel) in pixels.iter_mut().enumerate() {
+                pixel[3] = (data[i / 2] >> (4 * (i % 2)) & 0xf) * 17;
+            }
+        }
+        Block::Bc3 => {
+            pixels = color_block(&data[8..], false);
+            let alphas = interpolated_block(data);
+
+            (0..16).for_each(|i| pixels[i][3] = alphas[i]);
+        }
+        Block::Bc4 => {
+            let values = interpolated_block(data);
+
+            (0..16).for_each(|i| pixels[i] = [values[i], values[i], values[i], 255]);
+        }
+        Block::Bc5 => {
+            let (xs, ys) = (interpolated_block(data), interpolated_block(&data[8..]));
+
+            for i in 0..16 {
+                // the z of the unit normal is rebuilt from its x and y
+                let (x, y) = (xs[i] as f64 / 127.5 - 1.0, ys[i] as f64 / 127.5 - 1.0);
+                let z = (1.0 - x * x - y * y).max(0.0).sqrt();
+
+                pixels[i] = [xs[i], ys[i], ((z + 1.0) * 127.5).round() as u8, 255];
+            }
+        }
+    }
+
+    pixels
+}
+
+
+/// Decode the colors of a BC1 block, where a block with its colors in ascending order may have transparent pixels
+fn color_block(data: &[u8], transparency: bool) -> [[u8; 4]; 16] {
+    let (first, second) = (u16::from_le_bytes([data[0], data[1]]), u16::from_le_bytes([data[2], data[3]]));
+    let expand = |color: u16| {
+        let (r, g, b) = ((color >> 11) as u32, (color >> 5 & 0x3f) as u32, (color & 0x1f) as u32);
+        [r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2]
+    };
+    let (a, b) = (expand(first), expand(second));
+    let mix = |weight: u32, total: u32| {
+        let [r, g, b] = [0, 1, 2].map(|i| ((total - weight) * a[i] + weight * b[i]) / total);
+        [r as u8, g as u8, b as u8, 255]
+    };
+
+    let palette = if first > second || !transparency {
+        [mix(0, 1), mix(1, 1), mix(1, 3), mix(2, 3)]
+    } else {
+        [mix(0, 1), mix(1, 1), mix(1, 2), [0, 0, 0, 0]]
+    };
+
+    let indices = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+    let mut pixels = [[0; 4]; 16];
+
+    (0..16).for_each(|i| pixels[i] = palette[(indices >> (2 * i) & 0x3) as usize]);
+
+    pixels
+}
+
+
+/// Decode the values of a block of two endpoints and 3 bit indices, as in the alpha of BC3 and the channels of BC4
+fn interpolated_block(data: &[u8]) -> [u8; 16] {
+    let (first, second) = (data[0] as u32, data[1] as u32);
+    let mut palette = [first, second, 0, 0, 0, 0, 0, 0];
+
+    if first > second {
+        (1..=6).for_each(|i| palette[i + 1] = ((7 - i) as u32 * first + i as u32 * second) / 7);
+    } else {
+        (1..=4).for_each(|i| palette[i + 1] = ((5 - i) as u32 * first + i as u32 * second) / 5);
+        palette[6] = 0;
+        palette[7] = 255;
+    }
+
+    let indices = data[2..8].iter().rev().fold(0u64, |indices, &byte| indices << 8 | byte as u64);
+
+    let mut values = [0; 16];
+
+    (0..16).for_each(|i| values[i] = palette[(indices >> (3 * i) & 0x7) as usize] as u8);
+
+    values
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+
+    #[cfg(feature = "basis")]
+    use basis_universal::{BasisTextureFormat, Compressor, CompressorParams};
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    #[cfg(feature = "basis")]
+    use ruzstd::encoding::{compress_to_vec, CompressionLevel};
+
+    /// A BC1 block of pure red and pure blue with the indices of the given rows
+    fn red_blue_block(rows: [u8; 4]) -> Vec<u8> {
+        [&0xf800u16.to_le_bytes()[..], &0x001fu16.to_le_bytes(), &rows].concat()
+    }
+
+    fn dds(four_cc: &[u8; 4], width: u32, height: u32, data: &[u8]) -> Vec<u8> {
+        let mut header = [0u8; 128];
+        header[..4].copy_from_slice(DDS_MAGIC);
+        header[12..16].copy_from_slice(&height.to_le_bytes());
+        header[16..20].copy_from_slice(&width.to_le_bytes());
+        header[80..84].copy_from_slice(&DDPF_FOURCC.to_le_bytes());
+        header[84..88].copy_from_slice(four_cc);
+
+        [&header[..], data].concat()
+    }
+
+    fn ktx2(vk_format: u32, width: u32, height: u32, supercompression: u32, level: &[u8]) -> Vec<u8> {
+        let mut header = KTX2_IDENTIFIER.to_vec();
+
+        for value in &[vk_format, 1, width, height, 0, 0, 1, 1, supercompression, 0, 0, 0, 0] {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+
+        header.extend_from_slice(&[0; 16]);
+        header.extend_from_slice(&(104u64).to_le_bytes());
+        header.extend_from_slice(&(level.len() as u64).to_le_bytes());
+        header.extend_from_slice(&(level.len() as u64).to_le_bytes());
+
+        [&header[..], level].concat()
+    }
+
+    /// Encode an image of red and blue halves, with a transparent bottom half, in the given Basis Universal encoding
+    #[cfg(feature = "basis")]
+    fn basis(format: BasisTextureFormat) -> Vec<u8> {
+        let image = image::RgbaImage::from_fn(8, 8, |x, y| {
+            image::Rgba([if x < 4 { 255 } else { 0 }, 0, if x < 4 { 0 } else { 255 }, if y < 4 { 255 } else { 0 }])
+        });
+
+        let mut params = CompressorParams::new();
+        params.set_basis_format(format);
+        params.source_image_mut(0).init(&image, 8, 8, 4);
+
+        let mut compressor = Compressor::default();
+        unsafe {
+            assert!(compressor.init(&params));
+            compressor.process().unwrap();
+        }
+
+        compressor.basis_file().to_vec()
+    }
+
+    /// Move the first image of a .basis file to a KTX2 file, with the codebooks of ETC1S data in its global data
+    #[cfg(feature = "basis")]
+    fn basis_ktx2(file: &[u8], supercompression: u32) -> Vec<u8> {
+        let field = |offset: usize, size: usize| {
+            (0..size).fold(0, |value, i| value | (file[offset + i] as u32) << (8 * i))
+        };
+        let slices = (0..field(14, 3) as usize).map(|index| {
+            let descriptor = field(65, 4) as usize + 23 * index;
+            let offset = field(descriptor + 13, 4) as usize;
+
+            &file[offset..offset + field(descriptor + 17, 4) as usize]
+        }).collect::<Vec<_>>();
+
+        if field(20, 1) == 1 {
+            let level = match supercompression {
+                KTX2_ZSTANDARD => compress_to_vec(slices[0], CompressionLevel::Fastest),
+                _ => slices[0].to_vec()
+            };
+            let mut bytes = ktx2(0, 8, 8, supercompression, &level);
+            let descriptor = bytes.len() as u32;
+
+            bytes[48..52].copy_from_slice(&descriptor.to_le_bytes());
+            bytes.extend_from_slice(&[0; 16]);
+            bytes[descriptor as usize + 12] = KHR_DF_MODEL_UASTC;
+
+            return bytes;
+        }
+
+        // the endpoints, selectors, tables, and extended data follow each other in the .basis file
+        let (start, length) = (field(41, 4) as usize, field(45, 3) + field(54, 3) + field(61, 4) + field(73, 4));
+        let (rgb, alpha) = (slices[0].len() as u32, slices.get(1).map_or(0, |alpha| alpha.len() as u32));
+
+        let mut global = [field(39, 2) | field(48, 2) << 16, field(45, 3), field(54, 3), field(61, 4), field(73, 4)]
+            .iter().chain(&[0, 0, rgb, rgb, alpha]).flat_map(|value| value.to_le_bytes()).collect::<Vec<_>>();
+        global.extend_from_slice(&file[start..start + length as usize]);
+
+        let mut bytes = ktx2(0, 8, 8, KTX2_BASIS_LZ, &slices.concat());
+        let offset = bytes.len() as u64;
+
+        bytes[64..72].copy_from_slice(&offset.to_le_bytes());
+        bytes[72..80].copy_from_slice(&(global.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&global);
+
+        bytes
+    }
+
+    #[test]
+    fn test_color_block() {
+        // the first row is red, then the two interpolated colors, then blue
+        let pixels = decode_block(&red_blue_block([0b0000_0000, 0b1010_1010, 0b1111_1111, 0b0101_0101]), Block::Bc1);
+
+        assert_eq!(pixels[0], [255, 0, 0, 255]);
+        assert_eq!(pixels[4], [170, 0, 85, 255]);
+        assert_eq!(pixels[8], [85, 0, 170, 255]);
+        assert_eq!(pixels[15], [0, 0, 255, 255]);
+
+        // with the colors in ascending order the last index is transparent, except in BC3
+        let block = [&0x001fu16.to_le_bytes()[..], &0xf800u16.to_le_bytes(), &[0xff; 4]].concat();
+        assert_eq!(decode_block(&block, Block::Bc1)[0], [0, 0, 0, 0]);
+        assert_eq!(decode_block(&[&[255, 0, 0, 0, 0, 0, 0, 0][..], &block].concat(), Block::Bc3)[0][3], 255);
+    }
+
+    #[test]
+    fn test_interpolated_block() {
+        // indices 0, 1, and 2 of 255 and 0 in the first three pixels
+        let values = interpolated_block(&[255, 0, 0b1000_1000, 0, 0, 0, 0, 0]);
+        assert_eq!(&values[..3], &[255, 0, 218]);
+
+        // with the endpoints in ascending order the last two indices are 0 and 255
+        let values = interpolated_block(&[10, 20, 0b1111_1110, 0b1111_1111, 0, 0, 0, 0]);
+        assert_eq!(&values[..3], &[0, 255, 255]);
+    }
+
+    #[test]
+    fn test_dds() {
+        // a 6x2 image is stored in two blocks, and the pixels past the edge are dropped
+        let data = [red_blue_block([0; 4]), red_blue_block([0x55; 4])].concat();
+        let image = from_bytes(&dds(b"DXT1", 6, 2, &data)).unwrap();
+
+        assert_eq!(image.dimensions(), (6, 2));
+        assert_eq!(*image.get_pixel(3, 1), image::Rgba([255, 0, 0, 255]));
+        assert_eq!(*image.get_pixel(4, 0), image::Rgba([0, 0, 255, 255]));
+
+        assert!(from_bytes(&dds(b"DXT1", 16, 16, &data)).is_err());
+        assert!(from_bytes(&dds(b"ETC2", 4, 4, &data)).is_err());
+
+        // uncompressed pixels are read through their masks, here 16 bit pixels with 5 bits of red
+        let mut bytes = dds(b"\0\0\0\0", 1, 1, &0x7c00u16.to_le_bytes());
+        bytes[80..84].copy_from_slice(&0x40u32.to_le_bytes());
+        bytes[88..92].copy_from_slice(&16u32.to_le_bytes());
+        bytes[92..96].copy_from_slice(&0x7c00u32.to_le_bytes());
+        bytes[96..100].copy_from_slice(&0x03e0u32.to_le_bytes());
+        bytes[100..104].copy_from_slice(&0x001fu32.to_le_bytes());
+
+        assert_eq!(*from_bytes(&bytes).unwrap().get_pixel(0, 0), image::Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn test_oversized_header() {
+        // sizes whose number of bytes overflows are rejected before anything is allocated
+        let error = from_bytes(&dds(b"DXT5", 0xffff_ffff, 0xffff_ffff, &[])).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let mut bytes = dds(b"\0\0\0\0", 0xffff_ffff, 0xffff_ffff, &[0; 16]);
+        bytes[88..92].copy_from_slice(&32u32.to_le_bytes());
+        assert_eq!(from_bytes(&bytes).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        // sizes that fit in memory but not in the data are rejected too
+        assert!(from_bytes(&dds(b"DXT1", 65536, 65536, &[0; 64])).is_err());
+
+        let mut bytes = ktx2(37, 0xffff_ffff, 0xffff_ffff, 0, &[0; 8]);
+        bytes[88..96].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(from_bytes(&bytes).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert!(from_bytes(&ktx2(37, 0xffff_ffff, 0xffff_ffff, 0, &[0; 8])).is_err());
+    }
+
+    #[test]
+    fn test_ktx2() {
+        let pixels = [1, 2, 3, 4, 5, 6, 7, 8];
+        let image = from_bytes(&ktx2(37, 2, 1, 0, &pixels)).unwrap();
+
+        assert_eq!(*image.get_pixel(1, 0), image::Rgba([5, 6, 7, 8]));
+
+        // zlib supercompressed levels are inflated first
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&red_blue_block([0x55; 4])).unwrap();
+        let image = from_bytes(&ktx2(131, 4, 4, KTX2_ZLIB, &encoder.finish().unwrap())).unwrap();
+
+        assert_eq!(*image.get_pixel(2, 3), image::Rgba([0, 0, 255, 255]));
+
+        assert!(!is_container(b"\x89PNG"));
+    }
+
+    #[test]
+    #[cfg(not(feature = "basis"))]
+    fn test_ktx2_without_basis() {
+        // Basis Universal data is reported as unsupported instead of decoded wrongly, naming its encoding
+        let etc1s = from_bytes(&ktx2(0, 4, 4, KTX2_BASIS_LZ, &[0; 8])).unwrap_err();
+        assert_eq!(etc1s.kind(), io::ErrorKind::Unsupported);
+        assert!(etc1s.to_string().contains("ETC1S"));
+
+        let mut uastc = ktx2(0, 4, 4, 0, &[0; 16]);
+        let descriptor = uastc.len() as u32;
+        uastc[48..52].copy_from_slice(&descriptor.to_le_bytes());
+        uastc.extend_from_slice(&[0; 16]);
+        uastc[descriptor as usize + 12] = KHR_DF_MODEL_UASTC;
+        assert!(from_bytes(&uastc).unwrap_err().to_string().contains("UASTC"));
+
+        let zstandard = from_bytes(&ktx2(37, 2, 1, KTX2_ZSTANDARD, &[0; 8])).unwrap_err();
+        assert_eq!(zstandard.kind(), io::ErrorKind::Unsupported);
+    }
+
+    #[test]
+    #[cfg(feature = "basis")]
+    fn test_ktx2_basis() {
+        let close = |pixel: &image::Rgba<u8>, color: [u8; 4]| {
+            (0..4).all(|i| (pixel[i] as i32 - color[i] as i32).abs() < 24)
+        };
+
+        // ETC1S keeps the alpha in a second slice, and UASTC in its blocks, which may also be Zstandard supercompressed
+        for bytes in &[basis_ktx2(&basis(BasisTextureFormat::ETC1S), KTX2_BASIS_LZ),
+                       basis_ktx2(&basis(BasisTextureFormat::UASTC4x4), 0),
+                       basis_ktx2(&basis(BasisTextureFormat::UASTC4x4), KTX2_ZSTANDARD)] {
+            let image = from_bytes(bytes).unwrap();
+
+            assert_eq!(image.dimensions(), (8, 8));
+            assert!(close(image.get_pixel(1, 1), [255, 0, 0, 255]), "{:?}", image.get_pixel(1, 1));
+            assert!(close(image.get_pixel(6, 2), [0, 0, 255, 255]), "{:?}", image.get_pixel(6, 2));
+            assert!(image.get_pixel(5, 6)[3] < 24, "{:?}", image.get_pixel(5, 6));
+        }
+
+        // level data that does not hold the blocks of the image is rejected
+        let mut bytes = basis_ktx2(&basis(BasisTextureFormat::UASTC4x4), 0);
+        bytes[88..96].copy_from_slice(&8u64.to_le_bytes());
+        assert_eq!(from_bytes(&bytes).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        let zstandard = from_bytes(&ktx2(37, 2, 1, KTX2_ZSTANDARD, &[0; 8])).unwrap_err();
+        assert_eq!(zstandard.kind(), io::ErrorKind::InvalidData);
+        assert!(!is_container(b"\x89PNG"));
+    }
+}
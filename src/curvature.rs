@@ -0,0 +1,226 @@
+use nalgebra::{Matrix4, Vector3, Vector4};
+
+use shader::{Contributions, Shader};
+use wavefront;
+
+
+/// How much a CavityShader darkens cavities and brightens edges
+///
+/// The cavity and edge are the fraction of the light taken from the most
+/// concave fragments and added to the most convex ones, and the scale
+/// multiplies the curvature before it is clamped to [-1, 1], so a greater
+/// scale lets gentler curves reach the full effect.
+///
+/// # Examples
+///
+/// ```
+/// # use rastermind::curvature::Cavity;
+/// let clay = Cavity { cavity: 0.8, edge: 0.2, ..Default::default() };
+/// ```
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cavity {
+    pub cavity: f64,
+    pub edge: f64,
+    pub scale: f64
+}
+
+
+impl Default for Cavity {
+    fn default() -> Cavity {
+        Cavity { cavity: 0.6, edge: 0.4, scale: 2.0 }
+    }
+}
+
+
+impl Cavity {
+    /// The factor the light of a fragment with the given curvature is scaled by
+    pub fn factor(&self, curvature: f64) -> f64 {
+        let curvature = (curvature * self.scale).clamp(-1.0, 1.0);
+
+        if curvature < 0.0 {
+            1.0 + self.cavity * curvature
+        } else {
+            1.0 + self.edge * curvature
+        }
+    }
+}
+
+
+/// Estimate the mean curvature at every geometric vertex of an Object
+///
+/// The curvature along an edge is how much the normals of its vertices turn
+/// over its length, and the curvature of a vertex is the mean along its
+/// edges, positive where the surface is convex and negative where it is
+/// concave. Normals are the area weighted normals of the faces around the
+/// vertex rather than those of the file, which scans often lack. The
+/// curvatures are multiplied by the mean length of the edges, so they are
+/// the turn of the normal from one vertex to the next and the same Cavity
+/// suits meshes of any size or density.
+///
+pub fn vertex_curvature(coordinates: &wavefront::Object) -> Vec<f64> {
+    let vertices = &coordinates.geometric_vertices;
+    let normals = coordinates.vertex_normals();
+
+    let mut sums = vec![(0.0, 0); vertices.len()];
+    let mut total_length = 0.0;
+    let mut edges = 0;
+
+    for face in &coordinates.geometric_faces {
+        for k in 0..=2 {
+            let (i, j) = (face[k] as usize, face[(k + 1) % 3] as usize);
+            let edge = vertices[j] - vertices[i];
+            let squared = edge.norm_squared();
+
+            if squared == 0.0 {
+                continue;
+            }
+
+            let curvature = (normals[j] - normals[i]).dot(&edge) / squared;
+
+            for &index in &[i, j] {
+                sums[index].0 += curvature;
+                sums[index].1 += 1;
+            }
+
+            total_length += squared.sqrt();
+            edges += 1;
+        }
+    }
+
+    let mean_length = if edges == 0 { 0.0 } else { total_length / edges as f64 };
+
+    sums.iter().map(|&(sum, count)| if count == 0 { 0.0 } else { sum / count as f64 * mean_length }).collect()
+}
+
+
+/// Darken the cavities and brighten the edges of another shader by the curvature of the surface
+///
+/// As with the cavity shading of sculpting tools, this accentuates the detail
+/// of untextured scans that plain diffuse light washes out. The curvatures
+/// are those of vertex_curvature for the drawn Object, interpolated across
+/// every face.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate rastermind;
+/// # use rastermind::curvature;
+/// # use rastermind::curvature::*;
+/// # use rastermind::shader::GouraudShader;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// let curvature = curvature::vertex_curvature(&coordinates);
+/// let shader = CavityShader::new(Box::new(GouraudShader::new()), &curvature, Cavity::default());
+/// # Ok(())
+/// # }
+/// ```
+///
+pub struct CavityShader<'a> {
+    pub shader: Box<dyn Shader + 'a>,
+    pub curvature: &'a [f64],
+    pub cavity: Cavity,
+    pub varying_curvature: Vector3<f64>
+}
+
+
+impl<'a> CavityShader<'a> {
+    /// Create a new instance of a CavityShader that shades the given shader by the given vertex curvatures
+    pub fn new(shader: Box<dyn Shader + 'a>, curvature: &'a [f64], cavity: Cavity) -> CavityShader<'a> {
+        CavityShader { shader, curvature, cavity, varying_curvature: Vector3::zeros() }
+    }
+
+    /// The factor the light of the fragment is scaled by
+    fn factor(&self, vertex: Vector3<f64>) -> f64 {
+        self.cavity.factor(self.varying_curvature.dot(&vertex))
+    }
+}
+
+
+impl<'a> Shader for CavityShader<'a> {
+    /// Position the vertices with the inner shader and keep their curvatures
+    fn vertex(&mut self, coordinates: &wavefront::Object,
+              view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+              model_view: &Matrix4<f64>, light_vector: &Vector3<f64>,
+              face_index: usize, vertex_index: usize) -> Vector4<f64> {
+
+        let geometric_index = coordinates.geometric_faces[face_index][vertex_index] as usize;
+        self.varying_curvature[vertex_index] = self.curvature.get(geometric_index).cloned().unwrap_or(0.0);
+
+        self.shader.vertex(coordinates, view_port, projection, model_view, light_vector,
+                           face_index, vertex_index)
+    }
+
+    /// Scale the color of the inner shader by the curvature of the fragment
+    fn fragment(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> image::Rgb<u8> {
+        let mut color = self.shader.fragment(vertex, texture);
+        let factor = self.factor(vertex);
+
+        (0..=2).for_each(|i| { color[i] = (color[i] as f64 * factor).min(255.0) as u8; });
+
+        color
+    }
+
+    /// Discard the fragments the inner shader discards
+    fn discard(&self, vertex: Vector3<f64>) -> bool {
+        self.shader.discard(vertex)
+    }
+
+    /// Scale the light of the inner shader by the curvature of the fragment
+    fn contributions(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> Contributions {
+        self.shader.contributions(vertex, texture).scale_light(self.factor(vertex))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shader;
+
+    fn tetrahedron() -> wavefront::Object {
+        wavefront::Object::from_bytes(b"v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\nvt 0 0\nvn 0 0 1\n\
+                                        f 1/1/1 3/1/1 2/1/1\nf 1/1/1 2/1/1 4/1/1\n\
+                                        f 1/1/1 4/1/1 3/1/1\nf 2/1/1 3/1/1 4/1/1\n",
+                                      &wavefront::LoadOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn test_vertex_curvature() {
+        let mut coordinates = tetrahedron();
+        let convex = vertex_curvature(&coordinates);
+
+        assert_eq!(convex.len(), 4);
+        assert!(convex.iter().all(|&curvature| curvature > 0.0));
+
+        // turned inside out, every vertex is in a cavity
+        coordinates.flip_winding();
+        let concave = vertex_curvature(&coordinates);
+
+        convex.iter().zip(&concave).for_each(|(convex, concave)| assert!((convex + concave).abs() < 1e-12));
+
+        // the curvature does not depend on the size of the mesh
+        coordinates.scale(10.0);
+        vertex_curvature(&coordinates).iter().zip(&concave)
+                                      .for_each(|(scaled, concave)| assert!((scaled - concave).abs() < 1e-12));
+    }
+
+    #[test]
+    fn test_cavity_shader() {
+        let curvature = [0.0];
+        let gray = image::Rgb([100, 100, 100]);
+        let texture = image::RgbImage::new(1, 1);
+        let mut shader = CavityShader::new(Box::new(shader::ColorShader::new(gray, false)), &curvature,
+                                           Cavity { cavity: 0.5, edge: 0.5, scale: 1.0 });
+        let center = Vector3::repeat(1.0 / 3.0);
+
+        assert_eq!(shader.fragment(center, &texture), gray);
+
+        shader.varying_curvature = Vector3::repeat(-3.0);
+        assert_eq!(shader.fragment(center, &texture), image::Rgb([50, 50, 50]));
+
+        shader.varying_curvature = Vector3::repeat(0.5);
+        assert_eq!(shader.fragment(center, &texture), image::Rgb([125, 125, 125]));
+    }
+}
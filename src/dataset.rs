@@ -0,0 +1,378 @@
+use std::f64::consts::PI;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use nalgebra::{Matrix4, Quaternion, UnitQuaternion, Vector3};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use camera;
+use error::MorpheusError;
+use render;
+use shader;
+use shader::Shader;
+use wavefront;
+
+
+/// The depth of the viewport used for every pass so the depth map has 16 bits of precision
+const DATASET_DEPTH: u32 = 65535;
+
+
+/// Options that control the frames of a synthetic dataset
+///
+/// Every frame turns the object to a random orientation about its center,
+/// places the camera at a random point on a sphere around the center, with a
+/// radius between the minimum and maximum distance, and shines the light
+/// from a random direction above the object. The distances must be positive
+/// and the minimum no larger than the maximum.
+/// The same seed always produces the same frames. With a lens distortion every
+/// image of a frame is warped so the dataset matches a real calibrated camera.
+///
+#[derive(Clone, Debug)]
+pub struct DatasetOptions {
+    pub frames: usize,
+    pub width: u32,
+    pub height: u32,
+    pub seed: u64,
+    pub minimum_distance: f64,
+    pub maximum_distance: f64,
+    pub distortion: Option<camera::Distortion>
+}
+
+
+impl Default for DatasetOptions {
+    fn default() -> DatasetOptions {
+        DatasetOptions { frames: 10, width: 512, height: 512, seed: 0,
+                         minimum_distance: 3.0, maximum_distance: 10.0, distortion: None }
+    }
+}
+
+
+/// The labels written alongside the images of a single frame
+#[derive(Debug, Serialize)]
+pub struct FrameLabels {
+    pub frame: usize,
+    pub width: u32,
+    pub height: u32,
+    pub eye: [f64; 3],
+    pub center: [f64; 3],
+    pub up: [f64; 3],
+    pub light: [f64; 3],
+    pub model_view: [[f64; 4]; 4],
+    pub projection: [[f64; 4]; 4],
+    pub viewport: [[f64; 4]; 4],
+    pub intrinsics: camera::Intrinsics,
+    pub extrinsics: [[f64; 4]; 3],
+    pub distortion: Option<camera::Distortion>,
+    pub objects: Vec<ObjectLabel>
+}
+
+
+/// The segmentation id and pose of one object or group in a frame, where the pose turns the object into the world
+#[derive(Clone, Debug, Serialize)]
+pub struct ObjectLabel {
+    pub name: String,
+    pub id: u16,
+    pub pose: [[f64; 4]; 4]
+}
+
+
+/// Render randomized views of an object into a labelled dataset in the given directory
+///
+/// For every frame the following files are written, where NNNN is the frame number:
+///
+/// * `NNNN_color.png`: the textured render
+/// * `NNNN_depth.png`: a 16 bit depth map where larger values are nearer and 0 is empty
+/// * `NNNN_normal.png`: view space normals mapped from [-1, 1] to [0, 255]
+/// * `NNNN_segmentation.png`: the id of the group covering each pixel, stored in the
+///   red (low byte) and green (high byte) channels, where 0 is empty
+/// * `NNNN.json`: the camera, light, matrices, pinhole intrinsics, extrinsics, and lens
+///   distortion of the frame and the ids of the groups
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate rastermind;
+/// # use std::path::Path;
+/// # use rastermind::dataset::{self, DatasetOptions};
+/// # use rastermind::{texture, wavefront};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// let coordinates = wavefront::Object::new("model.obj")?;
+/// let texture = texture::open("texture.png")?;
+///
+/// dataset::generate(&coordinates, &texture, &DatasetOptions::default(), Path::new("dataset"))?;
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn generate(coordinates: &wavefront::Object, texture: &image::RgbImage,
+                options: &DatasetOptions, directory: &Path) -> Result<(), MorpheusError> {
+
+    if !(options.minimum_distance > 0.0 && options.minimum_distance <= options.maximum_distance) {
+        return Err(MorpheusError::Scene(format!("the camera distance range {} to {} is not positive and increasing",
+                                                options.minimum_distance, options.maximum_distance)));
+    }
+
+    let written = |path: &Path, result: io::Result<()>| {
+        result.map_err(|error| MorpheusError::io(&path.display().to_string(), error))
+    };
+
+    written(directory, fs::create_dir_all(directory))?;
+
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let (mut objects, face_ids) = segmentation_ids(coordinates);
+    let center = bounding_box_center(coordinates);
+    let up = Vector3::y();
+
+    for frame in 0..options.frames {
+        let pose = Matrix4::new_translation(&center) * random_rotation(&mut rng).to_homogeneous()
+                   * Matrix4::new_translation(&-center);
+        for object in objects.iter_mut() {
+            object.pose = rows(&pose);
+        }
+
+        // the pose is baked into the vertices and normals, as scene objects are placed, so the light turns with it
+        let mut posed = coordinates.clone();
+        posed.transform(&pose);
+
+        let distance = if options.maximum_distance > options.minimum_distance {
+            rng.gen_range(options.minimum_distance, options.maximum_distance)
+        } else {
+            options.minimum_distance
+        };
+        let azimuth = rng.gen_range(0.0, 2.0 * PI);
+        let height: f64 = rng.gen_range(-0.9, 0.9);
+        let direction = Vector3::new((1.0 - height * height).sqrt() * azimuth.cos(), height,
+                                     (1.0 - height * height).sqrt() * azimuth.sin());
+        let eye = center + direction * distance;
+
+        let light_vector = Vector3::new(rng.gen_range(-1.0, 1.0), rng.gen_range(0.1, 1.0),
+                                        rng.gen_range(-1.0, 1.0)).normalize();
+
+        let model_view = shader::lookat(&eye, &center, &up);
+        let projection = shader::projection(-1.0 / (eye - center).norm());
+        let view_port = shader::viewport(options.width / 8, options.height / 8,
+                                         options.width * 3 / 4, options.height * 3 / 4,
+                                         DATASET_DEPTH);
+
+        let intrinsics = camera::Intrinsics::from_render(options.width, options.height, &eye, &center);
+        let distortion_map = options.distortion.map(|distortion| distortion.remap(&intrinsics));
+
+        let render_pass = |shader_for_face: &dyn Fn(usize) -> Box<dyn Shader>| {
+            let mut buffer = image::ImageBuffer::new(options.width, options.height);
+            let mut zbuffer = vec![-1.0; (options.width * options.height) as usize];
+
+            render::draw_faces(&posed, &mut buffer, texture, &mut zbuffer,
+                               &render::DepthOptions::default(), &view_port, &projection,
+                               &model_view, &light_vector, shader_for_face);
+
+            if let Some(ref map) = distortion_map {
+                buffer = camera::remap_image(map, &buffer);
+                zbuffer = camera::remap_values(map, &zbuffer, -1.0);
+            }
+
+            (buffer, zbuffer)
+        };
+
+        let prefix = directory.join(format!("{:04}", frame));
+        let path = |suffix: &str| format!("{}{}", prefix.display(), suffix);
+
+        let (color, zbuffer) = render_pass(&|_| Box::new(shader::GouraudShader::new()));
+        written(Path::new(&path("_color.png")), save(color, &path("_color.png")))?;
+        written(Path::new(&path("_depth.png")),
+                save_depth(&zbuffer, options.width, options.height, &path("_depth.png")))?;
+
+        let (normal, _) = render_pass(&|_| Box::new(shader::NormalShader::new()));
+        written(Path::new(&path("_normal.png")), save(normal, &path("_normal.png")))?;
+
+        let (segmentation, _) = render_pass(&|face_index| {
+            let id = face_ids[face_index];
+            Box::new(shader::ColorShader::new(image::Rgb([id as u8, (id >> 8) as u8, 0]), false))
+        });
+        written(Path::new(&path("_segmentation.png")), save(segmentation, &path("_segmentation.png")))?;
+
+        let labels = FrameLabels { frame, width: options.width, height: options.height,
+                                   eye: eye.into(), center: center.into(), up: up.into(),
+                                   light: light_vector.into(),
+                                   model_view: rows(&model_view), projection: rows(&projection),
+                                   viewport: rows(&view_port),
+                                   intrinsics,
+                                   extrinsics: camera::Extrinsics::from_lookat(&eye, &center, &up).rows(),
+                                   distortion: options.distortion,
+                                   objects: objects.clone() };
+
+        let file = File::create(path(".json")).map_err(|error| MorpheusError::io(&path(".json"), error))?;
+        serde_json::to_writer_pretty(file, &labels).map_err(|error| MorpheusError::asset(&path(".json"), error))?;
+    }
+
+    Ok(())
+}
+
+
+/// Pick an orientation uniformly at random, as Shoemake does from three uniform numbers
+fn random_rotation<R: Rng>(rng: &mut R) -> UnitQuaternion<f64> {
+    let (u1, u2, u3): (f64, f64, f64) = (rng.gen(), rng.gen_range(0.0, 2.0 * PI), rng.gen_range(0.0, 2.0 * PI));
+    let (a, b) = ((1.0 - u1).sqrt(), u1.sqrt());
+
+    UnitQuaternion::from_quaternion(Quaternion::new(b * u3.cos(), a * u2.sin(), a * u2.cos(), b * u3.sin()))
+}
+
+
+/// Assign a segmentation id to every group of the object, or to the whole object without groups
+fn segmentation_ids(coordinates: &wavefront::Object) -> (Vec<ObjectLabel>, Vec<u16>) {
+    let pose = rows(&Matrix4::identity());
+
+    if coordinates.groups.is_empty() {
+        return (vec![ObjectLabel { name: String::from("default"), id: 1, pose }],
+                vec![1; coordinates.geometric_faces.len()]);
+    }
+
+    let mut face_ids = vec![0; coordinates.geometric_faces.len()];
+    let objects = coordinates.groups.iter().enumerate().map(|(index, group)| {
+        let id = index as u16 + 1;
+        face_ids[group.faces.clone()].iter_mut().for_each(|face_id| *face_id = id);
+
+        ObjectLabel { name: group.name.clone(), id, pose }
+    }).collect();
+
+    (objects, face_ids)
+}
+
+
+/// Find the center of the axis aligned bounding box of the vertices of the object
+fn bounding_box_center(coordinates: &wavefront::Object) -> Vector3<f64> {
+    let vertices = &coordinates.geometric_vertices;
+
+    if vertices.is_empty() {
+        return Vector3::zeros();
+    }
+
+    let minimum = vertices.iter().fold(vertices[0], |a, b| a.zip_map(b, f64::min));
+    let maximum = vertices.iter().fold(vertices[0], |a, b| a.zip_map(b, f64::max));
+
+    (minimum + maximum) / 2.0
+}
+
+
+/// Copy the rows of the given matrix into nested arrays
+fn rows(matrix: &Matrix4<f64>) -> [[f64; 4]; 4] {
+    let mut rows = [[0.0; 4]; 4];
+
+    for (i, row) in rows.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = matrix[(i, j)];
+        }
+    }
+
+    rows
+}
+
+
+fn save(buffer: image::RgbImage, filename: &str) -> io::Result<()> {
+    image::ImageRgb8(buffer).flipv().save(filename)
+}
+
+
+/// Save the depth buffer as a 16 bit grayscale image with the first row at the top
+fn save_depth(zbuffer: &[f64], width: u32, height: u32, filename: &str) -> io::Result<()> {
+    let bytes: Vec<u8> = zbuffer.chunks(width as usize)
+                                .rev()
+                                .flat_map(|row| row.iter())
+                                .flat_map(|&depth| (depth.max(0.0).min(DATASET_DEPTH as f64) as u16)
+                                                   .to_be_bytes()
+                                                   .to_vec())
+                                .collect();
+
+    image::save_buffer(filename, &bytes, width, height, image::Gray(16))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use super::*;
+
+    #[test]
+    fn test_generate() {
+        let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nv 0 0 1\nvt 0 0\n\
+                                                          vn 0 0 1\ng left\nf 1/1/1 2/1/1 3/1/1\n\
+                                                          g right\nf 1/1/1 2/1/1 4/1/1\n",
+                                                        &wavefront::LoadOptions::default()).unwrap();
+        let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+        let options = DatasetOptions { frames: 2, width: 64, height: 48,
+                                       distortion: Some(camera::Distortion::new(0.1, 0.0, 0.0, 0.0, 0.0)),
+                                       ..Default::default() };
+
+        let mut directory = env::temp_dir();
+        directory.push("test_dataset");
+
+        generate(&coordinates, &texture, &options, &directory).unwrap();
+
+        let segmentation = image::open(directory.join("0001_segmentation.png")).unwrap().to_rgb();
+        let depth = fs::read(directory.join("0001_depth.png")).unwrap();
+        let labels: serde_json::Value = serde_json::from_reader(
+            File::open(directory.join("0001.json")).unwrap()).unwrap();
+
+        assert_eq!(segmentation.dimensions(), (64, 48));
+        assert!(segmentation.pixels().all(|pixel| pixel[0] <= 2 && pixel[1] == 0));
+        assert!(segmentation.pixels().any(|pixel| pixel[0] > 0));
+        // bit depth and color type in the header of the PNG file
+        assert_eq!((depth[24], depth[25]), (16, 0));
+        assert_eq!(labels["objects"][1]["name"], "right");
+        assert_eq!(labels["objects"][1]["id"], 2);
+        assert_eq!(labels["distortion"]["k1"], 0.1);
+        assert!(directory.join("0000_normal.png").exists());
+
+        // every frame turns the object about its center, which stays where it is
+        let pose = &labels["objects"][0]["pose"];
+        let other: serde_json::Value = serde_json::from_reader(
+            File::open(directory.join("0000.json")).unwrap()).unwrap();
+        assert_ne!(pose, &other["objects"][0]["pose"]);
+        assert_ne!(pose[0][0], 1.0);
+        assert_eq!(pose, &labels["objects"][1]["pose"]);
+
+        let reversed = DatasetOptions { minimum_distance: 10.0, maximum_distance: 3.0, ..options.clone() };
+        assert!(matches!(generate(&coordinates, &texture, &reversed, &directory), Err(MorpheusError::Scene(_))));
+        let fixed = DatasetOptions { frames: 1, minimum_distance: 5.0, maximum_distance: 5.0, ..options };
+        generate(&coordinates, &texture, &fixed, &directory).unwrap();
+    }
+
+    #[test]
+    fn test_lighting_follows_pose() {
+        // every pixel of a flat white square is lit by its one normal, turned by the pose of the frame
+        let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\nvt 0 0\n\
+                                                          vn 0 0 1\nf 1/1/1 2/1/1 3/1/1 4/1/1\n",
+                                                        &wavefront::LoadOptions::default()).unwrap();
+        let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+        let options = DatasetOptions { frames: 4, width: 32, height: 32, seed: 7, ..Default::default() };
+
+        let mut directory = env::temp_dir();
+        directory.push("test_dataset_lighting");
+
+        generate(&coordinates, &texture, &options, &directory).unwrap();
+
+        let mut lit = 0;
+
+        for frame in 0..options.frames {
+            let path = |suffix: &str| directory.join(format!("{:04}{}", frame, suffix));
+            let labels: serde_json::Value = serde_json::from_reader(File::open(path(".json")).unwrap()).unwrap();
+            let value = |value: &serde_json::Value| value.as_f64().unwrap();
+
+            let pose = &labels["objects"][0]["pose"];
+            let normal = Vector3::from_fn(|i, _| value(&pose[i][2]));
+            let light = Vector3::from_fn(|i, _| value(&labels["light"][i]));
+            let expected = (255.0 * normal.dot(&light).max(0.0)) as i32;
+
+            let color = image::open(path("_color.png")).unwrap().to_rgb();
+            let segmentation = image::open(path("_segmentation.png")).unwrap().to_rgb();
+            let (x, y, _) = segmentation.enumerate_pixels().find(|&(_, _, pixel)| pixel[0] > 0).unwrap();
+
+            assert!((color.get_pixel(x, y)[0] as i32 - expected).abs() <= 1);
+            lit += (expected > 0) as usize;
+        }
+
+        assert!(lit > 0);
+    }
+}
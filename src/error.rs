@@ -0,0 +1,167 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+use image::ImageError;
+
+
+/// The failures of the crate, returned instead of panicking so embedding applications can handle them
+///
+/// Asset errors name the file that could not be read or decoded, parse and
+/// invalid index errors name the line of a wavefront file that was rejected
+/// while loading it strictly, scene errors describe a scene or render request
+/// that could not be parsed, and render errors describe the configuration of
+/// a render that cannot be drawn. Files that cannot be read or written and
+/// images that cannot be decoded or encoded keep the error that caused them
+/// as their source, so its kind can be checked.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate rastermind;
+/// # use rastermind::wavefront;
+/// # use rastermind::MorpheusError;
+/// # fn draw(_: &wavefront::Object) {}
+/// # fn main() -> Result<(), MorpheusError> {
+/// match wavefront::Object::new("missing.obj") {
+///     Ok(coordinates) => draw(&coordinates),
+///     Err(MorpheusError::Asset { path, message }) => eprintln!("skipping {}: {}", path, message),
+///     Err(error) => return Err(error)
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+#[derive(Debug)]
+pub enum MorpheusError {
+    Asset { path: String, message: String },
+    Io { path: String, source: io::Error },
+    Image { path: String, source: ImageError },
+    Parse { path: String, line: usize, message: String },
+    InvalidIndex { path: String, line: usize, index: i64 },
+    Scene(String),
+    Render(String)
+}
+
+
+impl MorpheusError {
+    /// Create an error for the asset at the given path from the error that occurred while loading it
+    pub fn asset<E: fmt::Display>(path: &str, error: E) -> MorpheusError {
+        MorpheusError::Asset { path: path.to_string(), message: error.to_string() }
+    }
+
+    /// Create an error for the file at the given path that could not be read or written
+    pub fn io(path: &str, source: io::Error) -> MorpheusError {
+        MorpheusError::Io { path: path.to_string(), source }
+    }
+
+    /// Create an error for the image at the given path that could not be decoded or encoded
+    pub fn image(path: &str, source: ImageError) -> MorpheusError {
+        MorpheusError::Image { path: path.to_string(), source }
+    }
+
+    /// Create an error for an asset read from memory rather than from a file
+    pub fn data<E: fmt::Display>(error: E) -> MorpheusError {
+        MorpheusError::asset("<data>", error)
+    }
+}
+
+
+impl fmt::Display for MorpheusError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MorpheusError::Asset { ref path, ref message } => write!(formatter, "cannot load {}: {}", path, message),
+            MorpheusError::Io { ref path, ref source } => write!(formatter, "cannot access {}: {}", path, source),
+            MorpheusError::Image { ref path, ref source } => {
+                write!(formatter, "cannot load image {}: {}", path, source)
+            },
+            MorpheusError::Parse { ref path, line, ref message } => {
+                write!(formatter, "cannot parse line {} of {}: {}", line, path, message)
+            },
+            MorpheusError::InvalidIndex { ref path, line, index } => {
+                write!(formatter, "index {} on line {} of {} is out of range", index, line, path)
+            },
+            MorpheusError::Scene(ref message) => write!(formatter, "invalid scene: {}", message),
+            MorpheusError::Render(ref message) => write!(formatter, "cannot render: {}", message)
+        }
+    }
+}
+
+
+impl error::Error for MorpheusError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            MorpheusError::Io { ref source, .. } => Some(source),
+            MorpheusError::Image { ref source, .. } => Some(source),
+            _ => None
+        }
+    }
+}
+
+
+/// Errors are equal when they describe the same failure, with io and image sources compared by kind and message
+impl PartialEq for MorpheusError {
+    fn eq(&self, other: &MorpheusError) -> bool {
+        match (self, other) {
+            (MorpheusError::Io { path, source }, MorpheusError::Io { path: other, source: other_source }) => {
+                path == other && source.kind() == other_source.kind() && source.to_string() == other_source.to_string()
+            },
+            (MorpheusError::Image { path, source }, MorpheusError::Image { path: other, source: other_source }) => {
+                path == other && source.to_string() == other_source.to_string()
+            },
+            (MorpheusError::Io { .. }, _) | (MorpheusError::Image { .. }, _) => false,
+            (_, MorpheusError::Io { .. }) | (_, MorpheusError::Image { .. }) => false,
+            _ => self.to_string() == other.to_string()
+        }
+    }
+}
+
+
+impl From<MorpheusError> for io::Error {
+    fn from(error: MorpheusError) -> io::Error {
+        match error {
+            MorpheusError::Io { ref source, .. } => io::Error::new(source.kind(), error.to_string()),
+            _ => io::Error::other(error.to_string())
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let missing = io::Error::new(io::ErrorKind::NotFound, "no such file");
+
+        assert_eq!(MorpheusError::asset("head.obj", missing).to_string(), "cannot load head.obj: no such file");
+        assert_eq!(MorpheusError::Parse { path: String::from("head.obj"), line: 12,
+                                          message: String::from("invalid number 'x'") }.to_string(),
+                   "cannot parse line 12 of head.obj: invalid number 'x'");
+        assert_eq!(MorpheusError::InvalidIndex { path: String::from("head.obj"), line: 40, index: 9 }.to_string(),
+                   "index 9 on line 40 of head.obj is out of range");
+        assert_eq!(MorpheusError::Scene(String::from("expected a number")).to_string(),
+                   "invalid scene: expected a number");
+        assert_eq!(io::Error::from(MorpheusError::Render(String::from("no camera"))).to_string(),
+                   "cannot render: no camera");
+    }
+
+    #[test]
+    fn test_source() {
+        let missing = MorpheusError::io("head.obj", io::Error::new(io::ErrorKind::NotFound, "no such file"));
+
+        assert_eq!(missing.to_string(), "cannot access head.obj: no such file");
+        let source = error::Error::source(&missing).and_then(|source| source.downcast_ref::<io::Error>());
+        assert_eq!(source.map(|source| source.kind()), Some(io::ErrorKind::NotFound));
+        assert_eq!(io::Error::from(missing).kind(), io::ErrorKind::NotFound);
+
+        let unsupported = image::load_from_memory(b"not an image").err().unwrap();
+        let undecodable = MorpheusError::image("diffuse.png", unsupported);
+        assert!(undecodable.to_string().starts_with("cannot load image diffuse.png: "));
+        assert!(error::Error::source(&undecodable).unwrap().downcast_ref::<ImageError>().is_some());
+
+        assert!(error::Error::source(&MorpheusError::Scene(String::from("empty"))).is_none());
+        assert_ne!(undecodable, MorpheusError::Scene(String::from("empty")));
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use error::MorpheusError;
+use wavefront;
+
+
+/// The formats an Object can be written back out in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MeshFormat {
+    Obj,
+    Ply
+}
+
+
+impl MeshFormat {
+    /// The format of a file by its extension, regardless of case
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<MeshFormat> {
+        let extension = path.as_ref().extension()?.to_str()?.to_lowercase();
+
+        match extension.as_str() {
+            "obj" => Some(MeshFormat::Obj),
+            "ply" => Some(MeshFormat::Ply),
+            _ => None
+        }
+    }
+}
+
+
+/// Save an Object in the format of the extension of the path, which is either obj or ply
+///
+/// The object is written as it is in memory, so the geometry processing of
+/// the crate, such as the load options, generated normals, and transforms,
+/// can be used on its own to prepare meshes for other tools.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate rastermind;
+/// # use rastermind::{export, plugins};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// let mut coordinates = plugins::open("chair.fbx")?;
+/// coordinates.normalize();
+///
+/// export::save(&coordinates, "chair.ply")?;
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn save<P: AsRef<Path>>(coordinates: &wavefront::Object, path: P) -> Result<(), MorpheusError> {
+    let name = path.as_ref().display().to_string();
+    let format = MeshFormat::from_path(&path).ok_or_else(|| {
+        MorpheusError::asset(&name, "meshes can only be saved as obj or ply files")
+    })?;
+
+    let mut writer = BufWriter::new(File::create(&path).map_err(|error| MorpheusError::io(&name, error))?);
+
+    let written = match format {
+        MeshFormat::Obj => write_obj(coordinates, &mut writer),
+        MeshFormat::Ply => write_ply(coordinates, &mut writer)
+    };
+
+    written.and_then(|_| writer.flush()).map_err(|error| MorpheusError::io(&name, error))
+}
+
+
+/// Write an Object as a wavefront file, with its groups, smoothing groups, polylines, and points
+///
+/// Every face is written as a triangle with its geometric, texture, and normal
+/// indices, so the file loads back into the same Object.
+///
+pub fn write_obj<W: Write>(coordinates: &wavefront::Object, writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "# written by rastermind")?;
+
+    for vertex in &coordinates.geometric_vertices {
+        writeln!(writer, "v {} {} {}", vertex.x, vertex.y, vertex.z)?;
+    }
+
+    for vertex in &coordinates.texture_vertices {
+        writeln!(writer, "vt {} {}", vertex.x, vertex.y)?;
+    }
+
+    for normal in &coordinates.normal_vertices {
+        writeln!(writer, "vn {} {} {}", normal.x, normal.y, normal.z)?;
+    }
+
+    let group_starts: HashMap<usize, &str> = coordinates.groups.iter()
+                                                         .map(|group| (group.faces.start, group.name.as_str()))
+                                                         .collect();
+    let mut smoothing_group = None;
+
+    for (index, face) in coordinates.geometric_faces.iter().enumerate() {
+        if let Some(name) = group_starts.get(&index) {
+            writeln!(writer, "g {}", name)?;
+        }
+
+        let group = coordinates.smoothing_groups.get(index).cloned().unwrap_or(0);
+
+        if smoothing_group != Some(group) {
+            match group {
+                0 => writeln!(writer, "s off")?,
+                group => writeln!(writer, "s {}", group)?
+            }
+
+            smoothing_group = Some(group);
+        }
+
+        let texture = coordinates.texture_faces[index];
+        let normal = coordinates.normal_faces[index];
+
+        writeln!(writer, "f {}/{}/{} {}/{}/{} {}/{}/{}", face[0] + 1, texture[0] + 1, normal[0] + 1,
+                 face[1] + 1, texture[1] + 1, normal[1] + 1, face[2] + 1, texture[2] + 1, normal[2] + 1)?;
+    }
+
+    for line in &coordinates.lines {
+        let indices: Vec<String> = line.iter().map(|index| (index + 1).to_string()).collect();
+        writeln!(writer, "l {}", indices.join(" "))?;
+    }
+
+    for point in &coordinates.points {
+        writeln!(writer, "p {}", point + 1)?;
+    }
+
+    Ok(())
+}
+
+
+/// Write the faces of an Object as an ASCII PLY file with a position, normal, and texture coordinate per vertex
+///
+/// PLY files have one index per corner, so every distinct combination of
+/// geometric, texture, and normal index becomes a vertex of its own, which
+/// splits vertices along seams and hard edges. Polylines and points are not
+/// written.
+///
+pub fn write_ply<W: Write>(coordinates: &wavefront::Object, writer: &mut W) -> io::Result<()> {
+    let mut vertices: Vec<(i32, i32, i32)> = Vec::new();
+    let mut indices: HashMap<(i32, i32, i32), usize> = HashMap::new();
+
+    let faces: Vec<[usize; 3]> = (0..coordinates.geometric_faces.len()).map(|face| {
+        let mut corners = [0; 3];
+
+        for (i, corner) in corners.iter_mut().enumerate() {
+            let key = (coordinates.geometric_faces[face][i], coordinates.texture_faces[face][i],
+                       coordinates.normal_faces[face][i]);
+
+            *corner = *indices.entry(key).or_insert_with(|| {
+                vertices.push(key);
+                vertices.len() - 1
+            });
+        }
+
+        corners
+    }).collect();
+
+    writeln!(writer, "ply\nformat ascii 1.0\ncomment written by rastermind")?;
+    writeln!(writer, "element vertex {}", vertices.len())?;
+    writeln!(writer, "property float x\nproperty float y\nproperty float z")?;
+    writeln!(writer, "property float nx\nproperty float ny\nproperty float nz")?;
+    writeln!(writer, "property float s\nproperty float t")?;
+    writeln!(writer, "element face {}", faces.len())?;
+    writeln!(writer, "property list uchar int vertex_indices\nend_header")?;
+
+    for &(geometric, texture, normal) in &vertices {
+        let position = coordinates.geometric_vertices[geometric as usize];
+        let normal = coordinates.normal_vertices[normal as usize];
+        let texture = coordinates.texture_vertices[texture as usize];
+
+        writeln!(writer, "{} {} {} {} {} {} {} {}", position.x, position.y, position.z, normal.x, normal.y, normal.z,
+                 texture.x, texture.y)?;
+    }
+
+    for face in &faces {
+        writeln!(writer, "3 {} {} {}", face[0], face[1], face[2])?;
+    }
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+
+    const MESH: &[u8] = b"v 0 0 0\nv 2 0 0\nv 2 2 0\nv 0 2 0\nvt 0 0\nvt 1 1\nvn 0 0 1\n\
+                          g front\ns 1\nf 1/1/1 2/1/1 3/2/1 4/2/1\ng back\ns off\nf 3/1/1 2/1/1 1/1/1\nl 1 3\np 4\n";
+
+    #[test]
+    fn test_write_obj() {
+        let coordinates = wavefront::Object::from_bytes(MESH, &wavefront::LoadOptions::default()).unwrap();
+
+        let mut written = Vec::new();
+        write_obj(&coordinates, &mut written).unwrap();
+        let reloaded = wavefront::Object::from_bytes(&written, &wavefront::LoadOptions::default()).unwrap();
+
+        assert_eq!(reloaded.geometric_vertices, coordinates.geometric_vertices);
+        assert_eq!(reloaded.texture_vertices, coordinates.texture_vertices);
+        assert_eq!(reloaded.normal_vertices, coordinates.normal_vertices);
+        assert_eq!(reloaded.geometric_faces, coordinates.geometric_faces);
+        assert_eq!(reloaded.texture_faces, coordinates.texture_faces);
+        assert_eq!(reloaded.smoothing_groups, vec![1, 1, 0]);
+        assert_eq!(reloaded.groups, coordinates.groups);
+        assert_eq!((reloaded.lines.clone(), reloaded.points.clone()), (vec![vec![0, 2]], vec![3]));
+    }
+
+    #[test]
+    fn test_write_ply() {
+        let coordinates = wavefront::Object::from_bytes(MESH, &wavefront::LoadOptions::default()).unwrap();
+
+        let mut written = Vec::new();
+        write_ply(&coordinates, &mut written).unwrap();
+        let written = String::from_utf8(written).unwrap();
+        let (header, body) = written.split_at(written.find("end_header\n").unwrap() + 11);
+
+        // the third vertex is used with two texture coordinates, so it is written twice
+        assert!(header.contains("element vertex 5\n") && header.contains("element face 3\n"));
+        assert_eq!(body.lines().count(), 8);
+        assert_eq!(body.lines().nth(2), Some("2 2 0 0 0 1 1 1"));
+        assert_eq!(body.lines().nth(7), Some("3 4 1 0"));
+    }
+
+    #[test]
+    fn test_save() {
+        let coordinates = wavefront::Object::from_bytes(MESH, &wavefront::LoadOptions::default()).unwrap();
+        let path = env::temp_dir().join("morpheus_export.obj");
+
+        save(&coordinates, &path).unwrap();
+        assert_eq!(wavefront::Object::new(path.to_str().unwrap()).unwrap().geometric_faces.len(), 3);
+
+        assert_eq!(MeshFormat::from_path("mesh.PLY"), Some(MeshFormat::Ply));
+        assert!(save(&coordinates, env::temp_dir().join("morpheus_export.stl")).is_err());
+    }
+}
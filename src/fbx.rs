@@ -0,0 +1,660 @@
+use std::collections::HashMap;
+use std::fs;
+use std::iter::Peekable;
+use std::vec;
+
+use nalgebra::{Matrix3, Matrix4, Rotation3, U3, Vector2, Vector3};
+
+use error::MorpheusError;
+use mesh::{Mesh, MeshMaterial};
+use wavefront;
+use wavefront::{AxisConvention, Handedness, Units, UpAxis};
+
+
+/// The first bytes of binary FBX files, which are not supported
+const BINARY_MAGIC: &[u8] = b"Kaydara FBX Binary";
+
+/// The deepest hierarchy of models followed, which guards against connections that form a cycle
+const MAXIMUM_DEPTH: usize = 64;
+
+
+/// A node of an ASCII FBX document, such as `Vertices: *6 { a: 0,0,0,1,0,0 }`
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Node {
+    name: String,
+    properties: Vec<String>,
+    children: Vec<Node>
+}
+
+
+impl Node {
+    fn child(&self, name: &str) -> Option<&Node> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    /// The id that connections refer to the node by, which FBX 6 files write as the name of the node
+    fn id(&self) -> &str {
+        self.properties.first().map_or("", String::as_str)
+    }
+
+    /// The name of the node without the class that FBX files prefix it with, as in Model::Chair
+    fn object_name(&self) -> &str {
+        let name = self.properties.iter().find(|property| property.contains("::")).map_or(self.id(), String::as_str);
+
+        name.splitn(2, "::").last().unwrap_or(name)
+    }
+
+    /// The first property of the child with the given name
+    fn string(&self, name: &str) -> Option<&str> {
+        self.child(name).and_then(|child| child.properties.first()).map(String::as_str)
+    }
+
+    /// The numbers of the node, which FBX 7 files write in a child named a and FBX 6 files write directly
+    fn numbers(&self) -> Result<Vec<f64>, String> {
+        let properties = self.child("a").map_or(&self.properties, |array| &array.properties);
+
+        properties.iter().map(|number| {
+            number.parse().map_err(|_| format!("{} expects numbers, not '{}'", self.name, number))
+        }).collect()
+    }
+
+    /// The property with the given name from the Properties70 block of FBX 7 or the Properties60 block of FBX 6
+    fn property(&self, name: &str) -> Option<&Node> {
+        self.children.iter().filter(|child| child.name == "Properties70" || child.name == "Properties60")
+                     .flat_map(|properties| properties.children.iter())
+                     .find(|property| property.properties.first().map(String::as_str) == Some(name))
+    }
+
+    /// The last three numbers of the property with the given name, which hold its value
+    fn vector(&self, name: &str) -> Option<Vector3<f64>> {
+        let property = self.property(name)?;
+        let values = &property.properties[property.properties.len().saturating_sub(3)..];
+        let numbers = values.iter().map(|value| value.parse().ok()).collect::<Option<Vec<f64>>>()?;
+
+        if numbers.len() == 3 { Some(Vector3::new(numbers[0], numbers[1], numbers[2])) } else { None }
+    }
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Key(String),
+    Value(String),
+    Comma,
+    Open,
+    Close
+}
+
+
+/// Split an ASCII FBX document into its tokens, dropping comments and the lengths of arrays
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut characters = text.chars().peekable();
+    let mut line = 1;
+
+    while let Some(character) = characters.next() {
+        match character {
+            '\n' => line += 1,
+            ';' => {
+                while characters.peek().is_some_and(|&next| next != '\n') {
+                    characters.next();
+                }
+            },
+            '{' => tokens.push(Token::Open),
+            '}' => tokens.push(Token::Close),
+            ',' => tokens.push(Token::Comma),
+            '"' => {
+                let mut string = String::new();
+
+                loop {
+                    match characters.next() {
+                        Some('"') => break,
+                        Some(next) => string.push(next),
+                        None => return Err(format!("the string on line {} is not closed", line))
+                    }
+                }
+
+                tokens.push(Token::Value(string));
+            },
+            '*' => {
+                while characters.peek().is_some_and(char::is_ascii_digit) {
+                    characters.next();
+                }
+            },
+            character if character.is_whitespace() => {},
+            character => {
+                let mut word = character.to_string();
+
+                while let Some(&next) = characters.peek() {
+                    if next.is_whitespace() || ",{}\";:".contains(next) {
+                        break;
+                    }
+
+                    word.push(next);
+                    characters.next();
+                }
+
+                if characters.peek() == Some(&':') {
+                    characters.next();
+                    tokens.push(Token::Key(word));
+                } else {
+                    tokens.push(Token::Value(word));
+                }
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+
+/// Parse the nodes up to the end of the document, or up to the brace that closes them when they are nested
+fn parse_nodes(tokens: &mut Peekable<vec::IntoIter<Token>>, nested: bool) -> Result<Vec<Node>, String> {
+    let mut nodes = Vec::new();
+
+    loop {
+        let name = match tokens.next() {
+            Some(Token::Key(name)) => name,
+            Some(Token::Close) if nested => return Ok(nodes),
+            None if !nested => return Ok(nodes),
+            None => return Err(String::from("the document ends before a brace is closed")),
+            Some(token) => return Err(format!("expected the name of a node, not {:?}", token))
+        };
+
+        let mut node = Node { name, ..Default::default() };
+
+        while let Some(&Token::Value(_)) = tokens.peek() {
+            if let Some(Token::Value(value)) = tokens.next() {
+                node.properties.push(value);
+            }
+
+            if tokens.peek() == Some(&Token::Comma) {
+                tokens.next();
+            }
+        }
+
+        if tokens.peek() == Some(&Token::Open) {
+            tokens.next();
+            node.children = parse_nodes(tokens, true)?;
+        }
+
+        nodes.push(node);
+    }
+}
+
+
+/// A layer of per corner, per vertex, or per polygon values of a geometry, such as its normals or UVs
+struct Layer {
+    values: Vec<f64>,
+    indices: Option<Vec<f64>>,
+    mapping: String
+}
+
+
+impl Layer {
+    /// Read the first layer element with the given name, with its values and the indices into them
+    fn read(geometry: &Node, element: &str, values: &str, indices: &str) -> Result<Option<Layer>, String> {
+        let element = match geometry.child(element) {
+            Some(element) => element,
+            None => return Ok(None)
+        };
+
+        let values = element.child(values).map_or(Ok(Vec::new()), Node::numbers)?;
+        let indices = match element.string("ReferenceInformationType") {
+            Some("Direct") => None,
+            _ => element.child(indices).map(Node::numbers).transpose()?
+        };
+        let mapping = element.string("MappingInformationType").unwrap_or("AllSame").to_string();
+
+        Ok(Some(Layer { values, indices, mapping }))
+    }
+
+    /// The position of a corner of a polygon in the layer, given the index of the corner in the
+    /// geometry, the index of its vertex, and the index of the polygon
+    fn position(&self, corner: usize, vertex: usize, polygon: usize) -> usize {
+        match self.mapping.as_str() {
+            "ByPolygonVertex" => corner,
+            "ByVertex" | "ByVertice" | "ByControlPoint" => vertex,
+            "ByPolygon" => polygon,
+            _ => 0
+        }
+    }
+
+    /// The index of the value of a corner of a polygon, looked up through the indices of the layer when it has them
+    fn index(&self, corner: usize, vertex: usize, polygon: usize) -> usize {
+        let index = self.position(corner, vertex, polygon);
+
+        match self.indices {
+            Some(ref indices) => indices.get(index).map_or(usize::MAX, |&index| index as usize),
+            None => index
+        }
+    }
+}
+
+
+/// Load the meshes of an ASCII FBX file into one Mesh
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate rastermind;
+/// # use rastermind::{fbx, texture};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// let mesh = fbx::open("chair.fbx")?;
+/// let texture = texture::open(mesh.texture().unwrap())?;
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn open(filename: &str) -> Result<Mesh, MorpheusError> {
+    let bytes = fs::read(filename).map_err(|error| MorpheusError::io(filename, error))?;
+
+    parse(&bytes).map_err(|message| MorpheusError::Asset { path: filename.to_string(), message })
+}
+
+
+/// Parse the meshes of an ASCII FBX document into one Mesh
+///
+/// Every geometry is placed by the translation, rotation, and scale of its
+/// model and the models above it, and files authored with z up are turned so
+/// that y is up. Lengths are converted to meters with the unit scale factor
+/// of the global settings, which is the number of centimeters in a unit of
+/// the file, and kept in the units of the file when it has none. Polygons are
+/// split into triangles around their first corner, so they are expected to be
+/// convex.
+///
+/// The normals and UVs of the first layer of every geometry are read. When a
+/// geometry has no normals, smooth normals are generated for the whole mesh,
+/// and a geometry without UVs maps all of its corners to the corner of the
+/// texture. Every geometry is a group named after its model, and the
+/// materials of the models keep their diffuse color and the file name of
+/// their diffuse texture.
+///
+/// Binary FBX files fail to load, as do documents that are malformed or have
+/// an index out of range.
+///
+pub fn parse(bytes: &[u8]) -> Result<Mesh, String> {
+    if bytes.starts_with(BINARY_MAGIC) {
+        return Err(String::from("binary FBX files are not supported, export the file as ASCII FBX"));
+    }
+
+    let text = String::from_utf8_lossy(bytes);
+    let document = parse_nodes(&mut tokenize(&text)?.into_iter().peekable(), false)?;
+
+    let objects: Vec<&Node> = document.iter().filter(|node| node.name == "Objects")
+                                      .flat_map(|node| node.children.iter()).collect();
+    let connections: Vec<(&str, &str, Option<&str>)> = document.iter().filter(|node| node.name == "Connections")
+        .flat_map(|node| node.children.iter())
+        .filter(|connection| connection.properties.len() >= 3)
+        .map(|connection| (connection.properties[1].as_str(), connection.properties[2].as_str(),
+                           connection.properties.get(3).map(String::as_str)))
+        .collect();
+    let nodes: HashMap<&str, &Node> = objects.iter().map(|node| (node.id(), *node)).collect();
+
+    let parents = |node: &Node, class: &'static str| {
+        let id = node.id().to_string();
+
+        connections.iter().filter(move |connection| connection.0 == id).filter_map(|connection| nodes.get(connection.1))
+                   .filter(move |parent| parent.name == class).cloned().collect::<Vec<&Node>>()
+    };
+    let children = |node: &Node, class: &'static str| {
+        connections.iter().filter(|connection| connection.1 == node.id())
+                   .filter_map(|connection| nodes.get(connection.0).map(|child| (*child, connection.2)))
+                   .filter(|&(child, _)| child.name == class).collect::<Vec<(&Node, Option<&str>)>>()
+    };
+
+    let mut mesh = Mesh::default();
+    let mut material_indices: HashMap<&str, usize> = HashMap::new();
+    let mut missing_normals = false;
+
+    let geometries = objects.iter().filter(|node| {
+        node.name == "Geometry" || (node.name == "Model" && node.child("Vertices").is_some())
+    });
+
+    for geometry in geometries {
+        let model = match geometry.name.as_str() {
+            "Model" => Some(*geometry),
+            _ => parents(geometry, "Model").first().cloned()
+        };
+
+        let mut transform = Matrix4::identity();
+        let mut ancestor = model;
+
+        for _ in 0..MAXIMUM_DEPTH {
+            match ancestor {
+                Some(node) => {
+                    transform = local_transform(node) * transform;
+                    ancestor = parents(node, "Model").first().cloned();
+                },
+                None => break
+            }
+        }
+
+        let mut materials = Vec::new();
+
+        for (material, _) in model.map_or_else(Vec::new, |model| children(model, "Material")) {
+            let index = *material_indices.entry(material.id()).or_insert_with(|| {
+                let mut textures = children(material, "Texture");
+                textures.sort_by_key(|&(_, property)| property != Some("DiffuseColor"));
+
+                let texture = textures.first().and_then(|&(texture, _)| {
+                    texture.string("RelativeFilename").filter(|name| !name.is_empty())
+                           .or_else(|| texture.string("FileName"))
+                }).map(String::from);
+                let diffuse = material.vector("DiffuseColor").or_else(|| material.vector("Diffuse"))
+                                      .unwrap_or_else(|| Vector3::repeat(1.0));
+
+                mesh.materials.push(MeshMaterial { name: material.object_name().to_string(), diffuse, texture });
+                mesh.materials.len() - 1
+            });
+
+            materials.push(index);
+        }
+
+        let name = model.unwrap_or(geometry).object_name().to_string();
+        missing_normals |= append_geometry(&mut mesh, geometry, &name, &transform, &materials)?;
+    }
+
+    if mesh.object.geometric_vertices.is_empty() {
+        return Err(String::from("the file has no meshes"));
+    }
+
+    if missing_normals {
+        mesh.object.generate_normals();
+    }
+
+    let settings = document.iter().find(|node| node.name == "GlobalSettings");
+    let setting = |name: &str| {
+        settings.and_then(|settings| settings.property(name))
+                .and_then(|property| property.properties.last()).map(String::as_str)
+    };
+
+    if setting("UpAxis") == Some("2") {
+        mesh.object.convert_axes(&AxisConvention::new(UpAxis::Z, Handedness::Right));
+    }
+
+    let centimeters = setting("UnitScaleFactor").and_then(|factor| factor.parse::<f64>().ok())
+                                                .filter(|factor| factor.is_finite() && *factor > 0.0);
+
+    if let Some(centimeters) = centimeters {
+        mesh.object.scale(centimeters * Units::Centimeters.to_meters());
+    }
+
+    Ok(mesh)
+}
+
+
+/// The transform of a model relative to its parent from its translation, rotations, and scale
+fn local_transform(model: &Node) -> Matrix4<f64> {
+    let vector = |name: &str, default: f64| model.vector(name).unwrap_or_else(|| Vector3::repeat(default));
+    let rotation = |name: &str| {
+        let angles = vector(name, 0.0).map(f64::to_radians);
+
+        Rotation3::from_euler_angles(angles.x, angles.y, angles.z).to_homogeneous()
+    };
+
+    Matrix4::new_translation(&vector("Lcl Translation", 0.0)) * rotation("PreRotation") *
+        rotation("Lcl Rotation") * Matrix4::new_nonuniform_scaling(&vector("Lcl Scaling", 1.0))
+}
+
+
+/// Add a geometry to the mesh as a group, returning whether it had no normals
+fn append_geometry(mesh: &mut Mesh, geometry: &Node, name: &str, transform: &Matrix4<f64>, materials: &[usize])
+    -> Result<bool, String> {
+
+    let object = &mut mesh.object;
+    let out_of_range = || format!("the geometry {} has an index out of range", name);
+
+    let positions = geometry.child("Vertices").map_or(Ok(Vec::new()), Node::numbers)?;
+    let polygon_indices = geometry.child("PolygonVertexIndex").map_or(Ok(Vec::new()), Node::numbers)?;
+    let normals = Layer::read(geometry, "LayerElementNormal", "Normals", "NormalsIndex")?;
+    let uvs = Layer::read(geometry, "LayerElementUV", "UV", "UVIndex")?;
+    let material_layer = Layer::read(geometry, "LayerElementMaterial", "Materials", "")?;
+
+    let linear: Matrix3<f64> = transform.fixed_slice::<U3, U3>(0, 0).into_owned();
+    let normal_transform = linear.try_inverse().unwrap_or_else(Matrix3::identity).transpose();
+
+    let geometric_offset = object.geometric_vertices.len() as i32;
+    let texture_offset = object.texture_vertices.len() as i32;
+    let normal_offset = object.normal_vertices.len() as i32;
+
+    object.geometric_vertices.extend(positions.chunks(3).filter(|p| p.len() == 3).map(|p| {
+        transform.transform_point(&Vector3::new(p[0], p[1], p[2]).into()).coords
+    }));
+    object.normal_vertices.extend(normals.iter().flat_map(|layer| layer.values.chunks(3)).filter(|n| n.len() == 3)
+                                         .map(|n| (normal_transform * Vector3::new(n[0], n[1], n[2])).normalize()));
+
+    match uvs {
+        Some(ref layer) => object.texture_vertices.extend(layer.values.chunks(2).filter(|t| t.len() == 2)
+                                                                     .map(|t| Vector2::new(t[0], t[1]))),
+        None => object.texture_vertices.push(Vector2::zeros())
+    }
+
+    let vertex_count = object.geometric_vertices.len() as i32 - geometric_offset;
+    let texture_count = object.texture_vertices.len() as i32 - texture_offset;
+    let normal_count = object.normal_vertices.len() as i32 - normal_offset;
+
+    // the triangles of the geometry with their material, kept in order of material so each material is contiguous
+    let mut triangles: Vec<(Option<usize>, [Vector3<i32>; 3])> = Vec::new();
+    let mut polygon: Vec<Vector3<i32>> = Vec::new();
+    let mut polygon_count = 0;
+
+    for (corner, &index) in polygon_indices.iter().enumerate() {
+        let index = index as i64;
+        let (vertex, last) = if index < 0 { (!index, true) } else { (index, false) };
+
+        if vertex >= vertex_count as i64 {
+            return Err(out_of_range());
+        }
+
+        let texture = uvs.as_ref().map_or(0, |layer| layer.index(corner, vertex as usize, polygon_count)) as i64;
+        let normal = normals.as_ref().map_or(0, |layer| layer.index(corner, vertex as usize, polygon_count)) as i64;
+
+        if texture >= texture_count as i64 || (normals.is_some() && normal >= normal_count as i64) {
+            return Err(out_of_range());
+        }
+
+        polygon.push(Vector3::new(geometric_offset + vertex as i32, texture_offset + texture as i32,
+                                  normal_offset + normal as i32));
+
+        if last {
+            // the material layer holds the index of the material of every polygon among those of the model
+            let material = match material_layer {
+                Some(ref layer) => layer.values.get(layer.position(corner, vertex as usize, polygon_count))
+                                        .and_then(|&material| materials.get(material as usize)).cloned(),
+                None => materials.first().cloned()
+            };
+
+            for i in 1..polygon.len().saturating_sub(1) {
+                triangles.push((material, [polygon[0], polygon[i], polygon[i + 1]]));
+            }
+
+            polygon.clear();
+            polygon_count += 1;
+        }
+    }
+
+    triangles.sort_by_key(|&(material, _)| material);
+
+    let start = object.geometric_faces.len();
+
+    for (material, corners) in triangles {
+        object.geometric_faces.push(Vector3::new(corners[0][0], corners[1][0], corners[2][0]));
+        object.texture_faces.push(Vector3::new(corners[0][1], corners[1][1], corners[2][1]));
+        object.normal_faces.push(Vector3::new(corners[0][2], corners[1][2], corners[2][2]));
+        object.smoothing_groups.push(1);
+        mesh.face_materials.push(material);
+    }
+
+    if object.geometric_faces.len() > start {
+        object.groups.push(wavefront::Group { name: name.to_string(), faces: start..object.geometric_faces.len() });
+    }
+
+    Ok(normals.is_none())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use plugins;
+
+    const CUBE_CORNER: &str = r#"; FBX 7.4.0 project file
+FBXHeaderExtension:  {
+    FBXHeaderVersion: 1003
+}
+GlobalSettings:  {
+    Properties70:  {
+        P: "UpAxis", "int", "Integer", "",1
+    }
+}
+Objects:  {
+    Geometry: 100, "Geometry::Corner", "Mesh" {
+        Vertices: *15 {
+            a: 0,0,0,1,0,0,1,1,0,0,1,0,0,0,1
+        }
+        PolygonVertexIndex: *7 {
+            a: 0,1,2,-4,0,3,-5
+        }
+        LayerElementNormal: 0 {
+            MappingInformationType: "ByVertice"
+            ReferenceInformationType: "Direct"
+            Normals: *15 {
+                a: 0,0,1,0,0,1,0,0,1,0,0,1,-1,0,0
+            }
+        }
+        LayerElementUV: 0 {
+            MappingInformationType: "ByPolygonVertex"
+            ReferenceInformationType: "IndexToDirect"
+            UV: *8 {
+                a: 0,0,1,0,1,1,0,1
+            }
+            UVIndex: *7 {
+                a: 0,1,2,3,0,3,2
+            }
+        }
+        LayerElementMaterial: 0 {
+            MappingInformationType: "ByPolygon"
+            ReferenceInformationType: "IndexToDirect"
+            Materials: *2 {
+                a: 1,0
+            }
+        }
+    }
+    Model: 200, "Model::Corner", "Mesh" {
+        Properties70:  {
+            P: "Lcl Translation", "Lcl Translation", "", "A",10,0,0
+            P: "Lcl Scaling", "Lcl Scaling", "", "A",2,2,2
+        }
+    }
+    Material: 300, "Material::Wood", "" {
+        Properties70:  {
+            P: "DiffuseColor", "Color", "", "A",0.5,0.25,0
+        }
+    }
+    Material: 301, "Material::Paint", "" {
+    }
+    Texture: 400, "Texture::Wood", "" {
+        FileName: "C:/assets/wood.png"
+        RelativeFilename: "textures\wood.png"
+    }
+}
+Connections:  {
+    ;Model::Corner, Model::RootNode
+    C: "OO",200,0
+    C: "OO",100,200
+    C: "OO",300,200
+    C: "OO",301,200
+    C: "OP",400,300, "DiffuseColor"
+}
+"#;
+
+    #[test]
+    fn test_parse() {
+        let mesh = parse(CUBE_CORNER.as_bytes()).unwrap();
+        let object = &mesh.object;
+
+        assert_eq!(object.geometric_vertices[1], Vector3::new(12.0, 0.0, 0.0));
+        assert_eq!(object.geometric_faces.len(), 3);
+        assert_eq!(object.groups, vec![wavefront::Group { name: String::from("Corner"), faces: 0..3 }]);
+
+        // the triangle of the first material comes first, then the quad split in two
+        assert_eq!(object.geometric_faces[0], Vector3::new(0, 3, 4));
+        assert_eq!(object.texture_faces[0], Vector3::new(0, 3, 2));
+        assert_eq!(object.normal_faces[0], Vector3::new(0, 3, 4));
+        assert_eq!(object.normal_vertices[4], Vector3::new(-1.0, 0.0, 0.0));
+        assert_eq!(object.geometric_faces[1..], [Vector3::new(0, 1, 2), Vector3::new(0, 2, 3)]);
+        assert_eq!(object.texture_vertices[2], Vector2::new(1.0, 1.0));
+
+        assert_eq!(mesh.face_materials, vec![Some(0), Some(1), Some(1)]);
+        assert_eq!(mesh.material(1).unwrap().name, "Paint");
+        assert_eq!(mesh.materials[0], MeshMaterial { name: String::from("Wood"), diffuse: Vector3::new(0.5, 0.25, 0.0),
+                                                     texture: Some(String::from("textures\\wood.png")) });
+        assert_eq!(mesh.texture(), Some("textures\\wood.png"));
+    }
+
+    #[test]
+    fn test_fbx6() {
+        // FBX 6 files write their geometry inside the model and their arrays without braces
+        let document = br#"
+GlobalSettings:  {
+    Properties60:  {
+        Property: "UpAxis", "int", "",2
+    }
+}
+Objects:  {
+    Model: "Model::Plane", "Mesh" {
+        Vertices: 0,0,0,1,0,0,
+        1,1,0
+        PolygonVertexIndex: 0,1,-3
+    }
+}
+Connections:  {
+    Connect: "OO", "Model::Plane", "Model::Scene"
+}
+"#;
+        let mesh = parse(document).unwrap();
+
+        // the vertices are turned from z up and smooth normals are generated
+        assert_eq!(mesh.object.geometric_vertices[2], Vector3::new(1.0, 0.0, -1.0));
+        assert_eq!(mesh.object.normal_vertices.len(), 3);
+        assert_eq!(mesh.object.texture_vertices, vec![Vector2::zeros()]);
+        assert_eq!(mesh.object.groups[0].name, "Plane");
+        assert_eq!(mesh.face_materials, vec![None]);
+    }
+
+    #[test]
+    fn test_units() {
+        let up = r#"P: "UpAxis", "int", "Integer", "",1"#;
+        let unit = r#"P: "UnitScaleFactor", "double", "Number", "",1"#;
+        let centimeters = CUBE_CORNER.replace(up, &format!("{}\n        {}", up, unit));
+        let inches = centimeters.replace(r#""Number", "",1"#, r#""Number", "",2.54"#);
+
+        // the corner is 12 units along x, which are centimeters or inches
+        let vertex = |document: &str| parse(document.as_bytes()).unwrap().object.geometric_vertices[1];
+
+        assert!((vertex(&centimeters) - Vector3::new(0.12, 0.0, 0.0)).norm() < 1e-12);
+        assert!((vertex(&inches) - Vector3::new(12.0 * 0.0254, 0.0, 0.0)).norm() < 1e-12);
+        assert_eq!(vertex(CUBE_CORNER), Vector3::new(12.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_invalid_documents() {
+        assert!(parse(b"Kaydara FBX Binary  \x00\x1a\x00").unwrap_err().contains("binary"));
+        assert!(parse(b"Objects:  {\n").unwrap_err().contains("brace"));
+        assert!(parse(b"Objects:  {\n}\n").unwrap_err().contains("no meshes"));
+        assert!(parse(b"Objects: { Geometry: 1 { Vertices: 0,0,0 PolygonVertexIndex: 0,1,-3 } }").unwrap_err()
+                                                                                          .contains("out of range"));
+        assert!(parse(b"Objects: { Geometry: 1 { Vertices: 0,zero,0 } }").unwrap_err().contains("numbers"));
+
+        assert_eq!(open_registered(CUBE_CORNER).map(|object| object.geometric_faces.len()), Ok(3));
+    }
+
+    fn open_registered(document: &str) -> Result<wavefront::Object, String> {
+        let path = std::env::temp_dir().join("morpheus_corner.fbx");
+        fs::write(&path, document).unwrap();
+
+        plugins::open(path.to_str().unwrap()).map_err(|error| error.to_string())
+    }
+}
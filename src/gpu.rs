@@ -0,0 +1,442 @@
+use std::borrow::Cow;
+use std::sync::mpsc;
+
+use nalgebra::{Matrix4, Vector3};
+use wgpu::util::DeviceExt;
+
+use camera::Camera;
+use error::MorpheusError;
+use wavefront;
+
+
+/// The compute shaders of the vertex transform and the tile rasterization
+const SHADER: &str = include_str!("gpu.wgsl");
+
+/// The faces transformed by one workgroup of the transform pass
+const TRANSFORM_WORKGROUP: u32 = 64;
+
+/// The width and height in pixels of the tile drawn by one workgroup of the rasterize pass
+const TILE: u32 = 16;
+
+/// The size in bytes of a transformed face, which is eight vectors of four values
+const TRIANGLE_SIZE: u64 = 128;
+
+/// The size in bytes of the parameters of both passes, which are padded to a multiple of 16 bytes
+const PARAMETERS_SIZE: usize = 192;
+
+
+/// The shaders the GPU backend runs, as named by --shader
+///
+/// Both light the texture as the CPU shader of the same name does: Gouraud
+/// interpolates the light of the vertex normals across the face, and flat
+/// lights the whole face by its own normal.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shading {
+    Gouraud,
+    Flat
+}
+
+
+impl Shading {
+    /// Find the shading of the shader with the given name, failing for shaders that only run on the CPU
+    pub fn parse(name: &str) -> Result<Shading, MorpheusError> {
+        match name {
+            "gouraud" => Ok(Shading::Gouraud),
+            "flat" => Ok(Shading::Flat),
+            _ => Err(MorpheusError::Render(format!("the shader {} does not run on the gpu backend", name)))
+        }
+    }
+}
+
+
+/// A rasterizer that runs the vertex transform and the rasterization as compute shaders on a GPU
+///
+/// Every face is transformed into a screen space triangle by one invocation
+/// of the transform pass, then every tile of 16 by 16 pixels is drawn by one
+/// workgroup of the rasterize pass, which gathers the triangles overlapping
+/// the tile a chunk at a time and keeps the nearest fragment of every pixel
+/// in the invocation drawing it, so no depth buffer is shared. Faces are
+/// drawn in the order of the model and sampled as the CPU rasterizer samples
+/// them, so the images only differ where single precision rounds a sample
+/// or a depth the other way.
+///
+/// Any adapter that can run compute shaders is used, including the software
+/// adapters of Vulkan and OpenGL drivers such as llvmpipe.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::gpu::{Rasterizer, Shading};
+/// # use rastermind::{texture, wavefront, Camera};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// let coordinates = wavefront::Object::new("african_head.obj")?;
+/// let texture = texture::open("african_head_diffuse.png")?;
+/// let camera = Camera::builder().eye(Vector3::new(0.0, 15.0, 70.0)).build();
+///
+/// let rasterizer = Rasterizer::new()?;
+/// let buffer = rasterizer.draw_mesh(&coordinates, &texture, &camera, &Vector3::new(0.0, 0.0, 1.0),
+///                                   3840, 2160, Shading::Gouraud)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+pub struct Rasterizer {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    adapter: wgpu::AdapterInfo,
+    transform: wgpu::ComputePipeline,
+    rasterize: wgpu::ComputePipeline
+}
+
+
+impl Rasterizer {
+    /// Open the adapter the driver prefers for rendering
+    pub fn new() -> Result<Rasterizer, MorpheusError> {
+        Rasterizer::open(false)
+    }
+
+    /// Open a software adapter, which runs anywhere a driver provides one but much more slowly
+    pub fn software() -> Result<Rasterizer, MorpheusError> {
+        Rasterizer::open(true)
+    }
+
+    fn open(force_fallback_adapter: bool) -> Result<Rasterizer, MorpheusError> {
+        let instance = wgpu::Instance::default();
+        let options = wgpu::RequestAdapterOptions { power_preference: wgpu::PowerPreference::HighPerformance,
+                                                    force_fallback_adapter, compatible_surface: None };
+        let adapter = pollster::block_on(instance.request_adapter(&options))
+            .map_err(|error| MorpheusError::Render(format!("no gpu adapter: {}", error)))?;
+
+        if !adapter.get_downlevel_capabilities().flags.contains(wgpu::DownlevelFlags::COMPUTE_SHADERS) {
+            return Err(MorpheusError::Render(format!("the gpu adapter {} cannot run compute shaders",
+                                                     adapter.get_info().name)));
+        }
+
+        let descriptor = wgpu::DeviceDescriptor { label: Some("rastermind"), required_limits: adapter.limits(),
+                                                  ..Default::default() };
+        let (device, queue) = pollster::block_on(adapter.request_device(&descriptor))
+            .map_err(|error| MorpheusError::Render(format!("cannot open the gpu adapter: {}", error)))?;
+
+        let errors = device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("rasterizer"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(SHADER))
+        });
+        let pipeline = |entry_point| device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(entry_point),
+            layout: None,
+            module: &module,
+            entry_point: Some(entry_point),
+            compilation_options: Default::default(),
+            cache: None
+        });
+        let (transform, rasterize) = (pipeline("transform"), pipeline("rasterize"));
+
+        if let Some(error) = pollster::block_on(errors.pop()) {
+            return Err(MorpheusError::Render(format!("the gpu adapter {} cannot build the rasterizer: {}",
+                                                     adapter.get_info().name, error)));
+        }
+
+        Ok(Rasterizer { adapter: adapter.get_info(), device, queue, transform, rasterize })
+    }
+
+    /// The name of the adapter the rasterizer runs on
+    pub fn adapter_name(&self) -> &str {
+        &self.adapter.name
+    }
+
+    /// Draw an already loaded Object from the given camera into a new buffer of the given size
+    ///
+    /// The buffer is upside down like the buffers drawn on the CPU, and its
+    /// pixels are black where nothing is drawn. Faces whose indices are out of
+    /// range fail the render, as do models and buffers larger than the adapter
+    /// can hold.
+    ///
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_mesh(&self, coordinates: &wavefront::Object, texture: &image::RgbImage, camera: &Camera,
+                     light_vector: &Vector3<f64>, width: u32, height: u32,
+                     shading: Shading) -> Result<image::RgbImage, MorpheusError> {
+
+        if width == 0 || height == 0 || texture.width() == 0 || texture.height() == 0 {
+            return Err(MorpheusError::Render(String::from("the buffer and the texture must not be empty")));
+        }
+
+        let faces = face_indices(coordinates)?;
+
+        if faces.is_empty() {
+            return Ok(image::RgbImage::new(width, height));
+        }
+
+        let face_count = faces.len() as u32 / 9;
+        let pixels = width as u64 * height as u64;
+        let limits = self.device.limits();
+        let largest = (limits.max_storage_buffer_binding_size as u64).min(limits.max_buffer_size);
+
+        if face_count as u64 * TRIANGLE_SIZE > largest || pixels * 4 > largest ||
+            texture.width() as u64 * texture.height() as u64 * 4 > largest {
+            return Err(MorpheusError::Render(format!("the model or the buffer is too large for the gpu adapter {}",
+                                                     self.adapter.name)));
+        }
+
+        // the positions, texture coordinates, and normals share one buffer so the pass binds fewer buffers
+        let mut vertices: Vec<f32> = coordinates.geometric_vertices.iter().flat_map(|vertex| vertex.iter())
+                                                .map(|&value| value as f32).collect();
+        let texture_offset = vertices.len() as u32;
+        vertices.extend(coordinates.texture_vertices.iter().flat_map(|vertex| vertex.iter())
+                                   .map(|&value| value as f32));
+        let normal_offset = vertices.len() as u32;
+        vertices.extend(coordinates.normal_vertices.iter().flat_map(|vertex| vertex.iter())
+                                   .map(|&value| value as f32));
+
+        let texels: Vec<u32> = texture.pixels().map(|pixel| {
+            pixel[0] as u32 | (pixel[1] as u32) << 8 | (pixel[2] as u32) << 16
+        }).collect();
+
+        let groups = face_count.div_ceil(TRANSFORM_WORKGROUP);
+        let row_groups = groups.min(limits.max_compute_workgroups_per_dimension);
+        let tiles = (width.div_ceil(TILE), height.div_ceil(TILE));
+
+        if tiles.0.max(tiles.1) > limits.max_compute_workgroups_per_dimension {
+            return Err(MorpheusError::Render(format!("the buffer is too large for the gpu adapter {}",
+                                                     self.adapter.name)));
+        }
+
+        let model_view = camera.model_view();
+        let projection = camera.projection();
+        let transform = camera.viewport(width, height) * projection * model_view;
+
+        let mut parameters = Vec::with_capacity(PARAMETERS_SIZE);
+        push_matrix(&mut parameters, &transform);
+        push_matrix(&mut parameters, &(projection * model_view));
+        push_floats(&mut parameters, &[light_vector.x as f32, light_vector.y as f32, light_vector.z as f32, 0.0]);
+        push_words(&mut parameters, &[width, height, face_count, if shading == Shading::Flat { 1 } else { 0 },
+                                      texture.width(), texture.height(), texture_offset, normal_offset,
+                                      row_groups * TRANSFORM_WORKGROUP, 0, 0, 0]);
+
+        let buffer = |label, contents: &[u8], usage| {
+            self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor { label: Some(label), contents, usage })
+        };
+
+        let errors = self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let memory = self.device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+
+        let parameters = buffer("parameters", &parameters, wgpu::BufferUsages::UNIFORM);
+        let vertices = buffer("vertices", &floats(&vertices), wgpu::BufferUsages::STORAGE);
+        let faces = buffer("faces", &words(&faces), wgpu::BufferUsages::STORAGE);
+        let texels = buffer("texels", &words(&texels), wgpu::BufferUsages::STORAGE);
+        let triangles = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("triangles"), size: face_count as u64 * TRIANGLE_SIZE,
+            usage: wgpu::BufferUsages::STORAGE, mapped_at_creation: false
+        });
+        let colors = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("colors"), size: pixels * 4,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC, mapped_at_creation: false
+        });
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"), size: pixels * 4,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST, mapped_at_creation: false
+        });
+
+        let bind = |pipeline: &wgpu::ComputePipeline, buffers: &[(u32, &wgpu::Buffer)]| {
+            let entries: Vec<wgpu::BindGroupEntry> = buffers.iter().map(|&(binding, buffer)| {
+                wgpu::BindGroupEntry { binding, resource: buffer.as_entire_binding() }
+            }).collect();
+
+            self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None, layout: &pipeline.get_bind_group_layout(0), entries: &entries
+            })
+        };
+
+        let transform_group = bind(&self.transform, &[(0, &parameters), (1, &vertices), (2, &faces), (3, &triangles)]);
+        let rasterize_group = bind(&self.rasterize, &[(0, &parameters), (4, &triangles), (5, &texels), (6, &colors)]);
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("transform"),
+                                                                                      timestamp_writes: None });
+            pass.set_pipeline(&self.transform);
+            pass.set_bind_group(0, &transform_group, &[]);
+            pass.dispatch_workgroups(row_groups, groups.div_ceil(row_groups), 1);
+        }
+
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("rasterize"),
+                                                                                      timestamp_writes: None });
+            pass.set_pipeline(&self.rasterize);
+            pass.set_bind_group(0, &rasterize_group, &[]);
+            pass.dispatch_workgroups(tiles.0, tiles.1, 1);
+        }
+
+        encoder.copy_buffer_to_buffer(&colors, 0, &readback, 0, pixels * 4);
+        self.queue.submit(Some(encoder.finish()));
+
+        let (sender, receiver) = mpsc::channel();
+        readback.slice(..).map_async(wgpu::MapMode::Read, move |result| { let _ = sender.send(result); });
+
+        let failed = |error: String| MorpheusError::Render(format!("the gpu adapter {} failed: {}",
+                                                                   self.adapter.name, error));
+
+        self.device.poll(wgpu::PollType::wait_indefinitely()).map_err(|error| failed(error.to_string()))?;
+
+        for scope in [memory, errors] {
+            if let Some(error) = pollster::block_on(scope.pop()) {
+                return Err(failed(error.to_string()));
+            }
+        }
+
+        receiver.recv().map_err(|error| failed(error.to_string()))?
+                .map_err(|error| failed(error.to_string()))?;
+
+        let packed = readback.slice(..).get_mapped_range();
+        let bytes: Vec<u8> = packed.chunks(4).flat_map(|texel| texel[..3].to_vec()).collect();
+
+        image::RgbImage::from_raw(width, height, bytes)
+            .ok_or_else(|| failed(String::from("the buffer read back is not the size of the image")))
+    }
+}
+
+
+/// The geometric, texture, and normal indices of every face, failing for indices out of range
+fn face_indices(coordinates: &wavefront::Object) -> Result<Vec<u32>, MorpheusError> {
+    let mut indices = Vec::with_capacity(coordinates.geometric_faces.len() * 9);
+    let lists = [(&coordinates.geometric_faces, coordinates.geometric_vertices.len(), "vertex"),
+                 (&coordinates.texture_faces, coordinates.texture_vertices.len(), "texture vertex"),
+                 (&coordinates.normal_faces, coordinates.normal_vertices.len(), "normal")];
+
+    for face in 0..coordinates.geometric_faces.len() {
+        for &(faces, count, name) in &lists {
+            let corners = faces.get(face).ok_or_else(|| {
+                MorpheusError::Render(format!("face {} has no {} indices", face, name))
+            })?;
+
+            for &index in corners.iter() {
+                if index < 0 || index as usize >= count {
+                    return Err(MorpheusError::Render(format!("face {} has the {} index {} of {}", face, name,
+                                                             index, count)));
+                }
+
+                indices.push(index as u32);
+            }
+        }
+    }
+
+    Ok(indices)
+}
+
+
+fn push_matrix(bytes: &mut Vec<u8>, matrix: &Matrix4<f64>) {
+    // both nalgebra and WGSL store matrices by columns
+    let values: Vec<f32> = matrix.iter().map(|&value| value as f32).collect();
+    push_floats(bytes, &values);
+}
+
+
+fn push_floats(bytes: &mut Vec<u8>, values: &[f32]) {
+    bytes.extend(floats(values));
+}
+
+
+fn push_words(bytes: &mut Vec<u8>, values: &[u32]) {
+    bytes.extend(words(values));
+}
+
+
+fn floats(values: &[f32]) -> Vec<u8> {
+    values.iter().flat_map(|value| value.to_le_bytes().to_vec()).collect()
+}
+
+
+fn words(values: &[u32]) -> Vec<u8> {
+    values.iter().flat_map(|value| value.to_le_bytes().to_vec()).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use render;
+
+    /// Open the software adapter, or report that the test is skipped on machines without one
+    fn software() -> Option<Rasterizer> {
+        match Rasterizer::software() {
+            Ok(rasterizer) => Some(rasterizer),
+            Err(error) => {
+                eprintln!("skipping the gpu test: {}", error);
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn test_shading() {
+        assert_eq!(Shading::parse("gouraud"), Ok(Shading::Gouraud));
+        assert_eq!(Shading::parse("flat"), Ok(Shading::Flat));
+        assert!(matches!(Shading::parse("cel"), Err(MorpheusError::Render(_))));
+    }
+
+    #[test]
+    fn test_draw_mesh() {
+        let rasterizer = match software() {
+            Some(rasterizer) => rasterizer,
+            None => return
+        };
+
+        // two overlapping quads of a checkered texture, the nearer one tilted towards the light
+        let data = "v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\n\
+                    v -0.5 -0.5 0.5\nv 1.5 -0.5 0.3\nv 1.5 1.5 0.3\nv -0.5 1.5 0.5\n\
+                    vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nvn 0 0 1\nvn 0.3 0.2 1\n\
+                    f 1/1/1 2/2/1 3/3/1\nf 1/1/1 3/3/1 4/4/1\nf 5/1/2 6/2/2 7/3/2\nf 5/1/2 7/3/2 8/4/2\n";
+        let coordinates = wavefront::Object::from_bytes(data.as_bytes(), &Default::default()).unwrap();
+        let texture = image::RgbImage::from_fn(8, 8, |x, y| {
+            if (x + y) % 2 == 0 { image::Rgb([250, 200, 100]) } else { image::Rgb([40, 90, 160]) }
+        });
+        let camera = Camera::builder().eye(Vector3::new(0.5, 0.5, 4.0)).fov(40.0).build();
+        let light_vector = Vector3::new(0.2, 0.3, 1.0).normalize();
+
+        for &(shading, name) in &[(Shading::Gouraud, "gouraud"), (Shading::Flat, "flat")] {
+            let drawn = rasterizer.draw_mesh(&coordinates, &texture, &camera, &light_vector, 67, 45, shading)
+                                  .unwrap();
+
+            let mut pipeline = render::Pipeline::new(&camera, &light_vector, render::Framebuffer::new(67, 45));
+            pipeline.draw_mesh_with_shader(&coordinates, &texture, name).unwrap();
+            let expected = pipeline.into_framebuffer().into_color();
+
+            // single precision only changes the odd sample on an edge or a texel boundary
+            let differing = drawn.pixels().zip(expected.pixels())
+                                 .filter(|&(a, b)| (0..3).any(|i| (a[i] as i32 - b[i] as i32).abs() > 1))
+                                 .count();
+
+            assert_eq!(drawn.dimensions(), (67, 45));
+            assert!(expected.pixels().filter(|pixel| pixel[0] > 0).count() > 1000);
+            assert!(differing * 200 < 67 * 45, "{} of the pixels drawn by {} differ", differing, name);
+        }
+    }
+
+    #[test]
+    fn test_draw_mesh_validation() {
+        let rasterizer = match software() {
+            Some(rasterizer) => rasterizer,
+            None => return
+        };
+
+        let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+        let camera = Camera::builder().eye(Vector3::new(0.0, 0.0, 3.0)).build();
+        let mut coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n",
+                                                            &Default::default()).unwrap();
+
+        assert!(rasterizer.draw_mesh(&coordinates, &texture, &camera, &Vector3::z(), 0, 8, Shading::Flat).is_err());
+
+        coordinates.geometric_faces[0].x = 3;
+        assert!(matches!(rasterizer.draw_mesh(&coordinates, &texture, &camera, &Vector3::z(), 8, 8, Shading::Flat),
+                         Err(MorpheusError::Render(_))));
+
+        coordinates.geometric_faces.clear();
+        let empty = rasterizer.draw_mesh(&coordinates, &texture, &camera, &Vector3::z(), 8, 8, Shading::Flat).unwrap();
+        assert!(empty.pixels().all(|pixel| pixel[0] == 0));
+    }
+}
@@ -0,0 +1,241 @@
+// The vertex transform and tile rasterization of the GPU backend
+//
+// The transform pass turns every face into a screen space triangle with the
+// light and texture coordinates of its corners, and the rasterize pass draws
+// the triangles into tiles of 16 by 16 pixels. Both follow the CPU rasterizer:
+// pixels are sampled at their corner, barycentric coordinates are found the
+// same way, and a fragment is drawn where its depth is greater than the depth
+// drawn so far, starting from -1, so the first of equal fragments is kept.
+
+struct Parameters {
+    // the viewport, projection, and model view combined into one matrix
+    transform: mat4x4<f32>,
+    // the projection and model view, which place the vertices in the scene for the flat shader
+    camera: mat4x4<f32>,
+    light: vec4<f32>,
+    width: u32,
+    height: u32,
+    faces: u32,
+    shading: u32,
+    texture_width: u32,
+    texture_height: u32,
+    texture_offset: u32,
+    normal_offset: u32,
+    // the number of faces in a row of the dispatch of the transform pass
+    row: u32,
+}
+
+struct Triangle {
+    x: vec4<f32>,
+    y: vec4<f32>,
+    z: vec4<f32>,
+    w: vec4<f32>,
+    intensity: vec4<f32>,
+    u: vec4<f32>,
+    v: vec4<f32>,
+    // the first and last pixel of the bounding box, which is empty for degenerate triangles
+    bounds: vec4<u32>,
+}
+
+// the shading of the Gouraud shader is 0
+const FLAT: u32 = 1u;
+const TILE: u32 = 16u;
+const CHUNK: u32 = 256u;
+
+@group(0) @binding(0) var<uniform> parameters: Parameters;
+@group(0) @binding(1) var<storage, read> vertices: array<f32>;
+@group(0) @binding(2) var<storage, read> faces: array<u32>;
+@group(0) @binding(3) var<storage, read_write> triangles: array<Triangle>;
+
+@group(0) @binding(4) var<storage, read> drawn: array<Triangle>;
+@group(0) @binding(5) var<storage, read> texels: array<u32>;
+@group(0) @binding(6) var<storage, read_write> colors: array<u32>;
+
+// the triangles of the current chunk whose bounding box overlaps the tile, one bit per triangle
+var<workgroup> candidates: array<atomic<u32>, 8>;
+
+
+fn is_finite(value: vec4<f32>) -> bool {
+    return all(abs(value) <= vec4<f32>(3.4028235e38));
+}
+
+
+// truncate a screen coordinate to a pixel, saturating as a cast from f64 to u32 does
+fn pixel(value: f32) -> u32 {
+    return u32(clamp(value, 0.0, 4294967040.0));
+}
+
+
+fn vertex(offset: u32, index: u32) -> vec3<f32> {
+    return vec3<f32>(vertices[offset + 3u * index], vertices[offset + 3u * index + 1u],
+                     vertices[offset + 3u * index + 2u]);
+}
+
+
+@compute @workgroup_size(64)
+fn transform(@builtin(global_invocation_id) id: vec3<u32>) {
+    let face = id.x + id.y * parameters.row;
+
+    if face >= parameters.faces {
+        return;
+    }
+
+    var triangle: Triangle;
+    var scene: array<vec3<f32>, 3>;
+    var finite = true;
+
+    for (var corner = 0u; corner < 3u; corner++) {
+        let position = vec4<f32>(vertex(0u, faces[9u * face + corner]), 1.0);
+        let texture_index = parameters.texture_offset + 2u * faces[9u * face + 3u + corner];
+        let normal = vertex(parameters.normal_offset, faces[9u * face + 6u + corner]);
+        let clip = parameters.transform * position;
+        let placed = parameters.camera * position;
+
+        triangle.x[corner] = clip.x / clip.w;
+        triangle.y[corner] = clip.y / clip.w;
+        triangle.z[corner] = clip.z;
+        triangle.w[corner] = clip.w;
+        triangle.intensity[corner] = max(0.0, dot(normalize(normal), parameters.light.xyz));
+        triangle.u[corner] = vertices[texture_index];
+        triangle.v[corner] = vertices[texture_index + 1u];
+
+        scene[corner] = placed.xyz / placed.w;
+        finite = finite && is_finite(clip) && clip.w != 0.0;
+    }
+
+    if parameters.shading == FLAT {
+        let normal = normalize(cross(scene[1] - scene[0], scene[2] - scene[0]));
+        triangle.intensity = vec4<f32>(vec3<f32>(dot(normal, parameters.light.xyz)), 0.0);
+    }
+
+    let area = (triangle.x[1] - triangle.x[0]) * (triangle.y[2] - triangle.y[0]) -
+               (triangle.y[1] - triangle.y[0]) * (triangle.x[2] - triangle.x[0]);
+
+    if finite && is_finite(vec4<f32>(triangle.x.xyz, area)) && is_finite(vec4<f32>(triangle.y.xyz, 0.0)) &&
+       abs(area) >= 1e-12 {
+        let last = vec2<u32>(parameters.width - 1u, parameters.height - 1u);
+        let first = vec2<u32>(min(pixel(triangle.x[0]), min(pixel(triangle.x[1]), pixel(triangle.x[2]))),
+                              min(pixel(triangle.y[0]), min(pixel(triangle.y[1]), pixel(triangle.y[2]))));
+        let end = vec2<u32>(max(pixel(triangle.x[0]), max(pixel(triangle.x[1]), pixel(triangle.x[2]))),
+                            max(pixel(triangle.y[0]), max(pixel(triangle.y[1]), pixel(triangle.y[2]))));
+
+        triangle.bounds = vec4<u32>(min(first, last), min(end, last));
+    } else {
+        triangle.bounds = vec4<u32>(1u, 1u, 0u, 0u);
+    }
+
+    triangles[face] = triangle;
+}
+
+
+// the color of the texture nearest to the given texture coordinate, as texture::sample finds it
+fn sample(u: f32, v: f32) -> vec3<f32> {
+    let width = f32(parameters.texture_width);
+    let height = f32(parameters.texture_height);
+    let x = u32(clamp(u * width, 0.0, width - 1.0));
+    let y = u32(clamp(v * height, 0.0, height - 1.0));
+    let texel = texels[x + y * parameters.texture_width];
+
+    return vec3<f32>(f32(texel & 255u), f32((texel >> 8u) & 255u), f32((texel >> 16u) & 255u));
+}
+
+
+// the barycentric coordinate of the given pixel in the triangle and its depth, which is never drawn if not covered
+fn cover(triangle: Triangle, x: u32, y: u32) -> vec4<f32> {
+    let outside = vec4<f32>(0.0, 0.0, 0.0, -3.4028235e38);
+
+    if x < triangle.bounds.x || x > triangle.bounds.z || y < triangle.bounds.y || y > triangle.bounds.w {
+        return outside;
+    }
+
+    let u = vec3<f32>(triangle.x[2] - triangle.x[0], triangle.x[1] - triangle.x[0], triangle.x[0] - f32(x));
+    let v = vec3<f32>(triangle.y[2] - triangle.y[0], triangle.y[1] - triangle.y[0], triangle.y[0] - f32(y));
+    let w = cross(u, v);
+
+    if abs(w.z) < 0.01 {
+        return outside;
+    }
+
+    let coordinate = vec3<f32>(1.0 - (w.x + w.y) / w.z, w.y / w.z, w.x / w.z);
+
+    if any(coordinate < vec3<f32>(0.0)) {
+        return outside;
+    }
+
+    return vec4<f32>(coordinate, dot(triangle.z.xyz, coordinate) / dot(triangle.w.xyz, coordinate));
+}
+
+
+@compute @workgroup_size(16, 16)
+fn rasterize(@builtin(workgroup_id) tile: vec3<u32>, @builtin(local_invocation_id) local: vec3<u32>,
+             @builtin(local_invocation_index) index: u32) {
+
+    let x = tile.x * TILE + local.x;
+    let y = tile.y * TILE + local.y;
+    let inside = x < parameters.width && y < parameters.height;
+    let first = tile.xy * TILE;
+    let last = min(first + vec2<u32>(TILE - 1u), vec2<u32>(parameters.width - 1u, parameters.height - 1u));
+
+    var depth = -1.0;
+    var color = 0u;
+
+    for (var start = 0u; start < parameters.faces; start += CHUNK) {
+        if index < 8u {
+            atomicStore(&candidates[index], 0u);
+        }
+
+        workgroupBarrier();
+
+        let face = start + index;
+
+        if face < parameters.faces {
+            let bounds = drawn[face].bounds;
+
+            if bounds.x <= bounds.z && bounds.y <= bounds.w && bounds.x <= last.x && bounds.z >= first.x &&
+               bounds.y <= last.y && bounds.w >= first.y {
+                atomicOr(&candidates[index / 32u], 1u << (index % 32u));
+            }
+        }
+
+        workgroupBarrier();
+
+        var words: array<u32, 8>;
+
+        for (var word = 0u; word < 8u; word++) {
+            words[word] = atomicLoad(&candidates[word]);
+        }
+
+        workgroupBarrier();
+
+        if inside {
+            // the bits are visited in order so the faces are drawn in the order of the model
+            for (var word = 0u; word < 8u; word++) {
+                var bits = words[word];
+
+                while bits != 0u {
+                    let triangle = drawn[start + 32u * word + countTrailingZeros(bits)];
+                    bits &= bits - 1u;
+
+                    let fragment = cover(triangle, x, y);
+                    let coordinate = fragment.xyz;
+
+                    if depth < fragment.w {
+                        let intensity = dot(triangle.intensity.xyz, coordinate);
+                        let uv = vec2<f32>(dot(triangle.u.xyz, coordinate), dot(triangle.v.xyz, coordinate));
+                        let shade = sample(uv.x, uv.y) * intensity;
+                        // NaN turns black, as a cast from f64 to u8 turns it
+                        let finite = select(shade, vec3<f32>(0.0), shade != shade);
+                        let bytes = vec3<u32>(clamp(finite, vec3<f32>(0.0), vec3<f32>(255.0)));
+
+                        depth = fragment.w;
+                        color = bytes.x | (bytes.y << 8u) | (bytes.z << 16u);
+                    }
+                }
+            }
+        }
+    }
+
+    if inside {
+        colors[x + y * parameters.width] = color;
+    }
+}
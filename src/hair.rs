@@ -0,0 +1,364 @@
+use nalgebra::{Matrix3, Matrix4, Vector3, Vector4};
+
+use shader::{Contributions, Shader};
+use transparency;
+use vector;
+use wavefront;
+
+
+/// Options that control how the polylines of an Object are drawn as strands of hair
+///
+/// The width is the width of a strand at its root in the units of the scene
+/// and the taper is the width at its tip as a fraction of the root. Strands
+/// are lit with the Kajiya-Kay model, where the highlight runs across the
+/// strands and is tighter with a greater shininess.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate rastermind;
+/// # use rastermind::hair::HairOptions;
+/// let blond = HairOptions { color: image::Rgb([200, 160, 90]), width: 0.004, ..Default::default() };
+/// ```
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HairOptions {
+    pub width: f64,
+    pub taper: f64,
+    pub color: image::Rgb<u8>,
+    pub highlight: image::Rgb<u8>,
+    pub shininess: f64
+}
+
+
+impl Default for HairOptions {
+    fn default() -> HairOptions {
+        HairOptions { width: 0.01, taper: 0.2, color: image::Rgb([90, 60, 40]),
+                      highlight: image::Rgb([120, 110, 100]), shininess: 60.0 }
+    }
+}
+
+
+/// Strips of faces that follow the polylines of an Object and always face the camera
+///
+/// The normals of the strips are the tangents of the strands. Strands that are
+/// thinner than a pixel on the screen are widened to a pixel, and their
+/// coverage of that pixel, from 0 to 1 at every vertex, is drawn as a dither.
+/// The eye is the position of the camera, or the direction towards it with a
+/// w of 0 when the projection is orthographic.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::hair::*;
+/// # use rastermind::{shader, wavefront};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nl 1 2 3\n", &Default::default())?;
+/// # let (eye, center, up) = (Vector3::<f64>::new(0.0, 0.0, 3.0), Vector3::zeros(), Vector3::y());
+/// # let view_port = shader::viewport(64, 64, 384, 384, 255);
+/// # let projection = shader::projection(-1.0 / 3.0);
+/// # let model_view = shader::lookat(&eye, &center, &up);
+/// let ribbons = Ribbons::new(&coordinates, &view_port, &projection, &model_view, &HairOptions::default());
+/// # Ok(())
+/// # }
+/// ```
+///
+pub struct Ribbons {
+    pub object: wavefront::Object,
+    pub coverage: Vec<f64>,
+    pub strands: Vec<usize>,
+    pub eye: Vector4<f64>
+}
+
+
+impl Ribbons {
+    /// Build the strips of the polylines of the given Object as seen by the given camera
+    pub fn new(coordinates: &wavefront::Object, view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+               model_view: &Matrix4<f64>, options: &HairOptions) -> Ribbons {
+
+        let inverse = model_view.try_inverse().unwrap_or_else(Matrix4::identity);
+        let eye = if projection[(3, 2)] == 0.0 {
+            inverse * Vector4::new(0.0, 0.0, 1.0, 0.0)
+        } else {
+            inverse * Vector4::new(0.0, 0.0, -1.0 / projection[(3, 2)], 1.0)
+        };
+
+        let transform = view_port * projection * model_view;
+        let screen = |point: &Vector3<f64>| vector::project_to_3d(&(transform * vector::vectorize_to_4d(point)));
+
+        let mut ribbons = Ribbons { object: wavefront::Object { texture_vertices: vec![nalgebra::Vector2::zeros()],
+                                                                ..Default::default() },
+                                    coverage: Vec::new(), strands: Vec::new(), eye };
+
+        for (strand, line) in coordinates.lines.iter().enumerate().filter(|(_, line)| line.len() > 1) {
+            let points: Vec<Vector3<f64>> = line.iter().map(|&index| coordinates.geometric_vertices[index as usize])
+                                                       .collect();
+            let base = ribbons.object.geometric_vertices.len() as i32;
+
+            for (i, point) in points.iter().enumerate() {
+                let tangent = (points[(i + 1).min(points.len() - 1)] - points[i.saturating_sub(1)])
+                    .try_normalize(0.0)
+                    .unwrap_or_else(Vector3::y);
+                let toward_eye = if eye.w == 0.0 { eye.xyz() } else { eye.xyz() - point };
+                let side = tangent.cross(&toward_eye).try_normalize(0.0).unwrap_or_else(Vector3::x);
+
+                let along = i as f64 / (points.len() - 1) as f64;
+                let half_width = options.width * (1.0 + (options.taper - 1.0) * along) / 2.0;
+
+                // strands thinner than a pixel are widened to a pixel and dithered by how much of it they cover
+                let pixels = (screen(&(point + side * half_width)) - screen(&(point - side * half_width))).xy().norm();
+                let (half_width, coverage) = if pixels > 0.0 && pixels < 1.0 {
+                    (half_width / pixels, pixels)
+                } else {
+                    (half_width, 1.0)
+                };
+
+                ribbons.object.geometric_vertices.push(point - side * half_width);
+                ribbons.object.geometric_vertices.push(point + side * half_width);
+                ribbons.object.normal_vertices.push(tangent);
+                ribbons.coverage.extend_from_slice(&[coverage, coverage]);
+            }
+
+            for i in 0..points.len() as i32 - 1 {
+                let (a, b, c, d) = (base + 2 * i, base + 2 * i + 1, base + 2 * i + 3, base + 2 * i + 2);
+                let tangent = base / 2 + i;
+
+                ribbons.object.geometric_faces.push(Vector3::new(a, b, c));
+                ribbons.object.geometric_faces.push(Vector3::new(a, c, d));
+                ribbons.object.normal_faces.push(Vector3::new(tangent, tangent, tangent + 1));
+                ribbons.object.normal_faces.push(Vector3::new(tangent, tangent + 1, tangent + 1));
+                ribbons.strands.extend_from_slice(&[strand, strand]);
+            }
+        }
+
+        let face_count = ribbons.object.geometric_faces.len();
+        ribbons.object.texture_faces = vec![Vector3::zeros(); face_count];
+        ribbons.object.smoothing_groups = vec![0; face_count];
+
+        ribbons
+    }
+}
+
+
+/// Shade the strips of Ribbons as strands of hair with the Kajiya-Kay model
+///
+/// The diffuse light of a strand is the sine of the angle between the strand
+/// and the light, since light reaches it from all around, and the highlight
+/// peaks where the half vector is perpendicular to the strand.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::hair::*;
+/// # use rastermind::{shader, wavefront};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nl 1 2 3\n", &Default::default())?;
+/// # let (eye, center, up) = (Vector3::<f64>::new(0.0, 0.0, 3.0), Vector3::zeros(), Vector3::y());
+/// # let view_port = shader::viewport(64, 64, 384, 384, 255);
+/// # let projection = shader::projection(-1.0 / 3.0);
+/// # let model_view = shader::lookat(&eye, &center, &up);
+/// # let options = HairOptions::default();
+/// # let ribbons = Ribbons::new(&coordinates, &view_port, &projection, &model_view, &options);
+/// # let face_index = 0;
+/// let shader = HairShader::new(&options, &ribbons, face_index);
+/// # Ok(())
+/// # }
+/// ```
+///
+pub struct HairShader<'a> {
+    pub options: &'a HairOptions,
+    pub ribbons: &'a Ribbons,
+    pub pattern: usize,
+    pub varying_tangent: Matrix3<f64>,
+    pub varying_position: Matrix3<f64>,
+    pub varying_light: Matrix3<f64>,
+    pub varying_coverage: Vector3<f64>,
+    pub varying_screen: Matrix3<f64>,
+    pub varying_w: Vector3<f64>
+}
+
+
+impl<'a> HairShader<'a> {
+    /// Create a new instance of a HairShader for the given face of the Ribbons
+    pub fn new(options: &'a HairOptions, ribbons: &'a Ribbons, face_index: usize) -> HairShader<'a> {
+        HairShader { options, ribbons, pattern: ribbons.strands.get(face_index).cloned().unwrap_or(0),
+                     varying_tangent: Matrix3::zeros(), varying_position: Matrix3::zeros(),
+                     varying_light: Matrix3::zeros(), varying_coverage: Vector3::zeros(),
+                     varying_screen: Matrix3::zeros(), varying_w: Vector3::zeros() }
+    }
+
+    /// Find the diffuse and highlight intensities of the fragment
+    fn intensities(&self, vertex: Vector3<f64>) -> (f64, f64) {
+        let perspective = vertex.component_div(&self.varying_w);
+        let perspective = perspective / perspective.sum();
+
+        let tangent = (self.varying_tangent * perspective).try_normalize(0.0).unwrap_or_else(Vector3::y);
+        let light = (self.varying_light * perspective).try_normalize(0.0).unwrap_or_else(Vector3::z);
+        let position = self.varying_position * perspective;
+
+        let eye = self.ribbons.eye;
+        let view = if eye.w == 0.0 { eye.xyz() } else { eye.xyz() - position }.try_normalize(0.0)
+                                                                              .unwrap_or_else(Vector3::z);
+        let half = (light + view).try_normalize(0.0).unwrap_or(light);
+
+        let sine = |cosine: f64| (1.0 - cosine * cosine).max(0.0).sqrt();
+
+        (sine(tangent.dot(&light)), sine(tangent.dot(&half)).powf(self.options.shininess))
+    }
+}
+
+
+impl<'a> Shader for HairShader<'a> {
+    /// Position the vertices into their scene coordinates and keep the tangents of the strands
+    fn vertex(&mut self, coordinates: &wavefront::Object,
+              view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+              model_view: &Matrix4<f64>, light_vector: &Vector3<f64>,
+              face_index: usize, vertex_index: usize) -> Vector4<f64> {
+
+        let geometric_index = coordinates.geometric_faces[face_index][vertex_index] as usize;
+        let normal_index = coordinates.normal_faces[face_index][vertex_index] as usize;
+        let position = coordinates.geometric_vertices[geometric_index];
+
+        self.varying_tangent.set_column(vertex_index, &coordinates.normal_vertices[normal_index]);
+        self.varying_position.set_column(vertex_index, &position);
+        self.varying_light.set_column(vertex_index, light_vector);
+        self.varying_coverage[vertex_index] = self.ribbons.coverage.get(geometric_index).cloned().unwrap_or(1.0);
+
+        let screen_coordinate = view_port * projection * model_view * vector::vectorize_to_4d(&position);
+        self.varying_w[vertex_index] = screen_coordinate.w;
+        self.varying_screen.set_column(vertex_index, &vector::project_to_3d(&screen_coordinate));
+
+        screen_coordinate
+    }
+
+    /// Add the diffuse color of the strand and its highlight
+    fn fragment(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> image::Rgb<u8> {
+        let contributions = self.contributions(vertex, texture);
+        let mut color = contributions.diffuse;
+
+        (0..=2).for_each(|i| color[i] = color[i].saturating_add(contributions.specular[i]));
+
+        color
+    }
+
+    /// Discard the fragments that the dither leaves out of strands thinner than a pixel
+    fn discard(&self, vertex: Vector3<f64>) -> bool {
+        let pixel = self.varying_screen * vertex;
+        let coverage = self.varying_coverage.dot(&vertex);
+
+        coverage < 1.0 && !transparency::screen_door(pixel.x.round().max(0.0) as u32,
+                                                     pixel.y.round().max(0.0) as u32, coverage, self.pattern)
+    }
+
+    /// Split the light of the strand into its diffuse color and its highlight
+    fn contributions(&self, vertex: Vector3<f64>, _texture: &image::RgbImage) -> Contributions {
+        let (diffuse, specular) = self.intensities(vertex);
+        let scale = |color: image::Rgb<u8>, intensity: f64| {
+            image::Rgb([0, 1, 2].map(|i| (color[i] as f64 * intensity).min(255.0) as u8))
+        };
+
+        Contributions { diffuse: scale(self.options.color, diffuse), specular: scale(self.options.highlight, specular),
+                        ..Default::default() }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use render;
+    use shader;
+
+    fn strands(count: usize, spacing: f64) -> wavefront::Object {
+        let mut object = wavefront::Object::default();
+
+        for strand in 0..count {
+            let x = strand as f64 * spacing - 0.5;
+            let base = object.geometric_vertices.len() as i32;
+
+            object.geometric_vertices.extend((0..5).map(|i| Vector3::new(x, i as f64 / 2.0 - 1.0, 0.0)));
+            object.lines.push((base..base + 5).collect());
+        }
+
+        object
+    }
+
+    #[test]
+    fn test_ribbons() {
+        let view_port = shader::viewport(0, 0, 100, 100, 255);
+        let projection = shader::projection(-1.0 / 3.0);
+        let model_view = shader::lookat(&Vector3::new(0.0, 0.0, 3.0), &Vector3::zeros(), &Vector3::y());
+
+        let thick = HairOptions { width: 0.2, taper: 0.5, ..Default::default() };
+        let ribbons = Ribbons::new(&strands(2, 1.0), &view_port, &projection, &model_view, &thick);
+
+        // two faces for each of the four segments of both strands, each across the view of the camera
+        assert_eq!(ribbons.object.geometric_faces.len(), 16);
+        assert_eq!(ribbons.strands[8], 1);
+        assert!((ribbons.eye - Vector4::new(0.0, 0.0, 3.0, 1.0)).norm() < 1e-12);
+        assert!(ribbons.object.geometric_vertices.chunks(2).all(|pair| {
+            (pair[1] - pair[0]).dot(&(ribbons.eye.xyz() - (pair[0] + pair[1]) / 2.0)).abs() < 1e-12
+        }));
+
+        // the strands taper from the root to the tip and are tangent to their lines
+        let width = |ribbons: &Ribbons, index: usize| (ribbons.object.geometric_vertices[2 * index + 1] -
+                                                       ribbons.object.geometric_vertices[2 * index]).norm();
+        assert!((width(&ribbons, 0) - 0.2).abs() < 1e-12);
+        assert!((width(&ribbons, 4) - 0.1).abs() < 1e-12);
+        assert_eq!(ribbons.object.normal_vertices[2], Vector3::y());
+        assert!(ribbons.coverage.iter().all(|&coverage| coverage == 1.0));
+
+        // strands thinner than a pixel are a pixel wide and cover part of it
+        let thin = HairOptions { width: 0.001, ..Default::default() };
+        let ribbons = Ribbons::new(&strands(1, 1.0), &view_port, &projection, &model_view, &thin);
+
+        assert!((ribbons.coverage[0] - 0.05).abs() < 2e-3);
+        assert!(ribbons.coverage[8] < ribbons.coverage[0]);
+        assert!((width(&ribbons, 0) - 0.02).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_hair_shader() {
+        let view_port = shader::viewport(0, 0, 64, 64, 255);
+        let projection = shader::projection(0.0);
+        let model_view = Matrix4::identity();
+
+        let draw = |options: &HairOptions, light_vector: Vector3<f64>| {
+            let ribbons = Ribbons::new(&strands(16, 1.0 / 16.0), &view_port, &projection, &model_view, options);
+            let mut buffer = image::RgbImage::new(64, 64);
+            let mut zbuffer = vec![-1.0; 64 * 64];
+
+            render::draw_faces(&ribbons.object, &mut buffer, &image::RgbImage::new(1, 1), &mut zbuffer,
+                               &render::DepthOptions::default(), &view_port, &projection, &model_view,
+                               &light_vector, |face_index| -> Box<dyn Shader> {
+                Box::new(HairShader::new(options, &ribbons, face_index))
+            });
+
+            buffer
+        };
+
+        let options = HairOptions { width: 0.05, taper: 1.0, color: image::Rgb([200, 100, 50]),
+                                    highlight: image::Rgb([50, 50, 50]), shininess: 10.0 };
+
+        // light across the strands lights them fully, with the highlight where the half vector is across too
+        let across = draw(&options, Vector3::z());
+        assert_eq!(*across.get_pixel(16, 32), image::Rgb([250, 150, 100]));
+
+        // light along the strands leaves them dark
+        let along = draw(&options, Vector3::y());
+        assert!(along.get_pixel(16, 32)[0] < 30);
+
+        // strands much thinner than a pixel still show as a dither of some of their pixels
+        let thin = draw(&HairOptions { width: 0.005, ..options }, Vector3::z());
+        let drawn = thin.pixels().filter(|&&pixel| pixel != image::Rgb([0, 0, 0])).count();
+
+        assert!(drawn > 0 && drawn < 16 * 32);
+    }
+}
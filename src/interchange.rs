@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use nalgebra::{Matrix4, Quaternion, UnitQuaternion, Vector3};
+
+use assets::SearchPaths;
+use cache;
+use camera::Camera;
+use error::MorpheusError;
+use light::{Light, Spotlight};
+use plugins;
+use scene::{Scene, SceneLight, SceneObject};
+
+
+/// The version of the interchange format this crate reads
+pub const VERSION: u32 = 1;
+
+
+/// A model file and the texture it is drawn with, shared by every object that places it
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MeshReference {
+    pub path: String,
+    #[serde(default)]
+    pub texture: Option<String>
+}
+
+
+/// Where an object is placed, either by a matrix or by its translation, rotation, and scale
+///
+/// The matrix is written row by row, as Assimp writes the transformations of
+/// its nodes. Without a matrix the object is scaled, then rotated by the
+/// quaternion written w first as Blender writes them, then translated.
+///
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Transform {
+    pub matrix: Option<[f64; 16]>,
+    pub translation: [f64; 3],
+    pub rotation: [f64; 4],
+    pub scale: [f64; 3]
+}
+
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform { matrix: None, translation: [0.0; 3], rotation: [1.0, 0.0, 0.0, 0.0], scale: [1.0; 3] }
+    }
+}
+
+
+impl Transform {
+    /// The matrix that moves an object from its own coordinates into the scene
+    pub fn to_matrix(&self) -> Matrix4<f64> {
+        if let Some(ref matrix) = self.matrix {
+            return Matrix4::from_row_slice(matrix);
+        }
+
+        let [w, x, y, z] = self.rotation;
+        let rotation = UnitQuaternion::from_quaternion(Quaternion::new(w, x, y, z));
+
+        Matrix4::new_translation(&Vector3::from(self.translation)) * rotation.to_homogeneous() *
+            Matrix4::new_nonuniform_scaling(&Vector3::from(self.scale))
+    }
+}
+
+
+/// An object of an exported scene, placing a mesh by name
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ObjectNode {
+    pub name: String,
+    pub mesh: String,
+    #[serde(default)]
+    pub transform: Transform,
+    #[serde(default = "ObjectNode::default_opacity")]
+    pub opacity: f64
+}
+
+
+impl ObjectNode {
+    fn default_opacity() -> f64 {
+        1.0
+    }
+}
+
+
+/// A camera of an exported scene, with its vertical field of view in degrees
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraNode {
+    pub name: String,
+    pub eye: [f64; 3],
+    pub target: [f64; 3],
+    #[serde(default = "CameraNode::default_up")]
+    pub up: [f64; 3],
+    #[serde(default)]
+    pub fov: Option<f64>
+}
+
+
+impl CameraNode {
+    fn default_up() -> [f64; 3] {
+        [0.0, 1.0, 0.0]
+    }
+
+    /// The camera the node describes
+    pub fn to_camera(&self) -> Camera {
+        let builder = Camera::builder().eye(Vector3::from(self.eye)).target(Vector3::from(self.target))
+                                       .up(Vector3::from(self.up));
+
+        match self.fov {
+            Some(fov) => builder.fov(fov).build(),
+            None => builder.build()
+        }
+    }
+}
+
+
+/// A light of an exported scene
+///
+/// The direction of a directional light points from the scene towards the
+/// light, as with the light vectors of the renderer, while a spotlight points
+/// its direction at what it lights. The angles of a spotlight are in radians
+/// from its direction.
+///
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum LightNode {
+    Directional {
+        name: String,
+        direction: [f64; 3],
+        #[serde(default = "LightNode::default_intensity")]
+        intensity: f64
+    },
+    Spot {
+        name: String,
+        position: [f64; 3],
+        direction: [f64; 3],
+        inner_angle: f64,
+        outer_angle: f64,
+        #[serde(default = "LightNode::default_intensity")]
+        intensity: f64
+    }
+}
+
+
+impl LightNode {
+    fn default_intensity() -> f64 {
+        1.0
+    }
+
+    /// The light of the scene the node describes
+    pub fn to_light(&self) -> SceneLight {
+        match *self {
+            LightNode::Directional { ref name, direction, intensity } => {
+                let light = Light::Directional(Vector3::from(direction).normalize());
+
+                SceneLight { intensity, ..SceneLight::new(name, light) }
+            },
+            LightNode::Spot { ref name, position, direction, inner_angle, outer_angle, intensity } => {
+                let spot = Spotlight::new(Vector3::from(position), Vector3::from(direction), inner_angle, outer_angle);
+
+                SceneLight { intensity, ..SceneLight::new(name, Light::Spot(spot)) }
+            }
+        }
+    }
+}
+
+
+/// A scene authored in another tool and exported as JSON for the renderer
+///
+/// Meshes are model files referred to by name, so an object placed many
+/// times is only loaded once, and their paths are resolved relative to the
+/// scene file. Everything is in the coordinates of the renderer, which are
+/// right handed with y up; the Blender export script in the tools directory
+/// converts from the z up coordinates of Blender as it writes the scene. The
+/// active camera is the one the scene is rendered from, which defaults to the
+/// first camera.
+///
+/// # Examples
+///
+/// ```json
+/// {
+///     "version": 1,
+///     "meshes": {"suzanne": {"path": "meshes/suzanne.obj", "texture": "textures/suzanne.png"}},
+///     "objects": [{"name": "Suzanne", "mesh": "suzanne", "transform": {"translation": [0, 1, 0]}}],
+///     "cameras": [{"name": "Camera", "eye": [0, 2, 8], "target": [0, 1, 0], "fov": 40}],
+///     "lights": [{"type": "directional", "name": "Sun", "direction": [1, 2, 1], "intensity": 0.8}]
+/// }
+/// ```
+///
+/// ```no_run
+/// # extern crate rastermind;
+/// # use rastermind::{interchange, Renderer};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// let (scene, camera) = interchange::import("exported/scene.json")?;
+/// let image = Renderer::new(1024, 1024).with_camera(camera.unwrap_or_default()).render_scene(&scene)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SceneFile {
+    pub version: u32,
+    pub meshes: HashMap<String, MeshReference>,
+    pub objects: Vec<ObjectNode>,
+    pub cameras: Vec<CameraNode>,
+    pub lights: Vec<LightNode>,
+    pub active_camera: Option<String>
+}
+
+
+impl SceneFile {
+    /// Read a scene file, failing on versions newer than this crate reads
+    pub fn parse(json: &str) -> Result<SceneFile, MorpheusError> {
+        let file: SceneFile = serde_json::from_str(json).map_err(|error| MorpheusError::Scene(error.to_string()))?;
+
+        if file.version > VERSION {
+            return Err(MorpheusError::Scene(format!("version {} of the interchange format is newer than {}",
+                                                    file.version, VERSION)));
+        }
+
+        Ok(file)
+    }
+
+    /// The camera the scene is rendered from, if it has any
+    pub fn camera(&self) -> Result<Option<Camera>, MorpheusError> {
+        match self.active_camera {
+            Some(ref name) => self.cameras.iter().find(|camera| &camera.name == name)
+                                  .map(|camera| Some(camera.to_camera()))
+                                  .ok_or_else(|| MorpheusError::Scene(format!("there is no camera named {}", name))),
+            None => Ok(self.cameras.first().map(CameraNode::to_camera))
+        }
+    }
+
+    /// Load the meshes and textures of the scene, looking for them with the given search paths
+    ///
+    /// Textures and wavefront meshes are loaded through the cache, so scenes
+    /// that share them only read them once.
+    ///
+    pub fn load(&self, paths: &SearchPaths) -> Result<Scene, MorpheusError> {
+        let resolve = |path: &str| {
+            paths.resolve(path).and_then(|path| path.to_str().map(String::from)).unwrap_or_else(|| path.to_string())
+        };
+
+        let mut loaded = HashMap::new();
+        let mut scene = Scene::new();
+
+        for node in &self.objects {
+            if !loaded.contains_key(&node.mesh) {
+                let mesh = self.meshes.get(&node.mesh).ok_or_else(|| {
+                    MorpheusError::Scene(format!("object {} places the mesh {}, which is not in the scene",
+                                                 node.name, node.mesh))
+                })?;
+
+                let texture = match mesh.texture {
+                    Some(ref texture) => cache::texture(&resolve(texture))?.as_ref().clone(),
+                    None => image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]))
+                };
+
+                loaded.insert(node.mesh.clone(), (plugins::open(&resolve(&mesh.path))?, texture));
+            }
+
+            let (ref object, ref texture) = loaded[&node.mesh];
+            let mut object = SceneObject::new(object.clone(), texture.clone())
+                                         .with_transform(node.transform.to_matrix());
+            object.opacity = node.opacity;
+
+            scene.objects.push(object);
+        }
+
+        scene.lights.extend(self.lights.iter().map(LightNode::to_light));
+
+        Ok(scene)
+    }
+}
+
+
+/// Load the scene of the interchange file at the given path along with the camera it is rendered from
+pub fn import(path: &str) -> Result<(Scene, Option<Camera>), MorpheusError> {
+    let json = fs::read_to_string(path).map_err(|error| MorpheusError::io(path, error))?;
+    let file = SceneFile::parse(&json)?;
+
+    Ok((file.load(&SearchPaths::for_scene(Path::new(path)))?, file.camera()?))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+
+    #[test]
+    fn test_transform() {
+        let quarter = std::f64::consts::FRAC_PI_4.cos();
+        let transform = Transform { translation: [0.0, 0.0, 5.0], rotation: [quarter, 0.0, quarter, 0.0],
+                                    scale: [2.0, 2.0, 2.0], matrix: None };
+
+        // x is scaled, turned a quarter turn about y onto -z, and moved back
+        let point = transform.to_matrix().transform_point(&nalgebra::Point3::new(1.0, 0.0, 0.0));
+        assert!((point.coords - Vector3::new(0.0, 0.0, 3.0)).norm() < 1e-12);
+
+        let rows = Transform { matrix: Some([1.0, 0.0, 0.0, 4.0, 0.0, 1.0, 0.0, 5.0, 0.0, 0.0, 1.0, 6.0,
+                                             0.0, 0.0, 0.0, 1.0]),
+                               ..Default::default() };
+        assert_eq!(rows.to_matrix(), Matrix4::new_translation(&Vector3::new(4.0, 5.0, 6.0)));
+        assert_eq!(Transform::default().to_matrix(), Matrix4::identity());
+    }
+
+    #[test]
+    fn test_import() {
+        let directory = env::temp_dir().join("morpheus_interchange");
+        fs::create_dir_all(directory.join("meshes")).unwrap();
+        fs::write(directory.join("meshes/triangle.obj"),
+                  b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\nf 1/1/1 2/1/1 3/1/1\n").unwrap();
+
+        let scene = r#"{
+            "version": 1,
+            "meshes": {"triangle": {"path": "meshes/triangle.obj"}},
+            "objects": [{"name": "Left", "mesh": "triangle", "transform": {"translation": [-2, 0, 0]}},
+                        {"name": "Right", "mesh": "triangle", "transform": {"translation": [2, 0, 0]}, "opacity": 0.5}],
+            "cameras": [{"name": "Wide", "eye": [0, 0, 10], "target": [0, 0, 0]},
+                        {"name": "Close", "eye": [0, 0, 4], "target": [0, 0, 0], "fov": 50}],
+            "lights": [{"type": "directional", "name": "Sun", "direction": [0, 0, 2], "intensity": 0.5},
+                       {"type": "spot", "name": "Spot", "position": [0, 5, 0], "direction": [0, -1, 0],
+                        "inner_angle": 0.3, "outer_angle": 0.5}],
+            "active_camera": "Close"
+        }"#;
+        let path = directory.join("scene.json");
+        fs::write(&path, scene).unwrap();
+
+        let (scene, camera) = import(path.to_str().unwrap()).unwrap();
+
+        assert_eq!(scene.objects.len(), 2);
+        assert_eq!(scene.objects[1].transform, Matrix4::new_translation(&Vector3::new(2.0, 0.0, 0.0)));
+        assert_eq!(scene.objects[1].opacity, 0.5);
+        assert_eq!(scene.lights[0].light, Light::Directional(Vector3::z()));
+        assert_eq!(scene.lights[0].intensity, 0.5);
+        assert_eq!(camera.unwrap().fov, Some(50.0));
+
+        let file = |json: &str| {
+            SceneFile::parse(json).and_then(|file| file.load(&SearchPaths::default()).map(|_| file))
+        };
+        assert!(file(r#"{"version": 2}"#).is_err());
+        assert!(file(r#"{"objects": [{"name": "Lost", "mesh": "missing"}]}"#).is_err());
+        assert!(file(r#"{"lights": [{"type": "area", "name": "Panel"}]}"#).is_err());
+        assert!(file(r#"{"active_camera": "Missing"}"#).unwrap().camera().is_err());
+        assert_eq!(file("{}").unwrap().camera(), Ok(None));
+    }
+}
@@ -0,0 +1,126 @@
+//! A software rasterizer for wavefront OBJ models
+//!
+//! The Renderer draws a model from a fixed camera into an image, which is
+//! enough for thumbnails and previews, or a scene of several models placed
+//! by their own transforms and lit by its lights. The render, shader,
+//! wavefront and vector modules underneath it are public for drawing with
+//! custom shaders, views and buffers.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # extern crate rastermind;
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let renderer = rastermind::Renderer::new(512, 512);
+//! let image = renderer.render_file("african_head.obj", "african_head_diffuse.png")?;
+//!
+//! image.save("african_head.png")?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+#[cfg(feature = "basis")]
+extern crate basis_universal;
+extern crate clap;
+extern crate flate2;
+extern crate image;
+#[cfg(feature = "plugins")]
+extern crate libloading;
+extern crate memmap2;
+extern crate nalgebra;
+#[cfg(feature = "wgpu")]
+extern crate pollster;
+extern crate rand;
+#[cfg(feature = "basis")]
+extern crate ruzstd;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+#[macro_use]
+extern crate serde_json;
+extern crate toml;
+#[cfg(feature = "http")]
+extern crate ureq;
+#[cfg(feature = "wgpu")]
+extern crate wgpu;
+
+pub mod abuffer;
+pub mod animation;
+pub mod assets;
+pub mod cache;
+pub mod camera;
+pub mod clipping;
+pub mod collada;
+pub mod compressed;
+pub mod curvature;
+pub mod error;
+pub mod export;
+pub mod fbx;
+#[cfg(feature = "wgpu")]
+pub mod gpu;
+pub mod hair;
+pub mod interchange;
+pub mod light;
+pub mod lines;
+pub mod mesh;
+pub mod motion;
+pub mod occlusion;
+pub mod overlay;
+pub mod peeling;
+pub mod plugins;
+pub mod post;
+pub mod reflection;
+pub mod refraction;
+pub mod remote;
+pub mod render;
+pub mod reorder;
+pub mod scene;
+pub mod shader;
+pub mod shadow;
+pub mod sky;
+pub mod subsurface;
+pub mod svg;
+pub mod tangent;
+pub mod text;
+pub mod texture;
+pub mod transparency;
+pub mod vector;
+pub mod velocity;
+pub mod wavefront;
+pub mod weld;
+
+// the command line and the modes of the binary, which the binary needs public but which are not part of the library
+#[doc(hidden)]
+pub mod cli;
+#[doc(hidden)]
+pub mod compat;
+#[doc(hidden)]
+pub mod dataset;
+#[doc(hidden)]
+pub mod overrides;
+#[doc(hidden)]
+pub mod parameters;
+#[doc(hidden)]
+pub mod preview;
+#[doc(hidden)]
+pub mod progress;
+#[doc(hidden)]
+pub mod reference;
+#[doc(hidden)]
+pub mod rpc;
+#[doc(hidden)]
+pub mod service;
+#[doc(hidden)]
+pub mod sweep;
+#[doc(hidden)]
+pub mod terminal;
+
+mod bvh;
+mod queue;
+mod renderer;
+
+pub use animation::Animation;
+pub use camera::Camera;
+pub use error::MorpheusError;
+pub use error::MorpheusError as Error;
+pub use renderer::Renderer;
@@ -0,0 +1,276 @@
+use nalgebra::{Matrix3, Matrix4, Vector3, Vector4};
+
+use shader::{Contributions, Shader};
+use vector;
+use wavefront;
+
+
+/// A light that illuminates the objects of a scene
+///
+/// A directional light shines from infinitely far away, and its light vector
+/// points from the scene towards the light. A spotlight shines from a position
+/// into a cone around its direction.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::light::*;
+/// let sun = Light::Directional(Vector3::new(1.0, 2.0, 1.0).normalize());
+/// let spot = Light::Spot(Spotlight::new(Vector3::new(0.0, 5.0, 0.0), -Vector3::y(),
+///                                       0.3, 0.5));
+/// ```
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Light {
+    Directional(Vector3<f64>),
+    Spot(Spotlight)
+}
+
+
+/// A light at a position that shines into a cone around a direction
+///
+/// Points within the inner angle of the direction receive the full light,
+/// which fades smoothly to nothing at the outer angle. Angles are in radians.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Spotlight {
+    pub position: Vector3<f64>,
+    pub direction: Vector3<f64>,
+    pub inner_angle: f64,
+    pub outer_angle: f64
+}
+
+
+impl Spotlight {
+    /// Create a new Spotlight at the given position pointing in the given direction
+    pub fn new(position: Vector3<f64>, direction: Vector3<f64>,
+               inner_angle: f64, outer_angle: f64) -> Spotlight {
+        Spotlight { position, direction: direction.normalize(), inner_angle, outer_angle }
+    }
+
+    /// Find the fraction of the light that reaches the given point from the cone
+    pub fn falloff(&self, point: &Vector3<f64>) -> f64 {
+        let cosine = (point - self.position).normalize().dot(&self.direction);
+        let (inner, outer) = (self.inner_angle.cos(), self.outer_angle.cos());
+
+        if inner <= outer {
+            return if cosine >= outer { 1.0 } else { 0.0 };
+        }
+
+        let t = ((cosine - outer) / (inner - outer)).clamp(0.0, 1.0);
+
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+
+impl Light {
+    /// Find the light vector at the given point, which points from the point towards the light
+    pub fn light_vector(&self, point: &Vector3<f64>) -> Vector3<f64> {
+        match *self {
+            Light::Directional(light_vector) => light_vector.normalize(),
+            Light::Spot(ref spot) => (spot.position - point).normalize()
+        }
+    }
+
+    /// Find the fraction of the light that reaches the given point before shadows
+    pub fn falloff(&self, point: &Vector3<f64>) -> f64 {
+        match *self {
+            Light::Directional(_) => 1.0,
+            Light::Spot(ref spot) => spot.falloff(point)
+        }
+    }
+}
+
+
+/// Light another shader with a light that may vary across the scene
+///
+/// The shaded shader is given the light vector of each vertex, and its colors
+/// are scaled by the falloff of the light at each fragment, so a spotlight
+/// can light the middle of a face whose vertices are all outside its cone.
+/// The colors are also scaled by the intensity of the light, which is one
+/// unless it is given.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::light::*;
+/// # use rastermind::shader;
+/// # let light = Light::Directional(Vector3::y());
+/// let shader = LightShader::new(Box::new(shader::GouraudShader::new()), &light);
+/// let fill = LightShader::new(Box::new(shader::GouraudShader::new()), &light).with_intensity(0.4);
+/// ```
+///
+pub struct LightShader<'a> {
+    pub shader: Box<dyn Shader + 'a>,
+    pub light: &'a Light,
+    pub intensity: f64,
+    pub varying_position: Matrix3<f64>,
+    pub varying_w: Vector3<f64>
+}
+
+
+impl<'a> LightShader<'a> {
+    /// Create a new instance of a LightShader that lights the given shader
+    pub fn new(shader: Box<dyn Shader + 'a>, light: &'a Light) -> LightShader<'a> {
+        LightShader { shader, light, intensity: 1.0, varying_position: Matrix3::zeros(), varying_w: Vector3::zeros() }
+    }
+
+    /// Scale the light by the given intensity
+    pub fn with_intensity(mut self, intensity: f64) -> LightShader<'a> {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Find the fraction of the light that reaches the fragment at the given barycentric coordinate
+    fn attenuation(&self, vertex: Vector3<f64>) -> f64 {
+        if let Light::Directional(_) = *self.light {
+            return self.intensity;
+        }
+
+        let perspective = vertex.component_div(&self.varying_w);
+
+        self.intensity * self.light.falloff(&(self.varying_position * perspective / perspective.sum()))
+    }
+}
+
+
+impl<'a> Shader for LightShader<'a> {
+    /// Position the vertices with the lit shader using the light vector at each vertex
+    fn vertex(&mut self, coordinates: &wavefront::Object,
+              view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+              model_view: &Matrix4<f64>, _light_vector: &Vector3<f64>,
+              face_index: usize, vertex_index: usize) -> Vector4<f64> {
+
+        let geometric_index = coordinates.geometric_faces[face_index][vertex_index] as usize;
+        let position = coordinates.geometric_vertices[geometric_index];
+
+        self.varying_position.set_column(vertex_index, &position);
+
+        let screen_coordinate = self.shader.vertex(coordinates, view_port, projection, model_view,
+                                                   &self.light.light_vector(&position),
+                                                   face_index, vertex_index);
+        self.varying_w[vertex_index] = screen_coordinate.w;
+
+        screen_coordinate
+    }
+
+    /// Scale the color of the lit shader by the falloff and intensity of the light
+    fn fragment(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> image::Rgb<u8> {
+        let mut color = self.shader.fragment(vertex, texture);
+        let attenuation = self.attenuation(vertex);
+
+        if attenuation != 1.0 {
+            (0..=2).for_each(|i| { color[i] = (color[i] as f64 * attenuation).min(255.0) as u8; });
+        }
+
+        color
+    }
+
+    /// Discard the fragments the lit shader discards
+    fn discard(&self, vertex: Vector3<f64>) -> bool {
+        self.shader.discard(vertex)
+    }
+
+    /// Scale the light of the lit shader by the falloff and intensity of the light
+    fn contributions(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> Contributions {
+        let contributions = self.shader.contributions(vertex, texture);
+        let attenuation = self.attenuation(vertex);
+
+        if attenuation == 1.0 {
+            return contributions;
+        }
+
+        contributions.scale_light(attenuation)
+    }
+}
+
+
+/// Add the colors of several shaders of the same face, such as one lit by each light
+///
+/// The first shader positions the vertices. Without any shaders every
+/// fragment is black.
+///
+pub struct SumShader<'a> {
+    pub shaders: Vec<Box<dyn Shader + 'a>>
+}
+
+
+impl<'a> SumShader<'a> {
+    /// Create a new instance of a SumShader that adds the colors of the given shaders
+    pub fn new(shaders: Vec<Box<dyn Shader + 'a>>) -> SumShader<'a> {
+        SumShader { shaders }
+    }
+}
+
+
+impl<'a> Shader for SumShader<'a> {
+    /// Position the vertices with every shader and return the position of the first
+    fn vertex(&mut self, coordinates: &wavefront::Object,
+              view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+              model_view: &Matrix4<f64>, light_vector: &Vector3<f64>,
+              face_index: usize, vertex_index: usize) -> Vector4<f64> {
+
+        let positions: Vec<Vector4<f64>> = self.shaders.iter_mut().map(|shader| {
+            shader.vertex(coordinates, view_port, projection, model_view, light_vector,
+                          face_index, vertex_index)
+        }).collect();
+
+        match positions.first() {
+            Some(&position) => position,
+            None => {
+                let geometric_index = coordinates.geometric_faces[face_index][vertex_index] as usize;
+                view_port * projection * model_view *
+                    vector::vectorize_to_4d(&coordinates.geometric_vertices[geometric_index])
+            }
+        }
+    }
+
+    /// Add the colors of every shader, saturating at white
+    fn fragment(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> image::Rgb<u8> {
+        self.shaders.iter().fold(image::Rgb([0, 0, 0]), |mut sum, shader| {
+            let color = shader.fragment(vertex, texture);
+            (0..=2).for_each(|i| { sum[i] = sum[i].saturating_add(color[i]); });
+            sum
+        })
+    }
+
+    /// Discard the fragments any of the shaders discards
+    fn discard(&self, vertex: Vector3<f64>) -> bool {
+        self.shaders.iter().any(|shader| shader.discard(vertex))
+    }
+
+    /// Add the terms of the lighting of every shader
+    fn contributions(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> Contributions {
+        self.shaders.iter().fold(Contributions::default(),
+                                 |sum, shader| sum.add(&shader.contributions(vertex, texture)))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spotlight_falloff() {
+        let spot = Spotlight::new(Vector3::new(0.0, 1.0, 0.0), -Vector3::y(), 0.2, 0.4);
+
+        assert_eq!(spot.falloff(&Vector3::zeros()), 1.0);
+        assert_eq!(spot.falloff(&Vector3::new(1.0, 0.0, 0.0)), 0.0);
+
+        let inside = spot.falloff(&Vector3::new(0.3f64.tan(), 0.0, 0.0));
+        assert!(inside > 0.0 && inside < 1.0);
+        assert!(spot.falloff(&Vector3::new(0.25f64.tan(), 0.0, 0.0)) > inside);
+
+        assert_eq!(Light::Spot(spot).light_vector(&Vector3::new(0.0, -1.0, 0.0)), Vector3::y());
+        assert_eq!(Light::Directional(Vector3::new(0.0, 2.0, 0.0)).light_vector(&Vector3::zeros()),
+                   Vector3::y());
+    }
+}
@@ -0,0 +1,538 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use nalgebra::{Matrix4, Vector3};
+
+use render;
+use shader;
+use vector;
+use wavefront;
+
+
+/// How far a line may lie behind the depth buffer, as a fraction of the depth of the viewport, and still be visible
+const DEPTH_TOLERANCE: f64 = 2e-3;
+
+/// The depth of the viewport used by hidden line drawings
+const HIDDEN_LINE_DEPTH: u32 = 65535;
+
+/// The length in pixels of a dash and of the gap after it in dashed lines
+const DASH: f64 = 4.0;
+
+
+/// What to do with the lines that are hidden behind faces
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HiddenLines {
+    Drawn,
+    Removed,
+    Dashed
+}
+
+
+/// Options that control a hidden line drawing
+///
+/// Faces are filled with the fill color and their edges drawn in the line
+/// color. Only boundary edges and creases whose faces meet at more than the
+/// crease angle in radians are drawn, along with the silhouettes of the faces.
+///
+/// # Examples
+///
+/// ```
+/// # use rastermind::lines::*;
+/// let options = HiddenLineOptions { hidden_lines: HiddenLines::Dashed, ..Default::default() };
+/// ```
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HiddenLineOptions {
+    pub fill: image::Rgb<u8>,
+    pub line: image::Rgb<u8>,
+    pub hidden_lines: HiddenLines,
+    pub crease_angle: f64
+}
+
+
+impl Default for HiddenLineOptions {
+    fn default() -> HiddenLineOptions {
+        HiddenLineOptions { fill: image::Rgb([255, 255, 255]), line: image::Rgb([0, 0, 0]),
+                            hidden_lines: HiddenLines::Removed, crease_angle: 0.5 }
+    }
+}
+
+
+/// A straight line on the screen whose z is the depth of the viewport at either end
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Segment {
+    pub start: Vector3<f64>,
+    pub end: Vector3<f64>,
+    pub visible: bool
+}
+
+
+/// Find every edge of the faces of an Object once as a pair of geometric vertex indices
+///
+/// The first index of every edge is the smaller one and the edges are sorted.
+/// Edges of degenerate faces that join a vertex to itself are skipped.
+///
+pub fn mesh_edges(coordinates: &wavefront::Object) -> Vec<[usize; 2]> {
+    let mut edges = BTreeSet::new();
+
+    for face in &coordinates.geometric_faces {
+        for i in 0..3 {
+            let (a, b) = (face[i] as usize, face[(i + 1) % 3] as usize);
+
+            if a != b {
+                edges.insert([a.min(b), a.max(b)]);
+            }
+        }
+    }
+
+    edges.into_iter().collect()
+}
+
+
+/// Find the edges of an Object that are on its boundary or where its faces meet at more than the given angle
+///
+/// Edges shared by more than two faces are always included. The normals of
+/// the faces are found from their geometric vertices, so the angle does not
+/// depend on the vertex normals of the file.
+///
+pub fn feature_edges(coordinates: &wavefront::Object, crease_angle: f64) -> Vec<[usize; 2]> {
+    edge_faces(coordinates).into_iter().filter(|(_, faces)| {
+        faces.len() != 2 ||
+            face_normal(coordinates, faces[0]).angle(&face_normal(coordinates, faces[1])) > crease_angle
+    }).map(|(edge, _)| edge).collect()
+}
+
+
+/// Find the silhouette edges of an Object seen from the given eye
+///
+/// A silhouette edge is shared by a face that points towards the eye and a
+/// face that points away from it, so together the silhouette edges outline
+/// the object and the folds in it. Edges that are not shared by exactly two
+/// faces are never silhouette edges.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::{lines, wavefront};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// let silhouette = lines::silhouette_edges(&coordinates, &Vector3::new(0.0, 15.0, 70.0));
+///
+/// for [a, b] in silhouette {
+///     // do something with the vertices at either end of the edge
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn silhouette_edges(coordinates: &wavefront::Object, eye: &Vector3<f64>) -> Vec<[usize; 2]> {
+    let facing = |face_index: usize| {
+        let vertex = coordinates.geometric_vertices[coordinates.geometric_faces[face_index][0] as usize];
+        face_normal(coordinates, face_index).dot(&(eye - vertex)) > 0.0
+    };
+
+    edge_faces(coordinates).into_iter()
+                           .filter(|(_, faces)| faces.len() == 2 && facing(faces[0]) != facing(faces[1]))
+                           .map(|(edge, _)| edge)
+                           .collect()
+}
+
+
+/// Find the feature edges of an Object together with its silhouette edges seen from the given eye
+pub fn outline_edges(coordinates: &wavefront::Object, eye: &Vector3<f64>, crease_angle: f64) -> Vec<[usize; 2]> {
+    let edges: BTreeSet<[usize; 2]> = feature_edges(coordinates, crease_angle).into_iter()
+                                                                              .chain(silhouette_edges(coordinates, eye))
+                                                                              .collect();
+
+    edges.into_iter().collect()
+}
+
+
+/// Draw the visible silhouette edges of an Object over a render of it as a line outline
+///
+/// The depth buffer and matrices must be the ones the Object was drawn with,
+/// where the depth of the viewport is given, and the eye must be the eye of
+/// the model view matrix.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::{lines, shader, wavefront};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let mut buffer = image::RgbImage::new(512, 512);
+/// # let (eye, center, up) = (Vector3::<f64>::new(0.0, 0.0, 3.0), Vector3::zeros(), Vector3::y());
+/// # let view_port = shader::viewport(64, 64, 384, 384, 255);
+/// # let projection = shader::projection(-1.0 / 3.0);
+/// # let model_view = shader::lookat(&eye, &center, &up);
+/// # let zbuffer = lines::depth_buffer(&coordinates, 512, 512, &view_port, &projection, &model_view);
+/// lines::draw_silhouettes(&coordinates, &mut buffer, &zbuffer, 255, &view_port, &projection,
+///                         &model_view, &eye, image::Rgb([0, 0, 0]));
+/// # Ok(())
+/// # }
+/// ```
+///
+#[allow(clippy::too_many_arguments)]
+pub fn draw_silhouettes(coordinates: &wavefront::Object, buffer: &mut image::RgbImage,
+                        zbuffer: &[f64], depth: u32,
+                        view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+                        model_view: &Matrix4<f64>, eye: &Vector3<f64>, color: image::Rgb<u8>) {
+
+    let (width, height) = buffer.dimensions();
+    let edges = silhouette_edges(coordinates, eye);
+
+    for segment in segments(coordinates, &edges, zbuffer, width, height, depth,
+                            view_port, projection, model_view) {
+        if segment.visible {
+            draw_segment(buffer, &segment, color, false);
+        }
+    }
+}
+
+
+/// Find the faces on either side of every edge of an Object
+fn edge_faces(coordinates: &wavefront::Object) -> BTreeMap<[usize; 2], Vec<usize>> {
+    let mut faces: BTreeMap<[usize; 2], Vec<usize>> = BTreeMap::new();
+
+    for (face_index, face) in coordinates.geometric_faces.iter().enumerate() {
+        for i in 0..3 {
+            let (a, b) = (face[i] as usize, face[(i + 1) % 3] as usize);
+
+            if a != b {
+                faces.entry([a.min(b), a.max(b)]).or_default().push(face_index);
+            }
+        }
+    }
+
+    faces
+}
+
+
+/// Find the normal of a face from the winding of its geometric vertices
+fn face_normal(coordinates: &wavefront::Object, face_index: usize) -> Vector3<f64> {
+    let face = coordinates.geometric_faces[face_index];
+    let vertices: Vec<Vector3<f64>> = (0..3).map(|i| coordinates.geometric_vertices[face[i] as usize]).collect();
+
+    (vertices[1] - vertices[0]).cross(&(vertices[2] - vertices[0])).normalize()
+}
+
+
+/// Draw the depth of every face of an Object into a new depth buffer of the given size
+#[allow(clippy::too_many_arguments)]
+pub fn depth_buffer(coordinates: &wavefront::Object, width: u32, height: u32,
+                    view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+                    model_view: &Matrix4<f64>) -> Vec<f64> {
+
+    let mut buffer = image::ImageBuffer::new(width, height);
+    let mut zbuffer = vec![-1.0; (width * height) as usize];
+    let texture = image::RgbImage::new(1, 1);
+
+    render::draw_faces(coordinates, &mut buffer, &texture, &mut zbuffer,
+                       &render::DepthOptions::default(), view_port, projection, model_view,
+                       &Vector3::z(), |_| Box::new(shader::ColorShader::new(image::Rgb([255, 255, 255]), false)));
+
+    zbuffer
+}
+
+
+/// Project the given edges onto the screen and split them where they pass behind the depth buffer
+///
+/// An edge is visible where its depth is not behind the depth buffer at the
+/// nearest pixel or any of its neighbours, so edges that lie on the surface
+/// of the faces and along silhouettes are not hidden by the faces beside
+/// them. The depth is the depth of the viewport, which is used to scale the
+/// tolerance of the test. Edges that pass behind the camera are skipped.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::{lines, shader, wavefront};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let (eye, center, up) = (Vector3::<f64>::new(0.0, 0.0, 3.0), Vector3::zeros(), Vector3::y());
+/// # let view_port = shader::viewport(64, 64, 384, 384, 255);
+/// # let projection = shader::projection(-1.0 / 3.0);
+/// # let model_view = shader::lookat(&eye, &center, &up);
+/// let zbuffer = lines::depth_buffer(&coordinates, 512, 512, &view_port, &projection, &model_view);
+/// let segments = lines::segments(&coordinates, &lines::mesh_edges(&coordinates), &zbuffer,
+///                                512, 512, 255, &view_port, &projection, &model_view);
+/// # Ok(())
+/// # }
+/// ```
+///
+#[allow(clippy::too_many_arguments)]
+pub fn segments(coordinates: &wavefront::Object, edges: &[[usize; 2]], zbuffer: &[f64],
+                width: u32, height: u32, depth: u32,
+                view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+                model_view: &Matrix4<f64>) -> Vec<Segment> {
+
+    let transform = view_port * projection * model_view;
+    let tolerance = depth as f64 * DEPTH_TOLERANCE;
+
+    let nearest = |point: &Vector3<f64>| -> f64 {
+        let (x, y) = (point.x.round() as i64, point.y.round() as i64);
+        let mut nearest: Option<f64> = None;
+
+        for j in y - 1..=y + 1 {
+            for i in x - 1..=x + 1 {
+                if i >= 0 && j >= 0 && i < width as i64 && j < height as i64 {
+                    let value = zbuffer[(i + j * width as i64) as usize];
+                    nearest = Some(nearest.map_or(value, |nearest| nearest.min(value)));
+                }
+            }
+        }
+
+        // nothing can hide a line outside of the screen
+        nearest.unwrap_or(f64::NEG_INFINITY)
+    };
+
+    let mut segments = Vec::new();
+
+    for edge in edges {
+        let clip: Vec<_> = edge.iter()
+                               .map(|&index| transform * vector::vectorize_to_4d(&coordinates.geometric_vertices[index]))
+                               .collect();
+
+        if clip.iter().any(|point| point.w <= 0.0) {
+            continue;
+        }
+
+        let (start, end) = (vector::project_to_3d(&clip[0]), vector::project_to_3d(&clip[1]));
+        let steps = ((end - start).xy().norm() * 2.0).ceil().max(1.0) as usize;
+        let point_at = |step: usize| start + (end - start) * (step as f64 / steps as f64);
+
+        let mut run_start = 0;
+        let mut run_visible = None;
+
+        for step in 0..steps {
+            let middle = start + (end - start) * ((step as f64 + 0.5) / steps as f64);
+            let visible = middle.z + tolerance >= nearest(&middle);
+
+            match run_visible {
+                Some(previous) if previous != visible => {
+                    segments.push(Segment { start: point_at(run_start), end: point_at(step), visible: previous });
+                    run_start = step;
+                },
+                _ => ()
+            }
+
+            run_visible = Some(visible);
+        }
+
+        if let Some(visible) = run_visible {
+            segments.push(Segment { start: point_at(run_start), end, visible });
+        }
+    }
+
+    segments
+}
+
+
+/// Draw an Object as a technical illustration with filled faces and outlined edges
+///
+/// The feature and silhouette edges are tested against the depth of the
+/// faces, so hidden edges are removed or drawn dashed. Lines are also drawn
+/// where the depth of neighbouring pixels jumps or the faces end, which
+/// catches faces that pass through each other. The camera and viewport match
+/// draw_object.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::lines::*;
+/// # use rastermind::{lines, wavefront};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// let mut buffer = image::RgbImage::from_pixel(1024, 1024, image::Rgb([255, 255, 255]));
+///
+/// lines::draw_hidden_line(&coordinates, &mut buffer, &Vector3::new(0.0, 15.0, 70.0),
+///                         &Vector3::zeros(), &Vector3::y(), &HiddenLineOptions::default());
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn draw_hidden_line(coordinates: &wavefront::Object, buffer: &mut image::RgbImage,
+                        eye: &Vector3<f64>, center: &Vector3<f64>, up: &Vector3<f64>,
+                        options: &HiddenLineOptions) {
+
+    let (width, height) = buffer.dimensions();
+    let model_view = shader::lookat(eye, center, up);
+    let projection = shader::projection(-1.0 / (eye - center).norm());
+    let view_port = shader::viewport(width / 8, height / 8, width * 3 / 4, height * 3 / 4,
+                                     HIDDEN_LINE_DEPTH);
+
+    let mut zbuffer = vec![-1.0; (width * height) as usize];
+    let texture = image::RgbImage::new(1, 1);
+
+    render::draw_faces(coordinates, buffer, &texture, &mut zbuffer, &render::DepthOptions::default(),
+                       &view_port, &projection, &model_view, &Vector3::z(),
+                       |_| Box::new(shader::ColorShader::new(options.fill, false)));
+
+    let threshold = HIDDEN_LINE_DEPTH as f64 * DEPTH_TOLERANCE * 4.0;
+    let silhouettes: Vec<(u32, u32)> = (0..height).flat_map(|y| (0..width).map(move |x| (x, y)))
+        .filter(|&(x, y)| {
+            let depth = zbuffer[(x + y * width) as usize];
+            let neighbours = [(x + 1, y), (x, y + 1)];
+
+            neighbours.iter().any(|&(i, j)| {
+                i < width && j < height && {
+                    let neighbour = zbuffer[(i + j * width) as usize];
+                    (depth < 0.0) != (neighbour < 0.0) || (depth - neighbour).abs() > threshold
+                }
+            })
+        })
+        .collect();
+
+    for (x, y) in silhouettes {
+        buffer.put_pixel(x, y, options.line);
+    }
+
+    let edges = outline_edges(coordinates, eye, options.crease_angle);
+
+    for segment in segments(coordinates, &edges, &zbuffer, width, height, HIDDEN_LINE_DEPTH,
+                            &view_port, &projection, &model_view) {
+        match (segment.visible, options.hidden_lines) {
+            (true, _) | (false, HiddenLines::Drawn) => draw_segment(buffer, &segment, options.line, false),
+            (false, HiddenLines::Dashed) => draw_segment(buffer, &segment, options.line, true),
+            (false, HiddenLines::Removed) => ()
+        }
+    }
+}
+
+
+/// Draw a segment one pixel wide, leaving gaps between dashes when it is dashed
+fn draw_segment(buffer: &mut image::RgbImage, segment: &Segment, color: image::Rgb<u8>, dashed: bool) {
+    let length = (segment.end - segment.start).xy().norm();
+    let steps = length.ceil().max(1.0) as usize;
+
+    for step in 0..=steps {
+        let distance = length * step as f64 / steps as f64;
+
+        if dashed && distance % (DASH * 2.0) >= DASH {
+            continue;
+        }
+
+        let point = segment.start + (segment.end - segment.start) * (step as f64 / steps as f64);
+        let (x, y) = (point.x.round(), point.y.round());
+
+        if x >= 0.0 && y >= 0.0 && x < buffer.width() as f64 && y < buffer.height() as f64 {
+            buffer.put_pixel(x as u32, y as u32, color);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segments() {
+        // a small square in front of a long edge that passes behind it
+        let coordinates = wavefront::Object::from_bytes(b"v -0.5 -0.5 1\nv 0.5 -0.5 1\nv 0.5 0.5 1\nv -0.5 0.5 1\n\
+                                                          v -1.2 0 0\nv 1.2 0 0\nvt 0 0\nvn 0 0 1\n\
+                                                          f 1/1/1 2/1/1 3/1/1\nf 1/1/1 3/1/1 4/1/1\n\
+                                                          f 5/1/1 6/1/1 5/1/1\n",
+                                                        &wavefront::LoadOptions::default()).unwrap();
+
+        let model_view = shader::lookat(&Vector3::new(0.0, 0.0, 5.0), &Vector3::zeros(), &Vector3::y());
+        let projection = shader::projection(-1.0 / 5.0);
+        let view_port = shader::viewport(8, 8, 48, 48, 255);
+
+        let edges = mesh_edges(&coordinates);
+        assert_eq!(edges, vec![[0, 1], [0, 2], [0, 3], [1, 2], [2, 3], [4, 5]]);
+
+        let zbuffer = depth_buffer(&coordinates, 64, 64, &view_port, &projection, &model_view);
+        let segments = segments(&coordinates, &edges, &zbuffer, 64, 64, 255,
+                                &view_port, &projection, &model_view);
+
+        // the outline and diagonal of the square are visible
+        assert!(segments[..5].iter().all(|segment| segment.visible));
+
+        // the long edge is split into a visible, a hidden, and a visible part
+        let long: Vec<bool> = segments[5..].iter().map(|segment| segment.visible).collect();
+        assert_eq!(long, vec![true, false, true]);
+        assert!((segments[6].start.x - 17.0).abs() < 1.5);
+        assert!((segments[6].end.x - 47.0).abs() < 1.5);
+    }
+
+    #[test]
+    fn test_draw_hidden_line() {
+        // a square in front of a thin strip that passes behind it
+        let coordinates = wavefront::Object::from_bytes(b"v -0.5 -0.5 1\nv 0.5 -0.5 1\nv 0.5 0.5 1\nv -0.5 0.5 1\n\
+                                                          v -1.2 -0.1 0\nv 1.2 -0.1 0\nv 1.2 0.1 0\nv -1.2 0.1 0\n\
+                                                          vt 0 0\nvn 0 0 1\n\
+                                                          f 1/1/1 2/1/1 3/1/1\nf 1/1/1 3/1/1 4/1/1\n\
+                                                          f 5/1/1 6/1/1 7/1/1\nf 5/1/1 7/1/1 8/1/1\n",
+                                                        &wavefront::LoadOptions::default()).unwrap();
+
+        assert_eq!(feature_edges(&coordinates, 0.5).len(), 8);
+
+        let gray = image::Rgb([128, 128, 128]);
+        let draw = |hidden_lines| {
+            let mut buffer = image::RgbImage::from_pixel(64, 64, gray);
+            let options = HiddenLineOptions { hidden_lines, ..Default::default() };
+
+            draw_hidden_line(&coordinates, &mut buffer, &Vector3::new(0.0, 0.0, 5.0), &Vector3::zeros(),
+                             &Vector3::y(), &options);
+            buffer
+        };
+
+        let (fill, line) = (image::Rgb([255, 255, 255]), image::Rgb([0, 0, 0]));
+        let hidden_row = |buffer: &image::RgbImage| -> Vec<image::Rgb<u8>> {
+            (20..44).map(|x| *buffer.get_pixel(x, 30)).collect()
+        };
+
+        let removed = draw(HiddenLines::Removed);
+        assert_eq!(*removed.get_pixel(1, 32), gray);
+        assert_eq!(*removed.get_pixel(10, 30), line);
+        assert_eq!(*removed.get_pixel(10, 32), fill);
+        assert_eq!(*removed.get_pixel(25, 40), fill);
+        assert_eq!(*removed.get_pixel(17, 40), line);
+        assert!(hidden_row(&removed).iter().all(|&pixel| pixel == fill));
+
+        let dashed = hidden_row(&draw(HiddenLines::Dashed));
+        assert!(dashed.contains(&line) && dashed.contains(&fill));
+
+        assert!(hidden_row(&draw(HiddenLines::Drawn)).iter().all(|&pixel| pixel == line));
+    }
+
+    #[test]
+    fn test_silhouette_edges() {
+        // a tetrahedron with one face towards +z and the apex behind it
+        let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nv 0 0 -1\nvt 0 0\nvn 0 0 1\n\
+                                                          f 1/1/1 2/1/1 3/1/1\nf 1/1/1 4/1/1 2/1/1\n\
+                                                          f 2/1/1 4/1/1 3/1/1\nf 3/1/1 4/1/1 1/1/1\n",
+                                                        &wavefront::LoadOptions::default()).unwrap();
+
+        // from the front only the face towards the eye is visible, so its edges are the silhouette
+        assert_eq!(silhouette_edges(&coordinates, &Vector3::new(0.0, 0.0, 5.0)),
+                   vec![[0, 1], [0, 2], [1, 2]]);
+
+        // from behind the apex all of the back faces are visible and the silhouette is unchanged
+        assert_eq!(silhouette_edges(&coordinates, &Vector3::new(0.0, 0.0, -5.0)),
+                   vec![[0, 1], [0, 2], [1, 2]]);
+
+        // from the side the silhouette runs through the apex
+        let side = silhouette_edges(&coordinates, &Vector3::new(5.0, 0.0, -0.2));
+        assert!(side.contains(&[1, 3]) || side.contains(&[2, 3]));
+        assert_ne!(side, vec![[0, 1], [0, 2], [1, 2]]);
+
+        let outline = outline_edges(&coordinates, &Vector3::new(0.0, 0.0, 5.0), 3.0);
+        assert_eq!(outline, vec![[0, 1], [0, 2], [1, 2]]);
+    }
+}
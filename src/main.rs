@@ -1,35 +1,466 @@
-#![allow(dead_code)]
 extern crate image;
 extern crate nalgebra;
+extern crate rastermind;
 
 use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
+use std::thread;
+use std::time::Duration;
 
-use nalgebra::Vector3;
+use nalgebra::{Matrix4, Vector3};
 
-mod render;
-mod shader;
-mod vector;
-mod wavefront;
+use rastermind::{Animation, Renderer};
+use rastermind::error::MorpheusError;
+use rastermind::{abuffer, animation, camera, cli, compat, dataset, export, interchange, light, lines, motion,
+                 overrides, parameters, plugins, preview, progress, reference, render, rpc, scene, service, shadow,
+                 svg, sweep, terminal, text, texture, velocity, wavefront};
+
+
+/// Return the value of a result, or report its error and exit
+fn or_exit<T, E: fmt::Display>(result: Result<T, E>) -> T {
+    result.unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    })
+}
+
+
+/// Report the error of writing the file at the given path and exit
+fn written<P: AsRef<Path>>(path: P, result: io::Result<()>) {
+    or_exit(result.map_err(|error| MorpheusError::io(&path.as_ref().display().to_string(), error)))
+}
+
+
+/// Draw the model of a render with the compute shaders of the GPU backend
+#[cfg(feature = "wgpu")]
+fn draw_on_gpu(options: &cli::RenderOptions, coordinates: &wavefront::Object, texture: &image::RgbImage,
+               camera: &camera::Camera, light_vector: &Vector3<f64>, depth_options: render::DepthOptions)
+               -> Result<image::RgbImage, MorpheusError> {
+    use rastermind::gpu;
+
+    if options.progress.is_some() || depth_options != render::DepthOptions::default() {
+        return Err(MorpheusError::Render(String::from("--progress, --check-nan, and --merge-micro-triangles \
+                                                       only work with the cpu backend")));
+    }
+
+    let shading = gpu::Shading::parse(&options.shader)?;
+    let rasterizer = gpu::Rasterizer::new()?;
+
+    rasterizer.draw_mesh(coordinates, texture, camera, light_vector, options.width, options.height, shading)
+}
+
+
+/// Draw the model of a render on the GPU, which needs a build with the wgpu feature
+#[cfg(not(feature = "wgpu"))]
+fn draw_on_gpu(_options: &cli::RenderOptions, _coordinates: &wavefront::Object, _texture: &image::RgbImage,
+               _camera: &camera::Camera, _light_vector: &Vector3<f64>, _depth_options: render::DepthOptions)
+               -> Result<image::RgbImage, MorpheusError> {
+    Err(MorpheusError::Render(String::from("the gpu backend is only built in with the wgpu feature")))
+}
 
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let options = cli::Options::from_args(env::args()).unwrap_or_else(|error| error.exit());
+
+    for library in &options.plugins {
+        or_exit(unsafe { plugins::load_library(library) });
+    }
+
+    // every --set path=value overrides a parameter of the request, after those in the environment
+    let assignments: Vec<String> = overrides::from_environment(env::vars()).into_iter().chain(options.assignments)
+                                                                           .collect();
+
+    // models drawn in every frame of an animation or a preview are worth ordering for the vertex cache
+    let animated = wavefront::LoadOptions { optimize_vertex_cache: true, ..Default::default() };
+
+    match options.command {
+        cli::Command::Render(render) => {
+            render.check_shader().unwrap_or_else(|error| error.exit());
+            draw_render(&render, options.backend, options.depth);
+        }
+        cli::Command::Request { request, output } => {
+            let json = or_exit(fs::read_to_string(&request).map_err(|error| MorpheusError::io(&request, error)));
+            let png = or_exit(overrides::parse::<service::Request>(&json, &assignments)
+                                        .and_then(|request| service::render(&request)));
+
+            written(&output, fs::write(&output, png));
+        }
+        cli::Command::Sweep { output, request, axes } => {
+            // render every combination of one or two parameters into a labelled comparison sheet
+            let json = or_exit(fs::read_to_string(&request).map_err(|error| MorpheusError::io(&request, error)));
+            let axes: Vec<sweep::Axis> = axes.iter().map(|axis| or_exit(sweep::Axis::parse(axis))).collect();
+
+            let sheet = sweep::sweep(&axes[0], axes.get(1), &sweep::SheetOptions::default(), |cell| {
+                let cell_assignments: Vec<String> = assignments.iter().chain(cell).cloned().collect();
+                let png = overrides::parse::<service::Request>(&json, &cell_assignments)
+                                    .and_then(|request| service::render(&request))?;
+
+                image::load_from_memory(&png).map(|image| image.flipv().to_rgb())
+                                             .map_err(|error| MorpheusError::Render(error.to_string()))
+            });
+
+            written(&output, image::ImageRgb8(or_exit(sheet)).flipv().save(&output));
+        }
+        cli::Command::Serve => {
+            let stdin = io::stdin();
+            let stdout = io::stdout();
+
+            or_exit(service::serve(stdin.lock(), stdout.lock())
+                            .map_err(|error| format!("cannot serve render requests: {}", error)));
+        }
+        cli::Command::Dataset { model, texture, output, frames } => {
+            let coordinates = or_exit(wavefront::Object::new(&model));
+            let texture = or_exit(texture::open(&texture));
+            let mut options = dataset::DatasetOptions::default();
+
+            if let Some(frames) = frames {
+                options.frames = frames;
+            }
+
+            or_exit(dataset::generate(&coordinates, &texture, &options, Path::new(&output)));
+        }
+        cli::Command::Svg { model, output, hidden_lines } => {
+            let coordinates = or_exit(wavefront::Object::new(&model));
+            let options = svg::SvgOptions { hidden_lines, ..Default::default() };
+            let eye = Vector3::new(0.0, 15.0, 70.0);
+            let drawing = svg::render(&coordinates, 2048, 2048, &eye, &Vector3::zeros(), &Vector3::y(), &options);
+
+            written(&output, fs::write(&output, drawing));
+        }
+        cli::Command::HiddenLine { model, output, hidden_lines } => {
+            let coordinates = or_exit(wavefront::Object::new(&model));
+            let options = lines::HiddenLineOptions { hidden_lines, ..Default::default() };
+            let mut buffer = image::RgbImage::from_pixel(2048, 2048, image::Rgb([255, 255, 255]));
+            let eye = Vector3::new(0.0, 15.0, 70.0);
+
+            lines::draw_hidden_line(&coordinates, &mut buffer, &eye, &Vector3::zeros(), &Vector3::y(), &options);
+
+            written(&output, image::ImageRgb8(buffer).flipv().save(&output));
+        }
+        cli::Command::Turntable { model, texture, output, frames, samples } => {
+            let coordinates = or_exit(wavefront::Object::with_options(&model, &animated));
+            let texture = or_exit(texture::open(&texture));
+
+            written(&output, fs::create_dir_all(&output));
+
+            // the model turns once about its vertical axis in front of a camera that stays still
+            let (eye, up) = (Vector3::new(0.0, 15.0, 70.0), Vector3::y());
+            let mut spin = animation::ObjectTracks::new(0);
+            let start = animation::Keyframe { easing: animation::Easing::Linear,
+                                              ..animation::Keyframe::new(0.0, Vector3::zeros()) };
+            let turned = animation::Keyframe::new(frames as f64, -Vector3::y() * 2.0 * std::f64::consts::PI);
+            spin.rotation = animation::Track { keyframes: vec![start, turned], looped: true };
+
+            let view = |time: f64| {
+                let mut view = velocity::View::lookat(1024, 1024, 255, &eye, &Vector3::zeros(), &up);
+                view.model_view *= spin.transform(time);
+                view
+            };
+
+            let mut scene = scene::Scene::new();
+            scene.objects.push(scene::SceneObject::new(coordinates, texture));
+            scene.lights.push(scene::SceneLight::new("camera", light::Light::Directional(eye.normalize())));
+
+            let mut turntable = Animation::new(frames, 1024, 1024);
+            turntable.objects.push(spin.clone());
+            if samples > 1 {
+                turntable.motion_blur = Some(motion::MotionBlur { samples, ..Default::default() });
+            }
+
+            turntable.after_frame(|frame| {
+                let velocity = velocity::velocity_buffer(&frame.scene.objects[0].object, 1024, 1024,
+                                                         &view(frame.frame as f64 - 1.0), &view(frame.frame as f64));
+
+                let path = Path::new(&output).join(format!("frame_{:04}", frame.frame));
+                let (png, flow) = (path.with_extension("png"), path.with_extension("flo"));
+                written(&png, image::ImageRgb8(frame.color.clone()).flipv().save(&png));
+                written(&flow, velocity::save_flow(&flow, &velocity, 1024, 1024));
+            });
+
+            turntable.render(&mut scene, &mut animation::Camera::new(eye, Vector3::zeros(), up));
+        }
+        cli::Command::CameraPath { model, texture, path, output, fps } => {
+            // render a camera path recorded in the preview at the full resolution, one frame per image
+            let coordinates = or_exit(wavefront::Object::new(&model));
+            let texture = or_exit(texture::open(&texture));
+            let path = or_exit(preview::CameraPath::open(&path));
+
+            written(&output, fs::create_dir_all(&output));
+
+            for (frame, camera) in path.frames(fps).into_iter().enumerate() {
+                let image = or_exit(Renderer::new(2048, 2048).with_camera(camera).render(&coordinates, &texture));
+
+                let png = Path::new(&output).join(format!("frame_{:04}.png", frame));
+                written(&png, image.save(&png));
+            }
+        }
+        cli::Command::Export { model, output, normalize } => {
+            // write a model back out after it is loaded, converting between formats and optionally normalizing it
+            let mut coordinates = or_exit(plugins::open(&model));
+
+            if normalize {
+                coordinates.normalize();
+            }
+
+            or_exit(export::save(&coordinates, &output));
+        }
+        cli::Command::Import { scene, output, width, height } => {
+            // render a scene exported from Blender or Assimp from its active camera
+            let (scene, camera) = or_exit(interchange::import(&scene));
+            let image = or_exit(Renderer::new(width, height).with_camera(camera.unwrap_or_default())
+                                                            .render_scene(&scene));
+
+            written(&output, image.save(&output));
+        }
+        cli::Command::Scene { scene, output } => {
+            // render a scene description of models, camera, lights, and shader written in TOML or JSON
+            let image = or_exit(scene::load_scene(&scene));
+            written(&output, image.save(&output));
+        }
+        cli::Command::DeepExr { scene, output } => {
+            // record every fragment of a scene description in a deep OpenEXR file for deep compositing
+            let abuffer = or_exit(scene::load_deep_scene(&scene));
+            written(&output, abuffer::save_deep_exr(&output, &abuffer));
+        }
+        cli::Command::Backdrop { model, texture, photo, intrinsics, output } => {
+            let coordinates = or_exit(wavefront::Object::new(&model));
+            let texture = or_exit(texture::open(&texture));
+            let photo = or_exit(image::open(&photo).map_err(|error| MorpheusError::image(&photo, error))).to_rgb();
+            let [fx, fy, cx, cy] = intrinsics;
+            let intrinsics = camera::Intrinsics::new(fx, fy, cx, cy, photo.width(), photo.height());
+
+            // stand the model on a ground plane that only catches its shadow
+            let floor = coordinates.geometric_vertices.iter().fold(f64::MAX, |floor, vertex| floor.min(vertex.y));
+            let ground = format!("v -100 {0} -100\nv 100 {0} -100\nv 100 {0} 100\nv -100 {0} 100\nvt 0 0\nvn 0 1 0\n\
+                                  f 1/1/1 4/1/1 3/1/1\nf 1/1/1 3/1/1 2/1/1\n", floor);
+            let ground = or_exit(wavefront::Object::from_bytes(ground.as_bytes(), &wavefront::LoadOptions::default()));
+            let mut catcher = scene::SceneObject::new(ground, image::RgbImage::new(1, 1));
+            catcher.visibility = scene::Visibility { shadow_catcher: true, cast_shadows: false, ..Default::default() };
+
+            let mut scene = scene::Scene::new();
+            scene.shadows = Some(shadow::ShadowOptions::default());
+            scene.lights.push(scene::SceneLight::new("sun", light::Light::Directional(Vector3::new(0.3, 1.0, 0.5)
+                                                                                          .normalize())));
+            scene.objects.push(scene::SceneObject::new(coordinates, texture));
+            scene.objects.push(catcher);
+
+            let eye = Vector3::new(0.0, 15.0, 70.0);
+            let composite = scene::render_over_photo(&scene, &photo, &intrinsics, 255, &eye, &Vector3::zeros(),
+                                                     &Vector3::y());
+
+            written(&output, composite.save(&output));
+        }
+        cli::Command::Studio { model, texture, output } => {
+            let coordinates = or_exit(wavefront::Object::new(&model));
+            let texture = or_exit(texture::open(&texture));
+
+            // frame the model from the front and slightly above, whatever its size
+            let (minimum, maximum) = coordinates.bounds().unwrap_or((Vector3::zeros(), Vector3::zeros()));
+            let center = (minimum + maximum) / 2.0;
+            let eye = center + Vector3::new(0.3, 0.3, 1.0).normalize() * (maximum - minimum).norm().max(1.0) * 1.5;
+
+            // light the model with a key, fill and rim placed around it instead of by hand
+            let mut scene = scene::Scene::new();
+            scene.objects.push(scene::SceneObject::new(coordinates, texture));
+            scene.add_three_point_lights(&eye, &Vector3::y(), &scene::ThreePointLighting::default());
+
+            let mut buffer = image::RgbImage::new(1024, 1024);
+            scene::render_scene(&scene, &mut buffer, 255, &eye, &center, &Vector3::y());
+
+            written(&output, image::ImageRgb8(buffer).flipv().save(&output));
+        }
+        cli::Command::Reference { model, texture, output, samples } => {
+            let coordinates = or_exit(wavefront::Object::new(&model));
+            let texture = or_exit(texture::open(&texture));
+            let options = reference::ReferenceOptions { samples, ..Default::default() };
+
+            // use the camera and light of the default render so the two can be compared
+            let eye = Vector3::new(0.0, 15.0, 70.0);
+            let light_vector = eye.normalize();
+            let mut traced = image::RgbImage::new(512, 512);
+            let mut rasterized = image::RgbImage::new(512, 512);
+
+            or_exit(reference::render_reference(&coordinates, &texture, &mut traced, &options, &light_vector, &eye,
+                                                &Vector3::zeros(), &Vector3::y()));
+            render::draw_object(&coordinates, &mut rasterized, &texture, 255, &light_vector, &eye,
+                                &Vector3::zeros(), &Vector3::y());
+
+            println!("mean error of the rasterized render: {:.2}", reference::mean_error(&traced, &rasterized));
+            written(&output, image::ImageRgb8(traced).flipv().save(&output));
+        }
+        cli::Command::Text { font, text, output } => {
+            let font = or_exit(text::Font::open(&font).map_err(|error| MorpheusError::io(&font, error)));
+            let mut coordinates = or_exit(text::text_mesh(&font, &text, &text::TextOptions::default())
+                                              .map_err(|error| format!("cannot lay out '{}': {}", text, error)));
+
+            // without any glyphs there are no bounds to fit into the viewport
+            if coordinates.geometric_vertices.is_empty() {
+                or_exit(Err(format!("text needs text with glyphs in the font, not '{}'", text)))
+            }
+
+            // fit the text into the unit square the viewport is made for
+            let vertices = &coordinates.geometric_vertices;
+            let minimum = vertices.iter().fold(Vector3::repeat(f64::MAX), |minimum, v| minimum.zip_map(v, f64::min));
+            let maximum = vertices.iter().fold(Vector3::repeat(f64::MIN), |maximum, v| maximum.zip_map(v, f64::max));
+            let size = (maximum - minimum).x.max((maximum - minimum).y);
+
+            coordinates.transform(&(Matrix4::new_scaling(2.0 / size) *
+                                    Matrix4::new_translation(&(-(minimum + maximum) / 2.0))));
+
+            let mut buffer = image::RgbImage::new(1024, 1024);
+            let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+            let eye = Vector3::new(0.5, 0.5, 3.0);
+
+            render::draw_object(&coordinates, &mut buffer, &texture, 255, &eye.normalize(), &eye,
+                                &Vector3::zeros(), &Vector3::y());
+
+            written(&output, image::ImageRgb8(buffer).flipv().save(&output));
+        }
+        cli::Command::TinyRenderer { model, texture } => {
+            // match the output of the lessons pixel for pixel, including their file names
+            let coordinates = or_exit(wavefront::Object::new(&model));
+            let (texture, shading) = match texture {
+                Some(texture) => (or_exit(texture::open(&texture)), compat::LessonShading::Textured),
+                None => (image::RgbImage::new(1, 1), compat::LessonShading::Gouraud)
+            };
+            let options = compat::LessonOptions { shading, ..Default::default() };
+
+            let (image, zbuffer) = compat::render(&coordinates, &texture, &options);
+
+            written("output.tga", compat::save_tga("output.tga", &image));
+            written("zbuffer.tga", compat::save_tga_gray("zbuffer.tga", &zbuffer));
+        }
+        cli::Command::Preview { model, texture, frames, parameters } => {
+            // orbit the model in the terminal at a preview resolution that keeps up, then draw it fully once it stops
+            let coordinates = or_exit(wavefront::Object::with_options(&model, &animated));
+            let texture = or_exit(texture::open(&texture));
+            let mut watched = parameters.map(|path| or_exit(parameters::ParametersFile::open(&path)));
+            let mut preview = preview::AdaptiveResolution::new(Duration::from_millis(33));
+
+            let draw = |buffer: &mut image::RgbImage, eye: &Vector3<f64>,
+                        watched: &Option<parameters::ParametersFile>| match watched {
+                Some(watched) => watched.parameters.draw_object(&coordinates, buffer, &texture, 255,
+                                                                &eye.normalize(), eye, &Vector3::zeros(),
+                                                                &Vector3::y()),
+                None => render::draw_object(&coordinates, buffer, &texture, 255, &eye.normalize(), eye,
+                                            &Vector3::zeros(), &Vector3::y())
+            };
+
+            let mut eye = Vector3::new(0.0, 15.0, 70.0);
+
+            for frame in 0..=frames {
+                let angle = frame as f64 / frames.max(1) as f64 * 2.0 * std::f64::consts::PI;
+                eye = Vector3::new(70.0 * angle.sin(), 15.0, 70.0 * angle.cos());
+
+                let buffer = preview.render(160, 160, frame < frames, |buffer| { draw(buffer, &eye, &watched); });
+
+                print!("\x1b[H{}", terminal::ansi(&buffer));
+            }
+
+            // with a parameters file, keep watching it and draw the model again whenever its look changes
+            while let Some(ref mut file) = watched {
+                thread::sleep(Duration::from_millis(250));
+
+                match file.reload() {
+                    Ok(true) => {
+                        let buffer = preview.render(160, 160, false, |buffer| { draw(buffer, &eye, &watched); });
+                        print!("\x1b[H{}", terminal::ansi(&buffer));
+                    },
+                    Ok(false) => (),
+                    Err(error) => eprintln!("{}", error)
+                }
+            }
+        }
+        cli::Command::Rpc { model, texture, address } => {
+            // let other tools drive the preview over stdio, drawing it on stderr, or over TCP when an address is given
+            let mut preview = or_exit(rpc::Preview::open(&model, &texture, 160, 160));
+            let redraw = |preview: &rpc::Preview| {
+                eprint!("\x1b[H{}", terminal::ansi(&preview.render(preview.width, preview.height)));
+            };
+
+            match address {
+                Some(address) => or_exit(rpc::listen(&mut preview, address.as_str(), redraw)
+                                             .map_err(|error| format!("cannot listen on {}: {}", address, error))),
+                None => {
+                    let stdin = io::stdin();
+                    let stdout = io::stdout();
+
+                    or_exit(rpc::serve(&mut preview, stdin.lock(), stdout.lock(), redraw)
+                                .map_err(|error| format!("cannot serve the preview: {}", error)));
+                }
+            }
+        }
+        cli::Command::Terminal { output, model, texture, columns } => {
+            let coordinates = or_exit(plugins::open(&model));
+            let texture = or_exit(texture::open(&texture));
+
+            let mut buffer = image::ImageBuffer::new(columns, columns);
+            let eye = Vector3::new(0.0, 15.0, 70.0);
+
+            render::draw_object(&coordinates, &mut buffer, &texture, 255, &eye.normalize(),
+                                &eye, &Vector3::zeros(), &Vector3::y());
+
+            match output.as_str() {
+                "ansi" => print!("{}", terminal::ansi(&buffer)),
+                "ascii" => print!("{}", terminal::ascii(&buffer)),
+                "sixel" => println!("{}", terminal::graphics(&buffer, terminal::Graphics::Sixel)),
+                "kitty" => println!("{}", terminal::graphics(&buffer, terminal::Graphics::Kitty)),
+                _ => println!("{}", terminal::graphics(&buffer, terminal::Graphics::ITerm))
+            }
+        }
+    }
+}
+
+
+/// Draw a render of a single model with the given backend and save it
+fn draw_render(options: &cli::RenderOptions, backend: render::Backend, depth_options: render::DepthOptions) {
+    let coordinates = or_exit(wavefront::Object::new(&options.model));
+    let texture = or_exit(texture::open(&options.texture));
+
+    let camera = options.camera();
+    let light_vector = options.light_vector();
+
+    if backend == render::Backend::Gpu {
+        let buffer = or_exit(draw_on_gpu(options, &coordinates, &texture, &camera, &light_vector, depth_options));
+        written(&options.output, image::ImageRgb8(buffer).flipv().save(&options.output));
+        return;
+    }
+
+    let framebuffer = render::Framebuffer::new(options.width, options.height);
+    let mut pipeline = render::Pipeline::new(&camera, &light_vector, framebuffer).with_depth(depth_options);
 
-    let (width, height, depth) = (2048, 2048, 255);
+    // save the partial render every few seconds so long renders can be checked on
+    let mut progress = options.progress.as_ref().map(|&(ref path, seconds)| {
+        (path.clone(), progress::Progress::new(path, Duration::from_secs(seconds)))
+    });
 
-    let mut buffer = image::ImageBuffer::new(width, height);
+    let stats = match progress {
+        Some((ref path, ref mut progress)) => {
+            or_exit(pipeline.draw_mesh_progressively(&coordinates, &texture, options.shader.as_str(),
+                                                     |buffer, drawn, total| {
+                written(path, progress.update(buffer, drawn, total));
+            }))
+        },
+        None => or_exit(pipeline.draw_mesh_with_shader(&coordinates, &texture, options.shader.as_str()))
+    };
 
-    let texture = image::open(&args[2]).unwrap().flipv().to_rgb();
+    if stats.degenerate > 0 {
+        eprintln!("skipped {} degenerate of {} faces", stats.degenerate, stats.faces);
+    }
 
-    let eye = Vector3::new(0.0, 15.0, 70.0);
-    let center = Vector3::new(0.0, 0.0, 0.0);
-    let up = Vector3::new(0.0, 1.0, 0.0);
-    let light_vector = Vector3::new(0.0, 15.0, 70.0).normalize();
+    if stats.non_finite > 0 {
+        eprintln!("{} of {} faces have {} fragments that are not finite", stats.non_finite, stats.faces,
+                  stats.non_finite_fragments);
+    }
 
-    render::draw_triangle_mesh(&args[1], &mut buffer, &texture, depth,
-                               &light_vector, &eye, &center, &up);
+    if stats.batches > 1 {
+        eprintln!("drew {} faces in {} batches of the same material", stats.faces, stats.batches);
+    }
 
-    image::ImageRgb8(buffer).flipv()
-                            .save("output.png")
-                            .unwrap();
+    let buffer = pipeline.into_framebuffer().into_color();
+    written(&options.output, image::ImageRgb8(buffer).flipv().save(&options.output));
 }
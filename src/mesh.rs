@@ -0,0 +1,57 @@
+use nalgebra::Vector3;
+
+use wavefront;
+
+
+/// A material of an imported mesh, with the texture it names as it was written in the file
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeshMaterial {
+    pub name: String,
+    pub diffuse: Vector3<f64>,
+    pub texture: Option<String>
+}
+
+
+/// An Object imported from a format that keeps its materials with its geometry
+///
+/// Wavefront files keep their materials in separate libraries, so the
+/// materials of other formats are kept beside the Object, with the material
+/// of every face as an index into them. Faces without a material are None.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::fbx;
+/// # fn main() -> Result<(), rastermind::Error> {
+/// let mesh = fbx::open("chair.fbx")?;
+///
+/// for (face, geometric_face) in mesh.object.geometric_faces.iter().enumerate() {
+///     let diffuse = mesh.material(face).map_or(Vector3::repeat(1.0), |material| material.diffuse);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    pub object: wavefront::Object,
+    pub materials: Vec<MeshMaterial>,
+    pub face_materials: Vec<Option<usize>>
+}
+
+
+impl Mesh {
+    /// The material of the face with the given index, if it has one
+    pub fn material(&self, face: usize) -> Option<&MeshMaterial> {
+        self.face_materials.get(face).cloned().and_then(|material| material)
+                           .and_then(|material| self.materials.get(material))
+    }
+
+    /// The path of the first texture named by the materials, which is the texture of most single textured assets
+    pub fn texture(&self) -> Option<&str> {
+        self.materials.iter().filter_map(|material| material.texture.as_ref()).map(String::as_str).next()
+    }
+}
@@ -0,0 +1,152 @@
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use post;
+
+
+/// Options that control the motion blur of a frame of an animation
+///
+/// The shutter is how long it stays open as a fraction of a frame, where 0.5
+/// matches the 180 degree shutter of film cameras. The shutter opens at the
+/// time of the frame and is split into as many equal intervals as there are
+/// samples, where each sample is drawn at a random time within its interval,
+/// so the blur is smooth without the banding of evenly spaced copies.
+///
+/// # Examples
+///
+/// ```
+/// # use rastermind::motion::MotionBlur;
+/// let options = MotionBlur { samples: 16, shutter: 1.0, ..Default::default() };
+/// ```
+///
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MotionBlur {
+    pub samples: usize,
+    pub shutter: f64,
+    pub seed: u64
+}
+
+
+impl Default for MotionBlur {
+    fn default() -> MotionBlur {
+        MotionBlur { samples: 8, shutter: 0.5, seed: 0 }
+    }
+}
+
+
+/// Find the jittered times within the open shutter of the given frame that are drawn
+///
+/// # Examples
+///
+/// ```
+/// # use rastermind::motion::*;
+/// let times = shutter_times(12.0, &MotionBlur::default());
+/// assert!(times.iter().all(|&time| time >= 12.0 && time < 12.5));
+/// ```
+///
+pub fn shutter_times(frame: f64, options: &MotionBlur) -> Vec<f64> {
+    let samples = options.samples.max(1);
+    // every frame jitters differently, so the noise of the blur does not stand still
+    let mut rng = StdRng::seed_from_u64(options.seed ^ frame.to_bits());
+
+    (0..samples).map(|sample| {
+        let jitter: f64 = if samples > 1 { rng.gen() } else { 0.0 };
+
+        frame + options.shutter * (sample as f64 + jitter) / samples as f64
+    }).collect()
+}
+
+
+/// Render a frame with motion blur by averaging the images drawn at every shutter time
+///
+/// The draw function is given a fractional frame time and an empty framebuffer
+/// of the given size, and draws the animation as it is at that time. The
+/// images are averaged in linear light so bright moving objects leave
+/// trails of the right brightness.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use std::f64::consts::PI;
+/// # use rastermind::motion::*;
+/// # use rastermind::{render, wavefront};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let frame = 12;
+/// let buffer = render_motion_blur(512, 512, frame as f64, &MotionBlur::default(), |time, buffer| {
+///     let angle = time / 36.0 * 2.0 * PI;
+///     let eye = Vector3::new(angle.sin(), 0.0, angle.cos()) * 5.0;
+///
+///     render::draw_object(&coordinates, buffer, &texture, 255, &eye.normalize(), &eye,
+///                         &Vector3::zeros(), &Vector3::y());
+/// });
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn render_motion_blur<F>(width: u32, height: u32, frame: f64, options: &MotionBlur,
+                             mut draw: F) -> image::RgbImage where F: FnMut(f64, &mut image::RgbImage) {
+
+    let times = shutter_times(frame, options);
+    let mut sum = vec![[0.0; 3]; (width * height) as usize];
+
+    for &time in &times {
+        let mut buffer = image::ImageBuffer::new(width, height);
+        draw(time, &mut buffer);
+
+        for (total, pixel) in sum.iter_mut().zip(buffer.pixels()) {
+            (0..3).for_each(|i| total[i] += post::to_linear(pixel[i]));
+        }
+    }
+
+    image::ImageBuffer::from_fn(width, height, |x, y| {
+        let total = sum[(y * width + x) as usize];
+
+        image::Rgb([post::to_srgb(total[0] / times.len() as f64), post::to_srgb(total[1] / times.len() as f64),
+                    post::to_srgb(total[2] / times.len() as f64)])
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shutter_times() {
+        let options = MotionBlur { samples: 4, shutter: 0.5, seed: 3 };
+        let times = shutter_times(10.0, &options);
+
+        assert_eq!(times.len(), 4);
+        // one time in every quarter of the open shutter
+        for (sample, &time) in times.iter().enumerate() {
+            assert!(time >= 10.0 + 0.125 * sample as f64 && time < 10.0 + 0.125 * (sample + 1) as f64);
+        }
+
+        assert_eq!(times, shutter_times(10.0, &options));
+        assert_ne!(times[0] - 10.0, shutter_times(11.0, &options)[0] - 11.0);
+        assert_eq!(shutter_times(2.0, &MotionBlur { samples: 1, ..options }), vec![2.0]);
+    }
+
+    #[test]
+    fn test_render_motion_blur() {
+        let options = MotionBlur { samples: 8, shutter: 1.0, seed: 0 };
+
+        // a white column that moves 8 pixels to the right while the shutter is open
+        let buffer = render_motion_blur(16, 1, 0.0, &options, |time, buffer| {
+            buffer.put_pixel(4 + (time * 8.0) as u32, 0, image::Rgb([255, 255, 255]));
+        });
+
+        let values: Vec<u8> = buffer.pixels().map(|pixel| pixel[0]).collect();
+
+        assert!(values[..4].iter().chain(&values[12..]).all(|&value| value == 0));
+        assert!(values[4..12].iter().all(|&value| value > 0 && value < 255));
+        assert_eq!(values[4], post::to_srgb(1.0 / 8.0));
+    }
+}
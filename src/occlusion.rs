@@ -0,0 +1,209 @@
+use nalgebra::{Matrix4, Vector3, Vector4};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+use bvh::{Bvh, RAY_OFFSET};
+use reference;
+use shader::{Contributions, Shader};
+use wavefront;
+
+
+/// Options that control the ambient occlusion traced at the vertices of an Object
+///
+/// Every vertex casts the given number of rays over the hemisphere around
+/// its normal, and the rays that hit a face closer than the distance are
+/// occluded, so a shorter distance only darkens the creases and cavities
+/// while an infinite distance darkens everything that is enclosed. The same
+/// seed always casts the same rays.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OcclusionOptions {
+    pub samples: u32,
+    pub distance: f64,
+    pub seed: u64
+}
+
+
+impl Default for OcclusionOptions {
+    fn default() -> OcclusionOptions {
+        OcclusionOptions { samples: 64, distance: f64::INFINITY, seed: 0 }
+    }
+}
+
+
+/// Trace the fraction of the ambient light that reaches every geometric vertex of an Object
+///
+/// The rays are cosine weighted around the area weighted normal of the
+/// vertex and cast against a Bvh of the Object, so the fraction of rays that
+/// escape is the ambient light a diffuse surface receives, from 0 where it is
+/// fully enclosed to 1 where it is open. Vertices without a face are open.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate rastermind;
+/// # use rastermind::occlusion;
+/// # use rastermind::occlusion::*;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// let occlusion = occlusion::vertex_occlusion(&coordinates, &OcclusionOptions { samples: 16, ..Default::default() });
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn vertex_occlusion(coordinates: &wavefront::Object, options: &OcclusionOptions) -> Vec<f64> {
+    let bvh = Bvh::new(coordinates);
+    let normals = coordinates.vertex_normals();
+    let samples = options.samples.max(1);
+
+    // rays leave a little above the surface, in proportion to its size, so they do not hit the faces they leave
+    let offset = coordinates.bounds().map_or(0.0, |(minimum, maximum)| (maximum - minimum).norm() * 1e-6)
+                                     .max(RAY_OFFSET);
+    let mut rng = StdRng::seed_from_u64(options.seed);
+
+    coordinates.geometric_vertices.iter().zip(&normals).map(|(vertex, normal)| {
+        if *normal == Vector3::zeros() {
+            return 1.0;
+        }
+
+        let origin = vertex + normal * offset;
+        let open = (0..samples).filter(|_| {
+            !bvh.occluded(&origin, &reference::cosine_direction(normal, &mut rng), options.distance)
+        }).count();
+
+        open as f64 / samples as f64
+    }).collect()
+}
+
+
+/// Darken another shader by the ambient occlusion traced at the vertices of the drawn Object
+///
+/// The occlusion is interpolated across every face, which is far cheaper
+/// than tracing it for every fragment or baking it into a texture and suits
+/// untextured meshes that are dense enough. The shaders of the crate have
+/// no ambient light of their own, so the occlusion darkens all of their
+/// light, and it is also reported as the ambient occlusion of the fragment.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate rastermind;
+/// # use rastermind::occlusion::*;
+/// # use rastermind::shader::GouraudShader;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), rastermind::Error> {
+/// let options = wavefront::LoadOptions { ambient_occlusion: Some(OcclusionOptions::default()), ..Default::default() };
+/// let coordinates = wavefront::Object::with_options("scan.obj", &options)?;
+///
+/// let shader = OcclusionShader::new(Box::new(GouraudShader::new()), &coordinates.vertex_occlusion);
+/// # Ok(())
+/// # }
+/// ```
+///
+pub struct OcclusionShader<'a> {
+    pub shader: Box<dyn Shader + 'a>,
+    pub occlusion: &'a [f64],
+    pub varying_occlusion: Vector3<f64>
+}
+
+
+impl<'a> OcclusionShader<'a> {
+    /// Create a new instance of an OcclusionShader that darkens the given shader by the given vertex occlusion
+    pub fn new(shader: Box<dyn Shader + 'a>, occlusion: &'a [f64]) -> OcclusionShader<'a> {
+        OcclusionShader { shader, occlusion, varying_occlusion: Vector3::zeros() }
+    }
+
+    /// The fraction of the ambient light that reaches the fragment
+    fn occlusion(&self, vertex: Vector3<f64>) -> f64 {
+        self.varying_occlusion.dot(&vertex).clamp(0.0, 1.0)
+    }
+}
+
+
+impl<'a> Shader for OcclusionShader<'a> {
+    /// Position the vertices with the inner shader and keep their occlusion
+    fn vertex(&mut self, coordinates: &wavefront::Object,
+              view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+              model_view: &Matrix4<f64>, light_vector: &Vector3<f64>,
+              face_index: usize, vertex_index: usize) -> Vector4<f64> {
+
+        let geometric_index = coordinates.geometric_faces[face_index][vertex_index] as usize;
+        self.varying_occlusion[vertex_index] = self.occlusion.get(geometric_index).cloned().unwrap_or(1.0);
+
+        self.shader.vertex(coordinates, view_port, projection, model_view, light_vector,
+                           face_index, vertex_index)
+    }
+
+    /// Scale the color of the inner shader by the occlusion of the fragment
+    fn fragment(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> image::Rgb<u8> {
+        let mut color = self.shader.fragment(vertex, texture);
+        let occlusion = self.occlusion(vertex);
+
+        (0..=2).for_each(|i| { color[i] = (color[i] as f64 * occlusion) as u8; });
+
+        color
+    }
+
+    /// Discard the fragments the inner shader discards
+    fn discard(&self, vertex: Vector3<f64>) -> bool {
+        self.shader.discard(vertex)
+    }
+
+    /// Scale the light of the inner shader by the occlusion of the fragment and report it as its ambient occlusion
+    fn contributions(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> Contributions {
+        let occlusion = self.occlusion(vertex);
+        let gray = (occlusion * 255.0).round() as u8;
+
+        self.shader.contributions(vertex, texture).scale_light(occlusion)
+                   .add(&Contributions { ambient_occlusion: image::Rgb([gray, gray, gray]), ..Default::default() })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shader;
+
+    #[test]
+    fn test_vertex_occlusion() {
+        // a floor with a wall standing on its far edge, and a vertex in the open in front of it
+        let data = b"v -1 0 -1\nv 1 0 -1\nv 1 0 1\nv -1 0 1\nv -1 2 -1\nv 1 2 -1\nv 0 0 0.5\n\
+                     vt 0 0\nvn 0 1 0\n\
+                     f 1/1/1 4/1/1 3/1/1\nf 1/1/1 3/1/1 2/1/1\nf 1/1/1 2/1/1 6/1/1\nf 1/1/1 6/1/1 5/1/1\n";
+        let options = wavefront::LoadOptions { ambient_occlusion: Some(OcclusionOptions { samples: 256,
+                                                                                          ..Default::default() }),
+                                               ..Default::default() };
+        let coordinates = wavefront::Object::from_bytes(data, &options).unwrap();
+        let occlusion = &coordinates.vertex_occlusion;
+
+        assert_eq!(occlusion.len(), 7);
+
+        // the corners in the crease are more covered by the wall than the corners across the floor
+        assert!(occlusion[2] < 1.0 && occlusion[2] > 0.7);
+        assert!(occlusion[0] < occlusion[2] && occlusion[0] > 0.3);
+
+        // the vertex without a face is open, and a short distance leaves only the crease occluded
+        assert_eq!(occlusion[6], 1.0);
+
+        let near = vertex_occlusion(&coordinates, &OcclusionOptions { distance: 0.01, ..Default::default() });
+        assert_eq!(near[2], 1.0);
+        assert!(near[0] < 1.0);
+    }
+
+    #[test]
+    fn test_occlusion_shader() {
+        let occlusion = [0.5];
+        let texture = image::RgbImage::new(1, 1);
+        let mut shader = OcclusionShader::new(Box::new(shader::ColorShader::new(image::Rgb([200, 100, 50]), false)),
+                                              &occlusion);
+        shader.varying_occlusion = Vector3::repeat(0.5);
+
+        let center = Vector3::repeat(1.0 / 3.0);
+
+        assert_eq!(shader.fragment(center, &texture), image::Rgb([100, 50, 25]));
+        assert_eq!(shader.contributions(center, &texture).ambient_occlusion, image::Rgb([128, 128, 128]));
+        assert_eq!(shader.contributions(center, &texture).diffuse, image::Rgb([100, 50, 25]));
+    }
+}
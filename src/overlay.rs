@@ -0,0 +1,651 @@
+use nalgebra::{Matrix4, U3, Vector2, Vector3};
+
+use vector;
+
+
+/// How far an overlay line may lie behind the depth buffer, as a fraction of the depth of the viewport, and still be drawn
+const DEPTH_TOLERANCE: f64 = 2e-3;
+
+/// The length in pixels of the sides of an arrowhead
+const ARROW_LENGTH: f64 = 8.0;
+
+/// The angle in radians between the sides of an arrowhead and its line
+const ARROW_ANGLE: f64 = 0.4;
+
+/// The glyphs of the overlay font as rows of three pixels from the top, where the highest bit is on the left
+const GLYPHS: &[(char, [u8; 5])] = &[
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+    ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b111, 0b001, 0b111, 0b100, 0b111]),
+    ('3', [0b111, 0b001, 0b111, 0b001, 0b111]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+    ('5', [0b111, 0b100, 0b111, 0b001, 0b111]),
+    ('6', [0b111, 0b100, 0b111, 0b101, 0b111]),
+    ('7', [0b111, 0b001, 0b001, 0b001, 0b001]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+    ('9', [0b111, 0b101, 0b111, 0b001, 0b111]),
+    ('.', [0b000, 0b000, 0b000, 0b000, 0b010]),
+    ('-', [0b000, 0b000, 0b111, 0b000, 0b000]),
+    (',', [0b000, 0b000, 0b000, 0b010, 0b100]),
+    ('=', [0b000, 0b111, 0b000, 0b111, 0b000]),
+    ('_', [0b000, 0b000, 0b000, 0b000, 0b111]),
+    (':', [0b000, 0b010, 0b000, 0b010, 0b000]),
+    ('/', [0b001, 0b001, 0b010, 0b100, 0b100]),
+    ('[', [0b110, 0b100, 0b100, 0b100, 0b110]),
+    (']', [0b011, 0b001, 0b001, 0b001, 0b011]),
+    ('a', [0b000, 0b110, 0b011, 0b101, 0b111]),
+    ('b', [0b100, 0b100, 0b111, 0b101, 0b111]),
+    ('c', [0b000, 0b000, 0b111, 0b100, 0b111]),
+    ('d', [0b001, 0b001, 0b111, 0b101, 0b111]),
+    ('e', [0b000, 0b111, 0b111, 0b100, 0b011]),
+    ('f', [0b011, 0b100, 0b111, 0b100, 0b100]),
+    ('g', [0b000, 0b111, 0b100, 0b101, 0b111]),
+    ('h', [0b100, 0b100, 0b111, 0b101, 0b101]),
+    ('i', [0b010, 0b000, 0b010, 0b010, 0b010]),
+    ('j', [0b001, 0b000, 0b001, 0b101, 0b111]),
+    ('k', [0b100, 0b101, 0b110, 0b110, 0b101]),
+    ('l', [0b110, 0b010, 0b010, 0b010, 0b111]),
+    ('m', [0b000, 0b000, 0b111, 0b111, 0b101]),
+    ('n', [0b000, 0b000, 0b110, 0b101, 0b101]),
+    ('o', [0b000, 0b000, 0b111, 0b101, 0b111]),
+    ('p', [0b000, 0b111, 0b101, 0b111, 0b100]),
+    ('q', [0b000, 0b111, 0b101, 0b111, 0b001]),
+    ('r', [0b000, 0b000, 0b111, 0b100, 0b100]),
+    ('s', [0b000, 0b011, 0b110, 0b001, 0b110]),
+    ('t', [0b010, 0b111, 0b010, 0b010, 0b011]),
+    ('u', [0b000, 0b000, 0b101, 0b101, 0b111]),
+    ('v', [0b000, 0b000, 0b101, 0b101, 0b010]),
+    ('w', [0b000, 0b000, 0b101, 0b111, 0b111]),
+    ('x', [0b000, 0b000, 0b101, 0b010, 0b101]),
+    ('y', [0b000, 0b101, 0b111, 0b001, 0b110]),
+    ('z', [0b000, 0b111, 0b011, 0b100, 0b111]),
+    ('X', [0b101, 0b101, 0b010, 0b101, 0b101]),
+    ('Y', [0b101, 0b101, 0b010, 0b010, 0b010]),
+    ('Z', [0b111, 0b001, 0b010, 0b100, 0b111])
+];
+
+/// The colors of the x, y, and z axes of the gizmo
+const AXIS_COLORS: [[u8; 3]; 3] = [[230, 60, 60], [60, 200, 60], [70, 110, 240]];
+
+
+/// A dimension line that measures the distance between two points of a scene
+///
+/// The line is drawn between the points moved by the offset, which keeps it
+/// clear of the geometry it measures, with extension lines back to the points
+/// and arrowheads at either end. The label defaults to the distance between
+/// the points with two decimals. Lines are hidden where they pass behind the
+/// faces of the scene while the label is always drawn on top.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::overlay::*;
+/// let width = Dimension::new(Vector3::new(-1.0, 0.0, 1.0), Vector3::new(1.0, 0.0, 1.0),
+///                            Vector3::new(0.0, -0.3, 0.0));
+/// let labelled = Dimension { label: Some(String::from("2 m")), ..width };
+/// ```
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dimension {
+    pub start: Vector3<f64>,
+    pub end: Vector3<f64>,
+    pub offset: Vector3<f64>,
+    pub color: image::Rgb<u8>,
+    pub label: Option<String>,
+    pub text_scale: u32
+}
+
+
+impl Dimension {
+    /// Create a new black Dimension between the given points labelled with their distance
+    pub fn new(start: Vector3<f64>, end: Vector3<f64>, offset: Vector3<f64>) -> Dimension {
+        Dimension { start, end, offset, color: image::Rgb([0, 0, 0]), label: None, text_scale: 2 }
+    }
+
+    /// The label of the dimension, which is its length unless a label was given
+    pub fn text(&self) -> String {
+        self.label.clone().unwrap_or_else(|| format!("{:.2}", (self.end - self.start).norm()))
+    }
+}
+
+
+/// Draw the given dimensions over a render with the depth buffer and matrices it was drawn with
+///
+/// The depth of the viewport is used to scale the tolerance of the depth test.
+///
+#[allow(clippy::too_many_arguments)]
+pub fn draw_dimensions(dimensions: &[Dimension], buffer: &mut image::RgbImage, zbuffer: &[f64],
+                       depth: u32, view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+                       model_view: &Matrix4<f64>) {
+
+    let transform = view_port * projection * model_view;
+    let project = |point: &Vector3<f64>| vector::project_to_3d(&(transform * vector::vectorize_to_4d(point)));
+    let tolerance = depth as f64 * DEPTH_TOLERANCE;
+
+    for dimension in dimensions {
+        let (start, end) = (dimension.start + dimension.offset, dimension.end + dimension.offset);
+        let (a, b) = (project(&start), project(&end));
+
+        let mut line = |from: &Vector3<f64>, to: &Vector3<f64>| {
+            draw_depth_line(buffer, zbuffer, tolerance, from, to, dimension.color);
+        };
+
+        line(&project(&dimension.start), &a);
+        line(&project(&dimension.end), &b);
+        line(&a, &b);
+
+        let direction = (b - a).xy().try_normalize(1e-9).unwrap_or_else(Vector2::x);
+
+        for &(tip, back) in &[(a, direction), (b, -direction)] {
+            for &side in &[ARROW_ANGLE, -ARROW_ANGLE] {
+                let (sin, cos) = side.sin_cos();
+                let arm = Vector2::new(back.x * cos - back.y * sin, back.x * sin + back.y * cos) * ARROW_LENGTH;
+                line(&tip, &(tip + Vector3::new(arm.x, arm.y, 0.0)));
+            }
+        }
+
+        let text = dimension.text();
+        let (width, _) = text_size(&text, dimension.text_scale);
+        let middle = (a + b) / 2.0;
+
+        draw_text(buffer, middle.x as i64 - width as i64 / 2, middle.y as i64 + 3, &text,
+                  dimension.color, dimension.text_scale);
+    }
+}
+
+
+/// Draw a line between two screen points, skipping the pixels where it is behind the depth buffer
+fn draw_depth_line(buffer: &mut image::RgbImage, zbuffer: &[f64], tolerance: f64,
+                   from: &Vector3<f64>, to: &Vector3<f64>, color: image::Rgb<u8>) {
+
+    let (width, height) = buffer.dimensions();
+    let steps = (to - from).xy().norm().ceil().max(1.0) as usize;
+
+    for step in 0..=steps {
+        let point = from + (to - from) * (step as f64 / steps as f64);
+        let (x, y) = (point.x.round(), point.y.round());
+
+        if x < 0.0 || y < 0.0 || x >= width as f64 || y >= height as f64 {
+            continue;
+        }
+
+        if point.z + tolerance >= zbuffer[(x as u32 + y as u32 * width) as usize] {
+            buffer.put_pixel(x as u32, y as u32, color);
+        }
+    }
+}
+
+
+/// The corner of the screen that an overlay is drawn in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Corner {
+    BottomLeft,
+    BottomRight,
+    TopLeft,
+    TopRight
+}
+
+
+/// Options that control the orientation gizmo drawn in a corner of the screen
+///
+/// The axes are drawn as red, green, and blue lines for x, y, and z, and the
+/// orientation cube as the faces of a cube turned like the scene, each marked
+/// with the axis it faces. The size is the width of the gizmo in pixels.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate rastermind;
+/// # use rastermind::overlay::*;
+/// let gizmo = GizmoOptions { cube: true, corner: Corner::TopRight, ..Default::default() };
+/// ```
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GizmoOptions {
+    pub axes: bool,
+    pub cube: bool,
+    pub corner: Corner,
+    pub size: u32
+}
+
+
+impl Default for GizmoOptions {
+    fn default() -> GizmoOptions {
+        GizmoOptions { axes: true, cube: false, corner: Corner::BottomLeft, size: 64 }
+    }
+}
+
+
+/// Draw the orientation gizmo of the camera with the given model view matrix
+///
+/// Only the rotation of the camera is shown, so the gizmo stays the same size
+/// wherever the camera is.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::overlay::*;
+/// # use rastermind::{overlay, shader};
+/// # let mut buffer = image::RgbImage::new(256, 256);
+/// # let (eye, center, up) = (Vector3::<f64>::new(0.0, 2.0, 5.0), Vector3::zeros(), Vector3::y());
+/// overlay::draw_gizmo(&mut buffer, &shader::lookat(&eye, &center, &up), &GizmoOptions::default());
+/// ```
+///
+pub fn draw_gizmo(buffer: &mut image::RgbImage, model_view: &Matrix4<f64>, options: &GizmoOptions) {
+    let size = options.size as f64;
+    let margin = size / 2.0 + 4.0;
+    let (width, height) = (buffer.width() as f64, buffer.height() as f64);
+
+    let center = match options.corner {
+        Corner::BottomLeft => Vector2::new(margin, margin),
+        Corner::BottomRight => Vector2::new(width - margin, margin),
+        Corner::TopLeft => Vector2::new(margin, height - margin),
+        Corner::TopRight => Vector2::new(width - margin, height - margin)
+    };
+
+    let rotation = model_view.fixed_slice::<U3, U3>(0, 0).into_owned();
+    let axes: Vec<Vector3<f64>> = (0..3).map(|i| rotation.column(i).into_owned()).collect();
+    let radius = size * 0.4;
+    let screen = |view: &Vector3<f64>| center + view.xy() * radius;
+
+    if options.cube {
+        let half = 0.6;
+
+        // only the faces that point towards the camera are drawn, so they never overlap
+        for (axis, direction) in (0..3).flat_map(|axis| vec![(axis, 1.0), (axis, -1.0)]) {
+            let normal = axes[axis] * direction;
+
+            if normal.z <= 0.0 {
+                continue;
+            }
+
+            let (u, v) = (axes[(axis + 1) % 3] * half, axes[(axis + 2) % 3] * half);
+            let middle = normal * half;
+            let corners = [middle - u - v, middle + u - v, middle + u + v, middle - u + v];
+            let points: Vec<Vector2<f64>> = corners.iter().map(&screen).collect();
+
+            let shade = (120.0 + 120.0 * normal.z) as u8;
+            fill_triangle(buffer, &[points[0], points[1], points[2]], image::Rgb([shade, shade, shade]));
+            fill_triangle(buffer, &[points[0], points[2], points[3]], image::Rgb([shade, shade, shade]));
+
+            for i in 0..4 {
+                draw_screen_line(buffer, &points[i], &points[(i + 1) % 4], image::Rgb([40, 40, 40]));
+            }
+
+            let label = screen(&middle);
+            let name = ["X", "Y", "Z"][axis];
+            let text = if direction > 0.0 { name.to_string() } else { format!("-{}", name) };
+            let (text_width, text_height) = text_size(&text, 1);
+            draw_text(buffer, label.x as i64 - text_width as i64 / 2, label.y as i64 - text_height as i64 / 2,
+                      &text, image::Rgb(AXIS_COLORS[axis]), 1);
+        }
+    }
+
+    if options.axes {
+        let mut order = [0, 1, 2];
+        order.sort_by(|&a, &b| axes[a].z.partial_cmp(&axes[b].z).unwrap());
+
+        // the axes pointing away from the camera are drawn first so the nearer ones cover them
+        for &axis in &order {
+            let tip = screen(&axes[axis]);
+            let color = image::Rgb(AXIS_COLORS[axis]);
+
+            draw_screen_line(buffer, &center, &tip, color);
+
+            let label = center + (tip - center) * 1.25;
+            draw_text(buffer, label.x as i64 - 1, label.y as i64 - 2, ["X", "Y", "Z"][axis], color, 1);
+        }
+    }
+}
+
+
+/// Draw a line between two screen points over everything in the buffer
+fn draw_screen_line(buffer: &mut image::RgbImage, from: &Vector2<f64>, to: &Vector2<f64>, color: image::Rgb<u8>) {
+    let steps = (to - from).norm().ceil().max(1.0) as usize;
+
+    for step in 0..=steps {
+        let point = from + (to - from) * (step as f64 / steps as f64);
+        let (x, y) = (point.x.round(), point.y.round());
+
+        if x >= 0.0 && y >= 0.0 && x < buffer.width() as f64 && y < buffer.height() as f64 {
+            buffer.put_pixel(x as u32, y as u32, color);
+        }
+    }
+}
+
+
+/// Fill a triangle of screen points over everything in the buffer
+fn fill_triangle(buffer: &mut image::RgbImage, points: &[Vector2<f64>; 3], color: image::Rgb<u8>) {
+    let minimum = points.iter().fold(points[0], |a, b| a.zip_map(b, f64::min));
+    let maximum = points.iter().fold(points[0], |a, b| a.zip_map(b, f64::max));
+    let edge = |a: &Vector2<f64>, b: &Vector2<f64>, p: &Vector2<f64>| (b - a).perp(&(p - a));
+    let area = edge(&points[0], &points[1], &points[2]);
+
+    if area == 0.0 {
+        return;
+    }
+
+    for y in minimum.y.max(0.0).floor() as u32..(maximum.y.ceil().max(0.0) as u32).min(buffer.height()) {
+        for x in minimum.x.max(0.0).floor() as u32..(maximum.x.ceil().max(0.0) as u32).min(buffer.width()) {
+            let p = Vector2::new(x as f64, y as f64);
+            let inside = (0..3).all(|i| edge(&points[i], &points[(i + 1) % 3], &p) * area.signum() >= 0.0);
+
+            if inside {
+                buffer.put_pixel(x, y, color);
+            }
+        }
+    }
+}
+
+
+/// Options that control the exposure analysis of a render
+///
+/// The false color view shows the luminance of the render in gray with the
+/// pixels at or below the shadow clip marked in blue and the pixels at or
+/// above the highlight clip marked in red. It replaces the render, or is
+/// placed to its right when shown side by side. The luminance histogram is
+/// drawn over the bottom right corner.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate rastermind;
+/// # use rastermind::overlay::*;
+/// # use rastermind::overlay;
+/// # let buffer = image::RgbImage::new(64, 64);
+/// let options = AnalysisOptions { side_by_side: true, ..Default::default() };
+///
+/// let analysis = overlay::analyze(&buffer, &options);
+/// ```
+///
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnalysisOptions {
+    pub histogram: bool,
+    pub false_color: bool,
+    pub side_by_side: bool,
+    pub shadow_clip: u8,
+    pub highlight_clip: u8
+}
+
+
+impl Default for AnalysisOptions {
+    fn default() -> AnalysisOptions {
+        AnalysisOptions { histogram: true, false_color: true, side_by_side: false,
+                          shadow_clip: 2, highlight_clip: 253 }
+    }
+}
+
+
+/// Draw the exposure analysis of a render into a new image
+pub fn analyze(buffer: &image::RgbImage, options: &AnalysisOptions) -> image::RgbImage {
+    let (width, height) = buffer.dimensions();
+    let view = if options.false_color { false_color(buffer, options) } else { buffer.clone() };
+
+    let mut analysis = if options.side_by_side {
+        image::ImageBuffer::from_fn(width * 2, height, |x, y| {
+            if x < width { *buffer.get_pixel(x, y) } else { *view.get_pixel(x - width, y) }
+        })
+    } else {
+        view
+    };
+
+    if options.histogram {
+        let histogram = luminance_histogram(buffer);
+        let (panel_width, panel_height) = (width.min(256) / 2 * 2, (height / 4).max(1));
+
+        let x = analysis.width() - panel_width;
+        draw_histogram(&mut analysis, &histogram, x, 0, panel_width, panel_height);
+    }
+
+    analysis
+}
+
+
+/// Find the perceived brightness of an sRGB color in [0, 255]
+pub fn luminance(pixel: &image::Rgb<u8>) -> u8 {
+    (0.2126 * pixel[0] as f64 + 0.7152 * pixel[1] as f64 + 0.0722 * pixel[2] as f64).round() as u8
+}
+
+
+/// Count the pixels of the render at every luminance
+pub fn luminance_histogram(buffer: &image::RgbImage) -> [u32; 256] {
+    let mut histogram = [0; 256];
+
+    for pixel in buffer.pixels() {
+        histogram[luminance(pixel) as usize] += 1;
+    }
+
+    histogram
+}
+
+
+/// Show the luminance of the render in gray with the clipped shadows in blue and the clipped highlights in red
+pub fn false_color(buffer: &image::RgbImage, options: &AnalysisOptions) -> image::RgbImage {
+    image::ImageBuffer::from_fn(buffer.width(), buffer.height(), |x, y| {
+        let luminance = luminance(buffer.get_pixel(x, y));
+
+        if luminance <= options.shadow_clip {
+            image::Rgb([0, 0, 255])
+        } else if luminance >= options.highlight_clip {
+            image::Rgb([255, 0, 0])
+        } else {
+            image::Rgb([luminance, luminance, luminance])
+        }
+    })
+}
+
+
+/// Draw a histogram as bars on a dark panel with the given lower left corner and size
+///
+/// The bars are scaled so the fullest bin reaches the top of the panel, and
+/// the first and last bins, which hold the clipped pixels, are drawn in the
+/// colors of the false color view.
+///
+pub fn draw_histogram(buffer: &mut image::RgbImage, histogram: &[u32; 256], x: u32, y: u32,
+                      width: u32, height: u32) {
+
+    fill_rectangle(buffer, x as i64, y as i64, width as i64, height as i64, image::Rgb([32, 32, 32]));
+
+    let fullest = *histogram.iter().max().unwrap_or(&0);
+
+    if fullest == 0 || width == 0 {
+        return;
+    }
+
+    for column in 0..width {
+        let first = (column * 256 / width) as usize;
+        let last = (((column + 1) * 256 / width) as usize).max(first + 1);
+        let count = histogram[first..last].iter().max().cloned().unwrap_or(0);
+        // bins with any pixels are always visible so a few clipped pixels are not missed
+        let bar = (count as f64 / fullest as f64 * height as f64).round().max(count.min(1) as f64) as i64;
+
+        let color = match (first, last) {
+            (0, _) => image::Rgb([0, 0, 255]),
+            (_, 256) => image::Rgb([255, 0, 0]),
+            _ => image::Rgb([220, 220, 220])
+        };
+
+        fill_rectangle(buffer, (x + column) as i64, y as i64, 1, bar, color);
+    }
+}
+
+
+/// Find the size in pixels of the given text drawn at the given scale
+pub fn text_size(text: &str, scale: u32) -> (u32, u32) {
+    let characters = text.chars().count() as u32;
+
+    ((characters * 4).saturating_sub(1) * scale, 5 * scale)
+}
+
+
+/// Draw text with its lower left corner at the given pixel, where every pixel of the font is a square of scale pixels
+///
+/// Digits, lowercase letters, the names of the axes, and common punctuation
+/// are drawn, other capital letters are drawn as lowercase, and other
+/// characters are left as spaces.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate rastermind;
+/// # use rastermind::overlay;
+/// # let mut buffer = image::RgbImage::new(128, 64);
+/// overlay::draw_text(&mut buffer, 10, 10, "12.5 mm", image::Rgb([255, 255, 255]), 2);
+/// ```
+///
+pub fn draw_text(buffer: &mut image::RgbImage, x: i64, y: i64, text: &str,
+                 color: image::Rgb<u8>, scale: u32) {
+
+    let scale = scale.max(1) as i64;
+
+    for (index, character) in text.chars().enumerate() {
+        let glyph = GLYPHS.iter().find(|&&(glyph, _)| glyph == character)
+                          .or_else(|| GLYPHS.iter().find(|&&(glyph, _)| glyph == character.to_ascii_lowercase()));
+
+        let glyph = match glyph {
+            Some(&(_, rows)) => rows,
+            None => continue
+        };
+
+        for (row, bits) in glyph.iter().enumerate() {
+            for column in 0..3 {
+                if bits & (0b100 >> column) == 0 {
+                    continue;
+                }
+
+                let left = x + (index as i64 * 4 + column) * scale;
+                // the buffer is stored with its first row at the bottom
+                let bottom = y + (4 - row as i64) * scale;
+
+                fill_rectangle(buffer, left, bottom, scale, scale, color);
+            }
+        }
+    }
+}
+
+
+/// Fill the rectangle with the given lower left corner and size, clipped to the buffer
+fn fill_rectangle(buffer: &mut image::RgbImage, x: i64, y: i64, width: i64, height: i64,
+                  color: image::Rgb<u8>) {
+
+    for j in y.max(0)..(y + height).min(buffer.height() as i64) {
+        for i in x.max(0)..(x + width).min(buffer.width() as i64) {
+            buffer.put_pixel(i as u32, j as u32, color);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shader;
+
+    #[test]
+    fn test_draw_text() {
+        let mut buffer = image::RgbImage::new(16, 8);
+        let white = image::Rgb([255, 255, 255]);
+
+        draw_text(&mut buffer, 1, 1, "1?7", white, 1);
+
+        assert_eq!(text_size("1?7", 2), (22, 10));
+        // the top of the one is its middle column and the bottom of the seven is its right column
+        assert_eq!(*buffer.get_pixel(2, 5), white);
+        assert_eq!(*buffer.get_pixel(1, 5), image::Rgb([0, 0, 0]));
+        assert_eq!(*buffer.get_pixel(11, 1), white);
+        assert_eq!(*buffer.get_pixel(9, 1), image::Rgb([0, 0, 0]));
+        assert!((5..8).all(|x| (0..8).all(|y| buffer.get_pixel(x, y)[0] == 0)));
+    }
+
+    #[test]
+    fn test_draw_dimensions() {
+        let model_view = shader::lookat(&Vector3::new(0.0, 0.0, 5.0), &Vector3::zeros(), &Vector3::y());
+        let projection = shader::projection(-1.0 / 5.0);
+        let view_port = shader::viewport(8, 8, 48, 48, 255);
+
+        let mut buffer = image::RgbImage::from_pixel(64, 64, image::Rgb([255, 255, 255]));
+        let mut zbuffer = vec![-1.0; 64 * 64];
+
+        // a wall in front of the right half of the dimension line
+        for y in 0..64 {
+            for x in 40..64 {
+                zbuffer[x + y * 64] = 255.0;
+            }
+        }
+
+        let dimension = Dimension::new(Vector3::new(-1.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0),
+                                       Vector3::new(0.0, -0.5, 0.0));
+        assert_eq!(dimension.text(), "2.00");
+
+        draw_dimensions(&[dimension], &mut buffer, &zbuffer, 255, &view_port, &projection, &model_view);
+
+        let black = image::Rgb([0, 0, 0]);
+        assert_eq!(*buffer.get_pixel(20, 20), black);
+        assert_eq!(*buffer.get_pixel(8, 26), black);
+        assert_eq!(*buffer.get_pixel(8, 32), black);
+        assert_eq!(*buffer.get_pixel(45, 20), image::Rgb([255, 255, 255]));
+        assert!((23..32).any(|y| (20..44).any(|x| *buffer.get_pixel(x, y) == black)));
+    }
+
+    #[test]
+    fn test_draw_gizmo() {
+        // looking down the z axis x points right and y points up
+        let model_view = shader::lookat(&Vector3::new(0.0, 0.0, 5.0), &Vector3::zeros(), &Vector3::y());
+        let mut buffer = image::RgbImage::new(100, 100);
+
+        draw_gizmo(&mut buffer, &model_view, &GizmoOptions::default());
+
+        // the gizmo is centered 36 pixels from the bottom left corner with axes 25.6 pixels long
+        assert_eq!(*buffer.get_pixel(50, 36), image::Rgb(AXIS_COLORS[0]));
+        assert_eq!(*buffer.get_pixel(36, 50), image::Rgb(AXIS_COLORS[1]));
+        assert_eq!(*buffer.get_pixel(80, 80), image::Rgb([0, 0, 0]));
+
+        let cube = GizmoOptions { axes: false, cube: true, corner: Corner::TopRight, ..Default::default() };
+        let mut buffer = image::RgbImage::new(100, 100);
+
+        draw_gizmo(&mut buffer, &model_view, &cube);
+
+        // only the face towards the camera is visible, filled in the lightest shade
+        assert_eq!(*buffer.get_pixel(72, 72), image::Rgb([240, 240, 240]));
+        assert_eq!(*buffer.get_pixel(20, 20), image::Rgb([0, 0, 0]));
+    }
+
+    #[test]
+    fn test_analyze() {
+        let mut buffer = image::RgbImage::from_pixel(64, 64, image::Rgb([128, 128, 128]));
+
+        for x in 0..16 {
+            buffer.put_pixel(x, 63, image::Rgb([255, 255, 255]));
+            buffer.put_pixel(x, 40, image::Rgb([0, 0, 0]));
+        }
+
+        let histogram = luminance_histogram(&buffer);
+        assert_eq!((histogram[0], histogram[128], histogram[255]), (16, 64 * 64 - 32, 16));
+
+        let false_colored = analyze(&buffer, &AnalysisOptions { histogram: false, ..Default::default() });
+        assert_eq!(*false_colored.get_pixel(0, 63), image::Rgb([255, 0, 0]));
+        assert_eq!(*false_colored.get_pixel(0, 40), image::Rgb([0, 0, 255]));
+        assert_eq!(*false_colored.get_pixel(32, 32), image::Rgb([128, 128, 128]));
+
+        let beside = analyze(&buffer, &AnalysisOptions { side_by_side: true, ..Default::default() });
+        assert_eq!(beside.dimensions(), (128, 64));
+        assert_eq!(*beside.get_pixel(0, 63), image::Rgb([255, 255, 255]));
+        assert_eq!(*beside.get_pixel(64, 63), image::Rgb([255, 0, 0]));
+
+        // the histogram panel fills the bottom right corner with the fullest bin reaching its top
+        assert_eq!(*beside.get_pixel(127, 20), image::Rgb([128, 128, 128]));
+        assert_eq!(*beside.get_pixel(96, 15), image::Rgb([220, 220, 220]));
+        assert_eq!(*beside.get_pixel(64, 0), image::Rgb([0, 0, 255]));
+        assert_eq!(*beside.get_pixel(100, 15), image::Rgb([32, 32, 32]));
+    }
+}
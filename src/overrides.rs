@@ -0,0 +1,202 @@
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use error::MorpheusError;
+
+
+/// The prefix of the environment variables that hold an override each
+pub const ENVIRONMENT_PREFIX: &str = "MORPHEUS_SET";
+
+
+/// One step of the path of an override, into a field of an object or an element of an array
+#[derive(Clone, Debug, PartialEq)]
+enum Key {
+    Field(String),
+    Index(usize)
+}
+
+
+/// Split a path such as objects[0].shader into the fields and indices it steps through
+fn parse_path(path: &str) -> Result<Vec<Key>, String> {
+    let mut keys = Vec::new();
+
+    for segment in path.split('.') {
+        let (name, mut indices) = segment.split_at(segment.find('[').unwrap_or(segment.len()));
+
+        if name.is_empty() {
+            return Err(format!("'{}' has an empty field name", path));
+        }
+
+        keys.push(Key::Field(name.to_string()));
+
+        while !indices.is_empty() {
+            let end = indices.find(']').ok_or_else(|| format!("'{}' has an unclosed index", path))?;
+            let index = indices[1..end].trim().parse().map_err(|_| format!("'{}' has an invalid index", path))?;
+
+            keys.push(Key::Index(index));
+            indices = &indices[end + 1..];
+
+            if !indices.is_empty() && !indices.starts_with('[') {
+                return Err(format!("'{}' has text after an index", path));
+            }
+        }
+    }
+
+    Ok(keys)
+}
+
+
+/// Override a parameter of the given JSON document with an assignment of the form path=value
+///
+/// The path steps into the fields of objects with dots and into the elements
+/// of arrays with indices in brackets, and fields that are missing along the
+/// way are created. The value is parsed as JSON, and a value that is not
+/// valid JSON is taken as a string, so names do not need quotes.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate rastermind;
+/// # extern crate serde_json;
+/// # use std::fs;
+/// # use rastermind::overrides;
+/// let mut request = serde_json::from_str(&fs::read_to_string("request.json").unwrap()).unwrap();
+///
+/// overrides::apply(&mut request, "eye=[0, 10, 50]").unwrap();
+/// overrides::apply(&mut request, "post.exposure=0.5").unwrap();
+/// ```
+///
+pub fn apply(document: &mut Value, assignment: &str) -> Result<(), MorpheusError> {
+    assign(document, assignment).map_err(MorpheusError::Scene)
+}
+
+
+fn assign(document: &mut Value, assignment: &str) -> Result<(), String> {
+    let (path, value) = assignment.split_once('=')
+                                  .ok_or_else(|| format!("'{}' is not of the form path=value", assignment))?;
+    let mut target = document;
+
+    for key in parse_path(path.trim())? {
+        if target.is_null() {
+            *target = match key {
+                Key::Field(_) => Value::Object(Map::new()),
+                Key::Index(_) => Value::Array(Vec::new())
+            };
+        }
+
+        target = match (key, target) {
+            (Key::Field(name), Value::Object(fields)) => fields.entry(name).or_insert(Value::Null),
+            (Key::Index(index), Value::Array(elements)) => {
+                let length = elements.len();
+
+                elements.get_mut(index).ok_or_else(|| {
+                    format!("index {} of '{}' is past the end of an array of {}", index, path, length)
+                })?
+            }
+            (Key::Field(name), _) => return Err(format!("'{}' steps into the field {} of a non object", path, name)),
+            (Key::Index(index), _) => return Err(format!("'{}' steps into the index {} of a non array", path, index))
+        };
+    }
+
+    *target = serde_json::from_str(value.trim()).unwrap_or_else(|_| Value::String(value.trim().to_string()));
+
+    Ok(())
+}
+
+
+/// Parse the given JSON with every assignment applied to it in order
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate rastermind;
+/// # use std::fs;
+/// # use rastermind::{overrides, service};
+/// let assignments = vec![String::from("scene.camera.eye=[0, 10, 50]"), String::from("scene.width=512")];
+/// let request: service::Request = overrides::parse(&fs::read_to_string("request.json").unwrap(), &assignments)
+///                                           .unwrap();
+/// ```
+///
+pub fn parse<T: DeserializeOwned>(json: &str, assignments: &[String]) -> Result<T, MorpheusError> {
+    let invalid = |error: serde_json::Error| MorpheusError::Scene(error.to_string());
+    let mut document: Value = serde_json::from_str(json).map_err(invalid)?;
+
+    for assignment in assignments {
+        apply(&mut document, assignment)?;
+    }
+
+    serde_json::from_value(document).map_err(invalid)
+}
+
+
+/// Find the assignments held in the given environment variables
+///
+/// Every variable whose name starts with MORPHEUS_SET holds one assignment,
+/// such as MORPHEUS_SET_EYE="scene.camera.eye=[0, 10, 50]", and the
+/// assignments are sorted by the names of their variables so they apply in a
+/// stable order.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate rastermind;
+/// # use std::env;
+/// # use rastermind::overrides;
+/// # let command_line_assignments = vec![String::from("scene.width=512")];
+/// let mut assignments = overrides::from_environment(env::vars());
+/// assignments.extend(command_line_assignments);
+/// ```
+///
+pub fn from_environment<I: IntoIterator<Item = (String, String)>>(variables: I) -> Vec<String> {
+    let mut variables: Vec<(String, String)> = variables.into_iter()
+                                                        .filter(|(name, _)| name.starts_with(ENVIRONMENT_PREFIX))
+                                                        .collect();
+    variables.sort();
+
+    variables.into_iter().map(|(_, assignment)| assignment).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let mut document: Value = serde_json::from_str(r#"{"eye": [0, 0, 3], "objects": [{"shader": "phong"}]}"#)
+                                                 .unwrap();
+
+        apply(&mut document, "eye=[0,10,50]").unwrap();
+        apply(&mut document, "objects[0].shader=cel").unwrap();
+        apply(&mut document, "camera.fov = 45").unwrap();
+        apply(&mut document, r#"objects[0].name="10""#).unwrap();
+
+        assert_eq!(document["eye"], serde_json::json!([0, 10, 50]));
+        assert_eq!(document["objects"][0]["shader"], "cel");
+        assert_eq!(document["camera"]["fov"], 45);
+        assert_eq!(document["objects"][0]["name"], "10");
+
+        assert!(apply(&mut document, "objects[3].shader=cel").is_err());
+        assert!(apply(&mut document, "eye.x=1").is_err());
+        assert!(apply(&mut document, "objects[0=1").is_err());
+        assert!(apply(&mut document, "eye").is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        #[derive(Deserialize)]
+        struct Parameters { a: Vec<i32>, b: String }
+
+        let assignments = vec![String::from("a[1]=7"), String::from("b=text")];
+        let parameters: Parameters = parse(r#"{"a": [1, 2]}"#, &assignments).unwrap();
+        assert_eq!((parameters.a, parameters.b), (vec![1, 7], String::from("text")));
+    }
+
+    #[test]
+    fn test_from_environment() {
+        let variables = [("MORPHEUS_SET_B", "b=2"), ("PATH", "/bin"), ("MORPHEUS_SET_A", "a=1")];
+        let variables = variables.iter().map(|&(name, value)| (name.to_string(), value.to_string()));
+
+        assert_eq!(from_environment(variables), vec!["a=1", "b=2"]);
+    }
+}
@@ -0,0 +1,229 @@
+use std::fs;
+use std::time::SystemTime;
+
+use nalgebra::Vector3;
+
+use error::MorpheusError;
+use plugins;
+use render;
+use shader;
+use shader::Shader;
+use wavefront;
+
+
+/// The shader of a render and the parameters of its look, read from a TOML file
+///
+/// The shader is one of gouraud, cel, flat, color, and normal, or the name
+/// of a shader registered by a plugin. The bands and shadow are the light of
+/// the cel shader, the exponent raises the light of the gouraud shader, and
+/// the color and lit flag are those of the color shader. Parameters left out
+/// of the file keep their defaults, which match the shaders' own.
+///
+/// # Examples
+///
+/// ```toml
+/// shader = "cel"
+/// bands = [[0.9, 1.0], [0.4, 0.6]]
+/// shadow = 0.15
+/// ```
+///
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShaderParameters {
+    pub shader: String,
+    pub bands: Vec<(f64, f64)>,
+    pub shadow: f64,
+    pub exponent: f64,
+    pub color: [u8; 3],
+    pub lit: bool
+}
+
+
+impl Default for ShaderParameters {
+    fn default() -> ShaderParameters {
+        ShaderParameters { shader: String::from("gouraud"), bands: shader::CEL_BANDS.to_vec(), shadow: 0.20,
+                           exponent: 1.0, color: [255, 255, 255], lit: true }
+    }
+}
+
+
+impl ShaderParameters {
+    /// Read the parameters from the given TOML, failing on unknown keys, shaders, and exponents that are not positive
+    pub fn parse(toml: &str) -> Result<ShaderParameters, MorpheusError> {
+        let parameters: ShaderParameters = toml::from_str(toml).map_err(|error| {
+            MorpheusError::Scene(error.to_string())
+        })?;
+
+        if !(parameters.exponent > 0.0 && parameters.exponent.is_finite()) {
+            return Err(MorpheusError::Scene(format!("the exponent must be positive, not {}", parameters.exponent)));
+        }
+
+        parameters.shader()?;
+
+        Ok(parameters)
+    }
+
+    /// Create a new shader with the parameters
+    pub fn shader(&self) -> Result<Box<dyn Shader>, MorpheusError> {
+        match self.shader.as_str() {
+            "gouraud" => Ok(Box::new(shader::GouraudShader::new().with_exponent(self.exponent))),
+            "cel" => Ok(Box::new(shader::CelShader::new().with_bands(self.bands.clone(), self.shadow))),
+            "flat" => Ok(Box::new(shader::FlatShader::new())),
+            "color" => Ok(Box::new(shader::ColorShader::new(image::Rgb(self.color), self.lit))),
+            "normal" => Ok(Box::new(shader::NormalShader::new())),
+            name => plugins::shader(name).ok_or_else(|| MorpheusError::Scene(format!("unknown shader {}", name)))
+        }
+    }
+
+    /// Draw an already loaded Object with a new shader for every face
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_object(&self, coordinates: &wavefront::Object, buffer: &mut image::RgbImage,
+                       texture: &image::RgbImage, depth: u32,
+                       light_vector: &Vector3<f64>, eye: &Vector3<f64>,
+                       center: &Vector3<f64>, up: &Vector3<f64>) -> render::RenderStats {
+
+        let mut zbuffer = vec![-1.0; (buffer.width() * buffer.height() + 1) as usize];
+
+        let model_view = shader::lookat(eye, center, up);
+        let projection = shader::projection(-1.0 / (eye - center).norm());
+        let view_port = shader::viewport(buffer.width() / 8, buffer.height() / 8,
+                                         buffer.width() * 3 / 4, buffer.height() * 3 / 4,
+                                         depth);
+
+        // the shader was checked when the parameters were read, so only a plugin that went away falls back
+        render::draw_faces(coordinates, buffer, texture, &mut zbuffer, &render::DepthOptions::default(),
+                           &view_port, &projection, &model_view, light_vector, |_| {
+            self.shader().unwrap_or_else(|_| Box::new(shader::GouraudShader::new()))
+        })
+    }
+}
+
+
+/// A file of shader parameters that is read again whenever it changes
+///
+/// Previews check the file between frames, so the look of a model can be
+/// tweaked in an editor without restarting the preview. A file that fails
+/// to parse keeps the parameters last read from it.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::parameters::ParametersFile;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let (eye, center, up) = (Vector3::<f64>::new(0.0, 0.0, 3.0), Vector3::<f64>::zeros(), Vector3::<f64>::y());
+/// # let light_vector = Vector3::<f64>::z();
+/// # let mut buffer = image::RgbImage::new(512, 512);
+/// let mut watched = ParametersFile::open("look.toml")?;
+///
+/// loop {
+///     if watched.reload()? {
+///         watched.parameters.draw_object(&coordinates, &mut buffer, &texture, 255, &light_vector,
+///                                        &eye, &center, &up);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+pub struct ParametersFile {
+    pub path: String,
+    pub parameters: ShaderParameters,
+    modified: Option<SystemTime>
+}
+
+
+impl ParametersFile {
+    /// Read the parameters from the given file
+    pub fn open(path: &str) -> Result<ParametersFile, MorpheusError> {
+        let modified = modified(path);
+        let toml = fs::read_to_string(path).map_err(|error| MorpheusError::io(path, error))?;
+
+        Ok(ParametersFile { path: path.to_string(), parameters: ShaderParameters::parse(&toml)?, modified })
+    }
+
+    /// Read the parameters again if the file was modified since it was last read, returning whether they changed
+    pub fn reload(&mut self) -> Result<bool, MorpheusError> {
+        let modified = modified(&self.path);
+
+        if modified == self.modified {
+            return Ok(false);
+        }
+
+        // a file that fails to parse is not read again until it is saved again
+        self.modified = modified;
+
+        let toml = fs::read_to_string(&self.path).map_err(|error| MorpheusError::io(&self.path, error))?;
+        let parameters = ShaderParameters::parse(&toml)?;
+        let changed = parameters != self.parameters;
+
+        self.parameters = parameters;
+
+        Ok(changed)
+    }
+}
+
+
+/// The time the given file was last modified, or None if it cannot be read
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs::File;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse() {
+        let parameters = ShaderParameters::parse("shader = \"cel\"\nbands = [[0.9, 1.0], [0.4, 0.6]]\n").unwrap();
+
+        // parameters left out keep their defaults
+        assert_eq!(parameters.bands, vec![(0.9, 1.0), (0.4, 0.6)]);
+        assert_eq!(parameters.shadow, 0.20);
+        assert_eq!(ShaderParameters::parse("").unwrap(), ShaderParameters::default());
+
+        assert!(ShaderParameters::parse("shader = \"missing\"").is_err());
+        assert!(ShaderParameters::parse("exponent = 0.0").is_err());
+        assert!(ShaderParameters::parse("colour = [1, 2, 3]").is_err());
+    }
+
+    #[test]
+    fn test_reload() {
+        let mut path = env::temp_dir();
+        path.push("morpheus_parameters.toml");
+        let filename = path.to_str().unwrap();
+        let touch = |seconds: u64| {
+            File::options().write(true).open(filename).unwrap()
+                           .set_modified(SystemTime::now() + Duration::from_secs(seconds)).unwrap();
+        };
+
+        fs::write(filename, "exponent = 2.0\n").unwrap();
+
+        let mut watched = ParametersFile::open(filename).unwrap();
+        assert_eq!(watched.parameters.exponent, 2.0);
+        assert!(!watched.reload().unwrap());
+
+        fs::write(filename, "exponent = 4.0\n").unwrap();
+        touch(10);
+        assert!(watched.reload().unwrap());
+        assert_eq!(watched.parameters.exponent, 4.0);
+
+        // a broken file keeps the parameters read last
+        fs::write(filename, "exponent = \n").unwrap();
+        touch(20);
+        assert!(watched.reload().is_err());
+        assert_eq!(watched.parameters.exponent, 4.0);
+        assert!(!watched.reload().unwrap());
+    }
+}
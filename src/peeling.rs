@@ -0,0 +1,135 @@
+use post;
+
+
+/// One layer of surfaces peeled off a render, with the opacity of its surfaces in the alpha of its colors
+///
+/// The depth holds the depth buffer of the layer, where larger values are
+/// nearer and pixels without a surface in the layer are -1, so the depths of
+/// all the layers together are a layered depth image of the render.
+///
+pub struct Layer {
+    pub color: image::RgbaImage,
+    pub depth: Vec<f64>
+}
+
+
+impl Layer {
+    /// Whether no surface was drawn into the layer, which means every layer behind it is empty too
+    pub fn is_empty(&self) -> bool {
+        self.depth.iter().all(|&depth| depth == -1.0)
+    }
+}
+
+
+/// Peel up to the given number of layers of surfaces off a render, nearest first
+///
+/// The draw function draws a layer behind the depth buffer it is given, and
+/// is given a buffer of infinite depths for the first layer, so the first
+/// layer holds the nearest surfaces, the second the surfaces just behind
+/// them, and so on. Peeling stops early once a layer is empty. Every layer
+/// is a full render of its own, so peeling is exact but costs a render per
+/// layer.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate rastermind;
+/// # use rastermind::peeling::*;
+/// # let (width, height, scene) = (8, 8, ());
+/// # let background = image::RgbImage::new(width, height);
+/// # fn render_layer(_: &(), behind: &[f64]) -> Layer {
+/// #     Layer { color: image::RgbaImage::new(8, 8), depth: vec![std::f64::INFINITY; behind.len()] }
+/// # }
+/// let layers = peel(width, height, 4, |behind| render_layer(&scene, behind));
+/// let image = composite(&layers, &background);
+/// ```
+///
+pub fn peel<F>(width: u32, height: u32, layers: usize, mut draw: F) -> Vec<Layer> where F: FnMut(&[f64]) -> Layer {
+    let mut behind = vec![f64::INFINITY; (width * height) as usize];
+    let mut peeled = Vec::new();
+
+    for _ in 0..layers {
+        let layer = draw(&behind);
+
+        if layer.is_empty() {
+            break;
+        }
+
+        behind.copy_from_slice(&layer.depth);
+        peeled.push(layer);
+    }
+
+    peeled
+}
+
+
+/// Blend the layers over the given background from the furthest to the nearest
+///
+/// The colors are blended in linear light by the opacity of every layer, so
+/// the result is the same whatever order the surfaces were drawn in.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate rastermind;
+/// # use rastermind::peeling::*;
+/// # let layers = Vec::new();
+/// let image = composite(&layers, &image::RgbImage::from_pixel(512, 512, image::Rgb([255, 255, 255])));
+/// ```
+///
+pub fn composite(layers: &[Layer], background: &image::RgbImage) -> image::RgbImage {
+    image::ImageBuffer::from_fn(background.width(), background.height(), |x, y| {
+        let background = background.get_pixel(x, y);
+        let mut color = [0, 1, 2].map(|i| post::to_linear(background[i]));
+
+        for layer in layers.iter().rev() {
+            let pixel = layer.color.get_pixel(x, y);
+            let alpha = pixel[3] as f64 / 255.0;
+
+            (0..=2).for_each(|i| color[i] = post::to_linear(pixel[i]) * alpha + color[i] * (1.0 - alpha));
+        }
+
+        image::Rgb(color.map(post::to_srgb))
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peel() {
+        // three overlapping surfaces at depths 3, 2, and 1 over the left half of a 2x1 image
+        let surfaces = [(3.0, [255, 0, 0, 128]), (1.0, [0, 0, 255, 255]), (2.0, [0, 255, 0, 128])];
+
+        let mut draws = 0;
+        let layers = peel(2, 1, 8, |behind| {
+            let mut layer = Layer { color: image::RgbaImage::new(2, 1), depth: vec![-1.0; 2] };
+            draws += 1;
+
+            for &(depth, color) in &surfaces {
+                if depth < behind[0] && depth > layer.depth[0] {
+                    layer.depth[0] = depth;
+                    layer.color.put_pixel(0, 0, image::Rgba(color));
+                }
+            }
+
+            layer
+        });
+
+        // the layers are peeled nearest first, and peeling stops at the first empty layer
+        assert_eq!(layers.iter().map(|layer| layer.depth[0]).collect::<Vec<f64>>(), vec![3.0, 2.0, 1.0]);
+        assert_eq!(draws, 4);
+
+        let background = image::RgbImage::from_pixel(2, 1, image::Rgb([255, 255, 255]));
+        let image = composite(&layers, &background);
+
+        // the opaque blue at the back hides the background, and the translucent layers tint it
+        assert_eq!(*image.get_pixel(1, 0), image::Rgb([255, 255, 255]));
+        assert_eq!(image.get_pixel(0, 0)[2], post::to_srgb(post::to_linear(255) * (1.0 - 128.0f64 / 255.0).powi(2)));
+        assert!(image.get_pixel(0, 0)[0] > image.get_pixel(0, 0)[1]);
+    }
+}
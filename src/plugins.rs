@@ -0,0 +1,568 @@
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_void};
+use std::path::Path;
+use std::slice;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+
+use nalgebra::{Matrix2x3, Matrix3, Matrix4, Vector2, Vector3, Vector4};
+
+use cache;
+use collada;
+use error::MorpheusError;
+use fbx;
+use remote;
+use shader;
+use shader::Shader;
+use texture;
+use vector;
+use wavefront;
+
+
+/// A function that parses the contents of a model file into an Object
+pub type Loader = dyn Fn(&[u8]) -> Result<wavefront::Object, String> + Send + Sync;
+
+/// A function that creates a new shader for every face drawn with it
+pub type ShaderFactory = dyn Fn() -> Box<dyn Shader> + Send + Sync;
+
+
+/// The version of the C interface of plugins, which plugins check before they register anything
+pub const PLUGIN_VERSION: u32 = 1;
+
+/// The name of the function every plugin library exports to register its loaders and shaders
+pub const PLUGIN_ENTRY: &[u8] = b"morpheus_plugin_register\0";
+
+
+/// The loaders and shaders added by plugins, by file extension and by name
+///
+/// The shaders of the crate are registered under their names from the start,
+/// so they are chosen by name like the shaders of plugins and can be replaced,
+/// as are the loaders of the formats the crate reads besides wavefront files.
+///
+struct Registry {
+    loaders: HashMap<String, Arc<Loader>>,
+    shaders: HashMap<String, Arc<ShaderFactory>>
+}
+
+
+impl Default for Registry {
+    fn default() -> Registry {
+        let mut shaders: HashMap<String, Arc<ShaderFactory>> = HashMap::new();
+
+        shaders.insert(String::from("gouraud"), Arc::new(|| Box::new(shader::GouraudShader::new())));
+        shaders.insert(String::from("flat"), Arc::new(|| Box::new(shader::FlatShader::new())));
+        shaders.insert(String::from("cel"), Arc::new(|| Box::new(shader::CelShader::new())));
+        shaders.insert(String::from("normal"), Arc::new(|| Box::new(shader::NormalShader::new())));
+
+        let mut loaders: HashMap<String, Arc<Loader>> = HashMap::new();
+
+        loaders.insert(String::from("dae"), Arc::new(|bytes| collada::parse(bytes).map(|mesh| mesh.object)));
+        loaders.insert(String::from("fbx"), Arc::new(|bytes| fbx::parse(bytes).map(|mesh| mesh.object)));
+
+        Registry { loaders, shaders }
+    }
+}
+
+
+static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+
+
+fn registry() -> MutexGuard<'static, Registry> {
+    REGISTRY.get_or_init(Default::default).lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+
+/// Add a loader for model files with the given extension, replacing any loader already registered for it
+///
+/// Extensions are matched without their dot and regardless of case.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate rastermind;
+/// # use rastermind::plugins;
+/// # mod ply {
+/// #     pub fn parse(_: &[u8]) -> Result<rastermind::wavefront::Object, rastermind::Error> { unimplemented!() }
+/// # }
+/// plugins::register_loader("ply", |bytes| ply::parse(bytes).map_err(|error| error.to_string()));
+///
+/// let coordinates = plugins::open("bunny.ply").unwrap();
+/// ```
+///
+pub fn register_loader<F>(extension: &str, loader: F)
+    where F: Fn(&[u8]) -> Result<wavefront::Object, String> + Send + Sync + 'static {
+
+    registry().loaders.insert(extension.trim_start_matches('.').to_lowercase(), Arc::new(loader));
+}
+
+
+/// Add a shader under the given name, replacing any shader already registered under it
+///
+/// # Examples
+///
+/// ```
+/// # use rastermind::plugins;
+/// # use rastermind::shader::CelShader;
+/// plugins::register_shader("toon", || Box::new(CelShader::new()));
+///
+/// let shader = plugins::shader("toon").unwrap();
+/// ```
+///
+pub fn register_shader<F>(name: &str, factory: F) where F: Fn() -> Box<dyn Shader> + Send + Sync + 'static {
+    registry().shaders.insert(name.to_string(), Arc::new(factory));
+}
+
+
+/// Load a model with the loader registered for the extension of its file, or as a wavefront file without one
+///
+/// Wavefront files are loaded through the cache, so a scene or a service
+/// that opens the same file again only parses it once. Models at http and
+/// https URLs are downloaded by builds with the http feature.
+///
+pub fn open(filename: &str) -> Result<wavefront::Object, MorpheusError> {
+    let extension = Path::new(remote::file_part(filename)).extension().and_then(|extension| extension.to_str())
+                                       .map(str::to_lowercase).unwrap_or_default();
+    let loader = registry().loaders.get(&extension).cloned();
+
+    match loader {
+        Some(loader) => {
+            let bytes = if remote::is_url(filename) {
+                remote::fetch(filename)?
+            } else {
+                std::fs::read(filename).map_err(|error| MorpheusError::io(filename, error))?
+            };
+
+            loader(&bytes).map_err(|message| MorpheusError::Asset { path: filename.to_string(), message })
+        }
+        None => cache::mesh(filename, &wavefront::LoadOptions::default()).map(|mesh| (*mesh).clone())
+    }
+}
+
+
+/// Create a new shader registered under the given name, or None if neither the crate nor a plugin registered it
+pub fn shader(name: &str) -> Option<Box<dyn Shader>> {
+    let factory = registry().shaders.get(name).cloned();
+
+    factory.map(|factory| factory())
+}
+
+
+/// The extensions with a registered loader and the names of the registered shaders, both sorted
+pub fn registered() -> (Vec<String>, Vec<String>) {
+    let registry = registry();
+    let mut loaders: Vec<String> = registry.loaders.keys().cloned().collect();
+    let mut shaders: Vec<String> = registry.shaders.keys().cloned().collect();
+
+    loaders.sort();
+    shaders.sort();
+
+    (loaders, shaders)
+}
+
+
+/// A mesh passed across the C interface by a loader
+///
+/// Positions and normals are packed as x, y, z triples and texture
+/// coordinates as u, v pairs. Every face is nine zero based indices, the
+/// position, texture coordinate, and normal of each of its three corners.
+///
+#[repr(C)]
+pub struct PluginMesh {
+    pub positions: *const f64,
+    pub position_count: usize,
+    pub texture_coordinates: *const f64,
+    pub texture_coordinate_count: usize,
+    pub normals: *const f64,
+    pub normal_count: usize,
+    pub faces: *const i32,
+    pub face_count: usize
+}
+
+
+/// A loader exported by a plugin library for the files with the given extension
+///
+/// The load function fills in the mesh from the contents of a file and
+/// returns 0, or returns anything else if the file cannot be parsed. The
+/// mesh is handed back to the free function once it has been copied.
+///
+#[repr(C)]
+pub struct PluginLoader {
+    pub extension: *const c_char,
+    pub load: extern "C" fn(data: *const u8, length: usize, mesh: *mut PluginMesh) -> i32,
+    pub free: extern "C" fn(mesh: *mut PluginMesh)
+}
+
+
+/// The inputs of a fragment shaded by a plugin, interpolated across the face
+///
+/// The texel is the color of the texture at the texture coordinate, and
+/// colors are linear from 0 to 1.
+///
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PluginFragment {
+    pub barycentric: [f64; 3],
+    pub texture_coordinate: [f64; 2],
+    pub normal: [f64; 3],
+    pub light: [f64; 3],
+    pub texel: [f64; 3]
+}
+
+
+/// A shader exported by a plugin library under the given name
+///
+/// The shade function writes the red, green, and blue of a fragment from 0
+/// to 1, and the optional discard function returns anything but 0 for the
+/// fragments that are cut away.
+///
+#[repr(C)]
+pub struct PluginShader {
+    pub name: *const c_char,
+    pub shade: extern "C" fn(fragment: *const PluginFragment, color: *mut f64),
+    pub discard: Option<extern "C" fn(fragment: *const PluginFragment) -> i32>
+}
+
+
+/// The functions a plugin library registers its loaders and shaders through
+#[repr(C)]
+pub struct Registrar {
+    pub version: u32,
+    pub context: *mut c_void,
+    pub register_loader: extern "C" fn(context: *mut c_void, loader: *const PluginLoader),
+    pub register_shader: extern "C" fn(context: *mut c_void, shader: *const PluginShader)
+}
+
+
+/// The signature of the function every plugin library exports
+pub type PluginEntry = unsafe extern "C" fn(registrar: *const Registrar);
+
+
+/// Copy a mesh handed over by a plugin loader into an Object
+///
+/// # Safety
+///
+/// The arrays of the mesh must hold as many values as their counts say.
+///
+unsafe fn copy_mesh(mesh: &PluginMesh) -> Result<wavefront::Object, String> {
+    let values = |pointer: *const f64, count: usize| {
+        if count == 0 { &[][..] } else { slice::from_raw_parts(pointer, count) }
+    };
+
+    let positions = values(mesh.positions, mesh.position_count * 3);
+    let texture_coordinates = values(mesh.texture_coordinates, mesh.texture_coordinate_count * 2);
+    let normals = values(mesh.normals, mesh.normal_count * 3);
+    let faces = if mesh.face_count == 0 { &[][..] } else { slice::from_raw_parts(mesh.faces, mesh.face_count * 9) };
+
+    let counts = [mesh.position_count, mesh.texture_coordinate_count, mesh.normal_count];
+
+    if faces.chunks(3).any(|corner| (0..3).any(|i| corner[i] < 0 || corner[i] as usize >= counts[i])) {
+        return Err(String::from("the plugin returned a face with an index out of range"));
+    }
+
+    let mut object = wavefront::Object {
+        geometric_vertices: positions.chunks(3).map(|p| Vector3::new(p[0], p[1], p[2])).collect(),
+        texture_vertices: texture_coordinates.chunks(2).map(|t| Vector2::new(t[0], t[1])).collect(),
+        normal_vertices: normals.chunks(3).map(|n| Vector3::new(n[0], n[1], n[2])).collect(),
+        ..Default::default()
+    };
+
+    for face in faces.chunks(9) {
+        object.geometric_faces.push(Vector3::new(face[0], face[3], face[6]));
+        object.texture_faces.push(Vector3::new(face[1], face[4], face[7]));
+        object.normal_faces.push(Vector3::new(face[2], face[5], face[8]));
+    }
+
+    object.smoothing_groups = vec![0; object.geometric_faces.len()];
+
+    if object.texture_vertices.is_empty() && !object.geometric_faces.is_empty() {
+        return Err(String::from("the plugin returned faces without texture coordinates"));
+    }
+
+    if object.normal_vertices.is_empty() && !object.geometric_faces.is_empty() {
+        return Err(String::from("the plugin returned faces without normals"));
+    }
+
+    Ok(object)
+}
+
+
+extern "C" fn register_plugin_loader(_context: *mut c_void, loader: *const PluginLoader) {
+    let loader = match unsafe { loader.as_ref() } {
+        Some(loader) if !loader.extension.is_null() => loader,
+        _ => return
+    };
+
+    let extension = unsafe { CStr::from_ptr(loader.extension) }.to_string_lossy().into_owned();
+    let (load, free) = (loader.load, loader.free);
+
+    register_loader(&extension, move |bytes| {
+        let mut mesh = PluginMesh { positions: std::ptr::null(), position_count: 0,
+                                    texture_coordinates: std::ptr::null(), texture_coordinate_count: 0,
+                                    normals: std::ptr::null(), normal_count: 0, faces: std::ptr::null(),
+                                    face_count: 0 };
+
+        if load(bytes.as_ptr(), bytes.len(), &mut mesh) != 0 {
+            return Err(String::from("the plugin could not load the file"));
+        }
+
+        let object = unsafe { copy_mesh(&mesh) };
+        free(&mut mesh);
+
+        object
+    });
+}
+
+
+extern "C" fn register_plugin_shader(_context: *mut c_void, shader: *const PluginShader) {
+    let shader = match unsafe { shader.as_ref() } {
+        Some(shader) if !shader.name.is_null() => shader,
+        _ => return
+    };
+
+    let name = unsafe { CStr::from_ptr(shader.name) }.to_string_lossy().into_owned();
+    let (shade, discard) = (shader.shade, shader.discard);
+
+    register_shader(&name, move || Box::new(ForeignShader::new(shade, discard)));
+}
+
+
+/// Register the loaders and shaders of a plugin through its entry point
+///
+/// This is how plugin libraries are registered once they are loaded, and it
+/// also registers plugins linked into the program that implement the C
+/// interface.
+///
+/// # Safety
+///
+/// The entry point must only pass valid loaders and shaders whose functions
+/// and names stay alive for as long as the program runs.
+///
+pub unsafe fn register_entry(entry: PluginEntry) {
+    let registrar = Registrar { version: PLUGIN_VERSION, context: std::ptr::null_mut(),
+                                register_loader: register_plugin_loader, register_shader: register_plugin_shader };
+
+    entry(&registrar);
+}
+
+
+/// Load a plugin library and register its loaders and shaders
+///
+/// The library must export morpheus_plugin_register as a PluginEntry, and
+/// it stays loaded for as long as the program runs. Only builds with the
+/// plugins feature can load libraries.
+///
+/// # Safety
+///
+/// Loading a library runs its initialization code, and its entry point must
+/// uphold the contract of register_entry.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use rastermind::plugins;
+/// unsafe { plugins::load_library("libmorpheus_ply.so") }.unwrap();
+///
+/// let coordinates = plugins::open("bunny.ply").unwrap();
+/// ```
+///
+#[cfg(feature = "plugins")]
+pub unsafe fn load_library<P: AsRef<std::ffi::OsStr>>(path: P) -> Result<(), MorpheusError> {
+    let name = path.as_ref().to_string_lossy().into_owned();
+    let library = libloading::Library::new(path).map_err(|error| MorpheusError::asset(&name, error))?;
+    let entry = *library.get::<PluginEntry>(PLUGIN_ENTRY).map_err(|error| MorpheusError::asset(&name, error))?;
+
+    // the functions registered by the library point into it, so it is never unloaded
+    std::mem::forget(library);
+    register_entry(entry);
+
+    Ok(())
+}
+
+
+/// Load a plugin library, which needs a build with the plugins feature
+///
+/// # Safety
+///
+/// Nothing is loaded, so this is always safe, but it matches the signature
+/// of the builds that load libraries.
+///
+#[cfg(not(feature = "plugins"))]
+pub unsafe fn load_library<P: AsRef<std::ffi::OsStr>>(path: P) -> Result<(), MorpheusError> {
+    Err(MorpheusError::asset(&path.as_ref().to_string_lossy(),
+                             "plugin libraries can only be loaded in builds with the plugins feature"))
+}
+
+
+/// Shade fragments with the functions of a plugin, interpolating the texture coordinates and normals like Phong
+pub struct ForeignShader {
+    shade: extern "C" fn(fragment: *const PluginFragment, color: *mut f64),
+    discard: Option<extern "C" fn(fragment: *const PluginFragment) -> i32>,
+    varying_texture: Matrix2x3<f64>,
+    varying_normals: Matrix3<f64>,
+    light: Vector3<f64>
+}
+
+
+impl ForeignShader {
+    fn new(shade: extern "C" fn(*const PluginFragment, *mut f64),
+           discard: Option<extern "C" fn(*const PluginFragment) -> i32>) -> ForeignShader {
+
+        ForeignShader { shade, discard, varying_texture: Matrix2x3::zeros(), varying_normals: Matrix3::zeros(),
+                        light: Vector3::zeros() }
+    }
+
+    fn inputs(&self, vertex: Vector3<f64>, texture: Option<&image::RgbImage>) -> PluginFragment {
+        let uv = self.varying_texture * vertex;
+        let normal = (self.varying_normals * vertex).try_normalize(1e-12).unwrap_or_else(Vector3::zeros);
+        let texel = texture.map_or([0.0; 3], |texture| {
+            let pixel = texture::sample(texture, &uv);
+            [0, 1, 2].map(|i| pixel[i] as f64 / 255.0)
+        });
+
+        PluginFragment { barycentric: [vertex.x, vertex.y, vertex.z], texture_coordinate: [uv.x, uv.y],
+                         normal: [normal.x, normal.y, normal.z], light: [self.light.x, self.light.y, self.light.z],
+                         texel }
+    }
+}
+
+
+impl Shader for ForeignShader {
+    fn vertex(&mut self, coordinates: &wavefront::Object, view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+              model_view: &Matrix4<f64>, light_vector: &Vector3<f64>, face_index: usize,
+              vertex_index: usize) -> Vector4<f64> {
+
+        let geometric_index = coordinates.geometric_faces[face_index][vertex_index] as usize;
+        let texture_index = coordinates.texture_faces[face_index][vertex_index] as usize;
+        let normal_index = coordinates.normal_faces[face_index][vertex_index] as usize;
+
+        self.varying_texture.set_column(vertex_index, &coordinates.texture_vertices[texture_index]);
+        self.varying_normals.set_column(vertex_index, &coordinates.normal_vertices[normal_index]);
+        self.light = *light_vector;
+
+        view_port * projection * model_view * vector::vectorize_to_4d(&coordinates.geometric_vertices[geometric_index])
+    }
+
+    fn fragment(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> image::Rgb<u8> {
+        let color = self.shade(vertex, texture);
+
+        image::Rgb([0, 1, 2].map(|i| color[i].clamp(0.0, 255.0).round() as u8))
+    }
+
+    fn shade(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> Vector3<f64> {
+        let mut color = [0.0; 3];
+        (self.shade)(&self.inputs(vertex, Some(texture)), color.as_mut_ptr());
+
+        Vector3::from(color) * 255.0
+    }
+
+    fn discard(&self, vertex: Vector3<f64>) -> bool {
+        self.discard.is_some_and(|discard| discard(&self.inputs(vertex, None)) != 0)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+
+    use shader;
+
+    static POSITIONS: [f64; 9] = [-1.0, -1.0, 0.0, 1.0, -1.0, 0.0, 0.0, 1.0, 0.0];
+    static TEXTURE_COORDINATES: [f64; 2] = [0.5, 0.5];
+    static NORMALS: [f64; 3] = [0.0, 0.0, 1.0];
+    static FACES: [i32; 9] = [0, 0, 0, 1, 0, 0, 2, 0, 0];
+
+    extern "C" fn load(data: *const u8, length: usize, mesh: *mut PluginMesh) -> i32 {
+        if unsafe { slice::from_raw_parts(data, length) } != b"triangle" {
+            return 1;
+        }
+
+        unsafe {
+            *mesh = PluginMesh { positions: POSITIONS.as_ptr(), position_count: 3,
+                                 texture_coordinates: TEXTURE_COORDINATES.as_ptr(), texture_coordinate_count: 1,
+                                 normals: NORMALS.as_ptr(), normal_count: 1, faces: FACES.as_ptr(), face_count: 1 };
+        }
+
+        0
+    }
+
+    extern "C" fn free(_mesh: *mut PluginMesh) {}
+
+    extern "C" fn shade(fragment: *const PluginFragment, color: *mut f64) {
+        let fragment = unsafe { &*fragment };
+        let lit = fragment.normal.iter().zip(&fragment.light).map(|(n, l)| n * l).sum::<f64>().max(0.0);
+
+        unsafe { *color = lit * fragment.texel[0] };
+    }
+
+    extern "C" fn discard(fragment: *const PluginFragment) -> i32 {
+        (unsafe { &*fragment }.barycentric[0] > 0.9) as i32
+    }
+
+    unsafe extern "C" fn entry(registrar: *const Registrar) {
+        let registrar = &*registrar;
+        assert_eq!(registrar.version, PLUGIN_VERSION);
+
+        let loader = PluginLoader { extension: b"tri\0".as_ptr() as *const c_char, load, free };
+        let shader = PluginShader { name: b"foreign red\0".as_ptr() as *const c_char, shade,
+                                    discard: Some(discard) };
+
+        (registrar.register_loader)(registrar.context, &loader);
+        (registrar.register_shader)(registrar.context, &shader);
+    }
+
+    #[test]
+    fn test_static_plugins() {
+        register_loader(".Empty", |_| Ok(wavefront::Object::default()));
+        register_shader("static white", || Box::new(shader::ColorShader::new(image::Rgb([255, 255, 255]), false)));
+
+        let path = env::temp_dir().join("morpheus_plugin.empty");
+        fs::write(&path, b"").unwrap();
+
+        assert!(open(path.to_str().unwrap()).unwrap().geometric_vertices.is_empty());
+        assert!(shader("static white").is_some());
+        assert!(shader("missing").is_none());
+        assert_eq!(shader("cel").unwrap().name(), "rastermind::shader::CelShader");
+
+        let (loaders, shaders) = registered();
+        assert!(loaders.contains(&String::from("empty")) && shaders.contains(&String::from("static white")));
+        assert!(["flat", "gouraud", "normal"].iter().all(|&name| shaders.contains(&String::from(name))));
+    }
+
+    #[test]
+    fn test_foreign_plugins() {
+        unsafe { register_entry(entry) };
+
+        let path = env::temp_dir().join("morpheus_plugin.tri");
+        fs::write(&path, b"triangle").unwrap();
+
+        let coordinates = open(path.to_str().unwrap()).unwrap();
+        assert_eq!(coordinates.geometric_vertices.len(), 3);
+        assert_eq!(coordinates.texture_faces[0], Vector3::zeros());
+
+        fs::write(&path, b"square").unwrap();
+        assert!(open(path.to_str().unwrap()).is_err());
+
+        // the plugin shades the red of the texture by the light, and cuts away the corner near the first vertex
+        let mut shader = shader("foreign red").unwrap();
+        (0..=2).for_each(|i| {
+            shader.vertex(&coordinates, &Matrix4::identity(), &Matrix4::identity(), &Matrix4::identity(),
+                          &Vector3::z(), 0, i);
+        });
+
+        let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([128, 255, 255]));
+        assert_eq!(shader.fragment(Vector3::new(0.2, 0.4, 0.4), &texture), image::Rgb([128, 0, 0]));
+        assert!(shader.discard(Vector3::new(0.95, 0.05, 0.0)));
+        assert!(!shader.discard(Vector3::new(0.5, 0.25, 0.25)));
+    }
+
+    #[test]
+    fn test_load_library() {
+        let result = unsafe { load_library("/nonexistent/libmorpheus_plugin.so") };
+
+        assert!(result.is_err());
+    }
+}
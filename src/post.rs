@@ -0,0 +1,604 @@
+use nalgebra::Vector3;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use error::MorpheusError;
+
+
+/// The linear value that contrast is applied around, which is middle gray
+const CONTRAST_PIVOT: f64 = 0.18;
+
+/// The color temperature in kelvin that is white without any white balance
+const NEUTRAL_TEMPERATURE: f64 = 6500.0;
+
+/// The largest bloom radius in pixels, since the blur kernel reaches out three radii
+const MAXIMUM_BLOOM_RADIUS: f64 = 1024.0;
+
+
+/// A framebuffer of linear light that post effects are applied to
+///
+/// Pixels are stored row by row from the bottom of the image like the
+/// framebuffers of the renderer. Values are not limited to [0, 1] so effects
+/// can brighten and darken the image without losing detail in between.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinearImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<Vector3<f64>>
+}
+
+
+impl LinearImage {
+    /// Decode the sRGB colors of the given framebuffer into linear light
+    pub fn from_rgb(buffer: &image::RgbImage) -> LinearImage {
+        let pixels = buffer.pixels().map(|pixel| {
+            Vector3::new(to_linear(pixel[0]), to_linear(pixel[1]), to_linear(pixel[2]))
+        }).collect();
+
+        LinearImage { width: buffer.width(), height: buffer.height(), pixels }
+    }
+
+    /// Encode the linear light of the image into an sRGB framebuffer, clipping values outside of [0, 1]
+    pub fn to_rgb(&self) -> image::RgbImage {
+        image::ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            let pixel = self.get(x, y);
+            image::Rgb([to_srgb(pixel.x), to_srgb(pixel.y), to_srgb(pixel.z)])
+        })
+    }
+
+    /// Find the linear color of the pixel at the given coordinates
+    pub fn get(&self, x: u32, y: u32) -> Vector3<f64> {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    /// Interpolate the linear color between the centers of the nearest pixels, clamping at the edges
+    pub fn sample(&self, x: f64, y: f64) -> Vector3<f64> {
+        let x = (x - 0.5).clamp(0.0, self.width as f64 - 1.0);
+        let y = (y - 0.5).clamp(0.0, self.height as f64 - 1.0);
+        let (left, bottom) = (x.floor() as u32, y.floor() as u32);
+        let (right, top) = ((left + 1).min(self.width - 1), (bottom + 1).min(self.height - 1));
+        let (s, t) = (x.fract(), y.fract());
+
+        (self.get(left, bottom) * (1.0 - s) + self.get(right, bottom) * s) * (1.0 - t) +
+            (self.get(left, top) * (1.0 - s) + self.get(right, top) * s) * t
+    }
+}
+
+
+/// Camera controls applied to the linear framebuffer after rendering
+///
+/// The exposure is in stops, so every step of one doubles or halves the light.
+/// The white balance is the color temperature in kelvin that appears white;
+/// lower temperatures make the image cooler and higher temperatures make it
+/// warmer. A contrast above one spreads the values away from middle gray and
+/// a contrast below one pulls them towards it. Bloom is added to the exposed
+/// image and the chromatic aberration is applied before the contrast, while
+/// grain is added last.
+///
+/// The chromatic aberration is how many pixels the red and blue channels are
+/// pushed apart at the corners of the image, as if by a lens that bends each
+/// color by a different amount.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate rastermind;
+/// # use rastermind::post::*;
+/// # use rastermind::post;
+/// # let buffer = image::RgbImage::new(8, 8);
+/// let options = PostOptions { exposure: 0.5, white_balance: 5000.0,
+///                             bloom: Some(Bloom::default()), ..Default::default() };
+///
+/// let graded = post::process(&buffer, &options);
+/// ```
+///
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PostOptions {
+    pub exposure: f64,
+    pub white_balance: f64,
+    pub contrast: f64,
+    pub bloom: Option<Bloom>,
+    pub chromatic_aberration: f64,
+    pub grain: Option<Grain>
+}
+
+
+impl Default for PostOptions {
+    fn default() -> PostOptions {
+        PostOptions { exposure: 0.0, white_balance: NEUTRAL_TEMPERATURE, contrast: 1.0, bloom: None,
+                      chromatic_aberration: 0.0, grain: None }
+    }
+}
+
+
+impl PostOptions {
+    /// Check that every option is finite, the white balance is positive, and the bloom radius is not too large
+    pub fn validate(&self) -> Result<(), MorpheusError> {
+        let invalid = |message: String| Err(MorpheusError::Scene(message));
+        let bloom = self.bloom.clone().unwrap_or_default();
+        let grain = self.grain.clone().unwrap_or_default();
+
+        for (name, value) in [("exposure", self.exposure), ("white_balance", self.white_balance),
+                              ("contrast", self.contrast), ("chromatic_aberration", self.chromatic_aberration),
+                              ("bloom.threshold", bloom.threshold), ("bloom.intensity", bloom.intensity),
+                              ("bloom.radius", bloom.radius), ("grain.intensity", grain.intensity)] {
+            if !value.is_finite() {
+                return invalid(format!("the post option {} is {} but must be finite", name, value));
+            }
+        }
+
+        if self.white_balance <= 0.0 {
+            return invalid(format!("the white balance is {} kelvin but must be positive", self.white_balance));
+        }
+
+        if bloom.radius > MAXIMUM_BLOOM_RADIUS {
+            return invalid(format!("the bloom radius is {} pixels but must be at most {}", bloom.radius,
+                                   MAXIMUM_BLOOM_RADIUS));
+        }
+
+        Ok(())
+    }
+}
+
+
+/// A glow around the bright parts of the image
+///
+/// The light of every pixel above the threshold is blurred with a gaussian
+/// whose standard deviation is the radius in pixels, scaled by the intensity,
+/// and added back onto the image.
+///
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Bloom {
+    pub threshold: f64,
+    pub intensity: f64,
+    pub radius: f64
+}
+
+
+impl Default for Bloom {
+    fn default() -> Bloom {
+        Bloom { threshold: 0.8, intensity: 0.5, radius: 4.0 }
+    }
+}
+
+
+/// Random film grain added to every pixel
+///
+/// Each pixel is brightened or darkened by up to the intensity in linear
+/// light. The same seed always produces the same grain, so give every frame
+/// of an animation its own seed for grain that moves.
+///
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Grain {
+    pub intensity: f64,
+    pub seed: u64
+}
+
+
+impl Default for Grain {
+    fn default() -> Grain {
+        Grain { intensity: 0.05, seed: 0 }
+    }
+}
+
+
+/// Render at a fraction of the resolution for a deliberately blocky retro look
+///
+/// The image is drawn with every pixel covering a square of scale by scale
+/// output pixels, which are filled with nearest neighbour filtering. When a
+/// palette is given every color is replaced by the nearest color of the
+/// palette.
+///
+/// # Examples
+///
+/// ```
+/// # use rastermind::post::PixelArt;
+/// let pixel_art = PixelArt { scale: 4, palette: vec![[15, 56, 15], [48, 98, 48],
+///                                                    [139, 172, 15], [155, 188, 15]] };
+/// ```
+///
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PixelArt {
+    pub scale: u32,
+    pub palette: Vec<[u8; 3]>
+}
+
+
+impl Default for PixelArt {
+    fn default() -> PixelArt {
+        PixelArt { scale: 4, palette: Vec::new() }
+    }
+}
+
+
+/// Draw an image of the given size as pixel art
+///
+/// The draw function is given a framebuffer a scale times smaller than the
+/// image, rounded up, which is then locked to the palette and scaled up.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::post::*;
+/// # use rastermind::{post, render, wavefront};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let (eye, center, up) = (Vector3::<f64>::new(0.0, 0.0, 3.0), Vector3::zeros(), Vector3::y());
+/// # let light_vector = Vector3::<f64>::z();
+/// let buffer = post::render_pixel_art(256, 256, &PixelArt::default(), |buffer| {
+///     render::draw_object(&coordinates, buffer, &texture, 255, &light_vector, &eye, &center, &up);
+/// });
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn render_pixel_art<F>(width: u32, height: u32, pixel_art: &PixelArt, draw: F) -> image::RgbImage
+    where F: FnOnce(&mut image::RgbImage) {
+
+    let scale = pixel_art.scale.max(1);
+    let mut buffer = image::ImageBuffer::new(width.div_ceil(scale), height.div_ceil(scale));
+
+    draw(&mut buffer);
+    lock_palette(&mut buffer, &pixel_art.palette);
+
+    image::ImageBuffer::from_fn(width, height, |x, y| *buffer.get_pixel(x / scale, y / scale))
+}
+
+
+/// Replace every color of the framebuffer by the nearest color of the palette
+///
+/// An empty palette leaves the framebuffer unchanged.
+///
+pub fn lock_palette(buffer: &mut image::RgbImage, palette: &[[u8; 3]]) {
+    if palette.is_empty() {
+        return;
+    }
+
+    let distance = |a: &image::Rgb<u8>, b: &[u8; 3]| -> i32 {
+        (0..3).map(|i| (a[i] as i32 - b[i] as i32).pow(2)).sum()
+    };
+
+    for pixel in buffer.pixels_mut() {
+        let nearest = palette.iter().min_by_key(|color| distance(pixel, color)).unwrap();
+        *pixel = image::Rgb(*nearest);
+    }
+}
+
+
+/// Apply the post stage to the given sRGB framebuffer and return the result
+pub fn process(buffer: &image::RgbImage, options: &PostOptions) -> image::RgbImage {
+    if *options == PostOptions::default() {
+        return buffer.clone();
+    }
+
+    let mut image = LinearImage::from_rgb(buffer);
+    apply(&mut image, options);
+
+    image.to_rgb()
+}
+
+
+/// Apply the white balance, exposure, bloom, chromatic aberration, contrast, and grain to the linear image
+pub fn apply(image: &mut LinearImage, options: &PostOptions) {
+    let gains = blackbody(NEUTRAL_TEMPERATURE).component_div(&blackbody(options.white_balance)) *
+                2f64.powf(options.exposure);
+
+    for pixel in image.pixels.iter_mut() {
+        *pixel = pixel.component_mul(&gains);
+    }
+
+    if let Some(ref bloom) = options.bloom {
+        apply_bloom(image, bloom);
+    }
+
+    if options.chromatic_aberration != 0.0 {
+        apply_chromatic_aberration(image, options.chromatic_aberration);
+    }
+
+    for pixel in image.pixels.iter_mut() {
+        *pixel = pixel.map(|value| {
+            if value > 0.0 {
+                CONTRAST_PIVOT * (value / CONTRAST_PIVOT).powf(options.contrast)
+            } else {
+                0.0
+            }
+        });
+    }
+
+    if let Some(ref grain) = options.grain {
+        apply_grain(image, grain);
+    }
+}
+
+
+/// Add the blurred bright parts of the linear image back onto it
+pub fn apply_bloom(image: &mut LinearImage, bloom: &Bloom) {
+    let bright: Vec<Vector3<f64>> = image.pixels.iter().map(|pixel| {
+        pixel.map(|value| (value - bloom.threshold).max(0.0))
+    }).collect();
+
+    let kernel = gaussian_kernel(bloom.radius);
+    let (width, height) = (image.width as usize, image.height as usize);
+
+    let horizontal = blur(&bright, &kernel, width, height, 1, width);
+    let glow = blur(&horizontal, &kernel, width, height, width, 1);
+
+    for (pixel, glow) in image.pixels.iter_mut().zip(glow) {
+        *pixel += glow * bloom.intensity;
+    }
+}
+
+
+/// Push the red channel away from the center of the image and the blue channel towards it
+///
+/// The offset grows from nothing at the center to the given number of pixels
+/// at the corners.
+///
+pub fn apply_chromatic_aberration(image: &mut LinearImage, offset: f64) {
+    let center = (image.width as f64 / 2.0, image.height as f64 / 2.0);
+    let scale = offset / center.0.hypot(center.1);
+    let source = image.clone();
+
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let (dx, dy) = (x as f64 + 0.5 - center.0, y as f64 + 0.5 - center.1);
+            let channel = |factor: f64| {
+                source.sample(center.0 + dx * (1.0 + factor), center.1 + dy * (1.0 + factor))
+            };
+
+            let pixel = &mut image.pixels[(y * image.width + x) as usize];
+            pixel.x = channel(-scale).x;
+            pixel.z = channel(scale).z;
+        }
+    }
+}
+
+
+/// Add seeded random grain to the linear image
+pub fn apply_grain(image: &mut LinearImage, grain: &Grain) {
+    let mut rng = StdRng::seed_from_u64(grain.seed);
+
+    for pixel in image.pixels.iter_mut() {
+        let noise: f64 = rng.gen_range(-1.0, 1.0);
+        *pixel = pixel.map(|value| (value + noise * grain.intensity).max(0.0));
+    }
+}
+
+
+/// Find the normalized weights of a gaussian with the given standard deviation out to three deviations
+fn gaussian_kernel(deviation: f64) -> Vec<f64> {
+    if deviation <= 0.0 {
+        return vec![1.0];
+    }
+
+    let reach = (deviation * 3.0).ceil() as i64;
+    let weights: Vec<f64> = (-reach..=reach).map(|offset| {
+        (-(offset * offset) as f64 / (2.0 * deviation * deviation)).exp()
+    }).collect();
+    let total: f64 = weights.iter().sum();
+
+    weights.iter().map(|weight| weight / total).collect()
+}
+
+
+/// Convolve the pixels with the kernel along one axis, clamping at the edges of the image
+///
+/// The step is the distance between neighbouring pixels along the axis and
+/// the stride is the distance between the lines of pixels across it.
+///
+fn blur(pixels: &[Vector3<f64>], kernel: &[f64], width: usize, height: usize,
+        step: usize, stride: usize) -> Vec<Vector3<f64>> {
+
+    let (length, lines) = if step == 1 { (width, height) } else { (height, width) };
+    let reach = (kernel.len() / 2) as i64;
+    let mut blurred = vec![Vector3::zeros(); pixels.len()];
+
+    for line in 0..lines {
+        for position in 0..length {
+            blurred[line * stride + position * step] = kernel.iter().enumerate().map(|(i, weight)| {
+                let neighbour = (position as i64 + i as i64 - reach).clamp(0, length as i64 - 1) as usize;
+                pixels[line * stride + neighbour * step] * *weight
+            }).sum();
+        }
+    }
+
+    blurred
+}
+
+
+/// Decode an sRGB channel into linear light
+pub fn to_linear(value: u8) -> f64 {
+    decode(value as f64 / 255.0)
+}
+
+
+/// Decode an sRGB value in [0, 1] into linear light
+fn decode(value: f64) -> f64 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+
+/// Encode linear light into an sRGB channel
+pub fn to_srgb(value: f64) -> u8 {
+    to_srgb_dithered(value, 0.0)
+}
+
+
+/// Encode linear light into an sRGB channel, moved by the given threshold from -0.5 to 0.5 before it is rounded
+///
+/// Thresholds that vary from pixel to pixel in an ordered pattern break the
+/// bands of smooth gradients up into a dither that the eye averages.
+///
+pub fn to_srgb_dithered(value: f64, threshold: f64) -> u8 {
+    let value = value.clamp(0.0, 1.0);
+
+    let encoded = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+
+    (encoded * 255.0 + threshold).round().clamp(0.0, 255.0) as u8
+}
+
+
+/// Approximate the linear color of a black body at the given temperature in kelvin
+fn blackbody(temperature: f64) -> Vector3<f64> {
+    let t = temperature.clamp(1000.0, 40000.0) / 100.0;
+
+    let red = if t <= 66.0 { 255.0 } else { 329.698727446 * (t - 60.0).powf(-0.1332047592) };
+    let green = if t <= 66.0 {
+        99.4708025861 * t.ln() - 161.1195681661
+    } else {
+        288.1221695283 * (t - 60.0).powf(-0.0755148492)
+    };
+    let blue = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.5177312231 * (t - 10.0).ln() - 305.0447927307
+    };
+
+    // keep a little of every channel so the white balance never divides by zero
+    Vector3::new(red, green, blue).map(|channel| decode(channel.clamp(1.0, 255.0) / 255.0))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_srgb_round_trip() {
+        assert!((0..=255).all(|value| to_srgb(to_linear(value)) == value));
+        assert_eq!(to_srgb(2.0), 255);
+        assert_eq!(to_srgb(-1.0), 0);
+    }
+
+    #[test]
+    fn test_process() {
+        let gray = image::RgbImage::from_pixel(1, 1, image::Rgb([118, 118, 118]));
+        let linear = to_linear(118);
+
+        let process_gray = |options: PostOptions| {
+            LinearImage::from_rgb(&process(&gray, &options)).get(0, 0)
+        };
+
+        assert_eq!(process(&gray, &PostOptions::default()).get_pixel(0, 0), gray.get_pixel(0, 0));
+
+        let brighter = process_gray(PostOptions { exposure: 1.0, ..Default::default() });
+        assert!((brighter.x - 2.0 * linear).abs() < 0.01);
+
+        // middle gray is unchanged by contrast while brighter values get brighter
+        let contrast = PostOptions { contrast: 1.5, ..Default::default() };
+        assert!((process_gray(contrast.clone()).x - linear).abs() < 0.01);
+        let white = image::RgbImage::from_pixel(1, 1, image::Rgb([200, 200, 200]));
+        assert!(process(&white, &contrast).get_pixel(0, 0)[0] > 200);
+
+        let cool = process_gray(PostOptions { white_balance: 3200.0, ..Default::default() });
+        assert!(cool.z > cool.x);
+        let warm = process_gray(PostOptions { white_balance: 10000.0, ..Default::default() });
+        assert!(warm.x > warm.z);
+
+        assert!(PostOptions { bloom: Some(Bloom::default()), ..Default::default() }.validate().is_ok());
+        assert!(PostOptions { exposure: f64::NAN, ..Default::default() }.validate().is_err());
+        assert!(PostOptions { white_balance: 0.0, ..Default::default() }.validate().is_err());
+        let wide = Bloom { radius: 1e12, ..Default::default() };
+        assert!(PostOptions { bloom: Some(wide), ..Default::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_bloom() {
+        let mut buffer = image::RgbImage::from_pixel(11, 9, image::Rgb([50, 50, 50]));
+        buffer.put_pixel(5, 4, image::Rgb([255, 255, 255]));
+
+        let bloom = Bloom { threshold: 0.5, intensity: 1.0, radius: 1.0 };
+        let mut image = LinearImage::from_rgb(&buffer);
+        apply_bloom(&mut image, &bloom);
+
+        // the glow spreads evenly around the bright pixel and does not touch the far corners
+        assert!(image.get(4, 4).x > to_linear(50));
+        assert!((image.get(4, 4).x - image.get(6, 4).x).abs() < 1e-12);
+        assert!((image.get(5, 3).x - image.get(5, 5).x).abs() < 1e-12);
+        assert!(image.get(4, 4).x > image.get(3, 4).x);
+        assert_eq!(image.get(0, 0).x, to_linear(50));
+
+        // the blur preserves the light above the threshold
+        let total: f64 = image.pixels.iter().map(|pixel| pixel.x - to_linear(50)).sum();
+        assert!((total - (1.0 - to_linear(50)) - 0.5).abs() < 1e-9);
+
+        let dim = image::RgbImage::from_pixel(4, 4, image::Rgb([100, 100, 100]));
+        let options = PostOptions { bloom: Some(Bloom::default()), ..Default::default() };
+        assert_eq!(process(&dim, &options).get_pixel(1, 1), dim.get_pixel(1, 1));
+    }
+
+    #[test]
+    fn test_chromatic_aberration() {
+        let mut buffer = image::RgbImage::new(9, 9);
+        buffer.put_pixel(4, 4, image::Rgb([255, 255, 255]));
+        buffer.put_pixel(7, 4, image::Rgb([255, 255, 255]));
+
+        let mut image = LinearImage::from_rgb(&buffer);
+        apply_chromatic_aberration(&mut image, 2.0f64.hypot(2.0));
+
+        // the center is unchanged while the colors of the edge separate
+        assert_eq!(image.get(4, 4), Vector3::new(1.0, 1.0, 1.0));
+        assert_eq!(image.get(7, 4).y, 1.0);
+        assert!(image.get(7, 4).x < 1.0 && image.get(7, 4).z < 1.0);
+        assert!(image.get(8, 4).x > 0.0 && image.get(8, 4).z == 0.0);
+        assert!(image.get(6, 4).z > 0.0 && image.get(6, 4).x == 0.0);
+    }
+
+    #[test]
+    fn test_grain() {
+        let gray = image::RgbImage::from_pixel(16, 16, image::Rgb([118, 118, 118]));
+        let grained = |seed| {
+            process(&gray, &PostOptions { grain: Some(Grain { intensity: 0.1, seed }), ..Default::default() })
+        };
+
+        let (first, again, other) = (grained(1), grained(1), grained(2));
+
+        assert_eq!(first.into_raw(), again.clone().into_raw());
+        assert_ne!(again.clone().into_raw(), other.into_raw());
+
+        let pixels = LinearImage::from_rgb(&again).pixels;
+        let mean = pixels.iter().map(|pixel| pixel.x).sum::<f64>() / pixels.len() as f64;
+        assert!(pixels.iter().any(|pixel| pixel.x != to_linear(118)));
+        assert!((mean - to_linear(118)).abs() < 0.02);
+    }
+
+    #[test]
+    fn test_render_pixel_art() {
+        let pixel_art = PixelArt { scale: 3, palette: vec![[0, 0, 0], [255, 255, 255]] };
+        let mut size = (0, 0);
+
+        let buffer = render_pixel_art(8, 5, &pixel_art, |buffer| {
+            size = buffer.dimensions();
+            buffer.put_pixel(1, 0, image::Rgb([200, 180, 190]));
+            buffer.put_pixel(2, 1, image::Rgb([60, 20, 40]));
+        });
+
+        assert_eq!(size, (3, 2));
+        assert_eq!(buffer.dimensions(), (8, 5));
+        assert!((3..6).all(|x| (0..3).all(|y| buffer.get_pixel(x, y)[0] == 255)));
+        assert_eq!(*buffer.get_pixel(2, 0), image::Rgb([0, 0, 0]));
+        assert_eq!(*buffer.get_pixel(6, 0), image::Rgb([0, 0, 0]));
+        assert_eq!(*buffer.get_pixel(7, 4), image::Rgb([0, 0, 0]));
+    }
+}
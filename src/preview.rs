@@ -0,0 +1,281 @@
+use std::fs;
+use std::time::{Duration, Instant};
+
+use nalgebra::Vector3;
+
+use animation::{Easing, Keyframe, Track};
+use camera::Camera;
+use error::MorpheusError;
+
+
+/// Scale the resolution of an interactive preview to keep its frames within a target time
+///
+/// While the camera moves, frames are drawn at the resolution scale and
+/// stretched to the full size, and the scale follows the time the frames
+/// take, shrinking when they are slow and growing back when they are fast.
+/// The time of a frame grows with its pixels, which grow with the square of
+/// the scale, so every frame moves the scale part of the way towards the
+/// square root of the ratio of the target to the time the frame took. Once
+/// the camera stops, the frame is drawn at the full resolution.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use std::time::Duration;
+/// # use rastermind::preview::AdaptiveResolution;
+/// # use rastermind::{render, wavefront};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let (eye, center, up) = (Vector3::<f64>::new(0.0, 0.0, 3.0), Vector3::<f64>::zeros(), Vector3::<f64>::y());
+/// # let light_vector = Vector3::<f64>::z();
+/// # let camera_moving = true;
+/// let mut preview = AdaptiveResolution::new(Duration::from_millis(33));
+///
+/// let buffer = preview.render(1024, 1024, camera_moving, |buffer| {
+///     render::draw_object(&coordinates, buffer, &texture, 255, &light_vector, &eye, &center, &up);
+/// });
+/// # Ok(())
+/// # }
+/// ```
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdaptiveResolution {
+    pub target: Duration,
+    pub scale: f64,
+    pub minimum: f64
+}
+
+
+impl AdaptiveResolution {
+    /// Create a new AdaptiveResolution for the given target frame time that starts at the full resolution
+    pub fn new(target: Duration) -> AdaptiveResolution {
+        AdaptiveResolution { target, scale: 1.0, minimum: 0.1 }
+    }
+
+    /// Move the scale towards the one that draws a frame in the target time, given the time of the last frame
+    pub fn update(&mut self, frame_time: Duration) {
+        if frame_time.is_zero() {
+            return;
+        }
+
+        let ideal = self.scale * (self.target.as_secs_f64() / frame_time.as_secs_f64()).sqrt();
+
+        // only go half of the way, so a single slow frame does not make the preview flicker
+        self.scale = (self.scale * 0.5 + ideal * 0.5).clamp(self.minimum, 1.0);
+    }
+
+    /// The size of a frame drawn at the current scale for a preview of the given size
+    pub fn size(&self, width: u32, height: u32) -> (u32, u32) {
+        (((width as f64 * self.scale).round() as u32).max(1), ((height as f64 * self.scale).round() as u32).max(1))
+    }
+
+    /// Draw a frame of the given size, at the current scale while the camera moves and fully once it stops
+    ///
+    /// The draw function is given an empty framebuffer of the size to draw
+    /// at, and the frame is stretched to the given size with the nearest
+    /// pixels, so the preview stays blocky instead of blurry while it moves.
+    ///
+    pub fn render<F>(&mut self, width: u32, height: u32, moving: bool, mut draw: F) -> image::RgbImage
+        where F: FnMut(&mut image::RgbImage) {
+
+        if !moving {
+            let mut buffer = image::ImageBuffer::new(width, height);
+            draw(&mut buffer);
+
+            return buffer;
+        }
+
+        let (scaled_width, scaled_height) = self.size(width, height);
+        let mut buffer = image::ImageBuffer::new(scaled_width, scaled_height);
+        let start = Instant::now();
+
+        draw(&mut buffer);
+        self.update(start.elapsed());
+
+        image::ImageBuffer::from_fn(width, height, |x, y| {
+            *buffer.get_pixel((x * scaled_width / width).min(scaled_width - 1),
+                              (y * scaled_height / height).min(scaled_height - 1))
+        })
+    }
+}
+
+
+/// Where the camera of a preview was a number of seconds after recording started
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PathPose {
+    pub time: f64,
+    pub eye: [f64; 3],
+    pub center: [f64; 3],
+    pub up: [f64; 3]
+}
+
+
+/// The motion of the camera of an interactive preview, recorded to be rendered again offline
+///
+/// Poses are recorded whenever the camera moves, with the seconds since the
+/// recording started, and saved as JSON. Played back, the camera moves in a
+/// straight line from one pose to the next at the speed it was moved, so
+/// frames sampled at any rate follow the exploration that was recorded.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate image;
+/// # extern crate rastermind;
+/// # use rastermind::Renderer;
+/// # use rastermind::preview::CameraPath;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// let path = CameraPath::open("flythrough.json")?;
+///
+/// for (frame, camera) in path.frames(24.0).into_iter().enumerate() {
+///     let image = Renderer::new(2048, 2048).with_camera(camera).render(&coordinates, &texture)?;
+///     image.save(format!("frame_{:04}.png", frame))?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CameraPath {
+    pub poses: Vec<PathPose>
+}
+
+
+impl CameraPath {
+    /// Load a path saved as JSON from the given file
+    pub fn open(path: &str) -> Result<CameraPath, MorpheusError> {
+        let contents = fs::read_to_string(path).map_err(|error| MorpheusError::io(path, error))?;
+
+        serde_json::from_str(&contents).map_err(|error| MorpheusError::asset(path, error))
+    }
+
+    /// Save the path as JSON to the given file
+    pub fn save(&self, path: &str) -> Result<(), MorpheusError> {
+        let contents = serde_json::to_string_pretty(self).map_err(|error| MorpheusError::asset(path, error))?;
+
+        fs::write(path, contents).map_err(|error| MorpheusError::io(path, error))
+    }
+
+    /// Add the pose of the camera at the given time, unless the camera has not moved since the last pose
+    pub fn record(&mut self, time: f64, eye: &Vector3<f64>, center: &Vector3<f64>, up: &Vector3<f64>) {
+        let pose = PathPose { time, eye: (*eye).into(), center: (*center).into(), up: (*up).into() };
+
+        match self.poses.last() {
+            Some(last) if (last.eye, last.center, last.up) == (pose.eye, pose.center, pose.up) => (),
+            _ => self.poses.push(pose)
+        }
+    }
+
+    /// The seconds from the first pose to the last
+    pub fn duration(&self) -> f64 {
+        match (self.poses.first(), self.poses.last()) {
+            (Some(first), Some(last)) => last.time - first.time,
+            _ => 0.0
+        }
+    }
+
+    /// The camera at the given number of seconds after the first pose, or None for an empty path
+    pub fn sample(&self, time: f64) -> Option<Camera> {
+        let track = |part: fn(&PathPose) -> [f64; 3]| Track {
+            keyframes: self.poses.iter().map(|pose| {
+                Keyframe { time: pose.time, value: Vector3::from(part(pose)), easing: Easing::Linear }
+            }).collect(),
+            looped: false
+        };
+        let start = self.poses.first()?.time;
+
+        Some(Camera::builder().eye(track(|pose| pose.eye).sample(start + time)?)
+                              .target(track(|pose| pose.center).sample(start + time)?)
+                              .up(track(|pose| pose.up).sample(start + time)?)
+                              .build())
+    }
+
+    /// The cameras of the frames of the path at the given frames per second, including its last pose
+    pub fn frames(&self, fps: f64) -> Vec<Camera> {
+        let frames = (self.duration() * fps).ceil() as usize + 1;
+
+        (0..frames).filter_map(|frame| self.sample((frame as f64 / fps).min(self.duration()))).collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update() {
+        let mut preview = AdaptiveResolution::new(Duration::from_millis(30));
+
+        // a frame that takes four times the target wants half the resolution, and moves half of the way there
+        preview.update(Duration::from_millis(120));
+        assert!((preview.scale - 0.75).abs() < 1e-12);
+        assert_eq!(preview.size(1000, 500), (750, 375));
+
+        // fast frames grow the scale back, but never past the full resolution
+        (0..10).for_each(|_| preview.update(Duration::from_millis(1)));
+        assert_eq!(preview.scale, 1.0);
+
+        // very slow frames stop at the minimum scale
+        (0..20).for_each(|_| preview.update(Duration::from_secs(10)));
+        assert_eq!(preview.scale, preview.minimum);
+        assert_eq!(preview.size(5, 5), (1, 1));
+    }
+
+    #[test]
+    fn test_render() {
+        let mut preview = AdaptiveResolution { scale: 0.5, ..AdaptiveResolution::new(Duration::from_secs(60)) };
+        let mut sizes = Vec::new();
+
+        let moving = preview.render(8, 4, true, |buffer| {
+            sizes.push(buffer.dimensions());
+            buffer.put_pixel(1, 0, image::Rgb([255, 0, 0]));
+        });
+        let stopped = preview.render(8, 4, false, |buffer| sizes.push(buffer.dimensions()));
+
+        // moving frames are drawn small and stretched, and stopped frames are drawn at the full size
+        assert_eq!(sizes, vec![(4, 2), (8, 4)]);
+        assert_eq!(moving.dimensions(), (8, 4));
+        assert_eq!(*moving.get_pixel(2, 1), image::Rgb([255, 0, 0]));
+        assert_eq!(*moving.get_pixel(3, 0), image::Rgb([255, 0, 0]));
+        assert_eq!(*moving.get_pixel(4, 0), image::Rgb([0, 0, 0]));
+        assert_eq!(stopped.dimensions(), (8, 4));
+
+        // the quick frame let the scale grow towards the full resolution
+        assert!(preview.scale > 0.5);
+    }
+
+    #[test]
+    fn test_camera_path() {
+        let mut path = CameraPath::default();
+        assert_eq!(path.sample(0.0), None);
+        assert!(path.frames(24.0).is_empty());
+
+        path.record(2.0, &Vector3::new(0.0, 0.0, 4.0), &Vector3::zeros(), &Vector3::y());
+        path.record(2.5, &Vector3::new(0.0, 0.0, 4.0), &Vector3::zeros(), &Vector3::y());
+        path.record(3.0, &Vector3::new(4.0, 0.0, 0.0), &Vector3::zeros(), &Vector3::y());
+
+        // a camera that did not move is not recorded again, and playback starts at the first pose
+        assert_eq!(path.poses.len(), 2);
+        assert_eq!(path.duration(), 1.0);
+        assert_eq!(path.sample(0.25).unwrap().eye, Vector3::new(1.0, 0.0, 3.0));
+
+        let frames = path.frames(4.0);
+        assert_eq!(frames.len(), 5);
+        assert_eq!(frames[4].eye, Vector3::new(4.0, 0.0, 0.0));
+        assert_eq!(frames[4].target, Vector3::zeros());
+
+        let file = std::env::temp_dir().join("morpheus_camera_path.json");
+        path.save(file.to_str().unwrap()).unwrap();
+        assert_eq!(CameraPath::open(file.to_str().unwrap()).unwrap(), path);
+        assert!(CameraPath::open("missing.json").is_err());
+    }
+}
@@ -0,0 +1,117 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+
+/// Periodically save the partially drawn image of a long render
+///
+/// The partial image is written to a temporary file next to the given path
+/// and then renamed over it, so anyone watching the file, such as an image
+/// viewer or a web server, never sees a half written image.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use std::time::Duration;
+/// # use rastermind::progress::Progress;
+/// # use rastermind::{render, wavefront};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let (eye, center, up) = (Vector3::<f64>::new(0.0, 0.0, 3.0), Vector3::<f64>::zeros(), Vector3::<f64>::y());
+/// # let light_vector = Vector3::<f64>::z();
+/// # let mut buffer = image::RgbImage::new(512, 512);
+/// let mut partial = Progress::new("partial.png", Duration::from_secs(5));
+///
+/// render::draw_object_progressively(&coordinates, &mut buffer, &texture, 255, &light_vector,
+///                                   &eye, &center, &up,
+///                                   |buffer, drawn, total| partial.update(buffer, drawn, total).unwrap());
+/// # Ok(())
+/// # }
+/// ```
+///
+pub struct Progress {
+    pub path: PathBuf,
+    pub interval: Duration,
+    pub saves: usize,
+    last_save: Instant
+}
+
+
+impl Progress {
+    /// Create a new Progress that saves to the given path at most once every interval
+    pub fn new<P: AsRef<Path>>(path: P, interval: Duration) -> Progress {
+        Progress { path: path.as_ref().to_path_buf(), interval, saves: 0, last_save: Instant::now() }
+    }
+
+    /// Save the buffer if the interval has passed since the last save or the render is done
+    pub fn update(&mut self, buffer: &image::RgbImage, drawn: usize, total: usize) -> io::Result<()> {
+        if drawn < total && self.last_save.elapsed() < self.interval {
+            return Ok(());
+        }
+
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+
+        image::ImageRgb8(buffer.clone()).flipv().save_with_format(&temporary, image::ImageFormat::PNG)?;
+        fs::rename(&temporary, &self.path)?;
+
+        self.saves += 1;
+        self.last_save = Instant::now();
+
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use nalgebra::Vector3;
+
+    use render;
+    use wavefront;
+    use super::*;
+
+    #[test]
+    fn test_progress() {
+        let mut path = env::temp_dir();
+        path.push("test_progress.png");
+        let _ = fs::remove_file(&path);
+
+        let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\nvt 0 0\nvn 0 0 1\n\
+                                                          f 1/1/1 2/1/1 3/1/1\nf 1/1/1 3/1/1 4/1/1\n",
+                                                        &wavefront::LoadOptions::default()).unwrap();
+        let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+        let eye = Vector3::new(0.0, 0.0, 3.0);
+
+        let mut progress = Progress::new(&path, Duration::from_secs(3600));
+        let mut reports = Vec::new();
+        let mut buffer = image::ImageBuffer::new(32, 32);
+
+        render::draw_object_progressively(&coordinates, &mut buffer, &texture, 255, &Vector3::z(), &eye,
+                                          &Vector3::zeros(), &Vector3::y(), |buffer, drawn, total| {
+            reports.push((drawn, total));
+            progress.update(buffer, drawn, total).unwrap();
+        });
+
+        // only the finished image is saved within the interval
+        assert_eq!(reports, vec![(1, 2), (2, 2)]);
+        assert_eq!(progress.saves, 1);
+
+        let saved = image::open(&path).unwrap().flipv().to_rgb();
+        assert!(saved.pixels().zip(buffer.pixels()).all(|(a, b)| a == b));
+
+        let mut eager = Progress::new(&path, Duration::from_secs(0));
+        eager.update(&buffer, 1, 2).unwrap();
+        assert_eq!(eager.saves, 1);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
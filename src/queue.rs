@@ -0,0 +1,126 @@
+use nalgebra::{Matrix4, Vector3};
+
+use wavefront;
+
+
+/// An object waiting to be drawn, with its distance from the camera and the material it is drawn with
+///
+/// Objects with the same material share a texture, so drawing them one after
+/// another keeps the texture in the cache. Transparent objects are drawn
+/// after all of the opaque objects.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QueuedObject {
+    pub index: usize,
+    pub distance: f64,
+    pub material: usize,
+    pub transparent: bool
+}
+
+
+impl QueuedObject {
+    /// Queue the object at the given index by the distance of the center of its bounds from the camera
+    pub fn new(index: usize, coordinates: &wavefront::Object, model_view: &Matrix4<f64>, material: usize,
+               transparent: bool) -> QueuedObject {
+
+        let center = coordinates.bounds().map_or(Vector3::zeros(), |(minimum, maximum)| (minimum + maximum) / 2.0);
+
+        // the camera looks down its negative z axis
+        let distance = -model_view.transform_point(&nalgebra::Point3::from(center)).z;
+
+        QueuedObject { index, distance, material, transparent }
+    }
+}
+
+
+/// The order in which the objects of a scene are drawn
+///
+/// Opaque objects are drawn front to back, so the depth test rejects the
+/// fragments they hide before they are shaded. Objects of the same material
+/// are drawn together where the nearest of them would be drawn, nearest
+/// first. Transparent objects are drawn back to front afterwards, so every
+/// transparent surface is drawn over what is behind it.
+///
+/// # Examples
+///
+/// ```ignore
+/// let queue = RenderQueue::new(queued);
+///
+/// for index in queue.order() {
+///     draw(&scene.objects[index]);
+/// }
+/// ```
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RenderQueue {
+    pub opaque: Vec<usize>,
+    pub transparent: Vec<usize>
+}
+
+
+impl RenderQueue {
+    /// Sort the queued objects into their opaque and transparent passes
+    pub fn new(queued: Vec<QueuedObject>) -> RenderQueue {
+        let (mut opaque, mut transparent): (Vec<QueuedObject>, Vec<QueuedObject>) =
+            queued.into_iter().partition(|object| !object.transparent);
+
+        opaque.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+
+        // every material takes the place of its nearest object
+        let mut materials: Vec<usize> = Vec::new();
+        opaque.iter().for_each(|object| if !materials.contains(&object.material) { materials.push(object.material) });
+        opaque.sort_by_key(|object| materials.iter().position(|&material| material == object.material));
+
+        transparent.sort_by(|a, b| b.distance.total_cmp(&a.distance));
+
+        RenderQueue { opaque: opaque.iter().map(|object| object.index).collect(),
+                      transparent: transparent.iter().map(|object| object.index).collect() }
+    }
+
+    /// The indices of the objects in the order they are drawn
+    pub fn order(&self) -> impl Iterator<Item = usize> + '_ {
+        self.opaque.iter().chain(&self.transparent).cloned()
+    }
+}
+
+
+/// The material of every texture, which is the index of the first texture that is the same
+pub fn materials(textures: &[&image::RgbImage]) -> Vec<usize> {
+    textures.iter().enumerate().map(|(index, texture)| {
+        textures[..index].iter()
+                         .position(|other| other.dimensions() == texture.dimensions() &&
+                                           other.as_ref() == texture.as_ref())
+                         .unwrap_or(index)
+    }).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_queue() {
+        let queued = |index: usize, distance: f64, material: usize, transparent: bool| {
+            QueuedObject { index, distance, material, transparent }
+        };
+
+        let queue = RenderQueue::new(vec![queued(0, 5.0, 0, false), queued(1, 1.0, 1, false),
+                                          queued(2, 2.0, 0, true), queued(3, 8.0, 1, false),
+                                          queued(4, 3.0, 2, false), queued(5, 6.0, 2, true)]);
+
+        // the nearest opaque object brings the rest of its material forward, and transparency goes last
+        assert_eq!(queue.opaque, vec![1, 3, 4, 0]);
+        assert_eq!(queue.transparent, vec![5, 2]);
+        assert_eq!(queue.order().collect::<Vec<usize>>(), vec![1, 3, 4, 0, 5, 2]);
+
+        let red = image::RgbImage::from_pixel(2, 2, image::Rgb([255, 0, 0]));
+        let blue = image::RgbImage::from_pixel(2, 2, image::Rgb([0, 0, 255]));
+        assert_eq!(materials(&[&red, &blue, &red.clone(), &image::RgbImage::new(1, 1)]), vec![0, 1, 0, 3]);
+
+        let triangle = b"v -1 -1 0\nv 1 -1 0\nv 0 1 -4\nvt 0 0\nvn 0 0 1\nf 1/1/1 2/1/1 3/1/1\n";
+        let coordinates = wavefront::Object::from_bytes(triangle, &wavefront::LoadOptions::default()).unwrap();
+        let model_view = Matrix4::new_translation(&Vector3::new(0.0, 0.0, -3.0));
+        assert_eq!(QueuedObject::new(0, &coordinates, &model_view, 0, false).distance, 5.0);
+    }
+}
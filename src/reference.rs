@@ -0,0 +1,314 @@
+use std::f64::consts::PI;
+
+use nalgebra::{Matrix3, Vector2, Vector3};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use error::MorpheusError;
+use render;
+use shader;
+use texture;
+use wavefront;
+
+
+/// How far a ray starts from the surface it leaves so it does not hit that surface again
+const RAY_OFFSET: f64 = 1e-6;
+
+
+/// Options that control the reference path tracer
+///
+/// Every pixel averages the given number of paths, and every path bounces off
+/// diffuse surfaces at most the given number of times, so without bounces only
+/// the direct light is traced. Rays that leave the scene see a uniform sky of
+/// the given brightness, where one is as bright as the light. The same seed
+/// always traces the same paths.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReferenceOptions {
+    pub samples: u32,
+    pub bounces: u32,
+    pub sky: f64,
+    pub seed: u64
+}
+
+
+impl Default for ReferenceOptions {
+    fn default() -> ReferenceOptions {
+        ReferenceOptions { samples: 16, bounces: 2, sky: 0.0, seed: 0 }
+    }
+}
+
+
+/// The nearest face hit by a ray, with the distance along the ray and the barycentric coordinate of the hit
+struct Hit {
+    face: usize,
+    distance: f64,
+    coordinate: Vector3<f64>
+}
+
+
+/// Find where a ray first hits a triangle of the Object with the Möller–Trumbore test
+fn intersect(coordinates: &wavefront::Object, origin: &Vector3<f64>, direction: &Vector3<f64>) -> Option<Hit> {
+    let mut nearest: Option<Hit> = None;
+
+    for (face, indices) in coordinates.geometric_faces.iter().enumerate() {
+        let a = coordinates.geometric_vertices[indices[0] as usize];
+        let (ab, ac) = (coordinates.geometric_vertices[indices[1] as usize] - a,
+                        coordinates.geometric_vertices[indices[2] as usize] - a);
+
+        let p = direction.cross(&ac);
+        let determinant = ab.dot(&p);
+
+        if determinant.abs() < 1e-12 {
+            continue;
+        }
+
+        let t = origin - a;
+        let u = t.dot(&p) / determinant;
+        let q = t.cross(&ab);
+        let v = direction.dot(&q) / determinant;
+        let distance = ac.dot(&q) / determinant;
+
+        if u < 0.0 || v < 0.0 || u + v > 1.0 || distance <= RAY_OFFSET {
+            continue;
+        }
+
+        if nearest.as_ref().is_none_or(|hit| distance < hit.distance) {
+            nearest = Some(Hit { face, distance, coordinate: Vector3::new(1.0 - u - v, u, v) });
+        }
+    }
+
+    nearest
+}
+
+
+/// Pick a direction around the given normal with a probability proportional to its cosine
+pub fn cosine_direction(normal: &Vector3<f64>, rng: &mut StdRng) -> Vector3<f64> {
+    let (r, angle) = (rng.gen::<f64>().sqrt(), 2.0 * PI * rng.gen::<f64>());
+    let tangent = if normal.x.abs() > 0.9 { Vector3::y() } else { Vector3::x() }.cross(normal).normalize();
+    let bitangent = normal.cross(&tangent);
+
+    (tangent * r * angle.cos() + bitangent * r * angle.sin() + normal * (1.0 - r * r).max(0.0).sqrt()).normalize()
+}
+
+
+/// Trace the light arriving along a ray, as a color from 0 to 255 in every channel
+#[allow(clippy::too_many_arguments)]
+fn trace(coordinates: &wavefront::Object, texture: &image::RgbImage, light_vector: &Vector3<f64>,
+         options: &ReferenceOptions, origin: &Vector3<f64>, direction: &Vector3<f64>,
+         bounces: u32, rng: &mut StdRng) -> Vector3<f64> {
+
+    let hit = match intersect(coordinates, origin, direction) {
+        Some(hit) => hit,
+        None => return Vector3::repeat(255.0 * options.sky)
+    };
+
+    let normals = Matrix3::from_columns(&[0, 1, 2].map(|i| {
+        coordinates.normal_vertices[coordinates.normal_faces[hit.face][i] as usize].normalize()
+    }));
+    let normal = (normals * hit.coordinate).normalize();
+    // faces are two sided, so the back of a face is shaded as if it faced the ray
+    let normal = if normal.dot(direction) > 0.0 { -normal } else { normal };
+    let uv: Vector2<f64> = (0..=2).map(|i| {
+        coordinates.texture_vertices[coordinates.texture_faces[hit.face][i] as usize] * hit.coordinate[i]
+    }).sum();
+
+    let albedo = texture::sample(texture, &uv);
+    let albedo = Vector3::from_fn(|i, _| albedo[i] as f64 / 255.0);
+
+    let point = origin + direction * hit.distance;
+    let cosine = normal.dot(light_vector);
+    let lit = cosine > 0.0 && intersect(coordinates, &(point + normal * RAY_OFFSET), light_vector).is_none();
+    let mut radiance = if lit { Vector3::repeat(255.0 * cosine) } else { Vector3::zeros() };
+
+    if bounces > 0 {
+        // cosine weighted bounces cancel the cosine and the pi of a diffuse surface
+        let bounce = cosine_direction(&normal, rng);
+        radiance += trace(coordinates, texture, light_vector, options, &(point + normal * RAY_OFFSET), &bounce,
+                          bounces - 1, rng);
+    }
+
+    radiance.component_mul(&albedo)
+}
+
+
+/// Render a ground truth image of an Object lit by a directional light by tracing paths through it
+///
+/// The surfaces are diffuse, take their color from the texture, and are shaded
+/// with their interpolated normals, which is what the Gouraud shader
+/// approximates, but faces are two sided, shadow each other, and light
+/// bounces between them. The camera and the light match those of draw_object
+/// and every path goes through the middle of its pixel, so the rasterized
+/// render of the same Object can be compared with the reference pixel by
+/// pixel. Only the pixels that see the Object are written. Tracing tests
+/// every face for every ray, so it is only meant for small meshes.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::reference::*;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let (eye, center, up) = (Vector3::<f64>::new(0.0, 0.0, 3.0), Vector3::<f64>::zeros(), Vector3::<f64>::y());
+/// # let light_vector = Vector3::<f64>::z();
+/// let mut reference = image::ImageBuffer::new(128, 128);
+/// let options = ReferenceOptions { bounces: 0, ..Default::default() };
+///
+/// render_reference(&coordinates, &texture, &mut reference, &options, &light_vector, &eye, &center, &up)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+#[allow(clippy::too_many_arguments)]
+pub fn render_reference(coordinates: &wavefront::Object, texture: &image::RgbImage, buffer: &mut image::RgbImage,
+                        options: &ReferenceOptions, light_vector: &Vector3<f64>, eye: &Vector3<f64>,
+                        center: &Vector3<f64>, up: &Vector3<f64>) -> Result<(), MorpheusError> {
+
+    let (width, height) = buffer.dimensions();
+    render::validate(width, height, light_vector, eye, center, up)?;
+
+    // undo the viewport and projection of the rasterizer, which put the image plane through the center
+    let view_port = shader::viewport(width / 8, height / 8, width * 3 / 4, height * 3 / 4, 255);
+    let view_to_world = shader::lookat(eye, center, up).fixed_slice::<nalgebra::U3, nalgebra::U3>(0, 0).transpose();
+    let distance = (eye - center).norm();
+
+    let mut rng = StdRng::seed_from_u64(options.seed);
+
+    for y in 0..height {
+        for x in 0..width {
+            let ndc = Vector2::new((x as f64 - view_port[(0, 3)]) / view_port[(0, 0)],
+                                   (y as f64 - view_port[(1, 3)]) / view_port[(1, 1)]);
+            let direction = (view_to_world * Vector3::new(ndc.x, ndc.y, -distance)).normalize();
+
+            if intersect(coordinates, eye, &direction).is_none() {
+                continue;
+            }
+
+            let samples = options.samples.max(1);
+            let color: Vector3<f64> = (0..samples).map(|_| {
+                trace(coordinates, texture, light_vector, options, eye, &direction, options.bounces, &mut rng)
+            }).sum::<Vector3<f64>>() / samples as f64;
+
+            buffer.put_pixel(x, y, shader::to_color(color.map(|channel| channel.min(255.0))));
+        }
+    }
+
+    Ok(())
+}
+
+
+/// Find the mean absolute difference of the channels of two images of the same size, from 0 to 255
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::reference;
+/// # use rastermind::reference::*;
+/// # use rastermind::{render, wavefront};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let (eye, center, up) = (Vector3::<f64>::new(0.0, 0.0, 3.0), Vector3::<f64>::zeros(), Vector3::<f64>::y());
+/// # let light_vector = Vector3::<f64>::z();
+/// # let mut reference = image::RgbImage::new(32, 32);
+/// # let mut rasterized = image::RgbImage::new(32, 32);
+/// # render_reference(&coordinates, &texture, &mut reference, &Default::default(), &light_vector, &eye, &center, &up)?;
+/// # render::draw_object(&coordinates, &mut rasterized, &texture, 255, &light_vector, &eye, &center, &up);
+/// assert!(reference::mean_error(&reference, &rasterized) < 2.0);
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn mean_error(reference: &image::RgbImage, image: &image::RgbImage) -> f64 {
+    let total: f64 = reference.pixels().zip(image.pixels())
+                              .flat_map(|(a, b)| (0..3).map(move |i| (a[i] as f64 - b[i] as f64).abs()))
+                              .sum();
+
+    total / (3 * reference.width() * reference.height()).max(1) as f64
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn white() -> image::RgbImage {
+        image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]))
+    }
+
+    fn load(bytes: &[u8]) -> wavefront::Object {
+        wavefront::Object::from_bytes(bytes, &wavefront::LoadOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn test_matches_rasterizer() {
+        let quad = load(b"v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\nvt 0 0\nvn 0 0 1\n\
+                          f 1/1/1 2/1/1 3/1/1\nf 1/1/1 3/1/1 4/1/1\n");
+        let light_vector = Vector3::new(0.0, 0.6, 0.8);
+        let (eye, center, up) = (Vector3::new(0.5, 0.5, 3.0), Vector3::zeros(), Vector3::y());
+        let options = ReferenceOptions { bounces: 0, samples: 1, ..Default::default() };
+
+        let mut reference = image::RgbImage::new(32, 32);
+        render_reference(&quad, &white(), &mut reference, &options, &light_vector, &eye, &center, &up).unwrap();
+
+        let mut rasterized = image::RgbImage::new(32, 32);
+        render::draw_object(&quad, &mut rasterized, &white(), 255, &light_vector, &eye, &center, &up);
+
+        // both cover the same pixels of a flat quad and light them the same, but for rounding
+        assert_eq!(*reference.get_pixel(16, 16), image::Rgb([204, 204, 204]));
+        assert!(reference.pixels().zip(rasterized.pixels()).all(|(a, b)| (a[0] as i32 - b[0] as i32).abs() <= 1));
+        assert!(mean_error(&reference, &rasterized) < 2.0);
+        assert_eq!(mean_error(&reference, &reference), 0.0);
+    }
+
+    #[test]
+    fn test_shadows_and_bounces() {
+        let ground = b"v -4 0 -4\nv 4 0 -4\nv 4 0 4\nv -4 0 4\nvt 0 0\nvn 0 1 0\n\
+                       f 1/1/1 4/1/1 3/1/1\nf 1/1/1 3/1/1 2/1/1\n";
+        let blocker = b"v -1 1 -1\nv 1 1 -1\nv 1 1 1\nv -1 1 1\nvt 0 0\nvn 0 1 0\n\
+                        f 1/1/1 4/1/1 3/1/1\nf 1/1/1 3/1/1 2/1/1\n";
+        let mut scene = load(ground);
+        let blocker = load(blocker);
+
+        // the blocker hangs over the middle of the ground, which the camera sees from below it
+        let offset = scene.geometric_vertices.len() as i32;
+        scene.geometric_vertices.extend(blocker.geometric_vertices);
+        scene.geometric_faces.extend(blocker.geometric_faces.iter().map(|face| face.add_scalar(offset)));
+        scene.texture_faces.extend(blocker.texture_faces);
+        scene.normal_faces.extend(blocker.normal_faces);
+
+        let (eye, center, up) = (Vector3::new(0.0, 0.5, 4.0), Vector3::new(0.0, 0.5, 0.0), Vector3::y());
+        let direct = ReferenceOptions { bounces: 0, samples: 1, ..Default::default() };
+        let mut buffer = image::RgbImage::new(32, 32);
+        render_reference(&scene, &white(), &mut buffer, &direct, &Vector3::y(), &eye, &center, &up).unwrap();
+
+        // the ground under the blocker is in its shadow, and the ground past it is lit
+        let (shadowed, lit) = (*buffer.get_pixel(16, 10), *buffer.get_pixel(2, 10));
+        assert_eq!(shadowed, image::Rgb([0, 0, 0]));
+        assert_eq!(lit, image::Rgb([255, 255, 255]));
+
+        // a bounce off the ground lights the underside of the blocker, which faces away from the light
+        let bounced = ReferenceOptions { bounces: 1, samples: 64, ..Default::default() };
+        render_reference(&scene, &white(), &mut buffer, &bounced, &Vector3::y(), &eye, &center, &up).unwrap();
+        assert!(buffer.get_pixel(16, 22)[0] > 0);
+
+        // every path that leaves an open plane sees the sky, whatever direction it bounces in
+        let open = load(ground);
+        let sky = ReferenceOptions { bounces: 1, samples: 4, sky: 0.5, ..Default::default() };
+        render_reference(&open, &white(), &mut buffer, &sky, &-Vector3::y(), &eye, &center, &up).unwrap();
+        assert_eq!(*buffer.get_pixel(16, 4), image::Rgb([127, 127, 127]));
+
+        assert!(render_reference(&open, &white(), &mut image::RgbImage::new(0, 0), &sky, &Vector3::y(),
+                                 &eye, &center, &up).is_err());
+    }
+}
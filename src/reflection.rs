@@ -0,0 +1,253 @@
+use nalgebra::{Matrix3, Matrix4, Vector3, Vector4};
+
+use clipping::ClipPlane;
+use light::Light;
+use refraction;
+use shader::{Contributions, Shader};
+use vector;
+use wavefront;
+
+
+/// A mirror plane that reflects the rest of a scene onto the object it belongs to
+///
+/// The plane goes through the point and faces along the normal, and the
+/// object is meant to lie in it, such as a floor or the surface of water.
+/// The index of refraction sets how much light the plane reflects, which is
+/// little when looking straight down onto it and grows to all of the light
+/// at grazing angles, as with the Fresnel equations for water at 1.33 or
+/// polished stone at about 1.5.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::reflection::PlanarReflection;
+/// # use rastermind::scene::SceneObject;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// let mut water = SceneObject::new(wavefront::Object::new("water.obj")?, texture);
+/// water.reflection = Some(PlanarReflection::new(&Vector3::zeros(), &Vector3::y(), 1.33));
+/// # Ok(())
+/// # }
+/// ```
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlanarReflection {
+    pub point: Vector3<f64>,
+    pub normal: Vector3<f64>,
+    pub ior: f64
+}
+
+
+impl PlanarReflection {
+    /// Create a new PlanarReflection through the given point facing along the given normal
+    pub fn new(point: &Vector3<f64>, normal: &Vector3<f64>, ior: f64) -> PlanarReflection {
+        PlanarReflection { point: *point, normal: normal.normalize(), ior }
+    }
+
+    /// The matrix that mirrors points about the plane
+    pub fn matrix(&self) -> Matrix4<f64> {
+        let mut matrix = Matrix4::identity();
+        let reflect = Matrix3::identity() - self.normal * self.normal.transpose() * 2.0;
+
+        matrix.fixed_slice_mut::<nalgebra::U3, nalgebra::U3>(0, 0).copy_from(&reflect);
+        matrix.fixed_slice_mut::<nalgebra::U3, nalgebra::U1>(0, 3)
+              .copy_from(&(self.normal * 2.0 * self.normal.dot(&self.point)));
+
+        matrix
+    }
+
+    /// Mirror a direction about the plane
+    pub fn reflect(&self, direction: &Vector3<f64>) -> Vector3<f64> {
+        refraction::reflect(direction, &self.normal)
+    }
+
+    /// Mirror a light about the plane, so the mirrored scene is lit as the scene would be seen in the mirror
+    pub fn reflect_light(&self, light: &Light) -> Light {
+        match *light {
+            Light::Directional(direction) => Light::Directional(self.reflect(&direction)),
+            Light::Spot(spotlight) => {
+                let mut mirrored = spotlight;
+                mirrored.position = self.point + self.reflect(&(spotlight.position - self.point));
+                mirrored.direction = self.reflect(&spotlight.direction);
+
+                Light::Spot(mirrored)
+            }
+        }
+    }
+
+    /// Mirror a clipping plane about the plane, so it cuts away the same geometry of the mirrored scene
+    pub fn reflect_clip_plane(&self, plane: &ClipPlane) -> ClipPlane {
+        let point = self.matrix().transform_point(&nalgebra::Point3::from(plane.normal * -plane.distance));
+
+        ClipPlane::new(&point.coords, &self.reflect(&plane.normal))
+    }
+
+    /// The clipping plane that cuts away the mirrored geometry that was behind the plane before it was mirrored
+    pub fn clip_plane(&self) -> ClipPlane {
+        ClipPlane::new(&self.point, &-self.normal)
+    }
+
+    /// The fraction of the light reflected when looking at the plane at the given cosine from its normal
+    pub fn fresnel(&self, cosine: f64) -> f64 {
+        refraction::fresnel(cosine.abs(), self.ior)
+    }
+}
+
+
+/// Blend the mirrored scene onto the fragments of another shader by the Fresnel falloff of the plane
+///
+/// The reflection is an image of the scene mirrored about the plane, drawn
+/// from the same camera and at the same size as the render, along with its
+/// depth buffer. Fragments take the reflection at their own pixel, blended by
+/// how much of the light the plane reflects when seen from the eye, and keep
+/// their color where the reflection is empty.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::reflection::*;
+/// # use rastermind::shader::GouraudShader;
+/// # let eye = Vector3::new(0.0, 1.0, 3.0);
+/// # let reflection = PlanarReflection::new(&Vector3::zeros(), &Vector3::y(), 1.33);
+/// # let mirrored = image::RgbImage::new(64, 64);
+/// # let depths = vec![0.0; 64 * 64];
+/// let shader = ReflectionShader::new(Box::new(GouraudShader::new()), &reflection, &mirrored, &depths, &eye);
+/// ```
+///
+pub struct ReflectionShader<'a> {
+    pub shader: Box<dyn Shader + 'a>,
+    pub reflection: &'a PlanarReflection,
+    pub image: &'a image::RgbImage,
+    pub depths: &'a [f64],
+    pub eye: Vector3<f64>,
+    pub varying_position: Matrix3<f64>,
+    pub varying_screen: Matrix3<f64>,
+    pub varying_w: Vector3<f64>
+}
+
+
+impl<'a> ReflectionShader<'a> {
+    /// Create a new instance of a ReflectionShader that reflects the given image onto the given shader
+    pub fn new(shader: Box<dyn Shader + 'a>, reflection: &'a PlanarReflection, image: &'a image::RgbImage,
+               depths: &'a [f64], eye: &Vector3<f64>) -> ReflectionShader<'a> {
+
+        ReflectionShader { shader, reflection, image, depths, eye: *eye, varying_position: Matrix3::zeros(),
+                           varying_screen: Matrix3::zeros(), varying_w: Vector3::zeros() }
+    }
+
+    /// The color of the reflection at the fragment and how much of it is blended in, if anything is reflected there
+    fn reflected(&self, vertex: Vector3<f64>) -> Option<(image::Rgb<u8>, f64)> {
+        let screen = self.varying_screen * vertex;
+        let (x, y) = (screen.x.round(), screen.y.round());
+        let (width, height) = self.image.dimensions();
+
+        if x < 0.0 || y < 0.0 || x >= width as f64 || y >= height as f64 {
+            return None;
+        }
+
+        if self.depths.get((x as u32 + y as u32 * width) as usize).is_none_or(|&depth| depth == -1.0) {
+            return None;
+        }
+
+        // positions must be interpolated in perspective, or the falloff slides across large planes
+        let perspective = vertex.component_div(&self.varying_w);
+        let position = self.varying_position * perspective / perspective.sum();
+        let cosine = (self.eye - position).try_normalize(0.0).map_or(1.0, |view| view.dot(&self.reflection.normal));
+
+        Some((*self.image.get_pixel(x as u32, y as u32), self.reflection.fresnel(cosine)))
+    }
+}
+
+
+impl<'a> Shader for ReflectionShader<'a> {
+    /// Position the vertices with the inner shader and keep their positions in the scene and on the screen
+    fn vertex(&mut self, coordinates: &wavefront::Object,
+              view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+              model_view: &Matrix4<f64>, light_vector: &Vector3<f64>,
+              face_index: usize, vertex_index: usize) -> Vector4<f64> {
+
+        let geometric_index = coordinates.geometric_faces[face_index][vertex_index] as usize;
+        self.varying_position.set_column(vertex_index, &coordinates.geometric_vertices[geometric_index]);
+
+        let screen_coordinate = self.shader.vertex(coordinates, view_port, projection, model_view,
+                                                   light_vector, face_index, vertex_index);
+        self.varying_w[vertex_index] = screen_coordinate.w;
+        self.varying_screen.set_column(vertex_index, &vector::project_to_3d(&screen_coordinate));
+
+        screen_coordinate
+    }
+
+    /// Blend the reflection over the color of the inner shader
+    fn fragment(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> image::Rgb<u8> {
+        let mut color = self.shader.fragment(vertex, texture);
+
+        if let Some((reflected, fresnel)) = self.reflected(vertex) {
+            (0..=2).for_each(|i| {
+                color[i] = (color[i] as f64 * (1.0 - fresnel) + reflected[i] as f64 * fresnel).round() as u8;
+            });
+        }
+
+        color
+    }
+
+    /// Discard the fragments the inner shader discards
+    fn discard(&self, vertex: Vector3<f64>) -> bool {
+        self.shader.discard(vertex)
+    }
+
+    /// Report the blended reflection as specular light, taken from the light of the inner shader
+    fn contributions(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> Contributions {
+        let contributions = self.shader.contributions(vertex, texture);
+
+        match self.reflected(vertex) {
+            Some((reflected, fresnel)) => {
+                let specular = image::Rgb([0, 1, 2].map(|i| (reflected[i] as f64 * fresnel).round() as u8));
+
+                contributions.scale_light(1.0 - fresnel)
+                             .add(&Contributions { specular, ..Default::default() })
+            },
+            None => contributions
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_planar_reflection() {
+        let reflection = PlanarReflection::new(&Vector3::new(0.0, 1.0, 0.0), &(Vector3::y() * 2.0), 1.5);
+
+        let mirrored = reflection.matrix().transform_point(&nalgebra::Point3::new(1.0, 3.0, 2.0));
+        assert!((mirrored.coords - Vector3::new(1.0, -1.0, 2.0)).norm() < 1e-12);
+        assert_eq!(reflection.reflect(&Vector3::new(1.0, 1.0, 0.0)), Vector3::new(1.0, -1.0, 0.0));
+
+        match reflection.reflect_light(&Light::Directional(Vector3::y())) {
+            Light::Directional(direction) => assert_eq!(direction, -Vector3::y()),
+            Light::Spot(_) => panic!("a directional light stays directional")
+        }
+
+        // mirrored geometry is kept beneath the plane only
+        assert!(reflection.clip_plane().keeps(&Vector3::new(0.0, 0.5, 0.0)));
+        assert!(!reflection.clip_plane().keeps(&Vector3::new(0.0, 1.5, 0.0)));
+
+        let clipped = reflection.reflect_clip_plane(&ClipPlane::new(&Vector3::new(3.0, 0.0, 0.0), &Vector3::x()));
+        assert!(clipped.keeps(&Vector3::new(4.0, -2.0, 0.0)) && !clipped.keeps(&Vector3::new(2.0, -2.0, 0.0)));
+
+        // looking straight down reflects the least light, and grazing views reflect all of it
+        assert!((reflection.fresnel(1.0) - 0.04).abs() < 1e-12);
+        assert!((reflection.fresnel(0.0) - 1.0).abs() < 1e-12);
+        assert!(reflection.fresnel(0.5) > 0.04 && reflection.fresnel(0.5) < 0.1);
+    }
+}
@@ -0,0 +1,289 @@
+use std::f64::consts::PI;
+
+use nalgebra::{Matrix3, Matrix4, Vector2, Vector3, Vector4};
+
+use shader::Shader;
+use texture;
+use vector;
+use wavefront;
+
+
+/// What a refractive surface shows through itself
+///
+/// An environment is an equirectangular map of the directions around the
+/// scene, loaded like a texture with its top row pointing up, which glass
+/// both refracts and reflects. A background is the buffer of everything
+/// drawn before the glass, which is seen through the glass shifted by how
+/// far the view bends, where the thickness is the shift in pixels for a
+/// view that is bent by one radian.
+///
+#[derive(Clone, Copy, Debug)]
+pub enum Backdrop<'a> {
+    Environment(&'a image::RgbImage),
+    Background { buffer: &'a image::RgbImage, thickness: f64 }
+}
+
+
+/// Bend the given direction through a surface with the given normal and ratio of indices of refraction
+///
+/// The direction points towards the surface and the normal away from it on
+/// the side the direction comes from. There is no refracted direction when
+/// the light is totally reflected inside the surface.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::refraction::refract;
+/// // entering glass head on does not bend the view
+/// assert_eq!(refract(&-Vector3::z(), &Vector3::z(), 1.0 / 1.5), Some(-Vector3::z()));
+/// ```
+///
+pub fn refract(direction: &Vector3<f64>, normal: &Vector3<f64>, ratio: f64) -> Option<Vector3<f64>> {
+    let cosine = -normal.dot(direction);
+    let k = 1.0 - ratio * ratio * (1.0 - cosine * cosine);
+
+    if k < 0.0 {
+        None
+    } else {
+        Some(direction * ratio + normal * (ratio * cosine - k.sqrt()))
+    }
+}
+
+
+/// Mirror the given direction about a surface with the given normal
+pub fn reflect(direction: &Vector3<f64>, normal: &Vector3<f64>) -> Vector3<f64> {
+    direction - normal * 2.0 * normal.dot(direction)
+}
+
+
+/// Find how much light a surface with the given index of refraction reflects, using Schlick's approximation
+pub fn fresnel(cosine: f64, ior: f64) -> f64 {
+    let f0 = ((ior - 1.0) / (ior + 1.0)).powi(2);
+
+    f0 + (1.0 - f0) * (1.0 - cosine.clamp(0.0, 1.0)).powi(5)
+}
+
+
+/// Find the texture coordinate of the given direction in an equirectangular environment map
+///
+/// The center of the map looks along -z with y up.
+///
+pub fn environment_uv(direction: &Vector3<f64>) -> Vector2<f64> {
+    let direction = direction.normalize();
+
+    Vector2::new(0.5 + direction.x.atan2(-direction.z) / (2.0 * PI),
+                 0.5 + direction.y.clamp(-1.0, 1.0).asin() / PI)
+}
+
+
+/// Shade glass-like surfaces by bending the view through them with the given index of refraction
+///
+/// The color seen through the glass is tinted, and with an environment the
+/// light the glass reflects is added by the Fresnel term, so glass seen at
+/// grazing angles turns into a mirror. A background has nothing to reflect,
+/// so only the light seen through the glass is drawn. Faces that point away
+/// from the camera are the inside of the glass, where the view leaves it.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate image;
+/// # extern crate rastermind;
+/// # use rastermind::refraction::*;
+/// # use rastermind::texture;
+/// # fn main() -> Result<(), rastermind::Error> {
+/// let environment = texture::open("studio.png")?;
+/// let shader = RefractionShader::new(Backdrop::Environment(&environment), 1.5, image::Rgb([230, 255, 240]));
+/// # Ok(())
+/// # }
+/// ```
+///
+pub struct RefractionShader<'a> {
+    pub backdrop: Backdrop<'a>,
+    pub ior: f64,
+    pub tint: image::Rgb<u8>,
+    pub camera_distance: f64,
+    pub view_to_world: Matrix3<f64>,
+    pub varying_position: Matrix3<f64>,
+    pub varying_normal: Matrix3<f64>,
+    pub varying_screen: Matrix3<f64>,
+    pub varying_w: Vector3<f64>
+}
+
+
+impl<'a> RefractionShader<'a> {
+    /// Create a new instance of a RefractionShader that shows the backdrop through glass
+    pub fn new(backdrop: Backdrop<'a>, ior: f64, tint: image::Rgb<u8>) -> RefractionShader<'a> {
+        RefractionShader { backdrop, ior, tint, camera_distance: 0.0, view_to_world: Matrix3::identity(),
+                           varying_position: Matrix3::zeros(), varying_normal: Matrix3::zeros(),
+                           varying_screen: Matrix3::zeros(), varying_w: Vector3::zeros() }
+    }
+
+    /// Find the color of the backdrop along the given direction in view space seen from the given pixel
+    fn sample(&self, direction: &Vector3<f64>, view: &Vector3<f64>, pixel: &Vector3<f64>) -> image::Rgb<u8> {
+        match self.backdrop {
+            Backdrop::Environment(environment) => {
+                texture::sample(environment, &environment_uv(&(self.view_to_world * direction)))
+            },
+            Backdrop::Background { buffer, thickness } => {
+                let shift = (direction - view) * thickness;
+                let x = (pixel.x + shift.x).round().max(0.0).min(buffer.width() as f64 - 1.0);
+                let y = (pixel.y + shift.y).round().max(0.0).min(buffer.height() as f64 - 1.0);
+
+                *buffer.get_pixel(x as u32, y as u32)
+            }
+        }
+    }
+}
+
+
+impl<'a> Shader for RefractionShader<'a> {
+    /// Position the vertices into their scene coordinates and keep their positions and normals in view space
+    fn vertex(&mut self, coordinates: &wavefront::Object,
+              view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+              model_view: &Matrix4<f64>, _light_vector: &Vector3<f64>,
+              face_index: usize, vertex_index: usize) -> Vector4<f64> {
+
+        let geometric_index = coordinates.geometric_faces[face_index][vertex_index] as usize;
+        let normal_index = coordinates.normal_faces[face_index][vertex_index] as usize;
+
+        let gl_vertex = vector::vectorize_to_4d(&coordinates.geometric_vertices[geometric_index]);
+        let normal = coordinates.normal_vertices[normal_index];
+
+        // the projection divides by 1 - z / distance, so the camera is at the distance along z
+        self.camera_distance = if projection[(3, 2)] == 0.0 { 0.0 } else { -1.0 / projection[(3, 2)] };
+        self.view_to_world = model_view.fixed_slice::<nalgebra::U3, nalgebra::U3>(0, 0).transpose();
+
+        self.varying_position.set_column(vertex_index, &(model_view * gl_vertex).remove_row(3));
+        self.varying_normal.set_column(vertex_index,
+                                       &(model_view * Vector4::new(normal.x, normal.y, normal.z, 0.0)).remove_row(3));
+
+        let screen_coordinate = view_port * projection * model_view * gl_vertex;
+        self.varying_w[vertex_index] = screen_coordinate.w;
+        self.varying_screen.set_column(vertex_index, &vector::project_to_3d(&screen_coordinate));
+
+        screen_coordinate
+    }
+
+    /// Mix the tinted light seen through the glass with the light it reflects
+    fn fragment(&self, vertex: Vector3<f64>, _texture: &image::RgbImage) -> image::Rgb<u8> {
+        let perspective = vertex.component_div(&self.varying_w);
+        let perspective = perspective / perspective.sum();
+
+        let position = self.varying_position * perspective;
+        let view = if self.camera_distance > 0.0 {
+            (position - Vector3::new(0.0, 0.0, self.camera_distance)).normalize()
+        } else {
+            -Vector3::z()
+        };
+
+        let mut normal = (self.varying_normal * perspective).try_normalize(0.0).unwrap_or_else(Vector3::z);
+        let mut ratio = 1.0 / self.ior;
+
+        // the view leaves the glass through faces that point away from the camera
+        if normal.dot(&view) > 0.0 {
+            normal = -normal;
+            ratio = self.ior;
+        }
+
+        let pixel = self.varying_screen * vertex;
+        let reflected = reflect(&view, &normal);
+
+        let (through, reflectance) = match refract(&view, &normal, ratio) {
+            Some(refracted) => (refracted, fresnel(-normal.dot(&view), self.ior)),
+            None => (reflected, 1.0)
+        };
+        let reflectance = match self.backdrop {
+            Backdrop::Environment(_) => reflectance,
+            Backdrop::Background { .. } => 0.0
+        };
+
+        let seen = self.sample(&through, &view, &pixel);
+        let mirrored = self.sample(&reflected, &view, &pixel);
+
+        image::Rgb([0, 1, 2].map(|i| {
+            let transmitted = seen[i] as f64 * self.tint[i] as f64 / 255.0;
+            (transmitted * (1.0 - reflectance) + mirrored[i] as f64 * reflectance).round().min(255.0) as u8
+        }))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use render;
+    use shader;
+
+    #[test]
+    fn test_refract() {
+        let ratio = 1.0 / 1.5;
+        let direction = Vector3::new(1.0, 0.0, -1.0).normalize();
+        let refracted = refract(&direction, &Vector3::z(), ratio).unwrap();
+
+        // Snell's law holds and the view bends towards the normal as it enters
+        assert!((refracted.norm() - 1.0).abs() < 1e-12);
+        assert!((refracted.x - direction.x * ratio).abs() < 1e-12);
+        assert!(refracted.x < direction.x);
+
+        // leaving at a grazing angle is totally reflected
+        assert_eq!(refract(&Vector3::new(0.9, 0.0, -0.1).normalize(), &Vector3::z(), 1.5), None);
+        assert_eq!(reflect(&direction, &Vector3::z()), Vector3::new(direction.x, 0.0, -direction.z));
+
+        assert!((fresnel(1.0, 1.5) - 0.04).abs() < 1e-12);
+        assert_eq!(fresnel(0.0, 1.5), 1.0);
+    }
+
+    #[test]
+    fn test_environment_uv() {
+        assert_eq!(environment_uv(&-Vector3::z()), Vector2::new(0.5, 0.5));
+        assert_eq!(environment_uv(&Vector3::y()).y, 1.0);
+        assert_eq!(environment_uv(&Vector3::x()), Vector2::new(0.75, 0.5));
+    }
+
+    #[test]
+    fn test_refraction_shader() {
+        let square = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\nvt 0 0\nvn 0 0 1\n\
+                                                     f 1/1/1 2/1/1 3/1/1\nf 1/1/1 3/1/1 4/1/1\n",
+                                                   &wavefront::LoadOptions::default()).unwrap();
+        let background = image::RgbImage::from_fn(32, 32, |x, y| image::Rgb([x as u8 * 8, y as u8 * 8, 100]));
+        let environment = image::RgbImage::from_fn(4, 2, |x, y| image::Rgb([x as u8 * 60, y as u8 * 200, 50]));
+
+        let draw = |backdrop: Backdrop, ior: f64| {
+            let mut buffer = image::RgbImage::new(32, 32);
+            let mut zbuffer = vec![-1.0; 32 * 32];
+
+            render::draw_faces(&square, &mut buffer, &image::RgbImage::new(1, 1), &mut zbuffer,
+                               &render::DepthOptions::default(), &shader::viewport(4, 4, 24, 24, 255),
+                               &shader::projection(-1.0 / 3.0), &shader::lookat(&Vector3::new(0.0, 0.0, 3.0),
+                                                                                 &Vector3::zeros(), &Vector3::y()),
+                               &Vector3::z(), |_| -> Box<dyn Shader> {
+                Box::new(RefractionShader::new(backdrop, ior, image::Rgb([255, 255, 255])))
+            });
+
+            buffer
+        };
+
+        // without bending, the background is seen through the glass unchanged
+        let background_backdrop = Backdrop::Background { buffer: &background, thickness: 20.0 };
+        let clear = draw(background_backdrop, 1.0);
+        assert_eq!(*clear.get_pixel(16, 16), *background.get_pixel(16, 16));
+        assert_eq!(*clear.get_pixel(6, 20), *background.get_pixel(6, 20));
+
+        // glass bends the view towards its normal, so what is seen near its edges moves towards its center
+        let glass = draw(background_backdrop, 1.5);
+        assert_eq!(*glass.get_pixel(16, 16), *background.get_pixel(16, 16));
+        assert!(glass.get_pixel(6, 16)[0] > background.get_pixel(6, 16)[0]);
+
+        // looking straight through the environment shows its center, mixed with a little of what is behind the camera
+        let center = environment.get_pixel(2, 1);
+        let behind = environment.get_pixel(3, 1);
+
+        assert_eq!(*draw(Backdrop::Environment(&environment), 1.0).get_pixel(16, 16), *center);
+        assert_eq!(draw(Backdrop::Environment(&environment), 1.5).get_pixel(16, 16)[0],
+                   (center[0] as f64 * 0.96 + behind[0] as f64 * 0.04).round() as u8);
+    }
+}
@@ -0,0 +1,159 @@
+#[cfg(feature = "http")]
+use std::io::Read;
+
+use error::MorpheusError;
+
+
+/// Whether the given path of a model or texture is an http or https URL rather than a file
+///
+/// # Examples
+///
+/// ```
+/// # use rastermind::remote;
+/// assert!(remote::is_url("https://example.com/african_head.obj"));
+/// assert!(!remote::is_url("african_head.obj"));
+/// ```
+///
+pub fn is_url(path: &str) -> bool {
+    let lowercase = path.get(..8).unwrap_or(path).to_lowercase();
+
+    lowercase.starts_with("http://") || lowercase.starts_with("https://")
+}
+
+
+/// The part of the given URL that names the file, without its query or fragment
+///
+/// Loaders are chosen by the extension of the file, which the query of a
+/// URL would otherwise hide.
+///
+pub fn file_part(url: &str) -> &str {
+    url.split(['?', '#']).next().unwrap_or(url)
+}
+
+
+/// Download the contents of the model or texture at the given URL
+///
+/// Only builds with the http feature can download files. A response whose
+/// status is not a success fails with the status in the error. Downloads are
+/// not cached, so every load of the same URL downloads it again.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use rastermind::remote;
+/// let bytes = remote::fetch("https://example.com/african_head.obj").unwrap();
+/// ```
+///
+#[cfg(feature = "http")]
+pub fn fetch(url: &str) -> Result<Vec<u8>, MorpheusError> {
+    let response = ureq::get(url).call().map_err(|error| MorpheusError::asset(url, error))?;
+    let mut bytes = Vec::new();
+
+    response.into_reader().read_to_end(&mut bytes).map_err(|error| MorpheusError::io(url, error))?;
+
+    Ok(bytes)
+}
+
+
+/// Download the contents of the model or texture at the given URL, which needs a build with the http feature
+#[cfg(not(feature = "http"))]
+pub fn fetch(url: &str) -> Result<Vec<u8>, MorpheusError> {
+    Err(MorpheusError::asset(url, "URLs can only be loaded in builds with the http feature"))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "http")]
+    use std::io::{BufRead, BufReader, Write};
+    #[cfg(feature = "http")]
+    use std::net::TcpListener;
+    #[cfg(feature = "http")]
+    use std::thread;
+
+    #[cfg(feature = "http")]
+    use plugins;
+    #[cfg(feature = "http")]
+    use texture;
+    #[cfg(feature = "http")]
+    use wavefront;
+
+    /// Serve the given files over http on a free local port until the tests end, returning the address
+    #[cfg(feature = "http")]
+    fn serve(files: Vec<(&'static str, Vec<u8>)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut request = String::new();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                reader.read_line(&mut request).unwrap();
+
+                // the headers are read up to the blank line that ends them
+                let mut header = String::new();
+                while reader.read_line(&mut header).unwrap() > 2 {
+                    header.clear();
+                }
+
+                let path = request.split(' ').nth(1).unwrap_or("").split('?').next().unwrap_or("");
+                let response = match files.iter().find(|(name, _)| *name == path) {
+                    Some((_, body)) => [format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                                                body.len()).as_bytes(), body].concat(),
+                    None => b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec()
+                };
+
+                stream.write_all(&response).unwrap();
+            }
+        });
+
+        address
+    }
+
+    #[test]
+    fn test_is_url() {
+        assert!(is_url("http://localhost:8000/head.obj"));
+        assert!(is_url("HTTPS://example.com/diffuse.png"));
+        assert!(!is_url("textures/http.png"));
+        assert!(!is_url("http:/head.obj"));
+        assert!(!is_url(""));
+
+        assert_eq!(file_part("https://example.com/head.fbx?version=2#top"), "https://example.com/head.fbx");
+        assert_eq!(file_part("head.fbx"), "head.fbx");
+    }
+
+    #[test]
+    #[cfg(feature = "http")]
+    fn test_fetch() {
+        let mut png = Vec::new();
+        image::png::PNGEncoder::new(&mut png).encode(&[255, 0, 0, 0, 0, 255], 2, 1, image::ColorType::RGB(8)).unwrap();
+
+        let obj = b"v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n".to_vec();
+        let address = serve(vec![("/head.obj", obj.clone()), ("/head.remote", obj), ("/diffuse.png", png)]);
+
+        let object = wavefront::Object::new(&format!("{}/head.obj", address)).unwrap();
+        assert_eq!(object.geometric_vertices.len(), 3);
+
+        // the query is not part of the extension that picks the loader
+        plugins::register_loader("remote", |bytes| wavefront::Object::from_bytes(bytes, &Default::default())
+                                                       .map_err(|error| error.to_string()));
+        let object = plugins::open(&format!("{}/head.remote?version=2", address)).unwrap();
+        assert_eq!(object.geometric_faces.len(), 1);
+
+        let texture = texture::open(&format!("{}/diffuse.png", address)).unwrap();
+        assert_eq!(*texture.get_pixel(1, 0), image::Rgb([0, 0, 255]));
+
+        assert!(fetch(&format!("{}/missing.obj", address)).is_err());
+        assert!(texture::open(&format!("{}/head.obj", address)).is_err());
+    }
+
+    #[test]
+    #[cfg(not(feature = "http"))]
+    fn test_fetch_without_http() {
+        let error = fetch("http://localhost/head.obj").unwrap_err();
+
+        assert!(error.to_string().contains("http feature"));
+    }
+}
@@ -1,25 +1,296 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::mem;
 use std::mem::swap;
+use std::ops::Range;
 
-use nalgebra::{Vector2, Vector3, Vector4};
-use nalgebra::geometry::{Point2};
+use nalgebra::{Matrix4, Vector2, Vector3, Vector4};
 
+use camera::Camera;
+use clipping;
+use error::MorpheusError;
+use occlusion::OcclusionShader;
+use plugins;
+use reorder;
 use shader;
 use shader::Shader;
 use wavefront;
 use vector;
 
 
-/// Bresenham's algorithm: Draw a line in the given color from (x0, y0) to (x1, y1)
+/// A solid outline drawn around an object with an inverted hull
+///
+/// The thickness is in the units of the object, so the outline gets thinner
+/// as the object moves away from the camera, as with hand drawn toon styles.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Outline {
+    pub color: image::Rgb<u8>,
+    pub thickness: f64
+}
+
+
+/// Solid colors that replace the texture of an object or of some of its groups and faces
+///
+/// Faces in a range of face indices with a color use that color, with later
+/// ranges winning over earlier ones. Other faces in a group with a color of
+/// its own use that color, and all remaining faces use the object color when
+/// there is one and the texture otherwise. Lit
+/// colors are shaded by the light for clay renders while unlit colors are
+/// written unchanged, which produces segmentation images. An outline adds an
+/// inverted hull pass that complements the cel shader.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate rastermind;
+/// # use rastermind::render::{ColorOverride, Outline};
+/// let mut colors = ColorOverride::default();
+/// colors.object = Some(image::Rgb([200, 200, 200]));
+/// colors.groups.insert(String::from("glass"), image::Rgb([40, 40, 80]));
+/// colors.faces.push((12..16, image::Rgb([255, 0, 0])));
+/// colors.lit = true;
+/// colors.outline = Some(Outline { color: image::Rgb([0, 0, 0]), thickness: 0.02 });
+/// ```
+///
+#[derive(Clone, Debug, Default)]
+pub struct ColorOverride {
+    pub object: Option<image::Rgb<u8>>,
+    pub groups: HashMap<String, image::Rgb<u8>>,
+    pub faces: Vec<(Range<usize>, image::Rgb<u8>)>,
+    pub lit: bool,
+    pub outline: Option<Outline>
+}
+
+
+impl ColorOverride {
+    /// Find the override color of every face of the given object
+    pub fn face_colors(&self, coordinates: &wavefront::Object) -> Vec<Option<image::Rgb<u8>>> {
+        let mut colors = vec![self.object; coordinates.geometric_faces.len()];
+
+        for group in &coordinates.groups {
+            if let Some(&color) = self.groups.get(&group.name) {
+                colors[group.faces.clone()].iter_mut().for_each(|face| *face = Some(color));
+            }
+        }
+
+        // ranges past the last face of the object are cut short instead of failing the render
+        for &(ref faces, color) in &self.faces {
+            let end = faces.end.min(colors.len());
+            colors[faces.start.min(end)..end].iter_mut().for_each(|face| *face = Some(color));
+        }
+
+        colors
+    }
+}
+
+
+/// How the depth of a fragment is mapped to the value stored in the depth buffer
+///
+/// Standard stores the depth of the viewport after the perspective divide,
+/// which concentrates precision near the camera. Reverse-Z stores the near
+/// distance divided by the distance of the fragment and logarithmic depth
+/// stores the logarithm of the distance between the near and far distances,
+/// both of which spread precision evenly enough to draw large scenes with
+/// close objects. Larger values are always nearer.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DepthMapping {
+    Standard,
+    ReverseZ,
+    Logarithmic
+}
+
+
+/// The device that runs the vertex transform and rasterization of a render
+///
+/// The GPU backend runs the vertex transform and rasterization as compute
+/// shaders with wgpu, and is only built in with the wgpu feature. It draws the
+/// same models from the same cameras as the CPU rasterizer with the Gouraud
+/// and flat shaders, see gpu::Rasterizer.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Backend {
+    Cpu,
+    Gpu
+}
+
+
+impl Backend {
+    /// Find the backend with the given name as given to --backend
+    pub fn parse(name: &str) -> Option<Backend> {
+        match name {
+            "cpu" => Some(Backend::Cpu),
+            "gpu" => Some(Backend::Gpu),
+            _ => None
+        }
+    }
+
+    /// Whether renders can run on the backend in this build
+    pub fn is_available(self) -> bool {
+        self == Backend::Cpu || cfg!(feature = "wgpu")
+    }
+}
+
+
+/// The precision of the values stored in the depth buffer
+///
+/// Single rounds values to 32 bit floats and Fixed rounds values in [0, 1]
+/// to the given number of bits, which emulates the depth buffers of graphics
+/// hardware. Fixed precision is meant for the reverse-Z and logarithmic
+/// mappings, as standard depth values are not normalized.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DepthPrecision {
+    Double,
+    Single,
+    Fixed(u8)
+}
+
+
+/// A polygon offset that moves the depth of every fragment of an object towards the camera
+///
+/// As with glPolygonOffset the offset of a triangle is the factor times the
+/// largest slope of its depth in screen space plus the units times the
+/// smallest difference the depth buffer can store. Decals, floor grids, and
+/// outline shells drawn on top of a coplanar surface use a positive offset
+/// so they do not fight with the surface.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PolygonOffset {
+    pub factor: f64,
+    pub units: f64
+}
+
+
+/// Options that control the values stored in the depth buffer and the fragments drawn into it
+///
+/// Fragments nearer than the near distance, or further than the far distance
+/// with the logarithmic mapping, are not drawn by the reverse-Z and
+/// logarithmic mappings. The polygon offset applies to the object drawn with
+/// the options, so objects sharing a depth buffer may use different offsets.
+///
+/// While checking for shading that is not finite, fragments whose shader
+/// computes NaN or infinity, which would otherwise be converted to black or
+/// white, are drawn magenta instead and counted in the stats of the render,
+/// so numerical errors in custom shaders can be found. Checking shades every
+/// fragment twice, so it is meant for debugging.
+///
+/// Dense meshes such as scans have many triangles smaller than a pixel, most
+/// of which miss the sample at the corner of their pixel and are not drawn,
+/// which leaves holes and speckles in thin parts of the surface. Merging
+/// micro triangles shades the triangles within a pixel that miss its sample
+/// once at their centroid instead, and the nearest of them covers the pixel.
 ///
 /// # Examples
 ///
 /// ```
+/// # use rastermind::render::{DepthMapping, DepthOptions, DepthPrecision, PolygonOffset};
+/// let depth = DepthOptions { mapping: DepthMapping::ReverseZ, precision: DepthPrecision::Single,
+///                            ..Default::default() };
+/// let decal = DepthOptions { offset: PolygonOffset { factor: 1.0, units: 1.0 }, ..depth };
+/// ```
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DepthOptions {
+    pub mapping: DepthMapping,
+    pub precision: DepthPrecision,
+    pub near: f64,
+    pub far: f64,
+    pub offset: PolygonOffset,
+    pub check_non_finite: bool,
+    pub merge_micro_triangles: bool
+}
+
+
+impl Default for DepthOptions {
+    fn default() -> DepthOptions {
+        DepthOptions { mapping: DepthMapping::Standard, precision: DepthPrecision::Double,
+                       near: 0.1, far: 1000.0, offset: PolygonOffset::default(), check_non_finite: false,
+                       merge_micro_triangles: false }
+    }
+}
+
+
+impl DepthOptions {
+    /// Find the value stored in the depth buffer for a fragment
+    ///
+    /// The standard depth is the depth of the viewport after the perspective
+    /// divide and the distance is the distance of the fragment from the camera
+    /// along the view direction. The polygon offset is not applied.
+    ///
+    pub fn encode(&self, standard: f64, distance: f64) -> Option<f64> {
+        self.map(standard, distance).map(|value| self.quantize(value))
+    }
+
+    /// Map a fragment to a depth value before it is rounded to the precision
+    fn map(&self, standard: f64, distance: f64) -> Option<f64> {
+        match self.mapping {
+            DepthMapping::Standard => Some(standard),
+            DepthMapping::ReverseZ if distance >= self.near => Some(self.near / distance),
+            DepthMapping::Logarithmic if distance >= self.near && distance <= self.far => {
+                Some(1.0 - (distance / self.near).ln() / (self.far / self.near).ln())
+            },
+            _ => None
+        }
+    }
+
+    fn quantize(&self, value: f64) -> f64 {
+        match self.precision {
+            DepthPrecision::Double => value,
+            DepthPrecision::Single => value as f32 as f64,
+            DepthPrecision::Fixed(bits) => {
+                let steps = ((1u64 << bits.min(52)) - 1) as f64;
+                (value.clamp(0.0, 1.0) * steps).round() / steps
+            }
+        }
+    }
+
+    /// The smallest difference from the given value that the depth buffer can store
+    fn resolution(&self, value: f64) -> f64 {
+        match self.precision {
+            DepthPrecision::Double => value.abs().max(1.0) * f64::EPSILON,
+            DepthPrecision::Single => value.abs().max(1.0) * f32::EPSILON as f64,
+            DepthPrecision::Fixed(bits) => 1.0 / ((1u64 << bits.min(52)) - 1) as f64
+        }
+    }
+
+    /// Find the polygon offset of a triangle with the given depth values at the given screen points
+    fn triangle_offset(&self, points: &[Vector2<f64>], values: &[f64]) -> f64 {
+        if self.offset == PolygonOffset::default() {
+            return 0.0;
+        }
+
+        let (a, b) = (points[1] - points[0], points[2] - points[0]);
+        let (da, db) = (values[1] - values[0], values[2] - values[0]);
+        let area = a.x * b.y - b.x * a.y;
+
+        let slope = if area.abs() > 0.0 {
+            ((da * b.y - db * a.y) / area).abs().max(((a.x * db - b.x * da) / area).abs())
+        } else {
+            0.0
+        };
+
+        let largest = values.iter().fold(0.0f64, |largest, value| largest.max(value.abs()));
+
+        self.offset.factor * slope + self.offset.units * self.resolution(largest)
+    }
+}
+
+
+/// Bresenham's algorithm: Draw a line in the given color from (x0, y0) to (x1, y1)
+///
+/// # Examples
+///
+/// ```ignore
 /// let mut buffer = image::ImageBuffer::new(1921, 1081);
 ///
 /// draw_line(0, 0, 1920, 1080, &mut buffer, image::Rgb([255, 255, 255]))
 /// ```
 ///
-/// ```
+/// ```ignore
 /// let mut buffer = image::ImageBuffer::new(512, 512);
 ///
 /// draw_line(0, 0, 511, 511, &mut buffer, image::Rgb([128, 0, 255]))
@@ -45,205 +316,1488 @@ fn draw_line(mut x0: i32, mut y0: i32, mut x1: i32, mut y1: i32,
     let mut y = y0;
     let mut error = 0;
 
-    for x in x0 ..= x1 {
-        if steep {
-            buffer.put_pixel(y as u32, x as u32, color);
-        } else {
-            buffer.put_pixel(x as u32, y as u32, color);
-        }
-        error += delta_error;
-        if error > delta_x {
-            y += if y1 > y0 { 1 } else { -1 };
-            error -= delta_x * 2;
-        }
+    for x in x0 ..= x1 {
+        if steep {
+            buffer.put_pixel(y as u32, x as u32, color);
+        } else {
+            buffer.put_pixel(x as u32, y as u32, color);
+        }
+        error += delta_error;
+        if error > delta_x {
+            y += if y1 > y0 { 1 } else { -1 };
+            error -= delta_x * 2;
+        }
+    }
+}
+
+
+/// Draw a single pixel at the given screen coordinate if it is nearer than the depth buffer
+fn draw_point_3d(point: &Vector3<f64>, buffer: &mut image::RgbImage,
+                 zbuffer: &mut [f64], color: image::Rgb<u8>) {
+
+    // written so that points with NaN coordinates are not drawn either
+    if !(point.x >= 0.0 && point.y >= 0.0 && point.x < buffer.width() as f64 && point.y < buffer.height() as f64) {
+        return;
+    }
+
+    let index = (point.x as u32 + point.y as u32 * buffer.width()) as usize;
+
+    if zbuffer[index] < point.z {
+        zbuffer[index] = point.z;
+        buffer.put_pixel(point.x as u32, point.y as u32, color);
+    }
+}
+
+
+/// Draw a depth tested line in the given color between the given screen coordinates
+///
+/// The depth of each pixel is interpolated between the depths of the end points
+/// so lines are hidden behind triangles that are nearer to the camera. The end
+/// points are expected on the screen, as clipping::clip_segment leaves them.
+///
+fn draw_line_3d(start: &Vector3<f64>, end: &Vector3<f64>, buffer: &mut image::RgbImage,
+                zbuffer: &mut [f64], color: image::Rgb<u8>) {
+
+    let steps = (end.x - start.x).abs().max((end.y - start.y).abs()).ceil().max(1.0);
+
+    for step in 0..=steps as u32 {
+        let point = start.lerp(end, step as f64 / steps);
+        draw_point_3d(&point, buffer, zbuffer, color);
+    }
+}
+
+
+/// Counts of the faces considered by a render
+///
+/// Degenerate faces are those with no area on the screen or with a point that
+/// is not finite, which are skipped instead of being rasterized. Faces with
+/// shading that is not finite are only counted while checking for it.
+/// Batches are the runs of faces drawn one after another with the same
+/// material, so the texture or color of a batch stays in the cache. Vertex
+/// cache misses are the vertices a cache of the last transformed vertices,
+/// like that of graphics hardware, would have had to transform, which
+/// ordering the faces with Object::optimize_vertex_cache brings down.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::render::*;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let mut buffer = image::RgbImage::new(64, 64);
+/// # let (eye, center, up) = (Vector3::<f64>::new(0.0, 0.0, 3.0), Vector3::zeros(), Vector3::y());
+/// # let light_vector = Vector3::<f64>::z();
+/// let stats = draw_object(&coordinates, &mut buffer, &texture, 255, &light_vector, &eye, &center, &up);
+///
+/// if stats.degenerate > 0 {
+///     eprintln!("skipped {} of {} faces", stats.degenerate, stats.faces);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RenderStats {
+    pub faces: usize,
+    pub degenerate: usize,
+    pub non_finite: usize,
+    pub non_finite_fragments: usize,
+    pub batches: usize,
+    pub vertex_cache_misses: usize
+}
+
+
+impl RenderStats {
+    /// Add the counts of another render, such as another object of the same scene
+    pub fn add(&mut self, other: &RenderStats) {
+        self.faces += other.faces;
+        self.degenerate += other.degenerate;
+        self.non_finite += other.non_finite;
+        self.non_finite_fragments += other.non_finite_fragments;
+        self.batches += other.batches;
+        self.vertex_cache_misses += other.vertex_cache_misses;
+    }
+}
+
+
+/// Order the faces so the faces of every material are drawn together
+///
+/// Materials are drawn in the order their first faces appear in the given
+/// order, and the faces of a material keep their order within it.
+///
+/// # Examples
+///
+/// ```
+/// # use rastermind::render::batch_order;
+/// let materials = ["wood", "metal", "wood", "glass", "metal"];
+///
+/// assert_eq!(batch_order(&materials, &[0, 1, 2, 3, 4]), vec![0, 2, 1, 4, 3]);
+/// ```
+pub fn batch_order<M: PartialEq>(materials: &[M], order: &[usize]) -> Vec<usize> {
+    let mut firsts: Vec<usize> = Vec::new();
+
+    for &face in order {
+        if !firsts.iter().any(|&first| materials[first] == materials[face]) {
+            firsts.push(face);
+        }
+    }
+
+    let mut batched = order.to_vec();
+    batched.sort_by_key(|&face| firsts.iter().position(|&first| materials[first] == materials[face]));
+
+    batched
+}
+
+
+/// Count the runs of faces with the same material in the given order
+pub fn count_batches<M: PartialEq>(materials: &[M], order: &[usize]) -> usize {
+    order.iter().enumerate().filter(|&(drawn, &face)| {
+        drawn == 0 || materials[order[drawn - 1]] != materials[face]
+    }).count()
+}
+
+
+/// The color and depth buffers of a render, kept together so they can be reused across draw calls
+///
+/// The depth buffer holds one value per pixel of the color buffer, where
+/// larger values are nearer and -1 means nothing was drawn. Objects drawn
+/// into the same framebuffer are depth tested against each other. The color
+/// buffer is drawn bottom up, and resolving it flips it into an upright image.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::render::*;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let light_vector = Vector3::<f64>::z();
+/// # let camera = rastermind::Camera::default();
+/// # let (head, head_texture, eyes, eyes_texture) = (&coordinates, &texture, &coordinates, &texture);
+/// let mut framebuffer = Framebuffer::new(512, 512);
+///
+/// draw_object_into(&head, &mut framebuffer, &head_texture, &ColorOverride::default(), &light_vector, &camera);
+/// draw_object_into(&eyes, &mut framebuffer, &eyes_texture, &ColorOverride::default(), &light_vector, &camera);
+///
+/// framebuffer.resolve().save("head.png")?;
+/// # Ok(())
+/// # }
+/// ```
+///
+#[derive(Clone, Debug)]
+pub struct Framebuffer {
+    color: image::RgbImage,
+    depth: Vec<f64>
+}
+
+
+impl Framebuffer {
+    /// Create a new black Framebuffer of the given size with nothing drawn into it
+    pub fn new(width: u32, height: u32) -> Framebuffer {
+        Framebuffer::from_color(image::ImageBuffer::new(width, height))
+    }
+
+    /// Create a new Framebuffer that draws over the given color buffer with nothing drawn into its depth buffer
+    pub fn from_color(color: image::RgbImage) -> Framebuffer {
+        let depth = vec![-1.0; (color.width() * color.height()) as usize];
+
+        Framebuffer { color, depth }
+    }
+
+    /// Fill the color buffer with the given color and forget the depth of everything drawn
+    pub fn clear(&mut self, color: image::Rgb<u8>) {
+        self.color.pixels_mut().for_each(|pixel| *pixel = color);
+        self.depth.iter_mut().for_each(|depth| *depth = -1.0);
+    }
+
+    /// The color buffer flipped into an upright image
+    pub fn resolve(&self) -> image::RgbImage {
+        image::imageops::flip_vertical(&self.color)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.color.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.color.height()
+    }
+
+    pub fn color(&self) -> &image::RgbImage {
+        &self.color
+    }
+
+    pub fn depth(&self) -> &[f64] {
+        &self.depth
+    }
+
+    /// The depth at the given pixel, if anything was drawn there
+    pub fn depth_at(&self, x: u32, y: u32) -> Option<f64> {
+        Some(self.depth[(x + y * self.width()) as usize]).filter(|&depth| depth != -1.0)
+    }
+
+    /// The color and depth buffers, to be drawn into by functions such as draw_faces
+    pub fn buffers_mut(&mut self) -> (&mut image::RgbImage, &mut [f64]) {
+        (&mut self.color, &mut self.depth)
+    }
+
+    /// The color buffer, giving up the depth buffer
+    pub fn into_color(self) -> image::RgbImage {
+        self.color
+    }
+}
+
+
+/// The color of the fragments whose shading is not finite while checking for them
+pub const NON_FINITE_COLOR: image::Rgb<u8> = image::Rgb([255, 0, 255]);
+
+
+/// Check that a render of the given size and view can be drawn before drawing it
+///
+/// The image must not be empty, the eye must not be at the center, the up
+/// vector must not be parallel to the direction of view, and the light
+/// vector must be normalized, since any of these would otherwise fill the
+/// view matrices with NaN and silently produce a black image. The eye,
+/// center, and up vectors must also be finite.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::{render, wavefront};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let mut buffer = image::RgbImage::new(64, 64);
+/// # let (eye, center, up) = (Vector3::<f64>::new(0.0, 0.0, 3.0), Vector3::zeros(), Vector3::y());
+/// # let light_vector = Vector3::<f64>::z();
+/// render::validate(512, 512, &light_vector, &eye, &center, &up)?;
+/// render::draw_object(&coordinates, &mut buffer, &texture, 255, &light_vector, &eye, &center, &up);
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn validate(width: u32, height: u32, light_vector: &Vector3<f64>, eye: &Vector3<f64>,
+                center: &Vector3<f64>, up: &Vector3<f64>) -> Result<(), MorpheusError> {
+
+    let invalid = |message: String| Err(MorpheusError::Render(message));
+
+    if width == 0 || height == 0 {
+        return invalid(format!("the image is {}x{} but must not be empty", width, height));
+    }
+
+    for (name, vector) in [("eye", eye), ("center", center), ("up", up)] {
+        if vector.iter().any(|value| !value.is_finite()) {
+            return invalid(format!("the {} vector {:?} is not finite", name, [vector.x, vector.y, vector.z]));
+        }
+    }
+
+    let view = eye - center;
+
+    if view.norm() < 1e-9 {
+        return invalid(String::from("the eye and the center are at the same point, so there is no direction of view"));
+    }
+
+    if up.norm() < 1e-9 || up.cross(&view).norm() < 1e-9 * up.norm() * view.norm() {
+        return invalid(format!("the up vector {:?} is zero or parallel to the direction of view",
+                               [up.x, up.y, up.z]));
+    }
+
+    if !light_vector.norm().is_finite() || (light_vector.norm() - 1.0).abs() > 1e-6 {
+        return invalid(format!("the light vector must be normalized but has a length of {}", light_vector.norm()));
+    }
+
+    Ok(())
+}
+
+
+/// Whether a triangle with the given clip space points cannot be rasterized
+///
+/// A triangle is degenerate when one of its points has a component that is
+/// not finite or a w of zero, or when its projection on the screen has no
+/// area, any of which would make its barycentric coordinates meaningless and
+/// its bounding box arbitrarily large.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector4;
+/// # use rastermind::render::is_degenerate;
+/// let points = vec![Vector4::new(0.0, 0.0, 0.0, 1.0), Vector4::new(1.0, 1.0, 0.0, 1.0),
+///                   Vector4::new(2.0, 2.0, 0.0, 1.0)];
+///
+/// assert!(is_degenerate(&points));
+/// ```
+///
+pub fn is_degenerate(points: &[Vector4<f64>]) -> bool {
+    if points.iter().any(|point| point.iter().any(|value| !value.is_finite()) || point.w == 0.0) {
+        return true;
+    }
+
+    let projected: Vec<Vector3<f64>> = points.iter().map(vector::project_to_3d).collect();
+    let area = (projected[1] - projected[0]).xy().perp(&(projected[2] - projected[0]).xy());
+
+    !area.is_finite() || area.abs() < 1e-12
+}
+
+
+/// Find the bounding box of the given points within a buffer of the given size
+fn find_bounding_box(points: &Vec<Vector2<f64>>, width: u32, height: u32) -> (Vector2<u32>, Vector2<u32>) {
+    let mut bounding_box_minimum = Vector2::new(width - 1, height - 1);
+    let mut bounding_box_maximum = Vector2::new(0, 0);
+
+    for point in points {
+            bounding_box_minimum = bounding_box_minimum.zip_map(point, |a, b| a.min(b as u32));
+            bounding_box_maximum = bounding_box_maximum.zip_map(point, |a, b| a.max(b as u32));
+    }
+
+    bounding_box_maximum.x = bounding_box_maximum.x.min(width - 1);
+    bounding_box_maximum.y = bounding_box_maximum.y.min(height - 1);
+
+    (bounding_box_minimum, bounding_box_maximum)
+}
+
+
+/// Draw a filled triangle with the given points in the given color
+///
+/// Returns None when the triangle is degenerate and otherwise the number of
+/// fragments whose shading was found not to be finite, which are only
+/// looked for while checking for them.
+///
+/// # Examples
+///
+/// ```ignore
+/// let mut buffer = image::ImageBuffer::new(1921, 1081);
+/// let points =  vec![Vector3::new(0, 0, 0), Vector3::new(2, 2, 2), Vector3::new(0, 2, 2)]
+/// let zbuffer = vec![-1.0, -1.0, -1.0];
+///
+/// draw_triangle(&points, &mut buffer, &mut zbuffer, image::Rgb([255, 255, 255]))
+/// ```
+///
+#[allow(clippy::too_many_arguments)]
+fn draw_triangle(points: &Vec<Vector4<f64>>, buffer: &mut image::RgbImage,
+                 texture: &image::RgbImage, zbuffer: &mut [f64],
+                 depth: &DepthOptions, camera_distance: f64,
+                 behind: Option<&[f64]>, shader: &dyn Shader) -> Option<usize> {
+
+    let (width, height) = buffer.dimensions();
+    let mut non_finite = 0;
+
+    let drawn = rasterize_triangle(points, width, height, depth, camera_distance, shader, |x, y, coordinate, value| {
+        let index = (x + y * width) as usize;
+
+        if zbuffer[index] < value && behind.is_none_or(|behind| value < behind[index]) {
+            let mut color = shader.fragment(coordinate, texture);
+
+            if depth.check_non_finite && shader.shade(coordinate, texture).iter().any(|channel| !channel.is_finite()) {
+                non_finite += 1;
+                color = NON_FINITE_COLOR;
+            }
+
+            zbuffer[index] = value;
+            buffer.put_pixel(x, y, color);
+        }
+    });
+
+    if drawn { Some(non_finite) } else { None }
+}
+
+
+/// Find the fragments of a triangle with the given points that the shader does not discard
+///
+/// The fragment function is given the pixel, the barycentric coordinate, and
+/// the depth value of every fragment, with the polygon offset applied, but
+/// does not test the depth, so it decides for itself what to keep. Degenerate
+/// triangles are skipped, in which case false is returned. Triangles within
+/// a single pixel are tested without walking their bounding box.
+///
+#[allow(clippy::too_many_arguments)]
+pub fn rasterize_triangle<F>(points: &Vec<Vector4<f64>>, width: u32, height: u32,
+                             depth: &DepthOptions, camera_distance: f64,
+                             shader: &dyn Shader, mut fragment: F) -> bool
+    where F: FnMut(u32, u32, Vector3<f64>, f64) {
+
+    if is_degenerate(points) {
+        return false;
+    }
+
+    let projected_points: &Vec<Vector2<f64>> = &points.iter()
+                                                      .map(|&point| vector::project_to_3d(&point).remove_row(2))
+                                                      .collect();
+
+    let vertex_values: Option<Vec<f64>> = points.iter()
+                                                .map(|point| depth.map(point.z / point.w,
+                                                                       camera_distance * point.w))
+                                                .collect();
+    let offset = vertex_values.map_or(0.0, |values| depth.triangle_offset(projected_points, &values));
+
+    let (bounding_box_minimum, bounding_box_maximum) = find_bounding_box(projected_points, width, height);
+
+    let mut shade = |x: u32, y: u32, coordinate: Vector3<f64>| {
+        let z = (0..=2).map(|i| points[i].z * coordinate[i]).sum::<f64>();
+        let w = (0..=2).map(|i| points[i].w * coordinate[i]).sum::<f64>();
+
+        // the reciprocal of w is linear in screen space and w is proportional to the distance
+        let distance = camera_distance / (0..=2).map(|i| coordinate[i] / points[i].w).sum::<f64>();
+
+        if shader.discard(coordinate) {
+            return;
+        }
+
+        if let Some(value) = depth.map(z / w, distance) {
+            fragment(x, y, coordinate, depth.quantize(value + offset));
+        }
+    };
+
+    let covers = |coordinate: &Vector3<f64>| coordinate.x >= 0.0 && coordinate.y >= 0.0 && coordinate.z >= 0.0;
+
+    // a triangle within a single pixel has only the one sample to test
+    if bounding_box_minimum == bounding_box_maximum {
+        let (x, y) = (bounding_box_minimum.x, bounding_box_minimum.y);
+        let coordinate = shader::find_barycentric(projected_points, &Vector4::new(x as f64, y as f64, 0.0, 0.0));
+
+        if covers(&coordinate) {
+            shade(x, y, coordinate);
+        } else if depth.merge_micro_triangles {
+            let centroid = projected_points.iter().sum::<Vector2<f64>>() / 3.0;
+
+            if centroid.x.floor() == x as f64 && centroid.y.floor() == y as f64 {
+                shade(x, y, Vector3::repeat(1.0 / 3.0));
+            }
+        }
+
+        return true;
+    }
+
+    for x in bounding_box_minimum.x ..= bounding_box_maximum.x  {
+        for y in bounding_box_minimum.y ..= bounding_box_maximum.y {
+            let coordinate = shader::find_barycentric(projected_points, &Vector4::new(x as f64, y as f64, 0.0, 0.0));
+
+            if covers(&coordinate) {
+                shade(x, y, coordinate);
+            }
+        }
+    }
+
+    true
+}
+
+
+/// Draw every face of an Object with the shader returned for it by the given function
+///
+/// Faces are tested against and written to the given depth buffer, which must
+/// hold one value per pixel of the buffer, so several objects or passes can
+/// share it as long as they use the same depth options.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::render::*;
+/// # use rastermind::{shader, wavefront};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let mut buffer = image::RgbImage::new(64, 64);
+/// # let (eye, center, up) = (Vector3::<f64>::new(0.0, 0.0, 3.0), Vector3::zeros(), Vector3::y());
+/// # let light_vector = Vector3::<f64>::z();
+/// # let view_port = shader::viewport(8, 8, 48, 48, 255);
+/// # let projection = shader::projection(-1.0 / 3.0);
+/// # let model_view = shader::lookat(&eye, &center, &up);
+/// let mut zbuffer = vec![-1.0; (buffer.width() * buffer.height()) as usize];
+///
+/// draw_faces(&coordinates, &mut buffer, &texture, &mut zbuffer, &DepthOptions::default(),
+///            &view_port, &projection, &model_view, &light_vector,
+///            |_| Box::new(shader::GouraudShader::new()));
+/// # Ok(())
+/// # }
+/// ```
+#[allow(clippy::too_many_arguments)]
+pub fn draw_faces<'a, F>(coordinates: &wavefront::Object, buffer: &mut image::RgbImage,
+                         texture: &image::RgbImage, zbuffer: &mut [f64], depth: &DepthOptions,
+                         view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+                         model_view: &Matrix4<f64>, light_vector: &Vector3<f64>,
+                         shader_for_face: F) -> RenderStats where F: FnMut(usize) -> Box<dyn Shader + 'a> {
+
+    let order: Vec<usize> = (0..coordinates.geometric_faces.len()).collect();
+
+    draw_faces_in_order(coordinates, buffer, texture, zbuffer, depth, view_port, projection, model_view,
+                        light_vector, &order, shader_for_face, |_, _| ())
+}
+
+
+/// Draw the faces of an Object in the given order, reporting the buffer after every face
+///
+/// The progress function is given the buffer and the number of faces drawn
+/// so far, so long renders can save or show partial images as they go.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::render::*;
+/// # use rastermind::{shader, wavefront};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let mut buffer = image::RgbImage::new(64, 64);
+/// # let (eye, center, up) = (Vector3::<f64>::new(0.0, 0.0, 3.0), Vector3::zeros(), Vector3::y());
+/// # let light_vector = Vector3::<f64>::z();
+/// # let view_port = shader::viewport(8, 8, 48, 48, 255);
+/// # let projection = shader::projection(-1.0 / 3.0);
+/// # let model_view = shader::lookat(&eye, &center, &up);
+/// # let mut zbuffer = vec![-1.0; (buffer.width() * buffer.height()) as usize];
+/// let order = interleaved_order(coordinates.geometric_faces.len(), PROGRESSIVE_STRIDE);
+///
+/// draw_faces_in_order(&coordinates, &mut buffer, &texture, &mut zbuffer, &DepthOptions::default(),
+///                     &view_port, &projection, &model_view, &light_vector, &order,
+///                     |_| Box::new(shader::GouraudShader::new()),
+///                     |buffer, drawn| println!("{} of {} faces", drawn, order.len()));
+/// # Ok(())
+/// # }
+/// ```
+#[allow(clippy::too_many_arguments)]
+pub fn draw_faces_in_order<'a, F, P>(coordinates: &wavefront::Object, buffer: &mut image::RgbImage,
+                                     texture: &image::RgbImage, zbuffer: &mut [f64], depth: &DepthOptions,
+                                     view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+                                     model_view: &Matrix4<f64>, light_vector: &Vector3<f64>,
+                                     order: &[usize], shader_for_face: F, progress: P) -> RenderStats
+    where F: FnMut(usize) -> Box<dyn Shader + 'a>, P: FnMut(&image::RgbImage, usize) {
+
+    draw_ordered_faces(coordinates, buffer, texture, zbuffer, None, depth, view_port, projection, model_view,
+                       light_vector, order, shader_for_face, progress)
+}
+
+
+/// Draw every face of an Object where it is behind the depths of the given buffer
+///
+/// Fragments are only drawn where their depth is less than the depth of the
+/// given buffer at their pixel, as well as less than the depth buffer they
+/// are drawn into, so drawing again behind the depth buffer of a finished
+/// render draws the next layer of surfaces. The buffer behind must use the
+/// same depth options.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::render::*;
+/// # use rastermind::{shader, wavefront};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let mut buffer = image::RgbImage::new(64, 64);
+/// # let (eye, center, up) = (Vector3::<f64>::new(0.0, 0.0, 3.0), Vector3::zeros(), Vector3::y());
+/// # let light_vector = Vector3::<f64>::z();
+/// # let view_port = shader::viewport(8, 8, 48, 48, 255);
+/// # let projection = shader::projection(-1.0 / 3.0);
+/// # let model_view = shader::lookat(&eye, &center, &up);
+/// # let zbuffer = vec![-1.0; (buffer.width() * buffer.height()) as usize];
+/// let behind = zbuffer.clone();
+/// let mut zbuffer = vec![-1.0; (buffer.width() * buffer.height()) as usize];
+///
+/// draw_faces_behind(&coordinates, &mut buffer, &texture, &mut zbuffer, &behind, &DepthOptions::default(),
+///                   &view_port, &projection, &model_view, &light_vector,
+///                   |_| Box::new(shader::GouraudShader::new()));
+/// # Ok(())
+/// # }
+/// ```
+#[allow(clippy::too_many_arguments)]
+pub fn draw_faces_behind<'a, F>(coordinates: &wavefront::Object, buffer: &mut image::RgbImage,
+                                texture: &image::RgbImage, zbuffer: &mut [f64], behind: &[f64],
+                                depth: &DepthOptions, view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+                                model_view: &Matrix4<f64>, light_vector: &Vector3<f64>,
+                                shader_for_face: F) -> RenderStats where F: FnMut(usize) -> Box<dyn Shader + 'a> {
+
+    let order: Vec<usize> = (0..coordinates.geometric_faces.len()).collect();
+
+    draw_ordered_faces(coordinates, buffer, texture, zbuffer, Some(behind), depth, view_port, projection,
+                       model_view, light_vector, &order, shader_for_face, |_, _| ())
+}
+
+
+/// Draw the faces of an Object in the given order, behind the given depths if there are any, counting them
+#[allow(clippy::too_many_arguments)]
+fn draw_ordered_faces<'a, F, P>(coordinates: &wavefront::Object, buffer: &mut image::RgbImage,
+                                texture: &image::RgbImage, zbuffer: &mut [f64], behind: Option<&[f64]>,
+                                depth: &DepthOptions, view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+                                model_view: &Matrix4<f64>, light_vector: &Vector3<f64>,
+                                order: &[usize], mut shader_for_face: F, mut progress: P) -> RenderStats
+    where F: FnMut(usize) -> Box<dyn Shader + 'a>, P: FnMut(&image::RgbImage, usize) {
+
+    // the projection divides by the distance along the view direction over the distance to the center
+    let camera_distance = -1.0 / projection[(3, 2)];
+    let mut stats = RenderStats { batches: order.len().min(1), ..Default::default() };
+    let mut vertex_cache = reorder::VertexCache::default();
+
+    for (drawn, &face_index) in order.iter().enumerate() {
+        let mut shader = shader_for_face(face_index);
+        let mut screen_coordinates: Vec<Vector4<f64>> = Vec::new();
+
+        for vertex_index in 0..=2 {
+            screen_coordinates.push(shader.vertex(coordinates, view_port, projection,
+                                                  model_view, light_vector,
+                                                  face_index, vertex_index));
+        }
+
+        stats.faces += 1;
+        stats.vertex_cache_misses += coordinates.geometric_faces[face_index].iter()
+                                                .filter(|&&vertex| !vertex_cache.touch(vertex)).count();
+
+        match draw_triangle(&screen_coordinates, buffer, texture, zbuffer, depth, camera_distance, behind,
+                            shader.as_ref()) {
+            None => stats.degenerate += 1,
+            Some(0) => (),
+            Some(fragments) => {
+                stats.non_finite += 1;
+                stats.non_finite_fragments += fragments;
+            }
+        }
+
+        progress(buffer, drawn + 1);
+    }
+
+    stats
+}
+
+
+/// The number of passes over the faces when drawing progressively
+pub const PROGRESSIVE_STRIDE: usize = 16;
+
+
+/// Order the faces so every pass draws every stride-th face, starting one face later each pass
+///
+/// Faces that are next to each other in a file are usually next to each other
+/// in the model, so a partial render drawn in this order covers the whole
+/// model sparsely instead of finishing one part of it at a time.
+///
+/// # Examples
+///
+/// ```
+/// # use rastermind::render::interleaved_order;
+/// assert_eq!(interleaved_order(5, 2), vec![0, 2, 4, 1, 3]);
+/// ```
+pub fn interleaved_order(count: usize, stride: usize) -> Vec<usize> {
+    let stride = stride.max(1);
+
+    (0..stride.min(count)).flat_map(|offset| (offset..count).step_by(stride)).collect()
+}
+
+
+/// Draw an outline around an Object by drawing its inverted hull in a solid color
+///
+/// Only the faces of the hull that point away from the camera are drawn, so
+/// the hull is hidden by the object except where it sticks out around the
+/// silhouette and along creases.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::render::*;
+/// # use rastermind::{shader, wavefront};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let mut buffer = image::RgbImage::new(64, 64);
+/// # let (eye, center, up) = (Vector3::<f64>::new(0.0, 0.0, 3.0), Vector3::zeros(), Vector3::y());
+/// # let view_port = shader::viewport(8, 8, 48, 48, 255);
+/// # let projection = shader::projection(-1.0 / 3.0);
+/// # let model_view = shader::lookat(&eye, &center, &up);
+/// # let mut zbuffer = vec![-1.0; (buffer.width() * buffer.height()) as usize];
+/// let outline = Outline { color: image::Rgb([0, 0, 0]), thickness: 0.02 };
+///
+/// draw_outline(&coordinates, &mut buffer, &mut zbuffer, &DepthOptions::default(),
+///              &view_port, &projection, &model_view, &outline);
+/// # Ok(())
+/// # }
+/// ```
+#[allow(clippy::too_many_arguments)]
+pub fn draw_outline(coordinates: &wavefront::Object, buffer: &mut image::RgbImage,
+                    zbuffer: &mut [f64], depth: &DepthOptions,
+                    view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+                    model_view: &Matrix4<f64>, outline: &Outline) {
+
+    let hull = coordinates.inverted_hull(outline.thickness);
+    let texture = image::RgbImage::new(1, 1);
+    let camera_distance = -1.0 / projection[(3, 2)];
+
+    for face_index in 0..hull.geometric_faces.len() {
+        let mut shader = shader::ColorShader::new(outline.color, false);
+        let screen_coordinates: Vec<Vector4<f64>> = (0..=2).map(|vertex_index| {
+            shader.vertex(&hull, view_port, projection, model_view, &Vector3::z(),
+                          face_index, vertex_index)
+        }).collect();
+
+        let projected: Vec<Vector3<f64>> = screen_coordinates.iter().map(vector::project_to_3d).collect();
+        let area = (projected[1] - projected[0]).xy().perp(&(projected[2] - projected[0]).xy());
+
+        // the reversed winding makes faces that point away from the camera counter-clockwise
+        if area > 0.0 {
+            draw_triangle(&screen_coordinates, buffer, &texture, zbuffer, depth, camera_distance,
+                          None, &shader);
+        }
+    }
+}
+
+
+/// Draw a wire mesh on the given ImageBuffer with the coordinates from the given file
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate image;
+/// # extern crate rastermind;
+/// # use rastermind::render::draw_wire_mesh;
+/// # fn main() -> Result<(), rastermind::Error> {
+/// let width = 512;
+/// let height = 512;
+/// let mut buffer = image::ImageBuffer::new(width, height);
+///
+/// draw_wire_mesh("coordinates.obj", &mut buffer)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn draw_wire_mesh(filename: &str, buffer: &mut image::RgbImage) -> Result<(), MorpheusError> {
+    let coordinates = wavefront::Object::new(filename)?;
+
+    for face in coordinates.geometric_faces {
+        for i in 0..3 {
+            let v0 = coordinates.geometric_vertices[(face[i]) as usize];
+            let v1 = coordinates.geometric_vertices[(face[(i+1) % 3]) as usize];
+
+            let x0 = ((v0.x + 1.0) * buffer.width() as f64 / 2.0).min(buffer.width() as f64 - 1.0);
+            let y0 = ((v0.y + 1.0) * buffer.height() as f64 / 2.0).min(buffer.height() as f64 - 1.0);
+
+            let x1 = ((v1.x + 1.0) * buffer.width() as f64 / 2.0).min(buffer.width() as f64 - 1.0);
+            let y1 = ((v1.y + 1.0) * buffer.height() as f64 / 2.0).min(buffer.height() as f64 - 1.0);
+
+            draw_line(x0 as i32, y0 as i32, x1 as i32, y1 as i32,
+                      buffer, image::Rgb([255, 255, 255]));
+        }
+    }
+
+    Ok(())
+}
+
+
+/// The shader an object is drawn with, by the name it is registered under or as a shader of the caller
+///
+/// Names are looked up in the shaders registered with the plugins module,
+/// which start out with the gouraud, flat, cel, and normal shaders of the
+/// crate. A shader of the caller is shared by every face, which is fine for
+/// shaders that set all of their varyings for every face they are given.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::render::*;
+/// # use rastermind::shader;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let mut buffer = image::RgbImage::new(64, 64);
+/// # let (eye, center, up) = (Vector3::<f64>::new(0.0, 0.0, 3.0), Vector3::zeros(), Vector3::y());
+/// # let light_vector = Vector3::<f64>::z();
+/// # use rastermind::shader::Shader;
+/// # let bands = vec![(0.3, 0.5), (0.7, 1.0)];
+/// draw_triangle_mesh("coordinates.obj", &mut buffer, &texture, 255, &light_vector, &eye, &center, &up, "cel")?;
+///
+/// let toon: Box<dyn Shader> = Box::new(shader::CelShader::new().with_bands(bands, 0.1));
+/// draw_triangle_mesh("coordinates.obj", &mut buffer, &texture, 255, &light_vector, &eye, &center, &up, toon)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+pub enum ShaderChoice<'a> {
+    Named(String),
+    Shader(Box<dyn Shader + 'a>)
+}
+
+
+impl<'a> From<&str> for ShaderChoice<'a> {
+    fn from(name: &str) -> ShaderChoice<'a> {
+        ShaderChoice::Named(name.to_string())
+    }
+}
+
+
+impl<'a> From<String> for ShaderChoice<'a> {
+    fn from(name: String) -> ShaderChoice<'a> {
+        ShaderChoice::Named(name)
+    }
+}
+
+
+impl<'a> From<Box<dyn Shader + 'a>> for ShaderChoice<'a> {
+    fn from(shader: Box<dyn Shader + 'a>) -> ShaderChoice<'a> {
+        ShaderChoice::Shader(shader)
+    }
+}
+
+
+impl<'a> ShaderChoice<'a> {
+    /// A function that creates the shader of every face, failing for names that are not registered
+    pub fn factory(self) -> Result<Box<dyn FnMut() -> Box<dyn Shader + 'a> + 'a>, MorpheusError> {
+        match self {
+            ShaderChoice::Named(name) => {
+                if plugins::shader(&name).is_none() {
+                    return Err(MorpheusError::Render(format!("unknown shader {}", name)));
+                }
+
+                // the shader was found, so only a plugin replaced since then falls back
+                Ok(Box::new(move || plugins::shader(&name).unwrap_or_else(|| Box::new(shader::GouraudShader::new()))))
+            },
+            ShaderChoice::Shader(shader) => {
+                let shared = std::rc::Rc::new(RefCell::new(shader));
+
+                Ok(Box::new(move || Box::new(SharedShader(shared.clone()))))
+            }
+        }
+    }
+}
+
+
+/// A shader of the caller that is handed to every face in turn
+struct SharedShader<'a>(std::rc::Rc<RefCell<Box<dyn Shader + 'a>>>);
+
+
+impl<'a> Shader for SharedShader<'a> {
+    fn vertex(&mut self, coordinates: &wavefront::Object,
+              view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+              model_view: &Matrix4<f64>, light_vector: &Vector3<f64>,
+              face_index: usize, vertex_index: usize) -> Vector4<f64> {
+
+        self.0.borrow_mut().vertex(coordinates, view_port, projection, model_view, light_vector, face_index,
+                                   vertex_index)
+    }
+
+    fn fragment(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> image::Rgb<u8> {
+        self.0.borrow().fragment(vertex, texture)
+    }
+
+    fn shade(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> Vector3<f64> {
+        self.0.borrow().shade(vertex, texture)
+    }
+
+    fn name(&self) -> &'static str {
+        self.0.borrow().name()
+    }
+
+    fn discard(&self, vertex: Vector3<f64>) -> bool {
+        self.0.borrow().discard(vertex)
+    }
+
+    fn contributions(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> shader::Contributions {
+        self.0.borrow().contributions(vertex, texture)
     }
 }
 
 
-/// Fill triangle in the given color with points t0, t1, and t2
+/// How far the edges of a wireframe are drawn in front of their faces, in units of the depth of the viewport
+const WIRE_OFFSET: f64 = 1.0;
+
+
+/// The matrices, light and viewport of a frame, set up once for the framebuffer every pass is drawn into
+///
+/// Passes drawn through the same pipeline share its setup and are depth
+/// tested against each other, and the camera or the light can be changed
+/// between them, so a frame of several passes, such as a depth pass from the
+/// light followed by the beauty pass from the camera, or a wireframe over the
+/// shaded mesh, is set up without repeating the lookat, projection, and
+/// viewport of every pass.
 ///
 /// # Examples
 ///
-/// ```
-/// let mut buffer = image::ImageBuffer::new(1921, 1081);
+/// ```no_run
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::render::*;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let light_vector = Vector3::<f64>::z();
+/// # use rastermind::Camera;
+/// let camera = Camera::builder().eye(Vector3::new(0.0, 0.0, 3.0)).build();
+/// let mut pipeline = Pipeline::new(&camera, &light_vector, Framebuffer::new(512, 512));
 ///
-/// let mut t0 = Point2::new(0, 0);
-/// let mut t1 = Point2::new(2, 2);
-/// let mut t2 = Point2::new(0, 2);
+/// pipeline.draw_mesh(&coordinates, &texture);
+/// pipeline.draw_wire(&coordinates, image::Rgb([255, 255, 255]));
 ///
-/// fill_triangle(t0, t1, t2, &mut buffer, image::Rgb([255, 255, 255]))
+/// pipeline.framebuffer().resolve().save("wireframe.png")?;
+/// # Ok(())
+/// # }
 /// ```
 ///
-fn fill_triangle(mut t0: Point2<i32>, mut t1: Point2<i32>, mut t2: Point2<i32>,
-                 buffer: &mut image::RgbImage, color: image::Rgb<u8>) {
+#[derive(Clone, Debug)]
+pub struct Pipeline {
+    model_view: Matrix4<f64>,
+    projection: Matrix4<f64>,
+    view_port: Matrix4<f64>,
+    transform: Matrix4<f64>,
+    light_vector: Vector3<f64>,
+    depth: DepthOptions,
+    framebuffer: Framebuffer
+}
 
-    if t0.y > t1.y {
-        swap(&mut t0, &mut t1);
+
+impl Pipeline {
+    /// Set up the matrices of the camera for the given framebuffer
+    pub fn new(camera: &Camera, light_vector: &Vector3<f64>, framebuffer: Framebuffer) -> Pipeline {
+        let mut pipeline = Pipeline { model_view: Matrix4::identity(), projection: Matrix4::identity(),
+                                      view_port: Matrix4::identity(), transform: Matrix4::identity(),
+                                      light_vector: *light_vector, depth: DepthOptions::default(), framebuffer };
+        pipeline.set_camera(camera);
+
+        pipeline
     }
-    if t0.y > t2.y {
-        swap(&mut t0, &mut t2);
+
+    /// Draw with the given depth options instead of the default options
+    pub fn with_depth(mut self, depth: DepthOptions) -> Pipeline {
+        self.depth = depth;
+        self
     }
-    if t1.y > t2.y {
-        swap(&mut t1, &mut t2);
+
+    /// Set up the matrices of the given camera for the passes drawn from now on
+    pub fn set_camera(&mut self, camera: &Camera) {
+        self.model_view = camera.model_view();
+        self.projection = camera.projection();
+        self.view_port = camera.viewport(self.framebuffer.width(), self.framebuffer.height());
+        self.transform = self.view_port * self.projection * self.model_view;
     }
 
-    let triangle_height = t2.y - t0.y;
+    /// Light the passes drawn from now on from the given direction
+    pub fn set_light(&mut self, light_vector: &Vector3<f64>) {
+        self.light_vector = *light_vector;
+    }
 
-    for i in 0..triangle_height as i32 {
-        let second_half = i > (t1.y - t0.y) as i32 || (t1.y == t0.y);
-        let segment_height = if second_half {t2.y - t1.y} else {t1.y - t0.y};
+    pub fn model_view(&self) -> &Matrix4<f64> {
+        &self.model_view
+    }
 
-        let alpha = i as f64 / triangle_height as f64;
-        let beta = if second_half { (i as f64 - (t1.y - t0.y) as f64) / segment_height as f64} else
-                   {i as f64 / segment_height as f64};
+    pub fn projection(&self) -> &Matrix4<f64> {
+        &self.projection
+    }
 
-        let mut a = t0.x as f64 + ((t2 - t0).x as f64 * alpha);
-        let mut b = if second_half {t1.x as f64 + ((t2 - t1).x as f64 * beta)} else
-                    {t0.x as f64 + ((t1 - t0).x as f64 * beta)};
+    pub fn view_port(&self) -> &Matrix4<f64> {
+        &self.view_port
+    }
 
-        if a > b {
-            swap(&mut a, &mut b);
-        }
+    /// The viewport, projection, and model view combined into one matrix
+    pub fn transform(&self) -> &Matrix4<f64> {
+        &self.transform
+    }
 
-        for j in (a as u32)..=(b as u32) {
-            buffer.put_pixel(j, t0.y as u32 + i as u32, color);
-        }
+    pub fn light_vector(&self) -> &Vector3<f64> {
+        &self.light_vector
     }
-}
 
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
 
-/// Find the bounding box of the given points
-fn find_bounding_box(points: &Vec<Vector2<f64>>,
-                     buffer: &mut image::RgbImage) -> (Vector2<u32>, Vector2<u32>) {
+    pub fn framebuffer_mut(&mut self) -> &mut Framebuffer {
+        &mut self.framebuffer
+    }
 
-    let mut bounding_box_minimum = Vector2::new(buffer.width() - 1, buffer.height() - 1);
-    let mut bounding_box_maximum = Vector2::new(0, 0);
+    /// The framebuffer with every pass drawn into it
+    pub fn into_framebuffer(self) -> Framebuffer {
+        self.framebuffer
+    }
 
-    for point in points {
-            bounding_box_minimum = bounding_box_minimum.zip_map(&point, |a, b| a.min(b as u32));
-            bounding_box_maximum = bounding_box_maximum.zip_map(&point, |a, b| a.max(b as u32));
+    /// The screen coordinate of a point of the scene, with its depth in the viewport
+    pub fn project(&self, point: &Vector3<f64>) -> Vector3<f64> {
+        vector::project_to_3d(&(self.transform * vector::vectorize_to_4d(point)))
     }
 
-    (bounding_box_minimum, bounding_box_maximum)
+    /// Draw an already loaded Object with the Gouraud shader
+    pub fn draw_mesh(&mut self, coordinates: &wavefront::Object, texture: &image::RgbImage) -> RenderStats {
+        self.draw_mesh_with_colors(coordinates, texture, &ColorOverride::default())
+    }
+
+    /// Draw an already loaded Object with solid colors replacing its texture where given, in batches of one color
+    pub fn draw_mesh_with_colors(&mut self, coordinates: &wavefront::Object, texture: &image::RgbImage,
+                                 colors: &ColorOverride) -> RenderStats {
+
+        let faces: Vec<usize> = (0..coordinates.geometric_faces.len()).collect();
+        let order = batch_order(&colors.face_colors(coordinates), &faces);
+
+        self.draw_colored(coordinates, texture, colors, &order, &mut gouraud, &mut |_, _| ())
+    }
+
+    /// Draw an already loaded Object with the given shader, failing for names that are not registered
+    pub fn draw_mesh_with_shader<'a, S>(&mut self, coordinates: &wavefront::Object, texture: &image::RgbImage,
+                                        shader: S) -> Result<RenderStats, MorpheusError>
+        where S: Into<ShaderChoice<'a>> {
+
+        let mut factory = shader.into().factory()?;
+        let faces: Vec<usize> = (0..coordinates.geometric_faces.len()).collect();
+
+        Ok(self.draw_colored(coordinates, texture, &ColorOverride::default(), &faces, &mut *factory, &mut |_, _| ()))
+    }
+
+    /// Draw an already loaded Object with the given shader in an interleaved order, reporting the partial buffer
+    ///
+    /// The progress function is given the color buffer, the number of faces
+    /// drawn so far, and the number of faces, as with draw_object_progressively.
+    ///
+    pub fn draw_mesh_progressively<'a, S, P>(&mut self, coordinates: &wavefront::Object, texture: &image::RgbImage,
+                                             shader: S, mut progress: P) -> Result<RenderStats, MorpheusError>
+        where S: Into<ShaderChoice<'a>>, P: FnMut(&image::RgbImage, usize, usize) {
+
+        let mut factory = shader.into().factory()?;
+        let order = interleaved_order(coordinates.geometric_faces.len(), PROGRESSIVE_STRIDE);
+        let total = order.len();
+
+        Ok(self.draw_colored(coordinates, texture, &ColorOverride::default(), &order, &mut *factory,
+                             &mut |buffer, drawn| progress(buffer, drawn, total)))
+    }
+
+    /// Draw the edges of the faces of an already loaded Object in the given color
+    ///
+    /// The edges are depth tested and pulled towards the camera by the wire
+    /// offset, so a wireframe drawn after its own mesh shows the edges of the
+    /// faces that are not hidden by nearer faces.
+    ///
+    pub fn draw_wire(&mut self, coordinates: &wavefront::Object, color: image::Rgb<u8>) {
+        let transform = self.transform;
+        let screen_coordinates: Vec<Vector4<f64>> = coordinates.geometric_vertices.iter().map(|vertex| {
+            transform * vector::vectorize_to_4d(vertex)
+        }).collect();
+        let (buffer, zbuffer) = self.framebuffer.buffers_mut();
+        let offset = Vector3::new(0.0, 0.0, WIRE_OFFSET);
+
+        for face in &coordinates.geometric_faces {
+            for i in 0..3 {
+                let start = &screen_coordinates[face[i] as usize];
+                let end = &screen_coordinates[face[(i + 1) % 3] as usize];
+
+                if let Some((start, end)) = clipping::clip_segment(start, end, buffer.width(), buffer.height()) {
+                    draw_line_3d(&(start + offset), &(end + offset), buffer, zbuffer, color);
+                }
+            }
+        }
+    }
+
+    /// Draw an already loaded Object with its colors in the given order of faces, and the given shader elsewhere
+    fn draw_colored<'a, 's: 'a>(&mut self, coordinates: &'a wavefront::Object, texture: &image::RgbImage,
+                                colors: &ColorOverride, order: &[usize],
+                                shader: &mut dyn FnMut() -> Box<dyn Shader + 's>,
+                                progress: &mut dyn FnMut(&image::RgbImage, usize)) -> RenderStats {
+
+        let face_colors = colors.face_colors(coordinates);
+        let (buffer, zbuffer) = self.framebuffer.buffers_mut();
+
+        let mut stats = draw_faces_in_order(coordinates, buffer, texture, zbuffer, &self.depth,
+                                            &self.view_port, &self.projection, &self.model_view, &self.light_vector,
+                                            order, |face_index| -> Box<dyn Shader + 'a> {
+            let shader: Box<dyn Shader + 'a> = match face_colors[face_index] {
+                Some(color) => Box::new(shader::ColorShader::new(color, colors.lit)),
+                None => shader()
+            };
+
+            if coordinates.vertex_occlusion.is_empty() {
+                shader
+            } else {
+                Box::new(OcclusionShader::new(shader, &coordinates.vertex_occlusion))
+            }
+        }, progress);
+
+        stats.batches = count_batches(&face_colors, order);
+
+        if let Some(ref outline) = colors.outline {
+            draw_outline(coordinates, buffer, zbuffer, &self.depth, &self.view_port, &self.projection,
+                         &self.model_view, outline);
+        }
+
+        let transform = self.transform;
+        let screen_coordinate = |index: i32| {
+            transform * vector::vectorize_to_4d(&coordinates.geometric_vertices[index as usize])
+        };
+
+        for line in &coordinates.lines {
+            for segment in line.windows(2) {
+                let (start, end) = (screen_coordinate(segment[0]), screen_coordinate(segment[1]));
+
+                if let Some((start, end)) = clipping::clip_segment(&start, &end, buffer.width(), buffer.height()) {
+                    draw_line_3d(&start, &end, buffer, zbuffer, image::Rgb([255, 255, 255]));
+                }
+            }
+        }
+
+        // points at or behind the eye are not drawn
+        for point in coordinates.points.iter().map(|&point| screen_coordinate(point)).filter(|point| point.w > 0.0) {
+            draw_point_3d(&vector::project_to_3d(&point), buffer, zbuffer, image::Rgb([255, 255, 255]));
+        }
+
+        stats
+    }
 }
 
 
-/// Draw a filled triangle with the given points in the given color
+/// Draw a triangle mesh on the given ImageBuffer with the given shader and the illumination of the given vector
 ///
 /// # Examples
 ///
+/// ```no_run
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::render::*;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// let width = 512;
+/// let height = 512;
+/// let mut buffer = image::ImageBuffer::new(width, height);
+/// let light_vector = Vector3::new(0.0, 0.0, -1.0).normalize();
+///
+/// draw_triangle_mesh("coordinates.obj", &mut buffer, &texture, 255, &light_vector,
+///                    &Vector3::new(0.0, 0.0, 3.0), &Vector3::zeros(), &Vector3::y(), "gouraud")?;
+/// # Ok(())
+/// # }
 /// ```
-/// let mut buffer = image::ImageBuffer::new(1921, 1081);
-/// let points =  vec![Vector3::new(0, 0, 0), Vector3::new(2, 2, 2), Vector3::new(0, 2, 2)]
-/// let zbuffer = vec![-1.0, -1.0, -1.0];
+#[allow(clippy::too_many_arguments)]
+pub fn draw_triangle_mesh<'a, S>(filename: &str, buffer: &mut image::RgbImage,
+                                 texture: &image::RgbImage, depth: u32,
+                                 light_vector: &Vector3<f64>, eye: &Vector3<f64>,
+                                 center: &Vector3<f64>, up: &Vector3<f64>,
+                                 shader: S) -> Result<RenderStats, MorpheusError> where S: Into<ShaderChoice<'a>> {
+
+    let coordinates = wavefront::Object::new(filename)?;
+
+    draw_object_with_shader(&coordinates, buffer, texture, depth, light_vector, eye, center, up, shader)
+}
+
+
+/// Draw an already loaded Object into a new upright image, for servers and tests that need the pixels in memory
+///
+/// The camera and the light are checked with validate before anything is
+/// drawn, and the shader is chosen as with draw_object_with_shader.
+///
+/// # Examples
 ///
-/// draw_triangle(&points, &mut buffer, &mut zbuffer, image::Rgb([255, 255, 255]))
 /// ```
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::render::*;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let light_vector = Vector3::<f64>::z();
+/// # use rastermind::Camera;
+/// # let mut response = Vec::new();
+/// let camera = Camera::builder().eye(Vector3::new(0.0, 0.0, 3.0)).build();
+/// let image = render_to_image(&coordinates, &texture, 512, 512, &camera, &light_vector, "gouraud")?;
 ///
-fn draw_triangle(points: &Vec<Vector4<f64>>, buffer: &mut image::RgbImage,
-                 texture: &image::RgbImage, zbuffer: &mut Vec<f64>,
-                 shader: &dyn Shader) {
+/// image::ImageRgb8(image).write_to(&mut response, image::ImageOutputFormat::PNG)?;
+/// # Ok(())
+/// # }
+/// ```
+#[allow(clippy::too_many_arguments)]
+pub fn render_to_image<'a, S>(coordinates: &wavefront::Object, texture: &image::RgbImage, width: u32, height: u32,
+                              camera: &Camera, light_vector: &Vector3<f64>,
+                              shader: S) -> Result<image::RgbImage, MorpheusError>
+    where S: Into<ShaderChoice<'a>> {
 
-    let projected_points = &points.iter()
-                                  .map(|&point| vector::project_to_3d(&point).remove_row(2))
-                                  .collect();
+    render_depth_to_image(coordinates, texture, width, height, camera, light_vector, shader).map(|(image, _)| image)
+}
 
-    let (bounding_box_minimum, bounding_box_maximum) = find_bounding_box(&projected_points, buffer);
 
-    for x in bounding_box_minimum.x ..= bounding_box_maximum.x  {
-        for y in bounding_box_minimum.y ..= bounding_box_maximum.y {
-            let mut point = Vector4::new(x as f64, y as f64, 0.0, 0.0);
+/// Draw an already loaded Object into a new upright image along with its depth buffer
+///
+/// The depths are in the rows of the image, from the top, and are -1 where
+/// nothing was drawn.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::render::*;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let light_vector = Vector3::<f64>::z();
+/// # let camera = rastermind::Camera::default();
+/// let (image, depth) = render_depth_to_image(&coordinates, &texture, 512, 512, &camera, &light_vector, "flat")?;
+///
+/// let covered = depth.iter().filter(|&&depth| depth != -1.0).count();
+/// # Ok(())
+/// # }
+/// ```
+#[allow(clippy::too_many_arguments)]
+pub fn render_depth_to_image<'a, S>(coordinates: &wavefront::Object, texture: &image::RgbImage, width: u32,
+                                    height: u32, camera: &Camera, light_vector: &Vector3<f64>,
+                                    shader: S) -> Result<(image::RgbImage, Vec<f64>), MorpheusError>
+    where S: Into<ShaderChoice<'a>> {
 
-            let coordinate: Vector3<f64> = shader::find_barycentric(&projected_points, &point);
+    validate(width, height, light_vector, &camera.eye, &camera.target, &camera.up)?;
 
-            (0..=2).for_each(|i| point.z += points[i].z * coordinate[i]);
-            (0..=2).for_each(|j| point.w += points[j].w * coordinate[j]);
+    let mut pipeline = Pipeline::new(camera, light_vector, Framebuffer::new(width, height));
+    pipeline.draw_mesh_with_shader(coordinates, texture, shader)?;
 
-            if coordinate.x >= 0.0 && coordinate.y >= 0.0 && coordinate.z >= 0.0 &&
-                zbuffer[(point.x + (point.y * buffer.width() as f64)) as usize] < point.z / point.w {
+    let framebuffer = pipeline.into_framebuffer();
+    let depth = framebuffer.depth().chunks(width as usize).rev().flat_map(|row| row.iter().cloned()).collect();
 
-                let color = shader.fragment(coordinate, texture);
+    Ok((framebuffer.resolve(), depth))
+}
 
-                zbuffer[(point.x + (point.y * buffer.width() as f64)) as usize] = point.z / point.w;
-                buffer.put_pixel(point.x as u32, point.y as u32, color);
 
-            }
-        }
-    }
+/// Draw an already loaded Object on the given ImageBuffer with the illumination provided by the given vector
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::render::*;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// let mut buffer = image::ImageBuffer::new(512, 512);
+/// let coordinates = wavefront::Object::new("coordinates.obj")?;
+/// let light_vector = Vector3::new(0.0, 0.0, 1.0);
+///
+/// draw_object(&coordinates, &mut buffer, &texture, 255, &light_vector,
+///             &Vector3::new(0.0, 0.0, 3.0), &Vector3::zeros(), &Vector3::y());
+/// # Ok(())
+/// # }
+/// ```
+#[allow(clippy::too_many_arguments)]
+pub fn draw_object(coordinates: &wavefront::Object, buffer: &mut image::RgbImage,
+                   texture: &image::RgbImage, depth: u32,
+                   light_vector: &Vector3<f64>, eye: &Vector3<f64>,
+                   center: &Vector3<f64>, up: &Vector3<f64>) -> RenderStats {
+
+    draw_object_with_colors(coordinates, buffer, texture, &ColorOverride::default(), depth,
+                            light_vector, eye, center, up)
 }
 
 
-/// Draw a wire mesh on the given ImageBuffer with the coordinates from the given file
+/// Draw an already loaded Object with the given shader instead of the Gouraud shader
+///
+/// Everything else is drawn as with draw_object, so the shader is wrapped to
+/// darken the vertices of objects with ambient occlusion.
 ///
 /// # Examples
 ///
 /// ```
-/// let width = 512;
-/// let height = 512;
-/// let mut buffer = image::ImageBuffer::new(width, height);
-///
-/// draw_wire_mesh("coordinates.obj", &mut buffer);
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::render::*;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let mut buffer = image::RgbImage::new(64, 64);
+/// # let light_vector = Vector3::<f64>::z();
+/// draw_object_with_shader(&coordinates, &mut buffer, &texture, 255, &light_vector,
+///                         &Vector3::new(0.0, 0.0, 3.0), &Vector3::zeros(), &Vector3::y(), "flat")?;
+/// # Ok(())
+/// # }
 /// ```
-pub fn draw_wire_mesh(filename: &str, buffer: &mut image::RgbImage) {
-    let coordinates = wavefront::Object::new(filename);
+#[allow(clippy::too_many_arguments)]
+pub fn draw_object_with_shader<'a, S>(coordinates: &wavefront::Object, buffer: &mut image::RgbImage,
+                                      texture: &image::RgbImage, depth: u32,
+                                      light_vector: &Vector3<f64>, eye: &Vector3<f64>,
+                                      center: &Vector3<f64>, up: &Vector3<f64>,
+                                      shader: S) -> Result<RenderStats, MorpheusError>
+    where S: Into<ShaderChoice<'a>> {
+
+    let camera = Camera { eye: *eye, target: *center, up: *up, fov: None, depth };
+
+    with_pipeline(buffer, &camera, light_vector, |pipeline| {
+        pipeline.draw_mesh_with_shader(coordinates, texture, shader)
+    })
+}
 
-    for face in coordinates.geometric_faces {
-        for i in 0..3 {
-            let v0 = coordinates.geometric_vertices[(face[i]) as usize];
-            let v1 = coordinates.geometric_vertices[(face[(i+1) % 3]) as usize];
 
-            let x0 = ((v0.x + 1.0) * buffer.width() as f64 / 2.0).min(buffer.width() as f64 - 1.0);
-            let y0 = ((v0.y + 1.0) * buffer.height() as f64 / 2.0).min(buffer.height() as f64 - 1.0);
+/// Draw an already loaded Object with solid colors replacing its texture where given
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::render::*;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let light_vector = Vector3::<f64>::z();
+/// let mut buffer = image::ImageBuffer::new(512, 512);
+/// let coordinates = wavefront::Object::new("coordinates.obj")?;
+/// let colors = ColorOverride { object: Some(image::Rgb([255, 0, 0])), ..Default::default() };
+///
+/// draw_object_with_colors(&coordinates, &mut buffer, &texture, &colors, 255, &light_vector,
+///                         &Vector3::new(0.0, 0.0, 3.0), &Vector3::zeros(), &Vector3::y());
+/// # Ok(())
+/// # }
+/// ```
+#[allow(clippy::too_many_arguments)]
+pub fn draw_object_with_colors(coordinates: &wavefront::Object, buffer: &mut image::RgbImage,
+                               texture: &image::RgbImage, colors: &ColorOverride, depth: u32,
+                               light_vector: &Vector3<f64>, eye: &Vector3<f64>,
+                               center: &Vector3<f64>, up: &Vector3<f64>) -> RenderStats {
 
-            let x1 = ((v1.x + 1.0) * buffer.width() as f64 / 2.0).min(buffer.width() as f64 - 1.0);
-            let y1 = ((v1.y + 1.0) * buffer.height() as f64 / 2.0).min(buffer.height() as f64 - 1.0);
+    let camera = Camera { eye: *eye, target: *center, up: *up, fov: None, depth };
 
-            draw_line(x0 as i32, y0 as i32, x1 as i32, y1 as i32,
-                      buffer, image::Rgb([255, 255, 255]));
-        }
-    }
+    with_pipeline(buffer, &camera, light_vector, |pipeline| {
+        pipeline.draw_mesh_with_colors(coordinates, texture, colors)
+    })
 }
 
 
-/// Draw a triangle mesh on the given ImageBuffer with the illumination provided by the given vector
+/// Draw an already loaded Object into a framebuffer, depth tested against everything already drawn into it
+///
+/// The faces are drawn in batches of the same color, so faces drawn with
+/// the texture are not interleaved with faces of solid colors.
 ///
 /// # Examples
 ///
 /// ```
-/// let width = 512;
-/// let height = 512;
-/// let mut buffer = image::ImageBuffer::new(width, height);
-/// let light_vector = Vector3::new(0.0, 0.0, -1.0).normalize();
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::render::*;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let light_vector = Vector3::<f64>::z();
+/// # use rastermind::Camera;
+/// let mut framebuffer = Framebuffer::new(512, 512);
+///
+/// let camera = Camera::builder().eye(Vector3::new(0.0, 0.0, 3.0)).fov(40.0).build();
 ///
-/// draw_triangle_mesh("coordinates.obj", &mut buffer, light_vector);
+/// draw_object_into(&coordinates, &mut framebuffer, &texture, &ColorOverride::default(), &light_vector, &camera);
+/// # Ok(())
+/// # }
 /// ```
-pub fn draw_triangle_mesh(filename: &str, buffer: &mut image::RgbImage,
-                          texture: &image::RgbImage, depth: u32,
-                          light_vector: &Vector3<f64>, eye: &Vector3<f64>,
-                          center: &Vector3<f64>, up: &Vector3<f64>) {
+pub fn draw_object_into(coordinates: &wavefront::Object, framebuffer: &mut Framebuffer,
+                        texture: &image::RgbImage, colors: &ColorOverride,
+                        light_vector: &Vector3<f64>, camera: &Camera) -> RenderStats {
 
-    let coordinates = wavefront::Object::new(filename);
-    let mut zbuffer = vec![-1.0; (buffer.width() * buffer.height() + 1) as usize];
+    let mut pipeline = Pipeline::new(camera, light_vector, mem::replace(framebuffer, Framebuffer::new(0, 0)));
+    let stats = pipeline.draw_mesh_with_colors(coordinates, texture, colors);
+    *framebuffer = pipeline.into_framebuffer();
 
-    let model_view = shader::lookat(eye, center, up);
-    let projection = shader::projection(-1.0 / (eye - center).norm());
-    let view_port = shader::viewport(buffer.width() / 8, buffer.height() / 8,
-                                     buffer.width() * 3 / 4, buffer.height() * 3 / 4,
-                                     depth);
+    stats
+}
 
-    for face_index in 0..coordinates.geometric_faces.len() {
-        let mut shader = shader::GouraudShader::new();
 
-        let mut screen_coordinates: Vec<Vector4<f64>> = Vec::new();
+/// Draw an already loaded Object in an interleaved order, reporting the partially drawn buffer as it goes
+///
+/// The progress function is given the buffer, the number of faces drawn so far
+/// and the number of faces, and is called after every face, so it should
+/// decide for itself how often to act on it. The finished image matches
+/// draw_object.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::render::*;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let mut buffer = image::RgbImage::new(64, 64);
+/// # let eye = Vector3::<f64>::new(0.0, 0.0, 3.0);
+/// # let light_vector = Vector3::<f64>::z();
+/// # use std::time::Duration;
+/// # use rastermind::progress;
+/// let mut partial = progress::Progress::new("partial.png", Duration::from_secs(5));
+///
+/// draw_object_progressively(&coordinates, &mut buffer, &texture, 255, &light_vector,
+///                           &eye, &Vector3::zeros(), &Vector3::y(),
+///                           |buffer, drawn, total| partial.update(buffer, drawn, total).unwrap());
+/// # Ok(())
+/// # }
+/// ```
+#[allow(clippy::too_many_arguments)]
+pub fn draw_object_progressively<P>(coordinates: &wavefront::Object, buffer: &mut image::RgbImage,
+                                    texture: &image::RgbImage, depth: u32,
+                                    light_vector: &Vector3<f64>, eye: &Vector3<f64>,
+                                    center: &Vector3<f64>, up: &Vector3<f64>, mut progress: P) -> RenderStats
+    where P: FnMut(&image::RgbImage, usize, usize) {
+
+    let order = interleaved_order(coordinates.geometric_faces.len(), PROGRESSIVE_STRIDE);
+    let total = order.len();
+    let camera = Camera { eye: *eye, target: *center, up: *up, fov: None, depth };
+
+    with_pipeline(buffer, &camera, light_vector, |pipeline| {
+        pipeline.draw_colored(coordinates, texture, &ColorOverride::default(), &order, &mut gouraud,
+                              &mut |buffer, drawn| progress(buffer, drawn, total))
+    })
+}
 
-        for vertex_index in 0..=2 {
-            screen_coordinates.push(shader.vertex(&coordinates, &view_port, &projection,
-                                                  &model_view, &light_vector,
-                                                  face_index, vertex_index));
-        }
 
-        draw_triangle(&screen_coordinates, buffer, texture, &mut zbuffer, &shader);
-    }
+/// Draw into a color buffer through a pipeline of the camera with nothing drawn into its depth buffer
+fn with_pipeline<F, R>(buffer: &mut image::RgbImage, camera: &Camera, light_vector: &Vector3<f64>, draw: F) -> R
+    where F: FnOnce(&mut Pipeline) -> R {
+
+    let framebuffer = Framebuffer::from_color(mem::replace(buffer, image::ImageBuffer::new(0, 0)));
+    let mut pipeline = Pipeline::new(camera, light_vector, framebuffer);
+    let result = draw(&mut pipeline);
+    *buffer = pipeline.into_framebuffer().into_color();
+
+    result
+}
+
+
+/// Create the Gouraud shader that faces without a solid color are drawn with by default
+fn gouraud<'a>() -> Box<dyn Shader + 'a> {
+    Box::new(shader::GouraudShader::new())
 }
 
 
@@ -251,8 +1805,277 @@ pub fn draw_triangle_mesh(filename: &str, buffer: &mut image::RgbImage,
 mod tests {
     use std::env;
     use std::fs::File;
+    use nalgebra::geometry::Point2;
     use super::*;
 
+    #[test]
+    fn test_validate() {
+        let (eye, center, up, light) = (Vector3::new(0.0, 0.0, 3.0), Vector3::zeros(), Vector3::y(), Vector3::z());
+        let message = |result: Result<(), MorpheusError>| match result {
+            Err(MorpheusError::Render(message)) => message,
+            _ => String::new()
+        };
+
+        assert_eq!(validate(16, 16, &light, &eye, &center, &up), Ok(()));
+
+        assert!(message(validate(0, 16, &light, &eye, &center, &up)).contains("empty"));
+        assert!(message(validate(16, 16, &light, &eye, &eye, &up)).contains("same point"));
+        assert!(message(validate(16, 16, &light, &eye, &center, &Vector3::new(0.0, 0.0, -2.0))).contains("parallel"));
+        assert!(message(validate(16, 16, &light, &eye, &center, &Vector3::zeros())).contains("parallel"));
+        assert!(message(validate(16, 16, &(light * 2.0), &eye, &center, &up)).contains("normalized"));
+        assert!(message(validate(16, 16, &light, &Vector3::new(f64::NAN, 0.0, 3.0), &center, &up))
+                       .contains("not finite"));
+    }
+
+    #[test]
+    fn test_micro_triangles() {
+        let points = |x: f64, y: f64| vec![Vector4::new(x, y, 0.0, 1.0), Vector4::new(x + 0.3, y, 0.0, 1.0),
+                                           Vector4::new(x, y + 0.3, 0.0, 1.0)];
+        let fragments = |points: &Vec<Vector4<f64>>, merge_micro_triangles: bool| {
+            let mut fragments = Vec::new();
+            let depth = DepthOptions { merge_micro_triangles, ..Default::default() };
+            rasterize_triangle(points, 8, 8, &depth, 3.0, &shader::GouraudShader::new(),
+                               |x, y, coordinate, _| fragments.push((x, y, coordinate)));
+            fragments
+        };
+
+        // a triangle on the sample of its pixel covers it, and one that misses the sample does not
+        assert_eq!(fragments(&points(3.0, 4.0), false).len(), 1);
+        assert_eq!(fragments(&points(3.0, 4.0), false)[0].0, 3);
+        assert!(fragments(&points(3.5, 4.5), false).is_empty());
+
+        // unless it is merged into its pixel, where it is shaded at its centroid
+        assert_eq!(fragments(&points(3.5, 4.5), true), vec![(3, 4, Vector3::repeat(1.0 / 3.0))]);
+        assert!(fragments(&points(-0.5, 4.5), true).is_empty());
+    }
+
+    #[test]
+    fn test_batches() {
+        let materials = ["wood", "metal", "wood", "glass", "metal"];
+
+        assert_eq!(batch_order(&materials, &[0, 1, 2, 3, 4]), vec![0, 2, 1, 4, 3]);
+        assert_eq!(batch_order(&materials, &[3, 4, 2, 1]), vec![3, 4, 1, 2]);
+        assert_eq!(count_batches(&materials, &[0, 1, 2, 3, 4]), 5);
+        assert_eq!(count_batches(&materials, &[0, 2, 1, 4, 3]), 3);
+        assert_eq!(count_batches(&materials, &[]), 0);
+
+        // the faces of the two groups alternate in the file but are drawn in one batch per color
+        let strips = b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\n\
+                       g red\nf 1/1/1 2/1/1 3/1/1\ng textured\nf 1/1/1 2/1/1 3/1/1\n\
+                       g red\nf 1/1/1 2/1/1 3/1/1\ng textured\nf 1/1/1 2/1/1 3/1/1\n";
+        let coordinates = wavefront::Object::from_bytes(strips, &wavefront::LoadOptions::default()).unwrap();
+        let mut colors = ColorOverride::default();
+        colors.groups.insert(String::from("red"), image::Rgb([255, 0, 0]));
+
+        let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+        let mut buffer = image::RgbImage::new(32, 32);
+        let stats = draw_object_with_colors(&coordinates, &mut buffer, &texture, &colors, 255, &Vector3::z(),
+                                            &Vector3::new(0.0, 0.0, 3.0), &Vector3::zeros(), &Vector3::y());
+
+        assert_eq!(stats.faces, 4);
+        assert_eq!(stats.batches, 2);
+    }
+
+    #[test]
+    fn test_shader_choice() {
+        let triangle = b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\nf 1/1/1 2/1/1 3/1/1\n";
+        let coordinates = wavefront::Object::from_bytes(triangle, &wavefront::LoadOptions::default()).unwrap();
+        let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+        let draw = |shader: ShaderChoice| {
+            let mut buffer = image::RgbImage::new(32, 32);
+            draw_object_with_shader(&coordinates, &mut buffer, &texture, 255, &Vector3::z(),
+                                    &Vector3::new(0.0, 0.0, 3.0), &Vector3::zeros(), &Vector3::y(), shader)
+                .map(|_| *buffer.get_pixel(16, 14))
+        };
+
+        // the normal shader colors the face by its normal, which points straight at the camera
+        assert_eq!(draw("gouraud".into()), Ok(image::Rgb([255, 255, 255])));
+        assert_eq!(draw("normal".into()), Ok(image::Rgb([127, 127, 255])));
+        assert!(draw("plasma".into()).is_err());
+
+        let green: Box<dyn Shader> = Box::new(shader::ColorShader::new(image::Rgb([0, 255, 0]), false));
+        assert_eq!(draw(green.into()), Ok(image::Rgb([0, 255, 0])));
+    }
+
+    #[test]
+    fn test_framebuffer() {
+        let triangle = |z: f64| format!("v -1 -1 {0}\nv 1 -1 {0}\nv 0 1 {0}\nvt 0 0\nvn 0 0 1\n\
+                                         f 1/1/1 2/1/1 3/1/1\n", z);
+        let load = |z: f64| wavefront::Object::from_bytes(triangle(z).as_bytes(), &wavefront::LoadOptions::default())
+                                                         .unwrap();
+        let solid = |color: [u8; 3]| ColorOverride { object: Some(image::Rgb(color)), ..Default::default() };
+        let draw = |framebuffer: &mut Framebuffer, z: f64, color: [u8; 3]| {
+            let camera = Camera::builder().eye(Vector3::new(0.0, 0.0, 3.0)).build();
+
+            draw_object_into(&load(z), framebuffer, &image::RgbImage::new(1, 1), &solid(color), &Vector3::z(),
+                             &camera);
+        };
+
+        // the far triangle drawn second is hidden by the near triangle drawn first into the same framebuffer
+        let mut framebuffer = Framebuffer::new(32, 32);
+        draw(&mut framebuffer, 0.5, [255, 0, 0]);
+        draw(&mut framebuffer, -0.5, [0, 0, 255]);
+
+        assert_eq!(*framebuffer.color().get_pixel(16, 14), image::Rgb([255, 0, 0]));
+        assert!(framebuffer.depth_at(16, 14).is_some() && framebuffer.depth_at(0, 31).is_none());
+        assert_eq!(*framebuffer.resolve().get_pixel(16, 17), image::Rgb([255, 0, 0]));
+        assert_eq!(framebuffer.depth().len(), 32 * 32);
+
+        framebuffer.clear(image::Rgb([0, 255, 0]));
+        assert!(framebuffer.color().pixels().all(|&pixel| pixel == image::Rgb([0, 255, 0])));
+        assert!(framebuffer.depth().iter().all(|&depth| depth == -1.0));
+
+        draw(&mut framebuffer, -0.5, [0, 0, 255]);
+        assert_eq!(*framebuffer.color().get_pixel(16, 14), image::Rgb([0, 0, 255]));
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let square = b"v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\nvt 0 0\nvn 0 0 1\nf 1/1/1 2/1/1 3/1/1 4/1/1\n";
+        let coordinates = wavefront::Object::from_bytes(square, &wavefront::LoadOptions::default()).unwrap();
+        let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+        let front = Camera::builder().eye(Vector3::new(0.0, 0.0, 3.0)).build();
+
+        let mut pipeline = Pipeline::new(&front, &Vector3::z(), Framebuffer::new(32, 32));
+        assert_eq!(*pipeline.transform(), pipeline.view_port() * pipeline.projection() * pipeline.model_view());
+        assert_eq!(pipeline.project(&Vector3::zeros()).xy(), Vector2::new(16.0, 16.0));
+
+        // the wireframe is drawn over the shaded square, including its diagonal
+        let stats = pipeline.draw_mesh(&coordinates, &texture);
+        pipeline.draw_wire(&coordinates, image::Rgb([255, 0, 0]));
+
+        assert_eq!(stats.faces, 2);
+        assert_eq!(*pipeline.framebuffer().color().get_pixel(16, 16), image::Rgb([255, 0, 0]));
+        assert_eq!(*pipeline.framebuffer().color().get_pixel(20, 14), image::Rgb([255, 255, 255]));
+
+        // a second pass from behind draws into the same framebuffer with the matrices of its camera
+        pipeline.set_camera(&Camera::builder().eye(Vector3::new(0.0, 0.0, -3.0)).build());
+        pipeline.set_light(&-Vector3::z());
+        assert_eq!(pipeline.draw_mesh_with_shader(&coordinates, &texture, "normal").unwrap().faces, 2);
+        assert!(pipeline.draw_mesh_with_shader(&coordinates, &texture, "plasma").is_err());
+
+        let mut buffer = image::RgbImage::new(32, 32);
+        draw_object(&coordinates, &mut buffer, &texture, 255, &Vector3::z(), &front.eye, &front.target, &front.up);
+        let mut single = Pipeline::new(&front, &Vector3::z(), Framebuffer::new(32, 32));
+        single.draw_mesh(&coordinates, &texture);
+
+        assert_eq!(single.into_framebuffer().into_color().into_raw(), buffer.into_raw());
+    }
+
+    #[test]
+    fn test_render_to_image() {
+        let triangle = b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\nf 1/1/1 2/1/1 3/1/1\n";
+        let coordinates = wavefront::Object::from_bytes(triangle, &wavefront::LoadOptions::default()).unwrap();
+        let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+        let camera = Camera::builder().eye(Vector3::new(0.0, 0.0, 3.0)).build();
+
+        let (image, depth) = render_depth_to_image(&coordinates, &texture, 32, 16, &camera, &Vector3::z(), "gouraud")
+            .unwrap();
+
+        // the image and its depths are upright, so the apex of the triangle is near the top
+        let mut buffer = image::RgbImage::new(32, 16);
+        draw_object(&coordinates, &mut buffer, &texture, 255, &Vector3::z(), &camera.eye, &camera.target, &camera.up);
+        assert_eq!(image.clone().into_raw(), image::imageops::flip_vertical(&buffer).into_raw());
+
+        let covered = |row: u32| (0..32).filter(|&x| depth[(x + row * 32) as usize] != -1.0).count();
+        assert_eq!(depth.len(), 32 * 16);
+        assert!(covered(12) > covered(4) && covered(4) > 0);
+        assert_eq!(covered(4), (0..32).filter(|&x| image.get_pixel(x, 4)[0] > 0).count());
+
+        let flat = render_to_image(&coordinates, &texture, 32, 16, &camera, &Vector3::z(), "flat").unwrap();
+        assert_eq!(flat.dimensions(), (32, 16));
+        assert!(render_to_image(&coordinates, &texture, 0, 16, &camera, &Vector3::z(), "flat").is_err());
+        assert!(render_to_image(&coordinates, &texture, 32, 16, &camera, &Vector3::z(), "plasma").is_err());
+    }
+
+    #[test]
+    fn test_degenerate_faces() {
+        let point = |x: f64, y: f64| Vector4::new(x, y, 0.0, 1.0);
+
+        assert!(!is_degenerate(&[point(0.0, 0.0), point(4.0, 0.0), point(0.0, 4.0)]));
+        assert!(is_degenerate(&[point(0.0, 0.0), point(2.0, 2.0), point(4.0, 4.0)]));
+        assert!(is_degenerate(&[point(0.0, 0.0), point(f64::NAN, 0.0), point(0.0, 4.0)]));
+        assert!(is_degenerate(&[point(0.0, 0.0), point(4.0, 0.0), Vector4::new(0.0, 4.0, 0.0, 0.0)]));
+
+        // a face with no area and a face with a point that is not a number are counted but not drawn
+        let coordinates = wavefront::Object {
+            geometric_vertices: vec![Vector3::new(-1.0, -1.0, 0.0), Vector3::new(1.0, -1.0, 0.0),
+                                     Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 0.0, 0.0),
+                                     Vector3::new(f64::NAN, 0.0, 0.0)],
+            texture_vertices: vec![Vector2::zeros()],
+            normal_vertices: vec![Vector3::z()],
+            geometric_faces: vec![Vector3::new(0, 1, 2), Vector3::new(0, 3, 3), Vector3::new(0, 4, 2)],
+            texture_faces: vec![Vector3::zeros(); 3],
+            normal_faces: vec![Vector3::zeros(); 3],
+            ..Default::default()
+        };
+
+        let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+        let mut buffer = image::RgbImage::new(32, 32);
+        let stats = draw_object(&coordinates, &mut buffer, &texture, 255, &Vector3::z(),
+                                &Vector3::new(0.0, 0.0, 3.0), &Vector3::zeros(), &Vector3::y());
+
+        assert_eq!(stats, RenderStats { faces: 3, degenerate: 2, non_finite: 0, non_finite_fragments: 0, batches: 1,
+                                        vertex_cache_misses: 5 });
+        assert!(buffer.pixels().any(|pixel| pixel[0] > 0));
+    }
+
+    /// A shader that computes NaN in the corner of its triangles nearest to their first point
+    struct NanShader(shader::GouraudShader);
+
+    impl Shader for NanShader {
+        fn vertex(&mut self, coordinates: &wavefront::Object, view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+                  model_view: &Matrix4<f64>, light_vector: &Vector3<f64>, face_index: usize,
+                  vertex_index: usize) -> Vector4<f64> {
+
+            self.0.vertex(coordinates, view_port, projection, model_view, light_vector, face_index, vertex_index)
+        }
+
+        fn fragment(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> image::Rgb<u8> {
+            shader::to_color(self.shade(vertex, texture))
+        }
+
+        fn shade(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> Vector3<f64> {
+            let scale = if vertex.x > 0.5 { f64::NAN } else { 1.0 };
+
+            self.0.shade(vertex, texture) * scale
+        }
+    }
+
+    #[test]
+    fn test_check_non_finite() {
+        let coordinates = wavefront::Object {
+            geometric_vertices: vec![Vector3::new(-1.0, -1.0, 0.0), Vector3::new(1.0, -1.0, 0.0),
+                                     Vector3::new(0.0, 1.0, 0.0)],
+            texture_vertices: vec![Vector2::zeros()],
+            normal_vertices: vec![Vector3::z()],
+            geometric_faces: vec![Vector3::new(0, 1, 2)],
+            texture_faces: vec![Vector3::zeros()],
+            normal_faces: vec![Vector3::zeros()],
+            ..Default::default()
+        };
+
+        let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+        let mut buffer = image::RgbImage::new(32, 32);
+        let mut zbuffer = vec![-1.0; 32 * 32];
+
+        let model_view = shader::lookat(&Vector3::new(0.0, 0.0, 3.0), &Vector3::zeros(), &Vector3::y());
+        let projection = shader::projection(-1.0 / 3.0);
+        let view_port = shader::viewport(4, 4, 24, 24, 255);
+
+        let checked = DepthOptions { check_non_finite: true, ..Default::default() };
+        let stats = draw_faces(&coordinates, &mut buffer, &texture, &mut zbuffer, &checked,
+                               &view_port, &projection, &model_view, &Vector3::z(),
+                               |_| Box::new(NanShader(shader::GouraudShader::new())));
+
+        // the fragments near the first point are magenta and the rest are shaded as usual
+        assert_eq!((stats.faces, stats.degenerate, stats.non_finite, stats.batches), (1, 0, 1, 1));
+        assert!(stats.non_finite_fragments > 0);
+        assert!(buffer.pixels().any(|&pixel| pixel == NON_FINITE_COLOR));
+        assert!(buffer.pixels().any(|&pixel| pixel == image::Rgb([255, 255, 255])));
+        assert_eq!(NanShader(shader::GouraudShader::new()).name(), "rastermind::render::tests::NanShader");
+    }
+
     #[test]
     fn test_draw_line() {
         let mut dir = env::temp_dir();
@@ -289,6 +2112,239 @@ mod tests {
     }
 
 
+    #[test]
+    fn test_draw_line_3d() {
+        let mut buffer = image::ImageBuffer::new(16, 16);
+        let mut zbuffer = vec![-1.0; 16 * 16];
+
+        zbuffer[8 + 8 * 16] = 10.0;
+
+        draw_line_3d(&Vector3::new(0.0, 8.0, 0.0), &Vector3::new(15.0, 8.0, 15.0),
+                     &mut buffer, &mut zbuffer, image::Rgb([255, 255, 255]));
+        draw_point_3d(&Vector3::new(20.0, 3.0, 0.0), &mut buffer, &mut zbuffer,
+                      image::Rgb([255, 0, 0]));
+
+        for x in 0..16 {
+            let expected = if x == 8 { [0, 0, 0] } else { [255, 255, 255] };
+            assert_eq!(*buffer.get_pixel(x, 8), image::Rgb(expected));
+        }
+
+        assert_eq!(zbuffer[15 + 8 * 16], 15.0);
+        assert_eq!(zbuffer[8 + 8 * 16], 10.0);
+    }
+
+
+    #[test]
+    fn test_lines_through_the_eye() {
+        // a polyline and a point at the eye plane, and a point behind it, which would otherwise never finish drawing
+        let lines = b"v -1 0 0\nv 1 0 0\nv 0 1 3\nv 0 0 5\nl 1 2 3\nl 3 1\np 3\np 4\n";
+        let face = b"v -1 -1 0\nv 1 -1 0\nv 0 1 3\nf 1 2 3\n";
+        let options = wavefront::LoadOptions::default();
+        let texture = image::RgbImage::new(1, 1);
+        let camera = Camera::builder().eye(Vector3::new(0.0, 0.0, 3.0)).build();
+
+        let mut pipeline = Pipeline::new(&camera, &Vector3::z(), Framebuffer::new(32, 32));
+        pipeline.draw_mesh(&wavefront::Object::from_bytes(lines, &options).unwrap(), &texture);
+        pipeline.draw_wire(&wavefront::Object::from_bytes(face, &options).unwrap(), image::Rgb([255, 0, 0]));
+
+        // the segments in front of the eye are drawn, and the ones towards the eye are cut at the edge of the screen
+        let color = pipeline.into_framebuffer().into_color();
+        assert_eq!(*color.get_pixel(12, 16), image::Rgb([255, 255, 255]));
+        assert_eq!(*color.get_pixel(12, 4), image::Rgb([255, 0, 0]));
+        assert_eq!(*color.get_pixel(4, 31), image::Rgb([255, 0, 0]));
+        assert_eq!(*color.get_pixel(28, 31), image::Rgb([255, 0, 0]));
+        assert!(color.pixels().filter(|&&pixel| pixel != image::Rgb([0, 0, 0])).count() < 32 * 4);
+    }
+
+
+    #[test]
+    fn test_color_override() {
+        let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nv 0 0 0.5\nvt 0 0\nvn 0 0 1\n\
+                                                          g paint\nf 1/1/1 2/1/1 3/1/1\n\
+                                                          g glass\nf 1/1/1 2/1/1 4/1/1\n",
+                                                        &wavefront::LoadOptions::default()).unwrap();
+        let mut colors = ColorOverride::default();
+        colors.groups.insert(String::from("glass"), image::Rgb([0, 0, 255]));
+
+        assert_eq!(colors.face_colors(&coordinates), vec![None, Some(image::Rgb([0, 0, 255]))]);
+
+        // face ranges win over groups, the later range wins, and ranges past the last face are cut short
+        let mut faces = colors.clone();
+        faces.faces = vec![(1..5, image::Rgb([0, 0, 0])), (0..1, image::Rgb([1, 1, 1])), (1..2, image::Rgb([2, 2, 2]))];
+        assert_eq!(faces.face_colors(&coordinates), vec![Some(image::Rgb([1, 1, 1])), Some(image::Rgb([2, 2, 2]))]);
+
+        faces.faces = vec![(7..9, image::Rgb([0, 0, 0])), (Range { start: 1, end: 0 }, image::Rgb([0, 0, 0]))];
+        assert_eq!(faces.face_colors(&coordinates), colors.face_colors(&coordinates));
+
+        colors.object = Some(image::Rgb([255, 0, 0]));
+        let mut buffer = image::ImageBuffer::new(32, 32);
+        let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([0, 255, 0]));
+
+        draw_object_with_colors(&coordinates, &mut buffer, &texture, &colors, 255,
+                                &Vector3::new(0.0, 0.0, 1.0), &Vector3::new(0.0, 0.0, 3.0),
+                                &Vector3::zeros(), &Vector3::y());
+
+        assert_eq!(*buffer.get_pixel(16, 12), image::Rgb([0, 0, 255]));
+        assert_eq!(*buffer.get_pixel(16, 20), image::Rgb([255, 0, 0]));
+        assert!(buffer.pixels().all(|pixel| pixel[1] == 0));
+    }
+
+
+    #[test]
+    fn test_outline() {
+        let coordinates = wavefront::Object::from_bytes(b"v -1 -1 -1\nv 1 -1 -1\nv 1 1 -1\nv -1 1 -1\n\
+                                                          v -1 -1 1\nv 1 -1 1\nv 1 1 1\nv -1 1 1\nvt 0 0\n\
+                                                          vn 0 0 1\nvn 0 0 -1\nvn 1 0 0\nvn -1 0 0\n\
+                                                          vn 0 1 0\nvn 0 -1 0\n\
+                                                          f 5/1/1 6/1/1 7/1/1\nf 5/1/1 7/1/1 8/1/1\n\
+                                                          f 2/1/2 1/1/2 4/1/2\nf 2/1/2 4/1/2 3/1/2\n\
+                                                          f 6/1/3 2/1/3 3/1/3\nf 6/1/3 3/1/3 7/1/3\n\
+                                                          f 1/1/4 5/1/4 8/1/4\nf 1/1/4 8/1/4 4/1/4\n\
+                                                          f 8/1/5 7/1/5 3/1/5\nf 8/1/5 3/1/5 4/1/5\n\
+                                                          f 1/1/6 2/1/6 6/1/6\nf 1/1/6 6/1/6 5/1/6\n",
+                                                        &wavefront::LoadOptions::default()).unwrap();
+        let texture = image::RgbImage::new(1, 1);
+        let red = image::Rgb([255, 0, 0]);
+        let mut colors = ColorOverride { object: Some(red), ..Default::default() };
+
+        let render = |colors: &ColorOverride| {
+            let mut buffer = image::ImageBuffer::new(64, 64);
+            draw_object_with_colors(&coordinates, &mut buffer, &texture, colors, 255,
+                                    &Vector3::z(), &Vector3::new(4.0, 5.0, 10.0),
+                                    &Vector3::zeros(), &Vector3::y());
+            buffer
+        };
+
+        let plain = render(&colors);
+        colors.outline = Some(Outline { color: image::Rgb([0, 255, 0]), thickness: 0.2 });
+        let outlined = render(&colors);
+
+        // the outline surrounds the object without covering any of it
+        for (x, y, pixel) in outlined.enumerate_pixels() {
+            assert_eq!(*pixel == red, *plain.get_pixel(x, y) == red);
+        }
+
+        assert!(outlined.pixels().any(|&pixel| pixel == image::Rgb([0, 255, 0])));
+        assert_eq!(*outlined.get_pixel(32, 32), red);
+    }
+
+
+    #[test]
+    fn test_depth_options() {
+        let reverse = DepthOptions { mapping: DepthMapping::ReverseZ, ..Default::default() };
+        let logarithmic = DepthOptions { mapping: DepthMapping::Logarithmic, near: 1.0, far: 100.0,
+                                         ..Default::default() };
+
+        assert_eq!(reverse.encode(0.0, 0.1), Some(1.0));
+        assert_eq!(reverse.encode(0.0, 0.05), None);
+        assert!(reverse.encode(0.0, 5.0) > reverse.encode(0.0, 500.0));
+        assert_eq!(logarithmic.encode(0.0, 10.0), Some(0.5));
+        assert_eq!(logarithmic.encode(0.0, 200.0), None);
+
+        // 8 bits cannot tell distances this close apart while 24 bits can
+        let fixed = |bits| DepthOptions { precision: DepthPrecision::Fixed(bits), ..logarithmic };
+        assert_eq!(fixed(8).encode(0.0, 50.0), fixed(8).encode(0.0, 50.1));
+        assert!(fixed(24).encode(0.0, 50.0) > fixed(24).encode(0.0, 50.1));
+    }
+
+
+    #[test]
+    fn test_draw_faces_reverse_z() {
+        let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nv -1 -1 0.01\n\
+                                                          v 1 -1 0.01\nv 0 1 0.01\nvt 0 0\nvn 0 0 1\n\
+                                                          f 4/1/1 5/1/1 6/1/1\nf 1/1/1 2/1/1 3/1/1\n",
+                                                        &wavefront::LoadOptions::default()).unwrap();
+        let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+        let eye = Vector3::new(0.0, 0.0, 3.0);
+
+        for &mapping in &[DepthMapping::Standard, DepthMapping::ReverseZ, DepthMapping::Logarithmic] {
+            let depth = DepthOptions { mapping, precision: DepthPrecision::Single, ..Default::default() };
+            let mut buffer = image::ImageBuffer::new(32, 32);
+            let mut zbuffer = vec![-1.0; 32 * 32];
+
+            draw_faces(&coordinates, &mut buffer, &texture, &mut zbuffer, &depth,
+                       &shader::viewport(4, 4, 24, 24, 255), &shader::projection(-1.0 / 3.0),
+                       &shader::lookat(&eye, &Vector3::zeros(), &Vector3::y()), &Vector3::z(),
+                       |face_index| Box::new(shader::ColorShader::new(
+                           image::Rgb([255 * (1 - face_index as u8), 0, 255 * face_index as u8]), false)));
+
+            // the nearer triangle is drawn first and must hide the one behind it
+            assert_eq!(*buffer.get_pixel(16, 14), image::Rgb([255, 0, 0]));
+        }
+    }
+
+
+    #[test]
+    fn test_polygon_offset() {
+        let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 -0.5\nvt 0 0\nvn 0 0 1\n\
+                                                          f 1/1/1 2/1/1 3/1/1\n",
+                                                        &wavefront::LoadOptions::default()).unwrap();
+        let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+        let eye = Vector3::new(0.0, 0.0, 3.0);
+        let base = DepthOptions { mapping: DepthMapping::ReverseZ, precision: DepthPrecision::Fixed(16),
+                                  ..Default::default() };
+        let decal = DepthOptions { offset: PolygonOffset { factor: 1.0, units: 1.0 }, ..base };
+
+        for &(options, expected, hidden) in &[(base, [255, 0, 0], [0, 0, 255]),
+                                              (decal, [0, 0, 255], [255, 0, 0])] {
+            let mut buffer = image::ImageBuffer::new(32, 32);
+            let mut zbuffer = vec![-1.0; 32 * 32];
+
+            for (surface, &color) in [[255, 0, 0], [0, 0, 255]].iter().enumerate() {
+                let depth = if surface == 0 { base } else { options };
+
+                draw_faces(&coordinates, &mut buffer, &texture, &mut zbuffer, &depth,
+                           &shader::viewport(4, 4, 24, 24, 255), &shader::projection(-1.0 / 3.0),
+                           &shader::lookat(&eye, &Vector3::zeros(), &Vector3::y()), &Vector3::z(),
+                           |_| Box::new(shader::ColorShader::new(image::Rgb(color), false)));
+            }
+
+            // the coplanar decal is hidden by the surface unless it has an offset
+            assert!(buffer.pixels().any(|&pixel| pixel == image::Rgb(expected)));
+            assert!(buffer.pixels().all(|&pixel| pixel != image::Rgb(hidden)));
+        }
+    }
+
+
+    /// Fill triangle in the given color with points t0, t1, and t2
+    fn fill_triangle(mut t0: Point2<i32>, mut t1: Point2<i32>, mut t2: Point2<i32>,
+                     buffer: &mut image::RgbImage, color: image::Rgb<u8>) {
+
+        if t0.y > t1.y {
+            swap(&mut t0, &mut t1);
+        }
+        if t0.y > t2.y {
+            swap(&mut t0, &mut t2);
+        }
+        if t1.y > t2.y {
+            swap(&mut t1, &mut t2);
+        }
+
+        let triangle_height = t2.y - t0.y;
+
+        for i in 0..triangle_height {
+            let second_half = i > (t1.y - t0.y) || (t1.y == t0.y);
+            let segment_height = if second_half {t2.y - t1.y} else {t1.y - t0.y};
+
+            let alpha = i as f64 / triangle_height as f64;
+            let beta = if second_half { (i as f64 - (t1.y - t0.y) as f64) / segment_height as f64}
+                       else {i as f64 / segment_height as f64};
+
+            let mut a = t0.x as f64 + ((t2 - t0).x as f64 * alpha);
+            let mut b = if second_half {t1.x as f64 + ((t2 - t1).x as f64 * beta)}
+                        else {t0.x as f64 + ((t1 - t0).x as f64 * beta)};
+
+            if a > b {
+                swap(&mut a, &mut b);
+            }
+
+            for j in (a as u32)..=(b as u32) {
+                buffer.put_pixel(j, t0.y as u32 + i as u32, color);
+            }
+        }
+    }
+
+
     #[test]
     fn test_fill_triangle() {
         let mut dir = env::temp_dir();
@@ -317,4 +2373,26 @@ mod tests {
             }
         }
     }
+
+
+    #[test]
+    fn test_backend() {
+        assert_eq!(Backend::parse("cpu"), Some(Backend::Cpu));
+        assert_eq!(Backend::parse("gpu"), Some(Backend::Gpu));
+        assert_eq!(Backend::parse("tpu"), None);
+        assert!(Backend::Cpu.is_available());
+        assert_eq!(Backend::Gpu.is_available(), cfg!(feature = "wgpu"));
+    }
+
+
+    #[test]
+    fn test_interleaved_order() {
+        assert_eq!(interleaved_order(5, 2), vec![0, 2, 4, 1, 3]);
+        assert_eq!(interleaved_order(3, 16), vec![0, 1, 2]);
+        assert_eq!(interleaved_order(0, 16), Vec::<usize>::new());
+
+        let mut order = interleaved_order(100, PROGRESSIVE_STRIDE);
+        order.sort();
+        assert_eq!(order, (0..100).collect::<Vec<usize>>());
+    }
 }
@@ -0,0 +1,187 @@
+use nalgebra::Vector3;
+
+use abuffer::ABuffer;
+use camera::Camera;
+use error::MorpheusError;
+use render;
+use scene;
+use texture;
+use wavefront;
+
+
+/// Draw wavefront models into images from a single camera, for use from other crates
+///
+/// The light shines from the direction of the light vector, which defaults
+/// to the direction of the eye of the camera from the origin like the
+/// renders of the binary. Scenes of several objects are lit by their own
+/// lights instead. Rendered images are upright, so they can be saved or
+/// displayed as they are.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::{Camera, Renderer};
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// let camera = Camera::builder().eye(Vector3::new(0.0, 0.0, 3.0)).fov(40.0).build();
+/// let renderer = Renderer::new(256, 256).with_camera(camera);
+///
+/// let coordinates = wavefront::Object::new("african_head.obj")?;
+/// let image = renderer.render(&coordinates, &texture)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Renderer {
+    pub width: u32,
+    pub height: u32,
+    pub camera: Camera,
+    pub light_vector: Option<Vector3<f64>>
+}
+
+
+impl Default for Renderer {
+    fn default() -> Renderer {
+        Renderer { width: 2048, height: 2048, camera: Camera::default(), light_vector: None }
+    }
+}
+
+
+impl Renderer {
+    /// Create a new Renderer drawing images of the given size from the default camera
+    pub fn new(width: u32, height: u32) -> Renderer {
+        Renderer { width, height, ..Default::default() }
+    }
+
+    /// Draw from the given camera instead
+    pub fn with_camera(mut self, camera: Camera) -> Renderer {
+        self.camera = camera;
+        self
+    }
+
+    /// Check that images of the size of the renderer can be drawn from its camera
+    fn validate(&self) -> Result<(), MorpheusError> {
+        render::validate(self.width, self.height, &self.light_vector(), &self.camera.eye, &self.camera.target,
+                         &self.camera.up)
+    }
+
+    /// The normalized direction the light shines from
+    pub fn light_vector(&self) -> Vector3<f64> {
+        let light = self.light_vector.unwrap_or(self.camera.eye);
+
+        light.try_normalize(0.0).unwrap_or(light)
+    }
+
+    /// Draw an already loaded Object with the given texture into a new image
+    pub fn render(&self, coordinates: &wavefront::Object,
+                  texture: &image::RgbImage) -> Result<image::RgbImage, MorpheusError> {
+
+        self.validate()?;
+
+        let mut framebuffer = render::Framebuffer::new(self.width, self.height);
+        render::draw_object_into(coordinates, &mut framebuffer, texture, &render::ColorOverride::default(),
+                                 &self.light_vector(), &self.camera);
+
+        Ok(framebuffer.resolve())
+    }
+
+    /// Draw an already loaded Object into a new image along with its depths, which are -1 where nothing was drawn
+    pub fn render_with_depth(&self, coordinates: &wavefront::Object,
+                             texture: &image::RgbImage) -> Result<(image::RgbImage, Vec<f64>), MorpheusError> {
+
+        render::render_depth_to_image(coordinates, texture, self.width, self.height, &self.camera,
+                                      &self.light_vector(), "gouraud")
+    }
+
+    /// Draw every object of the scene into a new image, with their depths tested against each other
+    pub fn render_scene(&self, scene: &scene::Scene) -> Result<image::RgbImage, MorpheusError> {
+        self.validate()?;
+
+        let mut buffer = image::ImageBuffer::new(self.width, self.height);
+        scene::render_scene_with_camera(scene, &mut buffer, &self.camera);
+
+        Ok(image::imageops::flip_vertical(&buffer))
+    }
+
+    /// Record every fragment of every object of the scene in a new A-buffer, with the opacity of its object
+    pub fn render_abuffer(&self, scene: &scene::Scene) -> Result<ABuffer, MorpheusError> {
+        self.validate()?;
+
+        Ok(scene::render_abuffer(scene, self.width, self.height, &self.camera))
+    }
+
+    /// Load the model and the texture at the given paths and draw them into a new image
+    pub fn render_file(&self, model: &str, texture: &str) -> Result<image::RgbImage, MorpheusError> {
+        let coordinates = wavefront::Object::new(model)?;
+        let texture = texture::open(texture)?;
+
+        self.render(&coordinates, &texture)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use light;
+    use nalgebra::Matrix4;
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let triangle = b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\nf 1/1/1 2/1/1 3/1/1\n";
+        let coordinates = wavefront::Object::from_bytes(triangle, &wavefront::LoadOptions::default()).unwrap();
+        let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+
+        let renderer = Renderer::new(32, 16).with_camera(Camera::builder().eye(Vector3::new(0.0, 0.0, 3.0)).build());
+        let image = renderer.render(&coordinates, &texture).unwrap();
+
+        // the triangle points up in the upright image, so its bottom row is wider than its top row
+        let covered = |y: u32| (0..32).filter(|&x| image.get_pixel(x, y)[0] > 0).count();
+        assert_eq!(image.dimensions(), (32, 16));
+        assert!(covered(12) > covered(4) && covered(4) > 0);
+
+        let (_, depth) = renderer.render_with_depth(&coordinates, &texture).unwrap();
+        assert_eq!(depth.iter().filter(|&&depth| depth != -1.0).count(),
+                   image.pixels().filter(|pixel| pixel[0] > 0).count());
+
+        // a narrower field of view magnifies the triangle
+        let narrow = renderer.with_camera(Camera { fov: Some(20.0), ..renderer.camera });
+        let magnified = narrow.render(&coordinates, &texture).unwrap();
+        assert!(magnified.pixels().filter(|pixel| pixel[0] > 0).count() >
+                image.pixels().filter(|pixel| pixel[0] > 0).count());
+
+        let behind = renderer.with_camera(Camera { target: Vector3::new(0.0, 0.0, 3.0), ..renderer.camera });
+        assert!(behind.render(&coordinates, &texture).is_err());
+        assert!(renderer.render_file("missing.obj", "missing.png").is_err());
+    }
+
+    #[test]
+    fn test_render_scene() {
+        let square = b"v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\nvt 0 0\nvn 0 0 1\n\
+                       f 1/1/1 2/1/1 3/1/1\nf 1/1/1 3/1/1 4/1/1\n";
+        let square = wavefront::Object::from_bytes(square, &wavefront::LoadOptions::default()).unwrap();
+        let solid = |color: [u8; 3]| image::RgbImage::from_pixel(1, 1, image::Rgb(color));
+
+        // the red square is moved left and in front of the blue square, which is moved right
+        let mut scene = scene::Scene::new();
+        scene.lights.push(scene::SceneLight::new("sun", light::Light::Directional(Vector3::z())));
+        scene.objects.push(scene::SceneObject::new(square.clone(), solid([0, 0, 255]))
+                               .with_transform(Matrix4::new_translation(&Vector3::new(0.5, 0.0, 0.0))));
+        scene.objects.push(scene::SceneObject::new(square, solid([255, 0, 0]))
+                               .with_transform(Matrix4::new_translation(&Vector3::new(-0.5, 0.0, 0.5))));
+
+        let renderer = Renderer::new(64, 64).with_camera(Camera::builder().eye(Vector3::new(0.0, 0.0, 4.0)).build());
+        let image = renderer.render_scene(&scene).unwrap();
+
+        let (left, middle, right) = (image.get_pixel(16, 32), image.get_pixel(32, 32), image.get_pixel(50, 32));
+        assert!(left[0] > 0 && left[2] == 0);
+        assert!(middle[0] > 0 && middle[2] == 0);
+        assert!(right[0] == 0 && right[2] > 0);
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::VecDeque;
+
+use nalgebra::Vector3;
+
+
+/// The number of transformed vertices the vertex cache holds
+pub const CACHE_SIZE: usize = 32;
+
+// the weights of Tom Forsyth's linear speed vertex cache optimisation
+const CACHE_DECAY_POWER: f64 = 1.5;
+const LAST_TRIANGLE_SCORE: f64 = 0.75;
+const VALENCE_BOOST_SCALE: f64 = 2.0;
+const VALENCE_BOOST_POWER: f64 = 0.5;
+
+
+/// A first in, first out cache of the vertices transformed most recently, like those of graphics hardware
+///
+/// # Examples
+///
+/// ```
+/// # use rastermind::reorder::VertexCache;
+/// # let faces = vec![[0, 1, 2], [2, 1, 3]];
+/// let mut cache = VertexCache::default();
+///
+/// let misses = faces.iter().flat_map(|face| face.iter()).filter(|&&vertex| !cache.touch(vertex)).count();
+/// ```
+///
+#[derive(Clone, Debug, Default)]
+pub struct VertexCache {
+    vertices: VecDeque<i32>
+}
+
+
+impl VertexCache {
+    /// Use a vertex, returning whether it was still in the cache or had to be transformed again
+    pub fn touch(&mut self, vertex: i32) -> bool {
+        if self.vertices.contains(&vertex) {
+            return true;
+        }
+
+        if self.vertices.len() == CACHE_SIZE {
+            self.vertices.pop_front();
+        }
+
+        self.vertices.push_back(vertex);
+        false
+    }
+}
+
+
+/// The number of vertices that miss the vertex cache when the faces are drawn in the given order
+pub fn cache_misses(faces: &[Vector3<i32>], order: &[usize]) -> usize {
+    let mut cache = VertexCache::default();
+
+    order.iter().flat_map(|&face| faces[face].iter()).filter(|&&vertex| !cache.touch(vertex)).count()
+}
+
+
+/// How much drawing a face with the vertex next is worth, by where it is in the cache and how many faces still use it
+fn vertex_score(position: Option<usize>, remaining: usize) -> f64 {
+    if remaining == 0 {
+        return -1.0;
+    }
+
+    let cached = match position {
+        Some(position) if position < 3 => LAST_TRIANGLE_SCORE,
+        Some(position) => (1.0 - (position - 3) as f64 / (CACHE_SIZE - 3) as f64).powf(CACHE_DECAY_POWER),
+        None => 0.0
+    };
+
+    // vertices with few faces left are worth finishing, so they do not have to be transformed again later
+    cached + VALENCE_BOOST_SCALE * (remaining as f64).powf(-VALENCE_BOOST_POWER)
+}
+
+
+/// Order the faces so the vertices they share are drawn while they are still in the vertex cache
+///
+/// This is Tom Forsyth's linear speed vertex cache optimisation: every
+/// vertex is scored by its place in a simulated cache and by how many faces
+/// still use it, and the face whose vertices score the most is drawn next.
+/// Only the faces of the vertices in the cache are scored again after every
+/// face, so large meshes are ordered quickly. The faces are indices into
+/// the same vertices, such as the geometric faces of an Object.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate rastermind;
+/// # use rastermind::{reorder, wavefront};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// let order = reorder::vertex_cache_order(&coordinates.geometric_faces);
+///
+/// assert!(reorder::cache_misses(&coordinates.geometric_faces, &order) <= coordinates.geometric_vertices.len() * 2);
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn vertex_cache_order(faces: &[Vector3<i32>]) -> Vec<usize> {
+    let vertex_count = faces.iter().flat_map(|face| face.iter()).max().map_or(0, |&vertex| vertex as usize + 1);
+    let mut faces_of: Vec<Vec<usize>> = vec![Vec::new(); vertex_count];
+
+    for (index, face) in faces.iter().enumerate() {
+        for &vertex in face.iter() {
+            if faces_of[vertex as usize].last() != Some(&index) {
+                faces_of[vertex as usize].push(index);
+            }
+        }
+    }
+
+    let mut scores: Vec<f64> = faces_of.iter().map(|faces| vertex_score(None, faces.len())).collect();
+    let face_score = |face: &Vector3<i32>, scores: &[f64]| face.iter().map(|&vertex| scores[vertex as usize]).sum();
+    let mut face_scores: Vec<f64> = faces.iter().map(|face| face_score(face, &scores)).collect();
+
+    let mut drawn = vec![false; faces.len()];
+    let mut cache: Vec<usize> = Vec::with_capacity(CACHE_SIZE + 3);
+    let mut order = Vec::with_capacity(faces.len());
+    let mut best = None;
+    let mut next_undrawn = 0;
+
+    while order.len() < faces.len() {
+        // when no face of the cached vertices is left, carry on from the first face not drawn yet
+        let face = best.unwrap_or_else(|| {
+            while drawn[next_undrawn] {
+                next_undrawn += 1;
+            }
+            next_undrawn
+        });
+
+        drawn[face] = true;
+        order.push(face);
+
+        let corners: Vec<usize> = faces[face].iter().map(|&vertex| vertex as usize).collect();
+
+        for &vertex in &corners {
+            faces_of[vertex].retain(|&other| other != face);
+        }
+
+        let mut updated: Vec<usize> = Vec::with_capacity(CACHE_SIZE + 3);
+        for &vertex in corners.iter().chain(cache.iter()) {
+            if !updated.contains(&vertex) {
+                updated.push(vertex);
+            }
+        }
+
+        best = None;
+        let mut best_score = -1.0;
+
+        for (position, &vertex) in updated.iter().enumerate() {
+            let position = if position < CACHE_SIZE { Some(position) } else { None };
+            scores[vertex] = vertex_score(position, faces_of[vertex].len());
+        }
+
+        for &vertex in &updated {
+            for &other in &faces_of[vertex] {
+                face_scores[other] = face_score(&faces[other], &scores);
+
+                if face_scores[other] > best_score {
+                    best_score = face_scores[other];
+                    best = Some(other);
+                }
+            }
+        }
+
+        updated.truncate(CACHE_SIZE);
+        cache = updated;
+    }
+
+    order
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vertex_cache_order() {
+        // a grid of quads drawn column by column, which transforms every vertex of a long column twice
+        let (columns, rows) = (4, 100);
+        let vertex = |column: i32, row: i32| column * (rows + 1) + row;
+        let faces: Vec<Vector3<i32>> = (0..columns).flat_map(|column| (0..rows).flat_map(move |row| {
+            vec![Vector3::new(vertex(column, row), vertex(column + 1, row), vertex(column + 1, row + 1)),
+                 Vector3::new(vertex(column, row), vertex(column + 1, row + 1), vertex(column, row + 1))]
+        })).collect();
+
+        let unordered: Vec<usize> = (0..faces.len()).collect();
+        let order = vertex_cache_order(&faces);
+
+        let mut sorted = order.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, unordered);
+
+        let (before, after) = (cache_misses(&faces, &unordered), cache_misses(&faces, &order));
+        assert_eq!(before, 2 * (rows as usize + 1) * columns as usize);
+        assert!(after < before * 3 / 4, "{} misses after ordering, {} before", after, before);
+
+        assert!(vertex_cache_order(&[]).is_empty());
+    }
+}
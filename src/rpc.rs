@@ -0,0 +1,439 @@
+use std::io;
+use std::io::prelude::*;
+use std::net::{TcpListener, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Instant;
+
+use nalgebra::Vector3;
+use serde_json::Value;
+
+use cache;
+use error::MorpheusError;
+use preview::CameraPath;
+use render;
+use wavefront;
+
+
+/// The error codes of JSON-RPC 2.0
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+
+/// A call of a method read from a client
+#[derive(Debug, Deserialize)]
+struct Call {
+    jsonrpc: String,
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value
+}
+
+
+/// The parameters of camera.set, where the parts of the camera that are left out do not change
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CameraParams {
+    eye: Option<[f64; 3]>,
+    center: Option<[f64; 3]>,
+    up: Option<[f64; 3]>
+}
+
+
+/// The parameters of path.save, which is the file the recorded camera path is saved to
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PathParams {
+    path: String
+}
+
+
+/// The parameters of snapshot, where the size defaults to the size of the preview
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SnapshotParams {
+    path: String,
+    width: Option<u32>,
+    height: Option<u32>
+}
+
+
+/// The model, texture, and camera of an interactive preview that can be controlled by other tools
+///
+/// The model and texture are loaded through the asset cache, so reloading
+/// them only reads the files that changed since they were last loaded.
+///
+/// While a camera path is recorded, every move of the camera is added to it
+/// with the time it was made, so the exploration can be rendered offline.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::rpc::Preview;
+/// # fn main() -> Result<(), rastermind::Error> {
+/// let mut preview = Preview::open("african_head.obj", "african_head_diffuse.png", 160, 160)?;
+/// preview.eye = Vector3::new(0.0, 0.0, 50.0);
+///
+/// let buffer = preview.render(preview.width, preview.height);
+/// # Ok(())
+/// # }
+/// ```
+///
+pub struct Preview {
+    pub model: String,
+    pub texture: String,
+    pub eye: Vector3<f64>,
+    pub center: Vector3<f64>,
+    pub up: Vector3<f64>,
+    pub width: u32,
+    pub height: u32,
+    pub running: bool,
+    coordinates: Arc<wavefront::Object>,
+    texture_image: Arc<image::RgbImage>,
+    recording: Option<(Instant, CameraPath)>
+}
+
+
+impl Preview {
+    /// Load the given model and texture into a new Preview of the given size, looking at the origin
+    pub fn open(model: &str, texture: &str, width: u32, height: u32) -> Result<Preview, MorpheusError> {
+        Ok(Preview { model: model.to_string(), texture: texture.to_string(), eye: Vector3::new(0.0, 15.0, 70.0),
+                     center: Vector3::zeros(), up: Vector3::y(), width, height, running: true,
+                     coordinates: cache::mesh(model, &wavefront::LoadOptions::default())?,
+                     texture_image: cache::texture(texture)?, recording: None })
+    }
+
+    /// Load the model and texture again if their files changed, keeping the loaded ones if either fails to load
+    pub fn reload(&mut self) -> Result<(), MorpheusError> {
+        let coordinates = cache::mesh(&self.model, &wavefront::LoadOptions::default())?;
+        self.texture_image = cache::texture(&self.texture)?;
+        self.coordinates = coordinates;
+
+        Ok(())
+    }
+
+    /// Draw the model from the camera at the given size, lit from the eye
+    pub fn render(&self, width: u32, height: u32) -> image::RgbImage {
+        let mut buffer = image::ImageBuffer::new(width, height);
+
+        render::draw_object(&self.coordinates, &mut buffer, &self.texture_image, 255, &self.eye.normalize(),
+                            &self.eye, &self.center, &self.up);
+
+        buffer
+    }
+
+    /// Call the method of the given name with the given parameters
+    ///
+    /// The methods are camera.get, camera.set with any of the eye, center,
+    /// and up, assets.reload, path.record to start recording the camera,
+    /// path.save with the path to save the recording to, snapshot with the
+    /// path to save a PNG to and an optional size, and shutdown. Failed calls
+    /// return the JSON-RPC error code and message.
+    ///
+    pub fn call(&mut self, method: &str, params: Value) -> Result<Value, (i64, String)> {
+        let invalid = |error: serde_json::Error| (INVALID_PARAMS, error.to_string());
+        let params = if params.is_null() { Value::Object(Default::default()) } else { params };
+
+        match method {
+            "camera.get" => Ok(self.camera()),
+            "camera.set" => {
+                let camera: CameraParams = serde_json::from_value(params).map_err(invalid)?;
+
+                let eye = camera.eye.map_or(self.eye, Vector3::from);
+                let center = camera.center.map_or(self.center, Vector3::from);
+                let up = camera.up.map_or(self.up, Vector3::from);
+
+                // the light shines from the eye, so an eye at the origin has no light either
+                render::validate(self.width, self.height, &eye.normalize(), &eye, &center, &up)
+                       .map_err(|error| (INVALID_PARAMS, error.to_string()))?;
+
+                self.eye = eye;
+                self.center = center;
+                self.up = up;
+                self.record();
+
+                Ok(self.camera())
+            }
+            "assets.reload" => {
+                self.reload().map_err(|error| (SERVER_ERROR, error.to_string()))?;
+
+                Ok(json!({ "model": self.model, "texture": self.texture }))
+            }
+            "path.record" => {
+                self.recording = Some((Instant::now(), CameraPath::default()));
+                self.record();
+
+                Ok(Value::Null)
+            }
+            "path.save" => {
+                let save: PathParams = serde_json::from_value(params).map_err(invalid)?;
+                let (_, ref path) = *self.recording.as_ref()
+                                         .ok_or_else(|| (INVALID_REQUEST, String::from("the camera is not recorded")))?;
+
+                // a path that fails to save keeps recording, so it can be saved somewhere else
+                path.save(&save.path).map_err(|error| (SERVER_ERROR, error.to_string()))?;
+                let saved = json!({ "path": save.path, "poses": path.poses.len(), "duration": path.duration() });
+                self.recording = None;
+
+                Ok(saved)
+            }
+            "snapshot" => {
+                let snapshot: SnapshotParams = serde_json::from_value(params).map_err(invalid)?;
+                let (width, height) = (snapshot.width.unwrap_or(self.width), snapshot.height.unwrap_or(self.height));
+
+                if width == 0 || height == 0 {
+                    return Err((INVALID_PARAMS, format!("a snapshot of {}x{} would be empty", width, height)));
+                }
+
+                image::ImageRgb8(self.render(width, height)).flipv().save(&snapshot.path)
+                                                            .map_err(|error| (SERVER_ERROR, error.to_string()))?;
+
+                Ok(json!({ "path": snapshot.path, "width": width, "height": height }))
+            }
+            "shutdown" => {
+                self.running = false;
+
+                Ok(Value::Null)
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method '{}'", method)))
+        }
+    }
+
+    /// Add the camera to the path being recorded, if any
+    fn record(&mut self) {
+        if let Some((start, ref mut path)) = self.recording {
+            path.record(start.elapsed().as_secs_f64(), &self.eye, &self.center, &self.up);
+        }
+    }
+
+    fn camera(&self) -> Value {
+        json!({ "eye": [self.eye.x, self.eye.y, self.eye.z], "center": [self.center.x, self.center.y, self.center.z],
+                "up": [self.up.x, self.up.y, self.up.z] })
+    }
+}
+
+
+/// Answer the JSON-RPC 2.0 calls read from the given input until it is exhausted or the preview is shut down
+///
+/// Every line of the input is a call, and a single line holding the response
+/// is written for every call with an id, while notifications without an id
+/// are not answered. The redraw function is called after every call that
+/// succeeded, so the preview follows the changes pushed to it.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate rastermind;
+/// # use std::io;
+/// # use rastermind::rpc::Preview;
+/// # use rastermind::{rpc, terminal};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let mut preview = Preview::open("african_head.obj", "african_head_diffuse.png", 160, 160)?;
+/// let stdin = io::stdin();
+/// let stdout = io::stdout();
+///
+/// rpc::serve(&mut preview, stdin.lock(), stdout.lock(), |preview| {
+///     eprint!("\x1b[H{}", terminal::ansi(&preview.render(160, 160)));
+/// }).unwrap();
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn serve<R: BufRead, W: Write, F>(preview: &mut Preview, input: R, mut output: W,
+                                      mut redraw: F) -> io::Result<()> where F: FnMut(&Preview) {
+
+    for line in input.lines() {
+        let line = line?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (id, result) = match serde_json::from_str::<Call>(&line) {
+            Err(error) => (Some(Value::Null), Err((PARSE_ERROR, error.to_string()))),
+            Ok(ref call) if call.jsonrpc != "2.0" => {
+                (call.id.clone(), Err((INVALID_REQUEST, String::from("only JSON-RPC 2.0 is supported"))))
+            }
+            Ok(call) => (call.id, preview.call(&call.method, call.params))
+        };
+
+        if result.is_ok() {
+            redraw(preview);
+        }
+
+        if let Some(id) = id {
+            let response = match result {
+                Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                Err((code, message)) => json!({ "jsonrpc": "2.0", "id": id,
+                                                "error": { "code": code, "message": message } })
+            };
+
+            serde_json::to_writer(&mut output, &response)?;
+            output.write_all(b"\n")?;
+            output.flush()?;
+        }
+
+        if !preview.running {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+
+/// Answer JSON-RPC 2.0 calls from clients connecting to the given local address, one client at a time
+///
+/// Clients are served as with serve until one of them shuts the preview down.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate rastermind;
+/// # use rastermind::rpc::Preview;
+/// # use rastermind::{rpc, terminal};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let mut preview = Preview::open("african_head.obj", "african_head_diffuse.png", 160, 160)?;
+/// # let redraw = |preview: &Preview| eprint!("\x1b[H{}", terminal::ansi(&preview.render(160, 160)));
+/// rpc::listen(&mut preview, "127.0.0.1:9123", |preview| redraw(preview)).unwrap();
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn listen<A: ToSocketAddrs, F>(preview: &mut Preview, address: A, mut redraw: F) -> io::Result<()>
+    where F: FnMut(&Preview) {
+
+    let listener = TcpListener::bind(address)?;
+
+    for stream in listener.incoming() {
+        let stream = stream?;
+
+        serve(preview, io::BufReader::new(stream.try_clone()?), stream, &mut redraw)?;
+
+        if !preview.running {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+
+    fn preview(name: &str) -> Preview {
+        let mut model = env::temp_dir();
+        model.push(format!("morpheus_rpc_{}.obj", name));
+        let texture = model.with_extension("png");
+
+        fs::write(&model, b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\nf 1/1/1 2/1/1 3/1/1\n").unwrap();
+        image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255])).save(&texture).unwrap();
+
+        Preview::open(model.to_str().unwrap(), texture.to_str().unwrap(), 16, 16).unwrap()
+    }
+
+    #[test]
+    fn test_serve() {
+        let mut preview = preview("serve");
+        let snapshot = env::temp_dir().join("morpheus_rpc_snapshot.png");
+
+        let input = [
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "camera.set", "params": {"eye": [0, 0, 3]}}"#,
+            r#"{"jsonrpc": "2.0", "method": "camera.set", "params": {"center": [0, 0.5, 0]}}"#,
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "camera.get"}"#,
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "camera.set", "params": {"fov": 45}}"#,
+            r#"{"jsonrpc": "2.0", "id": "four", "method": "dance"}"#,
+            "{not json",
+            &format!(r#"{{"jsonrpc": "2.0", "id": 5, "method": "snapshot", "params": {{"path": {:?}, "width": 8}}}}"#,
+                     snapshot.to_str().unwrap()),
+            r#"{"jsonrpc": "2.0", "id": 6, "method": "shutdown"}"#,
+            r#"{"jsonrpc": "2.0", "id": 7, "method": "camera.get"}"#
+        ].join("\n");
+
+        let mut output = Vec::new();
+        let mut redraws = 0;
+
+        serve(&mut preview, input.as_bytes(), &mut output, |_| redraws += 1).unwrap();
+
+        let responses: Vec<Value> = output.split(|&byte| byte == b'\n').filter(|line| !line.is_empty())
+                                          .map(|line| serde_json::from_slice(line).unwrap()).collect();
+
+        // the notification is not answered and nothing is read after the shutdown
+        assert_eq!(responses.iter().map(|response| response["id"].clone()).collect::<Vec<Value>>(),
+                   vec![json!(1), json!(2), json!(3), json!("four"), Value::Null, json!(5), json!(6)]);
+        assert_eq!(redraws, 5);
+
+        assert_eq!(responses[0]["result"]["eye"], json!([0.0, 0.0, 3.0]));
+        assert_eq!(responses[1]["result"]["center"], json!([0.0, 0.5, 0.0]));
+        assert_eq!(responses[2]["error"]["code"], json!(INVALID_PARAMS));
+        assert_eq!(responses[3]["error"]["code"], json!(METHOD_NOT_FOUND));
+        assert_eq!(responses[4]["error"]["code"], json!(PARSE_ERROR));
+        assert_eq!(responses[5]["result"]["width"], json!(8));
+
+        let image = image::open(&snapshot).unwrap().to_rgb();
+        assert_eq!(image.dimensions(), (8, 16));
+        assert!(image.pixels().any(|pixel| pixel[0] > 0));
+        assert!(!preview.running);
+    }
+
+    #[test]
+    fn test_record_path() {
+        let mut preview = preview("record_path");
+        let path = env::temp_dir().join("morpheus_rpc_path.json");
+        let save = json!({ "path": path.to_str().unwrap() });
+
+        assert!(preview.call("path.save", save.clone()).is_err());
+
+        preview.call("path.record", Value::Null).unwrap();
+        preview.call("camera.set", json!({ "eye": [0, 0, 3] })).unwrap();
+        preview.call("camera.set", json!({ "center": [0, 1, 0] })).unwrap();
+        assert_eq!(preview.call("path.save", save).unwrap()["poses"], json!(3));
+
+        // the recording starts where the camera was and ends where it was moved to
+        let recorded = CameraPath::open(path.to_str().unwrap()).unwrap();
+        let last = recorded.sample(recorded.duration()).unwrap();
+        assert_eq!(recorded.sample(0.0).unwrap().eye, Vector3::new(0.0, 15.0, 70.0));
+        assert_eq!((last.eye, last.target), (Vector3::new(0.0, 0.0, 3.0), Vector3::y()));
+
+        // moves after the path was saved are not recorded
+        preview.call("camera.set", json!({ "eye": [0, 0, 5] })).unwrap();
+        assert!(preview.recording.is_none());
+    }
+
+    #[test]
+    fn test_invalid_camera() {
+        let mut preview = preview("invalid_camera");
+
+        let (code, message) = preview.call("camera.set", json!({ "eye": [0, 0, 0] })).unwrap_err();
+        assert_eq!(code, INVALID_PARAMS);
+        assert!(message.contains("same point"));
+
+        assert!(preview.call("camera.set", json!({ "up": [0, 15, 70] })).is_err());
+        assert!(preview.call("snapshot", json!({ "path": "unused.png", "width": 0 })).is_err());
+        assert_eq!(preview.eye, Vector3::new(0.0, 15.0, 70.0));
+    }
+
+    #[test]
+    fn test_reload() {
+        let mut preview = preview("reload");
+        fs::write(&preview.model, b"v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0\nvt 0 0\nvn 0 0 1\n\
+                                    f 1/1/1 2/1/1 3/1/1\nf 2/1/1 4/1/1 3/1/1\n").unwrap();
+        fs::File::options().write(true).open(&preview.model).unwrap()
+                           .set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(10)).unwrap();
+
+        assert_eq!(preview.call("assets.reload", Value::Null).unwrap()["model"], json!(preview.model));
+        assert_eq!(preview.coordinates.geometric_faces.len(), 2);
+    }
+}
@@ -0,0 +1,2036 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use nalgebra::{Matrix4, Vector3};
+
+use abuffer;
+use abuffer::ABuffer;
+use animation::CameraSpline;
+use assets::SearchPaths;
+use camera;
+use camera::Camera;
+use clipping::{Cap, ClipPlane, ClipShader};
+use curvature;
+use curvature::{Cavity, CavityShader};
+use error::MorpheusError;
+use hair;
+use hair::HairOptions;
+use interchange;
+use light::{Light, LightShader, Spotlight, SumShader};
+use occlusion::OcclusionShader;
+use overlay;
+use peeling;
+use plugins;
+use post;
+use queue;
+use queue::{QueuedObject, RenderQueue};
+use reflection::{PlanarReflection, ReflectionShader};
+use render;
+use renderer::Renderer;
+use shader;
+use shader::{Aov, AovShader, Shader};
+use shadow;
+use sky::Sky;
+use subsurface::{Subsurface, SubsurfaceShader};
+use transparency::ScreenDoorShader;
+use wavefront;
+
+
+/// The largest width and height of the image of a scene description
+const MAXIMUM_SIZE: u32 = 16384;
+
+
+/// Flags that control how an object takes part in a scene
+///
+/// A ground plane that receives shadows without being visible to the camera
+/// catches the shadows of the other objects, while an object that casts
+/// shadows without being visible blocks light without being drawn. A shadow
+/// catcher is drawn as nothing but the shadows it receives, which only shows
+/// in renders with transparency such as `render_product_shot`.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Visibility {
+    pub camera: bool,
+    pub cast_shadows: bool,
+    pub receive_shadows: bool,
+    pub shadow_catcher: bool
+}
+
+
+impl Default for Visibility {
+    fn default() -> Visibility {
+        Visibility { camera: true, cast_shadows: true, receive_shadows: true, shadow_catcher: false }
+    }
+}
+
+
+/// The names of the lights of a scene that light an object
+///
+/// An object is lit by the lights in the include list, or by every light when
+/// the list is empty, except for the lights in the exclude list. Objects
+/// still cast shadows from lights that do not light them.
+///
+/// # Examples
+///
+/// ```
+/// # use rastermind::scene::LightLinks;
+/// let links = LightLinks { include: vec![String::from("key"), String::from("rim")],
+///                          ..Default::default() };
+/// ```
+///
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LightLinks {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>
+}
+
+
+impl LightLinks {
+    /// Whether the light with the given name lights the object
+    pub fn links(&self, name: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|include| include == name)) &&
+            !self.exclude.iter().any(|exclude| exclude == name)
+    }
+}
+
+
+/// An object of a scene with its texture, colors, visibility, lights, placement, and material
+///
+/// The transform places the object in the scene, moving it from its own
+/// coordinates before it is lit, shadowed, clipped, and drawn. Objects that
+/// are not fully opaque are drawn with screen-door transparency, where every
+/// object is dithered with a pattern of its own so overlapping transparent
+/// objects show through each other, unless the scene is peeled into layers
+/// or recorded in an A-buffer, which blend them by their opacity. Objects
+/// with subsurface scattering are lit as translucent materials, which light
+/// from behind shines through. Objects with hair options draw their
+/// polylines as strands of hair, and objects with cavity options are
+/// darkened in their cavities and brightened on their edges by the
+/// curvature of their surface. Objects with a planar reflection, such as
+/// floors and water, reflect the rest of the scene mirrored about their
+/// plane. The polygon offset moves the depths of the object towards the
+/// camera, so decals drawn on a coplanar surface do not fight with it.
+///
+pub struct SceneObject {
+    pub object: wavefront::Object,
+    pub texture: image::RgbImage,
+    pub colors: render::ColorOverride,
+    pub visibility: Visibility,
+    pub light_links: LightLinks,
+    pub transform: Matrix4<f64>,
+    pub opacity: f64,
+    pub offset: render::PolygonOffset,
+    pub subsurface: Option<Subsurface>,
+    pub hair: Option<HairOptions>,
+    pub cavity: Option<Cavity>,
+    pub reflection: Option<PlanarReflection>
+}
+
+
+impl SceneObject {
+    /// Create a new SceneObject that is fully visible, lit by every light, and drawn with the given texture
+    pub fn new(object: wavefront::Object, texture: image::RgbImage) -> SceneObject {
+        SceneObject { object, texture, colors: render::ColorOverride::default(),
+                      visibility: Visibility::default(), light_links: LightLinks::default(),
+                      transform: Matrix4::identity(), opacity: 1.0, offset: render::PolygonOffset::default(),
+                      subsurface: None, hair: None, cavity: None, reflection: None }
+    }
+
+    /// Place the object in the scene with the given model transform
+    pub fn with_transform(mut self, transform: Matrix4<f64>) -> SceneObject {
+        self.transform = transform;
+        self
+    }
+
+    /// The object moved into the scene by its transform, which is only copied when it is moved
+    pub fn placed(&self) -> Cow<'_, wavefront::Object> {
+        if self.transform == Matrix4::identity() {
+            return Cow::Borrowed(&self.object);
+        }
+
+        let mut object = self.object.clone();
+        object.transform(&self.transform);
+
+        Cow::Owned(object)
+    }
+}
+
+
+/// A light of a scene with the name that objects link to it by and the intensity it is scaled by
+#[derive(Clone, Debug, PartialEq)]
+pub struct SceneLight {
+    pub name: String,
+    pub light: Light,
+    pub intensity: f64
+}
+
+
+impl SceneLight {
+    /// Create a new SceneLight with the given name at full intensity
+    pub fn new(name: &str, light: Light) -> SceneLight {
+        SceneLight { name: String::from(name), light, intensity: 1.0 }
+    }
+}
+
+
+/// The intensities of the key, fill, and rim lights of the three-point lighting preset
+///
+/// # Examples
+///
+/// ```
+/// # use rastermind::scene::ThreePointLighting;
+/// let dramatic = ThreePointLighting { fill: 0.15, ..Default::default() };
+/// ```
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ThreePointLighting {
+    pub key: f64,
+    pub fill: f64,
+    pub rim: f64
+}
+
+
+impl Default for ThreePointLighting {
+    fn default() -> ThreePointLighting {
+        ThreePointLighting { key: 0.9, fill: 0.35, rim: 0.6 }
+    }
+}
+
+
+/// Place the key, fill, and rim lights of a classic studio setup around the given bounds as seen from the eye
+///
+/// The key light shines from above and to the right of the camera, the fill
+/// light from its left and lower down to soften the shadows of the key, and
+/// the rim light from above and behind the subject to separate it from the
+/// background. Every light is a spotlight aimed at the center of the bounds
+/// from a few times its size away, whose cone fully lights the bounds.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::scene::*;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let eye = Vector3::<f64>::new(0.0, 2.0, 5.0);
+/// let (minimum, maximum) = coordinates.bounds().unwrap();
+/// let lights = three_point_lights(&minimum, &maximum, &eye, &Vector3::y(), &ThreePointLighting::default());
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn three_point_lights(minimum: &Vector3<f64>, maximum: &Vector3<f64>, eye: &Vector3<f64>, up: &Vector3<f64>,
+                          lighting: &ThreePointLighting) -> Vec<SceneLight> {
+
+    let center = (minimum + maximum) / 2.0;
+    let radius = ((maximum - minimum).norm() / 2.0).max(1e-6);
+    let distance = 3.0 * radius;
+
+    // the frame of the camera looking at the subject, falling back to the y axis when looking along the up vector
+    let forward = (center - eye).try_normalize(1e-12).unwrap_or_else(|| -Vector3::z());
+    let right = forward.cross(up).try_normalize(1e-12).unwrap_or_else(|| forward.cross(&Vector3::y()).normalize());
+    let above = right.cross(&forward);
+
+    // the cone is as wide as the bounds as seen from the light, with a soft edge around it
+    let inner_angle = (radius / distance).asin();
+
+    let light = |name: &str, direction: Vector3<f64>, intensity: f64| {
+        let position = center + direction.normalize() * distance;
+        let spot = Spotlight::new(position, center - position, inner_angle, inner_angle * 1.5);
+
+        SceneLight { intensity, ..SceneLight::new(name, Light::Spot(spot)) }
+    };
+
+    vec![light("key", -forward + right + above * 0.8, lighting.key),
+         light("fill", -forward - right + above * 0.2, lighting.fill),
+         light("rim", forward - right * 0.3 + above, lighting.rim)]
+}
+
+
+/// How the objects of a scene that are not fully opaque are drawn
+///
+/// Screen-door transparency dithers every transparent object with a pattern
+/// of its own and keeps a single depth buffer. An A-buffer records every
+/// fragment of every object instead, and blends them from the furthest to
+/// the nearest by their opacity, so transparent objects mix exactly whatever
+/// order they are drawn in, at the cost of the memory of every fragment.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Transparency {
+    #[default]
+    ScreenDoor,
+    ABuffer
+}
+
+
+/// Several objects lit by several lights
+///
+/// The light of every light that is linked to an object is added together.
+/// Shadows are cast from every light when shadow options are given, in
+/// cascades over the view of the camera when the options have several. The
+/// clipping planes cut away the geometry behind them, showing the cap where
+/// they open up closed objects. Dimensions are drawn over the objects and
+/// the orientation gizmo over everything in its corner. A sky is drawn
+/// behind the objects, with its sun where the first directional light
+/// shines from. Transparent objects are dithered unless the transparency of
+/// the scene records them in an A-buffer.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::light::Light;
+/// # use rastermind::scene::*;
+/// # use rastermind::{shadow, wavefront};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// let mut scene = Scene::new();
+/// scene.shadows = Some(shadow::ShadowOptions::default());
+///
+/// scene.lights.push(SceneLight::new("key", Light::Directional(Vector3::new(1.0, 2.0, 1.0).normalize())));
+/// scene.objects.push(SceneObject::new(wavefront::Object::new("model.obj")?, texture));
+/// # Ok(())
+/// # }
+/// ```
+///
+#[derive(Default)]
+pub struct Scene {
+    pub objects: Vec<SceneObject>,
+    pub lights: Vec<SceneLight>,
+    pub shadows: Option<shadow::ShadowOptions>,
+    pub clipping_planes: Vec<ClipPlane>,
+    pub cap: Option<Cap>,
+    pub dimensions: Vec<overlay::Dimension>,
+    pub gizmo: Option<overlay::GizmoOptions>,
+    pub sky: Option<Sky>,
+    pub transparency: Transparency
+}
+
+
+impl Scene {
+    /// Create a new empty Scene without any lights
+    pub fn new() -> Scene {
+        Scene::default()
+    }
+
+    /// Light the objects of the scene that the camera sees with the three-point lighting preset
+    ///
+    /// The lights are placed around the bounds of every object visible to the
+    /// camera as seen from the eye, and added to the lights already in the
+    /// scene. Nothing is added to a scene without any vertices.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate image;
+    /// # extern crate nalgebra;
+    /// # extern crate rastermind;
+    /// # use nalgebra::Vector3;
+    /// # use rastermind::scene::*;
+    /// # use rastermind::wavefront;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+    /// # let eye = Vector3::<f64>::new(0.0, 2.0, 5.0);
+    /// let mut scene = Scene::new();
+    /// scene.objects.push(SceneObject::new(wavefront::Object::new("model.obj")?, texture));
+    /// scene.add_three_point_lights(&eye, &Vector3::y(), &ThreePointLighting::default());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    pub fn add_three_point_lights(&mut self, eye: &Vector3<f64>, up: &Vector3<f64>, lighting: &ThreePointLighting) {
+        let bounds = self.objects.iter().filter(|object| object.visibility.camera && !object.visibility.shadow_catcher)
+                                        .filter_map(|object| object.placed().bounds())
+                                        .reduce(|(minimum, maximum), (low, high)| {
+                                            (minimum.zip_map(&low, f64::min), maximum.zip_map(&high, f64::max))
+                                        });
+
+        if let Some((minimum, maximum)) = bounds {
+            self.lights.extend(three_point_lights(&minimum, &maximum, eye, up, lighting));
+        }
+    }
+}
+
+
+/// Draw every object of the scene that is visible to the camera into the given buffer
+///
+/// Shadow catchers are not drawn, as there is nothing behind them for their
+/// shadows to darken. The depth buffer of the render is returned, where
+/// pixels without any object are -1.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::light::Light;
+/// # use rastermind::scene::*;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let mut scene = Scene::new();
+/// # scene.objects.push(SceneObject::new(coordinates.clone(), texture.clone()));
+/// # scene.lights.push(SceneLight::new("key", Light::Directional(Vector3::z())));
+/// let mut buffer = image::ImageBuffer::new(512, 512);
+///
+/// render_scene(&scene, &mut buffer, 255, &Vector3::new(0.0, 2.0, 5.0), &Vector3::zeros(),
+///              &Vector3::y());
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn render_scene(scene: &Scene, buffer: &mut image::RgbImage, depth: u32,
+                    eye: &Vector3<f64>, center: &Vector3<f64>, up: &Vector3<f64>) -> Vec<f64> {
+
+    render_scene_with_camera(scene, buffer, &Camera { eye: *eye, target: *center, up: *up, fov: None, depth })
+}
+
+
+/// Draw every object of the scene that is visible to the given camera into the given buffer
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::light::Light;
+/// # use rastermind::scene::*;
+/// # use rastermind::{wavefront, Camera};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let mut scene = Scene::new();
+/// # scene.objects.push(SceneObject::new(coordinates.clone(), texture.clone()));
+/// # scene.lights.push(SceneLight::new("key", Light::Directional(Vector3::z())));
+/// # let mut buffer = image::RgbImage::new(64, 64);
+/// let camera = Camera::builder().eye(Vector3::new(0.0, 2.0, 5.0)).fov(50.0).build();
+///
+/// render_scene_with_camera(&scene, &mut buffer, &camera);
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn render_scene_with_camera(scene: &Scene, buffer: &mut image::RgbImage, camera: &Camera) -> Vec<f64> {
+    let view_port = camera.viewport(buffer.width(), buffer.height());
+    let (eye, center, up) = (&camera.eye, &camera.target, &camera.up);
+
+    if let Some(ref sky) = scene.sky {
+        let sun = scene.lights.iter().find_map(|light| match light.light {
+            Light::Directional(direction) => Some(direction),
+            Light::Spot(_) => None
+        });
+
+        sky.draw_with_viewport(buffer, sun.as_ref(), &view_port, eye, center, up);
+    }
+
+    match scene.transparency {
+        Transparency::ScreenDoor => {
+            draw_scene(scene, buffer, camera.depth, &view_port, eye, center, up, Pass::Color, None, None, None)
+        },
+        Transparency::ABuffer => {
+            let mut abuffer = ABuffer::new(buffer.width(), buffer.height());
+            draw_scene(scene, buffer, camera.depth, &view_port, eye, center, up, Pass::Color, None, None,
+                       Some(&mut abuffer));
+
+            // the overlays are drawn over the blended objects, hidden by the nearest fragment of every pixel
+            *buffer = abuffer.resolve(buffer);
+            let zbuffer = abuffer.nearest_depths();
+            draw_overlays(scene, buffer, &zbuffer, camera.depth, &view_port, eye, center, up);
+
+            zbuffer
+        }
+    }
+}
+
+
+/// Record every fragment of the objects of the scene seen from the camera in an A-buffer
+///
+/// Every object is recorded with its opacity as the alpha of its fragments,
+/// so the A-buffer can be resolved into an image with exact transparency or
+/// saved as a deep image with `abuffer::save_deep_exr` for deep compositing.
+/// Shadow catchers and overlays are skipped.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::light::Light;
+/// # use rastermind::scene::*;
+/// # use rastermind::{abuffer, wavefront, Camera};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let mut scene = Scene::new();
+/// # scene.objects.push(SceneObject::new(coordinates.clone(), texture.clone()));
+/// # scene.lights.push(SceneLight::new("key", Light::Directional(Vector3::z())));
+/// let abuffer = render_abuffer(&scene, 1024, 1024, &Camera::default());
+///
+/// abuffer::save_deep_exr("glass.exr", &abuffer).unwrap();
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn render_abuffer(scene: &Scene, width: u32, height: u32, camera: &Camera) -> ABuffer {
+    let mut abuffer = ABuffer::new(width, height);
+    let mut buffer = image::ImageBuffer::new(width, height);
+
+    draw_scene(scene, &mut buffer, camera.depth, &camera.viewport(width, height), &camera.eye, &camera.target,
+               &camera.up, Pass::Color, None, None, Some(&mut abuffer));
+
+    abuffer
+}
+
+
+/// Render the objects of the scene and the shadows on its shadow catchers over a transparent background
+///
+/// Pixels of objects are opaque while pixels of shadow catchers are black
+/// with an alpha of how much light their shadows block, so the image can be
+/// composited onto any backdrop.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::light::Light;
+/// # use rastermind::scene::*;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let eye = Vector3::<f64>::new(0.0, 2.0, 5.0);
+/// # let mut scene = Scene::new();
+/// # scene.objects.push(SceneObject::new(coordinates.clone(), texture.clone()));
+/// # scene.lights.push(SceneLight::new("key", Light::Directional(Vector3::z())));
+/// let mut ground = SceneObject::new(wavefront::Object::new("ground.obj")?, texture.clone());
+/// ground.visibility = Visibility { shadow_catcher: true, cast_shadows: false, ..Default::default() };
+/// scene.objects.push(ground);
+///
+/// let image = render_product_shot(&scene, 1024, 1024, 255, &eye, &Vector3::zeros(), &Vector3::y());
+/// image.save("product.png").unwrap();
+/// # Ok(())
+/// # }
+/// ```
+///
+#[allow(clippy::too_many_arguments)]
+pub fn render_product_shot(scene: &Scene, width: u32, height: u32, depth: u32,
+                           eye: &Vector3<f64>, center: &Vector3<f64>,
+                           up: &Vector3<f64>) -> image::RgbaImage {
+
+    draw_product_shot(scene, width, height, depth, &default_viewport(width, height, depth), eye, center, up)
+}
+
+
+/// Render the objects of the scene over a photograph as if they were seen by the camera that took it
+///
+/// The intrinsics of the camera are for the size of the photo, in the
+/// pixels of the photo with its first row at the top, and the eye, center,
+/// and up place that camera in the scene. Shadow catchers darken the photo
+/// by the light their shadows block, so objects standing on a catcher that
+/// matches the ground of the photo look grounded. The composite has its
+/// first row at the top like the photo, so it can be saved as it is.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::light::Light;
+/// # use rastermind::scene::*;
+/// # use rastermind::{camera, wavefront};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let eye = Vector3::<f64>::new(0.0, 2.0, 5.0);
+/// # let mut scene = Scene::new();
+/// # scene.objects.push(SceneObject::new(coordinates.clone(), texture.clone()));
+/// # scene.lights.push(SceneLight::new("key", Light::Directional(Vector3::z())));
+/// let photo = image::open("street.jpg").unwrap().to_rgb();
+/// let intrinsics = camera::Intrinsics::new(1450.0, 1450.0, 960.0, 540.0, 1920, 1080);
+///
+/// let composite = render_over_photo(&scene, &photo, &intrinsics, 255, &eye, &Vector3::zeros(), &Vector3::y());
+/// composite.save("mockup.png").unwrap();
+/// # Ok(())
+/// # }
+/// ```
+///
+#[allow(clippy::too_many_arguments)]
+pub fn render_over_photo(scene: &Scene, photo: &image::RgbImage, intrinsics: &camera::Intrinsics, depth: u32,
+                         eye: &Vector3<f64>, center: &Vector3<f64>, up: &Vector3<f64>) -> image::RgbImage {
+
+    let (width, height) = photo.dimensions();
+    let shot = draw_product_shot(scene, width, height, depth, &intrinsics.viewport(eye, center, depth),
+                                 eye, center, up);
+
+    image::ImageBuffer::from_fn(width, height, |x, y| {
+        let background = photo.get_pixel(x, y);
+        let pixel = shot.get_pixel(x, height - 1 - y);
+        let alpha = pixel[3] as f64 / 255.0;
+
+        // blend in linear light, so shadows darken the photo as much as they block the light
+        let blend = |i: usize| post::to_srgb(post::to_linear(pixel[i]) * alpha +
+                                             post::to_linear(background[i]) * (1.0 - alpha));
+
+        image::Rgb([blend(0), blend(1), blend(2)])
+    })
+}
+
+
+/// Draw the objects over a transparent background with the shadows of the catchers as translucent black
+#[allow(clippy::too_many_arguments)]
+fn draw_product_shot(scene: &Scene, width: u32, height: u32, depth: u32, view_port: &Matrix4<f64>,
+                     eye: &Vector3<f64>, center: &Vector3<f64>, up: &Vector3<f64>) -> image::RgbaImage {
+
+    let mut color = image::ImageBuffer::new(width, height);
+    let mut mask = image::ImageBuffer::new(width, height);
+
+    draw_scene(scene, &mut color, depth, view_port, eye, center, up, Pass::CatcherLight, None, None, None);
+    draw_scene(scene, &mut mask, depth, view_port, eye, center, up, Pass::Mask, None, None, None);
+
+    image::ImageBuffer::from_fn(width, height, |x, y| {
+        let pixel = color.get_pixel(x, y);
+
+        match *mask.get_pixel(x, y) {
+            OBJECT_MASK => image::Rgba([pixel[0], pixel[1], pixel[2], 255]),
+            CATCHER_MASK => image::Rgba([0, 0, 0, 255 - pixel[0]]),
+            _ => image::Rgba([0, 0, 0, 0])
+        }
+    })
+}
+
+
+/// Render separate images of the terms of the lighting of the scene seen from the camera
+///
+/// Every AOV is drawn in a pass of its own with the same camera, so the
+/// images line up and can be composited or inspected separately. Shadow
+/// catchers and overlays are skipped as in render_scene.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::light::Light;
+/// # use rastermind::scene::*;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let eye = Vector3::<f64>::new(0.0, 2.0, 5.0);
+/// # let mut scene = Scene::new();
+/// # scene.objects.push(SceneObject::new(coordinates.clone(), texture.clone()));
+/// # scene.lights.push(SceneLight::new("key", Light::Directional(Vector3::z())));
+/// # use rastermind::shader::Aov;
+/// let aovs = render_aovs(&scene, 1024, 1024, 255, &eye, &Vector3::zeros(), &Vector3::y(),
+///                        &[Aov::Diffuse, Aov::Specular, Aov::AmbientOcclusion]);
+///
+/// for (aov, image) in aovs {
+///     image.save(format!("render_{}.png", aov.name())).unwrap();
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+#[allow(clippy::too_many_arguments)]
+pub fn render_aovs(scene: &Scene, width: u32, height: u32, depth: u32,
+                   eye: &Vector3<f64>, center: &Vector3<f64>, up: &Vector3<f64>,
+                   aovs: &[Aov]) -> Vec<(Aov, image::RgbImage)> {
+
+    aovs.iter().map(|&aov| {
+        let mut buffer = image::ImageBuffer::new(width, height);
+        draw_scene(scene, &mut buffer, depth, &default_viewport(width, height, depth), eye, center, up,
+                   Pass::Aov(aov), None, None, None);
+
+        (aov, buffer)
+    }).collect()
+}
+
+
+/// Peel the surfaces of the scene seen from the camera into layers, nearest first
+///
+/// Every layer is drawn in a pass of its own behind the depths of the layer in
+/// front of it, with the opacity of every object in the alpha of its pixels,
+/// so transparent objects can be blended exactly with `peeling::composite`
+/// instead of dithered. Up to the given number of layers are peeled, and
+/// surfaces behind the last layer are left out. Shadow catchers and overlays
+/// are skipped, and the depths of the layers make a layered depth image.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate image;
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::light::Light;
+/// # use rastermind::scene::*;
+/// # use rastermind::{peeling, wavefront};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// # let eye = Vector3::<f64>::new(0.0, 2.0, 5.0);
+/// # let mut scene = Scene::new();
+/// # scene.objects.push(SceneObject::new(coordinates.clone(), texture.clone()));
+/// # scene.lights.push(SceneLight::new("key", Light::Directional(Vector3::z())));
+/// let layers = render_peeled(&scene, 1024, 1024, 255, 4, &eye, &Vector3::zeros(), &Vector3::y());
+/// let image = peeling::composite(&layers, &image::RgbImage::new(1024, 1024));
+///
+/// image::ImageRgb8(image).flipv().save("glass.png").unwrap();
+/// # Ok(())
+/// # }
+/// ```
+///
+#[allow(clippy::too_many_arguments)]
+pub fn render_peeled(scene: &Scene, width: u32, height: u32, depth: u32, layers: usize,
+                     eye: &Vector3<f64>, center: &Vector3<f64>, up: &Vector3<f64>) -> Vec<peeling::Layer> {
+
+    let view_port = default_viewport(width, height, depth);
+
+    peeling::peel(width, height, layers, |behind| {
+        let mut color = image::ImageBuffer::new(width, height);
+        let mut opacity = image::ImageBuffer::new(width, height);
+
+        let zbuffer = draw_scene(scene, &mut color, depth, &view_port, eye, center, up, Pass::Color, Some(behind),
+                                 None, None);
+        draw_scene(scene, &mut opacity, depth, &view_port, eye, center, up, Pass::Opacity, Some(behind), None, None);
+
+        let color = image::ImageBuffer::from_fn(width, height, |x, y| {
+            let pixel: &image::Rgb<u8> = color.get_pixel(x, y);
+            let alpha = if zbuffer[(x + y * width) as usize] == -1.0 { 0 } else { opacity.get_pixel(x, y)[0] };
+
+            image::Rgba([pixel[0], pixel[1], pixel[2], alpha])
+        });
+
+        peeling::Layer { color, depth: zbuffer }
+    })
+}
+
+
+const OBJECT_MASK: image::Rgb<u8> = image::Rgb([255, 255, 255]);
+const CATCHER_MASK: image::Rgb<u8> = image::Rgb([255, 0, 0]);
+
+
+/// The passes that draw a scene
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Pass {
+    /// Draw the objects and skip the shadow catchers
+    Color,
+    /// Draw the objects and draw the shadow catchers as the light that reaches them
+    CatcherLight,
+    /// Draw the objects and the shadow catchers in the colors of their masks
+    Mask,
+    /// Draw the objects with one term of their lighting and skip the shadow catchers
+    Aov(Aov),
+    /// Draw the objects in the gray of their opacity and skip the shadow catchers
+    Opacity
+}
+
+
+/// The viewport that maps the projected scene onto the center three quarters of the buffer
+fn default_viewport(width: u32, height: u32, depth: u32) -> Matrix4<f64> {
+    shader::viewport(width / 8, height / 8, width * 3 / 4, height * 3 / 4, depth)
+}
+
+
+/// Draw the objects of the scene that are visible to the camera in the given pass
+///
+/// The objects are drawn in the order of a render queue, with the opaque
+/// objects front to back and grouped by their textures, and the transparent
+/// objects back to front after them.
+///
+/// A mirror draws the scene as it is seen in a planar reflection, with the
+/// objects and lights mirrored about its plane and everything mirrored from
+/// behind the plane cut away. Reflective objects and overlays are left out
+/// of the mirrored scene, and the color pass of the scene itself draws the
+/// reflections of its reflective objects onto them.
+///
+/// An A-buffer records the fragments of the objects with their opacity
+/// instead of drawing them into the buffer, and the overlays are left for
+/// the caller to draw once it is resolved.
+///
+#[allow(clippy::too_many_arguments)]
+fn draw_scene(scene: &Scene, buffer: &mut image::RgbImage, depth: u32, view_port: &Matrix4<f64>,
+              eye: &Vector3<f64>, center: &Vector3<f64>, up: &Vector3<f64>, pass: Pass,
+              behind: Option<&[f64]>, mirror: Option<&PlanarReflection>,
+              mut abuffer: Option<&mut ABuffer>) -> Vec<f64> {
+
+    let mut zbuffer = vec![-1.0; (buffer.width() * buffer.height()) as usize];
+    let unbounded = vec![f64::INFINITY; zbuffer.len()];
+    let peeled = behind.is_some();
+    let blended = abuffer.is_some();
+    let behind = behind.unwrap_or(&unbounded);
+
+    let model_view = shader::lookat(eye, center, up);
+    let projection = shader::projection(-1.0 / (eye - center).norm());
+
+    let placed: Vec<Cow<wavefront::Object>> = scene.objects.iter().map(|object| match mirror {
+        Some(mirror) => {
+            let mut placed = object.placed().into_owned();
+            placed.transform(&mirror.matrix());
+
+            Cow::Owned(placed)
+        },
+        None => object.placed()
+    }).collect();
+
+    let lights: Cow<[SceneLight]> = match mirror {
+        Some(mirror) => Cow::Owned(scene.lights.iter().map(|light| {
+            SceneLight { light: mirror.reflect_light(&light.light), ..light.clone() }
+        }).collect()),
+        None => Cow::Borrowed(&scene.lights)
+    };
+
+    // the mirrored scene is cut by the mirrored planes, and by the mirror itself without a cap
+    let (clipping_planes, cap): (Cow<[ClipPlane]>, Option<Cap>) = match mirror {
+        Some(mirror) => (Cow::Owned(scene.clipping_planes.iter().map(|plane| mirror.reflect_clip_plane(plane))
+                                                          .chain(Some(mirror.clip_plane())).collect()), None),
+        None => (Cow::Borrowed(&scene.clipping_planes), scene.cap)
+    };
+
+    let shadow_maps: Vec<Option<shadow::ShadowMap>> = lights.iter().map(|light| {
+        scene.shadows.filter(|_| pass != Pass::Mask).map(|options| {
+            let casters: Vec<&wavefront::Object> = scene.objects.iter()
+                                                                .zip(&placed)
+                                                                .filter(|(object, _)| object.visibility.cast_shadows)
+                                                                .map(|(_, placed)| placed.as_ref())
+                                                                .collect();
+
+            shadow::ShadowMap::cascaded(&casters, &light.light, &options, view_port, &projection, &model_view,
+                                        buffer.width(), buffer.height())
+        })
+    }).collect();
+
+    let textures: Vec<&image::RgbImage> = scene.objects.iter().map(|object| &object.texture).collect();
+    let materials = queue::materials(&textures);
+
+    let queue = RenderQueue::new(scene.objects.iter().zip(&placed).enumerate().filter(|(_, (object, _))| {
+        object.visibility.camera && (mirror.is_none() || object.reflection.is_none())
+    }).map(|(index, (object, placed))| {
+        QueuedObject::new(index, placed, &model_view, materials[index], object.opacity < 1.0)
+    }).collect());
+
+    // every reflection is drawn from the same camera, so it lines up with the pixels of its object
+    let reflections: Vec<Option<(image::RgbImage, Vec<f64>)>> = scene.objects.iter().map(|object| {
+        object.reflection.as_ref().filter(|_| pass == Pass::Color && mirror.is_none() && object.visibility.camera)
+                                  .map(|reflection| {
+            let mut image = image::ImageBuffer::new(buffer.width(), buffer.height());
+            let depths = draw_scene(scene, &mut image, depth, view_port, eye, center, up, Pass::Color, None,
+                                    Some(reflection), None);
+
+            (image, depths)
+        })
+    }).collect();
+
+    for pattern in queue.order() {
+        let (object, placed) = (&scene.objects[pattern], &placed[pattern]);
+        let catcher = object.visibility.shadow_catcher;
+
+        if catcher && matches!(pass, Pass::Color | Pass::Opacity | Pass::Aov(_)) {
+            continue;
+        }
+
+        let face_colors = object.colors.face_colors(placed);
+        let receives = object.visibility.receive_shadows;
+        let linked: Vec<(&SceneLight, Option<&shadow::ShadowMap>)> = lights.iter().zip(&shadow_maps)
+            .filter(|&(light, _)| object.light_links.links(&light.name))
+            .map(|(light, shadow_map)| (light, shadow_map.as_ref().filter(|_| receives)))
+            .collect();
+
+        let lit = |shader: &dyn Fn() -> Box<dyn Shader>| -> Box<dyn Shader> {
+            let shaders = linked.iter().map(|&(light, shadow_map)| {
+                let lit: Box<dyn Shader> = Box::new(LightShader::new(shader(), &light.light)
+                                                               .with_intensity(light.intensity));
+
+                match shadow_map {
+                    Some(shadow_map) => Box::new(shadow::ShadowShader::new(lit, shadow_map)),
+                    None => lit
+                }
+            }).collect();
+
+            Box::new(SumShader::new(shaders))
+        };
+
+        let opacity = image::Rgb([(object.opacity.clamp(0.0, 1.0) * 255.0).round() as u8; 3]);
+        let curvature = object.cavity.filter(|_| !catcher).map(|_| curvature::vertex_curvature(placed));
+
+        draw_faces(placed, object, buffer, abuffer.as_deref_mut(), &mut zbuffer, behind, view_port, &projection,
+                   &model_view, |face_index| -> Box<dyn Shader> {
+            // translucent objects are shadowed by their own shader, so the light that shines through is not blocked
+            let translucent = |color: Option<image::Rgb<u8>>, subsurface: Subsurface| -> Box<dyn Shader> {
+                let shaders = linked.iter().map(|&(light, shadow_map)| -> Box<dyn Shader> {
+                    let shader = match color {
+                        Some(color) => SubsurfaceShader::with_color(color, subsurface, shadow_map),
+                        None => SubsurfaceShader::new(subsurface, shadow_map)
+                    };
+
+                    Box::new(LightShader::new(Box::new(shader), &light.light).with_intensity(light.intensity))
+                }).collect();
+
+                Box::new(SumShader::new(shaders))
+            };
+
+            let shader: Box<dyn Shader> = match (pass, catcher, face_colors[face_index], object.subsurface) {
+                (Pass::Mask, true, _, _) => Box::new(shader::ColorShader::new(CATCHER_MASK, false)),
+                (Pass::Mask, false, _, _) => Box::new(shader::ColorShader::new(OBJECT_MASK, false)),
+                (Pass::Opacity, _, _, _) => Box::new(shader::ColorShader::new(opacity, false)),
+                (_, true, _, _) => {
+                    // every shadow on a catcher darkens the light that reaches it
+                    linked.iter().fold(Box::new(shader::ColorShader::new(image::Rgb([255, 255, 255]), false)),
+                                       |shader, &(_, shadow_map)| match shadow_map {
+                        Some(shadow_map) => Box::new(shadow::ShadowShader::new(shader, shadow_map)),
+                        None => shader
+                    })
+                },
+                (_, false, color, Some(subsurface)) => translucent(color, subsurface),
+                (_, false, Some(color), None) => lit(&|| Box::new(shader::ColorShader::new(color, object.colors.lit))),
+                (_, false, None, None) => lit(&|| Box::new(shader::GouraudShader::new()))
+            };
+
+            // cavities, edges, and occlusion shade the light of the surface, not the masks of the object
+            let shaded = !catcher && !matches!(pass, Pass::Mask | Pass::Opacity);
+
+            let shader: Box<dyn Shader> = match (&curvature, object.cavity) {
+                (Some(curvature), Some(cavity)) if shaded => Box::new(CavityShader::new(shader, curvature, cavity)),
+                _ => shader
+            };
+
+            let shader: Box<dyn Shader> = if shaded && !placed.vertex_occlusion.is_empty() {
+                Box::new(OcclusionShader::new(shader, &placed.vertex_occlusion))
+            } else {
+                shader
+            };
+
+            let shader: Box<dyn Shader> = match (&reflections[pattern], &object.reflection) {
+                (Some((image, depths)), Some(reflection)) => {
+                    Box::new(ReflectionShader::new(shader, reflection, image, depths, eye))
+                },
+                _ => shader
+            };
+
+            let shader: Box<dyn Shader> = if clipping_planes.is_empty() {
+                shader
+            } else {
+                Box::new(ClipShader::new(shader, &clipping_planes, cap))
+            };
+
+            // peeled layers and A-buffers blend by the opacity instead of dithering
+            let shader: Box<dyn Shader> = if object.opacity < 1.0 && !peeled && !blended {
+                Box::new(ScreenDoorShader::new(shader, object.opacity, pattern))
+            } else {
+                shader
+            };
+
+            match pass {
+                Pass::Aov(aov) => Box::new(AovShader::new(shader, aov)),
+                _ => shader
+            }
+        });
+
+        // hair is drawn after the faces of its object as strips that face the camera, and casts no shadows
+        let hair = object.hair.filter(|_| !catcher && !placed.lines.is_empty());
+
+        if let Some(ref options) = hair {
+            let ribbons = hair::Ribbons::new(placed, view_port, &projection, &model_view, options);
+
+            draw_faces(&ribbons.object, object, buffer, abuffer.as_deref_mut(), &mut zbuffer, behind, view_port,
+                       &projection, &model_view, |face_index| -> Box<dyn Shader> {
+                let shader: Box<dyn Shader> = match pass {
+                    Pass::Mask => Box::new(shader::ColorShader::new(OBJECT_MASK, false)),
+                    Pass::Opacity => Box::new(shader::ColorShader::new(opacity, false)),
+                    _ => lit(&|| Box::new(hair::HairShader::new(options, &ribbons, face_index)))
+                };
+
+                let shader: Box<dyn Shader> = if clipping_planes.is_empty() {
+                    shader
+                } else {
+                    Box::new(ClipShader::new(shader, &clipping_planes, cap))
+                };
+
+                match pass {
+                    Pass::Aov(aov) => Box::new(AovShader::new(shader, aov)),
+                    _ => shader
+                }
+            });
+        }
+    }
+
+    if pass == Pass::Color && !peeled && !blended && mirror.is_none() {
+        draw_overlays(scene, buffer, &zbuffer, depth, view_port, eye, center, up);
+    }
+
+    zbuffer
+}
+
+
+/// Draw faces with the texture, opacity, and offset of their object into the A-buffer if there is one, or the buffer
+#[allow(clippy::too_many_arguments)]
+fn draw_faces<'a, F>(coordinates: &wavefront::Object, object: &SceneObject, buffer: &mut image::RgbImage,
+                     abuffer: Option<&mut ABuffer>, zbuffer: &mut [f64], behind: &[f64], view_port: &Matrix4<f64>,
+                     projection: &Matrix4<f64>, model_view: &Matrix4<f64>,
+                     shader_for_face: F) where F: FnMut(usize) -> Box<dyn Shader + 'a> {
+
+    let options = render::DepthOptions { offset: object.offset, ..Default::default() };
+
+    match abuffer {
+        Some(abuffer) => abuffer::draw_faces(coordinates, abuffer, &object.texture, &options, view_port, projection,
+                                             model_view, &Vector3::z(), object.opacity, shader_for_face),
+        None => {
+            render::draw_faces_behind(coordinates, buffer, &object.texture, zbuffer, behind, &options, view_port,
+                                      projection, model_view, &Vector3::z(), shader_for_face);
+        }
+    }
+}
+
+
+/// Draw the dimensions of the scene over the objects with the given depths, and the gizmo over everything
+#[allow(clippy::too_many_arguments)]
+fn draw_overlays(scene: &Scene, buffer: &mut image::RgbImage, zbuffer: &[f64], depth: u32, view_port: &Matrix4<f64>,
+                 eye: &Vector3<f64>, center: &Vector3<f64>, up: &Vector3<f64>) {
+
+    let model_view = shader::lookat(eye, center, up);
+    let projection = shader::projection(-1.0 / (eye - center).norm());
+
+    overlay::draw_dimensions(&scene.dimensions, buffer, zbuffer, depth, view_port, &projection, &model_view);
+
+    if let Some(ref gizmo) = scene.gizmo {
+        overlay::draw_gizmo(buffer, &model_view, gizmo);
+    }
+}
+
+
+/// The camera of a scene description, with its vertical field of view in degrees
+///
+/// A camera left out of the description, or any of its fields, keeps the
+/// default camera of the binary. A camera path replaces the eye, target, and
+/// up with where the camera is on the path at the frame, so every frame of a
+/// fly-through is a description of its own.
+///
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CameraDescription {
+    pub eye: [f64; 3],
+    pub target: [f64; 3],
+    pub up: [f64; 3],
+    pub fov: Option<f64>,
+    pub path: Option<CameraSpline>,
+    pub frame: f64
+}
+
+
+impl Default for CameraDescription {
+    fn default() -> CameraDescription {
+        let camera = Camera::default();
+
+        CameraDescription { eye: camera.eye.into(), target: camera.target.into(), up: camera.up.into(),
+                            fov: camera.fov, path: None, frame: 0.0 }
+    }
+}
+
+
+impl CameraDescription {
+    /// The camera the description describes
+    pub fn to_camera(&self) -> Camera {
+        match self.path.as_ref().and_then(|path| path.camera(self.frame)) {
+            Some(camera) => Camera { eye: camera.eye, target: camera.center, up: camera.up, fov: self.fov,
+                                     ..Camera::default() },
+            None => Camera { eye: Vector3::from(self.eye), target: Vector3::from(self.target),
+                             up: Vector3::from(self.up), fov: self.fov, ..Camera::default() }
+        }
+    }
+}
+
+
+/// The solid colors of a model of a scene description, which replace its texture
+///
+/// The object color applies to every face of the model, the group colors to
+/// the faces of the groups they name, and the face colors to the faces from
+/// their first to their last index, as with `render::ColorOverride`.
+///
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorsDescription {
+    pub object: Option<[u8; 3]>,
+    pub groups: HashMap<String, [u8; 3]>,
+    pub faces: Vec<FaceColorDescription>,
+    pub lit: bool
+}
+
+
+/// The color of a single face, or of the faces from the first to the last index when a last index is given
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FaceColorDescription {
+    pub first: usize,
+    pub last: Option<usize>,
+    pub color: [u8; 3]
+}
+
+
+impl ColorsDescription {
+    /// The color override the description describes
+    pub fn to_colors(&self) -> render::ColorOverride {
+        let faces = self.faces.iter().map(|face| {
+            (face.first..face.last.unwrap_or(face.first).saturating_add(1), image::Rgb(face.color))
+        }).collect();
+
+        render::ColorOverride { object: self.object.map(image::Rgb),
+                                groups: self.groups.iter().map(|(name, &color)| (name.clone(), image::Rgb(color)))
+                                                          .collect(),
+                                faces, lit: self.lit, outline: None }
+    }
+}
+
+
+/// A model of a scene description, placed in the scene by its transform
+///
+/// The model is converted from the axes and units it was authored in and
+/// scaled before it is placed. Its colors, polygon offset, visibility, and
+/// light links are those of a SceneObject.
+///
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelDescription {
+    pub path: String,
+    #[serde(default)]
+    pub texture: Option<String>,
+    #[serde(default)]
+    pub transform: interchange::Transform,
+    #[serde(default = "ModelDescription::default_opacity")]
+    pub opacity: f64,
+    #[serde(default)]
+    pub axes: wavefront::AxisConvention,
+    #[serde(default)]
+    pub units: wavefront::Units,
+    #[serde(default = "ModelDescription::default_scale")]
+    pub scale: f64,
+    #[serde(default)]
+    pub colors: ColorsDescription,
+    #[serde(default)]
+    pub polygon_offset: render::PolygonOffset,
+    #[serde(default)]
+    pub visibility: Visibility,
+    #[serde(default)]
+    pub light_links: LightLinks
+}
+
+
+impl ModelDescription {
+    fn default_opacity() -> f64 {
+        1.0
+    }
+
+    fn default_scale() -> f64 {
+        1.0
+    }
+
+    /// Give an object loaded for the model the conventions and settings of the model
+    fn apply(&self, object: &mut SceneObject) {
+        object.object.convert_axes(&self.axes);
+        object.object.scale(self.units.to_meters() * self.scale);
+
+        object.colors = self.colors.to_colors();
+        object.offset = self.polygon_offset;
+        object.visibility = self.visibility;
+        object.light_links = self.light_links.clone();
+    }
+}
+
+
+/// A render written as a TOML or JSON file instead of arguments, with its models, camera, lights, and shader
+///
+/// Models are loaded with the plugins module, so any format it opens can be
+/// placed, and a model placed several times with the same texture is only
+/// loaded once. Transforms and lights are those of the interchange format.
+/// Without a shader the scene is drawn with its own lights, or with a light
+/// from the eye when it has none. A shader, by the name it is registered
+/// under with the plugins module, draws every model visible to the camera
+/// lit by the first directional light instead, or by the light from the eye,
+/// without the colors, offsets, and light links of the models. The post
+/// options grade the render once it is drawn, and the scene is drawn as
+/// pixel art when it is asked for. The exposure analysis replaces the
+/// graded render when it is asked for.
+///
+/// # Examples
+///
+/// ```toml
+/// width = 1024
+/// height = 768
+/// shader = "cel"
+///
+/// [camera]
+/// eye = [0, 1, 4]
+/// fov = 40
+///
+/// [[models]]
+/// path = "african_head.obj"
+/// texture = "african_head_diffuse.tga"
+/// transform = { translation = [0, 0.5, 0] }
+/// units = "centimeters"
+/// axes = { up = "z" }
+/// colors = { groups = { eyes = [20, 20, 20] }, faces = [{ first = 120, last = 140, color = [90, 0, 0] }], lit = true }
+/// light_links = { exclude = ["rim"] }
+///
+/// [post]
+/// exposure = 0.5
+/// bloom = { threshold = 0.9 }
+///
+/// [[lights]]
+/// type = "directional"
+/// name = "key"
+/// direction = [1, 1, 1]
+/// ```
+///
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SceneDescription {
+    pub width: u32,
+    pub height: u32,
+    pub shader: Option<String>,
+    pub camera: CameraDescription,
+    pub models: Vec<ModelDescription>,
+    pub lights: Vec<interchange::LightNode>,
+    pub post: post::PostOptions,
+    pub pixel_art: Option<post::PixelArt>,
+    pub analysis: Option<overlay::AnalysisOptions>
+}
+
+
+impl Default for SceneDescription {
+    fn default() -> SceneDescription {
+        SceneDescription { width: 2048, height: 2048, shader: None, camera: CameraDescription::default(),
+                           models: Vec::new(), lights: Vec::new(), post: post::PostOptions::default(),
+                           pixel_art: None, analysis: None }
+    }
+}
+
+
+impl SceneDescription {
+    /// Read a description from TOML, failing on unknown keys
+    pub fn from_toml(text: &str) -> Result<SceneDescription, MorpheusError> {
+        toml::from_str(text).map_err(|error| MorpheusError::Scene(error.to_string()))
+    }
+
+    /// Read a description from JSON, failing on unknown keys
+    pub fn from_json(text: &str) -> Result<SceneDescription, MorpheusError> {
+        serde_json::from_str(text).map_err(|error| MorpheusError::Scene(error.to_string()))
+    }
+
+    /// The models and lights of the description as an interchange scene, with one mesh per model file and texture
+    pub fn to_scene_file(&self) -> interchange::SceneFile {
+        let mut file = interchange::SceneFile { version: interchange::VERSION, lights: self.lights.clone(),
+                                                ..Default::default() };
+
+        for model in &self.models {
+            let reference = interchange::MeshReference { path: model.path.clone(), texture: model.texture.clone() };
+            let mesh = match file.meshes.iter().find(|&(_, mesh)| *mesh == reference) {
+                Some((name, _)) => name.clone(),
+                None => {
+                    let name = format!("mesh {}", file.meshes.len());
+                    file.meshes.insert(name.clone(), reference);
+                    name
+                }
+            };
+
+            file.objects.push(interchange::ObjectNode { name: model.path.clone(), mesh,
+                                                        transform: model.transform.clone(), opacity: model.opacity });
+        }
+
+        file
+    }
+
+    /// Check the size, camera, models, lights, shader, and post options of the description before anything is loaded
+    ///
+    /// The renderer checks the view and the light itself when it draws, and
+    /// the loaders check the indices of the faces they read.
+    ///
+    pub fn validate(&self) -> Result<(), MorpheusError> {
+        let invalid = |message: String| Err(MorpheusError::Scene(message));
+
+        if self.width > MAXIMUM_SIZE || self.height > MAXIMUM_SIZE {
+            return invalid(format!("the image is {}x{} but must be at most {}x{}", self.width, self.height,
+                                   MAXIMUM_SIZE, MAXIMUM_SIZE));
+        }
+
+        if !self.camera.frame.is_finite() {
+            return invalid(format!("the camera frame is {} but must be finite", self.camera.frame));
+        }
+
+        if let Some(fov) = self.camera.fov {
+            if !(fov > 0.0 && fov < 180.0) {
+                return invalid(format!("the field of view is {} degrees but must be between 0 and 180", fov));
+            }
+        }
+
+        for model in &self.models {
+            if !(model.scale.is_finite() && model.scale > 0.0 && model.opacity.is_finite()) {
+                return invalid(format!("the model {} has a scale of {} and an opacity of {}, which must be finite \
+                                        with a positive scale", model.path, model.scale, model.opacity));
+            }
+        }
+
+        for light in &self.lights {
+            let (name, direction) = match *light {
+                interchange::LightNode::Directional { ref name, direction, .. } => (name, direction),
+                interchange::LightNode::Spot { ref name, direction, .. } => (name, direction)
+            };
+            let length = Vector3::from(direction).norm();
+
+            if !(length.is_finite() && length > 0.0) {
+                return invalid(format!("the light {} has the direction {:?}, which must be finite and not zero",
+                                       name, direction));
+            }
+        }
+
+        if let Some(ref shader) = self.shader {
+            if plugins::shader(shader).is_none() {
+                return Err(MorpheusError::Render(format!("unknown shader {}", shader)));
+            }
+        }
+
+        self.post.validate()
+    }
+
+    /// Load the models of the description, looking for them with the given search paths, and draw and grade them
+    pub fn render(&self, paths: &SearchPaths) -> Result<image::RgbImage, MorpheusError> {
+        let scene = self.load(paths)?;
+
+        self.render_scene(scene)
+    }
+
+    /// Load the models of the description into a scene, converted and set up as the description says
+    ///
+    /// The description is validated first, so nothing is loaded for one
+    /// that cannot be drawn.
+    ///
+    pub fn load(&self, paths: &SearchPaths) -> Result<Scene, MorpheusError> {
+        self.validate()?;
+
+        let mut scene = self.to_scene_file().load(paths)?;
+
+        // the interchange scene places one object for every model, in the same order
+        for (model, object) in self.models.iter().zip(scene.objects.iter_mut()) {
+            model.apply(object);
+        }
+
+        Ok(scene)
+    }
+
+    /// Draw a scene loaded for the description, with any objects added to it, as the description says
+    ///
+    /// The scene is drawn with the camera, shader, and size of the
+    /// description, graded, drawn as pixel art, and analyzed in that order.
+    ///
+    pub fn render_scene(&self, scene: Scene) -> Result<image::RgbImage, MorpheusError> {
+        let buffer = match self.pixel_art {
+            Some(ref pixel_art) => {
+                let mut result = Ok(());
+                let buffer = post::render_pixel_art(self.width, self.height, pixel_art, |buffer| {
+                    result = self.draw(scene, buffer.width(), buffer.height())
+                                 .map(|drawn| *buffer = post::process(&drawn, &self.post));
+                });
+
+                result.map(|_| buffer)?
+            }
+            None => post::process(&self.draw(scene, self.width, self.height)?, &self.post)
+        };
+
+        // the histogram of the analysis is drawn at the bottom of a framebuffer, which is the top of an image
+        Ok(match self.analysis {
+            Some(ref analysis) => {
+                let flipped = image::imageops::flip_vertical(&buffer);
+                image::imageops::flip_vertical(&overlay::analyze(&flipped, analysis))
+            }
+            None => buffer
+        })
+    }
+
+    /// Draw a scene loaded for the description at the given size before it is graded
+    fn draw(&self, mut scene: Scene, width: u32, height: u32) -> Result<image::RgbImage, MorpheusError> {
+        let renderer = Renderer::new(width, height).with_camera(self.camera.to_camera());
+
+        let shader = match self.shader {
+            Some(ref shader) => shader,
+            None => {
+                if scene.lights.is_empty() {
+                    scene.lights.push(SceneLight::new("eye", Light::Directional(renderer.light_vector())));
+                }
+
+                return renderer.render_scene(&scene);
+            }
+        };
+
+        let light_vector = self.lights.iter().find_map(|light| match *light {
+            interchange::LightNode::Directional { direction, .. } => Some(Vector3::from(direction).normalize()),
+            interchange::LightNode::Spot { .. } => None
+        }).unwrap_or_else(|| renderer.light_vector());
+
+        let camera = &renderer.camera;
+        render::validate(width, height, &light_vector, &camera.eye, &camera.target, &camera.up)?;
+
+        let framebuffer = render::Framebuffer::new(width, height);
+        let mut pipeline = render::Pipeline::new(camera, &light_vector, framebuffer);
+
+        for object in scene.objects.iter().filter(|object| object.visibility.camera) {
+            pipeline.draw_mesh_with_shader(&object.placed(), &object.texture, shader.as_str())?;
+        }
+
+        Ok(pipeline.into_framebuffer().resolve())
+    }
+
+    /// Load the models of the description and record every fragment of them in an A-buffer for a deep image
+    ///
+    /// The scene is drawn with its own lights, or with a light from the eye
+    /// when it has none, so a description with a shader cannot be drawn deep.
+    /// The post options grade images, so they are left out of deep images.
+    ///
+    pub fn render_deep(&self, paths: &SearchPaths) -> Result<ABuffer, MorpheusError> {
+        if let Some(ref shader) = self.shader {
+            return Err(MorpheusError::Render(format!("the {} shader cannot draw a deep image", shader)));
+        }
+
+        let renderer = Renderer::new(self.width, self.height).with_camera(self.camera.to_camera());
+        let mut scene = self.load(paths)?;
+
+        if scene.lights.is_empty() {
+            scene.lights.push(SceneLight::new("eye", Light::Directional(renderer.light_vector())));
+        }
+
+        renderer.render_abuffer(&scene)
+    }
+}
+
+
+/// Read the scene description at the given path, as JSON for json files and TOML otherwise, and render it
+///
+/// The paths of models and textures are relative to the description.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate rastermind;
+/// # use rastermind::scene;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let image = scene::load_scene("scenes/head.toml")?;
+/// image.save("head.png").unwrap();
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn load_scene(path: &str) -> Result<image::RgbImage, MorpheusError> {
+    read_description(path)?.render(&SearchPaths::for_scene(path))
+}
+
+
+/// Read the scene description at the given path and record every fragment of it in an A-buffer
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate rastermind;
+/// # use rastermind::{abuffer, scene};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let abuffer = scene::load_deep_scene("scenes/glass.toml")?;
+/// abuffer::save_deep_exr("glass.exr", &abuffer).unwrap();
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn load_deep_scene(path: &str) -> Result<ABuffer, MorpheusError> {
+    read_description(path)?.render_deep(&SearchPaths::for_scene(path))
+}
+
+
+/// Read the scene description at the given path, as JSON for json files and TOML otherwise
+fn read_description(path: &str) -> Result<SceneDescription, MorpheusError> {
+    let text = fs::read_to_string(path).map_err(|error| MorpheusError::io(path, error))?;
+    let json = Path::new(path).extension().is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+
+    if json { SceneDescription::from_json(&text) } else { SceneDescription::from_toml(&text) }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use light;
+    use super::*;
+
+    fn colored(bytes: &[u8], color: [u8; 3], visibility: Visibility) -> SceneObject {
+        let object = wavefront::Object::from_bytes(bytes, &wavefront::LoadOptions::default()).unwrap();
+        let mut scene_object = SceneObject::new(object, image::RgbImage::new(1, 1));
+
+        scene_object.colors.object = Some(image::Rgb(color));
+        scene_object.visibility = visibility;
+        scene_object
+    }
+
+    #[test]
+    fn test_visibility_flags() {
+        let ground = b"v -4 0 -4\nv 4 0 -4\nv 4 0 4\nv -4 0 4\nvt 0 0\nvn 0 1 0\n\
+                       f 1/1/1 4/1/1 3/1/1\nf 1/1/1 3/1/1 2/1/1\n";
+        let blocker = b"v -1 1 -1\nv 1 1 -1\nv 1 1 1\nv -1 1 1\nvt 0 0\nvn 0 1 0\n\
+                        f 1/1/1 4/1/1 3/1/1\nf 1/1/1 3/1/1 2/1/1\n";
+
+        let render = |ground_visibility: Visibility, blocker_visibility: Visibility| {
+            let mut scene = Scene::new();
+            scene.lights.push(SceneLight::new("sun", Light::Directional(Vector3::y())));
+            scene.shadows = Some(shadow::ShadowOptions { size: 256, ..Default::default() });
+            scene.objects.push(colored(ground, [200, 200, 200], ground_visibility));
+            scene.objects.push(colored(blocker, [255, 0, 0], blocker_visibility));
+
+            let mut buffer = image::ImageBuffer::new(64, 64);
+            render_scene(&scene, &mut buffer, 255, &Vector3::new(0.0, 8.0, 0.0),
+                         &Vector3::zeros(), &-Vector3::z());
+            buffer
+        };
+
+        let hidden = Visibility { camera: false, ..Default::default() };
+        let lit = image::Rgb([200, 200, 200]);
+        let shadowed = image::Rgb([80, 80, 80]);
+
+        // the blocker is drawn in the middle and the ground around it
+        let default = render(Visibility::default(), Visibility::default());
+        assert_eq!(*default.get_pixel(32, 32), image::Rgb([255, 0, 0]));
+        assert_eq!(*default.get_pixel(32, 4), lit);
+
+        // without the blocker in view its shadow shows on the ground beneath it
+        let shadow = render(Visibility::default(), hidden);
+        assert_eq!(*shadow.get_pixel(32, 32), shadowed);
+
+        let no_cast = render(Visibility::default(), Visibility { cast_shadows: false, ..hidden });
+        assert_eq!(*no_cast.get_pixel(32, 32), lit);
+
+        let no_receive = render(Visibility { receive_shadows: false, ..Default::default() }, hidden);
+        assert_eq!(*no_receive.get_pixel(32, 32), lit);
+
+        let no_ground = render(hidden, hidden);
+        assert!(no_ground.pixels().all(|&pixel| pixel == image::Rgb([0, 0, 0])));
+    }
+
+
+    #[test]
+    fn test_spotlight() {
+        let ground = b"v -4 0 -4\nv 4 0 -4\nv 4 0 4\nv -4 0 4\nvt 0 0\nvn 0 1 0\n\
+                       f 1/1/1 4/1/1 3/1/1\nf 1/1/1 3/1/1 2/1/1\n";
+        let spot = light::Spotlight::new(Vector3::new(0.0, 0.5, 0.0), -Vector3::y(), 0.5, 0.8);
+
+        let mut scene = Scene::new();
+        scene.lights.push(SceneLight::new("spot", Light::Spot(spot)));
+        scene.objects.push(colored(ground, [200, 200, 200], Visibility::default()));
+
+        let mut buffer = image::ImageBuffer::new(64, 64);
+        render_scene(&scene, &mut buffer, 255, &Vector3::new(0.0, 8.0, 0.0), &Vector3::zeros(),
+                     &-Vector3::z());
+
+        // the pool of light fades out between the inner and outer cone
+        assert_eq!(*buffer.get_pixel(32, 32), image::Rgb([200, 200, 200]));
+        assert!(buffer.get_pixel(32, 41)[0] > 0 && buffer.get_pixel(32, 41)[0] < 200);
+        assert_eq!(*buffer.get_pixel(32, 50), image::Rgb([0, 0, 0]));
+    }
+
+
+    #[test]
+    fn test_three_point_lights() {
+        let wall = b"v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\nvt 0 0\nvn 0 0 1\n\
+                     f 1/1/1 2/1/1 3/1/1\nf 1/1/1 3/1/1 4/1/1\n";
+        let eye = Vector3::new(0.0, 0.0, 5.0);
+
+        let mut scene = Scene::new();
+        scene.objects.push(colored(wall, [100, 100, 100], Visibility::default()));
+        scene.objects.push(colored(wall, [0, 0, 0], Visibility { shadow_catcher: true, ..Default::default() }));
+        scene.objects[1].transform = Matrix4::new_scaling(100.0);
+        scene.add_three_point_lights(&eye, &Vector3::y(), &ThreePointLighting::default());
+
+        let names: Vec<&str> = scene.lights.iter().map(|light| light.name.as_str()).collect();
+        assert_eq!(names, vec!["key", "fill", "rim"]);
+        assert_eq!(scene.lights.iter().map(|light| light.intensity).collect::<Vec<f64>>(), vec![0.9, 0.35, 0.6]);
+
+        let spot = |index: usize| match scene.lights[index].light {
+            Light::Spot(spot) => spot,
+            _ => panic!("expected a spotlight")
+        };
+
+        // the shadow catcher does not widen the bounds, so the lights are a few times the size of the wall away
+        assert!((spot(0).position.norm() - 3.0 * 2.0f64.sqrt()).abs() < 1e-9);
+
+        // the key is to the right of and above the camera, the fill to its left, and the rim behind the wall
+        assert!(spot(0).position.x > 0.0 && spot(0).position.y > 0.0 && spot(0).position.z > 0.0);
+        assert!(spot(1).position.x < 0.0 && spot(1).position.z > 0.0);
+        assert!(spot(2).position.z < 0.0 && spot(2).position.y > 0.0);
+        assert!((0..3).all(|index| (spot(index).direction + spot(index).position.normalize()).norm() < 1e-9));
+
+        // the middle of the wall is inside every cone, and a colored wall takes the sum of the intensities
+        let mut buffer = image::ImageBuffer::new(32, 32);
+        render_scene(&scene, &mut buffer, 255, &eye, &Vector3::zeros(), &Vector3::y());
+
+        assert_eq!(*buffer.get_pixel(16, 16), image::Rgb([185, 185, 185]));
+
+        // nothing is added to a scene without vertices
+        let mut empty = Scene::new();
+        empty.add_three_point_lights(&eye, &Vector3::y(), &ThreePointLighting::default());
+        assert!(empty.lights.is_empty());
+    }
+
+
+    #[test]
+    fn test_render_aovs() {
+        let ground = b"v -4 0 -4\nv 4 0 -4\nv 4 0 4\nv -4 0 4\nvt 0 0\nvn 0 1 0\n\
+                       f 1/1/1 4/1/1 3/1/1\nf 1/1/1 3/1/1 2/1/1\n";
+        let spot = light::Spotlight::new(Vector3::new(0.0, 0.5, 0.0), -Vector3::y(), 0.5, 0.8);
+
+        let mut scene = Scene::new();
+        scene.lights.push(SceneLight::new("spot", Light::Spot(spot)));
+        scene.objects.push(colored(ground, [200, 100, 50], Visibility::default()));
+
+        let eye = Vector3::new(0.0, 8.0, 0.0);
+        let mut beauty = image::ImageBuffer::new(64, 64);
+        render_scene(&scene, &mut beauty, 255, &eye, &Vector3::zeros(), &-Vector3::z());
+
+        let aovs = render_aovs(&scene, 64, 64, 255, &eye, &Vector3::zeros(), &-Vector3::z(),
+                               &[Aov::Diffuse, Aov::Specular, Aov::AmbientOcclusion, Aov::Luminance]);
+        let pixel = |index: usize, y| *aovs[index].1.get_pixel(32, y);
+
+        assert_eq!(aovs.len(), 4);
+        assert_eq!(aovs[0].0, Aov::Diffuse);
+        // a colored object only has diffuse light, which fades out with the spotlight like the render
+        assert_eq!(pixel(0, 32), *beauty.get_pixel(32, 32));
+        assert_eq!(pixel(0, 41), *beauty.get_pixel(32, 41));
+        assert_eq!(pixel(1, 32), image::Rgb([0, 0, 0]));
+        assert_eq!(pixel(2, 32), image::Rgb([255, 255, 255]));
+        assert_eq!(pixel(3, 32), image::Rgb([118, 118, 118]));
+    }
+
+
+    #[test]
+    fn test_light_linking() {
+        let ground = b"v -4 0 -4\nv 4 0 -4\nv 4 0 4\nv -4 0 4\nvt 0 0\nvn 0 1 0\n\
+                       f 1/1/1 4/1/1 3/1/1\nf 1/1/1 3/1/1 2/1/1\n";
+        let left = light::Spotlight::new(Vector3::new(-0.5, 0.5, 0.0), -Vector3::y(), 0.3, 0.4);
+        let right = light::Spotlight::new(Vector3::new(0.5, 0.5, 0.0), -Vector3::y(), 0.3, 0.4);
+
+        let render = |light_links: LightLinks| {
+            let mut scene = Scene::new();
+            scene.lights.push(SceneLight::new("left", Light::Spot(left)));
+            scene.lights.push(SceneLight::new("right", Light::Spot(right)));
+            scene.objects.push(colored(ground, [100, 100, 100], Visibility::default()));
+            scene.objects[0].light_links = light_links;
+
+            let mut buffer = image::ImageBuffer::new(64, 64);
+            render_scene(&scene, &mut buffer, 255, &Vector3::new(0.0, 8.0, 0.0), &Vector3::zeros(),
+                         &-Vector3::z());
+            (*buffer.get_pixel(20, 32), *buffer.get_pixel(44, 32))
+        };
+
+        let lit = image::Rgb([100, 100, 100]);
+        let dark = image::Rgb([0, 0, 0]);
+        let name = |name: &str| vec![String::from(name)];
+
+        assert_eq!(render(LightLinks::default()), (lit, lit));
+        assert_eq!(render(LightLinks { include: name("left"), ..Default::default() }), (lit, dark));
+        assert_eq!(render(LightLinks { exclude: name("left"), ..Default::default() }), (dark, lit));
+        assert_eq!(render(LightLinks { include: name("left"), exclude: name("left") }), (dark, dark));
+    }
+
+
+    #[test]
+    fn test_render_product_shot() {
+        let ground = b"v -4 0 -4\nv 4 0 -4\nv 4 0 4\nv -4 0 4\nvt 0 0\nvn 0 1 0\n\
+                       f 1/1/1 4/1/1 3/1/1\nf 1/1/1 3/1/1 2/1/1\n";
+        let product = b"v -0.25 1 -0.25\nv 0.25 1 -0.25\nv 0.25 1 0.25\nv -0.25 1 0.25\nvt 0 0\nvn 0 1 0\n\
+                        f 1/1/1 4/1/1 3/1/1\nf 1/1/1 3/1/1 2/1/1\n";
+
+        let mut scene = Scene::new();
+        scene.lights.push(SceneLight::new("sun", Light::Directional(Vector3::new(1.0, 1.0, 0.0).normalize())));
+        scene.shadows = Some(shadow::ShadowOptions { size: 256, ..Default::default() });
+        scene.objects.push(colored(ground, [200, 200, 200],
+                                   Visibility { shadow_catcher: true, cast_shadows: false,
+                                                ..Default::default() }));
+        scene.objects.push(colored(product, [255, 0, 0], Visibility::default()));
+
+        let image = render_product_shot(&scene, 64, 64, 255, &Vector3::new(0.0, 8.0, 0.0),
+                                        &Vector3::zeros(), &-Vector3::z());
+
+        // the product is opaque, its shadow is translucent black, and the rest is transparent
+        assert_eq!(*image.get_pixel(32, 32), image::Rgba([255, 0, 0, 255]));
+        assert_eq!(*image.get_pixel(10, 32), image::Rgba([0, 0, 0, 153]));
+        assert_eq!(*image.get_pixel(44, 32), image::Rgba([0, 0, 0, 0]));
+        assert_eq!(*image.get_pixel(0, 0), image::Rgba([0, 0, 0, 0]));
+
+        let mut buffer = image::ImageBuffer::new(64, 64);
+        render_scene(&scene, &mut buffer, 255, &Vector3::new(0.0, 8.0, 0.0), &Vector3::zeros(),
+                     &-Vector3::z());
+        assert_eq!(*buffer.get_pixel(10, 32), image::Rgb([0, 0, 0]));
+    }
+
+    #[test]
+    fn test_opacity() {
+        let square = |z: f64| format!("v -1 -1 {0}\nv 1 -1 {0}\nv 1 1 {0}\nv -1 1 {0}\nvt 0 0\nvn 0 0 1\n\
+                                       f 1/1/1 2/1/1 3/1/1\nf 1/1/1 3/1/1 4/1/1\n", z);
+        let (red, green) = (image::Rgb([255, 0, 0]), image::Rgb([0, 255, 0]));
+
+        let mut scene = Scene::new();
+        scene.lights.push(SceneLight::new("front", Light::Directional(Vector3::z())));
+        scene.objects.push(colored(square(0.0).as_bytes(), [0, 255, 0], Visibility::default()));
+        scene.objects.push(colored(square(0.5).as_bytes(), [255, 0, 0], Visibility::default()));
+
+        let render = |scene: &Scene| {
+            let mut buffer = image::ImageBuffer::new(64, 64);
+            render_scene(scene, &mut buffer, 255, &Vector3::new(0.0, 0.0, 5.0), &Vector3::zeros(),
+                         &Vector3::y());
+            buffer
+        };
+
+        assert_eq!(*render(&scene).get_pixel(32, 32), red);
+
+        // the square behind shows through every other pixel of the transparent square in front
+        scene.objects[1].opacity = 0.5;
+        let buffer = render(&scene);
+
+        assert_eq!(*buffer.get_pixel(32, 32), green);
+        assert_eq!(*buffer.get_pixel(33, 32), red);
+        assert_eq!(*buffer.get_pixel(32, 33), red);
+
+        // an A-buffer blends every pixel of the transparent square with the square behind it instead
+        scene.transparency = Transparency::ABuffer;
+        let buffer = render(&scene);
+        let mixed = *buffer.get_pixel(32, 32);
+
+        assert_eq!(*buffer.get_pixel(33, 32), mixed);
+        assert!(mixed[0] > 100 && mixed[1] > 100 && mixed[2] == 0);
+        assert_eq!(*buffer.get_pixel(0, 0), image::Rgb([0, 0, 0]));
+
+        // an opaque square in front hides everything behind it, whatever order it is drawn in
+        scene.objects.insert(0, colored(square(1.0).as_bytes(), [0, 0, 255], Visibility::default()));
+        assert_eq!(*render(&scene).get_pixel(32, 32), image::Rgb([0, 0, 255]));
+    }
+
+    #[test]
+    fn test_subsurface() {
+        let slab = b"v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\nv -1 -1 -0.05\nv 1 -1 -0.05\nv 1 1 -0.05\nv -1 1 -0.05\n\
+                     vt 0 0\nvn 0 0 1\nvn 0 0 -1\n\
+                     f 1/1/1 2/1/1 3/1/1\nf 1/1/1 3/1/1 4/1/1\nf 5/1/2 7/1/2 6/1/2\nf 5/1/2 8/1/2 7/1/2\n";
+
+        let mut scene = Scene::new();
+        scene.lights.push(SceneLight::new("back", Light::Directional(-Vector3::z())));
+        scene.shadows = Some(shadow::ShadowOptions { size: 128, kernel: 0, ..Default::default() });
+        scene.objects.push(colored(slab, [255, 255, 255], Visibility::default()));
+        scene.objects[0].colors.lit = true;
+
+        let render = |scene: &Scene| {
+            let mut buffer = image::ImageBuffer::new(64, 64);
+            render_scene(scene, &mut buffer, 255, &Vector3::new(0.0, 0.0, 5.0), &Vector3::zeros(),
+                         &Vector3::y());
+            *buffer.get_pixel(32, 32)
+        };
+
+        // the light behind the slab only shines through it when it is translucent
+        assert_eq!(render(&scene), image::Rgb([0, 0, 0]));
+
+        scene.objects[0].subsurface = Some(Subsurface { color: image::Rgb([255, 0, 0]), ..Default::default() });
+        let shining = render(&scene);
+
+        assert!(shining[0] > 100);
+        assert_eq!(&shining.0[1..], &[0, 0]);
+    }
+
+    #[test]
+    fn test_hair() {
+        let strands: String = (0..21).map(|i| {
+            let x = i as f64 / 10.0 - 1.0;
+            format!("v {0} -1 0\nv {0} 0 0\nv {0} 1 0\nl {1} {2} {3}\n", x, 3 * i + 1, 3 * i + 2, 3 * i + 3)
+        }).collect();
+
+        let mut scene = Scene::new();
+        scene.lights.push(SceneLight::new("key", Light::Directional(Vector3::z())));
+        scene.objects.push(colored(strands.as_bytes(), [255, 255, 255], Visibility::default()));
+
+        let render = |scene: &Scene| {
+            let mut buffer = image::ImageBuffer::new(64, 64);
+            render_scene(scene, &mut buffer, 255, &Vector3::new(0.0, 0.0, 5.0), &Vector3::zeros(),
+                         &Vector3::y());
+            buffer.pixels().filter(|&&pixel| pixel != image::Rgb([0, 0, 0])).count()
+        };
+
+        // the polylines are only drawn as strands when the object has hair
+        assert_eq!(render(&scene), 0);
+
+        scene.objects[0].hair = Some(HairOptions { width: 0.05, taper: 1.0, ..Default::default() });
+        assert!(render(&scene) > 21 * 16);
+    }
+
+    #[test]
+    fn test_render_peeled() {
+        let back = b"v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\nvt 0 0\nvn 0 0 1\n\
+                     f 1/1/1 2/1/1 3/1/1\nf 1/1/1 3/1/1 4/1/1\n";
+        let front = b"v -1 -1 1\nv 1 -1 1\nv 1 1 1\nv -1 1 1\nvt 0 0\nvn 0 0 1\n\
+                      f 1/1/1 2/1/1 3/1/1\nf 1/1/1 3/1/1 4/1/1\n";
+
+        let mut scene = Scene::new();
+        scene.lights.push(SceneLight::new("key", Light::Directional(Vector3::z())));
+        scene.objects.push(colored(back, [255, 0, 0], Visibility::default()));
+        scene.objects.push(colored(front, [0, 0, 255], Visibility::default()));
+        scene.objects[1].opacity = 0.5;
+
+        let eye = Vector3::new(0.0, 0.0, 5.0);
+        let layers = render_peeled(&scene, 64, 64, 255, 4, &eye, &Vector3::zeros(), &Vector3::y());
+
+        // the translucent front is the first layer and the opaque back the second, with nothing behind it
+        assert_eq!(layers.len(), 2);
+        assert_eq!(*layers[0].color.get_pixel(32, 32), image::Rgba([0, 0, 255, 128]));
+        assert_eq!(*layers[1].color.get_pixel(32, 32), image::Rgba([255, 0, 0, 255]));
+        assert!(layers[0].depth[32 * 64 + 32] > layers[1].depth[32 * 64 + 32]);
+        assert_eq!(layers[0].color.get_pixel(0, 0)[3], 0);
+
+        // blending the layers mixes every pixel of the front with the back, instead of dithering it
+        let image = peeling::composite(&layers, &image::RgbImage::new(64, 64));
+        let mixed = *image.get_pixel(32, 32);
+
+        assert_eq!(*image.get_pixel(33, 32), mixed);
+        assert!(mixed[0] > 100 && mixed[2] > 100 && mixed[1] == 0);
+    }
+
+    #[test]
+    fn test_render_over_photo() {
+        let ground = b"v -4 0 -4\nv 4 0 -4\nv 4 0 4\nv -4 0 4\nvt 0 0\nvn 0 1 0\n\
+                       f 1/1/1 4/1/1 3/1/1\nf 1/1/1 3/1/1 2/1/1\n";
+        let product = b"v -0.25 1 -0.25\nv 0.25 1 -0.25\nv 0.25 1 0.25\nv -0.25 1 0.25\nvt 0 0\nvn 0 1 0\n\
+                        f 1/1/1 4/1/1 3/1/1\nf 1/1/1 3/1/1 2/1/1\n";
+
+        let mut scene = Scene::new();
+        scene.lights.push(SceneLight::new("sun", Light::Directional(Vector3::new(1.0, 1.0, 0.0).normalize())));
+        scene.shadows = Some(shadow::ShadowOptions { size: 256, ..Default::default() });
+        scene.objects.push(colored(ground, [200, 200, 200],
+                                   Visibility { shadow_catcher: true, cast_shadows: false,
+                                                ..Default::default() }));
+        scene.objects.push(colored(product, [255, 0, 0], Visibility::default()));
+
+        let eye = Vector3::new(0.0, 8.0, 0.0);
+        let photo = image::RgbImage::from_pixel(64, 64, image::Rgb([100, 150, 200]));
+        let intrinsics = camera::Intrinsics::from_render(64, 64, &eye, &Vector3::zeros());
+
+        let composite = render_over_photo(&scene, &photo, &intrinsics, 255, &eye, &Vector3::zeros(),
+                                          &-Vector3::z());
+
+        // the composite is the right way up, with the product over the photo and its shadow darkening it
+        assert_eq!(*composite.get_pixel(32, 31), image::Rgb([255, 0, 0]));
+        assert_eq!(*composite.get_pixel(44, 31), image::Rgb([100, 150, 200]));
+
+        let shadow = composite.get_pixel(10, 31);
+        assert!(shadow[0] < 100 && shadow[1] < 150 && shadow[2] < 200);
+        // the shadow scales the light of every channel of the photo alike
+        assert!((post::to_linear(shadow[2]) / post::to_linear(shadow[0]) -
+                 post::to_linear(200) / post::to_linear(100)).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_planar_reflection() {
+        let floor = b"v -2 0 -2\nv 2 0 -2\nv 2 0 2\nv -2 0 2\nvt 0 0\nvn 0 1 0\n\
+                      f 1/1/1 4/1/1 3/1/1\nf 1/1/1 3/1/1 2/1/1\n";
+        let wall = b"v -1 0 -1\nv 1 0 -1\nv 1 1 -1\nv -1 1 -1\nvt 0 0\nvn 0 0 1\n\
+                     f 1/1/1 2/1/1 3/1/1\nf 1/1/1 3/1/1 4/1/1\n";
+
+        let render = |reflection: Option<PlanarReflection>| {
+            let mut scene = Scene::new();
+            scene.lights.push(SceneLight::new("sun", Light::Directional(Vector3::new(0.0, 1.0, 1.0).normalize())));
+            scene.objects.push(colored(floor, [0, 0, 200], Visibility::default()));
+            scene.objects.push(colored(wall, [255, 0, 0], Visibility::default()));
+            scene.objects[0].reflection = reflection;
+
+            let mut buffer = image::ImageBuffer::new(64, 64);
+            render_scene(&scene, &mut buffer, 255, &Vector3::new(0.0, 1.0, 4.0), &Vector3::zeros(),
+                         &Vector3::y());
+            buffer
+        };
+
+        let plain = render(None);
+        let mirrored = render(Some(PlanarReflection::new(&Vector3::zeros(), &Vector3::y(), 1.5)));
+
+        // the wall shows in the floor beneath it, and the rest of the floor and the wall are untouched
+        let reflected = plain.pixels().zip(mirrored.pixels())
+                             .filter(|(plain, mirrored)| plain[0] == 0 && mirrored[0] > 0 && mirrored[2] > 0)
+                             .count();
+        assert!(reflected > 20);
+
+        assert!(plain.pixels().zip(mirrored.pixels()).all(|(plain, mirrored)| plain[0] == 0 || plain == mirrored));
+        assert_eq!(*mirrored.get_pixel(4, 20), image::Rgb([0, 0, 200]));
+    }
+
+    #[test]
+    fn test_load_scene() {
+        let directory = std::env::temp_dir().join("morpheus_description");
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("triangle.obj"),
+                  b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\nf 1/1/1 2/1/1 3/1/1\n").unwrap();
+
+        let toml = "width = 64\nheight = 32\n[camera]\neye = [0, 0, 5]\n\n\
+                    [[models]]\npath = \"triangle.obj\"\n\n\
+                    [[models]]\npath = \"triangle.obj\"\ntransform = { translation = [2, 0, 0] }\n";
+        let description = SceneDescription::from_toml(toml).unwrap();
+
+        // both models place the one mesh
+        assert_eq!(description.to_scene_file().meshes.len(), 1);
+        assert_eq!(description.camera.to_camera().eye, Vector3::new(0.0, 0.0, 5.0));
+
+        fs::write(directory.join("scene.toml"), toml).unwrap();
+        let image = load_scene(directory.join("scene.toml").to_str().unwrap()).unwrap();
+        assert_eq!(image.dimensions(), (64, 32));
+        assert_ne!(*image.get_pixel(32, 16), image::Rgb([0, 0, 0]));
+
+        let json = r#"{"width": 32, "height": 32, "shader": "flat", "camera": {"eye": [0, 0, 5]},
+                       "models": [{"path": "triangle.obj"}]}"#;
+        fs::write(directory.join("scene.json"), json).unwrap();
+        let image = load_scene(directory.join("scene.json").to_str().unwrap()).unwrap();
+        assert_ne!(*image.get_pixel(16, 16), image::Rgb([0, 0, 0]));
+
+        // a translucent model in front of another leaves both in the deep image
+        let deep = "width = 32\nheight = 32\n[camera]\neye = [0, 0, 5]\n\n\
+                    [[models]]\npath = \"triangle.obj\"\n\n\
+                    [[models]]\npath = \"triangle.obj\"\nopacity = 0.5\ntransform = { translation = [0, 0, 1] }\n";
+        fs::write(directory.join("deep.toml"), deep).unwrap();
+        let abuffer = load_deep_scene(directory.join("deep.toml").to_str().unwrap()).unwrap();
+
+        let alphas: Vec<f64> = abuffer.sorted(16, 16).iter().map(|fragment| fragment.alpha).collect();
+        assert_eq!(alphas, vec![0.5, 1.0]);
+        assert!(abuffer::deep_exr(&abuffer).starts_with(&[0x76, 0x2f, 0x31, 0x01]));
+        assert!(load_deep_scene(directory.join("scene.json").to_str().unwrap()).is_err());
+
+        assert!(SceneDescription::from_toml("resolution = 512").is_err());
+        assert!(SceneDescription::from_json(r#"{"shader": "missing"}"#).unwrap().render(&SearchPaths::default())
+                                                                            .is_err());
+    }
+
+    /// The first model of a description with the given keys, and a square loaded and set up as that model
+    fn described(keys: &str) -> (ModelDescription, SceneObject) {
+        let description = SceneDescription::from_toml(&format!("[[models]]\npath = \"square.obj\"\n{}", keys))
+                                           .unwrap();
+        let square = b"v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\nvt 0 0\nvn 0 0 1\n\
+                       g glass\nf 1/1/1 2/1/1 3/1/1\ng frame\nf 1/1/1 3/1/1 4/1/1\n";
+        let mut object = SceneObject::new(wavefront::Object::from_bytes(square, &Default::default()).unwrap(),
+                                          image::RgbImage::new(1, 1));
+
+        description.models[0].apply(&mut object);
+        (description.models[0].clone(), object)
+    }
+
+    #[test]
+    fn test_model_axes_and_units() {
+        let (model, object) = described("axes = { up = \"z\", handedness = \"left\" }\nunits = \"centimeters\"\n\
+                                         scale = 2\n");
+
+        assert_eq!(model.axes, wavefront::AxisConvention::new(wavefront::UpAxis::Z, wavefront::Handedness::Left));
+        assert_eq!(model.units, wavefront::Units::Centimeters);
+
+        // z up becomes y up, and centimeters become meters before the scale
+        assert!((object.object.geometric_vertices[2] - Vector3::new(0.02, 0.0, 0.02)).norm() < 1e-12);
+        assert!((object.object.normal_vertices[0] - Vector3::y()).norm() < 1e-12);
+
+        let (model, _) = described("");
+        assert_eq!((model.axes, model.units, model.scale), (Default::default(), wavefront::Units::Meters, 1.0));
+        assert!(SceneDescription::from_toml("[[models]]\npath = \"a.obj\"\nunits = \"furlongs\"").is_err());
+    }
+
+    #[test]
+    fn test_model_colors() {
+        let (_, object) = described("colors = { object = [200, 200, 200], groups = { glass = [40, 40, 80] }, \
+                                     lit = true }\n");
+
+        assert_eq!(object.colors.object, Some(image::Rgb([200, 200, 200])));
+        assert!(object.colors.lit);
+        assert_eq!(object.colors.face_colors(&object.object),
+                   vec![Some(image::Rgb([40, 40, 80])), Some(image::Rgb([200, 200, 200]))]);
+
+        assert_eq!(described("").1.colors.face_colors(&object.object), vec![None, None]);
+
+        // the faces from the first to the last index win over the groups, and a single face has no last index
+        let (_, faces) = described("colors = { groups = { glass = [40, 40, 80] }, faces = [\
+                                    { first = 0, last = 1, color = [1, 1, 1] }, { first = 1, color = [2, 2, 2] }] }\n");
+        let (first, second) = (image::Rgb([1, 1, 1]), image::Rgb([2, 2, 2]));
+
+        assert_eq!(faces.colors.faces, vec![(0..2, first), (1..2, second)]);
+        assert_eq!(faces.colors.face_colors(&faces.object), vec![Some(first), Some(second)]);
+        let missing_first = "[[models]]\npath = \"a.obj\"\ncolors = { faces = [{ color = [0, 0, 0] }] }";
+        assert!(SceneDescription::from_toml(missing_first).is_err());
+    }
+
+    #[test]
+    fn test_model_polygon_offset() {
+        let (_, decal) = described("polygon_offset = { factor = 1, units = 4 }\ncolors = { object = [255, 0, 0] }\n");
+        assert_eq!(decal.offset, render::PolygonOffset { factor: 1.0, units: 4.0 });
+
+        // the offset decal wins over the coplanar surface drawn before it, which would hide it without the offset
+        let (_, surface) = described("colors = { object = [0, 0, 255] }\n");
+        let mut scene = Scene::new();
+        scene.lights.push(SceneLight::new("front", Light::Directional(Vector3::z())));
+        scene.objects.push(surface);
+        scene.objects.push(decal);
+
+        let mut buffer = image::ImageBuffer::new(32, 32);
+        render_scene(&scene, &mut buffer, 255, &Vector3::new(0.5, 0.5, 5.0), &Vector3::zeros(), &Vector3::y());
+
+        assert!(buffer.pixels().filter(|&&pixel| pixel != image::Rgb([0, 0, 0])).all(|pixel| pixel[2] == 0));
+    }
+
+    #[test]
+    fn test_model_visibility() {
+        let (_, object) = described("visibility = { camera = false, shadow_catcher = true }\n");
+
+        assert_eq!(object.visibility, Visibility { camera: false, shadow_catcher: true, ..Default::default() });
+        assert_eq!(described("").1.visibility, Visibility::default());
+        assert!(SceneDescription::from_toml("[[models]]\npath = \"a.obj\"\nvisibility = { hidden = true }")
+                                 .is_err());
+    }
+
+    #[test]
+    fn test_model_light_links() {
+        let (_, object) = described("light_links = { include = [\"key\", \"rim\"], exclude = [\"rim\"] }\n");
+
+        assert!(object.light_links.links("key"));
+        assert!(!object.light_links.links("rim"));
+        assert!(!object.light_links.links("fill"));
+        assert!(described("").1.light_links.links("fill"));
+    }
+
+    #[test]
+    fn test_scene_post() {
+        let directory = std::env::temp_dir().join("morpheus_description_post");
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("triangle.obj"),
+                  b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\nf 1/1/1 2/1/1 3/1/1\n").unwrap();
+
+        let render = |post: &str| {
+            let toml = format!("width = 32\nheight = 32\n{}\n[camera]\neye = [0, 0, 5]\n\
+                                [[models]]\npath = \"triangle.obj\"\ncolors = {{ object = [60, 60, 60] }}\n", post);
+            fs::write(directory.join("scene.toml"), toml).unwrap();
+
+            load_scene(directory.join("scene.toml").to_str().unwrap()).unwrap().get_pixel(16, 20)[0]
+        };
+
+        // a stop of exposure doubles the light of the gray triangle
+        assert_eq!(render(""), 60);
+        assert!(render("post = { exposure = 1.0 }") > 80);
+        assert!(SceneDescription::from_toml("[post]\nexposure = 1\ngamma = 2").is_err());
+    }
+}
@@ -0,0 +1,240 @@
+use std::io::prelude::*;
+use std::io;
+use std::path::PathBuf;
+
+use assets::SearchPaths;
+use error::MorpheusError;
+use scene::{SceneDescription, SceneObject};
+use wavefront;
+
+
+/// A request to render a thumbnail of a scene description
+///
+/// The scene is a scene description, with its camera, post options, pixel
+/// art, and exposure analysis. The wavefront data in `model_data` is placed
+/// in the scene with a plain white texture, in addition to the models of the
+/// description, so a request can render a model it does not have a file for.
+/// Models are looked for relative to the working directory of the service
+/// first and then in the search paths in order, so requests can refer to
+/// their assets with portable paths. Models and textures read from files are
+/// cached across requests until their files change.
+///
+/// # Examples
+///
+/// ```json
+/// {"scene": {"width": 256, "height": 256, "camera": {"eye": [0, 1, 4]},
+///            "models": [{"path": "african_head.obj", "texture": "african_head_diffuse.tga"}],
+///            "post": {"exposure": 0.5}},
+///  "search_paths": ["/usr/share/models"]}
+/// ```
+///
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Request {
+    pub scene: SceneDescription,
+    pub model_data: Option<String>,
+    pub search_paths: Vec<String>
+}
+
+
+/// The header written before the PNG bytes of every response
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum Response {
+    Ok { length: usize },
+    Error { message: String }
+}
+
+
+/// Answer render requests read from the given input until it is exhausted
+///
+/// Every line of the input is a JSON encoded Request. For every request a
+/// single line containing a JSON encoded Response is written to the output.
+/// Successful responses are followed by exactly `length` bytes of PNG data.
+/// A request that fails to render produces an error response and does not
+/// stop the service, so the cost of starting up is paid only once.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate rastermind;
+/// # use std::io;
+/// # use rastermind::service;
+/// let stdin = io::stdin();
+/// let stdout = io::stdout();
+///
+/// service::serve(stdin.lock(), stdout.lock()).unwrap();
+/// ```
+///
+pub fn serve<R: BufRead, W: Write>(input: R, mut output: W) -> io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let result = serde_json::from_str::<Request>(&line)
+            .map_err(|error| error.to_string())
+            .and_then(|request| render(&request).map_err(|error| error.to_string()));
+
+        match result {
+            Ok(png) => {
+                write_header(&mut output, &Response::Ok { length: png.len() })?;
+                output.write_all(&png)?;
+            }
+            Err(message) => write_header(&mut output, &Response::Error { message })?
+        }
+
+        output.flush()?;
+    }
+
+    Ok(())
+}
+
+
+fn write_header<W: Write>(output: &mut W, response: &Response) -> io::Result<()> {
+    serde_json::to_writer(&mut *output, response)?;
+    output.write_all(b"\n")
+}
+
+
+/// Render the given request into an encoded PNG image
+///
+/// The scene description is validated before anything is loaded, the
+/// model data is loaded strictly so faces with indices out of range fail
+/// the request, and a request without any model is rejected.
+///
+pub fn render(request: &Request) -> Result<Vec<u8>, MorpheusError> {
+    if request.scene.models.is_empty() && request.model_data.is_none() {
+        return Err(MorpheusError::Scene(String::from("either scene models or model_data is required")));
+    }
+
+    let working = SearchPaths { base: Some(PathBuf::from(".")), directories: Vec::new() };
+    let paths = request.search_paths.iter().fold(working, SearchPaths::with_directory);
+    let mut scene = request.scene.load(&paths)?;
+
+    if let Some(ref data) = request.model_data {
+        let strict = wavefront::LoadOptions { strict: true, ..Default::default() };
+        let object = wavefront::Object::from_bytes(data.as_bytes(), &strict)?;
+        scene.objects.push(SceneObject::new(object, image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]))));
+    }
+
+    let buffer = request.scene.render_scene(scene)?;
+
+    let mut png = Vec::new();
+    image::ImageRgb8(buffer).write_to(&mut png, image::ImageOutputFormat::PNG)
+                            .map_err(|error| MorpheusError::Render(error.to_string()))?;
+
+    Ok(png)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serve() {
+        let input = concat!(
+            r#"{"model_data": "v -1 -1 0\nv 1 -1 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\nf 1/1/1 2/1/1 3/1/1\n","#,
+            r#" "scene": {"width": 32, "height": 16, "camera": {"eye": [0, 0, 3]}, "post": {"exposure": 0.5}}}"#, "\n",
+            "\n",
+            r#"{"scene": {"width": 32}}"#, "\n",
+            r#"{"width": 32}"#, "\n",
+            "not json\n");
+
+        let mut output = Vec::new();
+        serve(input.as_bytes(), &mut output).unwrap();
+
+        let newline = output.iter().position(|&byte| byte == b'\n').unwrap();
+        let header: Response = serde_json::from_slice(&output[..newline]).unwrap();
+
+        let length = match header {
+            Response::Ok { length } => length,
+            Response::Error { message } => panic!("{}", message)
+        };
+
+        let png = &output[newline + 1..newline + 1 + length];
+        let image = image::load_from_memory(png).unwrap().to_rgb();
+
+        assert_eq!(image.dimensions(), (32, 16));
+        assert!(image.pixels().any(|pixel| pixel[0] > 0));
+
+        let errors: Vec<Response> = output[newline + 1 + length..]
+            .split(|&byte| byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+
+        assert_eq!(errors.len(), 3);
+        assert!(errors.iter().all(|response| matches!(response, Response::Error { .. })));
+    }
+
+    #[test]
+    fn test_render_validation() {
+        let triangle = "v -1 -1 0\nv 1 -1 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\nf 1/1/1 2/1/1 3/1/1\n";
+        let request = |scene: &str, model_data: &str| {
+            let json = format!(r#"{{"scene": {}, "model_data": {:?}}}"#, scene, model_data);
+            render(&serde_json::from_str(&json).unwrap())
+        };
+
+        assert!(request(r#"{"width": 8, "height": 8, "camera": {"eye": [0, 0, 3]}}"#, triangle).is_ok());
+
+        // a view without direction is rejected by the renderer and everything else before the scene is loaded
+        assert!(matches!(request(r#"{"width": 8, "camera": {"eye": [0, 0, 0]}}"#, triangle),
+                         Err(MorpheusError::Render(_))));
+        assert!(matches!(request(r#"{"width": 8, "camera": {"eye": [0, 3, 0]}}"#, triangle),
+                         Err(MorpheusError::Render(_))));
+        assert!(matches!(request(r#"{"width": 100000, "height": 8}"#, triangle), Err(MorpheusError::Scene(_))));
+        assert!(matches!(request(r#"{"camera": {"fov": 0}}"#, triangle), Err(MorpheusError::Scene(_))));
+        assert!(matches!(request(r#"{"post": {"bloom": {"radius": 1e12}}}"#, triangle),
+                         Err(MorpheusError::Scene(_))));
+        assert!(matches!(request(r#"{"lights": [{"type": "directional", "name": "key", "direction": [0, 0, 0]}]}"#,
+                                 triangle), Err(MorpheusError::Scene(_))));
+        assert!(matches!(request(r#"{"models": [{"path": "a.obj", "scale": -1}]}"#, triangle),
+                         Err(MorpheusError::Scene(_))));
+
+        assert_eq!(request(r#"{"width": 8, "height": 8}"#, "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 7\n"),
+                   Err(MorpheusError::InvalidIndex { path: String::from("<data>"), line: 4, index: 7 }));
+    }
+
+    #[test]
+    fn test_camera_path() {
+        let request = |frame: f64| {
+            let path = r#"{"waypoints": [{"position": [0, 0, 3], "time": 0}, {"position": [0, 3, 0], "time": 10}]}"#;
+            let json = format!(r#"{{"model_data": "v -1 -1 0\nv 1 -1 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\nf 1/1/1 2/1/1 3/1/1",
+                                   "scene": {{"width": 16, "height": 16,
+                                              "camera": {{"eye": [0, 3, 0], "frame": {}, "path": {}}}}}}}"#,
+                               frame, path);
+            render(&serde_json::from_str(&json).unwrap())
+        };
+
+        // the path replaces the eye, which would look straight down the up vector by itself
+        assert!(request(0.0).is_ok());
+        assert!(matches!(request(10.0), Err(MorpheusError::Render(_))));
+    }
+
+    #[test]
+    fn test_pixel_art_and_analysis() {
+        let triangle = "v -1 -1 0\nv 1 -1 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\nf 1/1/1 2/1/1 3/1/1\n";
+        let request = |scene: &str| {
+            let json = format!(r#"{{"scene": {}, "model_data": {:?}}}"#, scene, triangle);
+            let png = render(&serde_json::from_str(&json).unwrap()).unwrap();
+
+            image::load_from_memory(&png).unwrap().to_rgb()
+        };
+
+        let blocky = request(r#"{"width": 16, "height": 16, "camera": {"eye": [0, 0, 3]},
+                                 "pixel_art": {"scale": 4, "palette": [[0, 0, 0], [255, 255, 255]]}}"#);
+        assert!(blocky.pixels().all(|pixel| pixel[0] == 0 || pixel[0] == 255));
+        assert!((0..16).all(|y| (0..16).all(|x| blocky.get_pixel(x, y) == blocky.get_pixel(x / 4 * 4, y / 4 * 4))));
+
+        // the histogram panel is drawn over the bottom quarter of the image and leaves the rest of it alone
+        let plain = request(r#"{"width": 16, "height": 16, "camera": {"eye": [0, 0, 3]}}"#);
+        let analyzed = request(r#"{"width": 16, "height": 16, "camera": {"eye": [0, 0, 3]},
+                                   "analysis": {"false_color": false}}"#);
+        assert!((0..12).all(|y| (0..16).all(|x| analyzed.get_pixel(x, y) == plain.get_pixel(x, y))));
+        assert!((12..16).any(|y| (0..16).any(|x| analyzed.get_pixel(x, y) != plain.get_pixel(x, y))));
+    }
+}
@@ -1,5 +1,6 @@
-use nalgebra::{Matrix2x3, Matrix4, Vector2, Vector3, Vector4};
+use nalgebra::{Matrix2x3, Matrix3, Matrix4, Vector2, Vector3, Vector4};
 
+use texture;
 use vector;
 use wavefront;
 
@@ -64,28 +65,41 @@ pub fn viewport(x: u32, y: u32, width: u32, height: u32, depth: u32) -> Matrix4<
 /// # Examples
 ///
 /// ```
-/// let points =  vec![Vector3::new(0, 0, 0), Vector3::new(2, 2, 2), Vector3::new(0, 2, 2)]
-/// let point = Point3::new(1.0, 1.0, 0.0);
-/// let barycentric_coordinates: Point3<f64> = find_barycentric(&points, &point);
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::{Vector2, Vector4};
+/// # use rastermind::shader::find_barycentric;
+/// let points = vec![Vector2::new(0.0, 0.0), Vector2::new(2.0, 0.0), Vector2::new(0.0, 2.0)];
+/// let point = Vector4::new(0.5, 0.5, 0.0, 1.0);
+/// let barycentric_coordinates = find_barycentric(&points, &point);
+///
+/// assert_eq!(barycentric_coordinates, nalgebra::Vector3::new(0.5, 0.25, 0.25));
 /// ```
 ///
-pub fn find_barycentric(points: &Vec<Vector2<f64>>, point: &Vector4<f64>) -> Vector3<f64> {
+pub fn find_barycentric(points: &[Vector2<f64>], point: &Vector4<f64>) -> Vector3<f64> {
     let u = Vector3::new(points[2].x - points[0].x, points[1].x - points[0].x, points[0].x - point.x);
     let v = Vector3::new(points[2].y - points[0].y, points[1].y - points[0].y, points[0].y - point.y);
 
     let w = u.cross(&v);
 
     if (w.z).abs() < 0.01 {
-        return Vector3::new(-1.0, 1.0, 1.0);
+        Vector3::new(-1.0, 1.0, 1.0)
     } else {
-        return Vector3::new(1.0 - (w.x + w.y) / w.z, w.y / w.z, w.x / w.z);
+        Vector3::new(1.0 - (w.x + w.y) / w.z, w.y / w.z, w.x / w.z)
     }
 
 }
 
 
+/// Convert a color computed by a shader to bytes, truncating every channel
+pub fn to_color(shade: Vector3<f64>) -> image::Rgb<u8> {
+    image::Rgb([shade.x as u8, shade.y as u8, shade.z as u8])
+}
+
+
 /// Shader trait can be used to implement multiple shaders
 pub trait Shader {
+    #[allow(clippy::too_many_arguments)]
     fn vertex(&mut self, coordinates: &wavefront::Object,
                   view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
                   model_view: &Matrix4<f64>, light_vector: &Vector3<f64>,
@@ -93,6 +107,176 @@ pub trait Shader {
 
 
     fn fragment(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> image::Rgb<u8>;
+
+
+    /// Find the color of the fragment before it is converted to bytes
+    ///
+    /// The conversion turns NaN into black and infinity into white, so
+    /// shaders that compute their colors with floats return them unconverted
+    /// here to let debug renders find numerical errors. The default returns
+    /// the converted color of the fragment.
+    ///
+    fn shade(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> Vector3<f64> {
+        let color = self.fragment(vertex, texture);
+
+        Vector3::new(color[0] as f64, color[1] as f64, color[2] as f64)
+    }
+
+
+    /// The name of the shader, reported along with the faces it failed to shade
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+
+    /// Whether the fragment is cut away and neither drawn nor written to the depth buffer
+    fn discard(&self, _vertex: Vector3<f64>) -> bool {
+        false
+    }
+
+
+    /// Split the color of the fragment into the terms of the lighting that make it up
+    ///
+    /// Shaders that only compute a final color report all of it as diffuse
+    /// light with no specular light or emission and no ambient occlusion.
+    ///
+    fn contributions(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> Contributions {
+        Contributions { diffuse: self.fragment(vertex, texture), ..Default::default() }
+    }
+}
+
+
+/// The terms of the lighting that make up the color of a fragment
+///
+/// The ambient occlusion is the fraction of the ambient light that reaches the
+/// fragment, where white is unoccluded.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contributions {
+    pub diffuse: image::Rgb<u8>,
+    pub specular: image::Rgb<u8>,
+    pub ambient_occlusion: image::Rgb<u8>,
+    pub emission: image::Rgb<u8>
+}
+
+
+impl Default for Contributions {
+    fn default() -> Contributions {
+        Contributions { diffuse: image::Rgb([0, 0, 0]), specular: image::Rgb([0, 0, 0]),
+                        ambient_occlusion: image::Rgb([255, 255, 255]), emission: image::Rgb([0, 0, 0]) }
+    }
+}
+
+
+impl Contributions {
+    /// Scale the light of the diffuse and specular terms, such as by a shadow or the falloff of a light
+    pub fn scale_light(mut self, factor: f64) -> Contributions {
+        for color in [&mut self.diffuse, &mut self.specular] {
+            (0..=2).for_each(|i| { color[i] = (color[i] as f64 * factor) as u8; });
+        }
+
+        self
+    }
+
+    /// Add the light of another fragment, saturating at white, while keeping the strongest occlusion
+    #[allow(clippy::should_implement_trait)]
+    pub fn add(mut self, other: &Contributions) -> Contributions {
+        (0..=2).for_each(|i| {
+            self.diffuse[i] = self.diffuse[i].saturating_add(other.diffuse[i]);
+            self.specular[i] = self.specular[i].saturating_add(other.specular[i]);
+            self.emission[i] = self.emission[i].saturating_add(other.emission[i]);
+            self.ambient_occlusion[i] = self.ambient_occlusion[i].min(other.ambient_occlusion[i]);
+        });
+
+        self
+    }
+}
+
+
+/// A separate image of one term of the lighting of a render
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aov {
+    Beauty,
+    Diffuse,
+    Specular,
+    AmbientOcclusion,
+    Emission,
+    Luminance
+}
+
+
+impl Aov {
+    /// The name of the image, used as the suffix of its file name
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::Beauty => "beauty",
+            Aov::Diffuse => "diffuse",
+            Aov::Specular => "specular",
+            Aov::AmbientOcclusion => "ao",
+            Aov::Emission => "emission",
+            Aov::Luminance => "luminance"
+        }
+    }
+}
+
+
+/// Shade every fragment with one term of the lighting of another shader
+///
+/// # Examples
+///
+/// ```
+/// # use rastermind::shader::*;
+/// let shader = AovShader::new(Box::new(GouraudShader::new()), Aov::Diffuse);
+/// ```
+///
+pub struct AovShader<'a> {
+    pub shader: Box<dyn Shader + 'a>,
+    pub aov: Aov
+}
+
+
+impl<'a> AovShader<'a> {
+    /// Create a new instance of an AovShader that shows the given term of the given shader
+    pub fn new(shader: Box<dyn Shader + 'a>, aov: Aov) -> AovShader<'a> {
+        AovShader { shader, aov }
+    }
+}
+
+
+impl<'a> Shader for AovShader<'a> {
+    /// Position the vertices with the inner shader
+    fn vertex(&mut self, coordinates: &wavefront::Object,
+              view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+              model_view: &Matrix4<f64>, light_vector: &Vector3<f64>,
+              face_index: usize, vertex_index: usize) -> Vector4<f64> {
+
+        self.shader.vertex(coordinates, view_port, projection, model_view, light_vector,
+                           face_index, vertex_index)
+    }
+
+    /// Return the term of the lighting of the inner shader
+    fn fragment(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> image::Rgb<u8> {
+        let contributions = || self.shader.contributions(vertex, texture);
+
+        match self.aov {
+            Aov::Beauty => self.shader.fragment(vertex, texture),
+            Aov::Diffuse => contributions().diffuse,
+            Aov::Specular => contributions().specular,
+            Aov::AmbientOcclusion => contributions().ambient_occlusion,
+            Aov::Emission => contributions().emission,
+            Aov::Luminance => {
+                let color = self.shader.fragment(vertex, texture);
+                let luminance = (0.2126 * color[0] as f64 + 0.7152 * color[1] as f64 +
+                                 0.0722 * color[2] as f64).round() as u8;
+                image::Rgb([luminance, luminance, luminance])
+            }
+        }
+    }
+
+    /// Discard the fragments the inner shader discards
+    fn discard(&self, vertex: Vector3<f64>) -> bool {
+        self.shader.discard(vertex)
+    }
 }
 
 
@@ -103,6 +287,13 @@ pub struct FlatShader {
 }
 
 
+impl Default for FlatShader {
+    fn default() -> FlatShader {
+        FlatShader::new()
+    }
+}
+
+
 impl FlatShader {
     /// Create a new instance of a FlatShader
     pub fn new() -> FlatShader {
@@ -136,6 +327,11 @@ impl Shader for FlatShader {
 
     /// Set the light intensity of the given vertex as determined by the vertex shader
     fn fragment(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> image::Rgb<u8> {
+        to_color(self.shade(vertex, texture))
+    }
+
+    /// Scale the texture by the light intensity of the face
+    fn shade(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> Vector3<f64> {
         let normal = (self.world_coordinates[1] - self.world_coordinates[0])
             .cross(&(self.world_coordinates[2] - self.world_coordinates[0])).normalize();
 
@@ -143,30 +339,53 @@ impl Shader for FlatShader {
 
         let uv: Vector2<f64> = self.varying_texture * vertex;
 
-        let width = (uv.x * texture.width() as f64) as usize;
-        let height = (uv.y * texture.height() as f64) as usize;
-        let mut texture_pixel = *texture.get_pixel(width as u32, height as u32);
-
-        (0..=2).for_each(|i| {texture_pixel[i] = (texture_pixel[i] as f64 * intensity) as u8;});
+        let texture_pixel = texture::sample(texture, &uv);
 
-        texture_pixel
+        Vector3::from_fn(|i, _| texture_pixel[i] as f64 * intensity)
     }
 }
 
 
+/// The bands of the CelShader by default, as the light a fragment must exceed and the light it is given
+pub const CEL_BANDS: [(f64, f64); 3] = [(0.95, 1.0), (0.50, 0.70), (0.10, 0.35)];
+
+
+/// Shade in flat bands of light like a cartoon
+///
+/// The light of every fragment is rounded down to the first band whose
+/// threshold it exceeds, so the bands are given from the brightest to the
+/// darkest, and the fragments below every band are given the shadow light.
+///
 pub struct CelShader {
+    pub bands: Vec<(f64, f64)>,
+    pub shadow: f64,
     pub varying_intensity: Vector3<f64>,
     pub varying_texture: Matrix2x3<f64>,
 }
 
 
+impl Default for CelShader {
+    fn default() -> CelShader {
+        CelShader::new()
+    }
+}
+
+
 impl CelShader {
     /// Create a new instance of a CelShader
     pub fn new() -> CelShader {
-        CelShader { varying_intensity: Vector3::zeros(),
+        CelShader { bands: CEL_BANDS.to_vec(), shadow: 0.20,
+                    varying_intensity: Vector3::zeros(),
                     varying_texture: Matrix2x3::zeros() }
 
     }
+
+    /// Use the given bands of thresholds and light, and the light of the fragments below them
+    pub fn with_bands(mut self, bands: Vec<(f64, f64)>, shadow: f64) -> CelShader {
+        self.bands = bands;
+        self.shadow = shadow;
+        self
+    }
 }
 
 
@@ -182,9 +401,7 @@ impl Shader for CelShader {
         let normal_index = coordinates.normal_faces[face_index][vertex_index] as usize;
 
         self.varying_intensity[vertex_index] = 0.0f64
-            .max(coordinates.normal_vertices[normal_index].map(|n| n as f64)
-                                                          .normalize()
-                                                          .dot(&light_vector));
+            .max(coordinates.normal_vertices[normal_index].normalize().dot(light_vector));
 
         self.varying_texture.set_column(vertex_index, &coordinates.texture_vertices[texture_index]);
 
@@ -195,34 +412,54 @@ impl Shader for CelShader {
 
     /// Set the light intensity of the given vertex as determined by the vertex shader
     fn fragment(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> image::Rgb<u8> {
-        let mut intensity: f64 = self.varying_intensity.dot(&vertex);
-        let uv: Vector2<f64> = self.varying_texture * vertex;
+        to_color(self.shade(vertex, texture))
+    }
 
-        if intensity > 0.95 { intensity = 1.0; }
-        else if intensity > 0.50 { intensity = 0.70; }
-        else if intensity > 0.10 { intensity = 0.35; }
-        else { intensity = 0.20; }
+    /// Scale the texture by the light intensity rounded down to one of the bands
+    fn shade(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> Vector3<f64> {
+        let intensity: f64 = self.varying_intensity.dot(&vertex);
+        let uv: Vector2<f64> = self.varying_texture * vertex;
 
-        let width = (uv.x * texture.width() as f64) as usize;
-        let height = (uv.y * texture.height() as f64) as usize;
-        let mut texture_pixel = *texture.get_pixel(width as u32, height as u32);
+        let intensity = self.bands.iter().find(|&&(threshold, _)| intensity > threshold)
+                                         .map_or(self.shadow, |&(_, light)| light);
 
-        (0..=2).for_each(|i| {texture_pixel[i] = (texture_pixel[i] as f64 * intensity) as u8;});
+        let texture_pixel = texture::sample(texture, &uv);
 
-        texture_pixel
+        Vector3::from_fn(|i, _| texture_pixel[i] as f64 * intensity)
     }
 }
 
+
+/// Shade with the light of the vertices interpolated across the face
+///
+/// The light is raised to the exponent, which is one by default, so a
+/// greater exponent narrows the bright part of a surface and a smaller one
+/// flattens the falloff of the light.
+///
 pub struct GouraudShader {
+    pub exponent: f64,
     pub varying_intensity: Vector3<f64>,
     pub varying_texture: Matrix2x3<f64>
 }
 
 
+impl Default for GouraudShader {
+    fn default() -> GouraudShader {
+        GouraudShader::new()
+    }
+}
+
+
 impl GouraudShader {
     /// Create a new instance of a GouraudShader
     pub fn new() -> GouraudShader {
-        GouraudShader { varying_intensity: Vector3::zeros(), varying_texture: Matrix2x3::zeros() }
+        GouraudShader { exponent: 1.0, varying_intensity: Vector3::zeros(), varying_texture: Matrix2x3::zeros() }
+    }
+
+    /// Raise the light to the given exponent
+    pub fn with_exponent(mut self, exponent: f64) -> GouraudShader {
+        self.exponent = exponent;
+        self
     }
 }
 
@@ -239,9 +476,7 @@ impl Shader for GouraudShader {
         let normal_index = coordinates.normal_faces[face_index][vertex_index] as usize;
 
         self.varying_intensity[vertex_index] = 0.0f64
-            .max(coordinates.normal_vertices[normal_index].map(|n| n as f64)
-                                                          .normalize()
-                                                          .dot(&light_vector));
+            .max(coordinates.normal_vertices[normal_index].normalize().dot(light_vector));
 
         self.varying_texture.set_column(vertex_index, &coordinates.texture_vertices[texture_index]);
 
@@ -252,16 +487,129 @@ impl Shader for GouraudShader {
 
     /// Set the light intensity of the given vertex as determined by the vertex shader
     fn fragment(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> image::Rgb<u8> {
+        to_color(self.shade(vertex, texture))
+    }
+
+    /// Scale the texture by the interpolated light intensity
+    fn shade(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> Vector3<f64> {
         let intensity: f64 = self.varying_intensity.dot(&vertex);
+        let intensity = if self.exponent == 1.0 { intensity } else { intensity.max(0.0).powf(self.exponent) };
         let uv: Vector2<f64> = self.varying_texture * vertex;
 
-        let width = (uv.x * texture.width() as f64) as usize;
-        let height = (uv.y * texture.height() as f64) as usize;
-        let mut texture_pixel = *texture.get_pixel(width as u32, height as u32);
+        let texture_pixel = texture::sample(texture, &uv);
+
+        Vector3::from_fn(|i, _| texture_pixel[i] as f64 * intensity)
+    }
+}
+
+
+/// Shade every fragment in a single color instead of sampling the texture
+///
+/// When lit, the color is scaled by the Gouraud intensity of the fragment,
+/// otherwise it is written unchanged.
+///
+pub struct ColorShader {
+    pub color: image::Rgb<u8>,
+    pub lit: bool,
+    pub varying_intensity: Vector3<f64>
+}
+
+
+impl ColorShader {
+    /// Create a new instance of a ColorShader with the given color
+    pub fn new(color: image::Rgb<u8>, lit: bool) -> ColorShader {
+        ColorShader { color, lit, varying_intensity: Vector3::zeros() }
+    }
+}
+
+
+impl Shader for ColorShader {
+    /// Position the vertices into their scene coordinates
+    fn vertex(&mut self, coordinates: &wavefront::Object,
+              view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+              model_view: &Matrix4<f64>, light_vector: &Vector3<f64>,
+              face_index: usize, vertex_index: usize) -> Vector4<f64> {
+
+        let geometric_index = coordinates.geometric_faces[face_index][vertex_index] as usize;
+        let normal_index = coordinates.normal_faces[face_index][vertex_index] as usize;
+
+        self.varying_intensity[vertex_index] = 0.0f64
+            .max(coordinates.normal_vertices[normal_index].normalize().dot(light_vector));
+
+        let gl_vertex = vector::vectorize_to_4d(&coordinates.geometric_vertices[geometric_index]);
+
+        view_port * projection * model_view * gl_vertex
+    }
+
+    /// Return the color of the shader, shaded by the light if the shader is lit
+    fn fragment(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> image::Rgb<u8> {
+        to_color(self.shade(vertex, texture))
+    }
+
+    /// Scale the color of the shader by the light if the shader is lit
+    fn shade(&self, vertex: Vector3<f64>, _texture: &image::RgbImage) -> Vector3<f64> {
+        let intensity: f64 = if self.lit { self.varying_intensity.dot(&vertex) } else { 1.0 };
+
+        Vector3::from_fn(|i, _| self.color[i] as f64 * intensity)
+    }
+}
+
+
+/// Shade every fragment with its view space normal encoded as a color
+///
+/// Each component of the normal is mapped from [-1, 1] to [0, 255], so a
+/// surface facing the camera is colored (128, 128, 255).
+///
+pub struct NormalShader {
+    pub varying_normal: Matrix3<f64>
+}
+
+
+impl Default for NormalShader {
+    fn default() -> NormalShader {
+        NormalShader::new()
+    }
+}
+
+
+impl NormalShader {
+    /// Create a new instance of a NormalShader
+    pub fn new() -> NormalShader {
+        NormalShader { varying_normal: Matrix3::zeros() }
+    }
+}
+
+
+impl Shader for NormalShader {
+    /// Position the vertices into their scene coordinates
+    fn vertex(&mut self, coordinates: &wavefront::Object,
+              view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+              model_view: &Matrix4<f64>, _light_vector: &Vector3<f64>,
+              face_index: usize, vertex_index: usize) -> Vector4<f64> {
+
+        let geometric_index = coordinates.geometric_faces[face_index][vertex_index] as usize;
+        let normal_index = coordinates.normal_faces[face_index][vertex_index] as usize;
+
+        let normal = coordinates.normal_vertices[normal_index];
+        let view_normal = model_view * Vector4::new(normal.x, normal.y, normal.z, 0.0);
+
+        self.varying_normal.set_column(vertex_index, &view_normal.remove_row(3));
+
+        let gl_vertex = vector::vectorize_to_4d(&coordinates.geometric_vertices[geometric_index]);
+
+        view_port * projection * model_view * gl_vertex
+    }
+
+    /// Encode the interpolated normal of the fragment as a color
+    fn fragment(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> image::Rgb<u8> {
+        to_color(self.shade(vertex, texture))
+    }
 
-        (0..=2).for_each(|i| {texture_pixel[i] = (texture_pixel[i] as f64 * intensity) as u8;});
+    /// Map the interpolated normal of the fragment from [-1, 1] to [0, 255]
+    fn shade(&self, vertex: Vector3<f64>, _texture: &image::RgbImage) -> Vector3<f64> {
+        let normal = (self.varying_normal * vertex).try_normalize(0.0).unwrap_or_else(Vector3::z);
 
-        texture_pixel
+        normal.add_scalar(1.0) * 127.5
     }
 }
 
@@ -304,7 +652,7 @@ mod tests {
         let eye: Vector3<f64> = Vector3::new(0.0, -1.0, 3.0);
         let center: Vector3<f64> = Vector3::zeros();
 
-        let view = projection(-1.0 / (&eye - &center).norm());
+        let view = projection(-1.0 / (eye - center).norm());
 
         assert_eq!(view.row(0)[0], 1.0);
         assert_eq!(view.row(0)[1], 0.0);
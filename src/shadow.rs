@@ -0,0 +1,763 @@
+use nalgebra::{Matrix3, Matrix4, Vector3, Vector4};
+
+use light::Light;
+use render;
+use shader;
+use shader::{Contributions, Shader};
+use vector;
+use wavefront;
+
+
+/// Options that control the shadows cast by a light
+///
+/// The size is the width and height of the shadow map in pixels and the bias
+/// is the distance, as a fraction of the extent of the scene, that a surface
+/// may lie behind the shadow map before it is in shadow, which prevents
+/// surfaces from shadowing themselves. The strength is how much of the light
+/// is blocked in shadow.
+///
+/// Shadow edges are softened by percentage-closer filtering over a square of
+/// texels with the given kernel radius, where 0 gives hard edges. Several
+/// light samples jitter the direction of a directional light within the given
+/// spread in radians and average their shadows into a penumbra.
+///
+/// A light size turns on percentage-closer soft shadows, where the filter
+/// grows with the distance between the blockers and the shadowed point, so
+/// shadows harden where objects touch the ground. The size is the angle the
+/// light spans in radians for directional lights and its width in the units
+/// of the scene for spotlights.
+///
+/// Directional lights over large scenes can split the view of the camera
+/// into several cascades by the distance from it, each with a map of its own,
+/// so near shadows get as many texels as far ones. The blend is the fraction
+/// at the end of every cascade over which it fades into the next, which hides
+/// the seam where the resolution changes.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShadowOptions {
+    pub size: u32,
+    pub bias: f64,
+    pub strength: f64,
+    pub kernel: u32,
+    pub light_samples: u32,
+    pub light_spread: f64,
+    pub light_size: f64,
+    pub cascades: u32,
+    pub cascade_blend: f64
+}
+
+
+impl Default for ShadowOptions {
+    fn default() -> ShadowOptions {
+        ShadowOptions { size: 1024, bias: 0.005, strength: 0.6, kernel: 1, light_samples: 1,
+                        light_spread: 0.0, light_size: 0.0, cascades: 1, cascade_blend: 0.1 }
+    }
+}
+
+
+/// The number of taps on either side of the center of the blocker search and the filter of soft shadows
+const PCSS_TAPS: u32 = 3;
+
+/// The largest radius in texels of the blocker search and the filter of soft shadows
+const PCSS_RADIUS: f64 = 16.0;
+
+
+/// How far the splits between cascades lean from even distances towards a logarithmic series
+const CASCADE_SPLIT: f64 = 0.5;
+
+
+/// The depth of the nearest surface to a light at every point of a grid
+///
+/// For a directional light the grid covers the bounding sphere of the shadow
+/// casters, seen from the light with an orthographic projection, and there is
+/// one grid for every light sample. A spotlight projects a single grid over
+/// its outer cone in perspective.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::light::Light;
+/// # use rastermind::shadow::*;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let (ground, model) = (coordinates.clone(), coordinates);
+/// let light = Light::Directional(Vector3::new(1.0, 2.0, 1.0).normalize());
+/// let shadow_map = ShadowMap::new(&[&ground, &model], &light, &ShadowOptions::default());
+///
+/// let visibility = shadow_map.visibility(&Vector3::new(0.0, 0.0, 0.0));
+/// # Ok(())
+/// # }
+/// ```
+///
+pub struct ShadowMap {
+    pub samples: Vec<ShadowSample>,
+    pub size: u32,
+    pub extent: f64,
+    pub options: ShadowOptions,
+    pub cascades: Vec<Cascade>,
+    pub camera: Option<CascadeCamera>
+}
+
+
+/// A shadow map that covers the view of the camera between two distances from it
+pub struct Cascade {
+    pub near: f64,
+    pub far: f64,
+    pub map: ShadowMap
+}
+
+
+/// The camera that the cascades of a shadow map split the view of
+///
+/// The distance is the distance of the camera from the center of its view,
+/// which is infinite for an orthographic projection, where distances are
+/// measured from the plane through the center of the view instead.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CascadeCamera {
+    pub model_view: Matrix4<f64>,
+    pub distance: f64
+}
+
+
+impl CascadeCamera {
+    /// Find how far the given point is from the camera along its view direction
+    pub fn depth(&self, point: &Vector3<f64>) -> f64 {
+        let z = (self.model_view * vector::vectorize_to_4d(point)).z;
+
+        if self.distance.is_finite() { self.distance - z } else { -z }
+    }
+}
+
+
+/// The depth grid of a single light direction and the transform from the scene onto it
+///
+/// The footprint is the width of a texel in the units of the scene, or its
+/// width one unit in front of the light in perspective.
+///
+pub struct ShadowSample {
+    pub depth: Vec<f64>,
+    pub transform: Matrix4<f64>,
+    pub perspective: bool,
+    pub footprint: f64
+}
+
+
+impl ShadowMap {
+    /// Render the depth of the given objects as seen from the light
+    pub fn new(casters: &[&wavefront::Object], light: &Light, options: &ShadowOptions) -> ShadowMap {
+        let vertices = || casters.iter().flat_map(|object| object.geometric_vertices.iter());
+        let count = vertices().count().max(1) as f64;
+        let center = vertices().fold(Vector3::zeros(), |sum, vertex| sum + vertex) / count;
+        let radius = vertices().fold(0.0f64, |radius, vertex| radius.max((vertex - center).norm()));
+
+        ShadowMap::fitted(casters, light, options, &center, radius)
+    }
+
+    /// Render the depth of the given objects as seen from the light in cascades over the view of a camera
+    ///
+    /// The view of the camera, from the nearest to the furthest caster in
+    /// front of it, is split into the cascades of the options, and the map of
+    /// every cascade is fitted around its part of the view, so the texels of
+    /// every map cover less of the scene the nearer they are to the camera.
+    /// Casters between the light and a cascade still shadow it. Spotlights,
+    /// and options with a single cascade, make a single map as `new` does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate nalgebra;
+    /// # extern crate rastermind;
+    /// # use nalgebra::Vector3;
+    /// # use rastermind::light::Light;
+    /// # use rastermind::shadow::*;
+    /// # use rastermind::{shader, wavefront};
+    /// # fn main() -> Result<(), rastermind::Error> {
+    /// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n",
+    /// #                                                   &Default::default())?;
+    /// # let (terrain, sun) = (coordinates, Light::Directional(Vector3::y()));
+    /// # let (eye, center, up) = (Vector3::<f64>::new(0.0, 0.0, 3.0), Vector3::zeros(), Vector3::y());
+    /// # let view_port = shader::viewport(128, 128, 768, 768, 255);
+    /// # let projection = shader::projection(-1.0 / 3.0);
+    /// # let model_view = shader::lookat(&eye, &center, &up);
+    /// let options = ShadowOptions { cascades: 4, ..Default::default() };
+    /// let shadow_map = ShadowMap::cascaded(&[&terrain], &sun, &options, &view_port, &projection, &model_view,
+    ///                                      1024, 1024);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    #[allow(clippy::too_many_arguments)]
+    pub fn cascaded(casters: &[&wavefront::Object], light: &Light, options: &ShadowOptions,
+                    view_port: &Matrix4<f64>, projection: &Matrix4<f64>, model_view: &Matrix4<f64>,
+                    width: u32, height: u32) -> ShadowMap {
+
+        if options.cascades <= 1 || !matches!(light, Light::Directional(_)) {
+            return ShadowMap::new(casters, light, options);
+        }
+
+        let camera = CascadeCamera { model_view: *model_view, distance: -1.0 / projection[(3, 2)] };
+        let (nearest, furthest) = casters.iter()
+                                         .flat_map(|object| object.geometric_vertices.iter())
+                                         .map(|vertex| camera.depth(vertex))
+                                         .fold((f64::MAX, f64::MIN), |(near, far), depth| {
+                                             (near.min(depth), far.max(depth))
+                                         });
+
+        // a perspective view starts just in front of the camera, where the logarithmic splits begin
+        let near = if camera.distance.is_finite() { nearest.max(furthest * 1e-3).max(1e-6) } else { nearest };
+        let far = furthest.max(near + 1e-6);
+        let count = options.cascades as usize;
+
+        let splits: Vec<f64> = (0..=count).map(|i| {
+            let fraction = i as f64 / count as f64;
+            let even = near + (far - near) * fraction;
+
+            if near > 0.0 {
+                CASCADE_SPLIT * near * (far / near).powf(fraction) + (1.0 - CASCADE_SPLIT) * even
+            } else {
+                even
+            }
+        }).collect();
+
+        let inverse_view = model_view.try_inverse().unwrap_or_else(Matrix4::identity);
+        let corner = |x: u32, y: u32, depth: f64| {
+            let ndc_x = (x as f64 - view_port[(0, 3)]) / view_port[(0, 0)];
+            let ndc_y = (y as f64 - view_port[(1, 3)]) / view_port[(1, 1)];
+
+            let view = if camera.distance.is_finite() {
+                let scale = depth / camera.distance;
+                Vector4::new(ndc_x * scale, ndc_y * scale, camera.distance - depth, 1.0)
+            } else {
+                Vector4::new(ndc_x, ndc_y, -depth, 1.0)
+            };
+
+            (inverse_view * view).xyz()
+        };
+
+        let cascades = splits.windows(2).map(|split| {
+            // every map also covers the band where it blends into the next one
+            let far = split[1] + (split[1] - split[0]) * options.cascade_blend;
+            let corners: Vec<Vector3<f64>> = [split[0], far].iter().flat_map(|&depth| {
+                [(0, 0), (width, 0), (0, height), (width, height)].map(|(x, y)| corner(x, y, depth))
+            }).collect();
+
+            let center = corners.iter().sum::<Vector3<f64>>() / corners.len() as f64;
+            let radius = corners.iter().fold(0.0f64, |radius, corner| radius.max((corner - center).norm()));
+
+            Cascade { near: split[0], far: split[1], map: ShadowMap::fitted(casters, light, options, &center, radius) }
+        }).collect();
+
+        ShadowMap { samples: Vec::new(), size: options.size, extent: 2.0 * (far - near), options: *options, cascades,
+                    camera: Some(camera) }
+    }
+
+    /// Render the depth of the given objects as seen from the light over the sphere with the given center and radius
+    fn fitted(casters: &[&wavefront::Object], light: &Light, options: &ShadowOptions,
+              center: &Vector3<f64>, radius: f64) -> ShadowMap {
+
+        let (center, radius) = (*center, radius.max(1e-9));
+
+        let view_port = shader::viewport(0, 0, options.size, options.size, 1);
+        let up = |direction: &Vector3<f64>| {
+            if direction.cross(&Vector3::y()).norm() > 1e-6 { Vector3::y() } else { Vector3::x() }
+        };
+
+        let projectors: Vec<(Matrix4<f64>, Matrix4<f64>, f64)> = match *light {
+            Light::Directional(ref light_vector) => {
+                light_directions(light_vector, options).iter().map(|direction| {
+                    (Matrix4::new_scaling(1.0 / radius) *
+                     shader::lookat(&(center + direction), &center, &up(direction)),
+                     shader::projection(0.0), 2.0 * radius / options.size as f64)
+                }).collect()
+            },
+            Light::Spot(ref spot) => {
+                // the outer cone fills the grid, one unit in front of the light
+                let scale = 1.0 / spot.outer_angle.min(1.5).tan();
+
+                vec![(Matrix4::new_nonuniform_scaling(&Vector3::new(scale, scale, 1.0)) *
+                      shader::lookat(&spot.position, &(spot.position + spot.direction),
+                                     &up(&spot.direction)),
+                      shader::projection(-1.0), 2.0 / scale / options.size as f64)]
+            }
+        };
+
+        // push the depth of sloped surfaces away from the light by the width of the filter
+        let depth_options = render::DepthOptions {
+            offset: render::PolygonOffset { factor: -(options.kernel as f64 + 1.0), units: 0.0 },
+            ..Default::default()
+        };
+
+        let samples = projectors.iter().map(|&(model_view, projection, footprint)| {
+            let mut buffer = image::ImageBuffer::new(options.size, options.size);
+            let mut depth = vec![-1.0; (options.size * options.size) as usize];
+            let texture = image::RgbImage::new(1, 1);
+
+            for object in casters {
+                render::draw_faces(object, &mut buffer, &texture, &mut depth,
+                                   &depth_options, &view_port, &projection,
+                                   &model_view, &Vector3::z(),
+                                   |_| Box::new(shader::ColorShader::new(image::Rgb([255, 255, 255]),
+                                                                         false)));
+            }
+
+            ShadowSample { depth, transform: view_port * projection * model_view,
+                           perspective: projection[(3, 2)] != 0.0, footprint }
+        }).collect();
+
+        ShadowMap { samples, size: options.size, extent: 2.0 * radius, options: *options, cascades: Vec::new(),
+                    camera: None }
+    }
+
+    /// Find the cascade that covers the given point, and how much it blends into the next cascade
+    fn cascade(&self, point: &Vector3<f64>) -> Option<(usize, f64)> {
+        let depth = self.camera.as_ref()?.depth(point);
+        let index = self.cascades.iter().position(|cascade| depth <= cascade.far).unwrap_or(self.cascades.len() - 1);
+        let cascade = &self.cascades[index];
+
+        let band = (cascade.far - cascade.near) * self.options.cascade_blend;
+        let blend = if index + 1 < self.cascades.len() && band > 0.0 {
+            ((depth - (cascade.far - band)) / band).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        Some((index, blend))
+    }
+
+    /// Find how much of the light reaches the given point, from 0 in shadow to 1 when lit
+    pub fn visibility(&self, point: &Vector3<f64>) -> f64 {
+        if let Some((index, blend)) = self.cascade(point) {
+            let visibility = self.cascades[index].map.visibility(point);
+
+            return if blend > 0.0 {
+                visibility * (1.0 - blend) + self.cascades[index + 1].map.visibility(point) * blend
+            } else {
+                visibility
+            };
+        }
+
+        self.samples.iter().map(|sample| self.sample_visibility(sample, point)).sum::<f64>() /
+            self.samples.len() as f64
+    }
+
+    /// Find how much of the light of a single light sample reaches the given point
+    fn sample_visibility(&self, sample: &ShadowSample, point: &Vector3<f64>) -> f64 {
+        let clip = sample.transform * vector::vectorize_to_4d(point);
+        let projected = vector::project_to_3d(&clip);
+
+        if clip.w <= 0.0 {
+            return 1.0;
+        }
+
+        // contact hardening filters over the penumbra of the blockers, and plain filtering over the kernel
+        let (taps, radius) = if self.options.light_size > 0.0 {
+            match self.penumbra(sample, &clip, &projected) {
+                Some(radius) => (self.options.kernel.max(PCSS_TAPS) as i64, radius),
+                None => return 1.0
+            }
+        } else {
+            (self.options.kernel as i64, self.options.kernel as f64)
+        };
+
+        let step = if taps == 0 { 0.0 } else { radius / taps as f64 };
+        let mut lit = 0;
+
+        for dx in -taps..=taps {
+            for dy in -taps..=taps {
+                let x = (projected.x + dx as f64 * step).floor() as i64;
+                let y = (projected.y + dy as f64 * step).floor() as i64;
+
+                if self.blocker(sample, &clip, &projected, x, y).is_none() {
+                    lit += 1;
+                }
+            }
+        }
+
+        lit as f64 / ((2 * taps + 1) * (2 * taps + 1)) as f64
+    }
+
+    /// Find the depth of the texel of the map that blocks the light from the given point, if it does
+    fn blocker(&self, sample: &ShadowSample, clip: &Vector4<f64>, projected: &Vector3<f64>,
+               x: i64, y: i64) -> Option<f64> {
+
+        if x < 0 || y < 0 || x >= self.size as i64 || y >= self.size as i64 {
+            return None;
+        }
+
+        let nearest = sample.depth[(x + y * self.size as i64) as usize];
+
+        // in perspective the depth is half the reciprocal of the distance from the
+        // light, otherwise the viewport maps the diameter of the scene onto a depth of 1
+        let unblocked = if sample.perspective {
+            nearest <= 0.0 || clip.w <= 0.5 / nearest + self.options.bias * self.extent
+        } else {
+            projected.z + self.options.bias >= nearest
+        };
+
+        if unblocked { None } else { Some(nearest) }
+    }
+
+    /// Find the radius in texels of the penumbra at the given point from the average depth of its blockers
+    ///
+    /// The penumbra widens with the distance between the blockers and the
+    /// point, so shadows are sharp where objects touch the ground and soft
+    /// further away. There is no penumbra when nothing blocks the light.
+    ///
+    fn penumbra(&self, sample: &ShadowSample, clip: &Vector4<f64>, projected: &Vector3<f64>) -> Option<f64> {
+        let light_size = self.options.light_size;
+
+        // search as far as a blocker right at the light could cast its penumbra
+        let search = if sample.perspective {
+            light_size * (1.0 - 1.0 / clip.w).max(0.0) / sample.footprint / 2.0
+        } else {
+            light_size.tan() * self.extent / sample.footprint / 2.0
+        }.clamp(1.0, PCSS_RADIUS);
+
+        let step = search / PCSS_TAPS as f64;
+        let taps = PCSS_TAPS as i64;
+        let blockers: Vec<f64> = (-taps..=taps).flat_map(|dx| (-taps..=taps).map(move |dy| (dx, dy)))
+                                               .filter_map(|(dx, dy)| {
+            self.blocker(sample, clip, projected, (projected.x + dx as f64 * step).floor() as i64,
+                         (projected.y + dy as f64 * step).floor() as i64)
+        }).collect();
+
+        if blockers.is_empty() {
+            return None;
+        }
+
+        let blocker = blockers.iter().sum::<f64>() / blockers.len() as f64;
+
+        let width = if sample.perspective {
+            let distance = 0.5 / blocker;
+            light_size * (clip.w - distance).max(0.0) / distance / (sample.footprint * clip.w)
+        } else {
+            light_size.tan() * (blocker - projected.z).max(0.0) * self.extent / sample.footprint
+        };
+
+        Some((width / 2.0).min(PCSS_RADIUS))
+    }
+
+    /// Find the factor that scales the color of a fragment at the given point
+    pub fn attenuation(&self, point: &Vector3<f64>) -> f64 {
+        1.0 - self.options.strength * (1.0 - self.visibility(point))
+    }
+
+    /// Find how far the light travels through the casters before it reaches the given point
+    ///
+    /// The distance is measured from the surface nearest to the light along
+    /// the direction of the light, in the units of the scene, so points on
+    /// that surface or outside of the map have no thickness.
+    ///
+    pub fn thickness(&self, point: &Vector3<f64>) -> f64 {
+        if let Some((index, _)) = self.cascade(point) {
+            return self.cascades[index].map.thickness(point);
+        }
+
+        let sample = match self.samples.first() {
+            Some(sample) => sample,
+            None => return 0.0
+        };
+
+        let clip = sample.transform * vector::vectorize_to_4d(point);
+        let projected = vector::project_to_3d(&clip);
+        let (x, y) = (projected.x.floor() as i64, projected.y.floor() as i64);
+
+        if clip.w <= 0.0 || x < 0 || y < 0 || x >= self.size as i64 || y >= self.size as i64 {
+            return 0.0;
+        }
+
+        let nearest = sample.depth[(x + y * self.size as i64) as usize];
+
+        if sample.perspective {
+            if nearest <= 0.0 { 0.0 } else { (clip.w - 0.5 / nearest).max(0.0) }
+        } else if nearest == -1.0 {
+            0.0
+        } else {
+            ((nearest - projected.z) * self.extent).max(0.0)
+        }
+    }
+}
+
+
+/// Jitter the direction of the light into the light samples of the options
+///
+/// The directions lie on a spiral that evenly covers the cone of the spread
+/// around the light, so the same options always give the same shadows.
+///
+fn light_directions(light_vector: &Vector3<f64>, options: &ShadowOptions) -> Vec<Vector3<f64>> {
+    let light_vector = light_vector.normalize();
+    let samples = options.light_samples.max(1);
+
+    if samples == 1 || options.light_spread <= 0.0 {
+        return vec![light_vector];
+    }
+
+    let helper = if light_vector.x.abs() < 0.9 { Vector3::x() } else { Vector3::y() };
+    let u = light_vector.cross(&helper).normalize();
+    let v = light_vector.cross(&u);
+
+    (0..samples).map(|i| {
+        let angle = options.light_spread * ((i as f64 + 0.5) / samples as f64).sqrt();
+        let turn = i as f64 * 2.399_963_229_728_653;
+
+        (light_vector + (u * turn.cos() + v * turn.sin()) * angle.tan()).normalize()
+    }).collect()
+}
+
+
+/// Darken the fragments of another shader where the shadow map blocks the light
+///
+/// # Examples
+///
+/// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::light::Light;
+/// # use rastermind::shadow::*;
+/// # use rastermind::{shader, wavefront};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let shadow_map = ShadowMap::new(&[&coordinates], &Light::Directional(Vector3::y()), &ShadowOptions::default());
+/// let shader = ShadowShader::new(Box::new(shader::GouraudShader::new()), &shadow_map);
+/// # Ok(())
+/// # }
+/// ```
+///
+pub struct ShadowShader<'a> {
+    pub shader: Box<dyn Shader + 'a>,
+    pub shadow_map: &'a ShadowMap,
+    pub varying_position: Matrix3<f64>,
+    pub varying_w: Vector3<f64>
+}
+
+
+impl<'a> ShadowShader<'a> {
+    /// Create a new instance of a ShadowShader that shadows the given shader
+    pub fn new(shader: Box<dyn Shader + 'a>, shadow_map: &'a ShadowMap) -> ShadowShader<'a> {
+        ShadowShader { shader, shadow_map, varying_position: Matrix3::zeros(),
+                       varying_w: Vector3::zeros() }
+    }
+}
+
+
+impl<'a> Shader for ShadowShader<'a> {
+    /// Position the vertices with the shadowed shader and keep their positions in the scene
+    fn vertex(&mut self, coordinates: &wavefront::Object,
+              view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+              model_view: &Matrix4<f64>, light_vector: &Vector3<f64>,
+              face_index: usize, vertex_index: usize) -> Vector4<f64> {
+
+        let geometric_index = coordinates.geometric_faces[face_index][vertex_index] as usize;
+        self.varying_position.set_column(vertex_index, &coordinates.geometric_vertices[geometric_index]);
+
+        let screen_coordinate = self.shader.vertex(coordinates, view_port, projection, model_view,
+                                                   light_vector, face_index, vertex_index);
+        self.varying_w[vertex_index] = screen_coordinate.w;
+
+        screen_coordinate
+    }
+
+    /// Scale the color of the shadowed shader by the light that reaches the fragment
+    fn fragment(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> image::Rgb<u8> {
+        let mut color = self.shader.fragment(vertex, texture);
+
+        // positions must be interpolated in perspective or shadows slide across large faces
+        let perspective = vertex.component_div(&self.varying_w);
+        let position = self.varying_position * perspective / perspective.sum();
+        let attenuation = self.shadow_map.attenuation(&position);
+
+        (0..=2).for_each(|i| { color[i] = (color[i] as f64 * attenuation) as u8; });
+
+        color
+    }
+
+    /// Discard the fragments the shadowed shader discards
+    fn discard(&self, vertex: Vector3<f64>) -> bool {
+        self.shader.discard(vertex)
+    }
+
+    /// Scale the light of the shadowed shader by the light that reaches the fragment
+    fn contributions(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> Contributions {
+        let perspective = vertex.component_div(&self.varying_w);
+        let position = self.varying_position * perspective / perspective.sum();
+
+        self.shader.contributions(vertex, texture).scale_light(self.shadow_map.attenuation(&position))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use light::Spotlight;
+    use super::*;
+
+    #[test]
+    fn test_visibility() {
+        let blocker = wavefront::Object::from_bytes(b"v -1 1 -1\nv 1 1 -1\nv 0 1 1\nvt 0 0\nvn 0 1 0\n\
+                                                      f 1/1/1 3/1/1 2/1/1\n",
+                                                    &wavefront::LoadOptions::default()).unwrap();
+        let shadow_map = ShadowMap::new(&[&blocker], &Light::Directional(Vector3::y()),
+                                        &ShadowOptions::default());
+
+        assert_eq!(shadow_map.visibility(&Vector3::new(0.0, 0.0, 0.0)), 0.0);
+        assert_eq!(shadow_map.visibility(&Vector3::new(0.0, 2.0, 0.0)), 1.0);
+        assert_eq!(shadow_map.visibility(&Vector3::new(0.0, 1.0, 0.0)), 1.0);
+        assert_eq!(shadow_map.visibility(&Vector3::new(5.0, 0.0, 0.0)), 1.0);
+        assert!((shadow_map.attenuation(&Vector3::zeros()) - 0.4).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_thickness() {
+        let blocker = wavefront::Object::from_bytes(b"v -1 1 -1\nv 1 1 -1\nv 0 1 1\nvt 0 0\nvn 0 1 0\n\
+                                                      f 1/1/1 3/1/1 2/1/1\n",
+                                                    &wavefront::LoadOptions::default()).unwrap();
+        let options = ShadowOptions { kernel: 0, ..Default::default() };
+        let shadow_map = ShadowMap::new(&[&blocker], &Light::Directional(Vector3::y()), &options);
+
+        assert!((shadow_map.thickness(&Vector3::new(0.0, 0.5, 0.0)) - 0.5).abs() < 0.01);
+        assert!((shadow_map.thickness(&Vector3::new(0.0, -1.0, 0.0)) - 2.0).abs() < 0.01);
+        assert_eq!(shadow_map.thickness(&Vector3::new(0.0, 2.0, 0.0)), 0.0);
+        assert_eq!(shadow_map.thickness(&Vector3::new(5.0, 0.0, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn test_soft_shadows() {
+        let blocker = wavefront::Object::from_bytes(b"v -1 1 -1\nv 1 1 -1\nv 1 1 1\nv -1 1 1\nvt 0 0\n\
+                                                      vn 0 1 0\nf 1/1/1 4/1/1 3/1/1\nf 1/1/1 3/1/1 2/1/1\n",
+                                                    &wavefront::LoadOptions::default()).unwrap();
+        let edge = Vector3::new(1.0, 0.0, 0.0);
+
+        let hard = ShadowOptions { size: 64, kernel: 0, ..Default::default() };
+        let filtered = ShadowOptions { kernel: 2, ..hard };
+        let jittered = ShadowOptions { light_samples: 16, light_spread: 0.3, ..hard };
+
+        let visibility = |options: &ShadowOptions, point: &Vector3<f64>| {
+            ShadowMap::new(&[&blocker], &Light::Directional(Vector3::y()), options).visibility(point)
+        };
+
+        let hard_edge = visibility(&hard, &edge);
+        assert!(hard_edge == 0.0 || hard_edge == 1.0);
+
+        // filtering blends the texels around the edge
+        let filtered_edge = visibility(&filtered, &edge);
+        assert!(filtered_edge > 0.0 && filtered_edge < 1.0);
+        assert_eq!(visibility(&filtered, &Vector3::zeros()), 0.0);
+
+        // jittered lights leave a penumbra that widens with the distance from the blocker
+        let penumbra = visibility(&jittered, &Vector3::new(1.1, 0.0, 0.0));
+        assert!(penumbra > 0.0 && penumbra < 1.0);
+        assert_eq!(visibility(&jittered, &Vector3::new(1.1, 0.99, 0.0)), 1.0);
+        assert_eq!(light_directions(&Vector3::y(), &jittered).len(), 16);
+    }
+
+    #[test]
+    fn test_contact_hardening() {
+        let ground = wavefront::Object::from_bytes(b"v -2 0 -2\nv 2 0 -2\nv 2 0 2\nv -2 0 2\nvt 0 0\nvn 0 1 0\n\
+                                                     f 1/1/1 4/1/1 3/1/1\nf 1/1/1 3/1/1 2/1/1\n",
+                                                   &wavefront::LoadOptions::default()).unwrap();
+        let blocker = |height: f64| {
+            let bytes = format!("v -1 {0} -1\nv 1 {0} -1\nv 1 {0} 1\nv -1 {0} 1\nvt 0 0\nvn 0 1 0\n\
+                                 f 1/1/1 4/1/1 3/1/1\nf 1/1/1 3/1/1 2/1/1\n", height);
+            wavefront::Object::from_bytes(bytes.as_bytes(), &wavefront::LoadOptions::default()).unwrap()
+        };
+
+        let hard = ShadowOptions { size: 256, kernel: 0, ..Default::default() };
+        let soft = ShadowOptions { light_size: 0.2, ..hard };
+        let visibility = |height: f64, options: &ShadowOptions, x: f64| {
+            ShadowMap::new(&[&ground, &blocker(height)], &Light::Directional(Vector3::y()), options)
+                .visibility(&Vector3::new(x, 0.0, 0.0))
+        };
+
+        // a blocker close to the ground casts a sharp shadow
+        assert_eq!(visibility(0.1, &soft, 0.9), 0.0);
+        assert_eq!(visibility(0.1, &soft, 1.1), 1.0);
+
+        // a blocker further away blurs the same edge into a penumbra on both sides of it
+        let inside = visibility(1.5, &soft, 0.9);
+        let outside = visibility(1.5, &soft, 1.1);
+
+        assert!(inside > 0.0 && inside < outside && outside < 1.0);
+        assert_eq!(visibility(1.5, &soft, 0.0), 0.0);
+        assert_eq!(visibility(1.5, &hard, 1.1), 1.0);
+    }
+
+    #[test]
+    fn test_cascades() {
+        let terrain = wavefront::Object::from_bytes(b"v -2 0 0\nv 2 0 0\nv 2 0 -100\nv -2 0 -100\nvt 0 0\nvn 0 1 0\n\
+                                                      f 1/1/1 2/1/1 3/1/1\nf 1/1/1 3/1/1 4/1/1\n",
+                                                    &wavefront::LoadOptions::default()).unwrap();
+        let pebble = wavefront::Object::from_bytes(b"v -0.1 0.5 -2.1\nv 0.1 0.5 -2.1\nv 0.1 0.5 -1.9\n\
+                                                     v -0.1 0.5 -1.9\nvt 0 0\nvn 0 1 0\n\
+                                                     f 1/1/1 4/1/1 3/1/1\nf 1/1/1 3/1/1 2/1/1\n",
+                                                   &wavefront::LoadOptions::default()).unwrap();
+
+        let (eye, center) = (Vector3::new(0.0, 2.0, 2.0), Vector3::new(0.0, 0.0, -10.0));
+        let model_view = shader::lookat(&eye, &center, &Vector3::y());
+        let projection = shader::projection(-1.0 / (eye - center).norm());
+        let view_port = shader::viewport(8, 8, 48, 48, 255);
+
+        let options = ShadowOptions { size: 64, kernel: 0, cascades: 4, ..Default::default() };
+        let sun = Light::Directional(Vector3::y());
+        let shadow_map = ShadowMap::cascaded(&[&terrain, &pebble], &sun, &options, &view_port, &projection,
+                                             &model_view, 64, 64);
+
+        // the cascades follow each other away from the camera and grow with the distance
+        assert_eq!(shadow_map.cascades.len(), 4);
+        assert!(shadow_map.cascades.windows(2).all(|pair| pair[0].far == pair[1].near));
+        assert!(shadow_map.cascades.windows(2).all(|pair| pair[0].map.extent < pair[1].map.extent));
+        assert!(shadow_map.cascades[0].map.extent * 3.0 < shadow_map.cascades[3].map.extent);
+
+        // the near cascade is fine enough to catch the shadow of a pebble, while the far terrain is lit
+        assert_eq!(shadow_map.visibility(&Vector3::new(0.0, 0.0, -2.0)), 0.0);
+        assert_eq!(shadow_map.visibility(&Vector3::new(0.3, 0.0, -2.0)), 1.0);
+        assert_eq!(shadow_map.visibility(&Vector3::new(0.0, 0.0, -90.0)), 1.0);
+
+        // points in the band at the end of a cascade blend into the next one
+        let first = &shadow_map.cascades[0];
+        let camera = shadow_map.camera.unwrap();
+        let band = (first.far - first.near) * options.cascade_blend;
+        let point = |depth: f64| eye + (center - eye).normalize() * depth * (eye - center).norm() /
+                                       camera.depth(&center);
+
+        assert_eq!(shadow_map.cascade(&point(first.far - band * 2.0)), Some((0, 0.0)));
+        let (index, blend) = shadow_map.cascade(&point(first.far - band / 2.0)).unwrap();
+        assert_eq!(index, 0);
+        assert!((blend - 0.5).abs() < 1e-6);
+        assert_eq!(shadow_map.cascade(&point(first.far + band)).map(|(index, _)| index), Some(1));
+
+        // a single cascade is a plain shadow map
+        let single = ShadowMap::cascaded(&[&terrain], &sun, &ShadowOptions { cascades: 1, ..options }, &view_port,
+                                         &projection, &model_view, 64, 64);
+        assert!(single.cascades.is_empty() && single.camera.is_none());
+    }
+
+    #[test]
+    fn test_spotlight_shadows() {
+        let blocker = wavefront::Object::from_bytes(b"v -0.5 1 -0.5\nv 0.5 1 -0.5\nv 0.5 1 0.5\n\
+                                                      v -0.5 1 0.5\nvt 0 0\nvn 0 1 0\n\
+                                                      f 1/1/1 4/1/1 3/1/1\nf 1/1/1 3/1/1 2/1/1\n",
+                                                    &wavefront::LoadOptions::default()).unwrap();
+        let spot = Spotlight::new(Vector3::new(0.0, 2.0, 0.0), -Vector3::y(), 0.6, 1.0);
+        let shadow_map = ShadowMap::new(&[&blocker], &Light::Spot(spot), &ShadowOptions::default());
+
+        // the blocker is half way to the ground so its shadow is twice as wide
+        assert_eq!(shadow_map.visibility(&Vector3::new(0.9, 0.0, 0.0)), 0.0);
+        assert_eq!(shadow_map.visibility(&Vector3::new(1.2, 0.0, 0.0)), 1.0);
+        assert_eq!(shadow_map.visibility(&Vector3::new(0.0, 1.5, 0.0)), 1.0);
+        assert_eq!(shadow_map.visibility(&Vector3::new(0.0, 3.0, 0.0)), 1.0);
+
+        // a wide spotlight blurs the edge of the shadow but not its center
+        let options = ShadowOptions { light_size: 0.4, ..Default::default() };
+        let soft = ShadowMap::new(&[&blocker], &Light::Spot(spot), &options);
+        let edge = soft.visibility(&Vector3::new(1.0, 0.0, 0.0));
+
+        assert!(edge > 0.0 && edge < 1.0);
+        assert_eq!(soft.visibility(&Vector3::new(0.0, 0.0, 0.0)), 0.0);
+    }
+}
@@ -0,0 +1,180 @@
+use nalgebra::{Matrix4, Vector2, Vector3};
+
+use post;
+use shader;
+use transparency::BAYER;
+
+
+/// A procedural sky drawn behind the objects of outdoor renders instead of an environment image
+///
+/// The sky fades from the horizon color up to the zenith color and down to
+/// the ground color below the horizon, with a disk of the sun color around
+/// the direction of the sun whose radius is in radians. The haze is a simple
+/// atmospheric tint that brightens the sky around the sun in its color, and
+/// no haze leaves a clear sky. Colors are blended in linear light and
+/// dithered as they are written, so the gradients do not band.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate rastermind;
+/// # use rastermind::sky::Sky;
+/// let dusk = Sky { zenith: image::Rgb([40, 50, 110]), horizon: image::Rgb([250, 150, 90]), haze: 0.8,
+///                  ..Default::default() };
+/// ```
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sky {
+    pub zenith: image::Rgb<u8>,
+    pub horizon: image::Rgb<u8>,
+    pub ground: image::Rgb<u8>,
+    pub sun: image::Rgb<u8>,
+    pub sun_radius: f64,
+    pub haze: f64
+}
+
+
+impl Default for Sky {
+    fn default() -> Sky {
+        Sky { zenith: image::Rgb([60, 110, 190]), horizon: image::Rgb([190, 210, 230]),
+              ground: image::Rgb([90, 85, 80]), sun: image::Rgb([255, 245, 225]), sun_radius: 0.03, haze: 0.3 }
+    }
+}
+
+
+impl Sky {
+    /// Find the linear color of the sky seen along the given direction, with the sun along the given one
+    ///
+    /// The edge of the sun disk is smoothed over the given angle, which is
+    /// the angle a pixel covers when the sky is drawn into an image.
+    ///
+    pub fn color(&self, direction: &Vector3<f64>, up: &Vector3<f64>, sun: Option<&Vector3<f64>>,
+                 pixel_angle: f64) -> Vector3<f64> {
+
+        let linear = |color: image::Rgb<u8>| Vector3::from_fn(|i, _| post::to_linear(color[i]));
+        let elevation = direction.dot(up).clamp(-1.0, 1.0);
+
+        // the square root keeps the sky pale only close to the horizon
+        let mut color = if elevation >= 0.0 {
+            linear(self.horizon).lerp(&linear(self.zenith), elevation.sqrt())
+        } else {
+            linear(self.horizon).lerp(&linear(self.ground), (-elevation).sqrt())
+        };
+
+        if let Some(sun) = sun.and_then(|sun| sun.try_normalize(0.0)) {
+            let cosine = direction.dot(&sun).clamp(-1.0, 1.0);
+            let coverage = ((self.sun_radius - cosine.acos()) / pixel_angle.max(1e-9) + 0.5).clamp(0.0, 1.0);
+
+            color += linear(self.sun) * self.haze.max(0.0) * cosine.max(0.0).powi(8);
+            color = color.lerp(&linear(self.sun), coverage);
+        }
+
+        color
+    }
+
+    /// Draw the sky into every pixel of the given buffer as seen from the camera of draw_object
+    ///
+    /// The sky is drawn over the whole buffer, so it is drawn before the
+    /// objects, which are drawn over it. The sun is the direction the light
+    /// shines from, as given to draw_object, and is left out when there is none.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate image;
+    /// # extern crate nalgebra;
+    /// # extern crate rastermind;
+    /// # use nalgebra::Vector3;
+    /// # use rastermind::sky::Sky;
+    /// # use rastermind::{render, wavefront};
+    /// # fn main() -> Result<(), rastermind::Error> {
+    /// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n",
+    /// #                                                   &Default::default())?;
+    /// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+    /// # let (eye, center, up) = (Vector3::<f64>::new(0.0, 0.0, 3.0), Vector3::<f64>::zeros(), Vector3::<f64>::y());
+    /// # let light_vector = Vector3::<f64>::z();
+    /// # let mut buffer = image::RgbImage::new(64, 64);
+    /// Sky::default().draw(&mut buffer, Some(&light_vector), &eye, &center, &up);
+    /// render::draw_object(&coordinates, &mut buffer, &texture, 255, &light_vector, &eye, &center, &up);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    pub fn draw(&self, buffer: &mut image::RgbImage, sun: Option<&Vector3<f64>>, eye: &Vector3<f64>,
+                center: &Vector3<f64>, up: &Vector3<f64>) {
+
+        let (width, height) = buffer.dimensions();
+        let view_port = shader::viewport(width / 8, height / 8, width * 3 / 4, height * 3 / 4, 255);
+
+        self.draw_with_viewport(buffer, sun, &view_port, eye, center, up);
+    }
+
+    /// Fill the buffer with the sky as seen through the given viewport, such as that of a camera
+    pub fn draw_with_viewport(&self, buffer: &mut image::RgbImage, sun: Option<&Vector3<f64>>,
+                              view_port: &Matrix4<f64>, eye: &Vector3<f64>, center: &Vector3<f64>,
+                              up: &Vector3<f64>) {
+
+        // undo the viewport and projection of the rasterizer, which put the image plane through the center
+        let view_to_world = shader::lookat(eye, center, up).fixed_slice::<nalgebra::U3, nalgebra::U3>(0, 0)
+                                                            .transpose();
+        let distance = (eye - center).norm();
+        let pixel_angle = 1.0 / (view_port[(0, 0)] * distance);
+        let sky_up = up.normalize();
+
+        for (x, y, pixel) in buffer.enumerate_pixels_mut() {
+            let ndc = Vector2::new((x as f64 - view_port[(0, 3)]) / view_port[(0, 0)],
+                                   (y as f64 - view_port[(1, 3)]) / view_port[(1, 1)]);
+            let direction = (view_to_world * Vector3::new(ndc.x, ndc.y, -distance)).normalize();
+
+            let color = self.color(&direction, &sky_up, sun, pixel_angle);
+            let threshold = (BAYER[y as usize % 4][x as usize % 4] as f64 + 0.5) / 16.0 - 0.5;
+
+            *pixel = image::Rgb([0, 1, 2].map(|i| post::to_srgb_dithered(color[i], threshold)));
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color() {
+        let sky = Sky { haze: 0.0, ..Default::default() };
+        let up = Vector3::y();
+        let srgb = |color: Vector3<f64>| image::Rgb([0, 1, 2].map(|i| post::to_srgb(color[i])));
+
+        assert_eq!(srgb(sky.color(&Vector3::y(), &up, None, 0.01)), sky.zenith);
+        assert_eq!(srgb(sky.color(&Vector3::x(), &up, None, 0.01)), sky.horizon);
+        assert_eq!(srgb(sky.color(&-Vector3::y(), &up, None, 0.01)), sky.ground);
+
+        // the sun covers its disk and the haze only brightens the sky around it
+        let sun = Vector3::new(1.0, 1.0, 0.0).normalize();
+        assert_eq!(srgb(sky.color(&sun, &up, Some(&sun), 0.01)), sky.sun);
+
+        let beside = Vector3::new(1.0, 1.2, 0.0).normalize();
+        let hazy = Sky { haze: 1.0, ..sky };
+        assert!(hazy.color(&beside, &up, Some(&sun), 0.01).x > sky.color(&beside, &up, Some(&sun), 0.01).x);
+        assert_eq!(hazy.color(&-sun, &up, Some(&sun), 0.01), sky.color(&-sun, &up, Some(&sun), 0.01));
+    }
+
+    #[test]
+    fn test_draw() {
+        let sky = Sky { haze: 0.0, ..Default::default() };
+        let mut buffer = image::RgbImage::new(64, 64);
+        let eye = Vector3::new(0.0, 0.0, 5.0);
+
+        // looking along the horizon towards the sun
+        sky.draw(&mut buffer, Some(&-Vector3::z()), &eye, &Vector3::zeros(), &Vector3::y());
+
+        assert_eq!(*buffer.get_pixel(32, 32), sky.sun);
+        assert!(buffer.get_pixel(32, 63)[2] > buffer.get_pixel(32, 0)[2]);
+
+        // the dither varies neighbouring pixels of the same color by at most one step
+        let row: Vec<u8> = (0..8).map(|x| buffer.get_pixel(x, 48)[0]).collect();
+        let (low, high) = (*row.iter().min().unwrap(), *row.iter().max().unwrap());
+        assert!(high - low <= 1);
+    }
+}
@@ -0,0 +1,217 @@
+use nalgebra::{Matrix2x3, Matrix3, Matrix4, Vector2, Vector3, Vector4};
+
+use shader::Shader;
+use shadow::ShadowMap;
+use texture;
+use vector;
+use wavefront;
+
+
+/// A cheap approximation of light that scatters under the surface of skin, wax, or jade
+///
+/// Wrap lighting lets the light reach past the terminator, where the surface
+/// would turn dark, in the scattering color, and light that shines through
+/// thin parts from behind is transmitted in the scattering color too. The
+/// wrap is how far past the terminator the light reaches, from 0 for plain
+/// diffuse lighting to 1 for light all around the surface, and the radius
+/// is the distance in the units of the scene over which the transmitted
+/// light falls off to about a third.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate rastermind;
+/// # use rastermind::subsurface::Subsurface;
+/// let wax = Subsurface { color: image::Rgb([255, 120, 60]), wrap: 0.6, radius: 0.2, ..Default::default() };
+/// ```
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Subsurface {
+    pub color: image::Rgb<u8>,
+    pub wrap: f64,
+    pub radius: f64,
+    pub transmission: f64
+}
+
+
+impl Default for Subsurface {
+    fn default() -> Subsurface {
+        Subsurface { color: image::Rgb([255, 100, 80]), wrap: 0.5, radius: 0.1, transmission: 1.0 }
+    }
+}
+
+
+/// Shade the texture or the given color of an Object as a translucent material
+///
+/// The shader is lit by the light vector it is given like the Gouraud shader.
+/// With a shadow map of the light, it darkens the light in shadow itself and
+/// transmits the light through the thickness of the casters in front of the
+/// fragment, so it must not be shadowed again by a ShadowShader, which
+/// would block the light that shines through.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::light::{Light, LightShader};
+/// # use rastermind::shadow::{ShadowMap, ShadowOptions};
+/// # use rastermind::subsurface::*;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let light = Light::Directional(Vector3::y());
+/// # let shadow_map = ShadowMap::new(&[&coordinates], &light, &ShadowOptions::default());
+/// let shader = LightShader::new(Box::new(SubsurfaceShader::new(Subsurface::default(), Some(&shadow_map))),
+///                               &light);
+/// # Ok(())
+/// # }
+/// ```
+///
+pub struct SubsurfaceShader<'a> {
+    pub subsurface: Subsurface,
+    pub shadow_map: Option<&'a ShadowMap>,
+    pub color: Option<image::Rgb<u8>>,
+    pub varying_lambert: Vector3<f64>,
+    pub varying_texture: Matrix2x3<f64>,
+    pub varying_position: Matrix3<f64>,
+    pub varying_w: Vector3<f64>
+}
+
+
+impl<'a> SubsurfaceShader<'a> {
+    /// Create a new instance of a SubsurfaceShader that samples the texture
+    pub fn new(subsurface: Subsurface, shadow_map: Option<&'a ShadowMap>) -> SubsurfaceShader<'a> {
+        SubsurfaceShader { subsurface, shadow_map, color: None, varying_lambert: Vector3::zeros(),
+                           varying_texture: Matrix2x3::zeros(), varying_position: Matrix3::zeros(),
+                           varying_w: Vector3::zeros() }
+    }
+
+    /// Create a new instance of a SubsurfaceShader that shades a single color instead of the texture
+    pub fn with_color(color: image::Rgb<u8>, subsurface: Subsurface,
+                      shadow_map: Option<&'a ShadowMap>) -> SubsurfaceShader<'a> {
+
+        SubsurfaceShader { color: Some(color), ..SubsurfaceShader::new(subsurface, shadow_map) }
+    }
+}
+
+
+impl<'a> Shader for SubsurfaceShader<'a> {
+    /// Position the vertices into their scene coordinates
+    fn vertex(&mut self, coordinates: &wavefront::Object,
+              view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+              model_view: &Matrix4<f64>, light_vector: &Vector3<f64>,
+              face_index: usize, vertex_index: usize) -> Vector4<f64> {
+
+        let geometric_index = coordinates.geometric_faces[face_index][vertex_index] as usize;
+        let texture_index = coordinates.texture_faces[face_index][vertex_index] as usize;
+        let normal_index = coordinates.normal_faces[face_index][vertex_index] as usize;
+
+        // the wrapped and transmitted light both need the side of the terminator the vertex is on
+        self.varying_lambert[vertex_index] = coordinates.normal_vertices[normal_index].normalize().dot(light_vector);
+        self.varying_texture.set_column(vertex_index, &coordinates.texture_vertices[texture_index]);
+        self.varying_position.set_column(vertex_index, &coordinates.geometric_vertices[geometric_index]);
+
+        let gl_vertex = vector::vectorize_to_4d(&coordinates.geometric_vertices[geometric_index]);
+        let screen_coordinate = view_port * projection * model_view * gl_vertex;
+        self.varying_w[vertex_index] = screen_coordinate.w;
+
+        screen_coordinate
+    }
+
+    /// Add the diffuse light, the light wrapped past the terminator, and the light shining through
+    fn fragment(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> image::Rgb<u8> {
+        let uv: Vector2<f64> = self.varying_texture * vertex;
+        let albedo = self.color.unwrap_or_else(|| texture::sample(texture, &uv));
+
+        let lambert = self.varying_lambert.dot(&vertex);
+        let wrap = self.subsurface.wrap.max(0.0);
+        let diffuse = lambert.max(0.0);
+        let wrapped = ((lambert + wrap) / (1.0 + wrap)).max(0.0) - diffuse;
+
+        let perspective = vertex.component_div(&self.varying_w);
+        let position = self.varying_position * perspective / perspective.sum();
+
+        let (attenuation, transmitted) = match self.shadow_map {
+            Some(shadow_map) => {
+                let thickness = shadow_map.thickness(&position);
+                let transmitted = (-thickness / self.subsurface.radius.max(1e-9)).exp() * (-lambert).max(0.0);
+
+                (shadow_map.attenuation(&position), transmitted * self.subsurface.transmission)
+            },
+            None => (1.0, 0.0)
+        };
+
+        let mut color = albedo;
+
+        (0..=2).for_each(|i| {
+            let scattered = albedo[i] as f64 * self.subsurface.color[i] as f64 / 255.0;
+            let light = albedo[i] as f64 * diffuse * attenuation + scattered * (wrapped * attenuation + transmitted);
+
+            color[i] = light.min(255.0) as u8;
+        });
+
+        color
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use light::Light;
+    use render;
+    use shader;
+    use shadow;
+
+    #[test]
+    fn test_subsurface_shader() {
+        // a thin slab seen from the front with the light behind it
+        let slab = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\n\
+                                                   v -1 -1 -0.05\nv 1 -1 -0.05\nv 1 1 -0.05\nv -1 1 -0.05\n\
+                                                   vt 0 0\nvn 0 0 1\nvn 0 0 -1\n\
+                                                   f 1/1/1 2/1/1 3/1/1\nf 1/1/1 3/1/1 4/1/1\n\
+                                                   f 5/1/2 7/1/2 6/1/2\nf 5/1/2 8/1/2 7/1/2\n",
+                                                 &wavefront::LoadOptions::default()).unwrap();
+        let light = Light::Directional(-Vector3::z());
+        let options = shadow::ShadowOptions { size: 64, kernel: 0, ..Default::default() };
+        let shadow_map = ShadowMap::new(&[&slab], &light, &options);
+        let white = image::Rgb([255, 255, 255]);
+
+        let draw = |light_vector: Vector3<f64>, subsurface: Subsurface, shadow_map: Option<&ShadowMap>| {
+            let mut buffer = image::RgbImage::new(32, 32);
+            let mut zbuffer = vec![-1.0; 32 * 32];
+
+            render::draw_faces(&slab, &mut buffer, &image::RgbImage::new(1, 1), &mut zbuffer,
+                               &render::DepthOptions::default(), &shader::viewport(4, 4, 24, 24, 255),
+                               &shader::projection(0.0), &Matrix4::identity(), &light_vector,
+                               |_| -> Box<dyn Shader> {
+                Box::new(SubsurfaceShader::with_color(white, subsurface, shadow_map))
+            });
+
+            *buffer.get_pixel(16, 16)
+        };
+
+        let subsurface = Subsurface { color: image::Rgb([255, 0, 0]), wrap: 0.5, radius: 0.1, transmission: 1.0 };
+
+        // lit from the front the scattering does not show
+        assert_eq!(draw(Vector3::z(), subsurface, None), white);
+
+        // at the terminator only the wrapped light shows, in the scattering color
+        assert_eq!(draw(Vector3::x(), subsurface, None), image::Rgb([85, 0, 0]));
+
+        // lit from behind, light shines through the thin slab in the scattering color
+        let shining = draw(-Vector3::z(), subsurface, Some(&shadow_map));
+        let expected = (255.0 * (-0.05f64 / 0.1).exp()) as u8;
+
+        assert!((shining[0] as i32 - expected as i32).abs() <= 3);
+        assert_eq!(&shining.0[1..], &[0, 0]);
+
+        // a thicker radius lets more of the light through and no transmission blocks it
+        assert!(draw(-Vector3::z(), Subsurface { radius: 0.5, ..subsurface }, Some(&shadow_map))[0] > shining[0]);
+        assert_eq!(draw(-Vector3::z(), Subsurface { transmission: 0.0, ..subsurface }, Some(&shadow_map)),
+                   image::Rgb([0, 0, 0]));
+    }
+}
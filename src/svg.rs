@@ -0,0 +1,187 @@
+use std::fmt::Write;
+
+use nalgebra::Vector3;
+
+use lines;
+use lines::{HiddenLines, Segment};
+use shader;
+use wavefront;
+
+
+/// The depth of the viewport used to test the visibility of lines
+const SVG_DEPTH: u32 = 65535;
+
+
+/// The edges of an Object that are drawn
+///
+/// Every edge of the faces is drawn for a wireframe, only the silhouette
+/// edges seen from the eye for an outline, or the silhouette edges together
+/// with the boundaries and the creases sharper than the given angle in
+/// radians for an illustration.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Edges {
+    Mesh,
+    Silhouette,
+    Outline(f64)
+}
+
+
+/// Options that control the lines of an SVG drawing
+///
+/// The stroke width is in pixels of the drawing. Hidden lines are drawn like
+/// a wireframe, removed for a hidden line drawing, or drawn dashed.
+///
+/// # Examples
+///
+/// ```
+/// # use rastermind::lines::HiddenLines;
+/// # use rastermind::svg::SvgOptions;
+/// let options = SvgOptions { hidden_lines: HiddenLines::Dashed, ..Default::default() };
+/// ```
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SvgOptions {
+    pub stroke: image::Rgb<u8>,
+    pub stroke_width: f64,
+    pub hidden_lines: HiddenLines,
+    pub edges: Edges
+}
+
+
+impl Default for SvgOptions {
+    fn default() -> SvgOptions {
+        SvgOptions { stroke: image::Rgb([0, 0, 0]), stroke_width: 1.0, hidden_lines: HiddenLines::Removed,
+                     edges: Edges::Mesh }
+    }
+}
+
+
+/// Draw the edges of an Object seen from the given camera as an SVG document
+///
+/// The camera and viewport match draw_object, so the drawing lines up with
+/// a render of the same size.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use std::fs;
+/// # use rastermind::svg::{self, SvgOptions};
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// let svg = svg::render(&coordinates, 1024, 1024, &Vector3::new(0.0, 15.0, 70.0),
+///                       &Vector3::zeros(), &Vector3::y(), &SvgOptions::default());
+///
+/// fs::write("drawing.svg", svg).unwrap();
+/// # Ok(())
+/// # }
+/// ```
+///
+#[allow(clippy::too_many_arguments)]
+pub fn render(coordinates: &wavefront::Object, width: u32, height: u32,
+              eye: &Vector3<f64>, center: &Vector3<f64>, up: &Vector3<f64>,
+              options: &SvgOptions) -> String {
+
+    let model_view = shader::lookat(eye, center, up);
+    let projection = shader::projection(-1.0 / (eye - center).norm());
+    let view_port = shader::viewport(width / 8, height / 8, width * 3 / 4, height * 3 / 4, SVG_DEPTH);
+
+    let zbuffer = lines::depth_buffer(coordinates, width, height, &view_port, &projection, &model_view);
+    let edges = match options.edges {
+        Edges::Mesh => lines::mesh_edges(coordinates),
+        Edges::Silhouette => lines::silhouette_edges(coordinates, eye),
+        Edges::Outline(crease_angle) => lines::outline_edges(coordinates, eye, crease_angle)
+    };
+    let segments = lines::segments(coordinates, &edges, &zbuffer,
+                                   width, height, SVG_DEPTH, &view_port, &projection, &model_view);
+
+    document(&segments, width, height, options)
+}
+
+
+/// Write the given segments as the paths of an SVG document of the given size
+///
+/// The segments are in screen space with y pointing up, so they are flipped
+/// to match the y down coordinates of SVG.
+///
+pub fn document(segments: &[Segment], width: u32, height: u32, options: &SvgOptions) -> String {
+    let color = format!("#{:02x}{:02x}{:02x}", options.stroke[0], options.stroke[1], options.stroke[2]);
+    let path = |visible: bool| -> String {
+        segments.iter()
+                .filter(|segment| segment.visible == visible)
+                .fold(String::new(), |mut path, segment| {
+                    write!(path, "M{:.2} {:.2}L{:.2} {:.2}", segment.start.x, height as f64 - segment.start.y,
+                           segment.end.x, height as f64 - segment.end.y).unwrap();
+                    path
+                })
+    };
+
+    let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" \
+                           viewBox=\"0 0 {0} {1}\">\n", width, height);
+    let stroke = format!("fill=\"none\" stroke=\"{}\" stroke-width=\"{}\" stroke-linecap=\"round\"",
+                         color, options.stroke_width);
+
+    writeln!(svg, "<path {} d=\"{}\"/>", stroke, path(true)).unwrap();
+
+    match options.hidden_lines {
+        HiddenLines::Drawn => writeln!(svg, "<path {} d=\"{}\"/>", stroke, path(false)).unwrap(),
+        HiddenLines::Dashed => writeln!(svg, "<path {} stroke-dasharray=\"{} {}\" d=\"{}\"/>", stroke,
+                                        options.stroke_width * 4.0, options.stroke_width * 3.0,
+                                        path(false)).unwrap(),
+        HiddenLines::Removed => ()
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document() {
+        let segment = |x: f64, visible| Segment { start: Vector3::new(x, 0.0, 0.0),
+                                                  end: Vector3::new(x, 10.0, 0.0), visible };
+        let segments = [segment(1.0, true), segment(2.0, false)];
+
+        let removed = document(&segments, 20, 10, &SvgOptions::default());
+        assert!(removed.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"20\" height=\"10\""));
+        assert!(removed.contains("stroke=\"#000000\""));
+        assert!(removed.contains("d=\"M1.00 10.00L1.00 0.00\""));
+        assert!(!removed.contains("M2.00"));
+
+        let options = SvgOptions { stroke: image::Rgb([255, 0, 16]), hidden_lines: HiddenLines::Dashed,
+                                   ..Default::default() };
+        let dashed = document(&segments, 20, 10, &options);
+        assert!(dashed.contains("stroke=\"#ff0010\""));
+        assert!(dashed.contains("stroke-dasharray=\"4 3\" d=\"M2.00 10.00L2.00 0.00\""));
+    }
+
+    #[test]
+    fn test_render() {
+        let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\n\
+                                                          f 1/1/1 2/1/1 3/1/1\n",
+                                                        &wavefront::LoadOptions::default()).unwrap();
+
+        let svg = render(&coordinates, 64, 64, &Vector3::new(0.0, 0.0, 3.0), &Vector3::zeros(),
+                         &Vector3::y(), &SvgOptions::default());
+
+        assert_eq!(svg.matches('M').count(), 3);
+        assert!(svg.ends_with("</svg>\n"));
+
+        // the triangle is flat, so its silhouette is empty while its outline is its boundary
+        let silhouette = SvgOptions { edges: Edges::Silhouette, ..Default::default() };
+        let outline = SvgOptions { edges: Edges::Outline(0.5), ..Default::default() };
+        let draw = |options| render(&coordinates, 64, 64, &Vector3::new(0.0, 0.0, 3.0), &Vector3::zeros(),
+                                    &Vector3::y(), &options);
+
+        assert_eq!(draw(silhouette).matches('M').count(), 0);
+        assert_eq!(draw(outline).matches('M').count(), 3);
+    }
+}
@@ -0,0 +1,209 @@
+use error::MorpheusError;
+use overlay;
+
+
+/// One parameter of a sweep and the values it takes
+///
+/// The path and the values are those of the overrides of a request, so any
+/// parameter that can be set with --set can be swept.
+///
+/// # Examples
+///
+/// ```
+/// # use rastermind::sweep::Axis;
+/// let angles = Axis::parse("scene.camera.eye=[0,0,3];[3,0,3];[-3,0,3]").unwrap();
+/// ```
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Axis {
+    pub path: String,
+    pub values: Vec<String>
+}
+
+
+impl Axis {
+    /// Parse an axis of the form path=value;value;value
+    pub fn parse(specification: &str) -> Result<Axis, MorpheusError> {
+        let (path, values) = specification.split_once('=').ok_or_else(|| {
+            MorpheusError::Scene(format!("'{}' is not of the form path=values", specification))
+        })?;
+        let values: Vec<String> = values.split(';').map(|value| value.trim().to_string())
+                                        .filter(|value| !value.is_empty()).collect();
+
+        if values.is_empty() {
+            return Err(MorpheusError::Scene(format!("'{}' has no values", specification)));
+        }
+
+        Ok(Axis { path: path.trim().to_string(), values })
+    }
+
+    /// The override that sets the parameter to its value at the given index
+    pub fn assignment(&self, index: usize) -> String {
+        format!("{}={}", self.path, self.values[index])
+    }
+}
+
+
+/// Options that control the layout of a comparison sheet
+///
+/// Every cell is separated from the next by the padding, and the labels are
+/// drawn at the given text scale in the label color over the background.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SheetOptions {
+    pub padding: u32,
+    pub text_scale: u32,
+    pub background: image::Rgb<u8>,
+    pub label_color: image::Rgb<u8>
+}
+
+
+impl Default for SheetOptions {
+    fn default() -> SheetOptions {
+        SheetOptions { padding: 8, text_scale: 2, background: image::Rgb([32, 32, 32]),
+                       label_color: image::Rgb([230, 230, 230]) }
+    }
+}
+
+
+/// Render a grid of images that vary one or two parameters and lay them out on a labelled comparison sheet
+///
+/// The rows take the values of the first axis and the columns those of the
+/// second, or a single column is drawn without a second axis. The render
+/// function is given the overrides of every cell, and every cell must be
+/// rendered at the same size. Every column is labelled with its value above
+/// it and every row with its value to its left. Like the renders, the sheet
+/// is stored with its first row at the bottom.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate rastermind;
+/// # use rastermind::sweep::*;
+/// # use rastermind::{overrides, service};
+/// # let json = String::from("{}");
+/// # fn render(_: &service::Request) -> Result<image::RgbImage, rastermind::Error> {
+/// #     Ok(image::RgbImage::new(8, 8))
+/// # }
+/// let eye = Axis::parse("scene.camera.eye=[0,0,3];[3,0,3]").unwrap();
+/// let exposure = Axis::parse("scene.post.exposure=0.5;1;2").unwrap();
+///
+/// let sheet = sweep(&eye, Some(&exposure), &SheetOptions::default(), |assignments| {
+///     overrides::parse(&json, assignments).and_then(|request| render(&request))
+/// }).unwrap();
+/// ```
+///
+pub fn sweep<F>(rows: &Axis, columns: Option<&Axis>, options: &SheetOptions,
+                mut render: F) -> Result<image::RgbImage, MorpheusError>
+    where F: FnMut(&[String]) -> Result<image::RgbImage, MorpheusError> {
+
+    let column_count = columns.map_or(1, |columns| columns.values.len());
+    let mut cells = Vec::new();
+
+    for row in 0..rows.values.len() {
+        for column in 0..column_count {
+            let mut assignments = vec![rows.assignment(row)];
+            assignments.extend(columns.map(|columns| columns.assignment(column)));
+
+            cells.push(render(&assignments)?);
+        }
+    }
+
+    let (width, height) = cells[0].dimensions();
+
+    if cells.iter().any(|cell| cell.dimensions() != (width, height)) {
+        return Err(MorpheusError::Render(String::from("every cell of a sweep must be rendered at the same size")));
+    }
+
+    let label = |axis: &Axis, index: usize| format!("{}={}", axis.path, axis.values[index]);
+    let label_width = (0..rows.values.len()).map(|row| overlay::text_size(&label(rows, row), options.text_scale).0)
+                                            .max().unwrap_or(0);
+    let text_height = overlay::text_size("", options.text_scale).1;
+    let label_height = if columns.is_some() { text_height + options.padding } else { 0 };
+
+    let padding = options.padding;
+    let left = padding + label_width + padding;
+    let sheet_width = left + column_count as u32 * (width + padding);
+    let sheet_height = padding + label_height + rows.values.len() as u32 * (height + padding);
+    let mut sheet = image::RgbImage::from_pixel(sheet_width, sheet_height, options.background);
+
+    for (index, cell) in cells.iter().enumerate() {
+        let (row, column) = ((index / column_count) as u32, (index % column_count) as u32);
+        // the first row of the sheet is at the top, which is the end of the buffer
+        let (x, y) = (left + column * (width + padding), sheet_height - (row + 1) * (height + padding) - label_height);
+
+        for (i, j, pixel) in cell.enumerate_pixels() {
+            sheet.put_pixel(x + i, y + j, *pixel);
+        }
+
+        if column == 0 {
+            overlay::draw_text(&mut sheet, padding as i64, (y + (height - text_height.min(height)) / 2) as i64,
+                               &label(rows, row as usize), options.label_color, options.text_scale);
+        }
+
+        if let (Some(columns), 0) = (columns, row) {
+            overlay::draw_text(&mut sheet, x as i64, (y + height + padding) as i64, &label(columns, column as usize),
+                               options.label_color, options.text_scale);
+        }
+    }
+
+    Ok(sheet)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let axis = Axis::parse("light = [1,1,1];[0,1,1]; ").unwrap();
+
+        assert_eq!(axis.path, "light");
+        assert_eq!(axis.values, vec!["[1,1,1]", "[0,1,1]"]);
+        assert_eq!(axis.assignment(1), "light=[0,1,1]");
+
+        assert!(Axis::parse("light").is_err());
+        assert!(Axis::parse("light=;").is_err());
+    }
+
+    #[test]
+    fn test_sweep() {
+        let rows = Axis::parse("a=1;2").unwrap();
+        let columns = Axis::parse("b=10;20;30").unwrap();
+        let options = SheetOptions { padding: 2, text_scale: 1, ..Default::default() };
+        let mut rendered = Vec::new();
+
+        let sheet = sweep(&rows, Some(&columns), &options, |assignments| {
+            rendered.push(assignments.to_vec());
+
+            let value = assignments.iter().map(|assignment| assignment[2..].parse::<u8>().unwrap()).sum();
+            Ok(image::RgbImage::from_pixel(8, 4, image::Rgb([value, 0, 0])))
+        }).unwrap();
+
+        // every combination is rendered once, row by row
+        assert_eq!(rendered.len(), 6);
+        assert_eq!(rendered[1], vec!["a=1", "b=20"]);
+
+        // the labels of the rows are 3 characters wide, and the labels of the columns take a row of text
+        let left = 2 + 11 + 2;
+        assert_eq!(sheet.dimensions(), (left + 3 * 10, 2 + 7 + 2 * 6));
+
+        // the first row of cells is at the top of the sheet, below the labels of the columns
+        assert_eq!(*sheet.get_pixel(left, sheet.height() - 2 - 7 - 1), image::Rgb([11, 0, 0]));
+        assert_eq!(*sheet.get_pixel(left + 2 * 10 + 7, 2), image::Rgb([32, 0, 0]));
+        assert!(sheet.pixels().any(|pixel| *pixel == options.label_color));
+
+        // a single axis makes a single column, and every cell must be the same size
+        assert_eq!(sweep(&rows, None, &options, |_| Ok(image::RgbImage::new(4, 4))).unwrap().dimensions(),
+                   (left + 6, 2 + 2 * 6));
+
+        let mut size = 3;
+        assert!(sweep(&rows, None, &options, |_| {
+            size += 1;
+            Ok(image::RgbImage::new(size, 4))
+        }).is_err());
+        assert!(sweep(&rows, None, &options, |_| Err(MorpheusError::Render(String::from("failed")))).is_err());
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+
+use nalgebra::{Vector3, Vector4};
+
+use wavefront;
+
+
+/// Generate the tangents of every corner of an Object the way MikkTSpace does, indexed like its normals
+///
+/// The tangent of a face points along increasing u of its texture
+/// coordinates, and at every corner it is made perpendicular to the normal
+/// of the corner and averaged with the tangents of the other faces that
+/// share the position, texture coordinate, and normal of the corner,
+/// weighted by the angle of each face at the corner. Faces whose texture
+/// coordinates are mirrored are not averaged with faces that are not, and
+/// the w of their tangents is -1 instead of 1, so the bitangent is always w
+/// times the cross product of the normal and the tangent. This matches the
+/// tangents normal maps are baked with, so they shade the same as they do
+/// in other tools.
+///
+/// The tangent vertices are returned along with a face of tangent indices
+/// for every face of the Object.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::{tangent, wavefront};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let (face, corner, normal) = (0, 0, Vector3::<f64>::z());
+/// let (tangents, faces) = tangent::tangents(&coordinates);
+///
+/// let tangent = tangents[faces[face][corner] as usize];
+/// let bitangent = tangent.w * normal.cross(&tangent.xyz());
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn tangents(coordinates: &wavefront::Object) -> (Vec<Vector4<f64>>, Vec<Vector3<i32>>) {
+    let mut sums: Vec<Vector3<f64>> = Vec::new();
+    let mut signs: Vec<f64> = Vec::new();
+    let mut normals: Vec<Vector3<f64>> = Vec::new();
+    let mut indices: HashMap<(i32, i32, i32, bool), i32> = HashMap::new();
+    let mut faces = Vec::with_capacity(coordinates.geometric_faces.len());
+
+    for face in 0..coordinates.geometric_faces.len() {
+        let geometric = coordinates.geometric_faces[face];
+        let texture = coordinates.texture_faces[face];
+        let normal = coordinates.normal_faces[face];
+
+        let positions: Vec<Vector3<f64>> = (0..3).map(|i| coordinates.geometric_vertices[geometric[i] as usize])
+                                                 .collect();
+        let uvs: Vec<(f64, f64)> = (0..3).map(|i| {
+            let uv = coordinates.texture_vertices[texture[i] as usize];
+            (uv.x, uv.y)
+        }).collect();
+
+        let (d1, d2) = (positions[1] - positions[0], positions[2] - positions[0]);
+        let (t21x, t21y) = (uvs[1].0 - uvs[0].0, uvs[1].1 - uvs[0].1);
+        let (t31x, t31y) = (uvs[2].0 - uvs[0].0, uvs[2].1 - uvs[0].1);
+
+        let signed_area = t21x * t31y - t21y * t31x;
+        let preserving = signed_area > 0.0;
+        let sign = if preserving { 1.0 } else { -1.0 };
+        let face_tangent = ((d1 * t31y - d2 * t21y) * sign).try_normalize(0.0).unwrap_or_else(Vector3::zeros);
+
+        let mut corners = Vector3::zeros();
+
+        for i in 0..3 {
+            let vertex_normal = coordinates.normal_vertices[normal[i] as usize];
+            let key = (geometric[i], texture[i], normal[i], preserving);
+
+            let index = *indices.entry(key).or_insert_with(|| {
+                sums.push(Vector3::zeros());
+                signs.push(sign);
+                normals.push(vertex_normal);
+                sums.len() as i32 - 1
+            });
+
+            // the angle of the face at the corner, measured in the plane of the normal like MikkTSpace does
+            let project = |edge: Vector3<f64>| (edge - vertex_normal * vertex_normal.dot(&edge)).try_normalize(0.0);
+            let angle = match (project(positions[(i + 1) % 3] - positions[i]),
+                               project(positions[(i + 2) % 3] - positions[i])) {
+                (Some(first), Some(second)) => first.dot(&second).clamp(-1.0, 1.0).acos(),
+                _ => 0.0
+            };
+
+            if let Some(tangent) = project(face_tangent) {
+                sums[index as usize] += tangent * angle;
+            }
+
+            corners[i] = index;
+        }
+
+        faces.push(corners);
+    }
+
+    let vertices = sums.iter().zip(&signs).zip(&normals).map(|((sum, &sign), normal)| {
+        let tangent = sum.try_normalize(0.0).unwrap_or_else(|| perpendicular(normal));
+
+        Vector4::new(tangent.x, tangent.y, tangent.z, sign)
+    }).collect();
+
+    (vertices, faces)
+}
+
+
+/// Any unit vector perpendicular to the given normal, for corners whose faces have no texture to follow
+fn perpendicular(normal: &Vector3<f64>) -> Vector3<f64> {
+    let axis = if normal.x.abs() > 0.9 { Vector3::y() } else { Vector3::x() };
+
+    (axis - normal * normal.dot(&axis)).try_normalize(0.0).unwrap_or(axis)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tangents() {
+        // a quad facing +z with u along +x, and a mirrored copy sharing its middle edge
+        let data = b"v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv -1 0 0\nv -1 1 0\n\
+                     vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nvn 0 0 1\n\
+                     f 1/1/1 2/2/1 3/3/1 4/4/1\nf 1/1/1 4/4/1 6/3/1 5/2/1\n";
+        let coordinates = wavefront::Object::from_bytes(data, &wavefront::LoadOptions::default()).unwrap();
+
+        let (tangents, faces) = tangents(&coordinates);
+        let tangent = |face: usize, corner: usize| tangents[faces[face][corner] as usize];
+
+        assert_eq!(faces.len(), 4);
+        assert!((tangent(0, 0) - Vector4::new(1.0, 0.0, 0.0, 1.0)).norm() < 1e-12);
+        assert!((tangent(1, 2) - Vector4::new(1.0, 0.0, 0.0, 1.0)).norm() < 1e-12);
+
+        // the mirrored faces run u along -x, and are kept apart from the corners they share
+        assert!((tangent(2, 0) - Vector4::new(-1.0, 0.0, 0.0, -1.0)).norm() < 1e-12);
+        assert_ne!(faces[0][0], faces[2][0]);
+        assert_eq!(faces[0][0], faces[1][0]);
+
+        // the bitangent follows v, which runs along +y on both halves
+        let bitangent = tangent(2, 1).w * Vector3::z().cross(&tangent(2, 1).xyz());
+        assert!((bitangent - Vector3::y()).norm() < 1e-12);
+    }
+}
@@ -0,0 +1,328 @@
+use std::collections::BTreeMap;
+
+
+/// The characters used for plain text output from darkest to brightest
+const ASCII_RAMP: &[u8] = b" .:-=+*#%@";
+
+/// The number of levels of each channel of the color cube that sixel images are quantized to
+const SIXEL_LEVELS: u32 = 6;
+
+/// The largest amount of base64 data sent in one escape sequence of the kitty protocol
+const KITTY_CHUNK: usize = 4096;
+
+const BASE64_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+
+/// Convert the framebuffer to colored text for terminals that support 24 bit color
+///
+/// Every character covers two rows of pixels with an upper half block whose
+/// foreground is the upper pixel and whose background is the lower pixel. The
+/// framebuffer is stored with its first row at the bottom, so the last row is
+/// printed first.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate rastermind;
+/// # use rastermind::terminal;
+/// # let buffer = image::RgbImage::new(8, 8);
+/// print!("{}", terminal::ansi(&buffer));
+/// ```
+///
+pub fn ansi(buffer: &image::RgbImage) -> String {
+    let mut text = String::new();
+
+    for (upper, lower) in row_pairs(buffer) {
+        for x in 0..buffer.width() {
+            let top = buffer.get_pixel(x, upper);
+
+            match lower {
+                Some(lower) => {
+                    let bottom = buffer.get_pixel(x, lower);
+                    text.push_str(&format!("\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m\u{2580}",
+                                           top[0], top[1], top[2], bottom[0], bottom[1], bottom[2]));
+                }
+                None => text.push_str(&format!("\x1b[38;2;{};{};{}m\x1b[49m\u{2580}",
+                                               top[0], top[1], top[2]))
+            }
+        }
+
+        text.push_str("\x1b[0m\n");
+    }
+
+    text
+}
+
+
+/// Convert the framebuffer to plain text by the luminance of each pair of rows
+///
+/// Brighter pixels are drawn with denser characters, so the text reads as a
+/// light image on a dark terminal.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate rastermind;
+/// # use rastermind::terminal;
+/// # let buffer = image::RgbImage::new(8, 8);
+/// print!("{}", terminal::ascii(&buffer));
+/// ```
+///
+pub fn ascii(buffer: &image::RgbImage) -> String {
+    let mut text = String::new();
+
+    for (upper, lower) in row_pairs(buffer) {
+        for x in 0..buffer.width() {
+            let rows = [Some(upper), lower];
+            let luminances: Vec<f64> = rows.iter()
+                                           .flatten()
+                                           .map(|&y| luminance(buffer.get_pixel(x, y)))
+                                           .collect();
+            let average = luminances.iter().sum::<f64>() / luminances.len() as f64;
+            let index = (average / 256.0 * ASCII_RAMP.len() as f64) as usize;
+
+            text.push(ASCII_RAMP[index.min(ASCII_RAMP.len() - 1)] as char);
+        }
+
+        text.push('\n');
+    }
+
+    text
+}
+
+
+/// A protocol for showing images inline in a terminal
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Graphics {
+    Sixel,
+    Kitty,
+    ITerm
+}
+
+
+/// Encode the framebuffer as an escape sequence that shows it inline in a capable terminal
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate rastermind;
+/// # use rastermind::terminal;
+/// # use rastermind::terminal::Graphics;
+/// # let buffer = image::RgbImage::new(8, 8);
+/// print!("{}", terminal::graphics(&buffer, Graphics::Kitty));
+/// ```
+///
+pub fn graphics(buffer: &image::RgbImage, protocol: Graphics) -> String {
+    match protocol {
+        Graphics::Sixel => sixel(buffer),
+        Graphics::Kitty => kitty(buffer),
+        Graphics::ITerm => iterm(buffer)
+    }
+}
+
+
+/// Encode the framebuffer as a sixel image quantized to a cube of 216 colors
+///
+/// Sixel images are drawn in bands of six rows, where every color used in a
+/// band is drawn as a run length encoded line of characters whose bits select
+/// the rows of the band that have the color.
+///
+pub fn sixel(buffer: &image::RgbImage) -> String {
+    let (width, height) = buffer.dimensions();
+    let step = 255 / (SIXEL_LEVELS - 1);
+    let index = |pixel: &image::Rgb<u8>| {
+        (0..3).fold(0, |index, i| index * SIXEL_LEVELS + (pixel[i] as u32 + step / 2) / step)
+    };
+
+    let mut text = format!("\x1bPq\"1;1;{};{}", width, height);
+
+    for color in 0..SIXEL_LEVELS.pow(3) {
+        let channels = [color / SIXEL_LEVELS / SIXEL_LEVELS, color / SIXEL_LEVELS % SIXEL_LEVELS,
+                        color % SIXEL_LEVELS];
+        let percents: Vec<u32> = channels.iter().map(|level| level * 100 / (SIXEL_LEVELS - 1)).collect();
+        text.push_str(&format!("#{};2;{};{};{}", color, percents[0], percents[1], percents[2]));
+    }
+
+    for band in (0..height).step_by(6) {
+        let mut rows: BTreeMap<u32, Vec<u8>> = BTreeMap::new();
+
+        for offset in 0..6.min(height - band) {
+            // the framebuffer is stored with its first row at the bottom
+            let y = height - 1 - band - offset;
+
+            for x in 0..width {
+                let row = rows.entry(index(buffer.get_pixel(x, y))).or_insert_with(|| vec![0; width as usize]);
+                row[x as usize] |= 1 << offset;
+            }
+        }
+
+        let lines: Vec<String> = rows.iter().map(|(color, row)| {
+            format!("#{}{}", color, run_length(row))
+        }).collect();
+
+        text.push_str(&lines.join("$"));
+        text.push('-');
+    }
+
+    text.push_str("\x1b\\");
+    text
+}
+
+
+/// Encode a row of sixels, replacing every run of more than three equal sixels by a repeat
+fn run_length(row: &[u8]) -> String {
+    let mut text = String::new();
+    let mut start = 0;
+
+    while start < row.len() {
+        let length = row[start..].iter().take_while(|&&sixel| sixel == row[start]).count();
+        let character = (row[start] + 63) as char;
+
+        if length > 3 {
+            text.push_str(&format!("!{}{}", length, character));
+        } else {
+            (0..length).for_each(|_| text.push(character));
+        }
+
+        start += length;
+    }
+
+    text
+}
+
+
+/// Encode the framebuffer as a PNG image sent in chunks with the kitty graphics protocol
+pub fn kitty(buffer: &image::RgbImage) -> String {
+    let data = base64(&png(buffer));
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(KITTY_CHUNK).collect();
+
+    chunks.iter().enumerate().map(|(i, chunk)| {
+        let more = if i + 1 < chunks.len() { 1 } else { 0 };
+        let control = if i == 0 { format!("f=100,a=T,m={}", more) } else { format!("m={}", more) };
+
+        format!("\x1b_G{};{}\x1b\\", control, String::from_utf8_lossy(chunk))
+    }).collect()
+}
+
+
+/// Encode the framebuffer as a PNG image with the inline image protocol of iTerm2
+pub fn iterm(buffer: &image::RgbImage) -> String {
+    let png = png(buffer);
+
+    format!("\x1b]1337;File=inline=1;size={};width={}px;height={}px:{}\x07",
+            png.len(), buffer.width(), buffer.height(), base64(&png))
+}
+
+
+fn png(buffer: &image::RgbImage) -> Vec<u8> {
+    let mut png = Vec::new();
+    image::ImageRgb8(buffer.clone()).flipv()
+                                    .write_to(&mut png, image::ImageOutputFormat::PNG)
+                                    .unwrap();
+    png
+}
+
+
+/// Encode the bytes with the standard padded base64 alphabet
+fn base64(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| group | (byte as u32) << (16 - 8 * i));
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(BASE64_ALPHABET[(group >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+
+    text
+}
+
+
+/// Pair the rows of the framebuffer from the top down, where the last row may have no partner
+fn row_pairs(buffer: &image::RgbImage) -> Vec<(u32, Option<u32>)> {
+    (0..buffer.height()).rev()
+                        .step_by(2)
+                        .map(|upper| (upper, upper.checked_sub(1)))
+                        .collect()
+}
+
+
+/// Find the perceived brightness of the color in [0, 255]
+fn luminance(pixel: &image::Rgb<u8>) -> f64 {
+    0.2126 * pixel[0] as f64 + 0.7152 * pixel[1] as f64 + 0.0722 * pixel[2] as f64
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ansi() {
+        let mut buffer = image::RgbImage::new(2, 3);
+        buffer.put_pixel(0, 2, image::Rgb([255, 0, 0]));
+        buffer.put_pixel(1, 1, image::Rgb([0, 0, 255]));
+
+        let text = ansi(&buffer);
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("\x1b[38;2;255;0;0m\x1b[48;2;0;0;0m\u{2580}"));
+        assert!(lines[0].contains("\x1b[38;2;0;0;0m\x1b[48;2;0;0;255m\u{2580}"));
+        assert!(lines[1].contains("\x1b[49m"));
+        assert_eq!(text.matches('\u{2580}').count(), 4);
+    }
+
+    #[test]
+    fn test_ascii() {
+        let mut buffer = image::RgbImage::new(3, 4);
+        buffer.put_pixel(0, 3, image::Rgb([255, 255, 255]));
+        buffer.put_pixel(0, 2, image::Rgb([255, 255, 255]));
+        buffer.put_pixel(2, 0, image::Rgb([255, 255, 255]));
+
+        assert_eq!(ascii(&buffer), "@  \n  =\n");
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn test_sixel() {
+        let mut buffer = image::RgbImage::new(5, 7);
+        buffer.put_pixel(0, 6, image::Rgb([255, 0, 0]));
+
+        let text = sixel(&buffer);
+
+        assert!(text.starts_with("\x1bPq\"1;1;5;7#0;2;0;0;0"));
+        assert!(text.contains("#180;2;100;0;0"));
+        // the top row of red in the first band and the rest of it in black
+        assert!(text.contains("#0}!4~$#180@!4?-#0!5@-"));
+        assert!(text.ends_with("\x1b\\"));
+    }
+
+    #[test]
+    fn test_inline_images() {
+        let buffer = image::RgbImage::new(64, 64);
+
+        let kitty = graphics(&buffer, Graphics::Kitty);
+        assert!(kitty.starts_with("\x1b_Gf=100,a=T,m=0;iVBORw0KGgo"));
+        assert!(kitty.ends_with("\x1b\\"));
+
+        let iterm = graphics(&buffer, Graphics::ITerm);
+        assert!(iterm.starts_with("\x1b]1337;File=inline=1;size="));
+        assert!(iterm.ends_with("\x07"));
+    }
+}
@@ -0,0 +1,919 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+
+use nalgebra::{Vector2, Vector3};
+
+use wavefront;
+
+
+/// The distance between the baselines of two lines of text as a fraction of the size
+const LINE_SPACING: f64 = 1.2;
+
+/// The deepest nesting of compound glyphs that is followed
+const MAXIMUM_COMPONENT_DEPTH: usize = 8;
+
+
+/// An outer contour and the holes inside it
+type Shape = (Vec<Vector2<f64>>, Vec<Vec<Vector2<f64>>>);
+
+
+/// A TrueType font that outlines of glyphs are read from
+///
+/// Only the tables needed for outlines and spacing are read: the character
+/// map, the glyph locations and outlines, and the horizontal metrics.
+/// Quadratic outlines from the glyf table are supported, while the cubic
+/// outlines of CFF fonts are not.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate rastermind;
+/// # use rastermind::text::*;
+/// let font = Font::open("DejaVuSans.ttf").unwrap();
+/// let glyph = font.glyph_index('A');
+/// ```
+///
+#[derive(Clone, Debug)]
+pub struct Font {
+    data: Vec<u8>,
+    tables: HashMap<[u8; 4], (usize, usize)>,
+    pub units_per_em: u16,
+    long_offsets: bool,
+    glyph_count: u16,
+    metric_count: u16,
+    character_map: usize
+}
+
+
+/// One point of a glyph outline, which is on the curve or the control point of a quadratic curve
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OutlinePoint {
+    pub position: Vector2<f64>,
+    pub on_curve: bool
+}
+
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+
+impl Font {
+    /// Read the font from the given TrueType file
+    pub fn open(filename: &str) -> io::Result<Font> {
+        Font::from_bytes(fs::read(filename)?)
+    }
+
+    /// Read the font from the contents of a TrueType file
+    pub fn from_bytes(data: Vec<u8>) -> io::Result<Font> {
+        let mut font = Font { data, tables: HashMap::new(), units_per_em: 0, long_offsets: false,
+                              glyph_count: 0, metric_count: 0, character_map: 0 };
+
+        for i in 0..font.u16(4)? as usize {
+            let record = 12 + 16 * i;
+            let tag = font.data.get(record..record + 4).ok_or_else(|| invalid("truncated table directory"))?;
+
+            font.tables.insert([tag[0], tag[1], tag[2], tag[3]],
+                               (font.u32(record + 8)? as usize, font.u32(record + 12)? as usize));
+        }
+
+        let head = font.table(b"head")?;
+        font.units_per_em = font.u16(head + 18)?;
+        font.long_offsets = font.u16(head + 50)? != 0;
+        font.glyph_count = font.u16(font.table(b"maxp")? + 4)?;
+        font.metric_count = font.u16(font.table(b"hhea")? + 34)?;
+        font.character_map = font.find_character_map()?;
+
+        if font.units_per_em == 0 {
+            return Err(invalid("the font has no units per em"));
+        }
+
+        Ok(font)
+    }
+
+    /// Find the index of the glyph of the given character, where missing characters are glyph 0
+    pub fn glyph_index(&self, character: char) -> u16 {
+        let code = character as u32;
+        let table = self.character_map;
+
+        let index = match self.u16(table) {
+            Ok(4) => self.format_4_index(table, code),
+            Ok(12) => self.format_12_index(table, code),
+            _ => None
+        };
+
+        index.filter(|&index| index < self.glyph_count).unwrap_or(0)
+    }
+
+    /// Find how far the pen moves after drawing the given glyph in font units
+    pub fn advance(&self, glyph: u16) -> u16 {
+        let metrics = match self.table(b"hmtx") {
+            Ok(metrics) => metrics,
+            Err(_) => return self.units_per_em
+        };
+        let index = glyph.min(self.metric_count.saturating_sub(1)) as usize;
+
+        self.u16(metrics + 4 * index).unwrap_or(self.units_per_em)
+    }
+
+    /// Read the contours of the given glyph in font units
+    pub fn outline(&self, glyph: u16) -> io::Result<Vec<Vec<OutlinePoint>>> {
+        self.glyph_outline(glyph, 0)
+    }
+
+    fn glyph_outline(&self, glyph: u16, depth: usize) -> io::Result<Vec<Vec<OutlinePoint>>> {
+        if depth > MAXIMUM_COMPONENT_DEPTH {
+            return Err(invalid("compound glyphs are nested too deeply"));
+        }
+
+        let (start, end) = self.glyph_range(glyph)?;
+
+        if start == end {
+            return Ok(Vec::new());
+        }
+
+        let contour_count = self.u16(start)? as i16;
+
+        if contour_count >= 0 {
+            self.simple_outline(start, contour_count as usize)
+        } else {
+            self.compound_outline(start, depth)
+        }
+    }
+
+    fn simple_outline(&self, start: usize, contour_count: usize) -> io::Result<Vec<Vec<OutlinePoint>>> {
+        let mut ends = Vec::with_capacity(contour_count);
+
+        for i in 0..contour_count {
+            ends.push(self.u16(start + 10 + 2 * i)? as usize);
+        }
+
+        let point_count = ends.last().map_or(0, |&end| end + 1);
+        let instructions = start + 10 + 2 * contour_count;
+        let mut offset = instructions + 2 + self.u16(instructions)? as usize;
+
+        let mut flags = Vec::with_capacity(point_count);
+
+        while flags.len() < point_count {
+            let flag = self.u8(offset)?;
+            offset += 1;
+            flags.push(flag);
+
+            if flag & 8 != 0 {
+                let repeats = self.u8(offset)?;
+                offset += 1;
+                (0..repeats).for_each(|_| flags.push(flag));
+            }
+        }
+
+        flags.truncate(point_count);
+
+        let mut read_coordinates = |short: u8, same: u8| -> io::Result<Vec<f64>> {
+            let mut value = 0i32;
+            let mut values = Vec::with_capacity(point_count);
+
+            for &flag in &flags {
+                if flag & short != 0 {
+                    let delta = self.u8(offset)? as i32;
+                    offset += 1;
+                    value += if flag & same != 0 { delta } else { -delta };
+                } else if flag & same == 0 {
+                    value += self.u16(offset)? as i16 as i32;
+                    offset += 2;
+                }
+
+                values.push(value as f64);
+            }
+
+            Ok(values)
+        };
+
+        let xs = read_coordinates(2, 16)?;
+        let ys = read_coordinates(4, 32)?;
+
+        let mut contours = Vec::with_capacity(contour_count);
+        let mut first = 0;
+
+        for &end in &ends {
+            if end < first || end >= point_count {
+                return Err(invalid("contour end points are out of order"));
+            }
+
+            contours.push((first..=end).map(|i| OutlinePoint { position: Vector2::new(xs[i], ys[i]),
+                                                                on_curve: flags[i] & 1 != 0 }).collect());
+            first = end + 1;
+        }
+
+        Ok(contours)
+    }
+
+    fn compound_outline(&self, start: usize, depth: usize) -> io::Result<Vec<Vec<OutlinePoint>>> {
+        let mut contours = Vec::new();
+        let mut offset = start + 10;
+
+        loop {
+            let flags = self.u16(offset)?;
+            let glyph = self.u16(offset + 2)?;
+            offset += 4;
+
+            let (dx, dy) = if flags & 1 != 0 {
+                offset += 4;
+                (self.u16(offset - 4)? as i16 as f64, self.u16(offset - 2)? as i16 as f64)
+            } else {
+                offset += 2;
+                (self.u8(offset - 2)? as i8 as f64, self.u8(offset - 1)? as i8 as f64)
+            };
+            // components placed by matching points rather than by offsets are placed without an offset
+            let (dx, dy) = if flags & 2 != 0 { (dx, dy) } else { (0.0, 0.0) };
+
+            let mut scale = [1.0, 0.0, 0.0, 1.0];
+            let f2dot14 = |offset: usize| self.u16(offset).map(|value| value as i16 as f64 / 16384.0);
+
+            if flags & 8 != 0 {
+                scale[0] = f2dot14(offset)?;
+                scale[3] = scale[0];
+                offset += 2;
+            } else if flags & 0x40 != 0 {
+                scale[0] = f2dot14(offset)?;
+                scale[3] = f2dot14(offset + 2)?;
+                offset += 4;
+            } else if flags & 0x80 != 0 {
+                for (i, value) in scale.iter_mut().enumerate() {
+                    *value = f2dot14(offset + 2 * i)?;
+                }
+                offset += 8;
+            }
+
+            for contour in self.glyph_outline(glyph, depth + 1)? {
+                contours.push(contour.iter().map(|point| {
+                    let p = point.position;
+                    OutlinePoint { position: Vector2::new(scale[0] * p.x + scale[2] * p.y + dx,
+                                                          scale[1] * p.x + scale[3] * p.y + dy),
+                                   on_curve: point.on_curve }
+                }).collect());
+            }
+
+            if flags & 0x20 == 0 {
+                return Ok(contours);
+            }
+        }
+    }
+
+    fn glyph_range(&self, glyph: u16) -> io::Result<(usize, usize)> {
+        if glyph >= self.glyph_count {
+            return Err(invalid("the glyph is not in the font"));
+        }
+
+        let locations = self.table(b"loca")?;
+        let index = glyph as usize;
+
+        let (start, end) = if self.long_offsets {
+            (self.u32(locations + 4 * index)? as usize, self.u32(locations + 4 * index + 4)? as usize)
+        } else {
+            (self.u16(locations + 2 * index)? as usize * 2, self.u16(locations + 2 * index + 2)? as usize * 2)
+        };
+
+        let glyphs = self.table(b"glyf")?;
+
+        Ok((glyphs + start, glyphs + end.max(start)))
+    }
+
+    /// Find the offset of the character map subtable for unicode, preferring the full range of characters
+    fn find_character_map(&self) -> io::Result<usize> {
+        let cmap = self.table(b"cmap")?;
+        let mut best = None;
+
+        for i in 0..self.u16(cmap + 2)? as usize {
+            let record = cmap + 4 + 8 * i;
+            let platform = self.u16(record)?;
+            let encoding = self.u16(record + 2)?;
+            let subtable = cmap + self.u32(record + 4)? as usize;
+            let unicode = platform == 0 || (platform == 3 && (encoding == 1 || encoding == 10));
+
+            match self.u16(subtable) {
+                Ok(12) if unicode => return Ok(subtable),
+                Ok(4) if unicode => best = Some(subtable),
+                _ => ()
+            }
+        }
+
+        best.ok_or_else(|| invalid("the font has no unicode character map"))
+    }
+
+    fn format_4_index(&self, table: usize, code: u32) -> Option<u16> {
+        let segments = self.u16(table + 6).ok()? as usize / 2;
+        let ends = table + 14;
+        let starts = ends + 2 * segments + 2;
+        let deltas = starts + 2 * segments;
+        let range_offsets = deltas + 2 * segments;
+
+        for i in 0..segments {
+            if (self.u16(ends + 2 * i).ok()? as u32) < code {
+                continue;
+            }
+
+            let start = self.u16(starts + 2 * i).ok()? as u32;
+
+            if start > code {
+                return None;
+            }
+
+            let delta = self.u16(deltas + 2 * i).ok()?;
+            let range_offset = self.u16(range_offsets + 2 * i).ok()? as usize;
+
+            if range_offset == 0 {
+                return Some((code as u16).wrapping_add(delta));
+            }
+
+            let glyph = self.u16(range_offsets + 2 * i + range_offset + 2 * (code - start) as usize).ok()?;
+
+            return if glyph == 0 { None } else { Some(glyph.wrapping_add(delta)) };
+        }
+
+        None
+    }
+
+    fn format_12_index(&self, table: usize, code: u32) -> Option<u16> {
+        for i in 0..self.u32(table + 12).ok()? as usize {
+            let group = table + 16 + 12 * i;
+            let start = self.u32(group).ok()?;
+
+            if code >= start && code <= self.u32(group + 4).ok()? {
+                return Some((self.u32(group + 8).ok()? + code - start) as u16);
+            }
+        }
+
+        None
+    }
+
+    fn table(&self, tag: &[u8; 4]) -> io::Result<usize> {
+        self.tables.get(tag).map(|&(offset, _)| offset)
+                            .ok_or_else(|| invalid(&format!("the font has no {} table",
+                                                            String::from_utf8_lossy(tag))))
+    }
+
+    fn u8(&self, offset: usize) -> io::Result<u8> {
+        self.data.get(offset).cloned().ok_or_else(|| invalid("unexpected end of font data"))
+    }
+
+    fn u16(&self, offset: usize) -> io::Result<u16> {
+        Ok((self.u8(offset)? as u16) << 8 | self.u8(offset + 1)? as u16)
+    }
+
+    fn u32(&self, offset: usize) -> io::Result<u32> {
+        Ok((self.u16(offset)? as u32) << 16 | self.u16(offset + 2)? as u32)
+    }
+}
+
+
+/// Options that control the geometry of extruded text
+///
+/// The size is the height of the em square in the units of the object, the
+/// depth is how far the text is extruded along z, and the bevel is how far
+/// its front and back edges are chamfered. Every quadratic curve of the
+/// outlines is split into the given number of straight segments. A bevel
+/// wider than half the thinnest stroke of the font folds the stroke over.
+///
+/// # Examples
+///
+/// ```
+/// # use rastermind::text::TextOptions;
+/// let options = TextOptions { depth: 0.5, bevel: 0.05, ..Default::default() };
+/// ```
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextOptions {
+    pub size: f64,
+    pub depth: f64,
+    pub bevel: f64,
+    pub curve_segments: usize
+}
+
+
+impl Default for TextOptions {
+    fn default() -> TextOptions {
+        TextOptions { size: 1.0, depth: 0.2, bevel: 0.01, curve_segments: 6 }
+    }
+}
+
+
+/// Convert a string into extruded and beveled 3D text
+///
+/// The text starts at the origin on its baseline and runs along x with y up.
+/// Lines are split at newlines. The text is centered on z, with its front
+/// facing +z, and every character is a group named after it so it can be
+/// colored on its own.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate image;
+/// # extern crate rastermind;
+/// # use rastermind::scene::{Scene, SceneObject};
+/// # use rastermind::text;
+/// # use rastermind::text::*;
+/// # let mut scene = Scene::new();
+/// # let texture = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]));
+/// let font = text::Font::open("DejaVuSans.ttf").unwrap();
+/// let title = text::text_mesh(&font, "morpheus", &TextOptions::default()).unwrap();
+///
+/// scene.objects.push(SceneObject::new(title, texture));
+/// ```
+///
+pub fn text_mesh(font: &Font, text: &str, options: &TextOptions) -> io::Result<wavefront::Object> {
+    let scale = options.size / font.units_per_em as f64;
+    let mut object = wavefront::Object { texture_vertices: vec![Vector2::zeros()], ..Default::default() };
+    let mut pen = Vector2::zeros();
+
+    for character in text.chars() {
+        if character == '\n' {
+            pen = Vector2::new(0.0, pen.y - options.size * LINE_SPACING);
+            continue;
+        }
+
+        let glyph = font.glyph_index(character);
+        let start = object.geometric_faces.len();
+
+        let contours: Vec<Vec<Vector2<f64>>> = font.outline(glyph)?.iter()
+            .map(|contour| flatten(contour, options.curve_segments))
+            .map(|contour| contour.iter().map(|point| pen + point * scale).collect())
+            .collect();
+
+        for (outer, holes) in nest_contours(contours) {
+            extrude(&mut object, &outer, &holes, options);
+        }
+
+        if object.geometric_faces.len() > start {
+            object.groups.push(wavefront::Group { name: character.to_string(),
+                                                  faces: start..object.geometric_faces.len() });
+        }
+
+        pen.x += font.advance(glyph) as f64 * scale;
+    }
+
+    object.texture_faces = vec![Vector3::zeros(); object.geometric_faces.len()];
+    object.smoothing_groups = vec![0; object.geometric_faces.len()];
+    object.generate_normals();
+
+    Ok(object)
+}
+
+
+/// Flatten a contour of quadratic curves into a closed polygon without a repeated last point
+///
+/// Two control points in a row imply an on curve point halfway between them.
+///
+pub fn flatten(contour: &[OutlinePoint], segments: usize) -> Vec<Vector2<f64>> {
+    let count = contour.len();
+    let segments = segments.max(1);
+
+    // start from a point on the curve, which is implied when every point is a control point
+    let start = match contour.iter().position(|point| point.on_curve) {
+        Some(index) => contour[index].position,
+        None if count > 0 => (contour[0].position + contour[1 % count].position) / 2.0,
+        None => return Vec::new()
+    };
+    let offset = contour.iter().position(|point| point.on_curve).map_or(0, |index| index + 1);
+
+    let mut polygon = vec![start];
+    let mut control: Option<Vector2<f64>> = None;
+
+    for i in 0..count {
+        let point = contour[(offset + i) % count];
+
+        match (control, point.on_curve) {
+            (None, true) => polygon.push(point.position),
+            (None, false) => control = Some(point.position),
+            (Some(previous), true) => {
+                curve(&mut polygon, previous, point.position, segments);
+                control = None;
+            },
+            (Some(previous), false) => {
+                curve(&mut polygon, previous, (previous + point.position) / 2.0, segments);
+                control = Some(point.position);
+            }
+        }
+    }
+
+    if let Some(previous) = control {
+        curve(&mut polygon, previous, start, segments);
+    }
+
+    polygon.dedup_by(|a, b| (*a - *b).norm() < 1e-9);
+
+    while polygon.len() > 1 && (polygon[0] - polygon[polygon.len() - 1]).norm() < 1e-9 {
+        polygon.pop();
+    }
+
+    polygon
+}
+
+
+/// Add the points of a quadratic curve from the last point of the polygon to the given end
+fn curve(polygon: &mut Vec<Vector2<f64>>, control: Vector2<f64>, end: Vector2<f64>, segments: usize) {
+    let start = polygon[polygon.len() - 1];
+
+    for step in 1..=segments {
+        let t = step as f64 / segments as f64;
+        polygon.push(start * (1.0 - t) * (1.0 - t) + control * 2.0 * t * (1.0 - t) + end * t * t);
+    }
+}
+
+
+/// Twice the signed area of a polygon, which is positive for counter-clockwise polygons
+fn signed_area(polygon: &[Vector2<f64>]) -> f64 {
+    (0..polygon.len()).map(|i| {
+        let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+        a.x * b.y - b.x * a.y
+    }).sum()
+}
+
+
+fn contains(polygon: &[Vector2<f64>], point: &Vector2<f64>) -> bool {
+    let mut inside = false;
+
+    for i in 0..polygon.len() {
+        let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+
+        if (a.y > point.y) != (b.y > point.y) && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x) {
+            inside = !inside;
+        }
+    }
+
+    inside
+}
+
+
+/// Group contours into outer contours and their holes by how deeply they are nested
+///
+/// Outer contours are made counter-clockwise and holes clockwise, whatever
+/// direction the font used.
+///
+fn nest_contours(contours: Vec<Vec<Vector2<f64>>>) -> Vec<Shape> {
+    let contours: Vec<Vec<Vector2<f64>>> = contours.into_iter().filter(|contour| contour.len() >= 3).collect();
+    let parents: Vec<Vec<usize>> = contours.iter().map(|contour| {
+        (0..contours.len()).filter(|&j| !std::ptr::eq(&contours[j], contour) && contains(&contours[j], &contour[0]))
+                           .collect()
+    }).collect();
+
+    let mut shapes: Vec<(usize, Vec<Vec<Vector2<f64>>>)> = Vec::new();
+
+    for (i, contour) in contours.iter().enumerate().filter(|&(i, _)| parents[i].len().is_multiple_of(2)) {
+        let mut outer = contour.clone();
+
+        if signed_area(&outer) < 0.0 {
+            outer.reverse();
+        }
+
+        shapes.push((i, vec![outer]));
+    }
+
+    for (i, contour) in contours.iter().enumerate().filter(|&(i, _)| !parents[i].len().is_multiple_of(2)) {
+        // the hole belongs to the outer contour nested exactly one level above it
+        let depth = parents[i].len() - 1;
+
+        if let Some(shape) = shapes.iter_mut().find(|(outer, _)| parents[i].contains(outer) &&
+                                                                 parents[*outer].len() == depth) {
+            let mut hole = contour.clone();
+
+            if signed_area(&hole) > 0.0 {
+                hole.reverse();
+            }
+
+            shape.1.push(hole);
+        }
+    }
+
+    shapes.into_iter().map(|(_, mut rings)| {
+        let outer = rings.remove(0);
+        (outer, rings)
+    }).collect()
+}
+
+
+/// Triangulate a polygon with holes by clipping ears, returning triangles of indices into the rings
+///
+/// The indices count the points of the outer ring first and then of every
+/// hole in order. Holes are joined to the outer ring by a bridge from their
+/// rightmost point, so the ears are clipped from a single polygon.
+///
+pub fn triangulate(outer: &[Vector2<f64>], holes: &[Vec<Vector2<f64>>]) -> Vec<[usize; 3]> {
+    let points: Vec<Vector2<f64>> = outer.iter().chain(holes.iter().flatten()).cloned().collect();
+    let mut polygon: Vec<usize> = (0..outer.len()).collect();
+
+    let mut starts = Vec::new();
+    let mut start = outer.len();
+
+    for hole in holes {
+        starts.push(start);
+        start += hole.len();
+    }
+
+    let mut order: Vec<usize> = (0..holes.len()).collect();
+    order.sort_by(|&a, &b| {
+        let right = |hole: &Vec<Vector2<f64>>| hole.iter().fold(f64::MIN, |right, point| right.max(point.x));
+        right(&holes[b]).partial_cmp(&right(&holes[a])).unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    for &h in &order {
+        let hole = &holes[h];
+        let rightmost = (0..hole.len()).max_by(|&a, &b| hole[a].x.partial_cmp(&hole[b].x)
+                                                                 .unwrap_or(std::cmp::Ordering::Equal))
+                                       .unwrap_or(0);
+        let from = hole[rightmost];
+
+        // bridge to the nearest point of the polygon that can be reached without crossing an edge
+        let edges: Vec<(Vector2<f64>, Vector2<f64>)> = (0..polygon.len())
+            .map(|i| (points[polygon[i]], points[polygon[(i + 1) % polygon.len()]]))
+            .chain(holes.iter().flat_map(|hole| (0..hole.len()).map(move |i| (hole[i], hole[(i + 1) % hole.len()]))))
+            .collect();
+
+        let target = (0..polygon.len())
+            .filter(|&i| !edges.iter().any(|&(a, b)| crosses(&from, &points[polygon[i]], &a, &b)))
+            .min_by(|&a, &b| {
+                let distance = |i: usize| (points[polygon[i]] - from).norm();
+                distance(a).partial_cmp(&distance(b)).unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .unwrap_or(0);
+
+        let mut bridged = polygon[..=target].to_vec();
+        bridged.extend((0..=hole.len()).map(|i| starts[h] + (rightmost + i) % hole.len()));
+        bridged.extend_from_slice(&polygon[target..]);
+        polygon = bridged;
+    }
+
+    clip_ears(&points, polygon)
+}
+
+
+/// Whether the open segments a-b and c-d cross, where touching at an end point does not count
+fn crosses(a: &Vector2<f64>, b: &Vector2<f64>, c: &Vector2<f64>, d: &Vector2<f64>) -> bool {
+    let side = |p: &Vector2<f64>, q: &Vector2<f64>, r: &Vector2<f64>| (q - p).perp(&(r - p));
+    let shared = [c, d].iter().any(|point| (*point - a).norm() < 1e-12 || (*point - b).norm() < 1e-12);
+
+    !shared && side(a, b, c) * side(a, b, d) < 0.0 && side(c, d, a) * side(c, d, b) < 0.0
+}
+
+
+fn clip_ears(points: &[Vector2<f64>], mut polygon: Vec<usize>) -> Vec<[usize; 3]> {
+    let mut triangles = Vec::new();
+
+    while polygon.len() > 3 {
+        let count = polygon.len();
+        let corner = |i: usize| [polygon[(i + count - 1) % count], polygon[i], polygon[(i + 1) % count]];
+        let convex = |i: usize| {
+            let [a, b, c] = corner(i);
+            (points[b] - points[a]).perp(&(points[c] - points[b])) > 0.0
+        };
+        let ear = |i: usize| {
+            let [a, b, c] = corner(i);
+
+            convex(i) && polygon.iter().all(|&p| {
+                let point = &points[p];
+                // the duplicated points of a bridge may sit on the corners of an ear
+                [a, b, c].iter().any(|&q| (points[q] - point).norm() < 1e-12) ||
+                    !inside_triangle(point, &points[a], &points[b], &points[c])
+            })
+        };
+
+        // a polygon without ears is degenerate, so any convex corner, or any corner at all, is clipped
+        let index = (0..count).find(|&i| ear(i))
+                              .or_else(|| (0..count).find(|&i| convex(i)))
+                              .unwrap_or(0);
+
+        triangles.push(corner(index));
+        polygon.remove(index);
+    }
+
+    if polygon.len() == 3 {
+        triangles.push([polygon[0], polygon[1], polygon[2]]);
+    }
+
+    triangles
+}
+
+
+fn inside_triangle(point: &Vector2<f64>, a: &Vector2<f64>, b: &Vector2<f64>, c: &Vector2<f64>) -> bool {
+    let side = |p: &Vector2<f64>, q: &Vector2<f64>| (q - p).perp(&(point - p));
+
+    side(a, b) >= 0.0 && side(b, c) >= 0.0 && side(c, a) >= 0.0
+}
+
+
+/// Move every point of a ring towards the inside of the shape by the given distance
+fn inset(ring: &[Vector2<f64>], distance: f64) -> Vec<Vector2<f64>> {
+    let count = ring.len();
+    // the inside is to the left of the edges of counter-clockwise outer rings and clockwise holes
+    let left = |a: &Vector2<f64>, b: &Vector2<f64>| {
+        let direction = (b - a).try_normalize(0.0).unwrap_or_else(Vector2::zeros);
+        Vector2::new(-direction.y, direction.x)
+    };
+
+    (0..count).map(|i| {
+        let before = left(&ring[(i + count - 1) % count], &ring[i]);
+        let after = left(&ring[i], &ring[(i + 1) % count]);
+        let miter = (before + after).try_normalize(0.0).unwrap_or(after);
+
+        ring[i] + miter * distance / miter.dot(&after).max(0.5)
+    }).collect()
+}
+
+
+/// Add the front, back, sides, and bevels of a shape extruded along z to the Object
+fn extrude(object: &mut wavefront::Object, outer: &[Vector2<f64>], holes: &[Vec<Vector2<f64>>],
+           options: &TextOptions) {
+
+    let front = options.depth / 2.0;
+    let bevel = options.bevel.max(0.0).min(front);
+    let rings: Vec<&[Vector2<f64>]> = Some(outer).into_iter().chain(holes.iter().map(Vec::as_slice)).collect();
+
+    // the layers of every ring from the front to the back, where the caps are inset by the bevel
+    let layers: Vec<(f64, f64)> = if bevel > 0.0 {
+        vec![(front, bevel), (front - bevel, 0.0), (bevel - front, 0.0), (-front, bevel)]
+    } else {
+        vec![(front, 0.0), (-front, 0.0)]
+    };
+
+    let point_count: usize = rings.iter().map(|ring| ring.len()).sum();
+    let base = object.geometric_vertices.len();
+    let vertex = |layer: usize, point: usize| (base + layer * point_count + point) as i32;
+
+    for &(z, distance) in &layers {
+        for ring in &rings {
+            object.geometric_vertices.extend(inset(ring, distance).iter().map(|point| Vector3::new(point.x, point.y, z)));
+        }
+    }
+
+    let last = layers.len() - 1;
+    // the caps are triangulated where they are inset, since thin triangles of the outline could fold over
+    let cap = inset(outer, layers[0].1);
+    let cap_holes: Vec<Vec<Vector2<f64>>> = holes.iter().map(|hole| inset(hole, layers[0].1)).collect();
+
+    for triangle in triangulate(&cap, &cap_holes) {
+        object.geometric_faces.push(Vector3::new(vertex(0, triangle[0]), vertex(0, triangle[1]),
+                                                 vertex(0, triangle[2])));
+        object.geometric_faces.push(Vector3::new(vertex(last, triangle[0]), vertex(last, triangle[2]),
+                                                 vertex(last, triangle[1])));
+    }
+
+    let mut start = 0;
+
+    for ring in &rings {
+        for i in 0..ring.len() {
+            let (a, b) = (start + i, start + (i + 1) % ring.len());
+
+            for layer in 0..last {
+                let (near_a, near_b) = (vertex(layer, a), vertex(layer, b));
+                let (far_a, far_b) = (vertex(layer + 1, a), vertex(layer + 1, b));
+
+                object.geometric_faces.push(Vector3::new(near_a, far_a, far_b));
+                object.geometric_faces.push(Vector3::new(near_a, far_b, near_b));
+            }
+        }
+
+        start += ring.len();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a font whose only character 'o' is a square with a square hole
+    fn square_font() -> Vec<u8> {
+        let be16 = |value: u16| value.to_be_bytes().to_vec();
+
+        // a clockwise outer square and a counter-clockwise hole with every point on the curve
+        let mut glyph = [be16(2), be16(0), be16(0), be16(100), be16(100), be16(3), be16(7), be16(0)].concat();
+        glyph.extend(vec![1u8; 8]);
+        let xs = [0i16, 0, 100, 100, 25, 75, 75, 25];
+        let ys = [0i16, 100, 100, 0, 25, 25, 75, 75];
+        let deltas = |values: &[i16]| -> Vec<u8> {
+            values.iter().enumerate()
+                  .flat_map(|(i, &value)| (value - if i == 0 { 0 } else { values[i - 1] }).to_be_bytes().to_vec())
+                  .collect()
+        };
+        glyph.extend(deltas(&xs));
+        glyph.extend(deltas(&ys));
+
+        let mut cmap = [be16(0), be16(1), be16(3), be16(1), 12u32.to_be_bytes().to_vec()].concat();
+        // two segments, one mapping 'o' to glyph 1 and the final segment that ends the table
+        cmap.extend([be16(4), be16(32), be16(0), be16(4), be16(0), be16(0), be16(0),
+                     be16(0x6f), be16(0xffff), be16(0), be16(0x6f), be16(0xffff),
+                     be16(1u16.wrapping_sub(0x6f)), be16(1), be16(0), be16(0)].concat());
+
+        let mut head = vec![0; 54];
+        head[18..20].copy_from_slice(&be16(100));
+        let mut hhea = vec![0; 36];
+        hhea[34..36].copy_from_slice(&be16(2));
+        let maxp = [0u32.to_be_bytes().to_vec(), be16(2)].concat();
+        let hmtx = [be16(50), be16(0), be16(120), be16(0)].concat();
+        let loca = [be16(0), be16(0), be16(glyph.len() as u16 / 2)].concat();
+
+        let tables: Vec<(&[u8; 4], Vec<u8>)> = vec![(b"cmap", cmap), (b"glyf", glyph), (b"head", head),
+                                                    (b"hhea", hhea), (b"hmtx", hmtx), (b"loca", loca),
+                                                    (b"maxp", maxp)];
+
+        let mut font = [0x00010000u32.to_be_bytes().to_vec(), be16(tables.len() as u16), vec![0; 6]].concat();
+        let mut offset = 12 + 16 * tables.len();
+
+        for (tag, table) in &tables {
+            font.extend_from_slice(&tag[..]);
+            font.extend([0u32.to_be_bytes(), (offset as u32).to_be_bytes(),
+                         (table.len() as u32).to_be_bytes()].concat());
+            offset += table.len();
+        }
+
+        for (_, table) in tables {
+            font.extend(table);
+        }
+
+        font
+    }
+
+    #[test]
+    fn test_font() {
+        let font = Font::from_bytes(square_font()).unwrap();
+
+        assert_eq!(font.units_per_em, 100);
+        assert_eq!(font.glyph_index('o'), 1);
+        assert_eq!(font.glyph_index('x'), 0);
+        assert_eq!(font.advance(1), 120);
+
+        let outline = font.outline(1).unwrap();
+        assert_eq!(outline.len(), 2);
+        assert_eq!(outline[0][2].position, Vector2::new(100.0, 100.0));
+        assert_eq!(outline[1][0].position, Vector2::new(25.0, 25.0));
+        assert!(font.outline(0).unwrap().is_empty());
+
+        assert!(Font::from_bytes(vec![0; 8]).is_err());
+    }
+
+    #[test]
+    fn test_flatten() {
+        let on = |x, y| OutlinePoint { position: Vector2::new(x, y), on_curve: true };
+        let off = |x, y| OutlinePoint { position: Vector2::new(x, y), on_curve: false };
+
+        assert_eq!(flatten(&[on(0.0, 0.0), on(1.0, 0.0), on(0.0, 1.0)], 4).len(), 3);
+
+        // two control points in a row imply a point halfway between them
+        let curved = flatten(&[on(0.0, 0.0), off(2.0, 0.0), off(2.0, 2.0), on(0.0, 2.0)], 2);
+        assert_eq!(curved.len(), 5);
+        assert!(curved.contains(&Vector2::new(2.0, 1.0)));
+    }
+
+    #[test]
+    fn test_triangulate() {
+        let square = |size: f64| vec![Vector2::new(-size, -size), Vector2::new(size, -size),
+                                      Vector2::new(size, size), Vector2::new(-size, size)];
+        let mut hole = square(1.0);
+        hole.reverse();
+
+        let outer = square(2.0);
+        let points: Vec<Vector2<f64>> = outer.iter().chain(&hole).cloned().collect();
+        let triangles = triangulate(&outer, &[hole]);
+
+        // the area of the triangles is the area of the square without the hole
+        let area: f64 = triangles.iter().map(|t| (points[t[1]] - points[t[0]]).perp(&(points[t[2]] - points[t[0]])))
+                                 .sum::<f64>() / 2.0;
+
+        assert_eq!(triangles.len(), 8);
+        assert!((area - 12.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_text_mesh() {
+        let font = Font::from_bytes(square_font()).unwrap();
+        let options = TextOptions { size: 1.0, depth: 0.2, bevel: 0.02, curve_segments: 4 };
+
+        let mesh = text_mesh(&font, "oo\no", &options).unwrap();
+
+        assert_eq!(mesh.groups.len(), 3);
+        assert_eq!(mesh.groups[1].name, "o");
+        assert_eq!(mesh.normal_faces.len(), mesh.geometric_faces.len());
+
+        // every edge of the closed mesh is shared by exactly two faces in opposite directions
+        let mut edges: HashMap<(i32, i32), i32> = HashMap::new();
+        for face in &mesh.geometric_faces {
+            for i in 0..3 {
+                *edges.entry((face[i], face[(i + 1) % 3])).or_insert(0) += 1;
+            }
+        }
+        assert!(edges.iter().all(|(&(a, b), &count)| count == 1 && edges.get(&(b, a)) == Some(&1)));
+
+        let maximum = mesh.geometric_vertices.iter().fold(Vector3::repeat(f64::MIN), |maximum, v| maximum.zip_map(v, f64::max));
+        let minimum = mesh.geometric_vertices.iter().fold(Vector3::repeat(f64::MAX), |minimum, v| minimum.zip_map(v, f64::min));
+
+        assert!((maximum - Vector3::new(2.2, 1.0, 0.1)).norm() < 1e-9);
+        assert!((minimum - Vector3::new(0.0, -1.2, -0.1)).norm() < 1e-9);
+
+        // the front of the second character faces the camera
+        let front = mesh.groups[1].faces.clone().find(|&face| mesh.geometric_faces[face]
+                                                                   .iter()
+                                                                   .all(|&v| mesh.geometric_vertices[v as usize].z == 0.1))
+                                                 .unwrap();
+        assert_eq!(mesh.normal_vertices[mesh.normal_faces[front][0] as usize], Vector3::z());
+    }
+}
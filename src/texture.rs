@@ -0,0 +1,292 @@
+use std::fs;
+use std::path::Path;
+
+use nalgebra::Vector2;
+
+use compressed;
+use error::MorpheusError;
+use remote;
+
+
+/// Load a texture from the given image file
+///
+/// Images are stored with their first row at the top while texture coordinates
+/// place the origin at the bottom, so the image is flipped vertically. DDS
+/// and KTX2 textures are decompressed on the CPU.
+///
+/// Textures at http and https URLs are downloaded by builds with the http
+/// feature.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate rastermind;
+/// # use rastermind::texture;
+/// # fn main() -> Result<(), rastermind::Error> {
+/// let texture = texture::open("diffuse.png")?;
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn open(filename: &str) -> Result<image::RgbImage, MorpheusError> {
+    let bytes = if remote::is_url(filename) {
+        remote::fetch(filename)?
+    } else {
+        fs::read(filename).map_err(|error| MorpheusError::io(filename, error))?
+    };
+
+    decode(&bytes, filename)
+}
+
+
+/// Load a texture from the encoded image file contained in the given bytes
+///
+/// The format of the image is guessed from its contents.
+///
+/// # Examples
+///
+/// ```ignore
+/// let texture = texture::from_bytes(include_bytes!("diffuse.png")).unwrap();
+/// ```
+///
+pub fn from_bytes(bytes: &[u8]) -> Result<image::RgbImage, MorpheusError> {
+    decode(bytes, "<data>")
+}
+
+
+/// Decode the given bytes of the texture at the given path, naming it in the error if they cannot be decoded
+fn decode(bytes: &[u8], path: &str) -> Result<image::RgbImage, MorpheusError> {
+    let image = if compressed::is_container(bytes) {
+        let rgba = compressed::from_bytes(bytes).map_err(|error| MorpheusError::asset(path, error))?;
+        image::DynamicImage::ImageRgba8(rgba)
+    } else {
+        image::load_from_memory(bytes).map_err(|error| MorpheusError::image(path, error))?
+    };
+
+    Ok(image.flipv().to_rgb())
+}
+
+
+/// Find the texel of the given texture at the given texture coordinate
+///
+/// Coordinates outside of [0, 1] are clamped to the edge of the texture.
+///
+pub fn sample(texture: &image::RgbImage, uv: &Vector2<f64>) -> image::Rgb<u8> {
+    let x = (uv.x * texture.width() as f64).max(0.0).min(texture.width() as f64 - 1.0);
+    let y = (uv.y * texture.height() as f64).max(0.0).min(texture.height() as f64 - 1.0);
+
+    *texture.get_pixel(x as u32, y as u32)
+}
+
+
+/// How the material properties of a physically based material are packed into the channels of one texture
+///
+/// glTF keeps the roughness in the green channel and the metalness in the
+/// blue channel of its metallic roughness texture, and many assets put the
+/// ambient occlusion in the otherwise unused red channel, making a single
+/// occlusion, roughness, and metallic texture.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Packing {
+    /// A single property in every channel, as in a grayscale map
+    Single,
+    /// The roughness in green and the metalness in blue, as in glTF
+    RoughnessMetallic,
+    /// The ambient occlusion in red, the roughness in green, and the metalness in blue
+    OcclusionRoughnessMetallic
+}
+
+
+/// The material properties unpacked from the channels of a texture
+///
+/// Textures that do not hold an ambient occlusion leave it out, and a single
+/// property map is returned as the roughness with no metalness.
+///
+pub struct Material {
+    pub occlusion: Option<image::GrayImage>,
+    pub roughness: image::GrayImage,
+    pub metallic: Option<image::GrayImage>
+}
+
+
+/// The suffixes of the file names conventionally given to textures packing occlusion, roughness, and metalness
+const OCCLUSION_ROUGHNESS_METALLIC_NAMES: [&str; 4] = ["orm", "arm", "occlusionroughnessmetallic", "aorm"];
+
+/// The suffixes of the file names conventionally given to textures packing roughness and metalness
+const ROUGHNESS_METALLIC_NAMES: [&str; 3] = ["metallicroughness", "roughnessmetallic", "rm"];
+
+
+/// Guess how the material properties are packed into the given texture from its file name and its contents
+///
+/// File names that end with a conventional suffix such as "_orm" or
+/// "_metallicRoughness" decide the packing. Otherwise a texture whose
+/// channels are equal everywhere holds a single property, one whose red
+/// channel is the same everywhere holds no ambient occlusion, and any other
+/// texture is taken to pack all three.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate rastermind;
+/// # use rastermind::texture;
+/// # use rastermind::texture::Packing;
+/// # fn main() -> Result<(), rastermind::Error> {
+/// let texture = texture::open("helmet_orm.png")?;
+///
+/// assert_eq!(texture::detect_packing("helmet_orm.png", &texture), Packing::OcclusionRoughnessMetallic);
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn detect_packing(filename: &str, texture: &image::RgbImage) -> Packing {
+    let stem = Path::new(filename).file_stem().and_then(|stem| stem.to_str()).unwrap_or("").to_lowercase();
+    let suffix = stem.rsplit(['_', '-', '.', ' ']).next().unwrap_or("");
+
+    if OCCLUSION_ROUGHNESS_METALLIC_NAMES.contains(&suffix) {
+        return Packing::OcclusionRoughnessMetallic;
+    }
+
+    if ROUGHNESS_METALLIC_NAMES.contains(&suffix) || stem.ends_with("metallicroughness") {
+        return Packing::RoughnessMetallic;
+    }
+
+    let mut pixels = texture.pixels();
+    let first = match pixels.next() {
+        Some(pixel) => *pixel,
+        None => return Packing::Single
+    };
+
+    if texture.pixels().all(|pixel| pixel[0] == pixel[1] && pixel[1] == pixel[2]) {
+        Packing::Single
+    } else if pixels.all(|pixel| pixel[0] == first[0]) {
+        Packing::RoughnessMetallic
+    } else {
+        Packing::OcclusionRoughnessMetallic
+    }
+}
+
+
+/// Split the channels of a texture into the material properties they pack
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate rastermind;
+/// # use rastermind::texture;
+/// # use rastermind::texture::Packing;
+/// # let texture = image::RgbImage::new(4, 4);
+/// let material = texture::unpack(&texture, Packing::RoughnessMetallic);
+/// ```
+///
+pub fn unpack(texture: &image::RgbImage, packing: Packing) -> Material {
+    let channel = |index: usize| {
+        image::ImageBuffer::from_fn(texture.width(), texture.height(), |x, y| {
+            image::Luma([texture.get_pixel(x, y)[index]])
+        })
+    };
+
+    match packing {
+        Packing::Single => Material { occlusion: None, roughness: channel(0), metallic: None },
+        Packing::RoughnessMetallic => Material { occlusion: None, roughness: channel(1), metallic: Some(channel(2)) },
+        Packing::OcclusionRoughnessMetallic => {
+            Material { occlusion: Some(channel(0)), roughness: channel(1), metallic: Some(channel(2)) }
+        }
+    }
+}
+
+
+/// Load a texture of material properties from the given image file and unpack its channels
+///
+/// The packing of the channels is detected with detect_packing, so packed
+/// assets load without splitting them by hand.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate rastermind;
+/// # use rastermind::texture;
+/// # fn main() -> Result<(), rastermind::Error> {
+/// let material = texture::open_material("helmet_metallicRoughness.png")?;
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn open_material(filename: &str) -> Result<Material, MorpheusError> {
+    let texture = open(filename)?;
+
+    Ok(unpack(&texture, detect_packing(filename, &texture)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bytes() {
+        let mut image = image::RgbImage::new(1, 2);
+        image.put_pixel(0, 0, image::Rgb([255, 0, 0]));
+
+        let mut bytes = Vec::new();
+        image::png::PNGEncoder::new(&mut bytes).encode(&image, 1, 2, image::RGB(8)).unwrap();
+
+        let texture = from_bytes(&bytes).unwrap();
+
+        assert_eq!(*texture.get_pixel(0, 1), image::Rgb([255, 0, 0]));
+        assert_eq!(*texture.get_pixel(0, 0), image::Rgb([0, 0, 0]));
+
+        assert!(from_bytes(b"not an image").is_err());
+        assert!(open("/nonexistent/morpheus.png").is_err());
+    }
+
+    #[test]
+    fn test_sample_clamps() {
+        let mut texture = image::RgbImage::new(2, 2);
+        texture.put_pixel(1, 1, image::Rgb([0, 255, 0]));
+
+        assert_eq!(sample(&texture, &Vector2::new(1.0, 1.0)), image::Rgb([0, 255, 0]));
+        assert_eq!(sample(&texture, &Vector2::new(7.5, 0.9)), image::Rgb([0, 255, 0]));
+        assert_eq!(sample(&texture, &Vector2::new(-0.5, 0.0)), image::Rgb([0, 0, 0]));
+    }
+
+    #[test]
+    fn test_detect_packing() {
+        let texture = |pixels: &[[u8; 3]]| {
+            image::RgbImage::from_fn(pixels.len() as u32, 1, |x, _| image::Rgb(pixels[x as usize]))
+        };
+
+        let gray = texture(&[[10, 10, 10], [200, 200, 200]]);
+        let roughness_metallic = texture(&[[0, 10, 250], [0, 200, 0]]);
+        let packed = texture(&[[255, 10, 250], [90, 200, 0]]);
+
+        // the contents decide when the file name does not follow a convention
+        assert_eq!(detect_packing("rough.png", &gray), Packing::Single);
+        assert_eq!(detect_packing("material.png", &roughness_metallic), Packing::RoughnessMetallic);
+        assert_eq!(detect_packing("material.png", &packed), Packing::OcclusionRoughnessMetallic);
+        assert_eq!(detect_packing("empty.png", &image::RgbImage::new(0, 0)), Packing::Single);
+
+        // and the conventional suffixes win over the contents
+        assert_eq!(detect_packing("assets/Helmet_ORM.png", &gray), Packing::OcclusionRoughnessMetallic);
+        assert_eq!(detect_packing("Helmet_metallicRoughness.png", &packed), Packing::RoughnessMetallic);
+        assert_eq!(detect_packing("HelmetMetallicRoughness.jpg", &packed), Packing::RoughnessMetallic);
+    }
+
+    #[test]
+    fn test_unpack() {
+        let texture = image::RgbImage::from_pixel(2, 2, image::Rgb([30, 60, 90]));
+
+        let material = unpack(&texture, Packing::OcclusionRoughnessMetallic);
+        assert_eq!(*material.occlusion.unwrap().get_pixel(1, 1), image::Luma([30]));
+        assert_eq!(*material.roughness.get_pixel(1, 1), image::Luma([60]));
+        assert_eq!(*material.metallic.unwrap().get_pixel(1, 1), image::Luma([90]));
+
+        let material = unpack(&texture, Packing::RoughnessMetallic);
+        assert!(material.occlusion.is_none());
+        assert_eq!(*material.roughness.get_pixel(0, 0), image::Luma([60]));
+
+        let material = unpack(&texture, Packing::Single);
+        assert!(material.occlusion.is_none() && material.metallic.is_none());
+        assert_eq!(*material.roughness.get_pixel(0, 0), image::Luma([30]));
+    }
+}
@@ -0,0 +1,174 @@
+use nalgebra::{Matrix3, Matrix4, Vector3, Vector4};
+
+use shader::{Contributions, Shader};
+use vector;
+use wavefront;
+
+
+/// The order in which the pixels of every 4x4 tile are covered as the opacity grows
+pub const BAYER: [[usize; 4]; 4] = [[0, 8, 2, 10],
+                                [12, 4, 14, 6],
+                                [3, 11, 1, 9],
+                                [15, 7, 13, 5]];
+
+
+/// Whether a surface with the given opacity covers the given pixel when it is dithered with the given pattern
+///
+/// An opacity of 0.5 covers every other pixel like a checkerboard. Patterns
+/// shift the tile of the dither, so two surfaces with the same opacity and
+/// different patterns let each other show through.
+///
+/// # Examples
+///
+/// ```
+/// # use rastermind::transparency::screen_door;
+/// let covered = (0..4).flat_map(|x| (0..4).map(move |y| (x, y)))
+///                     .filter(|&(x, y)| screen_door(x, y, 0.25, 0))
+///                     .count();
+///
+/// assert_eq!(covered, 4);
+/// ```
+///
+pub fn screen_door(x: u32, y: u32, opacity: f64, pattern: usize) -> bool {
+    // the first patterns move the tile by one pixel, so low opacities cover pixels none of the others do
+    let offset_x = (pattern & 1) | (pattern >> 1 & 2);
+    let offset_y = (pattern >> 1 & 1) | (pattern >> 2 & 2);
+    let rank = BAYER[(y as usize + offset_y) % 4][(x as usize + offset_x) % 4];
+
+    (rank as f64 + 0.5) / 16.0 < opacity
+}
+
+
+/// Draw another shader as a transparent surface by discarding its fragments in an ordered dither
+///
+/// Screen-door transparency needs no sorting and keeps the opaque depth
+/// buffer, since every pixel is either drawn or not, so the objects behind
+/// show through the pixels that are left out. It is cheap and suits X-ray
+/// views of assemblies, though the dither pattern is visible up close.
+///
+/// # Examples
+///
+/// ```
+/// # use rastermind::shader;
+/// # use rastermind::transparency::ScreenDoorShader;
+/// let housing = ScreenDoorShader::new(Box::new(shader::GouraudShader::new()), 0.3, 1);
+/// ```
+///
+pub struct ScreenDoorShader<'a> {
+    pub shader: Box<dyn Shader + 'a>,
+    pub opacity: f64,
+    pub pattern: usize,
+    pub varying_screen: Matrix3<f64>
+}
+
+
+impl<'a> ScreenDoorShader<'a> {
+    /// Create a new instance of a ScreenDoorShader that draws the given shader with the given opacity
+    pub fn new(shader: Box<dyn Shader + 'a>, opacity: f64, pattern: usize) -> ScreenDoorShader<'a> {
+        ScreenDoorShader { shader, opacity, pattern, varying_screen: Matrix3::zeros() }
+    }
+}
+
+
+impl<'a> Shader for ScreenDoorShader<'a> {
+    /// Position the vertices with the inner shader and keep their positions on the screen
+    fn vertex(&mut self, coordinates: &wavefront::Object,
+              view_port: &Matrix4<f64>, projection: &Matrix4<f64>,
+              model_view: &Matrix4<f64>, light_vector: &Vector3<f64>,
+              face_index: usize, vertex_index: usize) -> Vector4<f64> {
+
+        let screen_coordinate = self.shader.vertex(coordinates, view_port, projection, model_view,
+                                                   light_vector, face_index, vertex_index);
+        self.varying_screen.set_column(vertex_index, &vector::project_to_3d(&screen_coordinate));
+
+        screen_coordinate
+    }
+
+    /// Return the color of the inner shader
+    fn fragment(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> image::Rgb<u8> {
+        self.shader.fragment(vertex, texture)
+    }
+
+    /// Return the terms of the lighting of the inner shader
+    fn contributions(&self, vertex: Vector3<f64>, texture: &image::RgbImage) -> Contributions {
+        self.shader.contributions(vertex, texture)
+    }
+
+    /// Discard the fragments that the dither leaves out and those the inner shader discards
+    fn discard(&self, vertex: Vector3<f64>) -> bool {
+        let pixel = self.varying_screen * vertex;
+
+        let (x, y) = (pixel.x.round().max(0.0) as u32, pixel.y.round().max(0.0) as u32);
+
+        !screen_door(x, y, self.opacity, self.pattern) || self.shader.discard(vertex)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use render;
+    use shader;
+
+    #[test]
+    fn test_screen_door() {
+        let covered = |opacity: f64, pattern: usize| -> Vec<(u32, u32)> {
+            (0..4).flat_map(|x| (0..4).map(move |y| (x, y)))
+                  .filter(|&(x, y)| screen_door(x, y, opacity, pattern))
+                  .collect()
+        };
+
+        assert!(covered(0.0, 0).is_empty());
+        assert_eq!(covered(1.0, 0).len(), 16);
+        assert_eq!(covered(0.5, 0).len(), 8);
+        assert_eq!(covered(0.5, 3).len(), 8);
+
+        // a half covers every other pixel, and the next pattern covers a different half
+        assert!(covered(0.5, 0).iter().all(|&(x, y)| (x + y) % 2 == 0));
+        assert!(covered(0.5, 1).iter().all(|&(x, y)| (x + y) % 2 == 1));
+
+        // the first four patterns of a quarter cover every pixel once
+        let mut quarters: Vec<(u32, u32)> = (0..4).flat_map(|pattern| covered(0.25, pattern)).collect();
+        quarters.sort();
+        quarters.dedup();
+        assert_eq!(quarters.len(), 16);
+
+        // the pixels covered at one opacity stay covered at every greater opacity
+        let quarter = covered(0.25, 2);
+        assert!(quarter.iter().all(|pixel| covered(0.75, 2).contains(pixel)));
+    }
+
+    #[test]
+    fn test_screen_door_shader() {
+        let square = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\nvt 0 0\nvn 0 0 1\n\
+                                                     f 1/1/1 2/1/1 3/1/1\nf 1/1/1 3/1/1 4/1/1\n",
+                                                   &wavefront::LoadOptions::default()).unwrap();
+        let red = image::Rgb([255, 0, 0]);
+
+        let draw = |opacity: f64| {
+            let mut buffer = image::RgbImage::new(32, 32);
+            let mut zbuffer = vec![-1.0; 32 * 32];
+
+            render::draw_faces(&square, &mut buffer, &image::RgbImage::new(1, 1), &mut zbuffer,
+                               &render::DepthOptions::default(),
+                               &shader::viewport(4, 4, 24, 24, 255), &shader::projection(0.0),
+                               &Matrix4::identity(), &Vector3::z(), |_| -> Box<dyn Shader> {
+                Box::new(ScreenDoorShader::new(Box::new(shader::ColorShader::new(red, false)), opacity, 0))
+            });
+
+            (buffer, zbuffer)
+        };
+
+        let (opaque, _) = draw(1.0);
+        let (half, zbuffer) = draw(0.5);
+
+        let drawn = |buffer: &image::RgbImage| buffer.pixels().filter(|&&pixel| pixel == red).count();
+
+        // half of the pixels of the square are left out, and they keep the depth buffer empty
+        assert!((drawn(&half) as i32 * 2 - drawn(&opaque) as i32).abs() <= 1);
+        assert_eq!(*half.get_pixel(16, 16), red);
+        assert_eq!(*half.get_pixel(17, 16), image::Rgb([0, 0, 0]));
+        assert_eq!(zbuffer[16 * 32 + 17], -1.0);
+    }
+}
@@ -6,8 +6,12 @@ use nalgebra::{Vector2, Vector3, Vector4};
 /// # Examples
 ///
 /// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::{Vector2, Vector3};
+/// # use rastermind::vector::*;
 /// let matrix: Vector3<f64> = Vector3::new(2.0, 4.0, 2.0);
-/// let point: Vector2<f64> = project_to_2d(matrix);
+/// let point: Vector2<f64> = project_to_2d(&matrix);
 /// assert!(point.x == 1.0 && point.y == 2.0);
 /// ```
 ///
@@ -21,8 +25,12 @@ pub fn project_to_2d(vector: &Vector3<f64>) -> Vector2<f64> {
 /// # Examples
 ///
 /// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::{Vector3, Vector4};
+/// # use rastermind::vector::*;
 /// let matrix: Vector4<f64> = Vector4::new(2.0, 4.0, 6.0, 2.0);
-/// let point: Vector3<f64> = project_to_3d(matrix);
+/// let point: Vector3<f64> = project_to_3d(&matrix);
 /// assert!(point.x == 1.0 && point.y == 2.0 && point.z == 3.0);
 /// ```
 ///
@@ -36,8 +44,12 @@ pub fn project_to_3d(vector: &Vector4<f64>) -> Vector3<f64> {
 /// # Examples
 ///
 /// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::{Vector2, Vector3};
+/// # use rastermind::vector::*;
 /// let point: Vector2<f64> = Vector2::new(1.0, 2.0);
-/// let vector: Vector3<f64> = vectorize_to_3d(point);
+/// let vector: Vector3<f64> = vectorize_to_3d(&point);
 /// assert!(vector.x == 1.0 && vector.y == 2.0 && vector.z == 1.0);
 /// ```
 ///
@@ -51,8 +63,12 @@ pub fn vectorize_to_3d(point: &Vector2<f64>) -> Vector3<f64> {
 /// # Examples
 ///
 /// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::{Vector3, Vector4};
+/// # use rastermind::vector::*;
 /// let point: Vector3<f64> = Vector3::new(1.0, 2.0, 3.0);
-/// let vector: Vector4<f64> = vectorize_to_4d(point);
+/// let vector: Vector4<f64> = vectorize_to_4d(&point);
 /// assert!(vector.x == 1.0 && vector.y == 2.0 && vector.z == 3.0 && vector.w == 1.0);
 /// ```
 ///
@@ -0,0 +1,291 @@
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+use nalgebra::{Matrix4, Vector2, Vector3, Vector4};
+
+use lines;
+use render;
+use shader;
+use vector;
+use wavefront;
+
+
+/// The tag at the start of every Middlebury optical flow file
+const FLOW_TAG: &[u8] = b"PIEH";
+
+
+/// The matrices that place an Object on the screen in one frame of an animation
+///
+/// An object that moves between frames is given its model transform as part
+/// of the model view matrix, so the same point of the object can be followed
+/// from one frame to the next.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::velocity::*;
+/// let view = View::lookat(512, 512, 255, &Vector3::new(0.0, 0.0, 3.0), &Vector3::zeros(), &Vector3::y());
+/// ```
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct View {
+    pub view_port: Matrix4<f64>,
+    pub projection: Matrix4<f64>,
+    pub model_view: Matrix4<f64>
+}
+
+
+impl View {
+    /// Create the View of the camera that draw_object uses for a buffer of the given size
+    pub fn lookat(width: u32, height: u32, depth: u32,
+                  eye: &Vector3<f64>, center: &Vector3<f64>, up: &Vector3<f64>) -> View {
+
+        View { view_port: shader::viewport(width / 8, height / 8, width * 3 / 4, height * 3 / 4, depth),
+               projection: shader::projection(-1.0 / (eye - center).norm()),
+               model_view: shader::lookat(eye, center, up) }
+    }
+
+    /// The transform from the coordinates of an Object to the screen
+    pub fn transform(&self) -> Matrix4<f64> {
+        self.view_port * self.projection * self.model_view
+    }
+}
+
+
+/// Find how far every pixel of the current frame moved on the screen since the previous frame
+///
+/// The surface seen at each pixel is found from the depth buffer of the
+/// current frame and projected with the matrices of the previous frame, so
+/// the velocity of a pixel is its position now minus its position then, in
+/// pixels with y pointing up. Pixels where the Object is not drawn have no
+/// velocity.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::velocity::*;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let (previous_eye, eye) = (Vector3::new(-0.1, 0.0, 3.0), Vector3::new(0.0, 0.0, 3.0));
+/// let previous = View::lookat(512, 512, 255, &previous_eye, &Vector3::zeros(), &Vector3::y());
+/// let current = View::lookat(512, 512, 255, &eye, &Vector3::zeros(), &Vector3::y());
+///
+/// let velocity = velocity_buffer(&coordinates, 512, 512, &previous, &current);
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn velocity_buffer(coordinates: &wavefront::Object, width: u32, height: u32,
+                       previous: &View, current: &View) -> Vec<Vector2<f64>> {
+
+    let zbuffer = lines::depth_buffer(coordinates, width, height, &current.view_port, &current.projection,
+                                      &current.model_view);
+
+    let unproject = current.transform().try_inverse().unwrap_or_else(Matrix4::zeros);
+    let reproject = previous.transform() * unproject;
+
+    zbuffer.iter().enumerate().map(|(index, &depth)| {
+        if depth == -1.0 {
+            return Vector2::zeros();
+        }
+
+        let pixel = Vector4::new((index as u32 % width) as f64, (index as u32 / width) as f64, depth, 1.0);
+        let before = vector::project_to_3d(&(reproject * pixel));
+
+        Vector2::new(pixel.x - before.x, pixel.y - before.y)
+    }).collect()
+}
+
+
+/// Find how far every pixel of the current frame moved on the screen since the previous frame for deforming geometry
+///
+/// The previous vertices are the positions of the vertices of the Object in
+/// the previous pose, such as the previous frame of a skinned character, in
+/// the same order as its geometric vertices, so the surface seen at every
+/// pixel is followed through its own deformation as well as through the
+/// motion of the camera and the Object. Vertices without a previous position
+/// are taken not to have moved.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::velocity::*;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+/// # let view = View::lookat(512, 512, 255, &Vector3::new(0.0, 0.0, 3.0), &Vector3::zeros(), &Vector3::y());
+/// # let (previous, current, frame) = (view.clone(), view, 12.0);
+/// # let skin = |frame: f64| {
+/// #     let mut pose = coordinates.clone();
+/// #     pose.geometric_vertices.iter_mut().for_each(|vertex| vertex.y += frame * 0.01);
+/// #     pose
+/// # };
+/// let previous_pose = skin(frame - 1.0);
+/// let current_pose = skin(frame);
+///
+/// let velocity = deforming_velocity_buffer(&current_pose, &previous_pose.geometric_vertices, 512, 512,
+///                                          &previous, &current);
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn deforming_velocity_buffer(coordinates: &wavefront::Object, previous_vertices: &[Vector3<f64>],
+                                 width: u32, height: u32, previous: &View, current: &View) -> Vec<Vector2<f64>> {
+
+    let mut zbuffer = vec![-1.0; (width * height) as usize];
+    let mut velocity = vec![Vector2::zeros(); (width * height) as usize];
+
+    let (now, before) = (current.transform(), previous.transform());
+    let camera_distance = -1.0 / current.projection[(3, 2)];
+    let shader = shader::ColorShader::new(image::Rgb([0, 0, 0]), false);
+
+    for face in &coordinates.geometric_faces {
+        let vertices: Vec<(Vector4<f64>, Vector4<f64>)> = (0..=2).map(|i| {
+            let index = face[i] as usize;
+            let position = coordinates.geometric_vertices[index];
+            let previous_position = previous_vertices.get(index).unwrap_or(&position);
+
+            (now * vector::vectorize_to_4d(&position), before * vector::vectorize_to_4d(previous_position))
+        }).collect();
+
+        let points: Vec<Vector4<f64>> = vertices.iter().map(|&(point, _)| point).collect();
+
+        render::rasterize_triangle(&points, width, height, &render::DepthOptions::default(), camera_distance,
+                                   &shader, |x, y, coordinate, depth| {
+            let index = (x + y * width) as usize;
+
+            if zbuffer[index] >= depth {
+                return;
+            }
+
+            // the previous position of the surface is interpolated in perspective before it is projected
+            let perspective = Vector3::new(coordinate.x / points[0].w, coordinate.y / points[1].w,
+                                           coordinate.z / points[2].w);
+            let perspective = perspective / perspective.sum();
+            let then = vector::project_to_3d(&(0..=2).map(|i| vertices[i].1 * perspective[i]).sum());
+
+            zbuffer[index] = depth;
+            velocity[index] = Vector2::new(x as f64 - then.x, y as f64 - then.y);
+        });
+    }
+
+    velocity
+}
+
+
+/// Save a velocity buffer as a Middlebury optical flow file
+///
+/// Flow files are stored with the first row at the top and y pointing down,
+/// so the rows and the vertical velocities are flipped from the buffer.
+///
+/// # Examples
+///
+/// ```no_run
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use rastermind::velocity::*;
+/// # let velocity = vec![nalgebra::Vector2::zeros(); 512 * 512];
+/// save_flow("frame_0001.flo", &velocity, 512, 512).unwrap();
+/// ```
+///
+pub fn save_flow<P: AsRef<Path>>(path: P, velocity: &[Vector2<f64>], width: u32, height: u32) -> io::Result<()> {
+    let mut bytes = FLOW_TAG.to_vec();
+    bytes.extend_from_slice(&width.to_le_bytes());
+    bytes.extend_from_slice(&height.to_le_bytes());
+
+    for row in velocity.chunks(width as usize).rev() {
+        for motion in row {
+            bytes.extend_from_slice(&(motion.x as f32).to_le_bytes());
+            bytes.extend_from_slice(&(-motion.y as f32).to_le_bytes());
+        }
+    }
+
+    File::create(path)?.write_all(&bytes)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_velocity_buffer() {
+        let square = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\nvt 0 0\nvn 0 0 1\n\
+                                                     f 1/1/1 2/1/1 3/1/1\nf 1/1/1 3/1/1 4/1/1\n",
+                                                   &wavefront::LoadOptions::default()).unwrap();
+        let view = |x: f64| View::lookat(64, 64, 255, &Vector3::new(x, 0.0, 3.0), &Vector3::new(x, 0.0, 0.0),
+                                         &Vector3::y());
+
+        let still = velocity_buffer(&square, 64, 64, &view(0.0), &view(0.0));
+        assert!(still.iter().all(|motion| motion.norm() < 1e-9));
+
+        // the camera moves left, so the square moves right by 0.1 of 24 pixels per unit
+        let moving = velocity_buffer(&square, 64, 64, &view(0.1), &view(0.0));
+        let center = moving[32 * 64 + 32];
+
+        assert!((center.x - 2.4).abs() < 1e-6);
+        assert!(center.y.abs() < 1e-6);
+        assert_eq!(moving[0], Vector2::zeros());
+    }
+
+    #[test]
+    fn test_deforming_velocity_buffer() {
+        let square = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\nvt 0 0\nvn 0 0 1\n\
+                                                     f 1/1/1 2/1/1 3/1/1\nf 1/1/1 3/1/1 4/1/1\n",
+                                                   &wavefront::LoadOptions::default()).unwrap();
+        let view = |x: f64| View::lookat(64, 64, 255, &Vector3::new(x, 0.0, 3.0), &Vector3::new(x, 0.0, 0.0),
+                                         &Vector3::y());
+
+        // without deformation it matches the velocity of the camera moving
+        let rigid = deforming_velocity_buffer(&square, &square.geometric_vertices, 64, 64, &view(0.1), &view(0.0));
+        let moving = velocity_buffer(&square, 64, 64, &view(0.1), &view(0.0));
+
+        assert!(rigid.iter().zip(&moving).all(|(a, b)| (a - b).norm() < 1e-6));
+
+        // the right edge was pulled out, so the pixels move right more the further right they are
+        let mut pose = square.geometric_vertices.clone();
+        pose[1].x = 0.5;
+        pose[2].x = 0.5;
+
+        let stretched = deforming_velocity_buffer(&square, &pose, 64, 64, &view(0.0), &view(0.0));
+        let (left, center, right) = (stretched[32 * 64 + 9], stretched[32 * 64 + 32], stretched[32 * 64 + 52]);
+
+        assert!(left.x.abs() < 0.5);
+        assert!(center.x > 2.0 && right.x > center.x);
+        assert!(center.y.abs() < 1e-6);
+        assert_eq!(stretched[0], Vector2::zeros());
+    }
+
+    #[test]
+    fn test_save_flow() {
+        let mut path = env::temp_dir();
+        path.push("test_save_flow.flo");
+
+        let velocity = [Vector2::new(1.0, 2.0), Vector2::new(3.0, 4.0)];
+        save_flow(&path, &velocity, 1, 2).unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(&bytes[..4], b"PIEH");
+        assert_eq!(bytes.len(), 12 + 2 * 8);
+        // the top row comes first with y pointing down
+        assert_eq!(&bytes[12..16], &3.0f32.to_le_bytes());
+        assert_eq!(&bytes[16..20], &(-4.0f32).to_le_bytes());
+    }
+}
@@ -1,34 +1,270 @@
+use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
+use std::io;
 use std::io::BufReader;
 use std::io::prelude::*;
+use std::ops::Range;
 
-use nalgebra::{Vector2, Vector3};
+use flate2::bufread::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use memmap2::Mmap;
+use nalgebra::{Matrix4, Vector2, Vector3, Vector4};
+
+use error::MorpheusError;
+use occlusion;
+use occlusion::OcclusionOptions;
+use remote;
+use reorder;
+use tangent;
+use weld;
+
+
+/// The axis that points up in the coordinate system of a wavefront file
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpAxis {
+    Y,
+    Z
+}
+
+
+/// The handedness of the coordinate system of a wavefront file
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Handedness {
+    Right,
+    Left
+}
+
+
+/// The orientation conventions a wavefront file was authored with.
+///
+/// The renderer works in a right handed coordinate system with Y pointing up,
+/// which is the default convention. Files exported with Z pointing up or with
+/// a left handed coordinate system are converted when they are loaded.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate nalgebra;
+/// # extern crate rastermind;
+/// # use nalgebra::Vector3;
+/// # use rastermind::wavefront;
+/// let blender = wavefront::AxisConvention::new(wavefront::UpAxis::Z, wavefront::Handedness::Right);
+/// let point = blender.convert(&Vector3::new(0.0, 0.0, 1.0));
+/// assert!(point.y == 1.0);
+/// ```
+///
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AxisConvention {
+    pub up: UpAxis,
+    pub handedness: Handedness
+}
+
+
+impl AxisConvention {
+    /// Create a new convention with the given up axis and handedness
+    pub fn new(up: UpAxis, handedness: Handedness) -> AxisConvention {
+        AxisConvention { up, handedness }
+    }
+
+    /// Convert the given vector from this convention into the Y-up right handed convention
+    pub fn convert(&self, vector: &Vector3<f64>) -> Vector3<f64> {
+        match (self.up, self.handedness) {
+            (UpAxis::Y, Handedness::Right) => *vector,
+            (UpAxis::Y, Handedness::Left) => Vector3::new(vector.x, vector.y, -vector.z),
+            (UpAxis::Z, Handedness::Right) => Vector3::new(vector.x, vector.z, -vector.y),
+            (UpAxis::Z, Handedness::Left) => Vector3::new(vector.x, vector.z, vector.y)
+        }
+    }
+
+    /// Whether the conversion mirrors geometry, which reverses the winding of faces
+    pub fn is_mirrored(&self) -> bool {
+        self.handedness == Handedness::Left
+    }
+}
+
+
+impl Default for AxisConvention {
+    fn default() -> AxisConvention {
+        AxisConvention::new(UpAxis::Y, Handedness::Right)
+    }
+}
+
+
+/// The first two bytes of every gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// The number of bytes at the start of a file that are checked for binary data
+const SNIFF_LENGTH: usize = 512;
+
+
+/// The unit of length a wavefront file was authored in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+    #[default]
+    Meters,
+    Centimeters,
+    Millimeters,
+    Inches,
+    Feet
+}
+
+
+impl Units {
+    /// The number of meters in one of the given unit
+    pub fn to_meters(self) -> f64 {
+        match self {
+            Units::Meters => 1.0,
+            Units::Centimeters => 0.01,
+            Units::Millimeters => 0.001,
+            Units::Inches => 0.0254,
+            Units::Feet => 0.3048
+        }
+    }
+}
+
+
+/// Options applied to an Object while it is loaded from a wavefront file
+///
+/// The units of the file are converted to meters and then multiplied by the
+/// scale so that objects authored at different scales can be composed.
+///
+/// Large files can be memory mapped rather than read through a buffer. The
+/// file must not be modified by another process while it is being loaded.
+///
+/// Files from exporters that wind their faces clockwise or write inward
+/// normals can have their winding or normals flipped. The renderer draws
+/// both sides of every face, so flipping does not hide any faces: the normals
+/// read from a file are what light a face, and the winding decides which way
+/// the normals generated for files without any point, which way the hull of
+/// an outline is pushed out, and which edges are found to be silhouettes.
+/// Normals generated from the winding already point the right way once it is
+/// flipped, so only normals read from the file are flipped.
+///
+/// Files exported with every triangle on its own vertices can have the
+/// vertices within the weld distance of each other merged, which also lets
+/// the normals generated for them be smooth. Objects drawn over and over,
+/// as in animations and previews, can have their faces ordered to transform
+/// fewer vertices.
+///
+/// With ambient occlusion options, the occlusion of every vertex is traced
+/// once the object is loaded, which takes a while for large meshes but costs
+/// nothing to draw afterwards. Tangents for normal maps are generated the
+/// same way once the object is loaded, converted, and scaled.
+///
+/// Strict loading fails on the first line that cannot be parsed, naming it,
+/// instead of skipping it with a warning.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LoadOptions {
+    pub axes: AxisConvention,
+    pub units: Units,
+    pub scale: f64,
+    pub memory_map: bool,
+    pub flip_winding: bool,
+    pub flip_normals: bool,
+    pub ambient_occlusion: Option<OcclusionOptions>,
+    pub tangents: bool,
+    pub weld: Option<f64>,
+    pub optimize_vertex_cache: bool,
+    pub strict: bool
+}
+
+
+impl Default for LoadOptions {
+    fn default() -> LoadOptions {
+        LoadOptions { axes: AxisConvention::default(), units: Units::default(), scale: 1.0,
+                      memory_map: false, flip_winding: false, flip_normals: false, ambient_occlusion: None,
+                      tangents: false, weld: None, optimize_vertex_cache: false, strict: false }
+    }
+}
 
 
 /// A wavefront object containing vertices and faces from a wavefront file.
 ///
 /// An instance of Object will contain the geometric vertices, geometric faces,
-/// texture vertices, and texture faces from a given wavefront object file.
+/// texture vertices, texture faces, normal vertices, and normal faces from a
+/// given wavefront object file along with the smoothing group of every face
+/// and the named groups the faces belong to. Polyline and point elements are
+/// kept as lists of geometric vertex indices. The vertex occlusion is the
+/// fraction of the ambient light that reaches every geometric vertex, and is
+/// empty unless it was traced while loading. The tangents are indexed by the
+/// tangent faces like the normals, with the sign of the bitangent as their w,
+/// and are empty unless they were generated.
 ///
 /// # Examples
 ///
 /// Create a new Object from a given file and iterate over its vertices
 ///
-/// ```
-/// let coordinates = wavefront::Object::new("file.obj");
+/// ```no_run
+/// # extern crate rastermind;
+/// # use rastermind::wavefront;
+/// # fn main() -> Result<(), rastermind::Error> {
+/// let coordinates = wavefront::Object::new("file.obj")?;
 ///
 /// for geometric_vertex in coordinates.geometric_vertices {
 ///     // do something with the vertices
 /// }
+/// # Ok(())
+/// # }
 /// ```
 ///
+#[derive(Clone, Debug, Default)]
 pub struct Object {
     pub geometric_vertices: Vec<Vector3<f64>>,
     pub geometric_faces: Vec<Vector3<i32>>,
     pub texture_vertices: Vec<Vector2<f64>>,
     pub texture_faces: Vec<Vector3<i32>>,
     pub normal_vertices: Vec<Vector3<f64>>,
-    pub normal_faces: Vec<Vector3<i32>>
+    pub normal_faces: Vec<Vector3<i32>>,
+    pub smoothing_groups: Vec<u32>,
+    pub groups: Vec<Group>,
+    pub lines: Vec<Vec<i32>>,
+    pub points: Vec<i32>,
+    pub material_libraries: Vec<String>,
+    pub warnings: Vec<Warning>,
+    pub vertex_occlusion: Vec<f64>,
+    pub tangent_vertices: Vec<Vector4<f64>>,
+    pub tangent_faces: Vec<Vector3<i32>>
+}
+
+
+/// A malformed line that was skipped while parsing a wavefront file
+///
+/// Lines skipped for an index that is out of range keep the index as it was
+/// written in the file.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Warning {
+    pub line: usize,
+    pub message: String,
+    pub index: Option<i64>
+}
+
+
+impl Warning {
+    /// The error of a strict load of the file at the given path that failed on this line
+    pub fn to_error(&self, path: &str) -> MorpheusError {
+        match self.index {
+            Some(index) => MorpheusError::InvalidIndex { path: path.to_string(), line: self.line, index },
+            None => MorpheusError::Parse { path: path.to_string(), line: self.line, message: self.message.clone() }
+        }
+    }
+}
+
+
+/// A named sub-mesh of an Object parsed from an 'o' or 'g' line
+///
+/// The faces of a group are the range of face indices between its line and
+/// the next 'o' or 'g' line.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Group {
+    pub name: String,
+    pub faces: Range<usize>
 }
 
 
@@ -37,98 +273,1430 @@ impl Object {
     ///
     /// The geometric vertices are parsed from all lines that begin with
     /// 'v' while the texture vertices are parsed from all lines that
-    /// begin with 'vt'. The faces are parsed from lines that begin
-    /// with 'f'. Faces are assumed to be in the form of a triangle triplet
-    /// such as 1100/1200/1300 1000/900/800 100/200/300 where 1100, 1000,
-    /// and 100 are the geometric faces and 1200, 900, and 200 are the
-    /// texture faces.
+    /// begin with 'vt' and the vertex normals from lines that begin with
+    /// 'vn'. The faces are parsed from lines that begin with 'f'. Faces are
+    /// assumed to be in the form of a triangle triplet such as
+    /// 1100/1200/1300 1000/900/800 100/200/300 where 1100, 1000, and 100 are
+    /// the geometric faces, 1200, 900, and 200 are the texture faces, and
+    /// 1300, 800, and 300 are the normal faces. The texture and normal
+    /// indices can be left out, as in 1100, 1100/1200, and 1100//1300. Faces
+    /// without texture coordinates sample the middle of the texture, so a
+    /// model without any is drawn in a single color of its texture, such as
+    /// a plain white texture. Quads and polygons with more vertices are split
+    /// into triangles by clipping ears off them in their own plane, so
+    /// concave polygons are split into triangles that stay inside them.
+    ///
+    /// Lines that begin with 's' set the smoothing group of the faces that
+    /// follow them, where 'off' and 0 disable smoothing. When the file has
+    /// no vertex normals, or any face leaves out its normal indices, the
+    /// normals of every face are generated from the smoothing groups.
+    ///
+    /// Lines that begin with 'o' or 'g' start a new named group. Faces that
+    /// come before the first group are placed in a group named 'default'. A
+    /// file without any 'o' or 'g' lines has no groups, and its faces are
+    /// treated as the 'default' group when groups are retained.
+    ///
+    /// Lines that begin with 'l' are parsed into polylines and lines that begin
+    /// with 'p' into points. Only their geometric vertex indices are kept.
+    ///
+    /// The paths of the material libraries named on 'mtllib' lines are kept as
+    /// they are written, to be resolved with assets::SearchPaths.
+    ///
+    /// Comments, blank lines, and unknown statements are ignored. Lines that
+    /// cannot be parsed are skipped and reported in the warnings of the Object.
+    ///
+    /// Files compressed with gzip, such as file.obj.gz, are decompressed while
+    /// they are read, and so are zlib streams and raw deflate data. Gzip and
+    /// zlib are recognized by their headers. Raw deflate data has no header,
+    /// so it is assumed when the start of the file is binary, which wavefront
+    /// text never is.
+    ///
+    /// Files at http and https URLs are downloaded by builds with the http
+    /// feature, and are never memory mapped.
     ///
     /// # Examples
     ///
     /// Create a new Object from a given file and iterate over its faces.
     ///
-    /// ```
-    /// let coordinates = wavefront::Object::new("file.obj");
+    /// ```no_run
+    /// # extern crate rastermind;
+    /// # use rastermind::wavefront;
+    /// let coordinates = wavefront::Object::new("file.obj").unwrap();
     ///
     /// for geometric_face in coordinates.geometric_faces {
     ///     // do something with the faces
     /// }
     /// ```
     ///
-    pub fn new(filename: &str) -> Object {
-        let file = BufReader::new(File::open(filename).unwrap());
-        let mut geometric_vertices: Vec<Vector3<f64>> = Vec::new();
-        let mut geometric_faces: Vec<Vector3<i32>> = Vec::new();
-        let mut texture_vertices: Vec<Vector2<f64>> = Vec::new();
-        let mut texture_faces: Vec<Vector3<i32>> = Vec::new();
+    pub fn new(filename: &str) -> Result<Object, MorpheusError> {
+        Object::with_options(filename, &LoadOptions::default())
+    }
+
+    /// Create an instance of Object from the given filename with the given load options.
+    ///
+    /// # Examples
+    ///
+    /// Load an object exported from a Z-up application.
+    ///
+    /// ```no_run
+    /// # extern crate rastermind;
+    /// # use rastermind::wavefront;
+    /// # fn main() -> Result<(), rastermind::Error> {
+    /// let options = wavefront::LoadOptions {
+    ///     axes: wavefront::AxisConvention::new(wavefront::UpAxis::Z, wavefront::Handedness::Right),
+    ///     units: wavefront::Units::Centimeters,
+    ///     ..Default::default()
+    /// };
+    /// let coordinates = wavefront::Object::with_options("file.obj", &options)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    pub fn with_options(filename: &str, options: &LoadOptions) -> Result<Object, MorpheusError> {
+        if remote::is_url(filename) {
+            let bytes = remote::fetch(filename)?;
+
+            return parse_decompressed(&bytes[..]).map_err(|error| MorpheusError::io(filename, error))?
+                                                 .check(filename, options);
+        }
+
+        let file = File::open(filename).map_err(|error| MorpheusError::io(filename, error))?;
+
+        let object = if options.memory_map {
+            let map = unsafe { Mmap::map(&file) }.map_err(|error| MorpheusError::io(filename, error))?;
+            parse_decompressed(&map[..])
+        } else {
+            parse_decompressed(BufReader::new(file))
+        };
+
+        object.map_err(|error| MorpheusError::io(filename, error))?.check(filename, options)
+    }
+
+    /// Create an instance of Object from the wavefront data in the given reader
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate rastermind;
+    /// # use rastermind::wavefront;
+    /// # use std::fs::File;
+    /// # use std::io::BufReader;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let file = BufReader::new(File::open("file.obj")?);
+    /// let coordinates = wavefront::Object::from_reader(file, &wavefront::LoadOptions::default())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    pub fn from_reader<R: BufRead>(reader: R, options: &LoadOptions) -> Result<Object, MorpheusError> {
+        parse_decompressed(reader).map_err(MorpheusError::data)?.check("<data>", options)
+    }
+
+    /// Create an instance of Object from the wavefront data in the given bytes
+    ///
+    /// Only compressed bytes can fail to load.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate rastermind;
+    /// # use rastermind::wavefront;
+    /// let data = b"v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\nf 1/1/1 2/1/1 3/1/1\n";
+    /// let coordinates = wavefront::Object::from_bytes(data, &wavefront::LoadOptions::default()).unwrap();
+    /// ```
+    ///
+    pub fn from_bytes(bytes: &[u8], options: &LoadOptions) -> Result<Object, MorpheusError> {
+        Object::from_reader(bytes, options)
+    }
+
+    /// Fail a strict load of a parsed Object on its first warning, or prepare it with the given load options
+    fn check(self, path: &str, options: &LoadOptions) -> Result<Object, MorpheusError> {
+        match self.warnings.first() {
+            Some(warning) if options.strict => Err(warning.to_error(path)),
+            _ => Ok(self.prepare(options))
+        }
+    }
+
+    /// Generate missing normals and apply the given load options to a parsed Object
+    fn prepare(mut self, options: &LoadOptions) -> Object {
+        if let Some(epsilon) = options.weld {
+            self.weld(epsilon);
+        }
+
+        if options.flip_winding {
+            self.flip_winding();
+        }
+
+        if self.normal_vertices.is_empty() {
+            self.generate_normals();
+        } else if options.flip_normals {
+            self.flip_normals();
+        }
+
+        self.convert_axes(&options.axes);
+        self.scale(options.units.to_meters() * options.scale);
+
+        if let Some(ref occlusion_options) = options.ambient_occlusion {
+            self.vertex_occlusion = occlusion::vertex_occlusion(&self, occlusion_options);
+        }
+
+        if options.tangents {
+            self.generate_tangents();
+        }
+
+        if options.optimize_vertex_cache {
+            self.optimize_vertex_cache();
+        }
+
+        self
+    }
+
+    /// Convert the vertices and normals of the object from the given convention
+    ///
+    /// Conversions that change the handedness mirror the object, so the winding
+    /// of every face is reversed to keep the faces pointing outwards.
+    ///
+    pub fn convert_axes(&mut self, convention: &AxisConvention) {
+        for vertex in self.geometric_vertices.iter_mut() {
+            *vertex = convention.convert(vertex);
+        }
+
+        for normal in self.normal_vertices.iter_mut() {
+            *normal = convention.convert(normal);
+        }
+
+        let sign = if convention.is_mirrored() { -1.0 } else { 1.0 };
+
+        for tangent in self.tangent_vertices.iter_mut() {
+            let converted = convention.convert(&tangent.xyz());
+            *tangent = Vector4::new(converted.x, converted.y, converted.z, tangent.w * sign);
+        }
+
+        if convention.is_mirrored() {
+            self.flip_winding();
+        }
+    }
+
+    /// Reverse the winding of every face, which turns the faces to point the other way
+    pub fn flip_winding(&mut self) {
+        for faces in [&mut self.geometric_faces, &mut self.texture_faces,
+                      &mut self.normal_faces, &mut self.tangent_faces].iter_mut() {
+            for face in faces.iter_mut() {
+                face.swap_rows(1, 2);
+            }
+        }
+    }
+
+    /// Point every vertex normal the other way
+    pub fn flip_normals(&mut self) {
+        for normal in self.normal_vertices.iter_mut() {
+            *normal = -*normal;
+        }
+    }
+
+    /// Generate vertex normals from the faces and smoothing groups of the object
+    ///
+    /// Faces that share a vertex and a smoothing group share an area weighted
+    /// normal at that vertex so curved regions shade smoothly. Faces outside of
+    /// any smoothing group use their own face normal so hard edges stay sharp.
+    ///
+    pub fn generate_normals(&mut self) {
+        let face_normals: Vec<Vector3<f64>> = self.geometric_faces.iter().map(|face| {
+            let v0 = self.geometric_vertices[face[0] as usize];
+            let v1 = self.geometric_vertices[face[1] as usize];
+            let v2 = self.geometric_vertices[face[2] as usize];
+
+            (v1 - v0).cross(&(v2 - v0))
+        }).collect();
+
+        let mut smooth_normals: HashMap<(i32, u32), Vector3<f64>> = HashMap::new();
+
+        for (face_index, face) in self.geometric_faces.iter().enumerate() {
+            let group = self.smoothing_groups[face_index];
+
+            if group != 0 {
+                for vertex_index in face.iter() {
+                    *smooth_normals.entry((*vertex_index, group))
+                                   .or_insert_with(Vector3::zeros) += face_normals[face_index];
+                }
+            }
+        }
+
         let mut normal_vertices: Vec<Vector3<f64>> = Vec::new();
         let mut normal_faces: Vec<Vector3<i32>> = Vec::new();
+        let mut normal_indices: HashMap<(i32, u32), i32> = HashMap::new();
+
+        for (face_index, face) in self.geometric_faces.iter().enumerate() {
+            let group = self.smoothing_groups[face_index];
+
+            if group == 0 {
+                normal_vertices.push(face_normals[face_index].try_normalize(0.0)
+                                                             .unwrap_or_else(Vector3::zeros));
+
+                let normal_index = normal_vertices.len() as i32 - 1;
+                normal_faces.push(Vector3::new(normal_index, normal_index, normal_index));
+            } else {
+                normal_faces.push(face.map(|vertex_index| {
+                    *normal_indices.entry((vertex_index, group)).or_insert_with(|| {
+                        normal_vertices.push(smooth_normals[&(vertex_index, group)]
+                                             .try_normalize(0.0)
+                                             .unwrap_or_else(Vector3::zeros));
+
+                        normal_vertices.len() as i32 - 1
+                    })
+                }));
+            }
+        }
+
+        self.normal_vertices = normal_vertices;
+        self.normal_faces = normal_faces;
+    }
+
+    /// Merge the vertices, texture vertices, and normals that are within the given distance of each other
+    ///
+    /// Each kind of vertex is welded on its own and the faces, lines, and
+    /// points are indexed into what is left, so the seams of the texture and
+    /// the hard edges of the normals stay where they are while the positions
+    /// along them are shared. Faces that welding collapses are kept.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate rastermind;
+    /// # use rastermind::wavefront;
+    /// # fn main() -> Result<(), rastermind::Error> {
+    /// let mut coordinates = wavefront::Object::new("exploded.obj")?;
+    /// coordinates.weld(1e-6);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    pub fn weld(&mut self, epsilon: f64) {
+        let (geometric_vertices, geometric) = weld::weld(&self.geometric_vertices, epsilon);
+        let uvs: Vec<Vector3<f64>> = self.texture_vertices.iter().map(|uv| Vector3::new(uv.x, uv.y, 0.0)).collect();
+        let (texture_vertices, texture) = weld::weld(&uvs, epsilon);
+        let (normal_vertices, normal) = weld::weld(&self.normal_vertices, epsilon);
+
+        let remap = |faces: &mut Vec<Vector3<i32>>, remap: &[i32]| {
+            for face in faces.iter_mut() {
+                *face = face.map(|index| remap.get(index as usize).cloned().unwrap_or(index));
+            }
+        };
+        remap(&mut self.geometric_faces, &geometric);
+        remap(&mut self.texture_faces, &texture);
+        remap(&mut self.normal_faces, &normal);
+
+        for index in self.lines.iter_mut().flat_map(|line| line.iter_mut()).chain(self.points.iter_mut()) {
+            *index = geometric[*index as usize];
+        }
+
+        if !self.vertex_occlusion.is_empty() {
+            let mut occlusion = vec![1.0; geometric_vertices.len()];
+            for (vertex, &welded) in geometric.iter().enumerate().rev() {
+                occlusion[welded as usize] = self.vertex_occlusion[vertex];
+            }
+            self.vertex_occlusion = occlusion;
+        }
+
+        self.geometric_vertices = geometric_vertices;
+        self.texture_vertices = texture_vertices.iter().map(|uv| Vector2::new(uv.x, uv.y)).collect();
+        self.normal_vertices = normal_vertices;
+    }
+
+    /// Order the faces so the vertices they share stay in the vertex cache, returning the faces in their new order
+    ///
+    /// The faces of every group are only ordered among themselves, so the
+    /// groups keep their faces. Data kept beside the faces, such as the
+    /// materials of a Mesh, can be put in the same order with the returned
+    /// indices of the faces as they were.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate rastermind;
+    /// # use rastermind::wavefront;
+    /// # use rastermind::mesh::Mesh;
+    /// # fn main() -> Result<(), rastermind::Error> {
+    /// # let object = wavefront::Object::from_bytes(b"v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n", &Default::default())?;
+    /// # let mut mesh = Mesh { object, materials: Vec::new(), face_materials: vec![None] };
+    /// let order = mesh.object.optimize_vertex_cache();
+    /// mesh.face_materials = order.iter().map(|&face| mesh.face_materials[face]).collect();
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    pub fn optimize_vertex_cache(&mut self) -> Vec<usize> {
+        let mut bounds = vec![0, self.geometric_faces.len()];
+        bounds.extend(self.groups.iter().flat_map(|group| vec![group.faces.start, group.faces.end]));
+        bounds.sort_unstable();
+        bounds.dedup();
+
+        let order: Vec<usize> = bounds.windows(2).flat_map(|range| {
+            reorder::vertex_cache_order(&self.geometric_faces[range[0]..range[1]]).into_iter()
+                                                                                  .map(move |face| face + range[0])
+        }).collect();
+
+        let reordered = |faces: &Vec<Vector3<i32>>| order.iter().map(|&face| faces[face]).collect();
+        self.geometric_faces = reordered(&self.geometric_faces);
+        self.texture_faces = reordered(&self.texture_faces);
+        self.normal_faces = reordered(&self.normal_faces);
+
+        if !self.tangent_faces.is_empty() {
+            self.tangent_faces = reordered(&self.tangent_faces);
+        }
+
+        self.smoothing_groups = order.iter().map(|&face| self.smoothing_groups[face]).collect();
+
+        order
+    }
+
+    /// Generate the tangents of every face corner for normal mapping, as MikkTSpace does
+    ///
+    /// The tangents follow the texture coordinates and normals the object has
+    /// now, and are transformed along with it afterwards.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate rastermind;
+    /// # use rastermind::wavefront;
+    /// # fn main() -> Result<(), rastermind::Error> {
+    /// let mut coordinates = wavefront::Object::new("african_head.obj")?;
+    /// coordinates.generate_tangents();
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    pub fn generate_tangents(&mut self) {
+        let (tangent_vertices, tangent_faces) = tangent::tangents(self);
+
+        self.tangent_vertices = tangent_vertices;
+        self.tangent_faces = tangent_faces;
+    }
+
+    /// Find the group with the given name
+    pub fn group(&self, name: &str) -> Option<&Group> {
+        self.groups.iter().find(|group| group.name == name)
+    }
+
+    /// Keep only the faces of the groups for which the given predicate returns true
+    ///
+    /// # Examples
+    ///
+    /// Hide the wheels of a car.
+    ///
+    /// ```no_run
+    /// # extern crate rastermind;
+    /// # use rastermind::wavefront;
+    /// # fn main() -> Result<(), rastermind::Error> {
+    /// let mut coordinates = wavefront::Object::new("car.obj")?;
+    /// coordinates.retain_groups(|name| !name.starts_with("wheel"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    pub fn retain_groups<F>(&mut self, mut keep: F) where F: FnMut(&str) -> bool {
+        let mut retained = Object::default();
+        let ungrouped = self.groups.is_empty();
+        let groups = if ungrouped {
+            vec![Group { name: String::from("default"), faces: 0..self.geometric_faces.len() }]
+        } else {
+            self.groups.clone()
+        };
+
+        for group in groups.iter().filter(|group| keep(&group.name)) {
+            let start = retained.geometric_faces.len();
+
+            retained.geometric_faces.extend_from_slice(&self.geometric_faces[group.faces.clone()]);
+            retained.texture_faces.extend_from_slice(&self.texture_faces[group.faces.clone()]);
+            retained.normal_faces.extend_from_slice(&self.normal_faces[group.faces.clone()]);
+            if !self.tangent_faces.is_empty() {
+                retained.tangent_faces.extend_from_slice(&self.tangent_faces[group.faces.clone()]);
+            }
+            retained.smoothing_groups.extend_from_slice(&self.smoothing_groups[group.faces.clone()]);
+            retained.groups.push(Group { name: group.name.clone(),
+                                         faces: start..retained.geometric_faces.len() });
+        }
+
+        self.geometric_faces = retained.geometric_faces;
+        self.texture_faces = retained.texture_faces;
+        self.normal_faces = retained.normal_faces;
+        self.tangent_faces = retained.tangent_faces;
+        self.smoothing_groups = retained.smoothing_groups;
+        self.groups = if ungrouped { Vec::new() } else { retained.groups };
+    }
+
+    /// Create a new Object that contains only the faces of the group with the given name
+    pub fn sub_mesh(&self, name: &str) -> Option<Object> {
+        if self.group(name).is_none() && !(self.groups.is_empty() && name == "default") {
+            return None;
+        }
+
+        let mut sub_mesh = self.clone();
+        sub_mesh.retain_groups(|group_name| group_name == name);
+
+        Some(sub_mesh)
+    }
+
+    /// Find the area weighted normal of the faces around every geometric vertex, ignoring smoothing groups
+    ///
+    /// Vertices that no face uses have a zero normal.
+    ///
+    pub fn vertex_normals(&self) -> Vec<Vector3<f64>> {
+        let mut normals = vec![Vector3::zeros(); self.geometric_vertices.len()];
+
+        for face in &self.geometric_faces {
+            let (a, b, c) = (face[0] as usize, face[1] as usize, face[2] as usize);
+            let normal = (self.geometric_vertices[b] - self.geometric_vertices[a])
+                .cross(&(self.geometric_vertices[c] - self.geometric_vertices[a]));
+
+            (0..=2).for_each(|i| normals[face[i] as usize] += normal);
+        }
+
+        normals.iter().map(|normal| normal.try_normalize(0.0).unwrap_or_else(Vector3::zeros)).collect()
+    }
+
+    /// Find the corners of the axis aligned box around the vertices of the object, or None without vertices
+    pub fn bounds(&self) -> Option<(Vector3<f64>, Vector3<f64>)> {
+        let first = *self.geometric_vertices.first()?;
+
+        Some(self.geometric_vertices.iter().fold((first, first), |(minimum, maximum), vertex| {
+            (minimum.zip_map(vertex, f64::min), maximum.zip_map(vertex, f64::max))
+        }))
+    }
+
+    /// Uniformly scale the vertices of the object by the given factor
+    pub fn scale(&mut self, factor: f64) {
+        for vertex in self.geometric_vertices.iter_mut() {
+            *vertex *= factor;
+        }
+    }
+
+    /// Center the object on the origin and scale it uniformly so its longest side spans from -1 to 1
+    ///
+    /// Objects without vertices, or whose vertices are all at one point, are
+    /// only centered.
+    ///
+    pub fn normalize(&mut self) {
+        let (minimum, maximum) = match self.bounds() {
+            Some(bounds) => bounds,
+            None => return
+        };
+
+        let center = (minimum + maximum) / 2.0;
+        let extent = (maximum - minimum).max() / 2.0;
+        let factor = if extent > 0.0 { 1.0 / extent } else { 1.0 };
+
+        for vertex in self.geometric_vertices.iter_mut() {
+            *vertex = (*vertex - center) * factor;
+        }
+    }
+
+    /// Transform the vertices and normals of the object by the given matrix
+    ///
+    /// Normals are transformed by the inverse transpose so they stay
+    /// perpendicular to the faces under non-uniform scaling, and transforms
+    /// that mirror the object reverse the winding of every face.
+    ///
+    pub fn transform(&mut self, matrix: &Matrix4<f64>) {
+        for vertex in self.geometric_vertices.iter_mut() {
+            *vertex = matrix.transform_point(&(*vertex).into()).coords;
+        }
+
+        let linear = matrix.fixed_slice::<nalgebra::U3, nalgebra::U3>(0, 0).into_owned();
+        let normal_matrix = linear.try_inverse().map_or(linear, |inverse| inverse.transpose());
+
+        for normal in self.normal_vertices.iter_mut() {
+            *normal = normal_matrix * *normal;
+        }
+
+        let sign = linear.determinant().signum();
+
+        for tangent in self.tangent_vertices.iter_mut() {
+            let transformed = (linear * tangent.xyz()).try_normalize(0.0).unwrap_or_else(|| tangent.xyz());
+            *tangent = Vector4::new(transformed.x, transformed.y, transformed.z, tangent.w * sign);
+        }
 
-        for line in file.lines().map(|l| l.unwrap()) {
-            if line.starts_with("v ") {
-                let v_coordinates = line.split_at(2).1
-                                        .split_whitespace()
-                                        .map(|n| n.parse().unwrap())
-                                        .collect::<Vec<f64>>();
-
-                geometric_vertices.push(Vector3::new(v_coordinates[0],
-                                                     v_coordinates[1],
-                                                     v_coordinates[2]));
-            }
-            else if line.starts_with("vt ") {
-                let vt_coordinates = line.split_at(3).1
-                                         .split_whitespace()
-                                         .map(|n| n.parse().unwrap())
-                                         .collect::<Vec<f64>>();
-
-                texture_vertices.push(Vector2::new(vt_coordinates[0], vt_coordinates[1]));
-            }
-            else if line.starts_with("vn ") {
-                let vn_coordinates = line.split_at(3).1
-                                         .split_whitespace()
-                                         .map(|n| n.parse().unwrap())
-                                         .collect::<Vec<f64>>();
-
-                normal_vertices.push(Vector3::new(vn_coordinates[0],
-                                                  vn_coordinates[1],
-                                                  vn_coordinates[2]));
-            }
-            else if line.starts_with("f ") {
-                let f_coordinates = line.split_at(2).1
-                                        .split(|c| c == '/' || c == ' ')
-                                        .map(|n| n.parse().unwrap())
-                                        .collect::<Vec<i32>>();
-
-                geometric_faces.push(Vector3::new(f_coordinates[0] - 1,
-                                                  f_coordinates[3] - 1,
-                                                  f_coordinates[6] - 1));
-
-                texture_faces.push(Vector3::new(f_coordinates[1] - 1,
-                                                f_coordinates[4] - 1,
-                                                f_coordinates[7] - 1));
-
-                normal_faces.push(Vector3::new(f_coordinates[2] - 1,
-                                               f_coordinates[5] - 1,
-                                               f_coordinates[8] - 1));
-
-                if f_coordinates.len() == 12 {
-                    geometric_faces.push(Vector3::new(f_coordinates[0] - 1,
-                                                      f_coordinates[6] - 1,
-                                                      f_coordinates[9] - 1));
-
-                    texture_faces.push(Vector3::new(f_coordinates[1] - 1,
-                                                    f_coordinates[7] - 1,
-                                                    f_coordinates[10] - 1));
-
-                    normal_faces.push(Vector3::new(f_coordinates[2] - 1,
-                                                   f_coordinates[8] - 1,
-                                                   f_coordinates[11] - 1));
+        if linear.determinant() < 0.0 {
+            for faces in [&mut self.geometric_faces, &mut self.texture_faces,
+                          &mut self.normal_faces, &mut self.tangent_faces].iter_mut() {
+                for face in faces.iter_mut() {
+                    face.swap_rows(1, 2);
                 }
             }
         }
+    }
+
+    /// Create an inverted hull of the object for drawing outlines
+    ///
+    /// Every vertex is pushed outwards by the given thickness along the average
+    /// of the normals of the faces that use it, which keeps the hull closed at
+    /// hard edges, and the winding and normals of every face are reversed so
+    /// the faces of the hull that point away from the camera are its front.
+    ///
+    pub fn inverted_hull(&self, thickness: f64) -> Object {
+        let mut hull = self.clone();
+        let mut directions = vec![Vector3::zeros(); self.geometric_vertices.len()];
+
+        for (face, normals) in self.geometric_faces.iter().zip(&self.normal_faces) {
+            for i in 0..3 {
+                directions[face[i] as usize] += self.normal_vertices[normals[i] as usize].normalize();
+            }
+        }
+
+        for (vertex, direction) in hull.geometric_vertices.iter_mut().zip(&directions) {
+            if direction.norm() > 0.0 {
+                *vertex += direction.normalize() * thickness;
+            }
+        }
+
+        for normal in hull.normal_vertices.iter_mut() {
+            *normal = -*normal;
+        }
+
+        for faces in [&mut hull.geometric_faces, &mut hull.texture_faces,
+                      &mut hull.normal_faces].iter_mut() {
+            for face in faces.iter_mut() {
+                face.swap_rows(1, 2);
+            }
+        }
+
+        hull
+    }
+}
+
+
+/// Parser state for reading a wavefront file line by line
+struct ObjectParser {
+    object: Object,
+    smoothing_group: u32,
+    group_name: String,
+    group_start: usize,
+    grouped: bool,
+    missing_normals: bool,
+    missing_texture: bool
+}
+
+
+impl ObjectParser {
+    fn new() -> ObjectParser {
+        ObjectParser { object: Object::default(), smoothing_group: 0,
+                       group_name: String::from("default"), group_start: 0, grouped: false,
+                       missing_normals: false, missing_texture: false }
+    }
+
+    /// Parse every line of the given reader into an Object
+    fn parse<R: BufRead>(mut self, mut reader: R) -> io::Result<Object> {
+        let mut buffer = Vec::new();
+        let mut line_number = 0;
+
+        while reader.read_until(b'\n', &mut buffer)? > 0 {
+            line_number += 1;
+
+            let line = String::from_utf8_lossy(&buffer);
+            let tokens: Vec<&str> = line.split('#').next().unwrap().split_whitespace().collect();
+
+            if let Err(error) = self.parse_line(&tokens) {
+                let index = match error { LineError::OutOfRange(index) => Some(index), _ => None };
+                self.object.warnings.push(Warning { line: line_number, message: error.to_string(), index });
+            }
+
+            buffer.clear();
+        }
+
+        if self.grouped {
+            self.close_group();
+        }
+
+        // faces without normals cannot be shaded alongside those with them, so the normals are generated for all
+        if self.missing_normals {
+            self.object.normal_vertices.clear();
+            self.object.normal_faces.clear();
+        }
+
+        // faces without texture coordinates all sample the middle of the texture
+        if self.missing_texture {
+            let middle = self.object.texture_vertices.len() as i32;
+            self.object.texture_vertices.push(Vector2::new(0.5, 0.5));
+
+            for face in self.object.texture_faces.iter_mut() {
+                *face = face.map(|index| if index < 0 { middle } else { index });
+            }
+        }
+
+        Ok(self.object)
+    }
+
+    /// Parse the tokens of a single line with comments and whitespace removed
+    fn parse_line(&mut self, tokens: &[&str]) -> Result<(), LineError> {
+        let (keyword, arguments) = match tokens.split_first() {
+            Some((keyword, arguments)) => (*keyword, arguments),
+            None => return Ok(())
+        };
+
+        match keyword {
+            "v" => {
+                let v_coordinates = parse_floats(arguments, 3)?;
+                self.object.geometric_vertices.push(Vector3::new(v_coordinates[0],
+                                                                 v_coordinates[1],
+                                                                 v_coordinates[2]));
+            }
+            "vt" => {
+                let vt_coordinates = parse_floats(arguments, 1)?;
+                self.object.texture_vertices.push(Vector2::new(vt_coordinates[0],
+                                                               *vt_coordinates.get(1).unwrap_or(&0.0)));
+            }
+            "vn" => {
+                let vn_coordinates = parse_floats(arguments, 3)?;
+                self.object.normal_vertices.push(Vector3::new(vn_coordinates[0],
+                                                              vn_coordinates[1],
+                                                              vn_coordinates[2]));
+            }
+            "o" | "g" => {
+                self.close_group();
+                self.grouped = true;
+
+                self.group_name = if arguments.is_empty() { String::from("default") }
+                                  else { arguments.join(" ") };
+                self.group_start = self.object.geometric_faces.len();
+            }
+            "s" => {
+                self.smoothing_group = match arguments.first() {
+                    Some(&"off") => 0,
+                    Some(group) => group.parse().map_err(|_| format!("invalid smoothing group '{}'", group))?,
+                    None => return Err(LineError::from("missing smoothing group"))
+                };
+            }
+            "l" => {
+                if arguments.len() < 2 {
+                    return Err(LineError::from("line with fewer than two vertices"));
+                }
+
+                let line = arguments.iter()
+                                    .map(|n| parse_index(n.split('/').next().unwrap(),
+                                                         self.object.geometric_vertices.len()))
+                                    .collect::<Result<Vec<i32>, LineError>>()?;
+                self.object.lines.push(line);
+            }
+            "p" => {
+                if arguments.is_empty() {
+                    return Err(LineError::from("point without vertices"));
+                }
+
+                let points = arguments.iter()
+                                      .map(|n| parse_index(n, self.object.geometric_vertices.len()))
+                                      .collect::<Result<Vec<i32>, LineError>>()?;
+                self.object.points.extend(points);
+            }
+            "mtllib" => {
+                if arguments.is_empty() {
+                    return Err(LineError::from("missing material library"));
+                }
+
+                self.object.material_libraries.extend(arguments.iter().map(|name| name.to_string()));
+            }
+            "f" => self.parse_face(arguments)?,
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Parse the v/vt/vn triplets of a face into triangles, where the texture and normal indices can be left out
+    fn parse_face(&mut self, arguments: &[&str]) -> Result<(), LineError> {
+        if arguments.len() < 3 {
+            return Err(LineError::from("face with fewer than three vertices"));
+        }
+
+        let mut vertices: Vec<Vector3<i32>> = Vec::new();
+
+        for argument in arguments {
+            let indices: Vec<&str> = argument.split('/').collect();
+
+            if indices.len() > 3 {
+                return Err(LineError::Malformed(format!("face vertex '{}' is not in the form v, v/vt, v//vn, \
+                                                         or v/vt/vn", argument)));
+            }
+
+            // indices that are left out are -1 until the whole file is read
+            let optional = |position: usize, count: usize| match indices.get(position) {
+                Some(index) if !index.is_empty() => parse_index(index, count),
+                _ => Ok(-1)
+            };
+
+            vertices.push(Vector3::new(parse_index(indices[0], self.object.geometric_vertices.len())?,
+                                       optional(1, self.object.texture_vertices.len())?,
+                                       optional(2, self.object.normal_vertices.len())?));
+        }
+
+        self.missing_texture |= vertices.iter().any(|vertex| vertex[1] < 0);
+        self.missing_normals |= vertices.iter().any(|vertex| vertex[2] < 0);
+
+        let positions: Vec<Vector3<f64>> = vertices.iter()
+                                                   .map(|vertex| self.object.geometric_vertices[vertex[0] as usize])
+                                                   .collect();
+
+        for [a, b, c] in triangulate(&positions) {
+            self.push_triangle(&vertices[a], &vertices[b], &vertices[c]);
+        }
+
+        Ok(())
+    }
+
+    /// Add a triangle from three vertices given as geometric, texture, and normal indices
+    fn push_triangle(&mut self, a: &Vector3<i32>, b: &Vector3<i32>, c: &Vector3<i32>) {
+        self.object.geometric_faces.push(Vector3::new(a[0], b[0], c[0]));
+        self.object.texture_faces.push(Vector3::new(a[1], b[1], c[1]));
+        self.object.normal_faces.push(Vector3::new(a[2], b[2], c[2]));
+        self.object.smoothing_groups.push(self.smoothing_group);
+    }
+
+    /// Record the faces parsed since the last 'o' or 'g' line as a group
+    fn close_group(&mut self) {
+        let face_count = self.object.geometric_faces.len();
+
+        if face_count > self.group_start {
+            self.object.groups.push(Group { name: self.group_name.clone(),
+                                            faces: self.group_start..face_count });
+        }
+
+        self.group_start = face_count;
+    }
+}
+
+
+/// Parse the given reader into an Object, decompressing it first if it is gzip, zlib, or deflate compressed
+fn parse_decompressed<R: BufRead>(mut reader: R) -> io::Result<Object> {
+    let start = reader.fill_buf()?;
+
+    if start.starts_with(&GZIP_MAGIC) {
+        ObjectParser::new().parse(BufReader::new(GzDecoder::new(reader)))
+    } else if is_zlib_header(start) {
+        ObjectParser::new().parse(BufReader::new(ZlibDecoder::new(reader)))
+    } else if is_binary(start) {
+        ObjectParser::new().parse(BufReader::new(DeflateDecoder::new(reader)))
+    } else {
+        ObjectParser::new().parse(reader)
+    }
+}
+
+
+/// Whether the given bytes start with a zlib header using deflate, whose two bytes are a multiple of 31
+fn is_zlib_header(start: &[u8]) -> bool {
+    match start {
+        [method, flags, ..] => {
+            method & 0x0f == 8 && method >> 4 <= 7 && (u16::from(*method) << 8 | u16::from(*flags)) % 31 == 0
+        }
+        _ => false
+    }
+}
+
+
+/// Whether the start of the given bytes holds control characters that text does not
+fn is_binary(start: &[u8]) -> bool {
+    start.iter().take(SNIFF_LENGTH).any(|&byte| byte < 0x20 && !b"\t\n\x0c\r".contains(&byte))
+}
+
+
+/// Why a line of a wavefront file could not be parsed
+enum LineError {
+    Malformed(String),
+    OutOfRange(i64)
+}
+
+
+impl From<String> for LineError {
+    fn from(message: String) -> LineError {
+        LineError::Malformed(message)
+    }
+}
+
+
+impl From<&str> for LineError {
+    fn from(message: &str) -> LineError {
+        LineError::Malformed(message.to_string())
+    }
+}
+
+
+impl fmt::Display for LineError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LineError::Malformed(ref message) => write!(formatter, "{}", message),
+            LineError::OutOfRange(index) => write!(formatter, "index {} is out of range", index)
+        }
+    }
+}
+
+
+/// Parse at least the given number of floating point values from the given tokens
+fn parse_floats(tokens: &[&str], minimum: usize) -> Result<Vec<f64>, String> {
+    if tokens.len() < minimum {
+        return Err(format!("expected {} values but found {}", minimum, tokens.len()));
+    }
+
+    tokens.iter()
+          .map(|n| n.parse::<f64>()
+                    .ok()
+                    .filter(|value| value.is_finite())
+                    .ok_or_else(|| format!("invalid number '{}'", n)))
+          .collect()
+}
+
+
+/// Split a polygon into triangles by clipping its ears, returning the indices of the corners of each
+///
+/// The polygon is projected onto the plane its Newell normal is closest to,
+/// and the first corner that forms a triangle turning the way the polygon
+/// winds without any other corner inside it is clipped until a triangle is
+/// left. Convex polygons are split into a fan around their first corner.
+/// Polygons without an ear to clip, such as degenerate or self intersecting
+/// ones, have what is left of them split into a fan instead.
+///
+fn triangulate(points: &[Vector3<f64>]) -> Vec<[usize; 3]> {
+    let normal = (0..points.len()).fold(Vector3::zeros(), |normal: Vector3<f64>, i| {
+        normal + points[i].cross(&points[(i + 1) % points.len()])
+    });
+    let axis = normal.iamax();
+    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+
+    let cross = |a: usize, b: usize, c: usize| {
+        (points[b][u] - points[a][u]) * (points[c][v] - points[a][v]) -
+            (points[b][v] - points[a][v]) * (points[c][u] - points[a][u])
+    };
+    let winding = normal[axis].signum();
+
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let ear = (1..count + 1).map(|i| i % count).find(|&i| {
+            let (a, b, c) = (remaining[(i + count - 1) % count], remaining[i], remaining[(i + 1) % count]);
+
+            cross(a, b, c) * winding > 0.0 && remaining.iter().all(|&p| {
+                p == a || p == b || p == c ||
+                    !(cross(a, b, p) * winding >= 0.0 && cross(b, c, p) * winding >= 0.0 &&
+                      cross(c, a, p) * winding >= 0.0)
+            })
+        });
+
+        match ear {
+            Some(i) => {
+                triangles.push([remaining[(i + count - 1) % count], remaining[i], remaining[(i + 1) % count]]);
+                remaining.remove(i);
+            },
+            None => break
+        }
+    }
+
+    triangles.extend((1..remaining.len() - 1).map(|i| [remaining[0], remaining[i], remaining[i + 1]]));
+    triangles
+}
+
+
+/// Parse a one based or negative relative index into a zero based index
+///
+/// Negative indices count backwards from the most recently defined element,
+/// so -1 refers to the last of the count elements defined so far.
+///
+fn parse_index(token: &str, count: usize) -> Result<i32, LineError> {
+    let index: i64 = token.parse().map_err(|_| LineError::Malformed(format!("invalid index '{}'", token)))?;
+    let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(LineError::OutOfRange(index));
+    }
+
+    Ok(resolved as i32)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use flate2::Compression;
+    use flate2::write::{DeflateEncoder, GzEncoder, ZlibEncoder};
+    use render;
+    use super::*;
+
+    fn write_object(name: &str, contents: &str) -> String {
+        let mut dir = env::temp_dir();
+        dir.push(name);
+
+        File::create(&dir).unwrap().write_all(contents.as_bytes()).unwrap();
+
+        dir.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_convert_z_up() {
+        let convention = AxisConvention::new(UpAxis::Z, Handedness::Right);
+        let point = convention.convert(&Vector3::new(1.0, 2.0, 3.0));
+
+        assert_eq!(point, Vector3::new(1.0, 3.0, -2.0));
+        assert!(!convention.is_mirrored());
+    }
+
+    #[test]
+    fn test_convert_left_handed() {
+        let y_up = AxisConvention::new(UpAxis::Y, Handedness::Left);
+        let z_up = AxisConvention::new(UpAxis::Z, Handedness::Left);
+
+        assert_eq!(y_up.convert(&Vector3::new(1.0, 2.0, 3.0)), Vector3::new(1.0, 2.0, -3.0));
+        assert_eq!(z_up.convert(&Vector3::new(1.0, 2.0, 3.0)), Vector3::new(1.0, 3.0, 2.0));
+        assert!(y_up.is_mirrored() && z_up.is_mirrored());
+    }
+
+    #[test]
+    fn test_convert_axes_reverses_winding() {
+        let mut object = Object { geometric_vertices: vec![Vector3::new(0.0, 0.0, 1.0)],
+                                  geometric_faces: vec![Vector3::new(0, 1, 2)],
+                                  texture_vertices: Vec::new(),
+                                  texture_faces: vec![Vector3::new(3, 4, 5)],
+                                  normal_vertices: vec![Vector3::new(0.0, 0.0, 1.0)],
+                                  normal_faces: vec![Vector3::new(6, 7, 8)],
+                                  ..Default::default() };
+
+        object.convert_axes(&AxisConvention::new(UpAxis::Y, Handedness::Left));
+
+        assert_eq!(object.geometric_vertices[0], Vector3::new(0.0, 0.0, -1.0));
+        assert_eq!(object.normal_vertices[0], Vector3::new(0.0, 0.0, -1.0));
+        assert_eq!(object.geometric_faces[0], Vector3::new(0, 2, 1));
+        assert_eq!(object.texture_faces[0], Vector3::new(3, 5, 4));
+        assert_eq!(object.normal_faces[0], Vector3::new(6, 8, 7));
+    }
+
+    /// Two faces meeting at a right angle along the edge between vertices 0 and 1
+    fn folded_quad(smoothing_groups: Vec<u32>) -> Object {
+        Object { geometric_vertices: vec![Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0),
+                                          Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 0.0, 1.0)],
+                 geometric_faces: vec![Vector3::new(0, 1, 2), Vector3::new(1, 0, 3)],
+                 texture_vertices: Vec::new(),
+                 texture_faces: vec![Vector3::zeros(); 2],
+                 smoothing_groups,
+                 ..Default::default() }
+    }
+
+    #[test]
+    fn test_generate_normals_smoothing_group() {
+        let mut object = folded_quad(vec![1, 1]);
+        object.generate_normals();
+
+        let shared = object.normal_vertices[object.normal_faces[0][0] as usize];
+
+        assert_eq!(object.normal_faces[0][0], object.normal_faces[1][1]);
+        assert!((shared - Vector3::new(0.0, 1.0, 1.0).normalize()).norm() < 1e-9);
+        assert_eq!(object.normal_vertices.len(), 4);
+    }
+
+    #[test]
+    fn test_generate_normals_hard_edges() {
+        let mut object = folded_quad(vec![1, 2]);
+        object.generate_normals();
+
+        assert_eq!(object.normal_vertices[object.normal_faces[0][0] as usize],
+                   Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(object.normal_vertices[object.normal_faces[1][1] as usize],
+                   Vector3::new(0.0, 1.0, 0.0));
+
+        let mut object = folded_quad(vec![0, 0]);
+        object.generate_normals();
+
+        assert_eq!(object.normal_vertices.len(), 2);
+        assert_eq!(object.normal_faces[1], Vector3::new(1, 1, 1));
+    }
+
+    #[test]
+    fn test_parse_normals() {
+        let options = LoadOptions::default();
+        let read = Object::from_bytes(b"v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\nvn 1 0 0\n\
+                                        f 1/1/2 2/1/1 3/1/-1\n", &options).unwrap();
+
+        assert_eq!(read.normal_vertices, vec![Vector3::new(0.0, 0.0, 1.0), Vector3::new(1.0, 0.0, 0.0)]);
+        assert_eq!(read.normal_faces, vec![Vector3::new(1, 0, 1)]);
+
+        // a face without normal indices has the normals of every face generated from the winding
+        let generated = Object::from_bytes(b"v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\nvt 0 0\nvn 1 0 0\n\
+                                             f 1/1/1 2/1/1 3/1/1\nf 1/1 4/1 2/1/\n", &options).unwrap();
+
+        assert!(generated.warnings.is_empty());
+        assert_eq!(generated.normal_faces.len(), 2);
+        assert_eq!(generated.normal_vertices[generated.normal_faces[0][0] as usize], Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(generated.normal_vertices[generated.normal_faces[1][2] as usize], Vector3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_face_forms() {
+        let contents = b"v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 1\nvn 0 0 1\n\
+                         f 1 2 3\nf 1//1 2//1 3//1\nf 1/1 2/2 3/2\nf 1/ 2/2/1 3/-1/-1\nf 1/2/3/4 2 3\n";
+        let object = Object::from_bytes(contents, &LoadOptions::default()).unwrap();
+
+        assert_eq!(object.warnings.len(), 1);
+        assert_eq!(object.geometric_faces, vec![Vector3::new(0, 1, 2); 4]);
+
+        // the faces without texture coordinates sample the middle of the texture
+        assert_eq!(object.texture_vertices[2], Vector2::new(0.5, 0.5));
+        assert_eq!(object.texture_faces, vec![Vector3::new(2, 2, 2), Vector3::new(2, 2, 2), Vector3::new(0, 1, 1),
+                                              Vector3::new(2, 1, 1)]);
+
+        // some faces have no normals, so every face is shaded with generated ones
+        assert_eq!(object.normal_faces.len(), 4);
+        assert!(object.normal_faces.iter().all(|face| object.normal_vertices[face[0] as usize] == Vector3::z()));
+
+        let texture = image::RgbImage::from_pixel(2, 2, image::Rgb([255, 255, 255]));
+        let mut buffer = image::RgbImage::new(32, 32);
+        render::draw_object(&object, &mut buffer, &texture, 255, &Vector3::z(), &Vector3::new(0.0, 0.0, 3.0),
+                            &Vector3::zeros(), &Vector3::y());
+        assert!(buffer.pixels().any(|pixel| pixel[0] > 0));
+    }
+
+    #[test]
+    fn test_groups() {
+        let filename = write_object("test_groups.obj", "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\n\
+                                                         f 1/1/1 2/1/1 3/1/1\n\
+                                                         o body\ng paint\n\
+                                                         f 1/1/1 2/1/1 3/1/1\nf 1/1/1 3/1/1 2/1/1\n\
+                                                         g glass\nf 2/1/1 3/1/1 1/1/1\n");
+        let mut object = Object::new(&filename).unwrap();
+
+        assert_eq!(object.groups, vec![Group { name: "default".to_string(), faces: 0..1 },
+                                       Group { name: "paint".to_string(), faces: 1..3 },
+                                       Group { name: "glass".to_string(), faces: 3..4 }]);
+
+        let glass = object.sub_mesh("glass").unwrap();
+        assert_eq!(glass.geometric_faces, vec![Vector3::new(1, 2, 0)]);
+        assert!(object.sub_mesh("body").is_none());
+
+        object.retain_groups(|name| name != "paint");
+        assert_eq!(object.geometric_faces.len(), 2);
+        assert_eq!(object.group("glass").unwrap().faces, 1..2);
+
+        // a single group is closed at the end of the file
+        let single = Object::from_bytes(b"v 0 0 0\nv 1 0 0\nv 0 1 0\ng body\nf 1 2 3\n", &LoadOptions::default())
+            .unwrap();
+        assert_eq!(single.groups, vec![Group { name: "body".to_string(), faces: 0..1 }]);
+
+        // the faces of a file without groups are the default group
+        let mut ungrouped = Object::from_bytes(b"v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n", &LoadOptions::default())
+            .unwrap();
+        assert!(ungrouped.groups.is_empty());
+        assert_eq!(ungrouped.sub_mesh("default").unwrap().geometric_faces.len(), 1);
+
+        ungrouped.retain_groups(|name| name != "wheel");
+        assert_eq!(ungrouped.geometric_faces.len(), 1);
+        ungrouped.retain_groups(|name| name != "default");
+        assert!(ungrouped.geometric_faces.is_empty());
+    }
+
+    #[test]
+    fn test_load_errors() {
+        match Object::new("/nonexistent/morpheus.obj") {
+            Err(MorpheusError::Io { path, source }) => {
+                assert_eq!(path, "/nonexistent/morpheus.obj");
+                assert_eq!(source.kind(), io::ErrorKind::NotFound);
+            },
+            _ => panic!("expected an io error")
+        }
+
+        // a gzip header followed by data that is not compressed
+        assert!(Object::from_bytes(&[0x1f, 0x8b, 0x08, 0x00, 0xff, 0xff], &LoadOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_lines_and_points() {
+        let filename = write_object("test_lines_and_points.obj", "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\n\
+                                                                  l 1 2 3\nl 3/1 1/1\np 2 3\np 1\n");
+        let object = Object::new(&filename).unwrap();
+
+        assert_eq!(object.lines, vec![vec![0, 1, 2], vec![2, 0]]);
+        assert_eq!(object.points, vec![1, 2, 0]);
+        assert!(object.geometric_faces.is_empty());
+    }
+
+    #[test]
+    fn test_polygons() {
+        // a convex pentagon and a concave arrowhead starting at its tip, with its notch at its last corner
+        let contents = b"v 0 0 0\nv 2 0 0\nv 3 1 0\nv 1 2 0\nv -1 1 0\nv 0 0 1\nv 2 -1 1\nv 0 2 1\nv -2 -1 1\n\
+                         vt 0 0\nvt 1 1\nvn 0 0 1\n\
+                         f 1/1/1 2/1/1 3/2/1 4/1/1 5/1/1\nf 7/1/1 8/1/1 9/2/1 6/1/1\n";
+        let object = Object::from_bytes(contents, &LoadOptions::default()).unwrap();
+
+        assert!(object.warnings.is_empty());
+        assert_eq!(object.geometric_faces[..3], [Vector3::new(0, 1, 2), Vector3::new(0, 2, 3), Vector3::new(0, 3, 4)]);
+        assert_eq!(object.texture_faces[0], Vector3::new(0, 0, 1));
+
+        // a fan around the tip would cover the notch, so the ears are clipped around it
+        let area = |face: &Vector3<i32>| {
+            let [a, b, c] = [0, 1, 2].map(|i| object.geometric_vertices[face[i] as usize]);
+            (b - a).cross(&(c - a)).z / 2.0
+        };
+        assert_eq!(object.geometric_faces.len(), 5);
+        assert!(object.geometric_faces[3..].iter().all(|face| area(face) > 0.0));
+        assert_eq!(area(&object.geometric_faces[3]) + area(&object.geometric_faces[4]), 4.0);
+    }
+
+    #[test]
+    fn test_material_libraries() {
+        let object = Object::from_bytes(b"mtllib head.mtl ../shared/eyes.mtl\nv 0 0 0\nmtllib\n",
+                                        &LoadOptions::default()).unwrap();
+
+        assert_eq!(object.material_libraries, vec!["head.mtl", "../shared/eyes.mtl"]);
+        assert_eq!(object.warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_whitespace_and_comments() {
+        let contents = "# comment\r\nv\t1e-5  2.0 3 # trailing\r\nv -1 0 0\nv 0 -1 0 1.0\n\n\
+                        vt 0.5\r\nvn 0 0 1\n  f 1/1/1\t2/1/1 -1/-1/-1\r\n";
+        let object = ObjectParser::new().parse(contents.as_bytes()).unwrap();
+
+        assert_eq!(object.geometric_vertices[0], Vector3::new(1e-5, 2.0, 3.0));
+        assert_eq!(object.texture_vertices, vec![Vector2::new(0.5, 0.0)]);
+        assert_eq!(object.geometric_faces, vec![Vector3::new(0, 1, 2)]);
+        assert_eq!(object.normal_faces, vec![Vector3::new(0, 0, 0)]);
+        assert!(object.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_malformed_lines() {
+        let contents = "v 0 0 0\nv 1 0\nv 1 0 nan\nv 0 1 0\nv 1 1 0\nvt 0 0\nvn 0 0 1\n\
+                        f 1/1/1 2/1/1\nf 1/1/1 2/1/1 9/1/1\nf 1/1/1 2/1/1 3/x/1\ns sometimes\n\
+                        f 1/1/1 2/1/1 3/1/1\n";
+        let object = ObjectParser::new().parse(contents.as_bytes()).unwrap();
+
+        assert_eq!(object.geometric_vertices.len(), 3);
+        assert_eq!(object.geometric_faces, vec![Vector3::new(0, 1, 2)]);
+        assert_eq!(object.warnings.iter().map(|warning| warning.line).collect::<Vec<usize>>(),
+                   vec![2, 3, 8, 9, 10, 11]);
+        assert_eq!(object.warnings[3].index, Some(9));
+    }
+
+    #[test]
+    fn test_strict_loading() {
+        let strict = LoadOptions { strict: true, ..Default::default() };
+        let contents = b"v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\nf 1/1/1 2/1/1 7/1/1\n";
+
+        assert!(Object::from_bytes(contents, &LoadOptions::default()).is_ok());
+        assert_eq!(Object::from_bytes(contents, &strict).unwrap_err(),
+                   MorpheusError::InvalidIndex { path: String::from("<data>"), line: 6, index: 7 });
+
+        let filename = write_object("test_strict_loading.obj", "v 0 0 0\nv 1 x 0\n");
+        match Object::with_options(&filename, &strict) {
+            Err(MorpheusError::Parse { path, line, .. }) => assert_eq!((path, line), (filename, 2)),
+            _ => panic!("expected a parse error")
+        }
+    }
+
+    #[test]
+    fn test_fuzz_parser() {
+        let keywords = ["v", "vt", "vn", "f", "l", "p", "s", "g", "o", "#", "", "usemtl"];
+        let values = ["1", "-1", "0", "2/1/1", "1//1", "-1/-1/-1", "1e-5", "nan", "inf",
+                      "/", "//", "3/", "\t", "\r", "off", "99999999999", "é", "-0"];
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut random = |limit: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize % limit
+        };
+
+        for _ in 0..200 {
+            let mut contents: Vec<u8> = Vec::new();
+
+            for _ in 0..random(40) {
+                contents.extend_from_slice(keywords[random(keywords.len())].as_bytes());
+
+                for _ in 0..random(6) {
+                    contents.push(b' ');
+                    contents.extend_from_slice(values[random(values.len())].as_bytes());
+                }
+
+                if random(10) == 0 {
+                    contents.push(random(256) as u8);
+                }
+
+                contents.push(b'\n');
+            }
+
+            let object = ObjectParser::new().parse(&contents[..]).unwrap();
+            let faces = object.geometric_faces.iter()
+                              .chain(object.texture_faces.iter())
+                              .chain(object.normal_faces.iter());
+
+            assert!(faces.flat_map(|face| face.iter().cloned().collect::<Vec<i32>>())
+                         .all(|index| index >= 0));
+            assert!(object.geometric_faces.iter()
+                          .all(|face| face.iter().all(|&i| (i as usize) < object.geometric_vertices.len())));
+        }
+    }
+
+    #[test]
+    fn test_compressed_and_memory_mapped() {
+        let contents = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\nf 1/1/1 2/1/1 3/1/1\n";
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(contents.as_bytes()).unwrap();
+
+        let mut dir = env::temp_dir();
+        dir.push("test_compressed.obj.gz");
+        File::create(&dir).unwrap().write_all(&encoder.finish().unwrap()).unwrap();
+
+        let filename = write_object("test_memory_mapped.obj", contents);
+
+        for filename in [dir.to_str().unwrap(), &filename].iter() {
+            for &memory_map in [false, true].iter() {
+                let options = LoadOptions { memory_map, ..Default::default() };
+                let object = Object::with_options(filename, &options).unwrap();
+
+                assert_eq!(object.geometric_faces, vec![Vector3::new(0, 1, 2)]);
+                assert!(object.warnings.is_empty());
+            }
+        }
+
+        let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+        zlib.write_all(contents.as_bytes()).unwrap();
+        let mut deflate = DeflateEncoder::new(Vec::new(), Compression::default());
+        deflate.write_all(contents.as_bytes()).unwrap();
+
+        for data in [zlib.finish().unwrap(), deflate.finish().unwrap()].iter() {
+            let object = Object::from_bytes(data, &LoadOptions::default()).unwrap();
+            assert_eq!(object.geometric_faces, vec![Vector3::new(0, 1, 2)]);
+        }
+
+        // text starting with a letter that happens to look like a zlib method is not taken for one
+        assert!(!is_zlib_header(b"xo"));
+        assert!(!is_binary(contents.as_bytes()));
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let options = LoadOptions { units: Units::Centimeters, ..Default::default() };
+        let object = Object::from_bytes(b"v 100 0 0\nv 0 100 0\nv 0 0 100\nf 1/1/1 2/1/1 3/1/1\n",
+                                        &options).unwrap();
+
+        assert_eq!(object.geometric_vertices[0], Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(object.warnings.len(), 1);
+        assert!(object.geometric_faces.is_empty());
+    }
+
+    #[test]
+    fn test_flip_winding_and_normals() {
+        let data = b"v 0 0 0\nv 0 1 0\nv 1 0 0\nvt 0 0\nvn 0 0 -1\nf 1/1/1 2/1/1 3/1/1\n";
+        let options = LoadOptions { flip_winding: true, flip_normals: true, ..Default::default() };
+
+        // the clockwise face is wound counterclockwise and its inward normal points out again
+        let object = Object::from_bytes(data, &options).unwrap();
+        assert_eq!(object.geometric_faces[0], Vector3::new(0, 2, 1));
+        assert_eq!(object.normal_vertices[0], Vector3::z());
+
+        // the winding and the normals are flipped independently
+        let object = Object::from_bytes(data, &LoadOptions { flip_winding: true, ..Default::default() }).unwrap();
+        assert_eq!(object.geometric_faces[0], Vector3::new(0, 2, 1));
+        assert_eq!(object.normal_vertices[0], -Vector3::z());
+    }
+
+    #[test]
+    fn test_load_tangents() {
+        let data = b"v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 0 1\nvn 0 0 1\nf 1/1/1 2/2/1 3/3/1\n";
+        let mut object = Object::from_bytes(data, &LoadOptions { tangents: true, ..Default::default() }).unwrap();
+
+        assert_eq!(object.tangent_vertices, vec![Vector4::new(1.0, 0.0, 0.0, 1.0); 3]);
+        assert_eq!(object.tangent_faces, vec![Vector3::new(0, 1, 2)]);
+
+        // mirroring the object mirrors its tangents and the side their bitangents are on
+        object.transform(&Matrix4::new_nonuniform_scaling(&Vector3::new(-2.0, 1.0, 1.0)));
+        assert_eq!(object.tangent_vertices, vec![Vector4::new(-1.0, 0.0, 0.0, -1.0); 3]);
+        assert_eq!(object.tangent_faces, vec![Vector3::new(0, 2, 1)]);
+
+        assert!(Object::from_bytes(data, &LoadOptions::default()).unwrap().tangent_vertices.is_empty());
+    }
+
+    #[test]
+    fn test_weld() {
+        // a folded quad exported as two triangles on vertices of their own
+        let data = b"v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 0 0.0000001\nv 0 1 0\nv 1 1 1\n\
+                     vt 0 0\nvt 1 0\nvt 0 1\nvt 0 0\nvt 1 0\nvt 0 1\ns 1\n\
+                     f 1/1 2/2 3/3\nf 4/4 6/5 5/6\nl 1 4\n";
+
+        let exploded = Object::from_bytes(data, &LoadOptions::default()).unwrap();
+        assert_ne!(exploded.normal_faces[0][1], exploded.normal_faces[1][0]);
+
+        let welded = Object::from_bytes(data, &LoadOptions { weld: Some(1e-6), ..Default::default() }).unwrap();
+
+        assert_eq!((welded.geometric_vertices.len(), welded.texture_vertices.len()), (4, 3));
+        assert_eq!(welded.geometric_faces, vec![Vector3::new(0, 1, 2), Vector3::new(1, 3, 2)]);
+        assert_eq!(welded.texture_faces, vec![Vector3::new(0, 1, 2), Vector3::new(0, 1, 2)]);
+        assert_eq!(welded.lines, vec![vec![0, 1]]);
+
+        // the shared edge is smooth once its vertices are shared
+        assert_eq!(welded.normal_faces[0][1], welded.normal_faces[1][0]);
+    }
+
+    #[test]
+    fn test_optimize_vertex_cache() {
+        let mut contents = String::from("vt 0 0\nvn 0 0 1\n");
+        for row in 0..=60 {
+            contents += &format!("v 0 {} 0\nv 1 {} 0\nv 2 {} 0\n", row, row, row);
+        }
+
+        // two columns of quads, drawn as a group each with the faces of both columns interleaved
+        for (group, rows) in [("bottom", 0..30), ("top", 30..60)].iter() {
+            contents += &format!("g {}\n", group);
+            for column in 0..2 {
+                for row in rows.clone() {
+                    let (a, b) = (row * 3 + column + 1, row * 3 + column + 4);
+                    contents += &format!("f {}/1/1 {}/1/1 {}/1/1 {}/1/1\n", a, a + 1, b + 1, b);
+                }
+            }
+        }
+
+        let mut object = Object::from_bytes(contents.as_bytes(), &LoadOptions::default()).unwrap();
+        let (faces, groups) = (object.geometric_faces.clone(), object.groups.clone());
+        let unordered: Vec<usize> = (0..faces.len()).collect();
+
+        let order = object.optimize_vertex_cache();
+
+        assert_eq!(object.groups, groups);
+        assert!(order[..120].iter().all(|&face| face < 120));
+        assert_eq!(order.iter().map(|&face| faces[face]).collect::<Vec<Vector3<i32>>>(), object.geometric_faces);
+        assert!(reorder::cache_misses(&faces, &order) < reorder::cache_misses(&faces, &unordered));
+    }
+
+    #[test]
+    fn test_units() {
+        assert_eq!(Units::Meters.to_meters(), 1.0);
+        assert_eq!(Units::Centimeters.to_meters(), 0.01);
+        assert!((Units::Inches.to_meters() * 12.0 - Units::Feet.to_meters()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_transform() {
+        let mut object = Object::from_bytes(b"v 1 0 0\nv 0 1 0\nv 0 0 0\nvt 0 0\nvn 1 1 0\nf 1/1/1 2/1/1 3/1/1\n",
+                                            &LoadOptions::default()).unwrap();
+
+        object.transform(&Matrix4::new_translation(&Vector3::new(0.0, 0.0, 2.0)));
+        assert_eq!(object.geometric_vertices[0], Vector3::new(1.0, 0.0, 2.0));
+        assert_eq!(object.normal_vertices[0], Vector3::new(1.0, 1.0, 0.0));
+
+        // stretching along x tilts the normal towards y and mirroring reverses the winding
+        object.transform(&Matrix4::new_nonuniform_scaling(&Vector3::new(-2.0, 1.0, 1.0)));
+        assert_eq!(object.geometric_vertices[0], Vector3::new(-2.0, 0.0, 2.0));
+        assert_eq!(object.normal_vertices[0], Vector3::new(-0.5, 1.0, 0.0));
+        assert_eq!(object.geometric_faces[0], Vector3::new(0, 2, 1));
+    }
+
+    #[test]
+    fn test_normalize() {
+        let mut object = Object::from_bytes(b"v 2 1 0\nv 6 1 0\nv 4 3 1\nvt 0 0\nvn 0 0 1\nf 1/1/1 2/1/1 3/1/1\n",
+                                            &LoadOptions::default()).unwrap();
+
+        object.normalize();
+        assert_eq!(object.geometric_vertices, vec![Vector3::new(-1.0, -0.5, -0.25), Vector3::new(1.0, -0.5, -0.25),
+                                                   Vector3::new(0.0, 0.5, 0.25)]);
 
-        Object { geometric_vertices: geometric_vertices, geometric_faces: geometric_faces,
-                 texture_vertices: texture_vertices, texture_faces: texture_faces,
-                 normal_vertices: normal_vertices, normal_faces: normal_faces }
+        let mut point = Object::from_bytes(b"v 3 3 3\np 1\n", &LoadOptions::default()).unwrap();
+        point.normalize();
+        assert_eq!(point.geometric_vertices, vec![Vector3::zeros()]);
     }
 }
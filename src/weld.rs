@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use nalgebra::Vector3;
+
+
+/// Merge the points that are within the given distance of an earlier point, returning the points left and where each went
+///
+/// Every point is merged into the first point before it that is within the
+/// distance, so the points left keep their order and the index of each
+/// given point into them is returned along with them. The points are
+/// bucketed in a grid with cells as wide as the distance so only the
+/// neighbouring cells are searched, and a distance of 0 only merges points
+/// that are exactly equal.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate rastermind;
+/// # use rastermind::{wavefront, weld};
+/// # fn main() -> Result<(), rastermind::Error> {
+/// # let mut coordinates = wavefront::Object::from_bytes(b"v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n",
+/// #                                                   &Default::default())?;
+/// let (welded, remap) = weld::weld(&coordinates.geometric_vertices, 1e-6);
+///
+/// for face in coordinates.geometric_faces.iter_mut() {
+///     *face = face.map(|index| remap[index as usize]);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+pub fn weld(points: &[Vector3<f64>], epsilon: f64) -> (Vec<Vector3<f64>>, Vec<i32>) {
+    let size = if epsilon > 0.0 { epsilon } else { 1.0 };
+    let cell = |point: &Vector3<f64>| point.map(|value| (value / size).floor() as i64);
+
+    let mut welded: Vec<Vector3<f64>> = Vec::new();
+    let mut grid: HashMap<Vector3<i64>, Vec<i32>> = HashMap::new();
+
+    let remap = points.iter().map(|point| {
+        let center = cell(point);
+        let mut found = None;
+
+        'search: for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let neighbours = match grid.get(&(center + Vector3::new(x, y, z))) {
+                        Some(neighbours) => neighbours,
+                        None => continue
+                    };
+
+                    if let Some(&index) = neighbours.iter().find(|&&index| {
+                        (welded[index as usize] - point).norm() <= epsilon
+                    }) {
+                        found = Some(index);
+                        break 'search;
+                    }
+                }
+            }
+        }
+
+        found.unwrap_or_else(|| {
+            welded.push(*point);
+            grid.entry(center).or_default().push(welded.len() as i32 - 1);
+            welded.len() as i32 - 1
+        })
+    }).collect();
+
+    (welded, remap)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weld() {
+        let points = [Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1e-7),
+                      Vector3::new(1.0 - 1e-7, 0.0, 0.0), Vector3::new(0.5, 0.0, 0.0)];
+
+        let (welded, remap) = weld(&points, 1e-6);
+        assert_eq!(welded, vec![points[0], points[1], points[4]]);
+        assert_eq!(remap, vec![0, 1, 0, 1, 2]);
+
+        // without a distance only equal points are merged
+        let (welded, remap) = weld(&[points[0], points[2], points[0]], 0.0);
+        assert_eq!(welded.len(), 2);
+        assert_eq!(remap, vec![0, 1, 0]);
+    }
+}
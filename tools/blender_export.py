@@ -0,0 +1,129 @@
+"""Export the open Blender scene for rastermind import
+
+Run from Blender with the directory to write the scene into:
+
+    blender model.blend --background --python tools/blender_export.py -- exported
+
+The directory receives scene.json and a meshes directory with one OBJ file
+per mesh, written in the coordinates of the mesh so that objects sharing a
+mesh share its file. Blender is z up while the renderer is y up, so every
+position, direction and transform is turned a quarter turn about x as it is
+written. Sun and spot lights are exported; other lights are skipped with a
+warning, as the renderer has nothing to draw them with.
+"""
+
+import json
+import math
+import os
+import sys
+
+import bpy
+import mathutils
+
+
+# turns the z up coordinates of Blender into the y up coordinates of the renderer
+Y_UP = mathutils.Matrix(((1, 0, 0, 0), (0, 0, 1, 0), (0, -1, 0, 0), (0, 0, 0, 1)))
+
+
+def vector(v):
+    return list((Y_UP.to_3x3() @ v)[:])
+
+
+def matrix(m):
+    converted = Y_UP @ m @ Y_UP.inverted()
+
+    return [value for row in converted for value in row]
+
+
+def texture_of(material):
+    if material is None or not material.use_nodes:
+        return None
+
+    for node in material.node_tree.nodes:
+        if node.type == 'TEX_IMAGE' and node.image is not None:
+            return bpy.path.abspath(node.image.filepath)
+
+    return None
+
+
+def export_mesh(obj, directory):
+    name = bpy.path.clean_name(obj.data.name)
+    path = os.path.join('meshes', name + '.obj')
+
+    # the object is exported where it is placed, so it is moved to the origin for the export
+    placed = obj.matrix_world.copy()
+    obj.matrix_world = mathutils.Matrix.Identity(4)
+
+    bpy.ops.object.select_all(action='DESELECT')
+    obj.select_set(True)
+    bpy.ops.wm.obj_export(filepath=os.path.join(directory, path), export_selected_objects=True,
+                          export_materials=False, export_triangulated_mesh=True, forward_axis='NEGATIVE_Z',
+                          up_axis='Y')
+
+    obj.matrix_world = placed
+
+    mesh = {'path': path}
+    texture = texture_of(obj.active_material)
+    if texture is not None:
+        mesh['texture'] = os.path.relpath(texture, directory)
+
+    return name, mesh
+
+
+def export_camera(obj):
+    eye = obj.matrix_world.to_translation()
+    forward = obj.matrix_world.to_3x3() @ mathutils.Vector((0, 0, -1))
+    up = obj.matrix_world.to_3x3() @ mathutils.Vector((0, 1, 0))
+
+    return {'name': obj.name, 'eye': vector(eye), 'target': vector(eye + forward), 'up': vector(up),
+            'fov': math.degrees(obj.data.angle_y)}
+
+
+def export_light(obj):
+    position = obj.matrix_world.to_translation()
+    direction = obj.matrix_world.to_3x3() @ mathutils.Vector((0, 0, -1))
+
+    if obj.data.type == 'SUN':
+        return {'type': 'directional', 'name': obj.name, 'direction': vector(-direction),
+                'intensity': obj.data.energy}
+
+    if obj.data.type == 'SPOT':
+        outer = obj.data.spot_size / 2
+        return {'type': 'spot', 'name': obj.name, 'position': vector(position), 'direction': vector(direction),
+                'inner_angle': outer * (1 - obj.data.spot_blend), 'outer_angle': outer,
+                'intensity': obj.data.energy / 1000}
+
+    print('skipping the {} light {}, which the renderer cannot draw'.format(obj.data.type.lower(), obj.name))
+    return None
+
+
+def export(directory):
+    os.makedirs(os.path.join(directory, 'meshes'), exist_ok=True)
+
+    scene = {'version': 1, 'meshes': {}, 'objects': [], 'cameras': [], 'lights': []}
+
+    for obj in bpy.context.scene.objects:
+        if obj.type == 'MESH':
+            name = bpy.path.clean_name(obj.data.name)
+            if name not in scene['meshes']:
+                name, scene['meshes'][name] = export_mesh(obj, directory)
+
+            scene['objects'].append({'name': obj.name, 'mesh': name,
+                                     'transform': {'matrix': matrix(obj.matrix_world)}})
+        elif obj.type == 'CAMERA':
+            scene['cameras'].append(export_camera(obj))
+        elif obj.type == 'LIGHT':
+            light = export_light(obj)
+            if light is not None:
+                scene['lights'].append(light)
+
+    if bpy.context.scene.camera is not None:
+        scene['active_camera'] = bpy.context.scene.camera.name
+
+    with open(os.path.join(directory, 'scene.json'), 'w') as file:
+        json.dump(scene, file, indent=4)
+
+
+if __name__ == '__main__':
+    arguments = sys.argv[sys.argv.index('--') + 1:] if '--' in sys.argv else []
+    export(arguments[0] if arguments else 'exported')